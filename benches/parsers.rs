@@ -0,0 +1,77 @@
+//! Benchmarks of parsers against large generated fixtures: JBODs with hundreds of disks and pools
+//! with hundreds of thousands of snapshots. Run with `cargo bench --bench parsers`.
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+
+use libzetta::parsers::{parse_zpools,
+                        zfs::{parse_datasets, parse_datasets_with_type}};
+
+/// `zpool status` of a pool with `vdevs` raidz2 vdevs, `width` disks each.
+fn jbod_status(vdevs: usize, width: usize) -> String {
+    let mut stdout = String::from(
+        "  pool: tank\n state: ONLINE\n  scan: scrub repaired 0B in 00:00:01 with 0 errors on \
+         Sun Jul 25 16:07:50 2021\nconfig:\n\n        NAME                              STATE     \
+         READ WRITE CKSUM\n        tank                              ONLINE       0     0     0\n",
+    );
+    for vdev in 0..vdevs {
+        stdout.push_str(&format!(
+            "          raidz2-{}                        ONLINE       0     0     0\n",
+            vdev
+        ));
+        for disk in 0..width {
+            stdout.push_str(&format!(
+                "            /dev/disk/by-id/wwn-0x5000c500a{:04}{:04}  ONLINE       0     0     \
+                 0\n",
+                vdev, disk
+            ));
+        }
+    }
+    stdout.push_str("\nerrors: No known data errors\n");
+    stdout
+}
+
+/// `zfs list -H -o type,name` of a pool with `count` snapshots spread over 100 datasets.
+fn snapshot_list(count: usize) -> String {
+    let mut stdout = String::new();
+    for idx in 0..count {
+        stdout.push_str(&format!(
+            "snapshot\ttank/vm/disk-{}@autosnap_{:08}_hourly\n",
+            idx % 100,
+            idx
+        ));
+    }
+    stdout
+}
+
+fn bench_status(c: &mut Criterion) {
+    let mut group = c.benchmark_group("zpool_status");
+    // 90 disk JBOD and something a lot bigger.
+    for &(vdevs, width) in &[(9, 10), (100, 10)] {
+        let stdout = jbod_status(vdevs, width);
+        group.throughput(Throughput::Bytes(stdout.len() as u64));
+        group.bench_with_input(
+            BenchmarkId::from_parameter(vdevs * width),
+            &stdout,
+            |b, stdout| b.iter(|| parse_zpools(black_box(stdout)).unwrap()),
+        );
+    }
+    group.finish();
+}
+
+fn bench_list(c: &mut Criterion) {
+    let mut group = c.benchmark_group("zfs_list");
+    for &count in &[1_000, 100_000] {
+        let with_type = snapshot_list(count);
+        let names: String = with_type.lines().map(|line| &line[9..]).collect::<Vec<_>>().join("\n");
+        group.throughput(Throughput::Elements(count as u64));
+        group.bench_with_input(BenchmarkId::new("names", count), &names, |b, stdout| {
+            b.iter(|| parse_datasets(black_box(stdout)))
+        });
+        group.bench_with_input(BenchmarkId::new("with_type", count), &with_type, |b, stdout| {
+            b.iter(|| parse_datasets_with_type(black_box(stdout)).unwrap())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_status, bench_list);
+criterion_main!(benches);
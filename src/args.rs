@@ -0,0 +1,135 @@
+//! `key=value` property arguments for `zpool(8)` and `zfs(8)`.
+//!
+//! Commands are spawned without a shell, so nothing here is quoted. What still can go wrong is
+//! the content: a name that starts with `-` is read as an option, `=` in a name moves the split,
+//! a line break in a value corrupts every `-H` output that includes the property afterwards, and
+//! NUL can't be passed to a process at all. Every property argument is built or checked here
+//! before it reaches a command line, and values of user properties set through `libzfs_core` go
+//! through [`validate_value`](fn.validate_value.html) too.
+//!
+//! ### Usage
+//! ```rust
+//! use libzetta::args::{property_arg, ArgError};
+//!
+//! assert_eq!("comment=rack 4", property_arg("comment", "rack 4").unwrap());
+//! assert!(matches!(property_arg("-f", "x"), Err(ArgError::InvalidKey(_))));
+//! assert!(matches!(property_arg("comment", "a\nb"), Err(ArgError::InvalidValue(..))));
+//! ```
+use std::ffi::{OsStr, OsString};
+
+use crate::{zfs::{validators::is_property_name_char, USER_PROPERTY_NAME_MAX_LENGTH,
+                   USER_PROPERTY_VALUE_MAX_LENGTH},
+            zpool::PropPair};
+
+quick_error! {
+    /// Property that can't be passed to `zpool` or `zfs`.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub enum ArgError {
+        /// Name is empty, too long, starts with `-` or has characters other than lowercase ASCII
+        /// letters, digits and `:._-@`.
+        InvalidKey(key: String) {
+            display("invalid property name {:?}", key)
+        }
+        /// Value can't be passed safely.
+        InvalidValue(key: String, reason: &'static str) {
+            display("invalid value of property {}: {}", key, reason)
+        }
+    }
+}
+
+/// Check property name. Same rules as for user property names, plus `@` of `feature@...`.
+pub fn validate_key(key: &str) -> Result<(), ArgError> {
+    let valid_char = |c: char| is_property_name_char(c) || c == '@';
+    if key.is_empty()
+        || key.len() >= USER_PROPERTY_NAME_MAX_LENGTH
+        || key.starts_with('-')
+        || !key.chars().all(valid_char)
+    {
+        return Err(ArgError::InvalidKey(key.to_string()));
+    }
+    Ok(())
+}
+
+/// Check property value.
+///
+/// * `key` - Name of the property, used in the error.
+/// * `value` - Value to check.
+pub fn validate_value(key: &str, value: &str) -> Result<(), ArgError> {
+    let reason = if value.contains('\0') {
+        "contains NUL"
+    } else if value.contains(|c| c == '\n' || c == '\r') {
+        "contains line break"
+    } else if value.len() >= USER_PROPERTY_VALUE_MAX_LENGTH {
+        "too long"
+    } else {
+        return Ok(());
+    };
+    Err(ArgError::InvalidValue(key.to_string(), reason))
+}
+
+/// Build `key=value` argument.
+pub fn property_arg(key: &str, value: &str) -> Result<OsString, ArgError> {
+    validate_key(key)?;
+    validate_value(key, value)?;
+    Ok(OsString::from(format!("{}={}", key, value)))
+}
+
+/// Build `key=value` argument out of [`PropPair`](../zpool/trait.PropPair.html).
+pub fn pair_arg<P: PropPair + ?Sized>(key: &str, value: &P) -> Result<OsString, ArgError> {
+    validate_key(key)?;
+    let pair = OsString::from(value.to_pair(key));
+    check_pair(&pair)?;
+    Ok(pair)
+}
+
+/// Check `key=value` argument that was built elsewhere.
+pub fn check_pair(pair: &OsStr) -> Result<(), ArgError> {
+    let pair = pair.to_str().ok_or_else(|| ArgError::InvalidKey(pair.to_string_lossy().into()))?;
+    let mut split = pair.splitn(2, '=');
+    let key = split.next().unwrap_or_default();
+    validate_key(key)?;
+    let value = split.next().ok_or_else(|| ArgError::InvalidValue(key.to_string(), "missing"))?;
+    validate_value(key, value)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn keys() {
+        for key in &["comment", "feature@async_destroy", "com.example:backup-id", "io_n"] {
+            assert_eq!(Ok(()), validate_key(key), "{}", key);
+        }
+        assert_eq!(Ok(()), validate_key(&"x".repeat(255)));
+        for key in &["", "-o", "a=b", "a b", "päth", "a+b", "Comment", &"x".repeat(256)] {
+            assert_eq!(Err(ArgError::InvalidKey(key.to_string())), validate_key(key));
+        }
+    }
+
+    #[test]
+    fn values() {
+        assert_eq!(Ok(()), validate_value("comment", "rack 4, shelf=2 -f"));
+        assert_eq!(Ok(()), validate_value("comment", ""));
+        let err = |reason| Err(ArgError::InvalidValue(String::from("comment"), reason));
+        assert_eq!(err("contains NUL"), validate_value("comment", "a\0b"));
+        assert_eq!(err("contains line break"), validate_value("comment", "a\nb"));
+        assert_eq!(err("contains line break"), validate_value("comment", "a\r"));
+        assert_eq!(Ok(()), validate_value("comment", &"x".repeat(8191)));
+        assert_eq!(err("too long"), validate_value("comment", &"x".repeat(8192)));
+    }
+
+    #[test]
+    fn pairs() {
+        assert_eq!("failmode=wait", property_arg("failmode", "wait").unwrap());
+        assert_eq!("autoexpand=on", pair_arg("autoexpand", &true).unwrap());
+        let comment = String::from("one\ntwo");
+        assert!(pair_arg("comment", &comment).is_err());
+        assert_eq!(Ok(()), check_pair(OsStr::new("comment=a=b")));
+        assert_eq!(
+            Err(ArgError::InvalidValue(String::from("comment"), "missing")),
+            check_pair(OsStr::new("comment"))
+        );
+        assert!(check_pair(OsStr::new("-f=x")).is_err());
+    }
+}
@@ -0,0 +1,481 @@
+//! `zetta` - command line front end to libzetta.
+//!
+//! The tool is a reference user of the crate: every command goes through the same engines a
+//! consumer would use, so it doubles as a smoke test on a live system and as a quick way to see
+//! what the library makes of a pool. It's only built with the `cli` feature:
+//!
+//! ```text
+//! cargo run --features cli --bin zetta -- status --json
+//! ```
+//!
+//! ### Usage
+//! ```text
+//! zetta status [--json] [POOL]...
+//! zetta create [-f] [-m MOUNTPOINT] [-R ALTROOT] POOL VDEV...
+//! zetta snapshot [-o PROPERTY=VALUE]... SNAPSHOT...
+//! zetta replicate [-F] [-i FROM] SNAPSHOT DESTINATION
+//! ```
+//!
+//! `VDEV` uses `zpool create` syntax: `mirror`, `raidz`, `raidz2` and `raidz3` start a group of
+//! devices, `log`, `cache` and `spare` start a section, every other word is a device.
+use std::{collections::HashMap, convert::TryFrom, env, error, ffi::OsStr, path::PathBuf,
+          process};
+
+use libzetta::{args::check_pair,
+               names::{PoolName, SnapshotName},
+               zfs::{replication::replicate, DelegatingZfsEngine, SendFlags, ZfsEngine},
+               zpool::{vdev::ErrorStatistics, CreateMode, CreateVdevRequest, CreateZpoolRequest,
+                       Disk, Health, Vdev, VdevType, Zpool, ZpoolEngine, ZpoolOpen3}};
+
+type CliResult<T = ()> = Result<T, Box<dyn error::Error>>;
+
+static USAGE: &str = "usage:
+    zetta status [--json] [POOL]...
+    zetta create [-f] [-m MOUNTPOINT] [-R ALTROOT] POOL VDEV...
+    zetta snapshot [-o PROPERTY=VALUE]... SNAPSHOT...
+    zetta replicate [-F] [-i FROM] SNAPSHOT DESTINATION
+";
+
+/// Words that start a group of devices in a vdev spec.
+static GROUPS: &[&str] = &["mirror", "raidz", "raidz1", "raidz2", "raidz3"];
+
+fn main() {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if let Err(err) = run(&args) {
+        eprintln!("zetta: {}", err);
+        process::exit(1);
+    }
+}
+
+fn run(args: &[String]) -> CliResult {
+    let (command, rest) = match args.split_first() {
+        Some((command, rest)) => (command.as_str(), rest),
+        None => return Err(USAGE.into()),
+    };
+    match command {
+        "status" => status(rest),
+        "create" => create(rest),
+        "snapshot" => snapshot(rest),
+        "replicate" => replicate_snapshot(rest),
+        "help" | "-h" | "--help" => {
+            print!("{}", USAGE);
+            Ok(())
+        },
+        other => Err(format!("unknown command '{}'\n{}", other, USAGE).into()),
+    }
+}
+
+/// Options and operands of a command. Options without a value are stored with an empty one.
+#[derive(Debug, Default, PartialEq)]
+struct Parsed<'a> {
+    options:  Vec<(&'a str, &'a str)>,
+    operands: Vec<&'a str>,
+}
+
+impl<'a> Parsed<'a> {
+    fn has(&self, option: &str) -> bool { self.options.iter().any(|(name, _)| *name == option) }
+
+    /// Value of the last occurrence of the option.
+    fn value(&self, option: &str) -> Option<&'a str> {
+        self.options.iter().rev().find(|(name, _)| *name == option).map(|(_, value)| *value)
+    }
+}
+
+/// Split arguments into options and operands.
+///
+/// * `switches` - Options without a value.
+/// * `with_value` - Options that take the next argument as their value.
+fn parse_args<'a>(
+    args: &'a [String],
+    switches: &[&str],
+    with_value: &[&str],
+) -> CliResult<Parsed<'a>> {
+    let mut parsed = Parsed::default();
+    let mut args = args.iter().map(String::as_str);
+    while let Some(arg) = args.next() {
+        if arg == "--" {
+            parsed.operands.extend(args.by_ref());
+        } else if switches.contains(&arg) {
+            parsed.options.push((arg, ""));
+        } else if with_value.contains(&arg) {
+            let value = args.next().ok_or_else(|| format!("option {} needs a value", arg))?;
+            parsed.options.push((arg, value));
+        } else if arg.starts_with('-') && arg.len() > 1 {
+            return Err(format!("unknown option '{}'\n{}", arg, USAGE).into());
+        } else {
+            parsed.operands.push(arg);
+        }
+    }
+    Ok(parsed)
+}
+
+fn status(args: &[String]) -> CliResult {
+    let parsed = parse_args(args, &["--json"], &[])?;
+    let engine = ZpoolOpen3::default();
+    let pools = if parsed.operands.is_empty() {
+        engine.all()?
+    } else {
+        let mut pools = Vec::with_capacity(parsed.operands.len());
+        for name in &parsed.operands {
+            pools.push(engine.status(PoolName::try_from(*name)?)?);
+        }
+        pools
+    };
+    if parsed.has("--json") {
+        let pools: Vec<String> = pools.iter().map(pool_json).collect();
+        println!("[{}]", pools.join(","));
+    } else {
+        for pool in &pools {
+            print!("{}", pool_text(pool));
+        }
+    }
+    Ok(())
+}
+
+fn create(args: &[String]) -> CliResult {
+    let parsed = parse_args(args, &["-f"], &["-m", "-R"])?;
+    let (name, devices) = match parsed.operands.split_first() {
+        Some((name, devices)) if !devices.is_empty() => (PoolName::try_from(*name)?, devices),
+        _ => return Err(USAGE.into()),
+    };
+    let spec = parse_spec(devices)?;
+    let mut builder = CreateZpoolRequest::builder();
+    builder
+        .name(name.as_str())
+        .vdevs(spec.vdevs)
+        .logs(spec.logs)
+        .caches(spec.caches)
+        .spares(spec.spares);
+    if parsed.has("-f") {
+        builder.create_mode(CreateMode::Force);
+    }
+    if let Some(mount) = parsed.value("-m") {
+        builder.mount(PathBuf::from(mount));
+    }
+    if let Some(altroot) = parsed.value("-R") {
+        builder.altroot(PathBuf::from(altroot));
+    }
+    ZpoolOpen3::default().create(builder.build()?)?;
+    Ok(())
+}
+
+fn snapshot(args: &[String]) -> CliResult {
+    let parsed = parse_args(args, &[], &["-o"])?;
+    if parsed.operands.is_empty() {
+        return Err(USAGE.into());
+    }
+    let mut properties = HashMap::new();
+    for (_, pair) in parsed.options.iter().filter(|(name, _)| *name == "-o") {
+        check_pair(OsStr::new(pair))?;
+        let mut split = pair.splitn(2, '=');
+        if let (Some(key), Some(value)) = (split.next(), split.next()) {
+            properties.insert(key.to_string(), value.to_string());
+        }
+    }
+    let mut snapshots = Vec::with_capacity(parsed.operands.len());
+    for name in &parsed.operands {
+        snapshots.push(PathBuf::from(SnapshotName::try_from(*name)?));
+    }
+    let properties = if properties.is_empty() { None } else { Some(properties) };
+    DelegatingZfsEngine::new()?.snapshot(&snapshots, properties)?;
+    Ok(())
+}
+
+fn replicate_snapshot(args: &[String]) -> CliResult {
+    let parsed = parse_args(args, &["-F"], &["-i"])?;
+    let (snapshot, destination) = match parsed.operands.as_slice() {
+        [snapshot, destination] => {
+            (SnapshotName::try_from(*snapshot)?, SnapshotName::try_from(*destination)?)
+        },
+        _ => return Err(USAGE.into()),
+    };
+    let from = match parsed.value("-i") {
+        Some(from) => Some(PathBuf::from(SnapshotName::try_from(from)?)),
+        None => None,
+    };
+    let engine = DelegatingZfsEngine::new()?;
+    let size = replicate(
+        &engine,
+        snapshot.into(),
+        from,
+        SendFlags::empty(),
+        &engine,
+        destination.into(),
+        parsed.has("-F"),
+    )?;
+    println!("replicated, estimated stream size {} bytes", size);
+    Ok(())
+}
+
+/// Section of a vdev spec.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Section {
+    Data,
+    Log,
+    Cache,
+    Spare,
+}
+
+/// Devices of a new pool, split the way `CreateZpoolRequest` wants them.
+#[derive(Debug, Default, PartialEq)]
+struct Spec {
+    vdevs:  Vec<CreateVdevRequest>,
+    logs:   Vec<CreateVdevRequest>,
+    caches: Vec<PathBuf>,
+    spares: Vec<PathBuf>,
+}
+
+impl Spec {
+    fn add(&mut self, section: Section, vdev: CreateVdevRequest) -> CliResult {
+        match (section, vdev) {
+            (Section::Data, vdev) => self.vdevs.push(vdev),
+            (Section::Log, vdev) => self.logs.push(vdev),
+            (Section::Cache, CreateVdevRequest::SingleDisk(disk)) => self.caches.push(disk),
+            (Section::Spare, CreateVdevRequest::SingleDisk(disk)) => self.spares.push(disk),
+            (Section::Cache, _) | (Section::Spare, _) => {
+                return Err("cache and spare devices can't be grouped".into());
+            },
+        }
+        Ok(())
+    }
+}
+
+/// Vdev out of a group keyword and its devices.
+fn group_vdev(kind: &str, disks: Vec<PathBuf>) -> CliResult<CreateVdevRequest> {
+    if disks.is_empty() {
+        return Err(format!("{} without devices", kind).into());
+    }
+    match kind {
+        "mirror" => Ok(CreateVdevRequest::Mirror(disks)),
+        "raidz" | "raidz1" => Ok(CreateVdevRequest::RaidZ(disks)),
+        "raidz2" => Ok(CreateVdevRequest::RaidZ2(disks)),
+        "raidz3" => Ok(CreateVdevRequest::RaidZ3(disks)),
+        other => Err(format!("unknown vdev type '{}'", other).into()),
+    }
+}
+
+/// Parse vdevs written the way `zpool create` takes them.
+fn parse_spec(words: &[&str]) -> CliResult<Spec> {
+    let mut spec = Spec::default();
+    let mut section = Section::Data;
+    let mut group: Option<(&str, Vec<PathBuf>)> = None;
+    for &word in words {
+        let next_section = match word {
+            "log" => Some(Section::Log),
+            "cache" => Some(Section::Cache),
+            "spare" => Some(Section::Spare),
+            _ => None,
+        };
+        let starts_group = GROUPS.contains(&word);
+        if next_section.is_some() || starts_group {
+            if let Some((kind, disks)) = group.take() {
+                spec.add(section, group_vdev(kind, disks)?)?;
+            }
+        }
+        if let Some(next_section) = next_section {
+            section = next_section;
+        } else if starts_group {
+            group = Some((word, Vec::new()));
+        } else if let Some((_, ref mut disks)) = group {
+            disks.push(PathBuf::from(word));
+        } else {
+            spec.add(section, CreateVdevRequest::disk(word))?;
+        }
+    }
+    if let Some((kind, disks)) = group {
+        spec.add(section, group_vdev(kind, disks)?)?;
+    }
+    Ok(spec)
+}
+
+fn kind_name(kind: &VdevType) -> String {
+    match kind {
+        VdevType::SingleDisk => "disk".into(),
+        VdevType::Mirror => "mirror".into(),
+        VdevType::RaidZ => "raidz1".into(),
+        VdevType::RaidZ2 => "raidz2".into(),
+        VdevType::RaidZ3 => "raidz3".into(),
+        VdevType::DRaid { parity, data, children, spares } => {
+            format!("draid{}:{}d:{}c:{}s", parity, data, children, spares)
+        },
+    }
+}
+
+/// One row of text status: name indented by depth, health and error counters.
+fn push_row(
+    out: &mut String,
+    depth: usize,
+    name: &str,
+    health: &Health,
+    errors: &ErrorStatistics,
+) {
+    let name = format!("{:indent$}{}", "", name, indent = depth * 2);
+    out.push_str(&format!(
+        "{:<40} {:<8} {:>5} {:>5} {:>5}\n",
+        name,
+        health.as_str(),
+        errors.read,
+        errors.write,
+        errors.checksum
+    ));
+}
+
+fn push_disk(out: &mut String, depth: usize, disk: &Disk) {
+    let path = disk.path().to_string_lossy();
+    push_row(out, depth, &path, disk.health(), disk.error_statistics());
+}
+
+fn push_vdevs(out: &mut String, depth: usize, vdevs: &[Vdev]) {
+    for vdev in vdevs {
+        let mut disk_depth = depth;
+        if *vdev.kind() != VdevType::SingleDisk {
+            let name = kind_name(vdev.kind());
+            push_row(out, depth, &name, vdev.health(), vdev.error_statistics());
+            disk_depth += 1;
+        }
+        for disk in vdev.disks() {
+            push_disk(out, disk_depth, disk);
+        }
+    }
+}
+
+/// Pool as a tree in the spirit of `zpool status`.
+fn pool_text(pool: &Zpool) -> String {
+    let mut out =
+        format!("{:<40} {:<8} {:>5} {:>5} {:>5}\n", "NAME", "STATE", "READ", "WRITE", "CKSUM");
+    push_row(&mut out, 0, pool.name(), pool.health(), pool.error_statistics());
+    push_vdevs(&mut out, 1, pool.vdevs());
+    if !pool.logs().is_empty() {
+        out.push_str("  logs\n");
+        push_vdevs(&mut out, 2, pool.logs());
+    }
+    for (section, disks) in &[("cache", pool.caches()), ("spares", pool.spares())] {
+        if !disks.is_empty() {
+            out.push_str(&format!("  {}\n", section));
+            for disk in disks.iter() {
+                push_disk(&mut out, 2, disk);
+            }
+        }
+    }
+    if let Some(errors) = pool.errors() {
+        out.push_str(&format!("errors: {}\n", errors));
+    }
+    out
+}
+
+/// String as JSON string literal.
+fn json_string(value: &str) -> String {
+    let mut out = String::with_capacity(value.len() + 2);
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c < ' ' => out.push_str(&format!("\\u{:04x}", u32::from(c))),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+fn json_list<T>(items: &[T], to_json: fn(&T) -> String) -> String {
+    let items: Vec<String> = items.iter().map(to_json).collect();
+    format!("[{}]", items.join(","))
+}
+
+/// Health and error counters as JSON object members.
+fn state_json(health: &Health, errors: &ErrorStatistics) -> String {
+    format!(
+        "\"health\":{},\"read\":{},\"write\":{},\"checksum\":{}",
+        json_string(health.as_str()),
+        errors.read,
+        errors.write,
+        errors.checksum
+    )
+}
+
+fn disk_json(disk: &Disk) -> String {
+    format!(
+        "{{\"path\":{},{}}}",
+        json_string(&disk.path().to_string_lossy()),
+        state_json(disk.health(), disk.error_statistics())
+    )
+}
+
+fn vdev_json(vdev: &Vdev) -> String {
+    format!(
+        "{{\"kind\":{},{},\"disks\":{}}}",
+        json_string(&kind_name(vdev.kind())),
+        state_json(vdev.health(), vdev.error_statistics()),
+        json_list(vdev.disks(), disk_json)
+    )
+}
+
+fn pool_json(pool: &Zpool) -> String {
+    let errors = pool.errors().as_ref().map_or_else(|| String::from("null"), |e| json_string(e));
+    format!(
+        "{{\"name\":{},{},\"vdevs\":{},\"logs\":{},\"caches\":{},\"spares\":{},\"errors\":{}}}",
+        json_string(pool.name()),
+        state_json(pool.health(), pool.error_statistics()),
+        json_list(pool.vdevs(), vdev_json),
+        json_list(pool.logs(), vdev_json),
+        json_list(pool.caches(), disk_json),
+        json_list(pool.spares(), disk_json),
+        errors
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn specs() {
+        let words =
+            ["c", "mirror", "a", "b", "log", "mirror", "d", "e", "cache", "f", "spare", "g"];
+        let spec = parse_spec(&words).unwrap();
+        let mirror = CreateVdevRequest::Mirror(vec!["a".into(), "b".into()]);
+        let expected = Spec {
+            vdevs:  vec![CreateVdevRequest::disk("c"), mirror],
+            logs:   vec![CreateVdevRequest::Mirror(vec!["d".into(), "e".into()])],
+            caches: vec!["f".into()],
+            spares: vec!["g".into()],
+        };
+        assert_eq!(expected, spec);
+
+        let words = ["raidz", "a", "b", "c", "raidz2", "d", "e", "f", "g"];
+        let spec = parse_spec(&words).unwrap();
+        assert_eq!(
+            vec![
+                CreateVdevRequest::RaidZ(vec!["a".into(), "b".into(), "c".into()]),
+                CreateVdevRequest::RaidZ2(vec!["d".into(), "e".into(), "f".into(), "g".into()]),
+            ],
+            spec.vdevs
+        );
+
+        assert!(parse_spec(&["mirror", "log", "a"]).is_err());
+        assert!(parse_spec(&["cache", "mirror", "a", "b"]).is_err());
+    }
+
+    #[test]
+    fn args() {
+        let args: Vec<String> =
+            ["-f", "-m", "/mnt", "tank", "--", "-weird"].iter().map(|s| s.to_string()).collect();
+        let parsed = parse_args(&args, &["-f"], &["-m"]).unwrap();
+        assert!(parsed.has("-f"));
+        assert_eq!(Some("/mnt"), parsed.value("-m"));
+        assert_eq!(vec!["tank", "-weird"], parsed.operands);
+        assert!(parse_args(&args[..2], &["-f"], &["-m"]).is_err());
+        assert!(parse_args(&args, &[], &["-m"]).is_err());
+    }
+
+    #[test]
+    fn json() {
+        assert_eq!("\"tank\"", json_string("tank"));
+        assert_eq!("\"a\\\"b\\\\c\\nd\\u0001\"", json_string("a\"b\\c\nd\u{1}"));
+    }
+}
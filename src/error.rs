@@ -0,0 +1,211 @@
+//! Crate-wide error that covers both [`zpool`](../zpool/index.html) and [`zfs`](../zfs/index.html).
+//!
+//! Module errors stay as they are and carry all the details. [`Error`](enum.Error.html) wraps
+//! either of them, so code that works with both pools and datasets can use `?` everywhere and
+//! branch on a single [`ErrorKind`](enum.ErrorKind.html).
+//!
+//! ### Usage
+//! ```rust,no_run
+//! use libzetta::{zfs::{DelegatingZfsEngine, ZfsEngine},
+//!                zpool::{ZpoolEngine, ZpoolOpen3},
+//!                ErrorKind, Result};
+//!
+//! fn pool_and_dataset_exist(pool: &str, dataset: &str) -> Result<bool> {
+//!     let zpool = ZpoolOpen3::default();
+//!     let zfs = DelegatingZfsEngine::new()?;
+//!     Ok(zpool.exists(pool)? && zfs.exists(dataset)?)
+//! }
+//!
+//! match pool_and_dataset_exist("tank", "tank/usr") {
+//!     Err(ref e) if e.kind() == ErrorKind::PermissionDenied => println!("run me as root"),
+//!     other => println!("{:?}", other),
+//! }
+//! ```
+use crate::{names::NameError,
+            zfs,
+            zpool::{ZpoolError, ZpoolErrorKind}};
+
+/// Type alias to `Result<T, libzetta::Error>`.
+pub type Result<T, E = Error> = std::result::Result<T, E>;
+
+quick_error! {
+    /// Error of any module of this crate.
+    #[derive(Debug)]
+    pub enum Error {
+        /// Error from `zpool` module.
+        Zpool(err: ZpoolError) {
+            cause(err)
+            display("{}", err)
+            from()
+        }
+        /// Error from `zfs` module.
+        Zfs(err: zfs::Error) {
+            cause(err)
+            display("{}", err)
+            from()
+        }
+        /// Malformed name, see [`names`](../names/index.html).
+        Name(err: NameError) {
+            cause(err)
+            display("{}", err)
+            from()
+        }
+    }
+}
+
+/// Category of error, shared by all modules.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum ErrorKind {
+    /// `zpool`/`zfs` not found in PATH or `libzfs_core` failed to initialize.
+    CmdNotFound,
+    /// Pool, dataset or device doesn't exist.
+    NotFound,
+    /// Request failed validation or was rejected as invalid.
+    InvalidInput,
+    /// Pool or device is used by someone else.
+    InUse,
+    /// Operation is not possible in current state of the pool (e.g. no scrub to pause, no
+    /// replicas left).
+    InvalidState,
+    /// Not enough privileges.
+    PermissionDenied,
+    /// Failed to parse output of a command.
+    ParseError,
+    /// Any other I/O error.
+    Io,
+    /// Operation isn't implemented by this engine.
+    Unimplemented,
+    /// Don't know (yet) how to categorize this error.
+    Other,
+}
+
+impl Error {
+    /// Convert into `ErrorKind`.
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Error::Zpool(err) => ErrorKind::from(err.kind()),
+            Error::Zfs(err) => ErrorKind::from(err.kind()),
+            Error::Name(_) => ErrorKind::InvalidInput,
+        }
+    }
+
+    /// Underlying `zpool` error, if this is one.
+    pub fn as_zpool(&self) -> Option<&ZpoolError> {
+        match self {
+            Error::Zpool(err) => Some(err),
+            Error::Zfs(_) | Error::Name(_) => None,
+        }
+    }
+
+    /// Underlying `zfs` error, if this is one.
+    pub fn as_zfs(&self) -> Option<&zfs::Error> {
+        match self {
+            Error::Zfs(err) => Some(err),
+            Error::Zpool(_) | Error::Name(_) => None,
+        }
+    }
+}
+
+impl From<ZpoolErrorKind> for ErrorKind {
+    fn from(kind: ZpoolErrorKind) -> ErrorKind {
+        match kind {
+            ZpoolErrorKind::CmdNotFound => ErrorKind::CmdNotFound,
+            ZpoolErrorKind::Io => ErrorKind::Io,
+            ZpoolErrorKind::PoolNotFound
+            | ZpoolErrorKind::DeviceNotFound
+            | ZpoolErrorKind::NoSuchDevice => ErrorKind::NotFound,
+            ZpoolErrorKind::InvalidTopology
+            | ZpoolErrorKind::UnknownRaidType
+            | ZpoolErrorKind::DeviceTooSmall
+            | ZpoolErrorKind::MismatchedReplicationLevel
+            | ZpoolErrorKind::InvalidCacheDevice
+            | ZpoolErrorKind::TrimNotSupported
+            | ZpoolErrorKind::InvalidProperty => ErrorKind::InvalidInput,
+            ZpoolErrorKind::VdevReuse
+            | ZpoolErrorKind::ActiveOnOtherHost
+            | ZpoolErrorKind::ActiveVdev
+            | ZpoolErrorKind::LockHeld => ErrorKind::InUse,
+            ZpoolErrorKind::NoActiveScrubs
+            | ZpoolErrorKind::NoValidReplicas
+            | ZpoolErrorKind::CannotAttach
+            | ZpoolErrorKind::CannotReplace
+            | ZpoolErrorKind::CannotRemove
+            | ZpoolErrorKind::CannotSplit
+            | ZpoolErrorKind::OnlyDevice
+            | ZpoolErrorKind::CheckpointExists
+            | ZpoolErrorKind::NotInitializing
+            | ZpoolErrorKind::PoolChanged
+            | ZpoolErrorKind::NoCheckpoint => ErrorKind::InvalidState,
+            ZpoolErrorKind::PermissionDenied | ZpoolErrorKind::Denied => {
+                ErrorKind::PermissionDenied
+            },
+            ZpoolErrorKind::ParseError
+            | ZpoolErrorKind::UnrecognizedOutput
+            | ZpoolErrorKind::Overflow => ErrorKind::ParseError,
+            ZpoolErrorKind::Other => ErrorKind::Other,
+        }
+    }
+}
+
+impl From<zfs::ErrorKind> for ErrorKind {
+    fn from(kind: zfs::ErrorKind) -> ErrorKind {
+        match kind {
+            zfs::ErrorKind::CmdNotFound | zfs::ErrorKind::LZCInitializationFailed => {
+                ErrorKind::CmdNotFound
+            },
+            zfs::ErrorKind::Io | zfs::ErrorKind::NvOpError => ErrorKind::Io,
+            zfs::ErrorKind::DatasetNotFound
+            | zfs::ErrorKind::HoldNotFound
+            | zfs::ErrorKind::MissingIncrementalSource => ErrorKind::NotFound,
+            zfs::ErrorKind::InvalidInput
+            | zfs::ErrorKind::ValidationErrors
+            | zfs::ErrorKind::InvalidManifest
+            | zfs::ErrorKind::InvalidProperty
+            | zfs::ErrorKind::NotAVolume
+            | zfs::ErrorKind::NotAClone
+            | zfs::ErrorKind::VolumeShrink
+            | zfs::ErrorKind::InvalidVolumeSize
+            | zfs::ErrorKind::InvalidStream => ErrorKind::InvalidInput,
+            zfs::ErrorKind::InsufficientSpace
+            | zfs::ErrorKind::RollbackFailed
+            | zfs::ErrorKind::NotLatestSnapshot
+            | zfs::ErrorKind::NoSnapshots
+            | zfs::ErrorKind::DeviceNotReady => ErrorKind::InvalidState,
+            zfs::ErrorKind::DatasetExists
+            | zfs::ErrorKind::DatasetBusy
+            | zfs::ErrorKind::HoldExists
+            | zfs::ErrorKind::MountpointCollision => ErrorKind::InUse,
+            zfs::ErrorKind::Denied => ErrorKind::PermissionDenied,
+            zfs::ErrorKind::Unimplemented => ErrorKind::Unimplemented,
+            zfs::ErrorKind::Unknown
+            | zfs::ErrorKind::MultiOpError
+            | zfs::ErrorKind::BatchFailed => ErrorKind::Other,
+            zfs::ErrorKind::ParseError | zfs::ErrorKind::Overflow => ErrorKind::ParseError,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[test]
+    fn kinds() {
+        let err = Error::from(ZpoolError::PoolNotFound);
+        assert_eq!(ErrorKind::NotFound, err.kind());
+        assert!(err.as_zpool().is_some());
+        assert!(err.as_zfs().is_none());
+
+        let err = Error::from(zfs::Error::DatasetNotFound(PathBuf::from("tank/usr")));
+        assert_eq!(ErrorKind::NotFound, err.kind());
+        assert!(err.as_zfs().is_some());
+
+        assert_eq!(ErrorKind::InUse, Error::from(ZpoolError::LockHeld(42)).kind());
+        assert_eq!(ErrorKind::Unimplemented, Error::from(zfs::Error::Unimplemented).kind());
+
+        let err = Error::from(crate::names::PoolName::new("mirror").unwrap_err());
+        assert_eq!(ErrorKind::InvalidInput, err.kind());
+        assert!(err.as_zpool().is_none());
+    }
+}
@@ -0,0 +1,183 @@
+//! What this build of the crate and this system can do.
+//!
+//! Code that is compiled against libzetta but runs on many hosts can't tell up front which
+//! optional parts of the crate were compiled in or which `zpool(8)` and `zfs(8)` subcommands the
+//! installed ZFS knows. [`features`](fn.features.html) answers both: cargo features of the build,
+//! whether `libzfs_core` initializes, and subcommands listed in the usage of `zpool` and `zfs`.
+//! [`Features`](struct.Features.html) prints as `key=value` lines, so it can be logged or handed
+//! to another process as is. The same pairs are available from
+//! [`to_pairs`](struct.Features.html#method.to_pairs) for callers that want to serialize them
+//! their own way.
+//!
+//! Detection runs `zpool` and `zfs` without arguments. Binaries are taken from `ZPOOL_CMD` and
+//! `ZFS_CMD` like in open3 engines.
+//!
+//! ### Usage
+//! ```rust,no_run
+//! let features = libzetta::features();
+//! if features.supports_zpool("checkpoint") {
+//!     println!("checkpoints are available");
+//! }
+//! print!("{}", features);
+//! ```
+use std::{env, ffi::OsString, fmt, process::Command};
+
+use crate::zfs::ZfsLzc;
+
+/// Compile-time features and runtime capabilities.
+#[derive(Debug, Clone, PartialEq, Eq, Getters, CopyGetters)]
+pub struct Features {
+    /// Version of the crate.
+    #[get_copy = "pub"]
+    version:           &'static str,
+    /// `disk-management` cargo feature: [`partition`](../zpool/partition/index.html) module.
+    #[get_copy = "pub"]
+    disk_management:   bool,
+    /// `cli` cargo feature: the `zetta` command line tool.
+    #[get_copy = "pub"]
+    cli:               bool,
+    /// `libzfs_core` initialized, so [`ZfsLzc`](../zfs/struct.ZfsLzc.html) and
+    /// [`DelegatingZfsEngine`](../zfs/struct.DelegatingZfsEngine.html) can be used.
+    #[get_copy = "pub"]
+    lzc:               bool,
+    /// Subcommands of `zpool`. Empty if it's not installed.
+    #[get = "pub"]
+    zpool_subcommands: Vec<String>,
+    /// Subcommands of `zfs`. Empty if it's not installed.
+    #[get = "pub"]
+    zfs_subcommands:   Vec<String>,
+}
+
+impl Features {
+    /// `zpool` is installed.
+    pub fn zpool(&self) -> bool { !self.zpool_subcommands.is_empty() }
+
+    /// `zfs` is installed.
+    pub fn zfs(&self) -> bool { !self.zfs_subcommands.is_empty() }
+
+    /// `zpool` knows this subcommand.
+    pub fn supports_zpool(&self, subcommand: &str) -> bool {
+        self.zpool_subcommands.iter().any(|known| known == subcommand)
+    }
+
+    /// `zfs` knows this subcommand.
+    pub fn supports_zfs(&self, subcommand: &str) -> bool {
+        self.zfs_subcommands.iter().any(|known| known == subcommand)
+    }
+
+    /// Names of cargo features this build was compiled with.
+    pub fn cargo_features(&self) -> Vec<&'static str> {
+        let features = [("disk-management", self.disk_management), ("cli", self.cli)];
+        features.iter().filter(|(_, enabled)| *enabled).map(|(name, _)| *name).collect()
+    }
+
+    /// Everything as `(key, value)` pairs in the order `Display` prints them. Lists are joined
+    /// with `,`.
+    pub fn to_pairs(&self) -> Vec<(&'static str, String)> {
+        vec![
+            ("version", self.version.to_string()),
+            ("disk_management", self.disk_management.to_string()),
+            ("cli", self.cli.to_string()),
+            ("lzc", self.lzc.to_string()),
+            ("zpool_subcommands", self.zpool_subcommands.join(",")),
+            ("zfs_subcommands", self.zfs_subcommands.join(",")),
+        ]
+    }
+}
+
+impl fmt::Display for Features {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (key, value) in self.to_pairs() {
+            writeln!(f, "{}={}", key, value)?;
+        }
+        Ok(())
+    }
+}
+
+/// Subcommands from usage that `zpool` and `zfs` print when run without arguments: first word of
+/// every tab-indented line. Aliases like `unmount|umount` count as two.
+fn parse_usage(usage: &str) -> Vec<String> {
+    let mut subcommands: Vec<String> = Vec::new();
+    for line in usage.lines().filter(|line| line.starts_with('\t')) {
+        let word = line.split_whitespace().next().unwrap_or_default();
+        for name in word.split('|') {
+            let known = subcommands.iter().any(|known| known == name);
+            if !name.is_empty() && !name.starts_with('-') && !known {
+                subcommands.push(name.to_string());
+            }
+        }
+    }
+    subcommands
+}
+
+fn subcommands(var: &str, default: &str) -> Vec<String> {
+    let cmd = env::var_os(var).unwrap_or_else(|| OsString::from(default));
+    match Command::new(cmd).output() {
+        Ok(out) => parse_usage(&String::from_utf8_lossy(&out.stderr)),
+        Err(_) => Vec::new(),
+    }
+}
+
+/// Find out what can be used. Spawns `zpool` and `zfs`, so don't call it in a hot loop.
+pub fn features() -> Features {
+    Features {
+        version:           crate::VERSION,
+        disk_management:   cfg!(feature = "disk-management"),
+        cli:               cfg!(feature = "cli"),
+        lzc:               ZfsLzc::new().is_ok(),
+        zpool_subcommands: subcommands("ZPOOL_CMD", "zpool"),
+        zfs_subcommands:   subcommands("ZFS_CMD", "zfs"),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    static USAGE: &str = "usage: zfs command args ...
+where 'command' is one of the following:
+
+\tversion
+
+\tcreate [-Pnpuv] [-o property=value] ... <filesystem>
+\t    -V <size> <volume>
+\tdestroy [-fnpRrv] <filesystem|volume>
+
+\tmount [-j]
+\tunmount|umount [-fu] -a
+\tunmount|umount [-fu] <filesystem|mountpoint>
+
+Each dataset is of the form: pool/[dataset/]*dataset[@name]
+";
+
+    #[test]
+    fn usage() {
+        let subcommands = parse_usage(USAGE);
+        assert_eq!(vec!["version", "create", "destroy", "mount", "unmount", "umount"], subcommands);
+        assert!(parse_usage("zfs: command not found\n").is_empty());
+    }
+
+    #[test]
+    fn display() {
+        let features = Features {
+            version:           "0.0.0",
+            disk_management:   false,
+            cli:               true,
+            lzc:               true,
+            zpool_subcommands: vec![String::from("create"), String::from("trim")],
+            zfs_subcommands:   Vec::new(),
+        };
+        assert!(features.supports_zpool("trim"));
+        assert!(!features.zfs());
+        assert_eq!(vec!["cli"], features.cargo_features());
+        assert_eq!(("cli", String::from("true")), features.to_pairs()[2]);
+        let expected = "version=0.0.0
+disk_management=false
+cli=true
+lzc=true
+zpool_subcommands=create,trim
+zfs_subcommands=
+";
+        assert_eq!(expected, features.to_string());
+    }
+}
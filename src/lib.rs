@@ -26,6 +26,22 @@
 //!
 //! Refer to the [zfs module documentation](zfs/index.html) for more information.
 //!
+//! ## Errors
+//! Every module has its own error type. [`Error`](enum.Error.html) wraps them all for
+//! applications that use more than one module.
+//!
+//! ## Features
+//! [`features`](fn.features.html) tells which optional parts were compiled in and what the
+//! installed ZFS supports.
+//!
+//! ## Names
+//! [`names`](names/index.html) has validated names of pools, datasets, snapshots and bookmarks.
+//! Engine methods accept them in place of strings and paths, but don't require them.
+//!
+//! ## Property schema
+//! [`schema`](schema/index.html) describes every native dataset and pool property: value type,
+//! allowed values, whether it's inherited or can only be set at creation.
+//!
 //! # Usage
 //!
 //! This section is currently under contstruction. Meanwhile, look at integration tests for
@@ -33,7 +49,8 @@
 //!
 //! # Project Structure
 //! ### parsers
-//! Module for PEG parsers backed by [Pest](https://pest.rs/).
+//! Parsers of command output. `zpool status` and dataset lists are parsed line by line, errors of
+//! `zfs` with PEG parsers backed by [Pest](https://pest.rs/).
 //!
 //! ### zpool
 //! This module contains everything you need to work with zpools.
@@ -48,7 +65,15 @@ use strum;
 pub use pest;
 
 // library modules
+pub mod error;
+pub use error::{Error, ErrorKind, Result};
+pub mod args;
+pub mod features;
+pub use features::features;
+pub mod middleware;
+pub mod names;
 pub mod parsers;
+pub mod schema;
 pub mod zfs;
 pub mod zpool;
 
@@ -0,0 +1,794 @@
+//! Hooks around engine operations.
+//!
+//! [`MiddlewareEngine`](struct.MiddlewareEngine.html) wraps any
+//! [`ZpoolEngine`](../zpool/trait.ZpoolEngine.html) or [`ZfsEngine`](../zfs/trait.ZfsEngine.html)
+//! and calls registered [`Middleware`](trait.Middleware.html) before and after every operation
+//! with its name, pools or datasets it works on, how long it took and what kind of error it
+//! returned. That's the place for metrics, tracing or policy: a middleware that returns an error
+//! from [`before`](trait.Middleware.html#method.before) stops the operation, and the engine
+//! returns `Denied` error of its module.
+//!
+//! Operation names are names of trait methods.
+//!
+//! ### Usage
+//! ```rust,no_run
+//! use libzetta::{middleware::{Middleware, MiddlewareEngine, Operation},
+//!                zpool::{DestroyMode, ZpoolEngine, ZpoolOpen3},
+//!                ErrorKind};
+//! use std::time::Duration;
+//!
+//! struct NoDestroy;
+//!
+//! impl Middleware for NoDestroy {
+//!     fn before(&self, op: &Operation) -> Result<(), String> {
+//!         if op.name().starts_with("destroy") {
+//!             return Err(String::from("destroy is not allowed in production"));
+//!         }
+//!         Ok(())
+//!     }
+//! }
+//!
+//! struct Timing;
+//!
+//! impl Middleware for Timing {
+//!     fn after(&self, op: &Operation, duration: Duration, error: Option<ErrorKind>) {
+//!         println!("{} {:?} took {:?}: {:?}", op.name(), op.targets(), duration, error);
+//!     }
+//! }
+//!
+//! let engine = MiddlewareEngine::new(ZpoolOpen3::default()).with(NoDestroy).with(Timing);
+//! assert!(engine.destroy("tank", DestroyMode::Gentle).is_err());
+//! ```
+use std::{collections::HashMap,
+          ffi::OsStr,
+          fmt,
+          os::unix::io::{AsRawFd, RawFd},
+          path::PathBuf,
+          sync::Arc,
+          time::{Duration, Instant}};
+
+use crate::{zfs::{self, delegation::Delegation, holds::Hold, rename::RenameOptions,
+                  BookmarkRequest, CreateDatasetRequest, DatasetKind, DestroyTiming, Properties,
+                  SendFlags, ZfsEngine},
+            zpool::{upgrade::UpgradeStatus, CreateMode, CreateVdevRequest, CreateZpoolRequest,
+                    DestroyMode, ExportMode, HistoryRecord, ImportRequest, ImportWarning,
+                    InitializeAction, IoStat, IoStatExt, LabelClearMode, LatencyHistogram,
+                    OfflineMode, OnlineMode, PoolIoStat, PoolLayout, PoolSummary, PropPair,
+                    SplitOptions, TrimOptions, VdevProperties, VdevPropertiesWrite, Zpool,
+                    ZpoolEngine, ZpoolError, ZpoolEvent, ZpoolProperties, ZpoolPropertiesWrite,
+                    ZpoolResult},
+            ErrorKind};
+
+/// Operation that is about to run or just ran.
+#[derive(Debug, Clone, PartialEq, Eq, Getters, CopyGetters)]
+pub struct Operation {
+    /// Name of the engine method, e.g. `destroy` or `snapshot`.
+    #[get_copy = "pub"]
+    name:    &'static str,
+    /// Pools or datasets it works on. Empty for operations on everything, like `all`.
+    #[get = "pub"]
+    targets: Vec<String>,
+}
+
+/// Hook that is called around every operation of a
+/// [`MiddlewareEngine`](struct.MiddlewareEngine.html).
+pub trait Middleware: Send + Sync {
+    /// Called before the operation. Returning `Err` with a reason stops it.
+    fn before(&self, _op: &Operation) -> Result<(), String> { Ok(()) }
+
+    /// Called after the operation with time it took and kind of error, `None` if it succeeded.
+    /// Not called for operations that were stopped in `before`.
+    fn after(&self, _op: &Operation, _duration: Duration, _error: Option<ErrorKind>) {}
+}
+
+/// Errors that middleware can report and produce.
+trait HookedError {
+    fn error_kind(&self) -> ErrorKind;
+    fn denied(reason: String) -> Self;
+}
+
+impl HookedError for ZpoolError {
+    fn error_kind(&self) -> ErrorKind { ErrorKind::from(self.kind()) }
+
+    fn denied(reason: String) -> Self { ZpoolError::Denied(reason) }
+}
+
+impl HookedError for zfs::Error {
+    fn error_kind(&self) -> ErrorKind { ErrorKind::from(self.kind()) }
+
+    fn denied(reason: String) -> Self { zfs::Error::Denied(reason) }
+}
+
+/// Engine that runs middleware around every operation of the engine it wraps. Middleware
+/// `before` hooks run in order of registration, `after` hooks in reverse order.
+#[derive(Clone)]
+pub struct MiddlewareEngine<E> {
+    inner:       E,
+    middlewares: Vec<Arc<dyn Middleware>>,
+}
+
+impl<E> fmt::Debug for MiddlewareEngine<E>
+where
+    E: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MiddlewareEngine")
+            .field("inner", &self.inner)
+            .field("middlewares", &self.middlewares.len())
+            .finish()
+    }
+}
+
+fn pool(name: &str) -> Vec<String> { vec![name.to_string()] }
+
+fn paths(paths: &[PathBuf]) -> Vec<String> {
+    paths.iter().map(|path| path.to_string_lossy().into_owned()).collect()
+}
+
+impl<E> MiddlewareEngine<E> {
+    /// Wrap an engine without any middleware.
+    pub fn new(inner: E) -> MiddlewareEngine<E> {
+        MiddlewareEngine { inner, middlewares: Vec::new() }
+    }
+
+    /// Add middleware. Builder-style version of [`register`](#method.register).
+    pub fn with<M: Middleware + 'static>(mut self, middleware: M) -> MiddlewareEngine<E> {
+        self.register(Arc::new(middleware));
+        self
+    }
+
+    /// Add middleware. Can be shared between engines.
+    pub fn register(&mut self, middleware: Arc<dyn Middleware>) {
+        self.middlewares.push(middleware)
+    }
+
+    /// Wrapped engine. Calls made on it directly skip middleware.
+    pub fn inner(&self) -> &E { &self.inner }
+
+    fn run<T, R, F>(&self, name: &'static str, targets: Vec<String>, op: F) -> Result<T, R>
+    where
+        R: HookedError,
+        F: FnOnce() -> Result<T, R>,
+    {
+        let operation = Operation { name, targets };
+        for middleware in &self.middlewares {
+            middleware.before(&operation).map_err(R::denied)?;
+        }
+        let started = Instant::now();
+        let result = op();
+        let duration = started.elapsed();
+        let error = result.as_ref().err().map(HookedError::error_kind);
+        for middleware in self.middlewares.iter().rev() {
+            middleware.after(&operation, duration, error);
+        }
+        result
+    }
+}
+
+impl<E: ZpoolEngine> ZpoolEngine for MiddlewareEngine<E> {
+    fn exists<N: AsRef<str>>(&self, name: N) -> ZpoolResult<bool> {
+        self.run("exists", pool(name.as_ref()), || self.inner.exists(name))
+    }
+
+    fn create(&self, request: CreateZpoolRequest) -> ZpoolResult<()> {
+        self.run("create", pool(request.name()), || self.inner.create(request))
+    }
+
+    fn create_dry_run(&self, request: CreateZpoolRequest) -> ZpoolResult<PoolLayout> {
+        self.run("create_dry_run", pool(request.name()), || self.inner.create_dry_run(request))
+    }
+
+    fn destroy<N: AsRef<str>>(&self, name: N, mode: DestroyMode) -> ZpoolResult<()> {
+        self.run("destroy", pool(name.as_ref()), || self.inner.destroy(name, mode))
+    }
+
+    fn read_properties<N: AsRef<str>>(&self, name: N) -> ZpoolResult<ZpoolProperties> {
+        self.run("read_properties", pool(name.as_ref()), || self.inner.read_properties(name))
+    }
+
+    fn update_properties<N: AsRef<str>>(
+        &self,
+        name: N,
+        props: ZpoolPropertiesWrite,
+    ) -> ZpoolResult<ZpoolProperties> {
+        self.run("update_properties", pool(name.as_ref()), || {
+            self.inner.update_properties(name, props)
+        })
+    }
+
+    fn set_property<N: AsRef<str>, P: PropPair>(
+        &self,
+        name: N,
+        key: &str,
+        value: &P,
+    ) -> ZpoolResult<()> {
+        self.run("set_property", pool(name.as_ref()), || self.inner.set_property(name, key, value))
+    }
+
+    fn read_vdev_properties<N: AsRef<str>, V: AsRef<OsStr>>(
+        &self,
+        name: N,
+        vdev: V,
+    ) -> ZpoolResult<VdevProperties> {
+        self.run("read_vdev_properties", pool(name.as_ref()), || {
+            self.inner.read_vdev_properties(name, vdev)
+        })
+    }
+
+    fn set_vdev_property<N: AsRef<str>, V: AsRef<OsStr>, P: PropPair>(
+        &self,
+        name: N,
+        vdev: V,
+        key: &str,
+        value: &P,
+    ) -> ZpoolResult<()> {
+        self.run("set_vdev_property", pool(name.as_ref()), || {
+            self.inner.set_vdev_property(name, vdev, key, value)
+        })
+    }
+
+    fn update_vdev_properties<N: AsRef<str>, V: AsRef<OsStr>>(
+        &self,
+        name: N,
+        vdev: V,
+        props: VdevPropertiesWrite,
+    ) -> ZpoolResult<()> {
+        self.run("update_vdev_properties", pool(name.as_ref()), || {
+            self.inner.update_vdev_properties(name, vdev, props)
+        })
+    }
+
+    fn status_with_vdev_properties<N: AsRef<str>>(&self, name: N) -> ZpoolResult<Zpool> {
+        self.run("status_with_vdev_properties", pool(name.as_ref()), || {
+            self.inner.status_with_vdev_properties(name)
+        })
+    }
+
+    fn status_with_maintenance<N: AsRef<str>>(&self, name: N) -> ZpoolResult<Zpool> {
+        self.run("status_with_maintenance", pool(name.as_ref()), || {
+            self.inner.status_with_maintenance(name)
+        })
+    }
+
+    fn export<N: AsRef<str>>(&self, name: N, mode: ExportMode) -> ZpoolResult<()> {
+        self.run("export", pool(name.as_ref()), || self.inner.export(name, mode))
+    }
+
+    fn available(&self) -> ZpoolResult<Vec<Zpool>> {
+        self.run("available", Vec::new(), || self.inner.available())
+    }
+
+    fn available_in_dir(&self, dir: PathBuf) -> ZpoolResult<Vec<Zpool>> {
+        self.run("available_in_dir", Vec::new(), || self.inner.available_in_dir(dir))
+    }
+
+    fn available_including_destroyed(&self, dir: Option<PathBuf>) -> ZpoolResult<Vec<Zpool>> {
+        self.run("available_including_destroyed", Vec::new(), || {
+            self.inner.available_including_destroyed(dir)
+        })
+    }
+
+    fn import<N: AsRef<str>>(&self, name: N) -> ZpoolResult<Vec<ImportWarning>> {
+        self.run("import", pool(name.as_ref()), || self.inner.import(name))
+    }
+
+    fn import_from_dir<N: AsRef<str>>(
+        &self,
+        name: N,
+        dir: PathBuf,
+    ) -> ZpoolResult<Vec<ImportWarning>> {
+        self.run("import_from_dir", pool(name.as_ref()), || self.inner.import_from_dir(name, dir))
+    }
+
+    fn import_with(&self, request: ImportRequest) -> ZpoolResult<Vec<ImportWarning>> {
+        self.run("import_with", pool(request.name()), || self.inner.import_with(request))
+    }
+
+    fn import_rewind_to_checkpoint<N: AsRef<str>>(
+        &self,
+        name: N,
+        dir: Option<PathBuf>,
+    ) -> ZpoolResult<Vec<ImportWarning>> {
+        self.run("import_rewind_to_checkpoint", pool(name.as_ref()), || {
+            self.inner.import_rewind_to_checkpoint(name, dir)
+        })
+    }
+
+    fn checkpoint<N: AsRef<str>>(&self, name: N) -> ZpoolResult<()> {
+        self.run("checkpoint", pool(name.as_ref()), || self.inner.checkpoint(name))
+    }
+
+    fn discard_checkpoint<N: AsRef<str>>(&self, name: N) -> ZpoolResult<()> {
+        self.run("discard_checkpoint", pool(name.as_ref()), || self.inner.discard_checkpoint(name))
+    }
+
+    fn status<N: AsRef<str>>(&self, name: N) -> ZpoolResult<Zpool> {
+        self.run("status", pool(name.as_ref()), || self.inner.status(name))
+    }
+
+    fn all(&self) -> ZpoolResult<Vec<Zpool>> { self.run("all", Vec::new(), || self.inner.all()) }
+
+    fn list(&self) -> ZpoolResult<Vec<PoolSummary>> {
+        self.run("list", Vec::new(), || self.inner.list())
+    }
+
+    fn unhealthy(&self) -> ZpoolResult<Vec<Zpool>> {
+        self.run("unhealthy", Vec::new(), || self.inner.unhealthy())
+    }
+
+    fn scrub<N: AsRef<str>>(&self, name: N) -> ZpoolResult<()> {
+        self.run("scrub", pool(name.as_ref()), || self.inner.scrub(name))
+    }
+
+    fn pause_scrub<N: AsRef<str>>(&self, name: N) -> ZpoolResult<()> {
+        self.run("pause_scrub", pool(name.as_ref()), || self.inner.pause_scrub(name))
+    }
+
+    fn resume_scrub<N: AsRef<str>>(&self, name: N) -> ZpoolResult<()> {
+        self.run("resume_scrub", pool(name.as_ref()), || self.inner.resume_scrub(name))
+    }
+
+    fn stop_scrub<N: AsRef<str>>(&self, name: N) -> ZpoolResult<()> {
+        self.run("stop_scrub", pool(name.as_ref()), || self.inner.stop_scrub(name))
+    }
+
+    fn history<N: AsRef<str>>(&self, name: N, internal: bool) -> ZpoolResult<Vec<HistoryRecord>> {
+        self.run("history", pool(name.as_ref()), || self.inner.history(name, internal))
+    }
+
+    fn events(&self) -> ZpoolResult<Vec<ZpoolEvent>> {
+        self.run("events", Vec::new(), || self.inner.events())
+    }
+
+    fn iostat<N: AsRef<str>>(&self, name: N) -> ZpoolResult<IoStat> {
+        self.run("iostat", pool(name.as_ref()), || self.inner.iostat(name))
+    }
+
+    fn iostat_vdevs<N: AsRef<str>>(&self, name: N) -> ZpoolResult<PoolIoStat> {
+        self.run("iostat_vdevs", pool(name.as_ref()), || self.inner.iostat_vdevs(name))
+    }
+
+    fn iostat_ext<N: AsRef<str>>(
+        &self,
+        name: N,
+        latency: bool,
+        queues: bool,
+    ) -> ZpoolResult<Vec<IoStatExt>> {
+        self.run("iostat_ext", pool(name.as_ref()), || self.inner.iostat_ext(name, latency, queues))
+    }
+
+    fn iostat_histograms<N: AsRef<str>>(&self, name: N) -> ZpoolResult<Vec<LatencyHistogram>> {
+        self.run("iostat_histograms", pool(name.as_ref()), || self.inner.iostat_histograms(name))
+    }
+
+    fn clear<N: AsRef<str>>(&self, name: N, device: Option<&OsStr>) -> ZpoolResult<()> {
+        self.run("clear", pool(name.as_ref()), || self.inner.clear(name, device))
+    }
+
+    fn take_offline<N: AsRef<str>, D: AsRef<OsStr>>(
+        &self,
+        name: N,
+        device: D,
+        mode: OfflineMode,
+    ) -> ZpoolResult<()> {
+        self.run("take_offline", pool(name.as_ref()), || {
+            self.inner.take_offline(name, device, mode)
+        })
+    }
+
+    fn bring_online<N: AsRef<str>, D: AsRef<OsStr>>(
+        &self,
+        name: N,
+        device: D,
+        mode: OnlineMode,
+    ) -> ZpoolResult<()> {
+        self.run("bring_online", pool(name.as_ref()), || {
+            self.inner.bring_online(name, device, mode)
+        })
+    }
+
+    fn attach<N: AsRef<str>, D: AsRef<OsStr>>(
+        &self,
+        name: N,
+        device: D,
+        new_device: D,
+    ) -> ZpoolResult<()> {
+        self.run("attach", pool(name.as_ref()), || self.inner.attach(name, device, new_device))
+    }
+
+    fn detach<N: AsRef<str>, D: AsRef<OsStr>>(&self, name: N, device: D) -> ZpoolResult<()> {
+        self.run("detach", pool(name.as_ref()), || self.inner.detach(name, device))
+    }
+
+    fn add_vdev<N: AsRef<str>>(
+        &self,
+        name: N,
+        new_vdev: CreateVdevRequest,
+        add_mode: CreateMode,
+    ) -> ZpoolResult<()> {
+        self.run("add_vdev", pool(name.as_ref()), || self.inner.add_vdev(name, new_vdev, add_mode))
+    }
+
+    fn add_zil<N: AsRef<str>>(
+        &self,
+        name: N,
+        new_zil: CreateVdevRequest,
+        add_mode: CreateMode,
+    ) -> ZpoolResult<()> {
+        self.run("add_zil", pool(name.as_ref()), || self.inner.add_zil(name, new_zil, add_mode))
+    }
+
+    fn add_cache<N: AsRef<str>, D: AsRef<OsStr>>(
+        &self,
+        name: N,
+        new_cache: D,
+        add_mode: CreateMode,
+    ) -> ZpoolResult<()> {
+        self.run("add_cache", pool(name.as_ref()), || {
+            self.inner.add_cache(name, new_cache, add_mode)
+        })
+    }
+
+    fn add_spare<N: AsRef<str>, D: AsRef<OsStr>>(
+        &self,
+        name: N,
+        new_spare: D,
+        add_mode: CreateMode,
+    ) -> ZpoolResult<()> {
+        self.run("add_spare", pool(name.as_ref()), || {
+            self.inner.add_spare(name, new_spare, add_mode)
+        })
+    }
+
+    fn replace_disk<N: AsRef<str>, D: AsRef<OsStr>, O: AsRef<OsStr>>(
+        &self,
+        name: N,
+        old_disk: D,
+        new_disk: O,
+    ) -> ZpoolResult<()> {
+        self.run("replace_disk", pool(name.as_ref()), || {
+            self.inner.replace_disk(name, old_disk, new_disk)
+        })
+    }
+
+    fn replace<N: AsRef<str>, D: AsRef<OsStr>, O: AsRef<OsStr>>(
+        &self,
+        name: N,
+        old_disk: D,
+        new_disk: O,
+        mode: CreateMode,
+    ) -> ZpoolResult<()> {
+        self.run("replace", pool(name.as_ref()), || {
+            self.inner.replace(name, old_disk, new_disk, mode)
+        })
+    }
+
+    fn remove<N: AsRef<str>, D: AsRef<OsStr>>(&self, name: N, device: D) -> ZpoolResult<()> {
+        self.run("remove", pool(name.as_ref()), || self.inner.remove(name, device))
+    }
+
+    fn remove_vdev<N: AsRef<str>, D: AsRef<OsStr>>(&self, name: N, vdev: D) -> ZpoolResult<()> {
+        self.run("remove_vdev", pool(name.as_ref()), || self.inner.remove_vdev(name, vdev))
+    }
+
+    fn cancel_removal<N: AsRef<str>>(&self, name: N) -> ZpoolResult<()> {
+        self.run("cancel_removal", pool(name.as_ref()), || self.inner.cancel_removal(name))
+    }
+
+    fn upgrade<N: AsRef<str>>(&self, name: N) -> ZpoolResult<()> {
+        self.run("upgrade", pool(name.as_ref()), || self.inner.upgrade(name))
+    }
+
+    fn upgrade_all(&self) -> ZpoolResult<()> {
+        self.run("upgrade_all", Vec::new(), || self.inner.upgrade_all())
+    }
+
+    fn upgrade_status<N: AsRef<str>>(&self, name: N) -> ZpoolResult<UpgradeStatus> {
+        self.run("upgrade_status", pool(name.as_ref()), || self.inner.upgrade_status(name))
+    }
+
+    fn labelclear<D: AsRef<OsStr>>(&self, device: D, mode: LabelClearMode) -> ZpoolResult<()> {
+        let targets = vec![device.as_ref().to_string_lossy().into_owned()];
+        self.run("labelclear", targets, || self.inner.labelclear(device, mode))
+    }
+
+    fn trim<N: AsRef<str>>(&self, name: N, options: TrimOptions) -> ZpoolResult<()> {
+        self.run("trim", pool(name.as_ref()), || self.inner.trim(name, options))
+    }
+
+    fn initialize<N: AsRef<str>, D: AsRef<OsStr>>(
+        &self,
+        name: N,
+        devices: &[D],
+        action: InitializeAction,
+    ) -> ZpoolResult<()> {
+        self.run("initialize", pool(name.as_ref()), || self.inner.initialize(name, devices, action))
+    }
+
+    fn split<N: AsRef<str>, M: AsRef<str>>(
+        &self,
+        name: N,
+        new_name: M,
+        options: SplitOptions,
+    ) -> ZpoolResult<()> {
+        let targets = vec![name.as_ref().to_string(), new_name.as_ref().to_string()];
+        self.run("split", targets, || self.inner.split(name, new_name, options))
+    }
+
+    fn reguid<N: AsRef<str>>(&self, name: N) -> ZpoolResult<()> {
+        self.run("reguid", pool(name.as_ref()), || self.inner.reguid(name))
+    }
+
+    fn reopen<N: AsRef<str>>(&self, name: N) -> ZpoolResult<()> {
+        self.run("reopen", pool(name.as_ref()), || self.inner.reopen(name))
+    }
+
+    fn sync(&self, name: Option<&str>) -> ZpoolResult<()> {
+        self.run("sync", name.map(pool).unwrap_or_default(), || self.inner.sync(name))
+    }
+}
+
+impl<E: ZfsEngine> ZfsEngine for MiddlewareEngine<E> {
+    fn exists<N: Into<PathBuf>>(&self, name: N) -> zfs::Result<bool> {
+        let name = name.into();
+        self.run("exists", paths(&[name.clone()]), || self.inner.exists(name))
+    }
+
+    fn create(&self, request: CreateDatasetRequest) -> zfs::Result<()> {
+        self.run("create", paths(&[request.name().clone()]), || self.inner.create(request))
+    }
+
+    fn snapshot(
+        &self,
+        snapshots: &[PathBuf],
+        user_properties: Option<HashMap<String, String>>,
+    ) -> zfs::Result<()> {
+        self.run("snapshot", paths(snapshots), || self.inner.snapshot(snapshots, user_properties))
+    }
+
+    fn bookmark(&self, bookmarks: &[BookmarkRequest]) -> zfs::Result<()> {
+        let targets = bookmarks.iter().map(|b| b.bookmark.to_string_lossy().into_owned());
+        self.run("bookmark", targets.collect(), || self.inner.bookmark(bookmarks))
+    }
+
+    fn destroy<N: Into<PathBuf>>(&self, name: N) -> zfs::Result<()> {
+        let name = name.into();
+        self.run("destroy", paths(&[name.clone()]), || self.inner.destroy(name))
+    }
+
+    fn destroy_snapshots(&self, snapshots: &[PathBuf], timing: DestroyTiming) -> zfs::Result<()> {
+        self.run("destroy_snapshots", paths(snapshots), || {
+            self.inner.destroy_snapshots(snapshots, timing)
+        })
+    }
+
+    fn destroy_bookmarks(&self, bookmarks: &[PathBuf]) -> zfs::Result<()> {
+        self.run("destroy_bookmarks", paths(bookmarks), || self.inner.destroy_bookmarks(bookmarks))
+    }
+
+    fn list<N: Into<PathBuf>>(&self, pool: N) -> zfs::Result<Vec<(DatasetKind, PathBuf)>> {
+        let pool = pool.into();
+        self.run("list", paths(&[pool.clone()]), || self.inner.list(pool))
+    }
+
+    fn list_filesystems<N: Into<PathBuf>>(&self, pool: N) -> zfs::Result<Vec<PathBuf>> {
+        let pool = pool.into();
+        self.run("list_filesystems", paths(&[pool.clone()]), || self.inner.list_filesystems(pool))
+    }
+
+    fn list_snapshots<N: Into<PathBuf>>(&self, pool: N) -> zfs::Result<Vec<PathBuf>> {
+        let pool = pool.into();
+        self.run("list_snapshots", paths(&[pool.clone()]), || self.inner.list_snapshots(pool))
+    }
+
+    fn list_bookmarks<N: Into<PathBuf>>(&self, pool: N) -> zfs::Result<Vec<PathBuf>> {
+        let pool = pool.into();
+        self.run("list_bookmarks", paths(&[pool.clone()]), || self.inner.list_bookmarks(pool))
+    }
+
+    fn list_volumes<N: Into<PathBuf>>(&self, pool: N) -> zfs::Result<Vec<PathBuf>> {
+        let pool = pool.into();
+        self.run("list_volumes", paths(&[pool.clone()]), || self.inner.list_volumes(pool))
+    }
+
+    fn read_properties<N: Into<PathBuf>>(&self, path: N) -> zfs::Result<Properties> {
+        let path = path.into();
+        self.run("read_properties", paths(&[path.clone()]), || self.inner.read_properties(path))
+    }
+
+    fn allow<N: Into<PathBuf>>(&self, dataset: N, delegation: &Delegation) -> zfs::Result<()> {
+        let dataset = dataset.into();
+        self.run("allow", paths(&[dataset.clone()]), || self.inner.allow(dataset, delegation))
+    }
+
+    fn unallow<N: Into<PathBuf>>(&self, dataset: N, delegation: &Delegation) -> zfs::Result<()> {
+        let dataset = dataset.into();
+        self.run("unallow", paths(&[dataset.clone()]), || self.inner.unallow(dataset, delegation))
+    }
+
+    fn permissions<N: Into<PathBuf>>(&self, dataset: N) -> zfs::Result<Vec<Delegation>> {
+        let dataset = dataset.into();
+        self.run("permissions", paths(&[dataset.clone()]), || self.inner.permissions(dataset))
+    }
+
+    fn send_full<N: Into<PathBuf>, FD: AsRawFd>(
+        &self,
+        path: N,
+        fd: FD,
+        flags: SendFlags,
+    ) -> zfs::Result<()> {
+        let path = path.into();
+        self.run("send_full", paths(&[path.clone()]), || self.inner.send_full(path, fd, flags))
+    }
+
+    fn send_incremental<N: Into<PathBuf>, F: Into<PathBuf>, FD: AsRawFd>(
+        &self,
+        path: N,
+        from: F,
+        fd: FD,
+        flags: SendFlags,
+    ) -> zfs::Result<()> {
+        let path = path.into();
+        let from = from.into();
+        self.run("send_incremental", paths(&[path.clone(), from.clone()]), || {
+            self.inner.send_incremental(path, from, fd, flags)
+        })
+    }
+
+    fn send_space<N: Into<PathBuf>>(
+        &self,
+        path: N,
+        from: Option<PathBuf>,
+        flags: SendFlags,
+    ) -> zfs::Result<u64> {
+        let path = path.into();
+        let mut targets = vec![path.clone()];
+        targets.extend(from.clone());
+        self.run("send_space", paths(&targets), || self.inner.send_space(path, from, flags))
+    }
+
+    fn receive<N: Into<PathBuf>, FD: AsRawFd>(
+        &self,
+        snapshot: N,
+        fd: FD,
+        force: bool,
+    ) -> zfs::Result<()> {
+        let snapshot = snapshot.into();
+        self.run("receive", paths(&[snapshot.clone()]), || self.inner.receive(snapshot, fd, force))
+    }
+
+    fn mount<N: Into<PathBuf>>(&self, name: N) -> zfs::Result<()> {
+        let name = name.into();
+        self.run("mount", paths(&[name.clone()]), || self.inner.mount(name))
+    }
+
+    fn unmount<N: Into<PathBuf>>(&self, name: N) -> zfs::Result<()> {
+        let name = name.into();
+        self.run("unmount", paths(&[name.clone()]), || self.inner.unmount(name))
+    }
+
+    fn rename<N: Into<PathBuf>, M: Into<PathBuf>>(
+        &self,
+        name: N,
+        new_name: M,
+        options: RenameOptions,
+    ) -> zfs::Result<()> {
+        let name = name.into();
+        let new_name = new_name.into();
+        self.run("rename", paths(&[name.clone(), new_name.clone()]), || {
+            self.inner.rename(name, new_name, options)
+        })
+    }
+
+    fn clone_snapshot<N: Into<PathBuf>, M: Into<PathBuf>>(
+        &self,
+        snapshot: N,
+        target: M,
+        properties: Option<HashMap<String, String>>,
+    ) -> zfs::Result<()> {
+        let snapshot = snapshot.into();
+        let target = target.into();
+        self.run("clone_snapshot", paths(&[snapshot.clone(), target.clone()]), || {
+            self.inner.clone_snapshot(snapshot, target, properties)
+        })
+    }
+
+    fn promote<N: Into<PathBuf>>(&self, name: N) -> zfs::Result<()> {
+        let name = name.into();
+        self.run("promote", paths(&[name.clone()]), || self.inner.promote(name))
+    }
+
+    fn rollback<N: Into<PathBuf>>(&self, name: N, destroy_newer: bool) -> zfs::Result<PathBuf> {
+        let name = name.into();
+        self.run("rollback", paths(&[name.clone()]), || self.inner.rollback(name, destroy_newer))
+    }
+
+    fn hold<N: Into<PathBuf>>(
+        &self,
+        snapshot: N,
+        tag: &str,
+        cleanup_fd: Option<RawFd>,
+    ) -> zfs::Result<()> {
+        let snapshot = snapshot.into();
+        self.run("hold", paths(&[snapshot.clone()]), || self.inner.hold(snapshot, tag, cleanup_fd))
+    }
+
+    fn release<N: Into<PathBuf>>(&self, snapshot: N, tag: &str) -> zfs::Result<()> {
+        let snapshot = snapshot.into();
+        self.run("release", paths(&[snapshot.clone()]), || self.inner.release(snapshot, tag))
+    }
+
+    fn holds<N: Into<PathBuf>>(&self, snapshot: N) -> zfs::Result<Vec<Hold>> {
+        let snapshot = snapshot.into();
+        self.run("holds", paths(&[snapshot.clone()]), || self.inner.holds(snapshot))
+    }
+
+    fn set_property<N: Into<PathBuf>>(&self, name: N, key: &str, value: &str) -> zfs::Result<()> {
+        let name = name.into();
+        self.run("set_property", paths(&[name.clone()]), || {
+            self.inner.set_property(name, key, value)
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct Recorder {
+        calls: Mutex<Vec<String>>,
+    }
+
+    impl Middleware for Recorder {
+        fn before(&self, op: &Operation) -> Result<(), String> {
+            self.calls.lock().unwrap().push(format!("before {}", op.name()));
+            if op.name() == "destroy" {
+                return Err(String::from("no"));
+            }
+            Ok(())
+        }
+
+        fn after(&self, op: &Operation, _duration: Duration, error: Option<ErrorKind>) {
+            self.calls.lock().unwrap().push(format!("after {} {:?}", op.name(), error));
+        }
+    }
+
+    struct Fake;
+
+    impl ZfsEngine for Fake {
+        fn exists<N: Into<PathBuf>>(&self, name: N) -> zfs::Result<bool> {
+            Ok(name.into() == PathBuf::from("tank/a"))
+        }
+    }
+
+    #[test]
+    fn hooks() {
+        let recorder = Arc::new(Recorder::default());
+        let mut engine = MiddlewareEngine::new(Fake);
+        engine.register(recorder.clone());
+
+        assert!(engine.exists("tank/a").unwrap());
+        let err = engine.destroy("tank/a").unwrap_err();
+        assert_eq!(zfs::ErrorKind::Denied, err.kind());
+        let err = engine.mount("tank/a").unwrap_err();
+        assert_eq!(zfs::ErrorKind::Unimplemented, err.kind());
+
+        let expected = vec![
+            "before exists",
+            "after exists None",
+            "before destroy",
+            "before mount",
+            "after mount Some(Unimplemented)",
+        ];
+        assert_eq!(expected, *recorder.calls.lock().unwrap());
+    }
+
+    #[test]
+    fn targets() {
+        assert_eq!(vec![String::from("tank")], pool("tank"));
+        let snapshots = [PathBuf::from("tank/a@1"), PathBuf::from("tank/b@1")];
+        assert_eq!(vec!["tank/a@1", "tank/b@1"], paths(&snapshots));
+    }
+}
@@ -0,0 +1,388 @@
+//! Validated names of pools, datasets, snapshots and bookmarks.
+//!
+//! Engines take names as strings and paths, so a malformed name only surfaces when the command
+//! runs, usually as an error that doesn't say what was wrong with it. Types here check names when
+//! they are built, with the same rules as ZFS itself, and can be passed to every engine method as
+//! they are: they convert into `PathBuf` for [`ZfsEngine`](../zfs/trait.ZfsEngine.html) and
+//! implement `AsRef<str>` for [`ZpoolEngine`](../zpool/trait.ZpoolEngine.html). Engines still take
+//! plain strings and paths too, and those are not checked up front.
+//!
+//! Engine methods don't take `impl Into<DatasetName>`: turning a string into a name can fail, and
+//! `Into` has no way to report that short of panicking. Changing every engine signature to
+//! `TryInto` would break every caller for a check they can already opt into by building a name
+//! first.
+//!
+//! ### Usage
+//! ```rust
+//! use libzetta::names::{DatasetName, PoolName, SnapshotName};
+//!
+//! let pool = PoolName::new("tank").unwrap();
+//! let home = pool.join("usr/home").unwrap();
+//! assert_eq!("tank/usr/home", home.as_str());
+//! assert_eq!(vec!["tank", "usr", "home"], home.components().collect::<Vec<_>>());
+//! assert_eq!(Some(DatasetName::new("tank/usr").unwrap()), home.parent());
+//!
+//! let snapshot = home.snapshot("today").unwrap();
+//! assert_eq!("today", snapshot.name());
+//! assert_eq!(pool, snapshot.pool());
+//! assert!("tank/usr@".parse::<SnapshotName>().is_err());
+//! assert!(PoolName::new("mirror").is_err());
+//! ```
+use std::{convert::TryFrom,
+          ffi::OsStr,
+          fmt,
+          path::{Path, PathBuf},
+          str::FromStr};
+
+use crate::zfs::{validators, ValidationError, DATASET_NAME_MAX_LENGTH};
+
+quick_error! {
+    /// Name that ZFS would reject.
+    #[derive(Debug, Clone, PartialEq, Eq)]
+    pub enum NameError {
+        /// Name is malformed.
+        InvalidName(name: String, reason: &'static str) {
+            display("invalid name {:?}: {}", name, reason)
+        }
+    }
+}
+
+/// Pool names ZFS keeps for itself: anything that starts with one of the prefixes and the exact
+/// names.
+const RESERVED_PREFIXES: [&str; 4] = ["mirror", "raidz", "draid", "spare"];
+const RESERVED_NAMES: [&str; 1] = ["log"];
+
+fn invalid<T>(name: &str, reason: &'static str) -> Result<T, NameError> {
+    Err(NameError::InvalidName(name.to_string(), reason))
+}
+
+/// Check one component of a name: a pool name, a dataset name between slashes, or a snapshot or
+/// bookmark name.
+fn check_component(name: &str, component: &str) -> Result<(), NameError> {
+    let valid_char = |c: char| c.is_ascii_alphanumeric() || "_-.: ".contains(c);
+    if component.is_empty() {
+        invalid(name, "empty component")
+    } else if component == "." || component == ".." {
+        invalid(name, "component can't be '.' or '..'")
+    } else if !component.chars().all(valid_char) {
+        invalid(name, "invalid character")
+    } else {
+        Ok(())
+    }
+}
+
+fn check_length(name: &str) -> Result<(), NameError> {
+    if name.len() > DATASET_NAME_MAX_LENGTH {
+        invalid(name, "name is too long")
+    } else {
+        Ok(())
+    }
+}
+
+fn check_pool(name: &str, pool: &str) -> Result<(), NameError> {
+    check_component(name, pool)?;
+    if !pool.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        return invalid(name, "pool name must begin with a letter");
+    }
+    let reserved_prefix = RESERVED_PREFIXES.iter().any(|prefix| pool.starts_with(prefix));
+    if reserved_prefix || RESERVED_NAMES.contains(&pool) {
+        return invalid(name, "pool name is reserved");
+    }
+    Ok(())
+}
+
+#[allow(clippy::wildcard_enum_match_arm)]
+fn check_dataset(name: &str, dataset: &str) -> Result<(), NameError> {
+    if dataset.contains(|c| c == '@' || c == '#') {
+        return invalid(name, "dataset name can't contain '@' or '#'");
+    }
+    // Pool alone is a valid dataset, but not for the validator of `zfs`.
+    if dataset.contains('/') {
+        validators::validate_name(dataset).or_else(|e| match e {
+            ValidationError::MissingName(_) => invalid(name, "empty component"),
+            ValidationError::MissingPool(_) => invalid(name, "missing pool name"),
+            ValidationError::NameTooLong(_) => invalid(name, "name is too long"),
+            _ => invalid(name, "invalid dataset name"),
+        })?;
+    }
+    let mut components = dataset.split('/');
+    check_pool(name, components.next().unwrap_or_default())?;
+    components.try_for_each(|component| check_component(name, component))
+}
+
+/// Check name made of a dataset and a snapshot or bookmark name.
+fn check_child(name: &str, separator: char) -> Result<(), NameError> {
+    check_length(name)?;
+    let mut parts = name.splitn(2, separator);
+    let dataset = parts.next().unwrap_or_default();
+    match parts.next() {
+        Some(child) => {
+            check_dataset(name, dataset)?;
+            check_component(name, child)
+        },
+        None if separator == '@' => invalid(name, "missing '@'"),
+        None => invalid(name, "missing '#'"),
+    }
+}
+
+macro_rules! impl_name {
+    ($name:ident) => {
+        impl $name {
+            /// Name as a string.
+            pub fn as_str(&self) -> &str { &self.0 }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result { f.write_str(&self.0) }
+        }
+
+        impl AsRef<str> for $name {
+            fn as_ref(&self) -> &str { &self.0 }
+        }
+
+        impl AsRef<OsStr> for $name {
+            fn as_ref(&self) -> &OsStr { OsStr::new(&self.0) }
+        }
+
+        impl AsRef<Path> for $name {
+            fn as_ref(&self) -> &Path { Path::new(&self.0) }
+        }
+
+        impl From<$name> for PathBuf {
+            fn from(name: $name) -> PathBuf { PathBuf::from(name.0) }
+        }
+
+        impl From<$name> for String {
+            fn from(name: $name) -> String { name.0 }
+        }
+
+        impl FromStr for $name {
+            type Err = NameError;
+
+            fn from_str(name: &str) -> Result<$name, NameError> { $name::new(name) }
+        }
+
+        impl TryFrom<&str> for $name {
+            type Error = NameError;
+
+            fn try_from(name: &str) -> Result<$name, NameError> { $name::new(name) }
+        }
+
+        impl TryFrom<String> for $name {
+            type Error = NameError;
+
+            fn try_from(name: String) -> Result<$name, NameError> { $name::new(name) }
+        }
+
+        impl TryFrom<&Path> for $name {
+            type Error = NameError;
+
+            fn try_from(name: &Path) -> Result<$name, NameError> {
+                $name::new(name.to_string_lossy())
+            }
+        }
+
+        impl TryFrom<PathBuf> for $name {
+            type Error = NameError;
+
+            fn try_from(name: PathBuf) -> Result<$name, NameError> {
+                $name::try_from(name.as_path())
+            }
+        }
+    };
+}
+
+/// Name of a pool, e.g. `tank`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct PoolName(String);
+
+impl PoolName {
+    /// Check and wrap the name.
+    ///
+    /// * `name` - Name of the pool.
+    pub fn new<N: Into<String>>(name: N) -> Result<PoolName, NameError> {
+        let name = name.into();
+        check_length(&name)?;
+        check_pool(&name, &name)?;
+        Ok(PoolName(name))
+    }
+
+    /// Root dataset of the pool. It has the same name as the pool.
+    pub fn root(&self) -> DatasetName { DatasetName(self.0.clone()) }
+
+    /// Dataset under the root dataset of the pool.
+    ///
+    /// * `path` - One or more components separated with `/`, e.g. `usr/home`.
+    pub fn join(&self, path: &str) -> Result<DatasetName, NameError> { self.root().join(path) }
+}
+
+impl_name!(PoolName);
+
+/// Name of a filesystem or a volume, e.g. `tank/usr/home`. Root dataset of a pool has the same
+/// name as the pool.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct DatasetName(String);
+
+impl DatasetName {
+    /// Check and wrap the name.
+    ///
+    /// * `name` - Name of the dataset, starting with the pool.
+    pub fn new<N: Into<String>>(name: N) -> Result<DatasetName, NameError> {
+        let name = name.into();
+        check_length(&name)?;
+        check_dataset(&name, &name)?;
+        Ok(DatasetName(name))
+    }
+
+    /// Pool the dataset is in.
+    pub fn pool(&self) -> PoolName {
+        PoolName(self.0.split('/').next().unwrap_or_default().to_string())
+    }
+
+    /// Components of the name, pool first.
+    pub fn components(&self) -> std::str::Split<'_, char> { self.0.split('/') }
+
+    /// Last component of the name.
+    pub fn basename(&self) -> &str { self.0.rsplit('/').next().unwrap_or_default() }
+
+    /// Parent dataset. `None` for the root dataset of a pool.
+    pub fn parent(&self) -> Option<DatasetName> {
+        self.0.rsplitn(2, '/').nth(1).map(|parent| DatasetName(parent.to_string()))
+    }
+
+    /// Whether this is the root dataset of a pool.
+    pub fn is_root(&self) -> bool { !self.0.contains('/') }
+
+    /// Dataset under this one.
+    ///
+    /// * `path` - One or more components separated with `/`, e.g. `usr/home`.
+    pub fn join(&self, path: &str) -> Result<DatasetName, NameError> {
+        DatasetName::new(format!("{}/{}", self.0, path))
+    }
+
+    /// Snapshot of this dataset.
+    ///
+    /// * `name` - Name of the snapshot, without the dataset part.
+    pub fn snapshot(&self, name: &str) -> Result<SnapshotName, NameError> {
+        SnapshotName::new(format!("{}@{}", self.0, name))
+    }
+
+    /// Bookmark of this dataset.
+    ///
+    /// * `name` - Name of the bookmark, without the dataset part.
+    pub fn bookmark(&self, name: &str) -> Result<BookmarkName, NameError> {
+        BookmarkName::new(format!("{}#{}", self.0, name))
+    }
+}
+
+impl_name!(DatasetName);
+
+impl From<PoolName> for DatasetName {
+    fn from(pool: PoolName) -> DatasetName { DatasetName(pool.0) }
+}
+
+/// Name of a snapshot, e.g. `tank/usr/home@today`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct SnapshotName(String);
+
+impl SnapshotName {
+    /// Check and wrap the name.
+    ///
+    /// * `name` - Full name of the snapshot, `dataset@snapshot`.
+    pub fn new<N: Into<String>>(name: N) -> Result<SnapshotName, NameError> {
+        let name = name.into();
+        check_child(&name, '@')?;
+        Ok(SnapshotName(name))
+    }
+
+    /// Dataset the snapshot is of.
+    pub fn dataset(&self) -> DatasetName {
+        DatasetName(self.0.split('@').next().unwrap_or_default().to_string())
+    }
+
+    /// Pool the snapshot is in.
+    pub fn pool(&self) -> PoolName { self.dataset().pool() }
+
+    /// Name of the snapshot without the dataset part.
+    pub fn name(&self) -> &str { self.0.rsplit('@').next().unwrap_or_default() }
+}
+
+impl_name!(SnapshotName);
+
+/// Name of a bookmark, e.g. `tank/usr/home#today`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct BookmarkName(String);
+
+impl BookmarkName {
+    /// Check and wrap the name.
+    ///
+    /// * `name` - Full name of the bookmark, `dataset#bookmark`.
+    pub fn new<N: Into<String>>(name: N) -> Result<BookmarkName, NameError> {
+        let name = name.into();
+        check_child(&name, '#')?;
+        Ok(BookmarkName(name))
+    }
+
+    /// Dataset the bookmark is of.
+    pub fn dataset(&self) -> DatasetName {
+        DatasetName(self.0.split('#').next().unwrap_or_default().to_string())
+    }
+
+    /// Pool the bookmark is in.
+    pub fn pool(&self) -> PoolName { self.dataset().pool() }
+
+    /// Name of the bookmark without the dataset part.
+    pub fn name(&self) -> &str { self.0.rsplit('#').next().unwrap_or_default() }
+}
+
+impl_name!(BookmarkName);
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn pools() {
+        assert_eq!("tank", PoolName::new("tank").unwrap().as_str());
+        assert!(PoolName::new("back-up_2.0:a b").is_ok());
+        let invalid = ["", "1tank", "tank/usr", "mirror0", "raidz", "draid2", "spares", "log"];
+        for name in invalid.iter().chain(&["t@nk", "pö"]) {
+            assert!(PoolName::new(*name).is_err(), "{}", name);
+        }
+        assert!(PoolName::new("logs").is_ok());
+        assert!(PoolName::new("x".repeat(255)).is_ok());
+        assert!(PoolName::new("x".repeat(256)).is_err());
+    }
+
+    #[test]
+    fn datasets() {
+        let home = DatasetName::new("tank/usr/home").unwrap();
+        assert_eq!(PoolName::new("tank").unwrap(), home.pool());
+        assert_eq!("home", home.basename());
+        assert_eq!(Some(DatasetName::new("tank/usr").unwrap()), home.parent());
+        assert_eq!(None, home.pool().root().parent());
+        assert!(home.pool().root().is_root());
+        assert_eq!("tank/usr/home/me", home.join("me").unwrap().as_str());
+        assert!(home.join("../etc").is_err());
+        assert_eq!(PathBuf::from("tank/usr/home"), PathBuf::from(&home));
+        for name in &["tank/", "tank//usr", "/tank", "tank/usr@snap", "tank/a#b", "tank/./a"] {
+            let err = DatasetName::new(*name).unwrap_err();
+            assert!(err.to_string().starts_with("invalid name"), "{}", name);
+        }
+    }
+
+    #[test]
+    fn snapshots_and_bookmarks() {
+        let snapshot: SnapshotName = "tank/usr@today".parse().unwrap();
+        assert_eq!("today", snapshot.name());
+        assert_eq!("tank/usr", snapshot.dataset().as_str());
+        assert_eq!("tank", snapshot.pool().as_str());
+        for name in &["tank/usr", "tank/usr@", "tank/usr@a@b", "@today", "tank/usr@a/b"] {
+            assert!(SnapshotName::new(*name).is_err(), "{}", name);
+        }
+        let bookmark = BookmarkName::try_from(Path::new("tank/usr#today")).unwrap();
+        assert_eq!("today", bookmark.name());
+        assert_eq!("tank/usr", bookmark.dataset().as_str());
+        assert!(BookmarkName::new("tank/usr@today").is_err());
+    }
+}
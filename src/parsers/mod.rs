@@ -1,63 +1,64 @@
-use pest_derive::Parser;
+use crate::{utils::ParseMode,
+            zpool::{Zpool, ZpoolResult}};
 
+mod status;
 pub mod zfs;
 pub use zfs::{Rule as ZfsRule, ZfsParser};
 
-#[derive(Parser)]
-#[grammar = "parsers/stdout.pest"] // relative to src
-pub struct StdoutParser;
+/// Parse output of `zpool status` or `zpool import` into pools. Parsing stops at the first pool
+/// that isn't recognized, everything after it is skipped.
+pub fn parse_zpools(stdout: &str) -> ZpoolResult<Vec<Zpool>> {
+    parse_zpools_with(stdout, ParseMode::Tolerant)
+}
+
+/// Same as `parse_zpools`, but in strict mode anything that is left after the last recognized
+/// pool is an error with the line parser failed at.
+pub fn parse_zpools_with(stdout: &str, mode: ParseMode) -> ZpoolResult<Vec<Zpool>> {
+    status::parse_zpools(stdout, mode)
+}
 
 #[cfg(test)]
 mod test {
     use std::path::PathBuf;
 
-    use pest::{consumes_to, parses_to, Parser};
-
     use crate::{parsers::*,
-                zpool::{vdev::{CreateVdevRequest, ErrorStatistics},
-                        CreateZpoolRequestBuilder, Health, Reason, Zpool}};
+                utils::ParseMode,
+                zpool::{vdev::{CreateVdevRequest, Disk, ErrorStatistics, VdevType},
+                        CreateZpoolRequestBuilder, DataError, Health, MaintenanceState, Reason,
+                        RemovalState, ScanKind, ScanStatus, ScrubState}};
 
     #[test]
     fn test_issue_78_minimal() {
-        let line = "  scan: resilver in progress since Tue Aug 13 23:03:11 2019\n\t42.5K scanned at 42.5K/s, 80K issued at 80K/s, 83K total\n\t512 resilvered, 96.39% done, no estimated completion time\n";
-        let mut pairs =
-            StdoutParser::parse(Rule::scan_line, line).unwrap_or_else(|e| panic!("{}", e));
-        let pair = pairs.next().unwrap();
-        let inner = pair.into_inner().next().unwrap().as_span();
-        let span = inner.as_str();
-        let expected = "resilver in progress since Tue Aug 13 23:03:11 2019\n\t42.5K scanned at 42.5K/s, 80K issued at 80K/s, 83K total\n\t512 resilvered, 96.39% done, no estimated completion time\n";
-        assert_eq!(expected, span);
-    }
-
-    #[test]
-    fn test_action_single_line() {
-        let one_line = " action: The pool can be imported using its name or numeric identifier.\n";
-        parses_to! {
-            parser: StdoutParser,
-            input: one_line,
-            rule: Rule::action,
-            tokens: [
-                action(0, 72, [
-                       multi_line_text(9,72)
-                ])
-            ]
+        let stdout = "  pool: tank\n state: ONLINE\n  scan: resilver in progress since Tue Aug 13 23:03:11 2019\n\t42.5K scanned at 42.5K/s, 80K issued at 80K/s, 83K total\n\t512 resilvered, 96.39% done, no estimated completion time\nconfig:\n\n\tNAME        STATE     READ WRITE CKSUM\n\ttank        ONLINE       0     0     0\n\t  sda       ONLINE       0     0     0\n\nerrors: No known data errors\n";
+        let zpools = parse_zpools_with(stdout, ParseMode::Strict).unwrap();
+        match zpools[0].scan() {
+            Some(ScanStatus::InProgress(progress)) => {
+                assert_eq!(&ScanKind::Resilver, progress.kind());
+                assert_eq!(&Some(83 * 1024), progress.total());
+                assert_eq!(&Some(512), progress.repaired());
+            },
+            other => panic!("{:?}", other),
         }
     }
 
     #[test]
     fn test_action_multiline() {
-        let two_lines = " action: The pool cannot be imported. Attach the missing\n        \
-                         devices and try again.\n";
-        parses_to! {
-            parser: StdoutParser,
-            input: two_lines,
-            rule: Rule::action,
-            tokens: [
-                action(0, 88, [
-                       multi_line_text(9,88)
-                ])
-            ]
-        }
+        let stdout = r#"   pool: tank
+     id: 3364973538352047455
+  state: UNAVAIL
+ action: The pool cannot be imported. Attach the missing
+        devices and try again.
+ config:
+
+        tank          UNAVAIL  insufficient replicas
+          sda         UNAVAIL  cannot open
+"#;
+        let zpools = parse_zpools_with(stdout, ParseMode::Strict).unwrap();
+        let expected = "The pool cannot be imported. Attach the missing\n        devices and try \
+                        again.\n";
+        assert_eq!(&Some(String::from(expected)), zpools[0].action());
+        assert_eq!(&Some(Reason::InsufficientReplicas), zpools[0].reason());
+        assert_eq!(&Some(Reason::CannotOpen), zpools[0].vdevs()[0].disks()[0].reason());
     }
 
     #[test]
@@ -73,42 +74,16 @@ mod test {
           /vdevs/import/vdev1  ONLINE
           "#;
 
-        // let pairs = StdoutParser::parse(Rule::zpool_import,
-        // stdout_valid_two_disks).unwrap_or_else(|e| panic!("{}", e));
-        // println!("{:#?}", pairs);
-        parses_to! {
-            parser: StdoutParser,
-            input: stdout_valid_two_disks,
-            rule: Rule::zpool,
-            tokens: [
-                zpool(0, 258, [
-                    pool_name(0, 17, [name(6,16)]),
-                    pool_id(17, 46, [digits(26,45)]),
-                    state(46, 62, [state_enum(55, 61)]),
-                    action(62, 134, [multi_line_text(71, 134)]),
-                    config(134, 143),
-                    pool_line(144, 182, [name(152, 162), state_enum(175, 181)]),
-                    vdevs(182, 258, [
-                        naked_vdev(182, 220, [
-                            disk_line(182, 220, [
-                                path(192, 211),
-                                state_enum(213, 219)
-                            ])
-                        ]),
-                        naked_vdev(220, 258, [
-                            disk_line(220, 258, [
-                                path(230, 249),
-                                state_enum(251, 257)
-                            ])
-                        ])
-                    ])
-                ])
-            ]
-        }
-        let mut pairs = StdoutParser::parse(Rule::zpool, stdout_valid_two_disks)
-            .unwrap_or_else(|e| panic!("{}", e));
-        let pair = pairs.next().unwrap();
-        Zpool::from_pest_pair(pair);
+        let zpools = parse_zpools_with(stdout_valid_two_disks, ParseMode::Strict).unwrap();
+        assert_eq!(1, zpools.len());
+        let zpool = &zpools[0];
+        assert_eq!("naked_test", zpool.name());
+        assert_eq!(&Some(3364973538352047455), zpool.id());
+        assert_eq!(&Health::Online, zpool.health());
+        let action = "The pool can be imported using its name or numeric identifier.\n";
+        assert_eq!(&Some(String::from(action)), zpool.action());
+        assert_eq!(2, zpool.vdevs().len());
+        assert_eq!(&PathBuf::from("/vdevs/import/vdev1"), zpool.vdevs()[1].disks()[0].path());
     }
 
     #[test]
@@ -129,10 +104,8 @@ mod test {
         exact configuration cannot be determined.
         "#;
 
-        let mut pairs = StdoutParser::parse(Rule::zpool, stdout_invalid_two_disks)
-            .unwrap_or_else(|e| panic!("{}", e));
-        let pair = pairs.next().unwrap();
-        let zpool = Zpool::from_pest_pair(pair);
+        let zpools = parse_zpools(stdout_invalid_two_disks).unwrap();
+        let zpool = &zpools[0];
 
         assert_eq!(&Health::Unavailable, zpool.health());
 
@@ -144,7 +117,7 @@ mod test {
             zpool.action()
         );
 
-        assert_eq!(&Some(Reason::Other(String::from("missing device"))), zpool.reason());
+        assert_eq!(&Some(Reason::MissingDevice), zpool.reason());
 
         let vdev = &zpool.vdevs()[0];
 
@@ -178,8 +151,8 @@ mod test {
           /vdevs/import/vdev1  ONLINE
           "#;
 
-        let pairs = StdoutParser::parse(Rule::zpools, stdout).unwrap_or_else(|e| panic!("{}", e));
-        let mut zpools = pairs.map(|pair| Zpool::from_pest_pair(pair));
+        let zpools = parse_zpools_with(stdout, ParseMode::Strict).unwrap();
+        let mut zpools = zpools.into_iter();
 
         let first = zpools.next().unwrap();
         assert_eq!(first.name(), &String::from("naked_test"));
@@ -191,6 +164,28 @@ mod test {
         assert!(none.is_none());
     }
 
+    #[test]
+    fn test_destroyed_import() {
+        let stdout = r#"   pool: destroyed_test
+     id: 3364973538352047455
+  state: ONLINE (DESTROYED)
+ action: The pool can be imported using its name or numeric identifier.
+ config:
+
+        destroyed_test         ONLINE
+          /vdevs/import/vdev0  ONLINE
+"#;
+
+        let zpools = parse_zpools_with(stdout, ParseMode::Strict).unwrap();
+        assert_eq!(1, zpools.len());
+        assert_eq!("destroyed_test", zpools[0].name());
+        assert_eq!(&Health::Online, zpools[0].health());
+        assert!(*zpools[0].destroyed());
+
+        let stdout = stdout.replace(" (DESTROYED)", "");
+        assert!(!*parse_zpools_with(&stdout, ParseMode::Strict).unwrap()[0].destroyed());
+    }
+
     #[test]
     fn test_status_scrub() {
         let stdout = r#"  pool: bootpool
@@ -226,12 +221,16 @@ config:
 errors: Pretend this is actual error
 "#;
 
-        let pairs = StdoutParser::parse(Rule::zpools, stdout).unwrap_or_else(|e| panic!("{}", e));
-
-        let mut zpools = pairs.map(|pair| Zpool::from_pest_pair(pair));
+        let zpools = parse_zpools_with(stdout, ParseMode::Strict).unwrap();
+        let mut zpools = zpools.into_iter();
         let first = zpools.next().unwrap();
         assert_eq!(first.name(), &String::from("bootpool"));
         assert!(first.errors().is_none());
+        assert_eq!(Some(ScrubState::Finished), first.scrub());
+        match first.scan() {
+            Some(ScanStatus::Finished(summary)) => assert_eq!(&Some(0), summary.errors()),
+            other => panic!("{:?}", other),
+        }
         let vdev = &first.vdevs()[0];
         let vdev_expected = CreateVdevRequest::SingleDisk(std::path::PathBuf::from("nvd0p2"));
         assert_eq!(vdev, &vdev_expected);
@@ -245,6 +244,259 @@ errors: Pretend this is actual error
         assert!(none.is_none());
     }
 
+    #[test]
+    fn test_status_spares() {
+        let stdout = r#"  pool: tank
+ state: ONLINE
+config:
+
+        NAME              STATE     READ WRITE CKSUM
+        tank              ONLINE       0     0     0
+          mirror-0        ONLINE       0     0     0
+            /vdevs/vdev0  ONLINE       0     0     0
+            /vdevs/vdev1  ONLINE       0     0     0
+        spares
+          /vdevs/vdev2    AVAIL
+          /vdevs/vdev3    AVAIL
+
+errors: No known data errors
+"#;
+
+        let zpools = parse_zpools_with(stdout, ParseMode::Strict).unwrap();
+        let spares = zpools[0].spares();
+        assert_eq!(2, spares.len());
+        assert_eq!(&PathBuf::from("/vdevs/vdev2"), spares[0].path());
+        assert_eq!(&Health::Available, spares[1].health());
+
+        let request = CreateZpoolRequestBuilder::default()
+            .name("tank")
+            .vdev(CreateVdevRequest::Mirror(vec!["/vdevs/vdev0".into(), "/vdevs/vdev1".into()]))
+            .spares(vec!["/vdevs/vdev2".into(), "/vdevs/vdev3".into()])
+            .build()
+            .unwrap();
+        assert_eq!(zpools[0], request);
+    }
+
+    #[test]
+    fn test_status_spares_in_use() {
+        let stdout = r#"  pool: tank
+ state: DEGRADED
+config:
+
+        NAME                STATE     READ WRITE CKSUM
+        tank                DEGRADED     0     0     0
+          mirror-0          DEGRADED     0     0     0
+            spare-0         DEGRADED     0     0     0
+              /vdevs/vdev0  FAULTED      0    14     0  too many errors
+              /vdevs/vdev3  ONLINE       0     0     0
+            /vdevs/vdev1    ONLINE       0     0     0
+          spare-1           DEGRADED     0     0     0
+            /vdevs/vdev2    REMOVED      0     0     0
+            /vdevs/vdev4    ONLINE       0     0     0
+        spares
+          /vdevs/vdev3      INUSE     currently in use
+          /vdevs/vdev4      INUSE     currently in use
+          /vdevs/vdev5      FAULTED   corrupted data
+
+errors: No known data errors
+"#;
+
+        let zpools = parse_zpools_with(stdout, ParseMode::Strict).unwrap();
+        let zpool = &zpools[0];
+        assert_eq!(2, zpool.vdevs().len());
+
+        let mirror = &zpool.vdevs()[0];
+        assert_eq!(&VdevType::Mirror, mirror.kind());
+        assert_eq!(2, mirror.disks().len());
+        let node = &mirror.disks()[0];
+        assert!(node.is_spare_node());
+        assert_eq!(&PathBuf::from("spare-0"), node.path());
+        assert_eq!(&Health::Degraded, node.health());
+        assert_eq!(2, node.children().len());
+        assert_eq!(&Health::Faulted, node.children()[0].health());
+        assert_eq!(14, node.children()[0].error_statistics().write);
+        assert_eq!(&PathBuf::from("/vdevs/vdev3"), node.children()[1].path());
+        assert!(!mirror.disks()[1].is_spare_node());
+        assert!(mirror.disks()[1].children().is_empty());
+
+        let single = &zpool.vdevs()[1];
+        assert_eq!(&VdevType::SingleDisk, single.kind());
+        assert_eq!(1, single.disks().len());
+        assert!(single.disks()[0].is_spare_node());
+        assert_eq!(&Health::Removed, single.disks()[0].children()[0].health());
+
+        assert_eq!(3, zpool.spares().len());
+        assert_eq!(&Health::Faulted, zpool.spares()[2].health());
+        let in_use: Vec<&PathBuf> = zpool.spares_in_use().into_iter().map(Disk::path).collect();
+        assert_eq!(vec!["/vdevs/vdev3", "/vdevs/vdev4"], in_use);
+    }
+
+    #[test]
+    fn test_status_draid() {
+        let stdout = r#"  pool: tank
+ state: ONLINE
+config:
+
+        NAME                 STATE     READ WRITE CKSUM
+        tank                 ONLINE       0     0     0
+          draid1:2d:4c:1s-0  ONLINE       0     0     0
+            /vdevs/vdev0     ONLINE       0     0     0
+            /vdevs/vdev1     ONLINE       0     0     0
+            /vdevs/vdev2     ONLINE       0     0     0
+            /vdevs/vdev3     ONLINE       0     0     0
+        spares
+          draid1-0-0         AVAIL
+
+errors: No known data errors
+"#;
+
+        let zpools = parse_zpools_with(stdout, ParseMode::Strict).unwrap();
+        let vdev = &zpools[0].vdevs()[0];
+        let kind = VdevType::DRaid { parity: 1, data: 2, children: 4, spares: 1 };
+        assert_eq!(&kind, vdev.kind());
+        assert_eq!(4, vdev.disks().len());
+        assert!(zpools[0].spares()[0].is_distributed_spare());
+
+        let disks = (0..4).map(|n| PathBuf::from(format!("/vdevs/vdev{}", n))).collect();
+        let draid = CreateVdevRequest::DRaid { parity: 1, data: 2, spares: 1, children: disks };
+        assert_eq!(vdev, &draid);
+    }
+
+    #[test]
+    fn test_status_cache_errors() {
+        let stdout = r#"  pool: tank
+ state: ONLINE
+config:
+
+        NAME              STATE     READ WRITE CKSUM
+        tank              ONLINE       0     0     0
+          /vdevs/vdev0    ONLINE       0     0     0
+        cache
+          nvd0            ONLINE       3     0     7
+          nvd1            UNAVAIL      0     0     0  cannot open
+
+errors: No known data errors
+"#;
+
+        let zpools = parse_zpools_with(stdout, ParseMode::Strict).unwrap();
+        let caches = zpools[0].caches();
+        assert_eq!(2, caches.len());
+        let expected = ErrorStatistics { read: 3, write: 0, checksum: 7 };
+        assert_eq!(&expected, caches[0].error_statistics());
+        assert_eq!(&Health::Unavailable, caches[1].health());
+        assert_eq!(&Some(Reason::CannotOpen), caches[1].reason());
+    }
+
+    #[test]
+    fn test_status_device_errors() {
+        let stdout = r#"  pool: tank
+ state: ONLINE
+status: One or more devices has experienced an unrecoverable error.  An
+        attempt was made to correct the error.  Applications are unaffected.
+action: Determine if the device needs to be replaced, and clear the errors
+        using 'zpool clear' or replace the device with 'zpool replace'.
+config:
+
+        NAME              STATE     READ WRITE CKSUM
+        tank              ONLINE       0     0     0
+          mirror-0        ONLINE       0     0     0
+            /vdevs/vdev0  ONLINE       0     0  1.23K
+            /vdevs/vdev1  ONLINE       2     0     0
+          /vdevs/vdev2    ONLINE       0     0     0
+
+errors: No known data errors
+"#;
+
+        let zpools = parse_zpools_with(stdout, ParseMode::Strict).unwrap();
+        let zpool = &zpools[0];
+        assert_eq!(2, zpool.vdevs().len());
+        let mirror = &zpool.vdevs()[0];
+        assert!(!mirror.error_statistics().has_errors());
+        let expected = ErrorStatistics { read: 0, write: 0, checksum: 1260 };
+        assert_eq!(&expected, mirror.disks()[0].error_statistics());
+        assert_eq!(2, mirror.disks()[1].error_statistics().read);
+
+        let failing = zpool.disks_with_errors();
+        assert_eq!(2, failing.len());
+        assert_eq!(&PathBuf::from("/vdevs/vdev0"), failing[0].path());
+        assert_eq!(&PathBuf::from("/vdevs/vdev1"), failing[1].path());
+    }
+
+    #[test]
+    fn test_status_aux_states() {
+        let stdout = r#"  pool: tank
+ state: DEGRADED
+status: One or more devices are faulted in response to persistent errors.
+        Sufficient replicas exist for the pool to continue functioning in a
+        degraded state.
+action: Replace the faulted device, or use 'zpool clear' to mark the device
+        repaired.
+config:
+
+        NAME              STATE     READ WRITE CKSUM
+        tank              DEGRADED     0     0     0
+          raidz1-0        DEGRADED     0     0     0
+            /vdevs/vdev0  FAULTED     14     0     0  too many errors
+            /vdevs/vdev1  UNAVAIL      0     0     0  corrupted data
+            /vdevs/vdev2  ONLINE       0     0     0
+          mirror-1        DEGRADED     0     0     0
+            /vdevs/vdev3  SPLIT        0     0     0  split into new pool
+            /vdevs/vdev4  REMOVED      0     0     0
+            /vdevs/vdev5  OFFLINE      0     0     0  external device fault  (untrimmed)
+            /vdevs/vdev6  ONLINE       0     0     0
+
+errors: No known data errors
+"#;
+
+        let zpools = parse_zpools_with(stdout, ParseMode::Strict).unwrap();
+        let raidz = zpools[0].vdevs()[0].disks();
+        assert_eq!(&Health::Faulted, raidz[0].health());
+        assert_eq!(&Some(Reason::TooManyErrors), raidz[0].reason());
+        assert_eq!(&Health::Unavailable, raidz[1].health());
+        assert_eq!(&Some(Reason::CorruptedData), raidz[1].reason());
+        assert_eq!(&None, raidz[2].reason());
+
+        let mirror = zpools[0].vdevs()[1].disks();
+        assert_eq!(&Health::Split, mirror[0].health());
+        assert_eq!(&Some(Reason::SplitIntoNewPool), mirror[0].reason());
+        assert_eq!(&Health::Removed, mirror[1].health());
+        assert_eq!(&Health::Offline, mirror[2].health());
+        assert_eq!(&Some(Reason::ExternalFault), mirror[2].reason());
+        assert_eq!(&Some(MaintenanceState::Never), mirror[2].trim());
+    }
+
+    #[test]
+    fn test_status_data_errors() {
+        let stdout = r#"  pool: tank
+ state: ONLINE
+status: One or more devices has experienced an error resulting in data
+        corruption.  Applications may be affected.
+action: Restore the file in question if possible.  Otherwise restore the
+        entire pool from backup.
+  scan: scrub repaired 0B in 00:00:01 with 2 errors on Sun Jul 25 16:07:50 2021
+config:
+
+        NAME          STATE     READ WRITE CKSUM
+        tank          ONLINE       0     0     0
+          /vdevs/a    ONLINE       0     0     4
+
+errors: Permanent errors have been detected in the following files:
+
+        /tank/important file.txt
+        tank/fs@daily:/db.sqlite
+        <metadata>:<0x3f>
+
+"#;
+
+        let zpools = parse_zpools_with(stdout, ParseMode::Strict).unwrap();
+        let errors = zpools[0].data_errors();
+        assert_eq!(3, errors.len());
+        assert_eq!(DataError::File(PathBuf::from("/tank/important file.txt")), errors[0]);
+        assert_eq!(DataError::DatasetFile("tank/fs@daily".into(), "/db.sqlite".into()), errors[1]);
+        assert_eq!(DataError::Metadata(0x3f), errors[2]);
+        assert!(zpools[0].errors().as_ref().unwrap().starts_with("Permanent errors"));
+    }
+
     #[test]
     fn test_no_status_line_in_status() {
         let stdout = r#"  pool: tests-12167169401705616934
@@ -259,11 +511,12 @@ config:
 errors: No known data errors
 "#;
 
-        let pairs = StdoutParser::parse(Rule::zpools, stdout).unwrap_or_else(|e| panic!("{}", e));
-        let mut zpools = pairs.map(|pair| Zpool::from_pest_pair(pair));
+        let zpools = parse_zpools_with(stdout, ParseMode::Strict).unwrap();
+        let mut zpools = zpools.into_iter();
 
         let first = zpools.next().unwrap();
         assert_eq!(first.name(), &String::from("tests-12167169401705616934"));
+        assert_eq!(Some(ScrubState::NotRequested), first.scrub());
 
         let vdev = &first.vdevs()[0];
         let vdev_expected =
@@ -271,6 +524,34 @@ errors: No known data errors
         assert_eq!(&vdev_expected, vdev);
     }
 
+    #[test]
+    fn test_removal_in_status() {
+        let stdout = r#"  pool: tests-5503412806410244127
+ state: ONLINE
+  scan: none requested
+remove: Evacuation of /vdevs/vdev1 in progress since Tue Jul 27 10:00:00 2021
+        12.0M copied out of 48.0M at 6.00M/s, 25.00% done, 0h0m to go
+config:
+
+        NAME                         STATE     READ WRITE CKSUM
+        tests-5503412806410244127    ONLINE       0     0     0
+          /vdevs/vdev0               ONLINE       0     0     0
+          /vdevs/vdev1               ONLINE       0     0     0
+
+errors: No known data errors
+"#;
+
+        let zpools = parse_zpools_with(stdout, ParseMode::Strict).unwrap();
+        let mut zpools = zpools.into_iter();
+
+        let first = zpools.next().unwrap();
+        let removal = first.removal().as_ref().unwrap();
+        assert_eq!(&RemovalState::InProgress, removal.state());
+        assert_eq!("/vdevs/vdev1", removal.device());
+        assert_eq!(Some(25.0), removal.percent_done());
+        assert_eq!(2, first.vdevs().len());
+    }
+
     #[test]
     fn test_raided_vdev_status() {
         let stdout = r#"  pool: eden
@@ -304,10 +585,7 @@ config:
 
 errors: No known data errors
 "#;
-        let mut pairs =
-            StdoutParser::parse(Rule::zpool, stdout).unwrap_or_else(|e| panic!("{}", e));
-        let pair = pairs.next().unwrap();
-        let zpool = Zpool::from_pest_pair(pair);
+        let zpool = parse_zpools_with(stdout, ParseMode::Strict).unwrap().remove(0);
 
         let mirror_drives = vec![
             PathBuf::from("gptid/d27f8063-d17d-11e4-9eed-10c37b9d936f"),
@@ -354,10 +632,7 @@ config:
 errors: No known data errors
 "#;
         let expected_errors = ErrorStatistics { read: 1, write: 2, checksum: 3 };
-        let mut pairs =
-            StdoutParser::parse(Rule::zpool, stdout).unwrap_or_else(|e| panic!("{}", e));
-        let pair = pairs.next().unwrap();
-        let zpool = Zpool::from_pest_pair(pair);
+        let zpool = parse_zpools_with(stdout, ParseMode::Strict).unwrap().remove(0);
         assert_eq!(&Health::Degraded, zpool.health());
         assert_eq!(&expected_errors, zpool.error_statistics());
 
@@ -396,20 +671,14 @@ config:
 
 errors: No known data errors
 "#;
-        let mut pairs =
-            StdoutParser::parse(Rule::zpools, stdout).unwrap_or_else(|e| panic!("{}", e));
-        let pair = pairs.next().unwrap();
-        let zpool = Zpool::from_pest_pair(pair);
+        let zpool = parse_zpools_with(stdout, ParseMode::Strict).unwrap().remove(0);
         assert_eq!(&Health::Degraded, zpool.health());
     }
 
     #[test]
     fn test_tabs_instead_of_8_spaces() {
         let stdout = "  pool: tests-5810578167377116542\n state: DEGRADED\nstatus: One or more devices has been taken offline by the administrator.\n\tSufficient replicas exist for the pool to continue functioning in a\n\tdegraded state.\naction: Online the device using \'zpool online\' or replace the device with\n\t\'zpool replace\'.\n  scan: none requested\nconfig:\n\n\tNAME                      STATE     READ WRITE CKSUM\n\ttests-5810578167377116542  DEGRADED     0     0     0\n\t  mirror-0                DEGRADED     0     0     0\n\t    15825580777360392022  OFFLINE      0     0     0  was /vdevs/vdev3\n\t    /vdevs/vdev4          ONLINE       0     0     0\n\nerrors: No known data errors\n";
-        let mut pairs =
-            StdoutParser::parse(Rule::zpool, stdout).unwrap_or_else(|e| panic!("{}", e));
-        let pair = pairs.next().unwrap();
-        let zpool = Zpool::from_pest_pair(pair);
+        let zpool = parse_zpools_with(stdout, ParseMode::Strict).unwrap().remove(0);
         assert_eq!(&Health::Degraded, zpool.health());
     }
 
@@ -434,10 +703,7 @@ config:
 errors: No known data errors
         "#;
 
-        let mut pairs =
-            StdoutParser::parse(Rule::zpools, stdout).unwrap_or_else(|e| panic!("{}", e));
-        let pair = pairs.next().unwrap();
-        let zpool = Zpool::from_pest_pair(pair);
+        let zpool = parse_zpools_with(stdout, ParseMode::Strict).unwrap().remove(0);
         let topo = CreateZpoolRequestBuilder::default()
             .name("hell")
             .vdev(CreateVdevRequest::SingleDisk(PathBuf::from("/vdevs/vdev0")))
@@ -451,12 +717,139 @@ errors: No known data errors
         assert_eq!(&topo, &zpool);
     }
 
+    #[test]
+    fn test_strict_mode() {
+        let stdout = r#"  pool: tests-12167169401705616934
+ state: ONLINE
+  scan: none requested
+config:
+
+        NAME                   STATE     READ WRITE CKSUM
+        tests-12167169401705616934  ONLINE       0     0     0
+          /vdevs/import/vdev0  ONLINE       0     0     0
+
+errors: No known data errors
+
+  pool: tests-5503412806410244127
+ state: ONLINE
+  scan: none requested
+ dedup: DDT entries 1, size 288B on disk, 160B in core
+config:
+
+        NAME                         STATE     READ WRITE CKSUM
+        tests-5503412806410244127    ONLINE       0     0     0
+          /vdevs/vdev0               ONLINE       0     0     0
+
+errors: No known data errors
+"#;
+        let zpools = parse_zpools(stdout).unwrap();
+        assert_eq!(2, zpools.len());
+        assert_eq!("tests-5503412806410244127", zpools[1].name());
+        assert_eq!(1, zpools[1].vdevs().len());
+
+        let err = parse_zpools_with(stdout, ParseMode::Strict).unwrap_err();
+        let expected = "unrecognized output: dedup: DDT entries 1, size 288B on disk, 160B in core";
+        assert_eq!(expected, err.to_string());
+
+        let first = stdout.split("\n\n  pool:").next().unwrap();
+        assert_eq!(1, parse_zpools_with(first, ParseMode::Strict).unwrap().len());
+    }
+
+    #[test]
+    fn test_checkpoint_and_allocation_classes() {
+        let stdout = r#"  pool: tank
+ state: ONLINE
+  scan: none requested
+checkpoint: created Tue Apr 10 12:02:19 2018, consumes 1.20M
+config:
+
+        NAME        STATE     READ WRITE CKSUM
+        tank        ONLINE       0     0     0
+          sda       ONLINE       0     0     0
+        special
+          mirror-1  ONLINE       0     0     0
+            sdb     ONLINE       0     0     0
+            sdc     ONLINE       0     0     0
+        dedup
+          sdd       ONLINE       0     0     0
+        logs
+          sde       ONLINE       0     0     0
+
+errors: No known data errors
+"#;
+        let zpool = parse_zpools_with(stdout, ParseMode::Strict).unwrap().remove(0);
+        let checkpoint = "created Tue Apr 10 12:02:19 2018, consumes 1.20M";
+        assert_eq!(&Some(String::from(checkpoint)), zpool.checkpoint());
+        assert_eq!(1, zpool.vdevs().len());
+        assert_eq!(1, zpool.special().len());
+        assert_eq!(&VdevType::Mirror, zpool.special()[0].kind());
+        assert_eq!(2, zpool.special()[0].disks().len());
+        assert_eq!(1, zpool.dedup().len());
+        assert_eq!(&PathBuf::from("sdd"), zpool.dedup()[0].disks()[0].path());
+        assert_eq!(1, zpool.logs().len());
+    }
+
+    #[test]
+    fn test_tolerant_mode_skips_unknown() {
+        let stdout = r#"  pool: tank
+ state: ONLINE
+ future: something newer OpenZFS prints
+        and its continuation
+config:
+
+        NAME        STATE     READ WRITE CKSUM
+        tank        ONLINE       0     0     0
+          sda       ONLINE       0     0     0
+        embedded
+          sdb       ONLINE       0     0     0
+        cache
+          sdc       ONLINE       0     0     0
+
+errors: No known data errors
+
+  pool: broken
+ state: CONFUSED
+config:
+
+  pool: backup
+ state: ONLINE
+config:
+
+        NAME        STATE     READ WRITE CKSUM
+        backup      ONLINE       0     0     0
+          sdd       ONLINE       0     0     0
+
+errors: No known data errors
+"#;
+        let zpools = parse_zpools(stdout).unwrap();
+        assert_eq!(2, zpools.len());
+        assert_eq!(1, zpools[0].vdevs().len());
+        assert_eq!(&PathBuf::from("sda"), zpools[0].vdevs()[0].disks()[0].path());
+        assert_eq!(1, zpools[0].caches().len());
+        assert_eq!("backup", zpools[1].name());
+
+        let err = parse_zpools_with(stdout, ParseMode::Strict).unwrap_err();
+        let expected = "unrecognized output: future: something newer OpenZFS prints";
+        assert_eq!(expected, err.to_string());
+        let stdout = stdout.replace(" future: something newer OpenZFS prints\n", "");
+        let stdout = stdout.replace("        and its continuation\n", "");
+        let err = parse_zpools_with(&stdout, ParseMode::Strict).unwrap_err();
+        assert_eq!("unrecognized output: embedded", err.to_string());
+    }
+
+    #[test]
+    fn test_error_count_overflow() {
+        let stdout = "  pool: tank\n state: ONLINE\nconfig:\n\n\tNAME     STATE     READ WRITE CKSUM\n\ttank     ONLINE       0     0     0\n\t  sda    ONLINE    99999999999999999999999     0  1.5K\n";
+        let zpool = parse_zpools_with(stdout, ParseMode::Strict).unwrap().remove(0);
+        let expected = ErrorStatistics { read: std::u64::MAX, write: 0, checksum: 1536 };
+        assert_eq!(&expected, zpool.vdevs()[0].disks()[0].error_statistics());
+    }
+
     #[test]
     fn test_zpool_int_overflow() {
         let stdout = include_str!("fixtures/SIGABRT.PID.84191.TIME.2019-08-21.20.04.09.fuzz");
-        let mut pairs =
-            StdoutParser::parse(Rule::zpools, stdout).unwrap_or_else(|e| panic!("{}", e));
-        let pair = pairs.next().unwrap();
-        let _zpool = Zpool::from_pest_pair(pair);
+        // Garbage from fuzzing must not panic, whatever it turns into.
+        let _ = parse_zpools(stdout).unwrap();
+        let _ = parse_zpools_with(stdout, ParseMode::Strict);
     }
 }
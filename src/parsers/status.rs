@@ -0,0 +1,365 @@
+//! Line-oriented parser of `zpool status` and `zpool import` output. A pool is a block of
+//! `key: value` lines, the device tree under `config:` and an optional `errors:` section. Nesting
+//! of the tree comes from indentation, so output with thousands of devices is parsed in one pass.
+use std::{iter::Peekable,
+          str::{FromStr, Lines}};
+
+use crate::{utils::ParseMode,
+            zpool::{description::ZpoolBuilder,
+                    maintenance::parse_suffixes,
+                    scan::parse_size,
+                    vdev::ErrorStatistics,
+                    DataError, Disk, Health, Reason, RemovalStatus, ScanStatus, Vdev, VdevType,
+                    Zpool, ZpoolError, ZpoolResult}};
+
+/// Line that couldn't be made sense of.
+type LineResult<'a, T> = Result<T, &'a str>;
+
+/// Header `zpool status` prints above the device tree.
+const TREE_HEADER: [&str; 5] = ["NAME", "STATE", "READ", "WRITE", "CKSUM"];
+
+/// Parse pools one after another. Tolerant mode skips keys and sections it doesn't know and pools
+/// it can't make sense of, strict mode fails with the first line that isn't recognized.
+pub(crate) fn parse_zpools(stdout: &str, mode: ParseMode) -> ZpoolResult<Vec<Zpool>> {
+    let mut lines = stdout.lines().peekable();
+    let mut zpools = Vec::new();
+    loop {
+        while lines.peek().map_or(false, |line| line.trim().is_empty()) {
+            lines.next();
+        }
+        if lines.peek().is_none() {
+            break;
+        }
+        match parse_zpool(&mut lines, mode) {
+            Ok(zpool) => zpools.push(zpool),
+            Err(line) if mode == ParseMode::Strict => {
+                return Err(ZpoolError::UnrecognizedOutput(line.trim().to_string()));
+            },
+            Err(_) => {
+                while lines.peek().map_or(false, |line| !is_pool_line(line)) {
+                    lines.next();
+                }
+            },
+        }
+    }
+    Ok(zpools)
+}
+
+fn parse_zpool<'a>(lines: &mut Peekable<Lines<'a>>, mode: ParseMode) -> LineResult<'a, Zpool> {
+    let first = lines.next().unwrap_or_default();
+    let name = match split_key(first) {
+        Some(("pool", name)) if !name.trim().is_empty() => name.trim(),
+        _ => return Err(first),
+    };
+    let mut zpool = Zpool::builder();
+    zpool.name(name).vdevs(Vec::<Vdev>::new());
+
+    let mut health = None;
+    loop {
+        let line = lines.next().ok_or(first)?;
+        let (key, value) = split_key(line).ok_or(line)?;
+        match key {
+            "id" => {
+                zpool.id(Some(value.trim().parse::<u64>().map_err(|_| line)?));
+            },
+            "state" => {
+                let mut words = value.split_whitespace();
+                health = Some(Health::try_from_str(words.next()).map_err(|_| line)?);
+                match words.next() {
+                    None => zpool.destroyed(false),
+                    Some("(DESTROYED)") => zpool.destroyed(true),
+                    Some(_) => return Err(line),
+                };
+            },
+            "action" => {
+                zpool.action(Some(multi_line_text(value, lines)));
+            },
+            "scan" => {
+                zpool.scan(ScanStatus::from_scan_line(&multi_line_text(value, lines)));
+            },
+            "remove" => {
+                zpool.removal(RemovalStatus::from_remove_line(&multi_line_text(value, lines)));
+            },
+            "checkpoint" => {
+                zpool.checkpoint(Some(multi_line_text(value, lines).trim_end().to_string()));
+            },
+            "status" | "see" => {
+                multi_line_text(value, lines);
+            },
+            "config" => break,
+            _ if mode == ParseMode::Strict => return Err(line),
+            _ => {
+                multi_line_text(value, lines);
+            },
+        }
+    }
+    zpool.health(health.ok_or(first)?);
+
+    parse_tree(lines, &mut zpool, mode)?;
+
+    while lines.peek().map_or(false, |line| line.trim().is_empty()) {
+        lines.next();
+    }
+    if lines.peek().map_or(false, |line| line.trim_start().starts_with("errors:")) {
+        parse_errors(lines, &mut zpool);
+    }
+    zpool.build().map_err(|_| first)
+}
+
+/// Device tree: pool line, its vdevs and `logs`, `cache`, `spares`, `special` and `dedup` sections.
+/// Ends with a blank line or `errors:`. Devices of unknown sections are skipped in tolerant mode.
+fn parse_tree<'a>(
+    lines: &mut Peekable<Lines<'a>>,
+    zpool: &mut ZpoolBuilder,
+    mode: ParseMode,
+) -> LineResult<'a, ()> {
+    while lines.peek().map_or(false, |line| line.trim().is_empty()) {
+        lines.next();
+    }
+    if lines.peek().map_or(false, |line| line.split_whitespace().eq(TREE_HEADER.iter().cloned())) {
+        lines.next();
+    }
+    let pool_line = match lines.next() {
+        Some(line) => line,
+        None => return Ok(()),
+    };
+    let base = indent(pool_line);
+    let pool = parse_node(pool_line)?;
+    zpool.error_statistics(pool.stats).reason(pool.reason.map(Reason::from_note));
+
+    let mut section = "";
+    let mut sections: Vec<(&str, Vec<(usize, Node<'_>)>)> = vec![(section, Vec::new())];
+    while let Some(line) = lines.peek().cloned() {
+        if line.trim().is_empty() || line.trim_start().starts_with("errors:") {
+            break;
+        }
+        lines.next();
+        let depth = indent(line);
+        if depth <= base {
+            section = match line.trim() {
+                name @ "logs" | name @ "cache" | name @ "spares" | name @ "special"
+                | name @ "dedup" => name,
+                _ if mode == ParseMode::Strict => return Err(line),
+                name => name,
+            };
+            sections.push((section, Vec::new()));
+        } else if let Some((_, nodes)) = sections.last_mut() {
+            nodes.push((depth, parse_node(line)?));
+        }
+    }
+
+    for (section, nodes) in sections {
+        let roots = nest(nodes);
+        match section {
+            "cache" => {
+                zpool.caches(roots.into_iter().map(Node::into_disk).collect::<Vec<_>>());
+            },
+            "spares" => {
+                zpool.spares(roots.into_iter().map(Node::into_disk).collect::<Vec<_>>());
+            },
+            "logs" => {
+                zpool.logs(roots.into_iter().map(Node::into_vdev).collect::<Vec<_>>());
+            },
+            "special" => {
+                zpool.special(roots.into_iter().map(Node::into_vdev).collect::<Vec<_>>());
+            },
+            "dedup" => {
+                zpool.dedup(roots.into_iter().map(Node::into_vdev).collect::<Vec<_>>());
+            },
+            "" => {
+                zpool.vdevs(roots.into_iter().map(Node::into_vdev).collect::<Vec<_>>());
+            },
+            _ => {},
+        }
+    }
+    Ok(())
+}
+
+/// `errors:` line, its continuation and files listed by `zpool status -v` after a blank line.
+fn parse_errors(lines: &mut Peekable<Lines<'_>>, zpool: &mut ZpoolBuilder) {
+    let line = lines.next().unwrap_or_default();
+    let value = line.trim_start().trim_start_matches("errors:");
+    let text = multi_line_text(value, lines);
+    if value.trim() == "No known data errors" {
+        return;
+    }
+    zpool.errors(Some(text));
+
+    let mut data_errors = Vec::new();
+    while let Some(line) = lines.peek().cloned() {
+        if let Some(entry) = continuation(line) {
+            data_errors.push(DataError::from_entry(entry));
+        } else if !line.trim().is_empty() {
+            break;
+        }
+        lines.next();
+    }
+    zpool.data_errors(data_errors);
+}
+
+/// Device line of the tree with devices nested under it.
+struct Node<'a> {
+    name:     &'a str,
+    health:   Health,
+    stats:    ErrorStatistics,
+    reason:   Option<&'a str>,
+    children: Vec<Node<'a>>,
+}
+
+impl<'a> Node<'a> {
+    /// Top-level node: either a group like `mirror-0` or a single device.
+    fn into_vdev(self) -> Vdev {
+        let kind = self.name.rsplitn(2, '-').nth(1).filter(|_| is_index(self.name));
+        match VdevType::from_str(kind.unwrap_or(self.name)) {
+            Ok(kind) => Vdev::builder()
+                .kind(kind)
+                .health(self.health)
+                .reason(self.reason.map(Reason::from_note))
+                .error_statistics(self.stats)
+                .disks(self.children.into_iter().map(Node::into_disk).collect())
+                .build()
+                .expect("Failed to build vdev"),
+            Err(_) => {
+                let disk = self.into_disk();
+                Vdev::builder()
+                    .kind(VdevType::SingleDisk)
+                    .health(disk.health().clone())
+                    .reason(None)
+                    .disks(vec![disk])
+                    .build()
+                    .expect("Failed to build Vdev")
+            },
+        }
+    }
+
+    /// Device. A `spare-N` node keeps its children, the reason of a leaf device can also carry
+    /// TRIM and initialization state.
+    fn into_disk(self) -> Disk {
+        let mut disk = Disk::builder();
+        disk.path(self.name).health(self.health).error_statistics(self.stats);
+        if self.children.is_empty() {
+            let suffixes = self.reason.map(parse_suffixes).unwrap_or_default();
+            disk.reason(suffixes.rest.map(|rest| Reason::from_note(&rest)))
+                .trim(suffixes.trim)
+                .initialize(suffixes.initialize);
+        } else {
+            disk.reason(self.reason.map(Reason::from_note))
+                .children(self.children.into_iter().map(Node::into_disk).collect::<Vec<_>>());
+        }
+        disk.build().expect("Failed to build disk")
+    }
+}
+
+/// Parse `name STATE [READ WRITE CKSUM] [note]`.
+fn parse_node(line: &str) -> LineResult<'_, Node<'_>> {
+    let (name, rest) = split_word(line);
+    let (state, mut rest) = split_word(rest);
+    if name.is_empty() {
+        return Err(line);
+    }
+    let health = Health::try_from_str(Some(state)).map_err(|_| line)?;
+
+    let mut stats = ErrorStatistics::default();
+    let (read, after_read) = split_word(rest);
+    let (write, after_write) = split_word(after_read);
+    let (checksum, after_checksum) = split_word(after_write);
+    if [read, write, checksum].iter().all(|count| is_error_count(count)) {
+        let count = |text| parse_size(text).unwrap_or(std::u64::MAX);
+        stats =
+            ErrorStatistics { read: count(read), write: count(write), checksum: count(checksum) };
+        rest = after_checksum;
+    }
+    let reason = Some(rest.trim_end()).filter(|reason| !reason.is_empty());
+    Ok(Node { name, health, stats, reason, children: Vec::new() })
+}
+
+/// Build the tree out of devices and their indentation.
+fn nest(nodes: Vec<(usize, Node<'_>)>) -> Vec<Node<'_>> {
+    fn attach<'a>(stack: &mut Vec<(usize, Node<'a>)>, roots: &mut Vec<Node<'a>>, node: Node<'a>) {
+        match stack.last_mut() {
+            Some((_, parent)) => parent.children.push(node),
+            None => roots.push(node),
+        }
+    }
+
+    let mut roots = Vec::new();
+    let mut stack: Vec<(usize, Node<'_>)> = Vec::new();
+    for (depth, node) in nodes {
+        while stack.last().map_or(false, |(top, _)| *top >= depth) {
+            if let Some((_, done)) = stack.pop() {
+                attach(&mut stack, &mut roots, done);
+            }
+        }
+        stack.push((depth, node));
+    }
+    while let Some((_, done)) = stack.pop() {
+        attach(&mut stack, &mut roots, done);
+    }
+    roots
+}
+
+/// First line of a pool: `pool: name`.
+fn is_pool_line(line: &str) -> bool { split_key(line).map_or(false, |(key, _)| key == "pool") }
+
+/// Split `key: value` line. Keys are single words.
+fn split_key(line: &str) -> Option<(&str, &str)> {
+    let line = line.trim_start();
+    let idx = line.find(':')?;
+    let key = &line[..idx];
+    if key.is_empty() || key.contains(char::is_whitespace) {
+        return None;
+    }
+    Some((key, &line[idx + 1..]))
+}
+
+/// Value of a key followed by its continuation lines, as `zpool` printed them.
+fn multi_line_text(value: &str, lines: &mut Peekable<Lines<'_>>) -> String {
+    let mut text = format!("{}\n", value.trim_start());
+    while let Some(line) = lines.peek().cloned() {
+        if continuation(line).is_none() {
+            break;
+        }
+        text.push_str(line);
+        text.push('\n');
+        lines.next();
+    }
+    text
+}
+
+/// Text of a line indented with a tab or 8 spaces.
+fn continuation(line: &str) -> Option<&str> {
+    line.strip_prefix('\t')
+        .or_else(|| line.strip_prefix("        "))
+        .filter(|text| !text.trim().is_empty())
+}
+
+/// Width of indentation. Tabs stop every 8 columns.
+fn indent(line: &str) -> usize {
+    line.chars().take_while(|c| c.is_whitespace()).fold(0, |width, c| match c {
+        '\t' => (width / 8 + 1) * 8,
+        _ => width + 1,
+    })
+}
+
+fn split_word(text: &str) -> (&str, &str) {
+    let text = text.trim_start();
+    let end = text.find(char::is_whitespace).unwrap_or_else(|| text.len());
+    (&text[..end], text[end..].trim_start())
+}
+
+/// Whether name ends with `-N`, like `mirror-0` does.
+fn is_index(name: &str) -> bool {
+    name.rsplit('-')
+        .next()
+        .map_or(false, |idx| !idx.is_empty() && idx.bytes().all(|b| b.is_ascii_digit()))
+}
+
+/// Error counter. Large ones are abbreviated: `1.23K`.
+fn is_error_count(text: &str) -> bool {
+    let number = text.trim_end_matches(|c| "KMGTPE".contains(c));
+    let mut parts = number.splitn(2, '.');
+    let digits = |part: &str| !part.is_empty() && part.bytes().all(|b| b.is_ascii_digit());
+    text.len() - number.len() <= 1
+        && parts.next().map_or(false, digits)
+        && parts.next().map_or(true, digits)
+}
@@ -1,12 +1,43 @@
 use pest_derive::Parser;
+use std::path::PathBuf;
+
+use crate::zfs::DatasetKind;
 
 #[derive(Parser)]
 #[grammar = "parsers/zfs.pest"] // relative to src
 pub struct ZfsParser;
 
+/// Parse output of `zfs list -H -o name`. One dataset per line, empty lines are skipped.
+///
+/// Line-oriented on purpose: listing of pools with hundreds of thousands of snapshots is two orders
+/// of magnitude faster this way than with `Rule::datasets`.
+pub fn parse_datasets(stdout: &str) -> Vec<PathBuf> {
+    stdout.lines().filter(|line| !line.trim().is_empty()).map(PathBuf::from).collect()
+}
+
+/// Parse output of `zfs list -H -o type,name`. Returns `None` if any line has unknown type or no
+/// name.
+pub fn parse_datasets_with_type(stdout: &str) -> Option<Vec<(DatasetKind, PathBuf)>> {
+    stdout
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| {
+            let mut cols = line.splitn(2, char::is_whitespace);
+            let kind = cols.next()?.parse().ok()?;
+            let name = cols.next()?.trim_start();
+            if name.is_empty() {
+                return None;
+            }
+            Some((kind, PathBuf::from(name)))
+        })
+        .collect()
+}
+
 #[cfg(test)]
 mod test {
     use super::{Rule, ZfsParser};
+    use crate::zfs::DatasetKind;
+    use std::path::PathBuf;
     use pest::{consumes_to, parses_to, Parser};
 
     #[test]
@@ -126,6 +157,30 @@ bookmark        z/var/mail#backup-2019-08-08
         }
     }
 
+    #[test]
+    fn test_line_oriented_datasets() {
+        let stdout = "s\ns/s/s/s\ns/d@test\ns/with space:colon\n\n";
+        let expected: Vec<PathBuf> = vec!["s", "s/s/s/s", "s/d@test", "s/with space:colon"]
+            .into_iter()
+            .map(PathBuf::from)
+            .collect();
+        assert_eq!(expected, super::parse_datasets(stdout));
+
+        let stdout = "volume\tz/iohyve/rancher/disk0\nfilesystem      z/var/mail\nsnapshot\tz/var/mail@backup-2019-08-08\nsnapshot\tz/var/mail@backup-2019-08-09\nbookmark\tz/var/mail#backup-2019-08-08\n        \n";
+        let datasets = super::parse_datasets_with_type(stdout).unwrap();
+        assert_eq!(5, datasets.len());
+        assert_eq!((DatasetKind::Volume, PathBuf::from("z/iohyve/rancher/disk0")), datasets[0]);
+        assert_eq!((DatasetKind::Filesystem, PathBuf::from("z/var/mail")), datasets[1]);
+        assert_eq!(DatasetKind::Snapshot, datasets[3].0);
+        assert_eq!(
+            (DatasetKind::Bookmark, PathBuf::from("z/var/mail#backup-2019-08-08")),
+            datasets[4]
+        );
+
+        assert_eq!(None, super::parse_datasets_with_type("pool\tz\n"));
+        assert_eq!(None, super::parse_datasets_with_type("filesystem\n"));
+    }
+
     #[test]
     fn test_issue_126() {
         let lines = r#"z/ROOT
@@ -0,0 +1,335 @@
+//! What properties of datasets and pools look like.
+//!
+//! Every native property is described by a [`PropertySchema`](struct.PropertySchema.html): type
+//! of its value, words it accepts, whether it's read-only, can only be set at creation time or is
+//! inherited by children, and OpenZFS version that introduced it. The tables follow
+//! `zfsprops(7)` and `zpoolprops(7)`, so UI and validation code doesn't have to copy them from
+//! man pages. User properties (`module:name`) are not described: their values are free-form
+//! strings.
+//!
+//! ### Usage
+//! ```rust
+//! use libzetta::schema::{dataset_property, pool_property, ValueType};
+//!
+//! let compression = dataset_property("compression").unwrap();
+//! assert!(compression.inheritable());
+//! assert!(compression.accepts("lz4"));
+//! assert!(!compression.accepts("rar"));
+//!
+//! let ashift = pool_property("ashift").unwrap();
+//! assert_eq!(ValueType::Number, ashift.value_type());
+//! assert!(dataset_property("casesensitivity").unwrap().create_only());
+//! ```
+use self::ValueType::{Bool, Index, Number, Path, Size, Text};
+
+/// OpenZFS version that every property is assumed to be in unless it says otherwise. ZFS on Linux
+/// 0.6 is the oldest release this crate was used with.
+pub const BASELINE: (u32, u32) = (0, 6);
+
+/// Type of property value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValueType {
+    /// `on` or `off`.
+    Bool,
+    /// One of the listed words.
+    Index(&'static [&'static str]),
+    /// Non-negative integer.
+    Number,
+    /// Size in bytes, with optional suffix: `512`, `128K`, `1.5G`.
+    Size,
+    /// Absolute path.
+    Path,
+    /// Free-form string.
+    Text,
+}
+
+/// Description of a native property.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, CopyGetters)]
+#[get_copy = "pub"]
+pub struct PropertySchema {
+    /// Name of the property.
+    name:        &'static str,
+    /// Type of the value.
+    value_type:  ValueType,
+    /// Words accepted on top of what the type allows, like `none` for quotas.
+    keywords:    &'static [&'static str],
+    /// Property is statistics or identity and can't be set.
+    readonly:    bool,
+    /// Property can only be set when a dataset or pool is created (or a pool is imported).
+    create_only: bool,
+    /// Children inherit the value unless they set their own.
+    inheritable: bool,
+    /// OpenZFS version (major, minor) that introduced the property.
+    since:       (u32, u32),
+}
+
+impl PropertySchema {
+    const fn new(name: &'static str, value_type: ValueType) -> PropertySchema {
+        PropertySchema {
+            name,
+            value_type,
+            keywords:    &[],
+            readonly:    false,
+            create_only: false,
+            inheritable: false,
+            since:       BASELINE,
+        }
+    }
+
+    const fn stat(name: &'static str, value_type: ValueType) -> PropertySchema {
+        PropertySchema { readonly: true, ..PropertySchema::new(name, value_type) }
+    }
+
+    const fn inherit(self) -> PropertySchema { PropertySchema { inheritable: true, ..self } }
+
+    const fn once(self) -> PropertySchema { PropertySchema { create_only: true, ..self } }
+
+    const fn or(self, keywords: &'static [&'static str]) -> PropertySchema {
+        PropertySchema { keywords, ..self }
+    }
+
+    const fn introduced(self, major: u32, minor: u32) -> PropertySchema {
+        PropertySchema { since: (major, minor), ..self }
+    }
+
+    /// Words that value can be: index values and keywords. Empty for free-form properties.
+    pub fn allowed_values(&self) -> Vec<&'static str> {
+        let mut values = match self.value_type {
+            ValueType::Bool => vec!["on", "off"],
+            ValueType::Index(values) => values.to_vec(),
+            _ => Vec::new(),
+        };
+        values.extend_from_slice(self.keywords);
+        values
+    }
+
+    /// Whether the property can be set to this value. Always `false` for read-only properties.
+    pub fn accepts(&self, value: &str) -> bool {
+        if self.readonly {
+            return false;
+        }
+        if self.keywords.contains(&value) {
+            return true;
+        }
+        match self.value_type {
+            ValueType::Bool => value == "on" || value == "off",
+            ValueType::Index(values) => values.contains(&value),
+            ValueType::Number => value.parse::<u64>().is_ok(),
+            ValueType::Size => is_size(value),
+            ValueType::Path => value.starts_with('/'),
+            ValueType::Text => true,
+        }
+    }
+
+    /// Whether OpenZFS of this version (major, minor) knows the property.
+    pub fn supported_by(&self, version: (u32, u32)) -> bool { version >= self.since }
+}
+
+fn is_size(value: &str) -> bool {
+    let split = value.find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(value.len());
+    let (number, suffix) = value.split_at(split);
+    if number.parse::<f64>().is_err() {
+        return false;
+    }
+    let suffix = suffix.to_ascii_uppercase();
+    let unit = |c: Option<char>| c.map_or(false, |c| "KMGTPEZ".contains(c));
+    match suffix.len() {
+        0 => true,
+        1 => suffix == "B" || unit(suffix.chars().next()),
+        2 => unit(suffix.chars().next()) && suffix.ends_with('B'),
+        3 => unit(suffix.chars().next()) && suffix.ends_with("IB"),
+        _ => false,
+    }
+}
+
+const CHECKSUM: &[&str] =
+    &["on", "off", "fletcher2", "fletcher4", "sha256", "noparity", "sha512", "skein", "edonr"];
+const COMPRESSION: &[&str] = &[
+    "on", "off", "lzjb", "gzip", "gzip-1", "gzip-2", "gzip-3", "gzip-4", "gzip-5", "gzip-6",
+    "gzip-7", "gzip-8", "gzip-9", "zle", "lz4", "zstd", "zstd-1", "zstd-2", "zstd-3", "zstd-4",
+    "zstd-5", "zstd-6", "zstd-7", "zstd-8", "zstd-9", "zstd-10", "zstd-11", "zstd-12", "zstd-13",
+    "zstd-14", "zstd-15", "zstd-16", "zstd-17", "zstd-18", "zstd-19", "zstd-fast", "zstd-fast-1",
+    "zstd-fast-2", "zstd-fast-3", "zstd-fast-4", "zstd-fast-5", "zstd-fast-6", "zstd-fast-7",
+    "zstd-fast-8", "zstd-fast-9", "zstd-fast-10", "zstd-fast-20", "zstd-fast-30", "zstd-fast-40",
+    "zstd-fast-50", "zstd-fast-60", "zstd-fast-70", "zstd-fast-80", "zstd-fast-90",
+    "zstd-fast-100", "zstd-fast-500", "zstd-fast-1000",
+];
+const DEDUP: &[&str] = &[
+    "on", "off", "verify", "sha256", "sha256,verify", "sha512", "sha512,verify", "skein",
+    "skein,verify", "edonr,verify",
+];
+const ACL_INHERIT: &[&str] =
+    &["discard", "noallow", "restricted", "passthrough", "passthrough-x"];
+const ENCRYPTION: &[&str] = &[
+    "off", "on", "aes-128-ccm", "aes-192-ccm", "aes-256-ccm", "aes-128-gcm", "aes-192-gcm",
+    "aes-256-gcm",
+];
+const HEALTH: &[&str] = &["ONLINE", "DEGRADED", "FAULTED", "OFFLINE", "REMOVED", "UNAVAIL"];
+const CACHE: &[&str] = &["all", "none", "metadata"];
+const VISIBILITY: &[&str] = &["hidden", "visible"];
+
+static DATASET: &[PropertySchema] = &[
+    PropertySchema::new("aclinherit", Index(ACL_INHERIT)).inherit(),
+    PropertySchema::new("aclmode", Index(&["discard", "groupmask", "passthrough", "restricted"]))
+        .inherit(),
+    PropertySchema::new("acltype", Index(&["off", "noacl", "nfsv4", "posix", "posixacl"]))
+        .inherit(),
+    PropertySchema::new("atime", Bool).inherit(),
+    PropertySchema::stat("available", Size),
+    PropertySchema::new("canmount", Index(&["on", "off", "noauto"])),
+    PropertySchema::new("casesensitivity", Index(&["sensitive", "insensitive", "mixed"]))
+        .inherit()
+        .once(),
+    PropertySchema::new("checksum", Index(CHECKSUM)).inherit(),
+    PropertySchema::stat("clones", Text),
+    PropertySchema::new("compression", Index(COMPRESSION)).inherit(),
+    PropertySchema::stat("compressratio", Text),
+    PropertySchema::new("copies", Index(&["1", "2", "3"])).inherit(),
+    PropertySchema::stat("createtxg", Number),
+    PropertySchema::stat("creation", Number),
+    PropertySchema::new("dedup", Index(DEDUP)).inherit(),
+    PropertySchema::stat("defer_destroy", Bool),
+    PropertySchema::new("devices", Bool).inherit(),
+    PropertySchema::new("dnodesize", Index(&["legacy", "auto", "1k", "2k", "4k", "8k", "16k"]))
+        .inherit()
+        .introduced(0, 7),
+    PropertySchema::new("encryption", Index(ENCRYPTION)).inherit().once().introduced(0, 8),
+    PropertySchema::stat("encryptionroot", Text).introduced(0, 8),
+    PropertySchema::new("exec", Bool).inherit(),
+    PropertySchema::new("filesystem_limit", Number).or(&["none"]).introduced(0, 7),
+    PropertySchema::stat("guid", Number),
+    PropertySchema::new("keyformat", Index(&["raw", "hex", "passphrase"])).once().introduced(0, 8),
+    PropertySchema::new("keylocation", Text).or(&["prompt"]).introduced(0, 8),
+    PropertySchema::stat("keystatus", Index(&["none", "unavailable", "available"]))
+        .introduced(0, 8),
+    PropertySchema::new("logbias", Index(&["latency", "throughput"])).inherit(),
+    PropertySchema::stat("logicalreferenced", Size),
+    PropertySchema::stat("logicalused", Size),
+    PropertySchema::new("mlslabel", Text).inherit(),
+    PropertySchema::stat("mounted", Bool),
+    PropertySchema::new("mountpoint", Path).inherit().or(&["none", "legacy"]),
+    PropertySchema::new("nbmand", Bool).inherit(),
+    PropertySchema::new("normalization", Index(&["none", "formC", "formD", "formKC", "formKD"]))
+        .inherit()
+        .once(),
+    PropertySchema::stat("objsetid", Number).introduced(0, 8),
+    PropertySchema::stat("origin", Text),
+    PropertySchema::new("overlay", Bool).inherit(),
+    PropertySchema::new("pbkdf2iters", Number).once().introduced(0, 8),
+    PropertySchema::new("primarycache", Index(CACHE)).inherit(),
+    PropertySchema::new("quota", Size).or(&["none"]),
+    PropertySchema::new("readonly", Bool).inherit(),
+    PropertySchema::stat("receive_resume_token", Text).introduced(0, 7),
+    PropertySchema::new("recordsize", Size).inherit(),
+    PropertySchema::new("redundant_metadata", Index(&["all", "most"])).inherit(),
+    PropertySchema::stat("refcompressratio", Text),
+    PropertySchema::stat("referenced", Size),
+    PropertySchema::new("refquota", Size).or(&["none"]),
+    PropertySchema::new("refreservation", Size).or(&["none", "auto"]),
+    PropertySchema::new("relatime", Bool).inherit(),
+    PropertySchema::new("reservation", Size).or(&["none"]),
+    PropertySchema::new("secondarycache", Index(CACHE)).inherit(),
+    PropertySchema::new("setuid", Bool).inherit(),
+    PropertySchema::new("sharenfs", Text).inherit(),
+    PropertySchema::new("sharesmb", Text).inherit(),
+    PropertySchema::new("snapdev", Index(VISIBILITY)).inherit(),
+    PropertySchema::new("snapdir", Index(VISIBILITY)).inherit(),
+    PropertySchema::stat("snapshot_count", Number).introduced(0, 7),
+    PropertySchema::new("snapshot_limit", Number).or(&["none"]).introduced(0, 7),
+    PropertySchema::new("special_small_blocks", Size).inherit().introduced(0, 8),
+    PropertySchema::new("sync", Index(&["standard", "always", "disabled"])).inherit(),
+    PropertySchema::stat("type", Index(&["filesystem", "volume", "snapshot", "bookmark"])),
+    PropertySchema::stat("used", Size),
+    PropertySchema::stat("usedbychildren", Size),
+    PropertySchema::stat("usedbydataset", Size),
+    PropertySchema::stat("usedbyrefreservation", Size),
+    PropertySchema::stat("usedbysnapshots", Size),
+    PropertySchema::stat("userrefs", Number),
+    PropertySchema::new("utf8only", Bool).inherit().once(),
+    PropertySchema::new("version", Number).or(&["current"]),
+    PropertySchema::new("volblocksize", Size).once(),
+    PropertySchema::new("volmode", Index(&["default", "full", "geom", "dev", "none"])).inherit(),
+    PropertySchema::new("volsize", Size),
+    PropertySchema::new("vscan", Bool).inherit(),
+    PropertySchema::stat("written", Size),
+    PropertySchema::new("xattr", Index(&["on", "off", "sa", "dir"])).inherit(),
+    PropertySchema::new("zoned", Bool).inherit(),
+];
+
+static POOL: &[PropertySchema] = &[
+    PropertySchema::stat("allocated", Size),
+    PropertySchema::new("altroot", Path).once(),
+    PropertySchema::new("ashift", Number),
+    PropertySchema::new("autoexpand", Bool),
+    PropertySchema::new("autoreplace", Bool),
+    PropertySchema::new("autotrim", Bool).introduced(0, 8),
+    PropertySchema::new("bootfs", Text),
+    PropertySchema::new("cachefile", Path).or(&["none"]),
+    PropertySchema::stat("capacity", Number),
+    PropertySchema::stat("checkpoint", Size).introduced(0, 8),
+    PropertySchema::new("comment", Text),
+    PropertySchema::new("compatibility", Text).or(&["off", "legacy"]).introduced(2, 1),
+    PropertySchema::stat("dedupratio", Text),
+    PropertySchema::new("delegation", Bool),
+    PropertySchema::stat("expandsize", Size),
+    PropertySchema::new("failmode", Index(&["wait", "continue", "panic"])),
+    PropertySchema::stat("fragmentation", Number),
+    PropertySchema::stat("free", Size),
+    PropertySchema::stat("freeing", Size),
+    PropertySchema::stat("guid", Number),
+    PropertySchema::stat("health", Index(HEALTH)),
+    PropertySchema::stat("leaked", Size),
+    PropertySchema::new("listsnapshots", Bool),
+    PropertySchema::stat("load_guid", Number).introduced(2, 0),
+    PropertySchema::new("multihost", Bool).introduced(0, 7),
+    PropertySchema::new("readonly", Bool).once(),
+    PropertySchema::stat("size", Size),
+    PropertySchema::new("version", Number),
+];
+
+/// Every native dataset property.
+pub fn dataset_properties() -> &'static [PropertySchema] { DATASET }
+
+/// Every pool property, not counting `feature@` ones.
+pub fn pool_properties() -> &'static [PropertySchema] { POOL }
+
+/// Native dataset property with this name.
+pub fn dataset_property(name: &str) -> Option<&'static PropertySchema> {
+    DATASET.iter().find(|prop| prop.name == name)
+}
+
+/// Pool property with this name.
+pub fn pool_property(name: &str) -> Option<&'static PropertySchema> {
+    POOL.iter().find(|prop| prop.name == name)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn lookup() {
+        let quota = dataset_property("quota").unwrap();
+        assert!(quota.accepts("10G"));
+        assert!(quota.accepts("1.5TiB"));
+        assert!(quota.accepts("none"));
+        assert!(!quota.accepts("lots"));
+        assert!(!quota.inheritable());
+        assert!(!dataset_property("used").unwrap().accepts("1G"));
+        assert!(!dataset_property("encryption").unwrap().supported_by((0, 7)));
+        assert_eq!(vec!["on", "off"], pool_property("autotrim").unwrap().allowed_values());
+        assert!(pool_property("compatibility").unwrap().accepts("legacy"));
+        assert_eq!(None, dataset_property("com.example:owner"));
+    }
+
+    #[test]
+    fn tables_are_sorted() {
+        for table in &[dataset_properties(), pool_properties()] {
+            let names: Vec<&str> = table.iter().map(|prop| prop.name()).collect();
+            let mut sorted = names.clone();
+            sorted.sort_unstable();
+            sorted.dedup();
+            assert_eq!(sorted, names);
+        }
+    }
+}
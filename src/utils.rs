@@ -1,3 +1,8 @@
+//! Helpers for parsing numeric property values.
+use std::{fmt,
+          num::{IntErrorKind, ParseFloatError, ParseIntError},
+          str::FromStr};
+
 /// Very pricey way of parsing strings. Used because some ratios have `x` character, and some don't.
 #[inline(always)]
 pub fn parse_float(input: &mut String) -> Result<f64, std::num::ParseFloatError> {
@@ -10,3 +15,168 @@ pub fn parse_float(input: &mut String) -> Result<f64, std::num::ParseFloatError>
     }
     input.parse()
 }
+
+/// What to do with numeric values that don't fit into their type. For example, `free` on a pool
+/// with leaked space can be reported close to 2^64, which doesn't fit into `i64`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Overflow {
+    /// Fail parsing. Default.
+    Error,
+    /// Clamp to the closest value the type can hold.
+    Saturate,
+}
+
+impl Default for Overflow {
+    fn default() -> Overflow { Overflow::Error }
+}
+
+/// What to do with output that parsers don't recognize, e.g. lines that a newer OpenZFS added.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseMode {
+    /// Skip it and return what was recognized. Default.
+    Tolerant,
+    /// Fail with `UnrecognizedOutput`. Meant for CI and test suites that must notice when output
+    /// of OpenZFS changes instead of losing data silently.
+    Strict,
+}
+
+impl Default for ParseMode {
+    fn default() -> ParseMode { ParseMode::Tolerant }
+}
+
+/// Integer types that can be parsed with [`parse_int`](fn.parse_int.html).
+pub trait Bounded: FromStr<Err = ParseIntError> + Copy {
+    /// Smallest value of the type.
+    const MIN: Self;
+    /// Largest value of the type.
+    const MAX: Self;
+}
+
+macro_rules! bounded {
+    ($($t:ty),*) => {
+        $(impl Bounded for $t {
+            const MAX: $t = <$t>::max_value();
+            const MIN: $t = <$t>::min_value();
+        })*
+    };
+}
+bounded!(u8, i8, u32, i64, u64, usize);
+
+/// Parse integer. Values that are too big or too small are either an error or clamped, depending
+/// on `overflow`.
+pub fn parse_int<T: Bounded>(input: &str, overflow: Overflow) -> Result<T, ParseIntError> {
+    match input.parse() {
+        Err(ref e) if overflow == Overflow::Saturate && is_overflow(e) => {
+            if input.starts_with('-') {
+                Ok(T::MIN)
+            } else {
+                Ok(T::MAX)
+            }
+        },
+        other => other,
+    }
+}
+
+/// Whether the error is caused by value being out of range of the type.
+pub fn is_overflow(err: &ParseIntError) -> bool {
+    matches!(err.kind(), IntErrorKind::PosOverflow | IntErrorKind::NegOverflow)
+}
+
+/// Ratio like `dedupratio` or `compressratio`. Some platforms print it with `x` suffix (`1.00x`),
+/// some without.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub struct Ratio(f64);
+
+impl Ratio {
+    /// Create a new ratio.
+    pub fn new(ratio: f64) -> Ratio { Ratio(ratio) }
+
+    /// Value of the ratio.
+    pub fn as_f64(self) -> f64 { self.0 }
+}
+
+impl FromStr for Ratio {
+    type Err = ParseFloatError;
+
+    fn from_str(s: &str) -> Result<Ratio, ParseFloatError> {
+        s.strip_suffix('x').unwrap_or(s).parse().map(Ratio)
+    }
+}
+
+impl fmt::Display for Ratio {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result { write!(f, "{:.2}x", self.0) }
+}
+
+impl From<Ratio> for f64 {
+    fn from(ratio: Ratio) -> f64 { ratio.0 }
+}
+
+/// Percentage like `capacity` or `fragmentation`. Accepts values with and without `%` suffix.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Percent(u8);
+
+impl Percent {
+    /// Create a new percentage.
+    pub fn new(percent: u8) -> Percent { Percent(percent) }
+
+    /// Value in percents.
+    pub fn as_u8(self) -> u8 { self.0 }
+}
+
+impl FromStr for Percent {
+    type Err = ParseIntError;
+
+    fn from_str(s: &str) -> Result<Percent, ParseIntError> {
+        s.strip_suffix('%').unwrap_or(s).parse().map(Percent)
+    }
+}
+
+impl fmt::Display for Percent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result { write!(f, "{}%", self.0) }
+}
+
+impl From<Percent> for u8 {
+    fn from(percent: Percent) -> u8 { percent.0 }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn extremes() {
+        let max = "18446744073709551615";
+        let over = "18446744073709551616";
+        assert_eq!(Ok(u64::max_value()), parse_int::<u64>(max, Overflow::Error));
+        assert!(is_overflow(&parse_int::<u64>(over, Overflow::Error).unwrap_err()));
+        assert_eq!(Ok(u64::max_value()), parse_int::<u64>(over, Overflow::Saturate));
+        assert_eq!(Ok(i64::max_value()), parse_int::<i64>(max, Overflow::Saturate));
+        let under = "-99999999999999999999";
+        assert_eq!(Ok(i64::min_value()), parse_int::<i64>(under, Overflow::Saturate));
+        assert_eq!(Ok(0), parse_int::<u64>("0", Overflow::Saturate));
+        assert!(!is_overflow(&parse_int::<u64>("-", Overflow::Saturate).unwrap_err()));
+        assert!(!is_overflow(&parse_int::<u64>("1.5", Overflow::Saturate).unwrap_err()));
+    }
+
+    #[test]
+    fn ratios() {
+        assert_eq!(Ok(Ratio::new(1.0)), "1.00x".parse());
+        assert_eq!(Ok(Ratio::new(1.0)), "1.00".parse());
+        assert_eq!(Ok(Ratio::new(0.0)), "0.00x".parse());
+        assert_eq!(Ok(Ratio::new(1234.56)), "1234.56x".parse());
+        assert!("x".parse::<Ratio>().is_err());
+        assert!("1.00xx".parse::<Ratio>().is_err());
+        assert_eq!("1.50x", Ratio::new(1.5).to_string());
+    }
+
+    #[test]
+    fn percents() {
+        assert_eq!(Ok(Percent::new(0)), "0%".parse());
+        assert_eq!(Ok(Percent::new(100)), "100".parse());
+        assert_eq!(Ok(Percent::new(255)), "255%".parse());
+        assert!("256%".parse::<Percent>().is_err());
+        assert!("-1%".parse::<Percent>().is_err());
+        assert!("-".parse::<Percent>().is_err());
+        assert_eq!("22%", Percent::new(22).to_string());
+    }
+}
@@ -0,0 +1,374 @@
+//! Splitting send streams into fixed-size chunks and putting them back together.
+//!
+//! Object stores don't like multi-terabyte uploads that can't be resumed, so streams are cut into
+//! chunks of the same size and handed to a [`ChunkSink`](trait.ChunkSink.html). Storage itself
+//! (S3, GCS, a directory) is implemented outside of this crate. Chunks are described by
+//! [`SendManifest`](../manifest/struct.SendManifest.html), which is also what drives resume and
+//! reassembly.
+//!
+//! ### Usage
+//! ```rust,no_run
+//! use libzetta::zfs::{chunked::{send_chunked, ChunkSink},
+//!                     DelegatingZfsEngine, SendFlags};
+//! use std::{collections::BTreeMap, io, path::PathBuf};
+//!
+//! #[derive(Default)]
+//! struct InMemory(BTreeMap<u64, Vec<u8>>);
+//!
+//! impl ChunkSink for InMemory {
+//!     fn put_chunk(&mut self, index: u64, data: &[u8]) -> io::Result<()> {
+//!         self.0.insert(index, data.to_vec());
+//!         Ok(())
+//!     }
+//! }
+//!
+//! let engine = DelegatingZfsEngine::new().unwrap();
+//! let mut sink = InMemory::default();
+//! let manifest = send_chunked(
+//!     &engine,
+//!     PathBuf::from("tank/data@today"),
+//!     None,
+//!     SendFlags::empty(),
+//!     &mut sink,
+//!     64 << 20,
+//! )
+//! .unwrap();
+//! println!("{}", manifest);
+//! ```
+use std::{convert::TryFrom,
+          fs::File,
+          io::{self, Read, Write},
+          os::unix::io::FromRawFd,
+          path::PathBuf,
+          thread};
+
+use crate::zfs::{manifest::{ChunkInfo, SendManifest},
+                 Error, Result, SendFlags, ZfsEngine};
+
+/// Function used to checksum chunks. Must return `algorithm:hex` string.
+pub type ChecksumFn = fn(&[u8]) -> String;
+
+/// Destination for chunks of a send stream.
+pub trait ChunkSink {
+    /// Store chunk with given index. Storing the same index twice must overwrite it.
+    fn put_chunk(&mut self, index: u64, data: &[u8]) -> io::Result<()>;
+}
+
+/// Source of previously stored chunks.
+pub trait ChunkSource {
+    /// Fetch chunk with given index.
+    fn get_chunk(&mut self, index: u64) -> io::Result<Vec<u8>>;
+}
+
+impl<T: ChunkSink + ?Sized> ChunkSink for &mut T {
+    fn put_chunk(&mut self, index: u64, data: &[u8]) -> io::Result<()> {
+        (**self).put_chunk(index, data)
+    }
+}
+
+impl<T: ChunkSource + ?Sized> ChunkSource for &mut T {
+    fn get_chunk(&mut self, index: u64) -> io::Result<Vec<u8>> { (**self).get_chunk(index) }
+}
+
+/// Default chunk checksum: 64-bit FNV-1a. It's only meant to catch corruption in storage, use
+/// something stronger if stored chunks can't be trusted.
+pub fn fnv1a64(data: &[u8]) -> String {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    for byte in data {
+        hash ^= u64::from(*byte);
+        hash = hash.wrapping_mul(0x0100_0000_01b3);
+    }
+    format!("fnv1a64:{:016x}", hash)
+}
+
+/// Splits everything written into it into chunks and passes them to the sink.
+pub struct ChunkWriter<S: ChunkSink> {
+    sink:       S,
+    chunk_size: u64,
+    checksum:   ChecksumFn,
+    buffer:     Vec<u8>,
+    chunks:     Vec<ChunkInfo>,
+    size:       u64,
+    stored:     Vec<ChunkInfo>,
+}
+
+impl<S: ChunkSink> ChunkWriter<S> {
+    /// Create a writer that cuts stream into chunks of `chunk_size` bytes.
+    pub fn new(sink: S, chunk_size: u64) -> ChunkWriter<S> {
+        ChunkWriter {
+            sink,
+            chunk_size,
+            checksum: fnv1a64,
+            buffer:   Vec::new(),
+            chunks:   Vec::new(),
+            size:     0,
+            stored:   Vec::new(),
+        }
+    }
+
+    /// Resume an interrupted upload. Chunks from `stored` that turn out to be identical to the
+    /// ones being written are not sent to the sink again. Stream has to be produced the same way
+    /// as before, if a chunk doesn't match - writing fails.
+    pub fn resume(sink: S, chunk_size: u64, stored: Vec<ChunkInfo>) -> ChunkWriter<S> {
+        let mut writer = ChunkWriter::new(sink, chunk_size);
+        writer.stored = stored;
+        writer
+    }
+
+    /// Use different checksum function.
+    pub fn with_checksum(mut self, checksum: ChecksumFn) -> ChunkWriter<S> {
+        self.checksum = checksum;
+        self
+    }
+
+    /// Chunks written so far.
+    pub fn chunks(&self) -> &[ChunkInfo] { &self.chunks }
+
+    fn flush_chunk(&mut self) -> io::Result<()> {
+        if self.buffer.is_empty() {
+            return Ok(());
+        }
+        let index = len_u64(self.chunks.len());
+        let size = len_u64(self.buffer.len());
+        let chunk = ChunkInfo::new(index, self.size, size, (self.checksum)(&self.buffer));
+        let already_stored = match self.stored.get(self.chunks.len()) {
+            Some(stored) if stored.size() == chunk.size() && stored.offset() == chunk.offset() => {
+                if stored.checksum() != chunk.checksum() {
+                    let msg = format!("chunk {} differs from stored one", index);
+                    return Err(io::Error::new(io::ErrorKind::InvalidData, msg));
+                }
+                true
+            },
+            _ => false,
+        };
+        if !already_stored {
+            self.sink.put_chunk(index, &self.buffer)?;
+        }
+        self.size += size;
+        self.chunks.push(chunk);
+        self.buffer.clear();
+        Ok(())
+    }
+
+    /// Store the last chunk and return the sink together with total size and list of chunks.
+    pub fn finish(mut self) -> io::Result<(S, u64, Vec<ChunkInfo>)> {
+        self.flush_chunk()?;
+        Ok((self.sink, self.size, self.chunks))
+    }
+}
+
+impl<S: ChunkSink> Write for ChunkWriter<S> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // Chunk that doesn't fit into memory can't be buffered anyway.
+        let chunk_size = usize::try_from(self.chunk_size).unwrap_or(usize::max_value());
+        let room = chunk_size - self.buffer.len();
+        let len = room.min(buf.len());
+        self.buffer.extend_from_slice(&buf[..len]);
+        if len_u64(self.buffer.len()) == self.chunk_size {
+            self.flush_chunk()?;
+        }
+        Ok(len)
+    }
+
+    /// Doesn't do anything: a partial chunk can only be stored by `finish`.
+    fn flush(&mut self) -> io::Result<()> { Ok(()) }
+}
+
+/// Reads chunks described by a manifest back as a single stream, verifying checksums.
+pub struct ChunkReader<S: ChunkSource> {
+    source:   S,
+    checksum: ChecksumFn,
+    chunks:   std::vec::IntoIter<ChunkInfo>,
+    current:  io::Cursor<Vec<u8>>,
+}
+
+impl<S: ChunkSource> ChunkReader<S> {
+    /// Create reader for chunks from `manifest`. Manifest is validated first.
+    pub fn new(source: S, manifest: &SendManifest) -> Result<ChunkReader<S>> {
+        manifest.validate()?;
+        Ok(ChunkReader {
+            source,
+            checksum: fnv1a64,
+            chunks:   manifest.chunks().clone().into_iter(),
+            current:  io::Cursor::new(Vec::new()),
+        })
+    }
+
+    /// Use different checksum function. Must be the one chunks were written with.
+    pub fn with_checksum(mut self, checksum: ChecksumFn) -> ChunkReader<S> {
+        self.checksum = checksum;
+        self
+    }
+}
+
+impl<S: ChunkSource> Read for ChunkReader<S> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            let read = self.current.read(buf)?;
+            if read > 0 || buf.is_empty() {
+                return Ok(read);
+            }
+            let chunk = match self.chunks.next() {
+                Some(chunk) => chunk,
+                None => return Ok(0),
+            };
+            let data = self.source.get_chunk(*chunk.index())?;
+            if len_u64(data.len()) != *chunk.size() || &(self.checksum)(&data) != chunk.checksum() {
+                let msg = format!("chunk {} is corrupted", chunk.index());
+                return Err(io::Error::new(io::ErrorKind::InvalidData, msg));
+            }
+            self.current = io::Cursor::new(data);
+        }
+    }
+}
+
+/// Send a snapshot and store it in chunks. Returns manifest that describes the stream.
+///
+/// * `engine` - Engine to send with.
+/// * `snapshot` - Snapshot to send.
+/// * `from` - Snapshot or bookmark to send incremental stream from.
+/// * `flags` - Send flags.
+/// * `sink` - Where to put chunks.
+/// * `chunk_size` - Size of a chunk in bytes.
+pub fn send_chunked<E, S>(
+    engine: &E,
+    snapshot: PathBuf,
+    from: Option<PathBuf>,
+    flags: SendFlags,
+    sink: S,
+    chunk_size: u64,
+) -> Result<SendManifest>
+where
+    E: ZfsEngine,
+    S: ChunkSink + Send,
+{
+    resume_send_chunked(engine, snapshot, from, flags, sink, chunk_size, Vec::new())
+}
+
+/// Same as [`send_chunked`](fn.send_chunked.html), but skips chunks that are already stored.
+///
+/// * `stored` - Chunks from manifest of the interrupted upload.
+pub fn resume_send_chunked<E, S>(
+    engine: &E,
+    snapshot: PathBuf,
+    from: Option<PathBuf>,
+    flags: SendFlags,
+    sink: S,
+    chunk_size: u64,
+    stored: Vec<ChunkInfo>,
+) -> Result<SendManifest>
+where
+    E: ZfsEngine,
+    S: ChunkSink + Send,
+{
+    if chunk_size == 0 {
+        return Err(Error::invalid_input());
+    }
+    let (mut reader, writer) = pipe()?;
+    let mut chunk_writer = ChunkWriter::resume(sink, chunk_size, stored);
+
+    let (sent, copied) = thread::scope(|scope| {
+        let copier = scope.spawn(move || {
+            io::copy(&mut reader, &mut chunk_writer)?;
+            chunk_writer.finish()
+        });
+        // `writer` is dropped when send is done, so the copier sees EOF.
+        let sent = match from.clone() {
+            Some(from) => engine.send_incremental(snapshot.clone(), from, writer, flags),
+            None => engine.send_full(snapshot.clone(), writer, flags),
+        };
+        (sent, copier.join().expect("Chunk writer thread panicked"))
+    });
+    sent?;
+    let (_, size, chunks) = copied?;
+
+    let manifest = SendManifest::for_stream(engine, snapshot, from, flags, size)?;
+    Ok(manifest.with_chunks(chunk_size, chunks))
+}
+
+pub(crate) fn pipe() -> io::Result<(File, File)> {
+    let mut fds = [0; 2];
+    if unsafe { libc::pipe(fds.as_mut_ptr()) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(unsafe { (File::from_raw_fd(fds[0]), File::from_raw_fd(fds[1])) })
+}
+
+/// Length of a buffer as `u64`, saturated on platforms with pointers wider than 64 bits.
+fn len_u64(len: usize) -> u64 { u64::try_from(len).unwrap_or(u64::max_value()) }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::collections::BTreeMap;
+
+    #[derive(Default)]
+    struct InMemory {
+        chunks: BTreeMap<u64, Vec<u8>>,
+        puts:   usize,
+    }
+
+    impl ChunkSink for InMemory {
+        fn put_chunk(&mut self, index: u64, data: &[u8]) -> io::Result<()> {
+            self.puts += 1;
+            self.chunks.insert(index, data.to_vec());
+            Ok(())
+        }
+    }
+
+    impl ChunkSource for InMemory {
+        fn get_chunk(&mut self, index: u64) -> io::Result<Vec<u8>> {
+            self.chunks.get(&index).cloned().ok_or_else(|| io::Error::from(io::ErrorKind::NotFound))
+        }
+    }
+
+    fn manifest(size: u64, chunks: Vec<ChunkInfo>) -> SendManifest {
+        SendManifest::builder()
+            .snapshot("tank/data@a")
+            .snapshot_guid(1u64)
+            .size(size)
+            .build()
+            .unwrap()
+            .with_chunks(4, chunks)
+    }
+
+    #[test]
+    fn split_and_reassemble() {
+        let data: Vec<u8> = (0..10).collect();
+        let mut writer = ChunkWriter::new(InMemory::default(), 4);
+        writer.write_all(&data).unwrap();
+        let (mut sink, size, chunks) = writer.finish().unwrap();
+        assert_eq!(10, size);
+        assert_eq!(vec![4, 4, 2], chunks.iter().map(|c| *c.size()).collect::<Vec<_>>());
+        assert_eq!(3, sink.puts);
+
+        let manifest = manifest(size, chunks);
+        let mut out = Vec::new();
+        ChunkReader::new(&mut sink, &manifest).unwrap().read_to_end(&mut out).unwrap();
+        assert_eq!(data, out);
+
+        sink.chunks.insert(1, vec![0, 0, 0, 0]);
+        let mut out = Vec::new();
+        let err = ChunkReader::new(&mut sink, &manifest).unwrap().read_to_end(&mut out);
+        assert_eq!(io::ErrorKind::InvalidData, err.unwrap_err().kind());
+    }
+
+    #[test]
+    fn resume_skips_stored() {
+        let data: Vec<u8> = (0..10).collect();
+        let mut writer = ChunkWriter::new(InMemory::default(), 4);
+        writer.write_all(&data[..6]).unwrap();
+        let stored = writer.chunks().to_vec();
+        assert_eq!(1, stored.len());
+
+        let mut writer = ChunkWriter::resume(InMemory::default(), 4, stored.clone());
+        writer.write_all(&data).unwrap();
+        let (sink, _, chunks) = writer.finish().unwrap();
+        assert_eq!(2, sink.puts);
+        assert_eq!(stored[0], chunks[0]);
+
+        let mut writer = ChunkWriter::resume(InMemory::default(), 4, stored);
+        let err = writer.write_all(&[9, 9, 9, 9]).unwrap_err();
+        assert_eq!(io::ErrorKind::InvalidData, err.kind());
+    }
+}
@@ -1,6 +1,9 @@
-use crate::zfs::{lzc::ZfsLzc, open3::ZfsOpen3, BookmarkRequest, CreateDatasetRequest, DatasetKind,
+use crate::zfs::{delegation::Delegation, holds::Hold, lzc::ZfsLzc, open3::ZfsOpen3,
+                 rename::RenameOptions, BookmarkRequest, CreateDatasetRequest, DatasetKind,
                  DestroyTiming, Properties, Result, SendFlags, ZfsEngine};
-use std::{collections::HashMap, os::unix::io::AsRawFd, path::PathBuf};
+use std::{collections::HashMap,
+          os::unix::io::{AsRawFd, RawFd},
+          path::PathBuf};
 
 /// Handy wrapper that delegates your call to correct implementation.
 pub struct DelegatingZfsEngine {
@@ -65,6 +68,74 @@ impl ZfsEngine for DelegatingZfsEngine {
         self.open3.read_properties(path)
     }
 
+    fn allow<N: Into<PathBuf>>(&self, dataset: N, delegation: &Delegation) -> Result<()> {
+        self.open3.allow(dataset, delegation)
+    }
+
+    fn unallow<N: Into<PathBuf>>(&self, dataset: N, delegation: &Delegation) -> Result<()> {
+        self.open3.unallow(dataset, delegation)
+    }
+
+    fn permissions<N: Into<PathBuf>>(&self, dataset: N) -> Result<Vec<Delegation>> {
+        self.open3.permissions(dataset)
+    }
+
+    fn mount<N: Into<PathBuf>>(&self, name: N) -> Result<()> { self.open3.mount(name) }
+
+    fn unmount<N: Into<PathBuf>>(&self, name: N) -> Result<()> { self.open3.unmount(name) }
+
+    fn rename<N: Into<PathBuf>, M: Into<PathBuf>>(
+        &self,
+        name: N,
+        new_name: M,
+        options: RenameOptions,
+    ) -> Result<()> {
+        if *options.no_remount() {
+            self.lzc.rename(name, new_name, options)
+        } else {
+            self.open3.rename(name, new_name, options)
+        }
+    }
+
+    fn clone_snapshot<N: Into<PathBuf>, M: Into<PathBuf>>(
+        &self,
+        snapshot: N,
+        target: M,
+        properties: Option<HashMap<String, String>>,
+    ) -> Result<()> {
+        self.open3.clone_snapshot(snapshot, target, properties)
+    }
+
+    fn promote<N: Into<PathBuf>>(&self, name: N) -> Result<()> { self.lzc.promote(name) }
+
+    fn rollback<N: Into<PathBuf>>(&self, name: N, destroy_newer: bool) -> Result<PathBuf> {
+        // libzfs_core can't list snapshots to destroy.
+        if destroy_newer {
+            self.open3.rollback(name, destroy_newer)
+        } else {
+            self.lzc.rollback(name, destroy_newer)
+        }
+    }
+
+    fn hold<N: Into<PathBuf>>(
+        &self,
+        snapshot: N,
+        tag: &str,
+        cleanup_fd: Option<RawFd>,
+    ) -> Result<()> {
+        self.lzc.hold(snapshot, tag, cleanup_fd)
+    }
+
+    fn release<N: Into<PathBuf>>(&self, snapshot: N, tag: &str) -> Result<()> {
+        self.lzc.release(snapshot, tag)
+    }
+
+    fn holds<N: Into<PathBuf>>(&self, snapshot: N) -> Result<Vec<Hold>> { self.lzc.holds(snapshot) }
+
+    fn set_property<N: Into<PathBuf>>(&self, name: N, key: &str, value: &str) -> Result<()> {
+        self.open3.set_property(name, key, value)
+    }
+
     fn send_full<N: Into<PathBuf>, FD: AsRawFd>(
         &self,
         path: N,
@@ -83,4 +154,22 @@ impl ZfsEngine for DelegatingZfsEngine {
     ) -> Result<()> {
         self.lzc.send_incremental(path, from, fd, flags)
     }
+
+    fn send_space<N: Into<PathBuf>>(
+        &self,
+        path: N,
+        from: Option<PathBuf>,
+        flags: SendFlags,
+    ) -> Result<u64> {
+        self.lzc.send_space(path, from, flags)
+    }
+
+    fn receive<N: Into<PathBuf>, FD: AsRawFd>(
+        &self,
+        snapshot: N,
+        fd: FD,
+        force: bool,
+    ) -> Result<()> {
+        self.lzc.receive(snapshot, fd, force)
+    }
 }
@@ -0,0 +1,467 @@
+//! Delegated administration (`zfs allow`).
+//!
+//! Permission names and the permissions they depend on are encoded once here. For example,
+//! `create` is useless without `mount`, and `clone` also needs `create` on the target. Templates
+//! like [`PermissionSet::backup_operator`](struct.PermissionSet.html#method.backup_operator) come
+//! with their dependencies included, and
+//! [`PermissionSet::missing_dependencies`](struct.PermissionSet.html#method.missing_dependencies)
+//! tells what a hand-written set lacks.
+//!
+//! ### Usage
+//! ```rust,no_run
+//! use libzetta::zfs::{delegation::{Delegate, Delegation, PermissionSet},
+//!                     DelegatingZfsEngine, ZfsEngine};
+//!
+//! let engine = DelegatingZfsEngine::new().unwrap();
+//! let backup = Delegate::User("backup".into());
+//! let delegation = Delegation::new(backup, PermissionSet::backup_operator());
+//! engine.allow("tank/usr", &delegation).unwrap();
+//!
+//! // Audit: is everything still there?
+//! let granted = engine.permissions("tank/usr").unwrap();
+//! assert!(granted.iter().any(|d| d.covers(&delegation)));
+//! ```
+use std::{collections::BTreeSet, fmt, str::FromStr};
+
+/// Single permission that can be delegated.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Permission {
+    /// Grant permissions the user has to others.
+    Allow,
+    /// Create bookmarks.
+    Bookmark,
+    /// Change encryption key.
+    ChangeKey,
+    /// Clone snapshots. Needs `create` and `mount` on the origin's parent.
+    Clone,
+    /// Create descendant datasets. Needs `mount`.
+    Create,
+    /// Destroy datasets. Needs `mount`.
+    Destroy,
+    /// List differences between snapshots.
+    Diff,
+    /// Add user holds on snapshots.
+    Hold,
+    /// Load encryption key.
+    LoadKey,
+    /// Mount and unmount datasets.
+    Mount,
+    /// Promote clones. Needs `mount` and `promote` on the origin.
+    Promote,
+    /// Receive streams. Needs `create` and `mount`.
+    Receive,
+    /// Release user holds.
+    Release,
+    /// Rename datasets. Needs `create` and `mount` on the new parent.
+    Rename,
+    /// Rollback to snapshots. Needs `mount`.
+    Rollback,
+    /// Send snapshots.
+    Send,
+    /// Share and unshare datasets.
+    Share,
+    /// Take snapshots. Needs `mount`.
+    Snapshot,
+    /// Set any user property.
+    UserProp,
+    /// Permission to change a single native property, e.g. `compression`.
+    Property(String),
+    /// Named permission set, e.g. `@backup`.
+    Set(String),
+}
+
+impl Permission {
+    /// Permissions ZFS also requires for this one to be useful.
+    #[allow(clippy::wildcard_enum_match_arm)]
+    pub fn requires(&self) -> &'static [Permission] {
+        match self {
+            Permission::Create
+            | Permission::Destroy
+            | Permission::Rollback
+            | Permission::Snapshot => &[Permission::Mount],
+            Permission::Clone | Permission::Receive | Permission::Rename => {
+                &[Permission::Create, Permission::Mount]
+            },
+            Permission::Promote => &[Permission::Mount],
+            _ => &[],
+        }
+    }
+
+    /// Name as understood by `zfs allow`.
+    pub fn as_str(&self) -> &str {
+        match self {
+            Permission::Allow => "allow",
+            Permission::Bookmark => "bookmark",
+            Permission::ChangeKey => "change-key",
+            Permission::Clone => "clone",
+            Permission::Create => "create",
+            Permission::Destroy => "destroy",
+            Permission::Diff => "diff",
+            Permission::Hold => "hold",
+            Permission::LoadKey => "load-key",
+            Permission::Mount => "mount",
+            Permission::Promote => "promote",
+            Permission::Receive => "receive",
+            Permission::Release => "release",
+            Permission::Rename => "rename",
+            Permission::Rollback => "rollback",
+            Permission::Send => "send",
+            Permission::Share => "share",
+            Permission::Snapshot => "snapshot",
+            Permission::UserProp => "userprop",
+            Permission::Property(name) | Permission::Set(name) => name,
+        }
+    }
+}
+
+impl FromStr for Permission {
+    type Err = std::convert::Infallible;
+
+    fn from_str(s: &str) -> Result<Permission, Self::Err> {
+        Ok(match s {
+            "allow" => Permission::Allow,
+            "bookmark" => Permission::Bookmark,
+            "change-key" => Permission::ChangeKey,
+            "clone" => Permission::Clone,
+            "create" => Permission::Create,
+            "destroy" => Permission::Destroy,
+            "diff" => Permission::Diff,
+            "hold" => Permission::Hold,
+            "load-key" => Permission::LoadKey,
+            "mount" => Permission::Mount,
+            "promote" => Permission::Promote,
+            "receive" => Permission::Receive,
+            "release" => Permission::Release,
+            "rename" => Permission::Rename,
+            "rollback" => Permission::Rollback,
+            "send" => Permission::Send,
+            "share" => Permission::Share,
+            "snapshot" => Permission::Snapshot,
+            "userprop" => Permission::UserProp,
+            set if set.starts_with('@') => Permission::Set(set.into()),
+            property => Permission::Property(property.into()),
+        })
+    }
+}
+
+impl fmt::Display for Permission {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result { f.write_str(self.as_str()) }
+}
+
+/// Set of permissions.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PermissionSet {
+    permissions: BTreeSet<Permission>,
+}
+
+impl PermissionSet {
+    /// Create a set out of given permissions. Dependencies are not added, see
+    /// [`with_dependencies`](#method.with_dependencies).
+    pub fn new<I: IntoIterator<Item = Permission>>(permissions: I) -> PermissionSet {
+        PermissionSet { permissions: permissions.into_iter().collect() }
+    }
+
+    /// Take snapshots and nothing else.
+    pub fn snapshot_only() -> PermissionSet {
+        PermissionSet::new(vec![Permission::Snapshot]).with_dependencies()
+    }
+
+    /// Take, hold and send snapshots. Enough for `zfs send` based backups, but can't destroy
+    /// anything.
+    pub fn backup_operator() -> PermissionSet {
+        PermissionSet::new(vec![
+            Permission::Snapshot,
+            Permission::Send,
+            Permission::Hold,
+            Permission::Release,
+            Permission::Bookmark,
+        ])
+        .with_dependencies()
+    }
+
+    /// Everything needed to manage datasets below the delegated one, without being able to pass
+    /// permissions on.
+    pub fn full_dataset_admin() -> PermissionSet {
+        PermissionSet::new(vec![
+            Permission::Bookmark,
+            Permission::Clone,
+            Permission::Create,
+            Permission::Destroy,
+            Permission::Diff,
+            Permission::Hold,
+            Permission::Mount,
+            Permission::Promote,
+            Permission::Receive,
+            Permission::Release,
+            Permission::Rename,
+            Permission::Rollback,
+            Permission::Send,
+            Permission::Share,
+            Permission::Snapshot,
+            Permission::UserProp,
+        ])
+        .with_dependencies()
+    }
+
+    /// Add permissions required by permissions already in the set.
+    pub fn with_dependencies(mut self) -> PermissionSet {
+        let missing = self.missing_dependencies();
+        self.permissions.extend(missing);
+        self
+    }
+
+    /// Permissions that are required by permissions in the set, but are not in the set.
+    pub fn missing_dependencies(&self) -> BTreeSet<Permission> {
+        self.permissions
+            .iter()
+            .flat_map(Permission::requires)
+            .filter(|p| !self.permissions.contains(*p))
+            .cloned()
+            .collect()
+    }
+
+    /// Whether every permission of `other` is in this set.
+    pub fn contains_all(&self, other: &PermissionSet) -> bool {
+        self.permissions.is_superset(&other.permissions)
+    }
+
+    /// Whether set has a permission.
+    pub fn contains(&self, permission: &Permission) -> bool {
+        self.permissions.contains(permission)
+    }
+
+    /// Iterate over permissions in the set.
+    pub fn iter(&self) -> impl Iterator<Item = &Permission> { self.permissions.iter() }
+
+    /// Whether set is empty.
+    pub fn is_empty(&self) -> bool { self.permissions.is_empty() }
+
+    /// Comma separated list as used by `zfs allow`.
+    pub fn to_arg(&self) -> String {
+        self.permissions.iter().map(Permission::as_str).collect::<Vec<_>>().join(",")
+    }
+}
+
+impl FromStr for PermissionSet {
+    type Err = std::convert::Infallible;
+
+    fn from_str(s: &str) -> Result<PermissionSet, Self::Err> {
+        Ok(PermissionSet::new(
+            s.split(',').map(str::trim).filter(|p| !p.is_empty()).filter_map(|p| p.parse().ok()),
+        ))
+    }
+}
+
+/// Who gets the permissions.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Delegate {
+    /// A user.
+    User(String),
+    /// A group.
+    Group(String),
+    /// Everyone.
+    Everyone,
+}
+
+/// Where permissions apply.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DelegationScope {
+    /// Only the dataset itself (`-l`).
+    Local,
+    /// Only descendants of the dataset (`-d`).
+    Descendant,
+    /// The dataset and its descendants. Default.
+    LocalAndDescendant,
+}
+
+impl Default for DelegationScope {
+    fn default() -> DelegationScope { DelegationScope::LocalAndDescendant }
+}
+
+/// Permissions granted to someone on a dataset.
+#[derive(Debug, Clone, PartialEq, Eq, Getters)]
+#[get = "pub"]
+pub struct Delegation {
+    /// Who gets permissions.
+    delegate:    Delegate,
+    /// Where permissions apply.
+    scope:       DelegationScope,
+    /// Permissions.
+    permissions: PermissionSet,
+}
+
+impl Delegation {
+    /// Delegate permissions on the dataset and its descendants.
+    pub fn new(delegate: Delegate, permissions: PermissionSet) -> Delegation {
+        Delegation { delegate, scope: DelegationScope::default(), permissions }
+    }
+
+    /// Change where permissions apply.
+    pub fn with_scope(mut self, scope: DelegationScope) -> Delegation {
+        self.scope = scope;
+        self
+    }
+
+    /// Whether this delegation grants everything `other` does to the same delegate with the same
+    /// scope.
+    pub fn covers(&self, other: &Delegation) -> bool {
+        self.delegate == other.delegate
+            && self.scope == other.scope
+            && self.permissions.contains_all(&other.permissions)
+    }
+
+    /// Arguments of `zfs allow`/`zfs unallow` without the command and the dataset.
+    pub fn to_args(&self) -> Vec<String> {
+        let mut args = Vec::with_capacity(4);
+        match self.scope {
+            DelegationScope::Local => args.push(String::from("-l")),
+            DelegationScope::Descendant => args.push(String::from("-d")),
+            DelegationScope::LocalAndDescendant => {},
+        }
+        match &self.delegate {
+            Delegate::User(name) => {
+                args.push(String::from("-u"));
+                args.push(name.clone());
+            },
+            Delegate::Group(name) => {
+                args.push(String::from("-g"));
+                args.push(name.clone());
+            },
+            Delegate::Everyone => args.push(String::from("-e")),
+        }
+        args.push(self.permissions.to_arg());
+        args
+    }
+
+    /// Parse output of `zfs allow <dataset>`. Only permissions set on the dataset itself are
+    /// returned, inherited ones and permission set definitions are skipped.
+    pub fn parse_allow(stdout: &str) -> Vec<Delegation> {
+        let mut delegations = Vec::new();
+        let mut scope = None;
+        let mut on_self = false;
+        let mut first_header = true;
+        for line in stdout.lines() {
+            if line.starts_with("---- Permissions on ") {
+                // `zfs allow` lists the dataset itself first, then its ancestors.
+                on_self = first_header;
+                first_header = false;
+                continue;
+            }
+            if !line.starts_with(char::is_whitespace) {
+                scope = match line.trim_end() {
+                    "Local+Descendent permissions:" => Some(DelegationScope::LocalAndDescendant),
+                    "Local permissions:" => Some(DelegationScope::Local),
+                    "Descendent permissions:" => Some(DelegationScope::Descendant),
+                    _ => None,
+                };
+                continue;
+            }
+            let scope = match scope {
+                Some(scope) if on_self => scope,
+                _ => continue,
+            };
+            let mut parts = line.split_whitespace();
+            let delegate = match (parts.next(), parts.next()) {
+                (Some("user"), Some(name)) => Delegate::User(name.into()),
+                (Some("group"), Some(name)) => Delegate::Group(name.into()),
+                (Some("everyone"), Some(permissions)) => {
+                    delegations.push(Delegation {
+                        delegate: Delegate::Everyone,
+                        scope,
+                        permissions: permissions.parse().unwrap_or_default(),
+                    });
+                    continue;
+                },
+                _ => continue,
+            };
+            if let Some(permissions) = parts.next() {
+                delegations.push(Delegation {
+                    delegate,
+                    scope,
+                    permissions: permissions.parse().unwrap_or_default(),
+                });
+            }
+        }
+        delegations
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn dependencies() {
+        let set = PermissionSet::new(vec![Permission::Clone, Permission::Send]);
+        let missing: Vec<_> = set.missing_dependencies().into_iter().collect();
+        assert_eq!(vec![Permission::Create, Permission::Mount], missing);
+
+        let set = set.with_dependencies();
+        assert!(set.missing_dependencies().is_empty());
+        assert_eq!("clone,create,mount,send", set.to_arg());
+
+        for template in &[
+            PermissionSet::snapshot_only(),
+            PermissionSet::backup_operator(),
+            PermissionSet::full_dataset_admin(),
+        ] {
+            assert!(template.missing_dependencies().is_empty());
+            assert!(!template.contains(&Permission::Allow));
+        }
+        assert!(!PermissionSet::backup_operator().contains(&Permission::Destroy));
+        assert_eq!("mount,snapshot", PermissionSet::snapshot_only().to_arg());
+    }
+
+    #[test]
+    fn parsing_names() {
+        let set: PermissionSet = "create, mount,compression,@backup,load-key".parse().unwrap();
+        assert!(set.contains(&Permission::Property("compression".into())));
+        assert!(set.contains(&Permission::Set("@backup".into())));
+        assert!(set.contains(&Permission::LoadKey));
+        assert_eq!("create,load-key,mount,compression,@backup", set.to_arg());
+    }
+
+    #[test]
+    fn args() {
+        let backup = Delegate::User("backup".into());
+        let delegation = Delegation::new(backup, PermissionSet::snapshot_only())
+            .with_scope(DelegationScope::Local);
+        assert_eq!(vec!["-l", "-u", "backup", "mount,snapshot"], delegation.to_args());
+
+        let hold = PermissionSet::new(vec![Permission::Hold]);
+        let delegation = Delegation::new(Delegate::Everyone, hold);
+        assert_eq!(vec!["-e", "hold"], delegation.to_args());
+    }
+
+    #[test]
+    fn parsing_allow() {
+        let stdout = "---- Permissions on tank/home ----------------------------------------\n\
+                      Permission sets:\n\
+                      \t@pset create,destroy\n\
+                      Local+Descendent permissions:\n\
+                      \tuser backup mount,send,snapshot\n\
+                      \tgroup staff mount\n\
+                      \teveryone hold\n\
+                      Local permissions:\n\
+                      \tuser bob snapshot\n\
+                      ---- Permissions on tank --------------------------------------------\n\
+                      Local+Descendent permissions:\n\
+                      \tuser root destroy\n";
+        let delegations = Delegation::parse_allow(stdout);
+        assert_eq!(4, delegations.len());
+        assert_eq!(&Delegate::User("backup".into()), delegations[0].delegate());
+        assert_eq!(&DelegationScope::LocalAndDescendant, delegations[0].scope());
+        assert_eq!(&Delegate::Everyone, delegations[2].delegate());
+        assert_eq!(&DelegationScope::Local, delegations[3].scope());
+
+        let expected = Delegation::new(
+            Delegate::User("backup".into()),
+            PermissionSet::new(vec![Permission::Send, Permission::Snapshot]),
+        );
+        assert!(delegations[0].covers(&expected));
+        assert!(!delegations[3].covers(&expected));
+        let backup = Delegate::User("backup".into());
+        let template = Delegation::new(backup, PermissionSet::backup_operator());
+        assert!(!delegations[0].covers(&template));
+    }
+}
@@ -9,6 +9,8 @@ pub enum DatasetKind {
     Volume,
     #[strum(serialize = "snapshot")]
     Snapshot,
+    #[strum(serialize = "bookmark")]
+    Bookmark,
 }
 
 impl Default for DatasetKind {
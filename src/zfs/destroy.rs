@@ -0,0 +1,214 @@
+//! Recursive destroy of dataset trees.
+//!
+//! libzfs_core only destroys leaves: a filesystem with children or snapshots and a snapshot with
+//! clones are busy.
+//! [`ZfsEngine::destroy_recursive`](../trait.ZfsEngine.html#method.destroy_recursive) does what
+//! `zfs destroy -r` does: it walks the tree and destroys dependents first - children before
+//! parents, snapshots before their filesystem and clones before their origin snapshot.
+//! Clones outside of the tree are only destroyed with
+//! [`dependents`](struct.DestroyOptionsBuilder.html#method.dependents), like `zfs destroy -R`
+//! does, otherwise the origin snapshot is reported as `DatasetBusy`. A snapshot can also be
+//! destroyed with [`defer`](struct.DestroyOptionsBuilder.html#method.defer), like
+//! `zfs destroy -d`: if it's held or cloned, it's marked with `defer_destroy` and goes away with
+//! the last hold or clone.
+//!
+//! ### Usage
+//! ```rust,no_run
+//! use libzetta::zfs::{destroy::DestroyOptions, DelegatingZfsEngine, ZfsEngine};
+//!
+//! let engine = DelegatingZfsEngine::new().unwrap();
+//! let options = DestroyOptions::builder().dependents(true).dry_run(true).build().unwrap();
+//! for dataset in engine.destroy_recursive("tank/usr", options).unwrap() {
+//!     println!("would destroy {}", dataset.display());
+//! }
+//! ```
+use std::{collections::{HashMap, HashSet},
+          path::{Path, PathBuf}};
+
+use crate::zfs::{DestroyTiming, Error, PathExt, Properties, Result, ZfsEngine};
+
+/// How far destroy goes.
+#[derive(Builder, Debug, Clone, Copy, Default, Getters, PartialEq, Eq)]
+#[builder(setter(into))]
+#[get = "pub"]
+pub struct DestroyOptions {
+    /// Destroy clones of snapshots in the tree with their own trees too (`-R`).
+    #[builder(default)]
+    dependents: bool,
+    /// Only work out what would be destroyed (`-n`).
+    #[builder(default)]
+    dry_run:    bool,
+    /// Mark held or cloned snapshots for deferred destruction instead of failing (`-d`). Only
+    /// valid for snapshots.
+    #[builder(default)]
+    defer:      bool,
+}
+
+impl DestroyOptions {
+    /// A preferred way to create this.
+    pub fn builder() -> DestroyOptionsBuilder { DestroyOptionsBuilder::default() }
+}
+
+/// Dataset a name can't outlive: filesystem of a snapshot or parent of a dataset.
+fn holder_of(name: &Path) -> Option<PathBuf> {
+    if name.is_snapshot() {
+        name.to_string_lossy().split('@').next().map(PathBuf::from)
+    } else {
+        name.parent().filter(|parent| !parent.as_os_str().is_empty()).map(PathBuf::from)
+    }
+}
+
+/// Everything `root` consists of and clones of its snapshots. Clones outside of `root` are
+/// followed with their trees if `dependents` is set and left alone if `defer` is.
+fn collect<E: ZfsEngine + ?Sized>(
+    engine: &E,
+    root: &Path,
+    options: DestroyOptions,
+) -> Result<(Vec<PathBuf>, HashMap<PathBuf, Vec<PathBuf>>)> {
+    let mut datasets: Vec<PathBuf> = Vec::new();
+    let mut clones: HashMap<PathBuf, Vec<PathBuf>> = HashMap::new();
+    let mut pending = vec![root.to_path_buf()];
+    while let Some(top) = pending.pop() {
+        let snapshots = if top.is_snapshot() {
+            vec![top.clone()]
+        } else {
+            datasets.extend(engine.list_filesystems(top.clone())?);
+            datasets.extend(engine.list_volumes(top.clone())?);
+            engine.list_snapshots(top.clone())?
+        };
+        for snapshot in snapshots {
+            if let Properties::Snapshot(props) = engine.read_properties(snapshot.clone())? {
+                for clone in props.clones().iter().flatten() {
+                    let known = datasets.contains(clone) || pending.contains(clone);
+                    if !clone.starts_with(root) && !known {
+                        if !options.dependents {
+                            if options.defer {
+                                continue;
+                            }
+                            return Err(Error::DatasetBusy(snapshot));
+                        }
+                        pending.push(clone.clone());
+                    }
+                    clones.entry(snapshot.clone()).or_default().push(clone.clone());
+                }
+            }
+            datasets.push(snapshot);
+        }
+    }
+    Ok((datasets, clones))
+}
+
+/// Order to destroy `datasets` in so that nothing is destroyed before its dependents.
+fn destroy_order(
+    root: &Path,
+    datasets: &[PathBuf],
+    clones: &HashMap<PathBuf, Vec<PathBuf>>,
+) -> Vec<PathBuf> {
+    let mut dependents: HashMap<PathBuf, Vec<PathBuf>> = clones.clone();
+    for dataset in datasets {
+        if let Some(holder) = holder_of(dataset).filter(|holder| datasets.contains(holder)) {
+            dependents.entry(holder).or_default().push(dataset.clone());
+        }
+    }
+
+    fn visit(
+        name: &Path,
+        dependents: &HashMap<PathBuf, Vec<PathBuf>>,
+        seen: &mut HashSet<PathBuf>,
+        order: &mut Vec<PathBuf>,
+    ) {
+        if !seen.insert(name.to_path_buf()) {
+            return;
+        }
+        for dependent in dependents.get(name).into_iter().flatten() {
+            visit(dependent, dependents, seen, order);
+        }
+        order.push(name.to_path_buf());
+    }
+
+    let mut order = Vec::with_capacity(datasets.len());
+    visit(root, &dependents, &mut HashSet::new(), &mut order);
+    order
+}
+
+/// Datasets to destroy for `zfs destroy -r` of `root` (`-R` with `dependents`), in order.
+pub(crate) fn plan<E: ZfsEngine + ?Sized>(
+    engine: &E,
+    root: &Path,
+    options: DestroyOptions,
+) -> Result<Vec<PathBuf>> {
+    if options.defer && !root.is_snapshot() {
+        return Err(Error::invalid_input());
+    }
+    let (datasets, clones) = collect(engine, root, options)?;
+    Ok(destroy_order(root, &datasets, &clones))
+}
+
+/// Destroy datasets of a plan in order. Snapshots are only marked with `defer`.
+pub(crate) fn execute<E: ZfsEngine + ?Sized>(
+    engine: &E,
+    order: &[PathBuf],
+    options: DestroyOptions,
+) -> Result<()> {
+    for dataset in order {
+        if options.defer && dataset.is_snapshot() {
+            engine.destroy_snapshots(&[dataset.clone()], DestroyTiming::Defer)?;
+        } else {
+            engine.destroy(dataset.clone())?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn paths(values: &[&str]) -> Vec<PathBuf> { values.iter().map(PathBuf::from).collect() }
+
+    #[test]
+    fn order() {
+        let datasets = paths(&[
+            "tank/a",
+            "tank/a/b",
+            "tank/a/b/c",
+            "tank/a/d",
+            "tank/a@1",
+            "tank/a/b@1",
+            "tank/a/b/c@1",
+            "tank/x",
+            "tank/x@1",
+        ]);
+        let mut clones = HashMap::new();
+        // Clone inside of the tree that is shallower than its origin.
+        clones.insert(PathBuf::from("tank/a/b/c@1"), paths(&["tank/a/d"]));
+        clones.insert(PathBuf::from("tank/a@1"), paths(&["tank/x"]));
+
+        let order = destroy_order(Path::new("tank/a"), &datasets, &clones);
+        let position = |name: &str| order.iter().position(|d| d == Path::new(name)).unwrap();
+        assert_eq!(datasets.len(), order.len());
+        assert_eq!(Path::new("tank/a"), order.last().unwrap());
+        assert!(position("tank/a/b/c") < position("tank/a/b"));
+        assert!(position("tank/a/b/c@1") < position("tank/a/b/c"));
+        assert!(position("tank/a/d") < position("tank/a/b/c@1"));
+        assert!(position("tank/x@1") < position("tank/x"));
+        assert!(position("tank/x") < position("tank/a@1"));
+    }
+
+    #[test]
+    fn defer_only_snapshots() {
+        struct Engine;
+        impl ZfsEngine for Engine {}
+
+        let options = DestroyOptions::builder().defer(true).build().unwrap();
+        let err = plan(&Engine, Path::new("tank/a"), options).unwrap_err();
+        assert_eq!(Error::invalid_input(), err);
+    }
+
+    #[test]
+    fn holders() {
+        assert_eq!(Some(PathBuf::from("tank/a")), holder_of(Path::new("tank/a@1")));
+        assert_eq!(Some(PathBuf::from("tank")), holder_of(Path::new("tank/a")));
+        assert_eq!(None, holder_of(Path::new("tank")));
+    }
+}
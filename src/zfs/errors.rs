@@ -1,7 +1,8 @@
-use crate::parsers::zfs::{Rule, ZfsParser};
-use libnv::nvpair::NvList;
+use crate::{args::ArgError,
+            parsers::zfs::{Rule, ZfsParser}};
+use libnv::nvpair::{NvList, Value};
 use pest::Parser;
-use std::{borrow::Cow, io, path::PathBuf};
+use std::{borrow::Cow, collections::HashMap, io, path::PathBuf};
 
 pub type Result<T, E = Error> = std::result::Result<T, E>;
 pub type ValidationResult<T = (), E = ValidationError> = std::result::Result<T, E>;
@@ -31,7 +32,97 @@ quick_error! {
         MultiOpError(err: NvList) {
             from()
         }
+        /// Batch operation failed for some of the names and nothing was done. Errors by name.
+        BatchFailed(errors: HashMap<PathBuf, Error>) {
+            display("operation failed for {} datasets", errors.len())
+        }
         Unimplemented {}
+        InvalidManifest(reason: String) {}
+        /// Dataset with this name already exists.
+        DatasetExists(dataset: PathBuf) {
+            display("dataset {} already exists", dataset.display())
+        }
+        /// Dataset can't be destroyed: it's mounted, held or has children, snapshots or clones.
+        DatasetBusy(dataset: PathBuf) {
+            display("dataset {} is busy", dataset.display())
+        }
+        /// Filesystem would be mounted over a path that is already in use.
+        MountpointCollision { dataset: PathBuf, mountpoint: PathBuf } {
+            display("{} can't be mounted at {}: path is in use",
+                    dataset.display(), mountpoint.display())
+        }
+        /// Operation failed and undoing it failed too. Some filesystems might be left unmounted or
+        /// under the new name.
+        RollbackFailed { error: String, rollback: String } {
+            display("{}, rollback failed: {}", error, rollback)
+        }
+        /// Operation was stopped by [`Middleware`](../middleware/trait.Middleware.html).
+        Denied(reason: String) {
+            display("operation denied: {}", reason)
+        }
+        /// Destination doesn't have enough space to receive the stream.
+        InsufficientSpace { needed: u64, available: u64 } {
+            display("insufficient space: need {} bytes, {} available", needed, available)
+        }
+        /// Property name or value can't be passed to `zfs`. See [`args`](../../args/index.html).
+        InvalidProperty(err: ArgError) {
+            cause(err)
+            display("{}", err)
+            from()
+        }
+        /// Snapshot already has a user hold with this tag.
+        HoldExists { snapshot: PathBuf, tag: String } {
+            display("{} already has hold {}", snapshot.display(), tag)
+        }
+        /// Snapshot has no user hold with this tag.
+        HoldNotFound { snapshot: PathBuf, tag: String } {
+            display("{} has no hold {}", snapshot.display(), tag)
+        }
+        /// Operation works only on volumes.
+        NotAVolume(dataset: PathBuf) {
+            display("{} is not a volume", dataset.display())
+        }
+        /// Snapshot isn't the most recent one of its dataset.
+        NotLatestSnapshot(snapshot: PathBuf) {
+            display("{} is not the most recent snapshot", snapshot.display())
+        }
+        /// Dataset has no snapshots to roll back to.
+        NoSnapshots(dataset: PathBuf) {
+            display("{} has no snapshots", dataset.display())
+        }
+        /// Operation works only on clones.
+        NotAClone(dataset: PathBuf) {
+            display("{} is not a clone", dataset.display())
+        }
+        /// Volume would get smaller and shrinking wasn't allowed.
+        VolumeShrink { current: u64, requested: u64 } {
+            display("refusing to shrink volume from {} to {} bytes", current, requested)
+        }
+        /// Volume size is not a multiple of `volblocksize`.
+        InvalidVolumeSize { size: u64, block_size: u64 } {
+            display("volume size {} is not a multiple of block size {}", size, block_size)
+        }
+        /// Send stream is damaged or not a send stream at all.
+        InvalidStream(reason: String) {
+            display("invalid send stream: {}", reason)
+        }
+        /// Snapshot an incremental stream is based on doesn't exist on the destination.
+        MissingIncrementalSource { dataset: PathBuf, guid: u64 } {
+            display("{} has no snapshot with guid {} to receive onto", dataset.display(), guid)
+        }
+        /// Device node didn't appear in time.
+        DeviceNotReady(device: PathBuf) {
+            display("device {} did not appear", device.display())
+        }
+        /// Property value printed by `zfs` can't be parsed.
+        ParseError { property: String, value: String } {
+            display("failed to parse {} value {:?}", property, value)
+        }
+        /// Numeric property value doesn't fit into its type. See
+        /// [`ZfsOpen3::with_overflow`](../struct.ZfsOpen3.html#method.with_overflow).
+        Overflow(value: String) {
+            display("value {} is out of range", value)
+        }
     }
 }
 
@@ -50,7 +141,29 @@ impl Error {
             Error::Unknown | Error::UnknownSoFar(_) => ErrorKind::Unknown,
             Error::ValidationErrors(_) => ErrorKind::ValidationErrors,
             Error::MultiOpError(_) => ErrorKind::MultiOpError,
+            Error::BatchFailed(_) => ErrorKind::BatchFailed,
             Error::Unimplemented => ErrorKind::Unimplemented,
+            Error::InvalidManifest(_) => ErrorKind::InvalidManifest,
+            Error::InsufficientSpace { .. } => ErrorKind::InsufficientSpace,
+            Error::DatasetExists(_) => ErrorKind::DatasetExists,
+            Error::DatasetBusy(_) => ErrorKind::DatasetBusy,
+            Error::MountpointCollision { .. } => ErrorKind::MountpointCollision,
+            Error::RollbackFailed { .. } => ErrorKind::RollbackFailed,
+            Error::Denied(_) => ErrorKind::Denied,
+            Error::InvalidProperty(_) => ErrorKind::InvalidProperty,
+            Error::HoldExists { .. } => ErrorKind::HoldExists,
+            Error::HoldNotFound { .. } => ErrorKind::HoldNotFound,
+            Error::NotAVolume(_) => ErrorKind::NotAVolume,
+            Error::NotAClone(_) => ErrorKind::NotAClone,
+            Error::NotLatestSnapshot(_) => ErrorKind::NotLatestSnapshot,
+            Error::NoSnapshots(_) => ErrorKind::NoSnapshots,
+            Error::VolumeShrink { .. } => ErrorKind::VolumeShrink,
+            Error::InvalidVolumeSize { .. } => ErrorKind::InvalidVolumeSize,
+            Error::InvalidStream(_) => ErrorKind::InvalidStream,
+            Error::MissingIncrementalSource { .. } => ErrorKind::MissingIncrementalSource,
+            Error::DeviceNotReady(_) => ErrorKind::DeviceNotReady,
+            Error::ParseError { .. } => ErrorKind::ParseError,
+            Error::Overflow(_) => ErrorKind::Overflow,
         }
     }
 
@@ -68,6 +181,18 @@ impl Error {
                     let dataset_name_pair = error_pair.into_inner().next().unwrap();
                     Error::DatasetNotFound(PathBuf::from(dataset_name_pair.as_str()))
                 },
+                Rule::dataset_busy => {
+                    let dataset_name_pair = error_pair.into_inner().next().unwrap();
+                    Error::DatasetBusy(PathBuf::from(dataset_name_pair.as_str()))
+                },
+                Rule::not_a_clone => {
+                    let dataset_name_pair = error_pair.into_inner().next().unwrap();
+                    Error::NotAClone(PathBuf::from(dataset_name_pair.as_str()))
+                },
+                Rule::not_latest_snapshot => {
+                    let dataset_name_pair = error_pair.into_inner().next().unwrap();
+                    Error::NotLatestSnapshot(PathBuf::from(dataset_name_pair.as_str()))
+                },
                 _ => Self::unknown_so_far(stderr),
             }
         } else {
@@ -75,6 +200,50 @@ impl Error {
         }
     }
 
+    /// Error for `errno` returned by libzfs_core for an operation on `dataset`.
+    pub(crate) fn from_errno(errno: i32, dataset: PathBuf) -> Self {
+        match errno {
+            libc::EEXIST => Error::DatasetExists(dataset),
+            libc::ENOENT => Error::DatasetNotFound(dataset),
+            _ => Error::Io(io::Error::from_raw_os_error(errno)),
+        }
+    }
+
+    /// Error for `errno` returned by libzfs_core when destroying `dataset`. Kernel uses `EEXIST`
+    /// for datasets with children or clones.
+    pub(crate) fn from_destroy_errno(errno: i32, dataset: PathBuf) -> Self {
+        match errno {
+            libc::EBUSY | libc::EEXIST => Error::DatasetBusy(dataset),
+            _ => Error::from_errno(errno, dataset),
+        }
+    }
+
+    /// `BatchFailed` out of errors list libzfs_core returns: errno of every failed name. Count of
+    /// errors that didn't fit into the list is dropped.
+    ///
+    /// * `errors` - Errors list.
+    /// * `to_error` - Error for errno and name, e.g. [`from_errno`](#method.from_errno).
+    #[allow(clippy::wildcard_enum_match_arm)]
+    pub(crate) fn from_errors_list<F>(errors: &NvList, to_error: F) -> Self
+    where
+        F: Fn(i32, PathBuf) -> Error,
+    {
+        let errors = errors
+            .iter()
+            .filter_map(|pair| {
+                let name = pair.key().to_string_lossy();
+                match pair.value() {
+                    Value::Int32(errno) if name != "N_MORE_ERRORS" => {
+                        let name = PathBuf::from(name.as_ref());
+                        Some((name.clone(), to_error(errno, name)))
+                    },
+                    _ => None,
+                }
+            })
+            .collect();
+        Error::BatchFailed(errors)
+    }
+
     pub fn invalid_input() -> Self { Error::Io(io::Error::from(io::ErrorKind::InvalidInput)) }
 }
 
@@ -90,6 +259,28 @@ pub enum ErrorKind {
     ValidationErrors,
     Unimplemented,
     MultiOpError,
+    BatchFailed,
+    InvalidManifest,
+    InsufficientSpace,
+    DatasetExists,
+    DatasetBusy,
+    MountpointCollision,
+    RollbackFailed,
+    Denied,
+    InvalidProperty,
+    HoldExists,
+    HoldNotFound,
+    NotAVolume,
+    NotAClone,
+    NotLatestSnapshot,
+    NoSnapshots,
+    VolumeShrink,
+    InvalidVolumeSize,
+    InvalidStream,
+    MissingIncrementalSource,
+    DeviceNotReady,
+    ParseError,
+    Overflow,
 }
 
 impl PartialEq for Error {
@@ -108,6 +299,42 @@ quick_error! {
         MissingName(dataset: PathBuf) {}
         MissingSnapshotName(dataset: PathBuf) {}
         MissingPool(dataset: PathBuf) {}
+        MissingBookmarkName(dataset: PathBuf) {}
+        InvalidBookmarkSource(dataset: PathBuf) {}
         Unknown(dataset: PathBuf) {}
+        InvalidUserPropertyName(name: String) {}
+        UserPropertyValueTooLong(name: String) {}
+        InvalidUserPropertyValue(name: String) {}
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_stderr() {
+        let err = Error::from_stderr(b"cannot open 'tank/usr': dataset does not exist\n");
+        assert_eq!(Error::DatasetNotFound(PathBuf::from("tank/usr")), err);
+
+        let stderr = b"cannot destroy 'tank/usr': filesystem has children\n\
+                       use '-r' to destroy the following datasets:\ntank/usr/home\n";
+        match Error::from_stderr(stderr) {
+            Error::DatasetBusy(dataset) => assert_eq!(PathBuf::from("tank/usr"), dataset),
+            err => panic!("Unexpected error: {}", err),
+        }
+        let err = Error::from_stderr(b"cannot destroy 'tank/vol': dataset is busy\n");
+        assert_eq!(ErrorKind::DatasetBusy, err.kind());
+
+        let err = Error::from_stderr(b"cannot promote 'tank/usr': not a cloned filesystem\n");
+        assert_eq!(Error::NotAClone(PathBuf::from("tank/usr")), err);
+
+        let stderr = b"cannot rollback to 'tank/usr@1': more recent snapshots or bookmarks exist\n\
+                       use '-r' to force deletion of the following snapshots and bookmarks:\n\
+                       tank/usr@2\n";
+        assert_eq!(ErrorKind::NotLatestSnapshot, Error::from_stderr(stderr).kind());
+
+        let err = Error::from_stderr(b"cannot destroy 'tank/usr': permission denied\n");
+        assert_eq!(ErrorKind::Unknown, err.kind());
     }
 }
@@ -0,0 +1,187 @@
+//! User holds on snapshots.
+//!
+//! A snapshot with a user hold can't be destroyed, so tooling holds snapshots it's about to send
+//! or to base the next incremental on. A hold is identified by its tag and a snapshot can have
+//! many. [`ZfsEngine::hold`](../trait.ZfsEngine.html#method.hold) with a descriptor from
+//! [`cleanup_file`](fn.cleanup_file.html) places a hold that goes away when the descriptor is
+//! closed, including when the process dies. [`HoldGuard`](struct.HoldGuard.html) releases its
+//! hold when it goes out of scope.
+//!
+//! ### Usage
+//! ```rust,no_run
+//! use libzetta::zfs::{holds::{cleanup_file, HoldGuard}, DelegatingZfsEngine, ZfsEngine};
+//! use std::os::unix::io::AsRawFd;
+//!
+//! let engine = DelegatingZfsEngine::new().unwrap();
+//! let cleanup = cleanup_file().unwrap();
+//! engine.hold("tank/usr@backup", "send", Some(cleanup.as_raw_fd())).unwrap();
+//! // Send the snapshot. The hold is released if the process dies on the way.
+//! engine.release("tank/usr@backup", "send").unwrap();
+//! for hold in engine.holds("tank/usr@backup").unwrap() {
+//!     println!("held by {} since {}", hold.tag(), hold.timestamp());
+//! }
+//!
+//! let guard = HoldGuard::new(&engine, "tank/usr@backup", "send", None).unwrap();
+//! // Send the snapshot. The hold is released when `guard` is dropped.
+//! drop(guard);
+//! ```
+use std::{fs::{File, OpenOptions},
+          os::unix::{fs::OpenOptionsExt, io::RawFd},
+          path::{Path, PathBuf}};
+
+use libnv::nvpair::{NvList, Value};
+use slog::Logger;
+
+use crate::{zfs::{Result, ZfsEngine},
+            GlobalLogger};
+
+/// Control device of ZFS.
+const ZFS_DEV: &str = "/dev/zfs";
+
+/// User hold on a snapshot.
+#[derive(Debug, Clone, PartialEq, Eq, Getters)]
+#[get = "pub"]
+pub struct Hold {
+    /// Name of the hold.
+    tag:       String,
+    /// When the hold was placed, in seconds since Unix epoch.
+    timestamp: i64,
+}
+
+impl Hold {
+    /// Holds out of what libzfs_core returns: time a hold was placed by its tag. Oldest first.
+    #[allow(clippy::wildcard_enum_match_arm)]
+    pub(crate) fn from_nvlist(holds: &NvList) -> Vec<Hold> {
+        let mut holds: Vec<Hold> = holds
+            .iter()
+            .filter_map(|pair| match pair.value() {
+                Value::Uint64(timestamp) => Some(Hold {
+                    tag:       pair.key().to_string_lossy().into_owned(),
+                    timestamp: timestamp as i64,
+                }),
+                _ => None,
+            })
+            .collect();
+        holds.sort_by(|a, b| (a.timestamp, &a.tag).cmp(&(b.timestamp, &b.tag)));
+        holds
+    }
+}
+
+/// Open a descriptor for holds that should only last as long as it's open.
+pub fn cleanup_file() -> Result<File> {
+    let file = OpenOptions::new().read(true).write(true).custom_flags(libc::O_EXCL).open(ZFS_DEV)?;
+    Ok(file)
+}
+
+/// User hold that is placed on construction and released on drop. A failed release on drop is
+/// logged and the hold stays; call [`release`](#method.release) to see the error instead.
+pub struct HoldGuard<'a, E: ZfsEngine> {
+    engine:   &'a E,
+    snapshot: PathBuf,
+    tag:      String,
+    held:     bool,
+    logger:   Logger,
+}
+
+impl<'a, E: ZfsEngine> HoldGuard<'a, E> {
+    /// Place a hold. See [`ZfsEngine::hold`](../trait.ZfsEngine.html#method.hold).
+    ///
+    /// * `engine` - Engine to hold and release with.
+    /// * `snapshot` - Snapshot to hold.
+    /// * `tag` - Name of the hold.
+    /// * `cleanup_fd` - Descriptor from [`cleanup_file`](fn.cleanup_file.html) to also release
+    /// the hold if the process dies before the guard is dropped.
+    pub fn new<N: Into<PathBuf>, T: Into<String>>(
+        engine: &'a E,
+        snapshot: N,
+        tag: T,
+        cleanup_fd: Option<RawFd>,
+    ) -> Result<HoldGuard<'a, E>> {
+        let snapshot = snapshot.into();
+        let tag = tag.into();
+        engine.hold(snapshot.clone(), &tag, cleanup_fd)?;
+        let logger = GlobalLogger::get().new(o!("zetta_module" => "zfs"));
+        Ok(HoldGuard { engine, snapshot, tag, held: true, logger })
+    }
+
+    /// Held snapshot.
+    pub fn snapshot(&self) -> &Path { &self.snapshot }
+
+    /// Name of the hold.
+    pub fn tag(&self) -> &str { &self.tag }
+
+    /// Release the hold now.
+    pub fn release(mut self) -> Result<()> {
+        self.held = false;
+        self.engine.release(self.snapshot.clone(), &self.tag)
+    }
+}
+
+impl<'a, E: ZfsEngine> Drop for HoldGuard<'a, E> {
+    fn drop(&mut self) {
+        if !self.held {
+            return;
+        }
+        if let Err(e) = self.engine.release(self.snapshot.clone(), &self.tag) {
+            warn!(self.logger, "failed to release hold";
+                  "snapshot" => %self.snapshot.display(), "tag" => &self.tag, "error" => %e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::cell::RefCell;
+
+    use super::*;
+    use crate::zfs::{Error, ErrorKind};
+
+    /// Engine that records holds and releases and fails to release `fail`.
+    #[derive(Default)]
+    struct Engine {
+        calls: RefCell<Vec<String>>,
+        fail:  Option<&'static str>,
+    }
+
+    impl ZfsEngine for Engine {
+        fn hold<N: Into<PathBuf>>(&self, snapshot: N, tag: &str, _: Option<RawFd>) -> Result<()> {
+            self.calls.borrow_mut().push(format!("hold {} {}", snapshot.into().display(), tag));
+            Ok(())
+        }
+
+        fn release<N: Into<PathBuf>>(&self, snapshot: N, tag: &str) -> Result<()> {
+            let snapshot = snapshot.into();
+            self.calls.borrow_mut().push(format!("release {} {}", snapshot.display(), tag));
+            if self.fail == Some(tag) {
+                return Err(Error::HoldNotFound { snapshot, tag: tag.into() });
+            }
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn release_on_drop() {
+        let engine = Engine::default();
+        {
+            let guard = HoldGuard::new(&engine, "tank/usr@backup", "send", None).unwrap();
+            assert_eq!(Path::new("tank/usr@backup"), guard.snapshot());
+            assert_eq!("send", guard.tag());
+            assert_eq!(vec!["hold tank/usr@backup send"], *engine.calls.borrow());
+        }
+        let expected = vec!["hold tank/usr@backup send", "release tank/usr@backup send"];
+        assert_eq!(expected, *engine.calls.borrow());
+    }
+
+    #[test]
+    fn release_once() {
+        let engine = Engine { fail: Some("send"), ..Engine::default() };
+        let guard = HoldGuard::new(&engine, "tank/usr@backup", "send", None).unwrap();
+        let err = guard.release().unwrap_err();
+        assert_eq!(ErrorKind::HoldNotFound, err.kind());
+        assert_eq!(2, engine.calls.borrow().len());
+
+        // Failure on drop is only logged.
+        drop(HoldGuard::new(&engine, "tank/usr@backup", "send", None).unwrap());
+        assert_eq!(4, engine.calls.borrow().len());
+    }
+}
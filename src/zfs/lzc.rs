@@ -1,21 +1,38 @@
-use crate::{zfs::{BookmarkRequest, Checksum, Compression, Copies, CreateDatasetRequest,
-                  DatasetKind, DestroyTiming, Error, Result, SendFlags, SnapDir, ValidationError,
-                  ZfsEngine},
+use crate::{args::{self, ArgError},
+            schema::{self, ValueType},
+            zfs::{BookmarkRequest, CacheMode, CanMount, Checksum, Compression, Copies,
+                  CreateDatasetRequest, DatasetKind, DestroyTiming, Error, Result, SendFlags,
+                  SnapDir, ValidationError, ZfsEngine},
             GlobalLogger};
 use cstr_argument::CStrArgument;
-use libnv::nvpair::NvList;
+use libnv::nvpair::{NvList, Value};
 use slog::Logger;
 
-use crate::zfs::{errors::Error::ValidationErrors,
+use crate::zfs::{errors::Error::ValidationErrors, holds::Hold,
                  properties::{AclInheritMode, AclMode, ZfsProp},
-                 PathExt};
+                 rename::RenameOptions, validators, PathExt, DATASET_NAME_MAX_LENGTH};
 use std::{collections::HashMap,
-          ffi::CString,
+          ffi::{CStr, CString},
           os::unix::io::{AsRawFd, RawFd},
-          path::PathBuf,
+          path::{Path, PathBuf},
           ptr::null_mut};
 use zfs_core_sys as sys;
 
+extern "C" {
+    // Not in zfs-core-sys 0.1, but libzfs_core has it since ZoL 0.8 and FreeBSD 12.
+    fn lzc_destroy(fsname: *const std::os::raw::c_char) -> std::os::raw::c_int;
+    fn lzc_rename(
+        source: *const std::os::raw::c_char,
+        target: *const std::os::raw::c_char,
+    ) -> std::os::raw::c_int;
+    // libnv can't put a list into a list.
+    fn nvlist_add_nvlist(
+        nvl: *mut std::ffi::c_void,
+        name: *const std::os::raw::c_char,
+        val: *mut std::ffi::c_void,
+    ) -> std::os::raw::c_int;
+}
+
 #[derive(Debug, Clone)]
 pub struct ZfsLzc {
     logger: Logger,
@@ -66,6 +83,33 @@ impl ZfsLzc {
             },
         }
     }
+
+    /// Create missing ancestors of `name` as filesystems, top first.
+    fn create_ancestors(&self, name: &Path) -> Result<()> {
+        let mut missing = Vec::new();
+        for ancestor in name.ancestors().skip(1).filter(|a| !a.as_os_str().is_empty()) {
+            if self.exists(ancestor)? {
+                break;
+            }
+            missing.push(ancestor.to_path_buf());
+        }
+        for ancestor in missing.into_iter().rev() {
+            // No properties, so that ancestors inherit everything, like with `zfs rename -p`.
+            let name = path_to_cstring(&ancestor)?;
+            let props = NvList::default();
+            let errno = unsafe {
+                zfs_core_sys::lzc_create(
+                    name.as_ptr(),
+                    DatasetKind::Filesystem.as_c_uint(),
+                    props.as_ptr(),
+                )
+            };
+            if errno != 0 {
+                return Err(Error::from_errno(errno, ancestor));
+            }
+        }
+        Ok(())
+    }
 }
 
 impl ZfsEngine for ZfsLzc {
@@ -88,44 +132,56 @@ impl ZfsEngine for ZfsLzc {
         let mut props = NvList::default();
         let name_c_string =
             CString::new(request.name().to_str().expect("Non UTF-8 name")).expect("NULL in name");
-        // LZC wants _everything_ as u64 even booleans.
-        props.insert_u64(AclInheritMode::nv_key(), request.acl_inherit.as_nv_value())?;
-        if let Some(acl_mode) = request.acl_mode {
-            props.insert_u64(AclMode::nv_key(), acl_mode.as_nv_value())?;
+        if request.kind == DatasetKind::Filesystem
+            && (request.volume_size.is_some() || request.volume_block_size.is_some())
+        {
+            return Err(Error::invalid_input());
         }
-        props.insert_u64("atime", bool_to_u64(request.atime))?;
+
+        if request.kind == DatasetKind::Volume && request.volume_size.is_none() {
+            return Err(Error::invalid_input());
+        }
+
+        // LZC wants _everything_ as u64 even booleans.
         props.insert_u64(Checksum::nv_key(), request.checksum.as_nv_value())?;
         props.insert_u64(Compression::nv_key(), request.compression.as_nv_value())?;
         props.insert_u64(Copies::nv_key(), request.copies().as_nv_value())?;
-        props.insert_u64("devices", bool_to_u64(request.devices))?;
-        props.insert_u64("exec", bool_to_u64(request.exec))?;
-        // saved fore mount point
         props.insert_u64("primarycache", request.primary_cache.as_nv_value())?;
-        if let Some(quota) = request.quota {
-            props.insert_u64("quota", quota)?;
-        }
         props.insert_u64("readonly", bool_to_u64(request.readonly))?;
-        if let Some(record_size) = request.record_size {
-            props.insert_u64("recordsize", record_size)?;
-        }
-        if let Some(ref_quota) = request.ref_quota {
-            props.insert_u64("refquota", ref_quota)?;
-        }
         if let Some(ref_reservation) = request.ref_reservation {
             props.insert_u64("refreservation", ref_reservation)?;
         }
-        props.insert_u64("secondarycache", request.secondary_cache.as_nv_value())?;
-        props.insert_u64("setuid", bool_to_u64(request.setuid))?;
-        props.insert_u64(SnapDir::nv_key(), request.snap_dir.as_nv_value())?;
-
-        if request.kind == DatasetKind::Filesystem
-            && (request.volume_size.is_some() || request.volume_block_size.is_some())
-        {
-            return Err(Error::invalid_input());
+        if let Some(reservation) = request.reservation {
+            props.insert_u64("reservation", reservation)?;
         }
+        props.insert_u64("secondarycache", request.secondary_cache.as_nv_value())?;
 
-        if request.kind == DatasetKind::Volume && request.volume_size.is_none() {
-            return Err(Error::invalid_input());
+        if request.kind == DatasetKind::Filesystem {
+            // Volumes reject filesystem properties.
+            props.insert_u64(AclInheritMode::nv_key(), request.acl_inherit.as_nv_value())?;
+            if let Some(acl_mode) = request.acl_mode {
+                props.insert_u64(AclMode::nv_key(), acl_mode.as_nv_value())?;
+            }
+            props.insert_u64("atime", bool_to_u64(request.atime))?;
+            props.insert_u64(CanMount::nv_key(), request.can_mount.as_nv_value())?;
+            props.insert_u64("devices", bool_to_u64(request.devices))?;
+            props.insert_u64("exec", bool_to_u64(request.exec))?;
+            if let Some(mount_point) = request.mount_point() {
+                let mount_point = mount_point.to_str().ok_or_else(Error::invalid_input)?;
+                props.insert_string("mountpoint", mount_point)?;
+            }
+            if let Some(quota) = request.quota {
+                props.insert_u64("quota", quota)?;
+            }
+            if let Some(record_size) = request.record_size {
+                props.insert_u64("recordsize", record_size)?;
+            }
+            if let Some(ref_quota) = request.ref_quota {
+                props.insert_u64("refquota", ref_quota)?;
+            }
+            props.insert_u64("setuid", bool_to_u64(request.setuid))?;
+            props.insert_u64(SnapDir::nv_key(), request.snap_dir.as_nv_value())?;
+            props.insert_u64("xattr", bool_to_u64(request.xattr))?;
         }
 
         if let Some(vol_size) = request.volume_size {
@@ -135,7 +191,6 @@ impl ZfsEngine for ZfsLzc {
             props.insert_u64("volblocksize", vol_block_size)?;
         }
 
-        props.insert("xattr", bool_to_u64(request.xattr))?;
         if let Some(user_props) = request.user_properties() {
             for (key, value) in user_props {
                 props.insert_string(key, value)?;
@@ -151,10 +206,12 @@ impl ZfsEngine for ZfsLzc {
 
         match errno {
             0 => Ok(()),
-            _ => {
-                let io_error = std::io::Error::from_raw_os_error(errno);
-                Err(Error::Io(io_error))
+            // Dataset is created under existing parent, so it's the parent that is missing.
+            libc::ENOENT => {
+                let parent = request.name().parent().map(PathBuf::from);
+                Err(Error::DatasetNotFound(parent.unwrap_or_else(|| request.name().clone())))
             },
+            _ => Err(Error::from_errno(errno, request.name().clone())),
         }
     }
 
@@ -163,8 +220,17 @@ impl ZfsEngine for ZfsLzc {
         snapshots: &[PathBuf],
         user_properties: Option<HashMap<String, String>>,
     ) -> Result<()> {
-        let validation_errors: Vec<ValidationError> =
+        let mut validation_errors: Vec<ValidationError> =
             snapshots.iter().map(PathBuf::validate).filter_map(Result::err).collect();
+        if let Some(ref user_properties) = user_properties {
+            validation_errors.extend(
+                user_properties
+                    .iter()
+                    .map(|(key, value)| validators::validate_user_property(key, value))
+                    .filter_map(Result::err),
+            );
+        }
+        validation_errors.extend(single_zpool(snapshots));
         if !validation_errors.is_empty() {
             return Err(ValidationErrors(validation_errors));
         }
@@ -190,7 +256,7 @@ impl ZfsEngine for ZfsLzc {
         if !errors_list_ptr.is_null() {
             let errors = unsafe { NvList::from_ptr(errors_list_ptr) };
             if !errors.is_empty() {
-                return Err(Error::from(errors));
+                return Err(Error::from_errors_list(&errors, Error::from_errno));
             }
         }
         match errno {
@@ -203,12 +269,8 @@ impl ZfsEngine for ZfsLzc {
     }
 
     fn bookmark(&self, bookmarks: &[BookmarkRequest]) -> Result<()> {
-        let validation_errors: Vec<ValidationError> = bookmarks
-            .iter()
-            .flat_map(|BookmarkRequest { snapshot, bookmark }| vec![snapshot, bookmark])
-            .map(PathBuf::validate)
-            .filter_map(Result::err)
-            .collect();
+        let validation_errors: Vec<ValidationError> =
+            bookmarks.iter().flat_map(BookmarkRequest::validate).collect();
         if !validation_errors.is_empty() {
             return Err(ValidationErrors(validation_errors));
         }
@@ -237,13 +299,63 @@ impl ZfsEngine for ZfsLzc {
         }
     }
 
+    /// Filesystems must be unmounted first, lzc doesn't unmount them. Missing bookmarks are not
+    /// reported.
+    fn destroy<N: Into<PathBuf>>(&self, name: N) -> Result<()> {
+        let name = name.into();
+        name.validate()?;
+
+        let name_str = name.to_string_lossy();
+        let errno = if name_str.contains('@') {
+            // Kernel silently skips snapshots that don't exist.
+            if !self.exists(name.clone())? {
+                return Err(Error::DatasetNotFound(name));
+            }
+            let mut snapshots_list = NvList::default();
+            snapshots_list.insert(&name_str, true)?;
+            let mut errors_list_ptr = null_mut();
+            let errno = unsafe {
+                zfs_core_sys::lzc_destroy_snaps(
+                    snapshots_list.as_ptr(),
+                    DestroyTiming::RightNow.as_c_uint(),
+                    &mut errors_list_ptr,
+                )
+            };
+            // Errors list only repeats errno of the only snapshot, free it.
+            if !errors_list_ptr.is_null() {
+                unsafe { NvList::from_ptr(errors_list_ptr) };
+            }
+            errno
+        } else if name_str.contains('#') {
+            let mut bookmarks_list = NvList::default();
+            bookmarks_list.insert(&name_str, true)?;
+            let mut errors_list_ptr = null_mut();
+            let errno = unsafe {
+                zfs_core_sys::lzc_destroy_bookmarks(bookmarks_list.as_ptr(), &mut errors_list_ptr)
+            };
+            if !errors_list_ptr.is_null() {
+                unsafe { NvList::from_ptr(errors_list_ptr) };
+            }
+            errno
+        } else {
+            let name_c_string = CString::new(name_str.as_ref()).expect("NULL in name");
+            unsafe { lzc_destroy(name_c_string.as_ptr()) }
+        };
+
+        match errno {
+            0 => Ok(()),
+            _ => Err(Error::from_destroy_errno(errno, name)),
+        }
+    }
+
     fn destroy_snapshots(&self, snapshots: &[PathBuf], timing: DestroyTiming) -> Result<()> {
-        let validation_errors: Vec<ValidationError> = snapshots
+        let mut validation_errors: Vec<ValidationError> = snapshots
             .iter()
             .map(PathBuf::validate)
             .filter(Result::is_err)
             .map(Result::unwrap_err)
             .collect();
+        validation_errors.extend(single_zpool(snapshots));
         if !validation_errors.is_empty() {
             return Err(ValidationErrors(validation_errors));
         }
@@ -265,7 +377,7 @@ impl ZfsEngine for ZfsLzc {
         if !errors_list_ptr.is_null() {
             let errors = unsafe { NvList::from_ptr(errors_list_ptr) };
             if !errors.is_empty() {
-                return Err(Error::from(errors));
+                return Err(Error::from_errors_list(&errors, Error::from_destroy_errno));
             }
         }
         match errno {
@@ -313,6 +425,243 @@ impl ZfsEngine for ZfsLzc {
         }
     }
 
+    /// libzfs_core doesn't touch mounts: filesystems stay mounted where they were, as if
+    /// `no_remount` was set. Missing parents are created as plain filesystems.
+    fn rename<N: Into<PathBuf>, M: Into<PathBuf>>(
+        &self,
+        name: N,
+        new_name: M,
+        options: RenameOptions,
+    ) -> Result<()> {
+        let name = name.into();
+        let new_name = new_name.into();
+        let mut validation_errors: Vec<ValidationError> =
+            [&name, &new_name].iter().map(PathExt::validate).filter_map(Result::err).collect();
+        validation_errors.extend(single_zpool(&[name.clone(), new_name.clone()]));
+        if !validation_errors.is_empty() {
+            return Err(ValidationErrors(validation_errors));
+        }
+        if *options.create_parents() {
+            if name.is_snapshot() || new_name.is_snapshot() {
+                return Err(Error::invalid_input());
+            }
+            self.create_ancestors(&new_name)?;
+        }
+
+        let name_c_string = CString::new(name.to_string_lossy().as_ref()).expect("NULL in name");
+        let new_name_c_string =
+            CString::new(new_name.to_string_lossy().as_ref()).expect("NULL in name");
+        let errno = unsafe { lzc_rename(name_c_string.as_ptr(), new_name_c_string.as_ptr()) };
+
+        match errno {
+            0 => Ok(()),
+            // Either the dataset or parent of the new name is missing.
+            libc::ENOENT if !self.exists(name.clone())? => Err(Error::DatasetNotFound(name)),
+            libc::ENOENT => {
+                let parent = new_name.parent().map(PathBuf::from);
+                Err(Error::DatasetNotFound(parent.unwrap_or(new_name)))
+            },
+            _ => Err(Error::from_errno(errno, new_name)),
+        }
+    }
+
+    /// Clone isn't mounted. Native properties are limited to what can be passed to the kernel
+    /// as is: booleans, plain numbers, paths, strings and index properties of
+    /// [`properties`](../properties/index.html), e.g. `compression` or `canmount`.
+    fn clone_snapshot<N: Into<PathBuf>, M: Into<PathBuf>>(
+        &self,
+        snapshot: N,
+        target: M,
+        properties: Option<HashMap<String, String>>,
+    ) -> Result<()> {
+        let snapshot = snapshot.into();
+        let target = target.into();
+        let mut validation_errors: Vec<ValidationError> =
+            [&snapshot, &target].iter().map(PathExt::validate).filter_map(Result::err).collect();
+        if !snapshot.is_snapshot() {
+            validation_errors.push(ValidationError::MissingSnapshotName(snapshot.clone()));
+        }
+        validation_errors.extend(single_zpool(&[snapshot.clone(), target.clone()]));
+        if !validation_errors.is_empty() {
+            return Err(ValidationErrors(validation_errors));
+        }
+
+        let mut props = NvList::default();
+        for (key, value) in properties.unwrap_or_default() {
+            insert_property(&mut props, &key, &value)?;
+        }
+        let target_c_string =
+            CString::new(target.to_string_lossy().as_ref()).expect("NULL in name");
+        let snapshot_c_string =
+            CString::new(snapshot.to_string_lossy().as_ref()).expect("NULL in name");
+        let errno = unsafe {
+            zfs_core_sys::lzc_clone(
+                target_c_string.as_ptr(),
+                snapshot_c_string.as_ptr(),
+                props.as_ptr(),
+            )
+        };
+
+        match errno {
+            0 => Ok(()),
+            // Either the snapshot or parent of the clone is missing.
+            libc::ENOENT if !self.exists(snapshot.clone())? => {
+                Err(Error::DatasetNotFound(snapshot))
+            },
+            libc::ENOENT => {
+                let parent = target.parent().map(PathBuf::from);
+                Err(Error::DatasetNotFound(parent.unwrap_or(target)))
+            },
+            _ => Err(Error::from_errno(errno, target)),
+        }
+    }
+
+    fn promote<N: Into<PathBuf>>(&self, name: N) -> Result<()> {
+        let name = name.into();
+        name.validate()?;
+        let name_c_string = CString::new(name.to_string_lossy().as_ref()).expect("NULL in name");
+        // Kernel puts name of a snapshot that exists in both datasets here.
+        let mut conflict = [0 as std::os::raw::c_char; DATASET_NAME_MAX_LENGTH + 1];
+        let errno = unsafe {
+            zfs_core_sys::lzc_promote(
+                name_c_string.as_ptr(),
+                conflict.as_mut_ptr(),
+                conflict.len() as std::os::raw::c_int,
+            )
+        };
+
+        match errno {
+            0 => Ok(()),
+            libc::EINVAL => Err(Error::NotAClone(name)),
+            libc::EEXIST => {
+                let snapshot = unsafe { CStr::from_ptr(conflict.as_ptr()) }.to_string_lossy();
+                Err(Error::DatasetExists(PathBuf::from(format!("{}@{}", name.display(), snapshot))))
+            },
+            _ => Err(Error::from_errno(errno, name)),
+        }
+    }
+
+    /// `destroy_newer` is not supported: libzfs_core can't list snapshots to destroy.
+    fn rollback<N: Into<PathBuf>>(&self, name: N, destroy_newer: bool) -> Result<PathBuf> {
+        let name = name.into();
+        name.validate()?;
+        if destroy_newer {
+            return Err(Error::Unimplemented);
+        }
+
+        let name_str = name.to_string_lossy();
+        let dataset = name_str.split('@').next().unwrap_or(&name_str);
+        let dataset_c_string = CString::new(dataset).expect("NULL in name");
+        let errno = if name.is_snapshot() {
+            let snapshot_c_string = CString::new(name_str.as_ref()).expect("NULL in name");
+            unsafe {
+                zfs_core_sys::lzc_rollback_to(dataset_c_string.as_ptr(), snapshot_c_string.as_ptr())
+            }
+        } else {
+            // Kernel puts name of the snapshot it rolled back to here.
+            let mut snapshot = [0 as std::os::raw::c_char; DATASET_NAME_MAX_LENGTH + 1];
+            let errno = unsafe {
+                zfs_core_sys::lzc_rollback(
+                    dataset_c_string.as_ptr(),
+                    snapshot.as_mut_ptr(),
+                    snapshot.len() as std::os::raw::c_int,
+                )
+            };
+            if errno == 0 {
+                let snapshot = unsafe { CStr::from_ptr(snapshot.as_ptr()) }.to_string_lossy();
+                return Ok(PathBuf::from(snapshot.as_ref()));
+            }
+            errno
+        };
+
+        match errno {
+            0 => Ok(name),
+            // Kernel says EXDEV when the snapshot isn't the latest one and EEXIST when there are
+            // newer bookmarks.
+            libc::EXDEV | libc::EEXIST => Err(Error::NotLatestSnapshot(name)),
+            _ => Err(Error::from_errno(errno, name)),
+        }
+    }
+
+    fn hold<N: Into<PathBuf>>(
+        &self,
+        snapshot: N,
+        tag: &str,
+        cleanup_fd: Option<RawFd>,
+    ) -> Result<()> {
+        let snapshot = snapshot.into();
+        validate_hold(&snapshot, tag)?;
+        let mut holds_list = NvList::default();
+        holds_list.insert_string(&snapshot.to_string_lossy(), tag)?;
+        let mut errors_list_ptr = null_mut();
+        let errno = unsafe {
+            zfs_core_sys::lzc_hold(
+                holds_list.as_ptr(),
+                cleanup_fd.unwrap_or(-1),
+                &mut errors_list_ptr,
+            )
+        };
+        let errors = if errors_list_ptr.is_null() {
+            None
+        } else {
+            Some(unsafe { NvList::from_ptr(errors_list_ptr) })
+        };
+
+        match single_errno(errno, errors) {
+            0 => Ok(()),
+            libc::EEXIST => Err(Error::HoldExists { snapshot, tag: tag.into() }),
+            errno => Err(Error::from_errno(errno, snapshot)),
+        }
+    }
+
+    fn release<N: Into<PathBuf>>(&self, snapshot: N, tag: &str) -> Result<()> {
+        let snapshot = snapshot.into();
+        validate_hold(&snapshot, tag)?;
+        let mut tags = NvList::default();
+        tags.insert(tag, true)?;
+        let holds = NvList::default();
+        let snapshot_c_string =
+            CString::new(snapshot.to_string_lossy().as_ref()).expect("NULL in name");
+        let errno = unsafe {
+            nvlist_add_nvlist(
+                holds.as_ptr().cast(),
+                snapshot_c_string.as_ptr(),
+                tags.as_ptr().cast(),
+            )
+        };
+        if errno != 0 {
+            return Err(Error::NvOpError(libnv::NvError::NativeError(errno)));
+        }
+        let mut errors_list_ptr = null_mut();
+        let errno = unsafe { zfs_core_sys::lzc_release(holds.as_ptr(), &mut errors_list_ptr) };
+        let errors = if errors_list_ptr.is_null() {
+            None
+        } else {
+            Some(unsafe { NvList::from_ptr(errors_list_ptr) })
+        };
+
+        match single_errno(errno, errors) {
+            0 => Ok(()),
+            libc::ESRCH => Err(Error::HoldNotFound { snapshot, tag: tag.into() }),
+            errno => Err(Error::from_errno(errno, snapshot)),
+        }
+    }
+
+    fn holds<N: Into<PathBuf>>(&self, snapshot: N) -> Result<Vec<Hold>> {
+        let snapshot = snapshot.into();
+        validate_snapshot(&snapshot)?;
+        let snapshot_c_string =
+            CString::new(snapshot.to_string_lossy().as_ref()).expect("NULL in name");
+        let mut holds_list_ptr = null_mut();
+        let errno =
+            unsafe { zfs_core_sys::lzc_get_holds(snapshot_c_string.as_ptr(), &mut holds_list_ptr) };
+        if errno != 0 {
+            return Err(Error::from_errno(errno, snapshot));
+        }
+        let holds = unsafe { NvList::from_ptr(holds_list_ptr) };
+        Ok(Hold::from_nvlist(&holds))
+    }
+
     fn send_full<N: Into<PathBuf>, FD: AsRawFd>(
         &self,
         path: N,
@@ -331,13 +680,156 @@ impl ZfsEngine for ZfsLzc {
     ) -> Result<()> {
         self.send(path.into(), Some(from.into()), fd.as_raw_fd(), flags)
     }
+
+    fn send_space<N: Into<PathBuf>>(
+        &self,
+        path: N,
+        from: Option<PathBuf>,
+        flags: SendFlags,
+    ) -> Result<u64> {
+        let snapshot = path_to_cstring(&path.into())?;
+        let from_cstr = from.as_deref().map(path_to_cstring).transpose()?;
+        let from_ptr = from_cstr.as_ref().map_or(std::ptr::null(), |f| f.as_ptr());
+        let mut space = 0;
+        let snapshot_ptr = snapshot.as_ptr();
+        let errno =
+            unsafe { zfs_core_sys::lzc_send_space(snapshot_ptr, from_ptr, flags.bits, &mut space) };
+        match errno {
+            0 => Ok(space),
+            _ => Err(Error::Io(std::io::Error::from_raw_os_error(errno))),
+        }
+    }
+
+    fn receive<N: Into<PathBuf>, FD: AsRawFd>(
+        &self,
+        snapshot: N,
+        fd: FD,
+        force: bool,
+    ) -> Result<()> {
+        let snapshot = snapshot.into();
+        snapshot.validate()?;
+        if snapshot.get_snapshot().is_none() {
+            return Err(ValidationError::MissingSnapshotName(snapshot).into());
+        }
+        let name = path_to_cstring(&snapshot)?;
+        let errno = unsafe {
+            zfs_core_sys::lzc_receive(
+                name.as_ptr(),
+                null_mut(),
+                std::ptr::null(),
+                force.into(),
+                fd.as_raw_fd(),
+            )
+        };
+        match errno {
+            0 => Ok(()),
+            _ => Err(Error::Io(std::io::Error::from_raw_os_error(errno))),
+        }
+    }
+}
+
+/// Name for libzfs_core. Names that aren't UTF-8 or have NUL in them are invalid input.
+fn path_to_cstring(path: &Path) -> Result<CString> {
+    let name = path.to_str().ok_or_else(Error::invalid_input)?;
+    CString::new(name).map_err(|_| Error::invalid_input())
+}
+
+fn validate_snapshot(snapshot: &PathBuf) -> Result<()> {
+    snapshot.validate()?;
+    if !snapshot.is_snapshot() {
+        return Err(ValidationError::MissingSnapshotName(snapshot.clone()).into());
+    }
+    Ok(())
+}
+
+/// Holds need a snapshot and a tag.
+fn validate_hold(snapshot: &PathBuf, tag: &str) -> Result<()> {
+    validate_snapshot(snapshot)?;
+    if tag.is_empty() {
+        return Err(Error::invalid_input());
+    }
+    Ok(())
+}
+
+/// Errno of an operation on a single name. Some failures are only reported in the errors list.
+fn single_errno(errno: i32, errors: Option<NvList>) -> i32 {
+    if errno != 0 {
+        return errno;
+    }
+    errors
+        .iter()
+        .flat_map(NvList::iter)
+        .find_map(|pair| if let Value::Int32(errno) = pair.value() { Some(errno) } else { None })
+        .unwrap_or(0)
+}
+
+/// Batch operations of lzc work within a single pool.
+fn single_zpool(names: &[PathBuf]) -> Option<ValidationError> {
+    let mut zpools: Vec<PathBuf> =
+        names.iter().filter_map(PathExt::get_pool).map(PathBuf::from).collect();
+    zpools.sort();
+    zpools.dedup();
+    if zpools.len() > 1 {
+        Some(ValidationError::MultipleZpools(zpools))
+    } else {
+        None
+    }
+}
+
+/// Add a property the way the kernel takes it: user properties, paths and strings as strings,
+/// everything else as numbers.
+fn insert_property(props: &mut NvList, key: &str, value: &str) -> Result<()> {
+    args::validate_key(key)?;
+    if key.contains(':') {
+        props.insert_string(key, value)?;
+        return Ok(());
+    }
+    let invalid = |reason| Error::from(ArgError::InvalidValue(key.to_string(), reason));
+    let schema = schema::dataset_property(key).ok_or_else(|| ArgError::InvalidKey(key.into()))?;
+    if !schema.accepts(value) {
+        return Err(invalid("not accepted by the property"));
+    }
+    let number = match schema.value_type() {
+        ValueType::Text | ValueType::Path => {
+            props.insert_string(key, value)?;
+            return Ok(());
+        },
+        ValueType::Bool => bool_to_u64(value == "on"),
+        ValueType::Number | ValueType::Size if value == "none" => 0,
+        ValueType::Number | ValueType::Size => {
+            value.parse().map_err(|_| invalid("only plain numbers can be passed to lzc"))?
+        },
+        ValueType::Index(_) => {
+            index_value(key, value).ok_or_else(|| invalid("can't be passed to lzc"))?
+        },
+    };
+    props.insert_u64(key, number)?;
+    Ok(())
+}
+
+/// Numeric value of index properties this crate has types for.
+fn index_value(key: &str, value: &str) -> Option<u64> {
+    fn parse<P: ZfsProp + std::str::FromStr>(value: &str) -> Option<u64> {
+        value.parse::<P>().ok().map(|prop| prop.as_nv_value())
+    }
+    match key {
+        "aclinherit" => parse::<AclInheritMode>(value),
+        "aclmode" => parse::<AclMode>(value),
+        "canmount" => parse::<CanMount>(value),
+        "checksum" => parse::<Checksum>(value),
+        "compression" => parse::<Compression>(value),
+        "copies" => parse::<Copies>(value),
+        "primarycache" | "secondarycache" => parse::<CacheMode>(value),
+        "snapdir" => parse::<SnapDir>(value),
+        _ => None,
+    }
 }
 
 // This should be mapped to values from nvpair.
 fn bool_to_u64(src: bool) -> u64 {
     if src {
-        0
-    } else {
         1
+    } else {
+        0
     }
 }
@@ -0,0 +1,421 @@
+//! Manifests for send streams stored outside of ZFS.
+//!
+//! When a send stream ends up in a file or an object store, something has to remember which
+//! snapshot it contains, what it's incremental from, how it was produced and how it was split.
+//! [`SendManifest`](struct.SendManifest.html) is that something. It serializes into a simple
+//! tab-separated text format (see `Display` and `FromStr` implementations), so it can be stored
+//! next to the stream without pulling any serialization framework.
+//!
+//! Checksums are opaque strings in `algorithm:hex` form - computing them is left to the caller.
+//!
+//! ### Format
+//! One key per line, the key and its values are separated by a tab (shown as spaces here).
+//! ```text
+//! version        1
+//! snapshot       tank/data@b
+//! snapshot_guid  2
+//! from           tank/data@a
+//! from_guid      1
+//! flags          4
+//! size           1000
+//! checksum       sha256:abcd
+//! chunk_size     512
+//! chunk          0  0    512  sha256:0000
+//! chunk          1  512  488  sha256:1111
+//! ```
+use std::{convert::TryFrom,
+          fmt,
+          path::{Path, PathBuf},
+          str::FromStr};
+
+use crate::zfs::{Error, PathExt, Properties, Result, SendFlags, ZfsEngine};
+
+/// Version of manifest format produced by this crate.
+pub const MANIFEST_VERSION: u32 = 1;
+
+/// Piece of a chunked send stream.
+#[derive(Debug, Clone, PartialEq, Eq, Getters)]
+#[get = "pub"]
+pub struct ChunkInfo {
+    /// Position of the chunk in the stream, starting from 0.
+    index:    u64,
+    /// Offset of the chunk in the stream in bytes.
+    offset:   u64,
+    /// Size of the chunk in bytes.
+    size:     u64,
+    /// Checksum of the chunk.
+    checksum: String,
+}
+
+impl ChunkInfo {
+    /// Describe a chunk.
+    pub fn new<C: Into<String>>(index: u64, offset: u64, size: u64, checksum: C) -> ChunkInfo {
+        ChunkInfo { index, offset, size, checksum: checksum.into() }
+    }
+}
+
+/// Metadata of a stored send stream.
+#[derive(Debug, Clone, PartialEq, Eq, Getters, Builder)]
+#[builder(setter(into))]
+#[get = "pub"]
+pub struct SendManifest {
+    /// Version of manifest format.
+    #[builder(default = "MANIFEST_VERSION")]
+    version:       u32,
+    /// Snapshot that was sent.
+    snapshot:      PathBuf,
+    /// GUID of the snapshot that was sent.
+    snapshot_guid: u64,
+    /// Snapshot or bookmark stream is incremental from. `None` for full streams. It's in another
+    /// dataset if the stream is incremental from the origin of a clone.
+    #[builder(default)]
+    from:          Option<PathBuf>,
+    /// GUID of `from`.
+    #[builder(default)]
+    from_guid:     Option<u64>,
+    /// Flags the stream was produced with.
+    #[builder(default)]
+    flags:         SendFlags,
+    /// Size of the whole stream in bytes.
+    size:          u64,
+    /// Checksum of the whole stream.
+    #[builder(default)]
+    checksum:      Option<String>,
+    /// Size of every chunk except the last one. `None` if stream wasn't chunked.
+    #[builder(default)]
+    chunk_size:    Option<u64>,
+    /// Chunks in stream order.
+    #[builder(default)]
+    chunks:        Vec<ChunkInfo>,
+}
+
+impl SendManifest {
+    /// A preferred way to create this.
+    pub fn builder() -> SendManifestBuilder { SendManifestBuilder::default() }
+
+    /// Describe a stream that was just sent: GUIDs of `snapshot` and `from` are looked up with
+    /// `engine`. Checksum and chunks can be filled in afterwards.
+    ///
+    /// * `engine` - Engine to read GUIDs with.
+    /// * `snapshot` - Snapshot that was sent.
+    /// * `from` - Snapshot or bookmark it was sent from, if stream is incremental.
+    /// * `flags` - Flags used for send.
+    /// * `size` - Number of bytes written.
+    pub fn for_stream<E: ZfsEngine>(
+        engine: &E,
+        snapshot: PathBuf,
+        from: Option<PathBuf>,
+        flags: SendFlags,
+        size: u64,
+    ) -> Result<SendManifest> {
+        let snapshot_guid =
+            read_guid(engine, &snapshot)?.ok_or_else(|| Error::DatasetNotFound(snapshot.clone()))?;
+        let from_guid = match from {
+            Some(ref from) => {
+                Some(read_guid(engine, from)?.ok_or_else(|| Error::DatasetNotFound(from.clone()))?)
+            },
+            None => None,
+        };
+        Ok(SendManifest {
+            version:    MANIFEST_VERSION,
+            snapshot,
+            snapshot_guid,
+            from,
+            from_guid,
+            flags,
+            size,
+            checksum:   None,
+            chunk_size: None,
+            chunks:     Vec::new(),
+        })
+    }
+
+    /// Replace chunking information. Stream size is set to the total size of `chunks`.
+    pub fn with_chunks(mut self, chunk_size: u64, chunks: Vec<ChunkInfo>) -> SendManifest {
+        self.size = chunks.iter().map(|chunk| chunk.size).sum();
+        self.chunk_size = Some(chunk_size);
+        self.chunks = chunks;
+        self
+    }
+
+    /// Is this manifest for an incremental stream.
+    pub fn is_incremental(&self) -> bool { self.from.is_some() }
+
+    /// Is this manifest for an incremental stream from the origin of a clone, i.e. `from` is in
+    /// another dataset than `snapshot`.
+    pub fn is_from_origin(&self) -> bool {
+        self.from.as_ref().map_or(false, |from| dataset_of(from) != dataset_of(&self.snapshot))
+    }
+
+    /// Check that manifest is consistent on its own: known version, `snapshot` is a snapshot,
+    /// `from` is a snapshot or bookmark and chunks cover the whole stream without gaps.
+    pub fn validate(&self) -> Result<()> {
+        if self.version != MANIFEST_VERSION {
+            return Err(invalid(format!("unsupported version {}", self.version)));
+        }
+        if !self.snapshot.is_snapshot() {
+            return Err(invalid(format!("{} is not a snapshot", self.snapshot.display())));
+        }
+        match (&self.from, self.from_guid) {
+            (None, None) => {},
+            (Some(from), Some(_)) => {
+                if !(from.is_snapshot() || from.is_bookmark()) {
+                    let reason = format!("{} is not a snapshot or bookmark", from.display());
+                    return Err(invalid(reason));
+                }
+            },
+            _ => return Err(invalid(String::from("from and from_guid must be set together"))),
+        }
+        self.validate_chunks()
+    }
+
+    fn validate_chunks(&self) -> Result<()> {
+        let chunk_size = match self.chunk_size {
+            Some(0) => return Err(invalid(String::from("chunk size is 0"))),
+            Some(chunk_size) => chunk_size,
+            None if self.chunks.is_empty() => return Ok(()),
+            None => return Err(invalid(String::from("chunks without chunk size"))),
+        };
+        let mut offset = 0;
+        for (idx, chunk) in self.chunks.iter().enumerate() {
+            let last = idx + 1 == self.chunks.len();
+            if u64::try_from(idx).ok() != Some(chunk.index) || chunk.offset != offset {
+                return Err(invalid(format!("chunk {} is out of order", idx)));
+            }
+            if chunk.size == 0 || chunk.size > chunk_size || (!last && chunk.size != chunk_size) {
+                return Err(invalid(format!("chunk {} has wrong size", idx)));
+            }
+            offset += chunk.size;
+        }
+        if offset != self.size {
+            return Err(invalid(format!("chunks cover {} bytes out of {}", offset, self.size)));
+        }
+        Ok(())
+    }
+
+    /// Check that stream described by this manifest can be received into `target` dataset: for
+    /// incremental streams `target` must have `from` snapshot with the same GUID, for full streams
+    /// `target` must not exist. In both cases the snapshot itself must not be in `target` yet.
+    /// Streams from the origin of a clone create `target` as a new clone, so it must not exist
+    /// either. Their origin is looked up by GUID anywhere in the pool, which isn't checked here.
+    ///
+    /// * `engine` - Engine to inspect `target` with.
+    /// * `target` - Dataset stream is going to be received into.
+    pub fn validate_for_receive<E: ZfsEngine, T: Into<PathBuf>>(
+        &self,
+        engine: &E,
+        target: T,
+    ) -> Result<()> {
+        self.validate()?;
+        let target = target.into();
+        let name = self.snapshot.get_snapshot().unwrap_or_default();
+        let snapshot = with_suffix(&target, '@', &name);
+        if engine.exists(snapshot.clone())? {
+            return Err(invalid(format!("{} already exists", snapshot.display())));
+        }
+
+        match (&self.from, self.from_guid) {
+            (Some(from), Some(from_guid)) if !self.is_from_origin() => {
+                let from = match from.get_snapshot() {
+                    Some(name) => with_suffix(&target, '@', &name),
+                    None => with_suffix(&target, '#', &from.get_bookmark().unwrap_or_default()),
+                };
+                let guid = read_guid(engine, &from)?;
+                if guid != Some(from_guid) {
+                    return Err(invalid(format!("{} doesn't match stream origin", from.display())));
+                }
+            },
+            _ => {
+                if engine.exists(target.clone())? {
+                    return Err(invalid(format!("{} already exists", target.display())));
+                }
+            },
+        }
+        Ok(())
+    }
+}
+
+#[allow(clippy::wildcard_enum_match_arm)]
+fn read_guid<E: ZfsEngine>(engine: &E, path: &Path) -> Result<Option<u64>> {
+    Ok(match engine.read_properties(path)? {
+        Properties::Snapshot(props) => *props.guid(),
+        Properties::Bookmark(props) => *props.guid(),
+        _ => None,
+    })
+}
+
+fn invalid(reason: String) -> Error { Error::InvalidManifest(reason) }
+
+fn dataset_of(path: &Path) -> String {
+    let name = path.to_string_lossy();
+    name.split(|c| c == '@' || c == '#').next().unwrap_or_default().to_owned()
+}
+
+fn with_suffix(dataset: &Path, separator: char, name: &str) -> PathBuf {
+    PathBuf::from(format!("{}{}{}", dataset.display(), separator, name))
+}
+
+impl fmt::Display for SendManifest {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "version\t{}", self.version)?;
+        writeln!(f, "snapshot\t{}", self.snapshot.display())?;
+        writeln!(f, "snapshot_guid\t{}", self.snapshot_guid)?;
+        if let Some(ref from) = self.from {
+            writeln!(f, "from\t{}", from.display())?;
+        }
+        if let Some(from_guid) = self.from_guid {
+            writeln!(f, "from_guid\t{}", from_guid)?;
+        }
+        writeln!(f, "flags\t{}", self.flags.bits())?;
+        writeln!(f, "size\t{}", self.size)?;
+        if let Some(ref checksum) = self.checksum {
+            writeln!(f, "checksum\t{}", checksum)?;
+        }
+        if let Some(chunk_size) = self.chunk_size {
+            writeln!(f, "chunk_size\t{}", chunk_size)?;
+        }
+        for chunk in &self.chunks {
+            writeln!(
+                f,
+                "chunk\t{}\t{}\t{}\t{}",
+                chunk.index, chunk.offset, chunk.size, chunk.checksum
+            )?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for SendManifest {
+    type Err = Error;
+
+    fn from_str(source: &str) -> Result<SendManifest> {
+        let mut builder = SendManifest::builder();
+        let mut chunks = Vec::new();
+        for line in source.lines().filter(|line| !line.is_empty()) {
+            let mut cols = line.split('\t');
+            let key = cols.next().unwrap_or_default();
+            let value = cols.next().ok_or_else(|| invalid(format!("no value for {}", key)))?;
+            match key {
+                "version" => {
+                    builder.version(parse_number::<u32>(key, value)?);
+                },
+                "snapshot" => {
+                    builder.snapshot(value);
+                },
+                "snapshot_guid" => {
+                    builder.snapshot_guid(parse_number::<u64>(key, value)?);
+                },
+                "from" => {
+                    builder.from(Some(PathBuf::from(value)));
+                },
+                "from_guid" => {
+                    builder.from_guid(Some(parse_number::<u64>(key, value)?));
+                },
+                "flags" => {
+                    let bits = parse_number::<u32>(key, value)?;
+                    let flags = SendFlags::from_bits(bits)
+                        .ok_or_else(|| invalid(format!("unknown flags {}", bits)))?;
+                    builder.flags(flags);
+                },
+                "size" => {
+                    builder.size(parse_number::<u64>(key, value)?);
+                },
+                "checksum" => {
+                    builder.checksum(Some(String::from(value)));
+                },
+                "chunk_size" => {
+                    builder.chunk_size(Some(parse_number::<u64>(key, value)?));
+                },
+                "chunk" => {
+                    let offset = cols.next().unwrap_or_default();
+                    let size = cols.next().unwrap_or_default();
+                    let checksum = cols.next().unwrap_or_default();
+                    chunks.push(ChunkInfo::new(
+                        parse_number(key, value)?,
+                        parse_number(key, offset)?,
+                        parse_number(key, size)?,
+                        checksum,
+                    ));
+                },
+                // Unknown keys are skipped, so older versions can read newer manifests.
+                _ => {},
+            }
+        }
+        builder.chunks(chunks);
+        builder.build().map_err(|err| invalid(err.to_string()))
+    }
+}
+
+fn parse_number<T: FromStr>(key: &str, value: &str) -> Result<T> {
+    value.parse().map_err(|_| invalid(format!("can't parse {} value {}", key, value)))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn manifest() -> SendManifest {
+        SendManifest::builder()
+            .snapshot("tank/data@b")
+            .snapshot_guid(2u64)
+            .from(Some(PathBuf::from("tank/data@a")))
+            .from_guid(Some(1))
+            .flags(SendFlags::LZC_SEND_FLAG_COMPRESS)
+            .size(1000u64)
+            .checksum(Some(String::from("sha256:abcd")))
+            .chunk_size(Some(512))
+            .chunks(vec![
+                ChunkInfo::new(0, 0, 512, "sha256:0000"),
+                ChunkInfo::new(1, 512, 488, "sha256:1111"),
+            ])
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn roundtrip() {
+        let manifest = manifest();
+        manifest.validate().unwrap();
+        let text = manifest.to_string();
+        assert!(text.starts_with("version\t1\nsnapshot\ttank/data@b\n"));
+        let parsed: SendManifest = text.parse().unwrap();
+        assert_eq!(manifest, parsed);
+
+        let err = "version\t1\nsize\tbig\n".parse::<SendManifest>().unwrap_err();
+        assert_eq!(crate::zfs::ErrorKind::InvalidManifest, err.kind());
+    }
+
+    #[test]
+    fn validation() {
+        let mut broken = manifest();
+        broken.chunks.pop();
+        assert!(broken.validate().is_err());
+
+        let mut broken = manifest();
+        broken.chunks.swap(0, 1);
+        assert!(broken.validate().is_err());
+
+        let mut broken = manifest();
+        broken.from = Some(PathBuf::from("tank/data"));
+        assert!(broken.validate().is_err());
+
+        // Incremental from the origin of a clone.
+        let mut clone = manifest();
+        clone.from = Some(PathBuf::from("tank/other@a"));
+        clone.validate().unwrap();
+        assert!(clone.is_from_origin());
+        assert!(!manifest().is_from_origin());
+
+        let mut broken = manifest();
+        broken.from_guid = None;
+        assert!(broken.validate().is_err());
+
+        let mut full = manifest();
+        full.from = None;
+        full.from_guid = None;
+        full.chunk_size = None;
+        full.chunks.clear();
+        full.validate().unwrap();
+        assert!(!full.is_incremental());
+    }
+}
@@ -1,4 +1,5 @@
-use std::{os::unix::io::AsRawFd, path::PathBuf};
+use std::{os::unix::io::{AsRawFd, RawFd},
+          path::PathBuf};
 
 use bitflags::bitflags;
 
@@ -10,8 +11,22 @@ pub use delegating::DelegatingZfsEngine;
 pub mod open3;
 pub use open3::ZfsOpen3;
 
+pub mod chunked;
+pub mod delegation;
+pub mod destroy;
+pub mod holds;
 pub mod lzc;
-use crate::zfs::properties::{AclInheritMode, AclMode};
+pub mod manifest;
+pub mod rename;
+pub mod replication;
+pub mod stream;
+pub mod zvol;
+pub use manifest::{ChunkInfo, SendManifest};
+use crate::zfs::{delegation::Delegation,
+                 destroy::DestroyOptions,
+                 holds::Hold,
+                 properties::{AclInheritMode, AclMode},
+                 rename::RenameOptions};
 pub use lzc::ZfsLzc;
 use std::collections::HashMap;
 
@@ -22,7 +37,14 @@ pub use properties::{CacheMode, CanMount, Checksum, Compression, Copies, Filesys
 mod pathext;
 pub use pathext::PathExt;
 
+/// Longest dataset name ZFS accepts, `ZFS_MAX_DATASET_NAME_LEN` without the terminating NUL.
 pub static DATASET_NAME_MAX_LENGTH: usize = 255;
+/// Size of the buffer for user property name (`ZAP_MAXNAMELEN`), terminating NUL included. Names
+/// must be shorter than that.
+pub static USER_PROPERTY_NAME_MAX_LENGTH: usize = 256;
+/// Size of the buffer for user property value in bytes (`ZFS_MAXPROPLEN`), terminating NUL
+/// included. Values must be shorter than that.
+pub static USER_PROPERTY_VALUE_MAX_LENGTH: usize = 8192;
 
 mod errors;
 
@@ -49,15 +71,45 @@ impl DestroyTiming {
     }
 }
 
+/// Request to create a bookmark.
 pub struct BookmarkRequest {
+    /// Source of the bookmark. Either a snapshot or, on OpenZFS 2.0+, another bookmark.
     pub snapshot: PathBuf,
+    /// Name of the new bookmark.
     pub bookmark: PathBuf,
 }
 
 impl BookmarkRequest {
+    /// Bookmark a snapshot.
     pub fn new(snapshot: PathBuf, bookmark: PathBuf) -> Self {
         BookmarkRequest { snapshot, bookmark }
     }
+
+    /// Copy an existing bookmark. Requires OpenZFS 2.0 or newer, older versions fail with
+    /// `ENOTSUP`. Both bookmarks must belong to the same dataset.
+    pub fn from_bookmark(source: PathBuf, bookmark: PathBuf) -> Self {
+        BookmarkRequest { snapshot: source, bookmark }
+    }
+
+    /// Whether this request copies an existing bookmark.
+    pub fn is_copy(&self) -> bool { self.snapshot.is_bookmark() }
+
+    /// Validate source and target names. Source must be a snapshot or a bookmark and target must
+    /// be a bookmark.
+    pub fn validate(&self) -> Vec<ValidationError> {
+        let mut errors: Vec<ValidationError> = vec![&self.snapshot, &self.bookmark]
+            .into_iter()
+            .map(PathBuf::validate)
+            .filter_map(std::result::Result::err)
+            .collect();
+        if !self.snapshot.is_snapshot() && !self.snapshot.is_bookmark() {
+            errors.push(ValidationError::InvalidBookmarkSource(self.snapshot.clone()));
+        }
+        if !self.bookmark.is_bookmark() {
+            errors.push(ValidationError::MissingBookmarkName(self.bookmark.clone()));
+        }
+        errors
+    }
 }
 
 bitflags! {
@@ -82,7 +134,12 @@ pub trait ZfsEngine {
     #[cfg_attr(tarpaulin, skip)]
     fn create(&self, _request: CreateDatasetRequest) -> Result<()> { Err(Error::Unimplemented) }
 
-    /// Create snapshots as one atomic operation.
+    /// Create snapshots as one atomic operation: all of them are taken in the same transaction
+    /// group or none is. If some can't be taken, `BatchFailed` tells why for each of them.
+    ///
+    /// * `snapshots` - Snapshots to create. Must be in the same pool.
+    /// * `user_properties` - User properties (e.g. `backup:job-id`) to set on every snapshot.
+    /// They are applied as part of the same operation, so a snapshot never exists without them.
     #[cfg_attr(tarpaulin, skip)]
     fn snapshot(
         &self,
@@ -96,12 +153,36 @@ pub trait ZfsEngine {
     #[cfg_attr(tarpaulin, skip)]
     fn bookmark(&self, _snapshots: &[BookmarkRequest]) -> Result<()> { Err(Error::Unimplemented) }
 
-    /// Deletes the dataset
-    /// Deletes the dataset
+    /// Deletes the dataset, snapshot or bookmark. Returns `DatasetNotFound` if it doesn't exist
+    /// and `DatasetBusy` if it's in use or has children, snapshots or clones.
     #[cfg_attr(tarpaulin, skip)]
     fn destroy<N: Into<PathBuf>>(&self, _name: N) -> Result<()> { Err(Error::Unimplemented) }
 
-    /// Delete snapshots as one atomic operation
+    /// Destroy a dataset with everything under it like `zfs destroy -r` does, or `-R` with
+    /// [`dependents`](destroy/struct.DestroyOptionsBuilder.html#method.dependents). Dependents are
+    /// destroyed first, one by one. Returns destroyed datasets in order they were destroyed, or
+    /// would be with `dry_run`. See [`destroy`](destroy/index.html).
+    ///
+    /// * `name` - Dataset or snapshot to destroy.
+    /// * `options` - Whether to destroy clones, defer destruction of snapshots or only plan.
+    fn destroy_recursive<N: Into<PathBuf>>(
+        &self,
+        name: N,
+        options: DestroyOptions,
+    ) -> Result<Vec<PathBuf>> {
+        let order = destroy::plan(self, &name.into(), options)?;
+        if !options.dry_run() {
+            destroy::execute(self, &order, options)?;
+        }
+        Ok(order)
+    }
+
+    /// Delete snapshots as one atomic operation. If some can't be destroyed, none is and
+    /// `BatchFailed` tells why for each of them, e.g. `DatasetBusy` for held or cloned snapshots.
+    /// Snapshots that don't exist are skipped.
+    ///
+    /// * `snapshots` - Snapshots to destroy. Must be in the same pool.
+    /// * `timing` - Whether to fail on busy snapshots or destroy them once they are released.
     #[cfg_attr(tarpaulin, skip)]
     fn destroy_snapshots(&self, _snapshots: &[PathBuf], _timing: DestroyTiming) -> Result<()> {
         Err(Error::Unimplemented)
@@ -137,6 +218,30 @@ pub trait ZfsEngine {
         Err(Error::Unimplemented)
     }
 
+    /// Grant permissions on the dataset (`zfs allow`).
+    ///
+    /// * `dataset` - Dataset to grant permissions on.
+    /// * `delegation` - Who gets which permissions.
+    #[cfg_attr(tarpaulin, skip)]
+    fn allow<N: Into<PathBuf>>(&self, _dataset: N, _delegation: &Delegation) -> Result<()> {
+        Err(Error::Unimplemented)
+    }
+
+    /// Revoke permissions on the dataset (`zfs unallow`).
+    ///
+    /// * `dataset` - Dataset to revoke permissions on.
+    /// * `delegation` - Who loses which permissions.
+    #[cfg_attr(tarpaulin, skip)]
+    fn unallow<N: Into<PathBuf>>(&self, _dataset: N, _delegation: &Delegation) -> Result<()> {
+        Err(Error::Unimplemented)
+    }
+
+    /// Permissions granted on the dataset itself. Inherited permissions are not included.
+    #[cfg_attr(tarpaulin, skip)]
+    fn permissions<N: Into<PathBuf>>(&self, _dataset: N) -> Result<Vec<Delegation>> {
+        Err(Error::Unimplemented)
+    }
+
     /// Send a full snapshot to a specified file descriptor.
     #[cfg_attr(tarpaulin, skip)]
     fn send_full<N: Into<PathBuf>, FD: AsRawFd>(
@@ -159,6 +264,147 @@ pub trait ZfsEngine {
     ) -> Result<()> {
         Err(Error::Unimplemented)
     }
+
+    /// Estimate size of the stream that `send_full` or `send_incremental` would produce.
+    ///
+    /// * `path` - Snapshot to send.
+    /// * `from` - Snapshot or bookmark to send incremental stream from.
+    /// * `flags` - Send flags.
+    #[cfg_attr(tarpaulin, skip)]
+    fn send_space<N: Into<PathBuf>>(
+        &self,
+        _path: N,
+        _from: Option<PathBuf>,
+        _flags: SendFlags,
+    ) -> Result<u64> {
+        Err(Error::Unimplemented)
+    }
+
+    /// Receive a stream from a specified file descriptor.
+    ///
+    /// * `snapshot` - Snapshot to create, e.g. `backup/data@today`.
+    /// * `fd` - Where to read the stream from.
+    /// * `force` - Roll back destination to its most recent snapshot before receiving.
+    #[cfg_attr(tarpaulin, skip)]
+    fn receive<N: Into<PathBuf>, FD: AsRawFd>(
+        &self,
+        _snapshot: N,
+        _fd: FD,
+        _force: bool,
+    ) -> Result<()> {
+        Err(Error::Unimplemented)
+    }
+
+    /// Mount a filesystem at its `mountpoint`.
+    ///
+    /// * `name` - Filesystem to mount.
+    #[cfg_attr(tarpaulin, skip)]
+    fn mount<N: Into<PathBuf>>(&self, _name: N) -> Result<()> { Err(Error::Unimplemented) }
+
+    /// Unmount a filesystem.
+    ///
+    /// * `name` - Filesystem to unmount.
+    #[cfg_attr(tarpaulin, skip)]
+    fn unmount<N: Into<PathBuf>>(&self, _name: N) -> Result<()> { Err(Error::Unimplemented) }
+
+    /// Rename a dataset together with its children. Mounted filesystems are remounted at their
+    /// new mountpoints unless `no_remount` is set. See
+    /// [`rename::RenamePlan`](rename/struct.RenamePlan.html) for a rename that checks mountpoints
+    /// first and undoes itself on failure.
+    ///
+    /// * `name` - Dataset or snapshot to rename.
+    /// * `new_name` - New name in the same pool. Parent must exist unless `create_parents` is set.
+    /// * `options` - `-p` and `-u` flags of `zfs rename`.
+    #[cfg_attr(tarpaulin, skip)]
+    fn rename<N: Into<PathBuf>, M: Into<PathBuf>>(
+        &self,
+        _name: N,
+        _new_name: M,
+        _options: RenameOptions,
+    ) -> Result<()> {
+        Err(Error::Unimplemented)
+    }
+
+    /// Create a writable dataset out of a snapshot. The snapshot can't be destroyed while the
+    /// clone exists.
+    ///
+    /// * `snapshot` - Snapshot to clone.
+    /// * `target` - Name of the clone. Must be in the same pool as the snapshot.
+    /// * `properties` - Properties to set on the clone, e.g. user properties.
+    #[cfg_attr(tarpaulin, skip)]
+    fn clone_snapshot<N: Into<PathBuf>, M: Into<PathBuf>>(
+        &self,
+        _snapshot: N,
+        _target: M,
+        _properties: Option<HashMap<String, String>>,
+    ) -> Result<()> {
+        Err(Error::Unimplemented)
+    }
+
+    /// Promote a clone: snapshots of its origin up to the one it was cloned from move to the clone
+    /// and the origin becomes a clone of it. Afterwards the former origin can be destroyed without
+    /// touching the clone.
+    ///
+    /// * `name` - Clone to promote. `NotAClone` if it isn't one.
+    #[cfg_attr(tarpaulin, skip)]
+    fn promote<N: Into<PathBuf>>(&self, _name: N) -> Result<()> { Err(Error::Unimplemented) }
+
+    /// Roll a filesystem or volume back to a snapshot. Changes made since the snapshot are lost.
+    /// Returns the snapshot it was rolled back to.
+    ///
+    /// * `name` - Snapshot to roll back to, or a dataset to roll back to its most recent snapshot.
+    /// `NoSnapshots` if the dataset has none.
+    /// * `destroy_newer` - Destroy snapshots taken after the one rolled back to (`-r`). Without it
+    /// rolling back to anything but the most recent snapshot fails with `NotLatestSnapshot`.
+    #[cfg_attr(tarpaulin, skip)]
+    fn rollback<N: Into<PathBuf>>(&self, _name: N, _destroy_newer: bool) -> Result<PathBuf> {
+        Err(Error::Unimplemented)
+    }
+
+    /// Place a user hold on a snapshot. Held snapshot can't be destroyed until every hold is
+    /// released. See [`holds`](holds/index.html).
+    ///
+    /// * `snapshot` - Snapshot to hold.
+    /// * `tag` - Name of the hold. `HoldExists` if the snapshot already has a hold with this tag.
+    /// * `cleanup_fd` - Descriptor from [`holds::cleanup_file`](holds/fn.cleanup_file.html). The
+    /// hold is released when it's closed. `None` for a hold that stays until it's released.
+    #[cfg_attr(tarpaulin, skip)]
+    fn hold<N: Into<PathBuf>>(
+        &self,
+        _snapshot: N,
+        _tag: &str,
+        _cleanup_fd: Option<RawFd>,
+    ) -> Result<()> {
+        Err(Error::Unimplemented)
+    }
+
+    /// Release a user hold. Snapshot marked for deferred destruction is destroyed with its last
+    /// hold.
+    ///
+    /// * `snapshot` - Held snapshot.
+    /// * `tag` - Name of the hold. `HoldNotFound` if the snapshot has no hold with this tag.
+    #[cfg_attr(tarpaulin, skip)]
+    fn release<N: Into<PathBuf>>(&self, _snapshot: N, _tag: &str) -> Result<()> {
+        Err(Error::Unimplemented)
+    }
+
+    /// User holds on a snapshot, oldest first. Empty if nothing holds it.
+    ///
+    /// * `snapshot` - Snapshot to look at.
+    #[cfg_attr(tarpaulin, skip)]
+    fn holds<N: Into<PathBuf>>(&self, _snapshot: N) -> Result<Vec<Hold>> {
+        Err(Error::Unimplemented)
+    }
+
+    /// Set a property of a dataset.
+    ///
+    /// * `name` - Dataset to change.
+    /// * `key` - Name of the property.
+    /// * `value` - New value, as `zfs set` takes it.
+    #[cfg_attr(tarpaulin, skip)]
+    fn set_property<N: Into<PathBuf>>(&self, _name: N, _key: &str, _value: &str) -> Result<()> {
+        Err(Error::Unimplemented)
+    }
 }
 
 #[derive(Default, Builder, Debug, Clone, Getters)]
@@ -287,7 +533,9 @@ impl CreateDatasetRequest {
 }
 
 pub(crate) mod validators {
-    use crate::zfs::{errors::ValidationResult, ValidationError, DATASET_NAME_MAX_LENGTH};
+    use crate::{args::validate_value,
+                zfs::{errors::ValidationResult, ValidationError, DATASET_NAME_MAX_LENGTH,
+                      USER_PROPERTY_NAME_MAX_LENGTH, USER_PROPERTY_VALUE_MAX_LENGTH}};
     use std::path::Path;
 
     pub fn validate_name<P: AsRef<Path>>(dataset: P) -> ValidationResult {
@@ -312,6 +560,29 @@ pub(crate) mod validators {
                 Ok(())
             })
     }
+
+    /// Whether ZFS allows the character in a property name: lowercase letters, digits and `:`,
+    /// `-`, `.`, `_`.
+    pub fn is_property_name_char(c: char) -> bool {
+        c.is_ascii_lowercase() || c.is_ascii_digit() || ":-._".contains(c)
+    }
+
+    /// User property names must contain a colon and consist of
+    /// [allowed characters](fn.is_property_name_char.html). Values can't contain NUL or line
+    /// breaks.
+    pub fn validate_user_property(name: &str, value: &str) -> ValidationResult {
+        if !name.contains(':')
+            || name.len() >= USER_PROPERTY_NAME_MAX_LENGTH
+            || !name.chars().all(is_property_name_char)
+        {
+            return Err(ValidationError::InvalidUserPropertyName(name.to_owned()));
+        }
+        if value.len() >= USER_PROPERTY_VALUE_MAX_LENGTH {
+            return Err(ValidationError::UserPropertyValueTooLong(name.to_owned()));
+        }
+        validate_value(name, value)
+            .map_err(|_| ValidationError::InvalidUserPropertyValue(name.to_owned()))
+    }
 }
 
 #[cfg(test)]
@@ -362,4 +633,64 @@ mod test {
         let expected = Error::from(vec![ValidationError::NameTooLong(path.clone())]);
         assert_eq!(expected, result);
     }
+
+    #[test]
+    fn test_user_property_validator() {
+        use super::validators::validate_user_property;
+
+        assert!(validate_user_property("backup:job-id", "42").is_ok());
+        assert!(validate_user_property("com.example:a_b.c", "").is_ok());
+        assert_eq!(
+            Err(ValidationError::InvalidUserPropertyName(String::from("com.example:a_b+c"))),
+            validate_user_property("com.example:a_b+c", "")
+        );
+        assert_eq!(
+            Err(ValidationError::InvalidUserPropertyName(String::from("jobid"))),
+            validate_user_property("jobid", "42")
+        );
+        assert_eq!(
+            Err(ValidationError::InvalidUserPropertyName(String::from("Backup:id"))),
+            validate_user_property("Backup:id", "42")
+        );
+        let name = format!("backup:{}", "x".repeat(248));
+        assert!(validate_user_property(&name, "42").is_ok());
+        let name = format!("backup:{}", "x".repeat(249));
+        assert_eq!(
+            Err(ValidationError::InvalidUserPropertyName(name.clone())),
+            validate_user_property(&name, "42")
+        );
+        assert!(validate_user_property("backup:job-id", &"x".repeat(8191)).is_ok());
+        let value = "x".repeat(8192);
+        assert_eq!(
+            Err(ValidationError::UserPropertyValueTooLong(String::from("backup:job-id"))),
+            validate_user_property("backup:job-id", &value)
+        );
+        assert_eq!(
+            Err(ValidationError::InvalidUserPropertyValue(String::from("backup:job-id"))),
+            validate_user_property("backup:job-id", "4\n2")
+        );
+    }
+
+    #[test]
+    fn test_bookmark_request_validation() {
+        use super::BookmarkRequest;
+
+        let request = BookmarkRequest::new(PathBuf::from("z/a@snap"), PathBuf::from("z/a#mark"));
+        assert!(!request.is_copy());
+        assert!(request.validate().is_empty());
+
+        let request =
+            BookmarkRequest::from_bookmark(PathBuf::from("z/a#mark"), PathBuf::from("z/a#copy"));
+        assert!(request.is_copy());
+        assert!(request.validate().is_empty());
+
+        let request = BookmarkRequest::new(PathBuf::from("z/a"), PathBuf::from("z/a@copy"));
+        assert_eq!(
+            vec![
+                ValidationError::InvalidBookmarkSource(PathBuf::from("z/a")),
+                ValidationError::MissingBookmarkName(PathBuf::from("z/a@copy")),
+            ],
+            request.validate()
+        );
+    }
 }
@@ -1,24 +1,25 @@
-use crate::zfs::{DatasetKind, Error, FilesystemProperties, Properties, Result, VolumeProperties,
-                 ZfsEngine};
+use crate::zfs::{delegation::Delegation, rename::RenameOptions, DatasetKind, Error,
+                 FilesystemProperties, PathExt, Properties, Result, VolumeProperties, ZfsEngine};
 use chrono::NaiveDateTime;
 use slog::Logger;
-use std::{ffi::OsString,
+use std::{collections::HashMap,
+          ffi::OsString,
           path::PathBuf,
           process::{Command, Stdio}};
 
-use crate::{parsers::zfs::{Rule, ZfsParser},
-            utils::parse_float,
+use crate::{args::property_arg,
+            parsers::zfs::{parse_datasets, parse_datasets_with_type},
+            utils::{is_overflow, parse_int, Bounded, Overflow},
             zfs::properties::{BookmarkProperties, SnapshotProperties},
             GlobalLogger};
-use pest::Parser;
-use std::str::Lines;
+use std::str::{FromStr, Lines};
 
-static FAILED_TO_PARSE: &str = "Failed to parse value";
 static DATE_FORMAT: &str = "%a %b %e %k:%M %Y";
 
 pub struct ZfsOpen3 {
     cmd_name: OsString,
     logger:   Logger,
+    overflow: Overflow,
 }
 
 impl ZfsOpen3 {
@@ -31,7 +32,14 @@ impl ZfsOpen3 {
             None => "zfs".into(),
         };
 
-        ZfsOpen3 { logger, cmd_name }
+        ZfsOpen3 { logger, cmd_name, overflow: Overflow::default() }
+    }
+
+    /// Set what to do with property values that don't fit into their type. By default it's an
+    /// error.
+    pub fn with_overflow(mut self, overflow: Overflow) -> ZfsOpen3 {
+        self.overflow = overflow;
+        self
     }
 
     pub fn logger(&self) -> &Logger { &self.logger }
@@ -46,11 +54,25 @@ impl ZfsOpen3 {
         z.stderr(Stdio::null());
         z
     }
+
+    fn delegate(&self, cmd: &str, dataset: PathBuf, delegation: &Delegation) -> Result<()> {
+        let mut z = self.zfs();
+        z.arg(cmd);
+        z.args(delegation.to_args());
+        z.arg(dataset.as_os_str());
+        debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
+        let out = z.output()?;
+        if out.status.success() {
+            Ok(())
+        } else {
+            Err(Error::from_stderr(&out.stderr))
+        }
+    }
 }
 
 impl ZfsEngine for ZfsOpen3 {
     fn destroy<N: Into<PathBuf>>(&self, name: N) -> Result<()> {
-        let mut z = self.zfs_mute();
+        let mut z = self.zfs();
         z.arg("destroy");
         z.arg(name.into().as_os_str());
 
@@ -59,12 +81,10 @@ impl ZfsEngine for ZfsOpen3 {
         if out.status.success() {
             Ok(())
         } else {
-            Err(Error::Unknown)
+            Err(Error::from_stderr(&out.stderr))
         }
     }
 
-    #[allow(clippy::option_unwrap_used)]
-    #[allow(clippy::result_unwrap_used)]
     fn list<N: Into<PathBuf>>(&self, prefix: N) -> Result<Vec<(DatasetKind, PathBuf)>> {
         let mut z = self.zfs();
         z.args(&["list", "-t", "all", "-o", "type,name", "-Hpr"]);
@@ -74,30 +94,8 @@ impl ZfsEngine for ZfsOpen3 {
         let out = z.output()?;
         if out.status.success() {
             let stdout = String::from_utf8_lossy(&out.stdout);
-            ZfsParser::parse(Rule::datasets_with_type, &stdout)
-                .map(|mut pairs| {
-                    pairs
-                        .next()
-                        .unwrap()
-                        .into_inner()
-                        .map(|pair| {
-                            //
-                            // - datasets_with_type
-                            //   - dataset_with_type
-                            //     - dataset_type: "volume"
-                            //     - dataset_name: "z/iohyve/rancher/disk0"
-                            debug_assert_eq!(Rule::dataset_with_type, pair.as_rule());
-                            let mut inner = pair.into_inner();
-
-                            let dataset_type_pair = inner.next().unwrap();
-                            let dataset_name_pair = inner.next().unwrap();
-                            let dataset_type = dataset_type_pair.as_str().parse().unwrap();
-                            let dataset_name = PathBuf::from(dataset_name_pair.as_str());
-                            (dataset_type, dataset_name)
-                        })
-                        .collect()
-                })
-                .map_err(|_| Error::UnknownSoFar(String::from(stdout)))
+            parse_datasets_with_type(&stdout)
+                .ok_or_else(|| Error::UnknownSoFar(String::from(stdout)))
         } else {
             Err(Error::from_stderr(&out.stderr))
         }
@@ -149,10 +147,10 @@ impl ZfsEngine for ZfsOpen3 {
             let first = lines.next().expect("Empty stdout with 0 exit code");
             let kind = parse_prop_line(&first).1;
             let ret = match kind.as_ref() {
-                "filesystem" => parse_filesystem_lines(&mut lines, path),
-                "snapshot" => parse_snapshot_lines(&mut lines, path),
-                "volume" => parse_volume_lines(&mut lines, path),
-                "bookmark" => parse_bookmark_lines(&mut lines, path),
+                "filesystem" => parse_filesystem_lines(&mut lines, path, self.overflow)?,
+                "snapshot" => parse_snapshot_lines(&mut lines, path, self.overflow)?,
+                "volume" => parse_volume_lines(&mut lines, path, self.overflow)?,
+                "bookmark" => parse_bookmark_lines(&mut lines, path, self.overflow)?,
                 _ => parse_unknown_lines(&mut lines),
             };
             Ok(ret)
@@ -160,27 +158,163 @@ impl ZfsEngine for ZfsOpen3 {
             Err(Error::from_stderr(&out.stderr))
         }
     }
+
+    fn allow<N: Into<PathBuf>>(&self, dataset: N, delegation: &Delegation) -> Result<()> {
+        self.delegate("allow", dataset.into(), delegation)
+    }
+
+    fn unallow<N: Into<PathBuf>>(&self, dataset: N, delegation: &Delegation) -> Result<()> {
+        self.delegate("unallow", dataset.into(), delegation)
+    }
+
+    fn mount<N: Into<PathBuf>>(&self, name: N) -> Result<()> {
+        let mut z = self.zfs();
+        z.arg("mount");
+        z.arg(name.into().as_os_str());
+        debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
+        let out = z.output()?;
+        if out.status.success() {
+            Ok(())
+        } else {
+            Err(Error::from_stderr(&out.stderr))
+        }
+    }
+
+    fn unmount<N: Into<PathBuf>>(&self, name: N) -> Result<()> {
+        let mut z = self.zfs();
+        z.arg("unmount");
+        z.arg(name.into().as_os_str());
+        debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
+        let out = z.output()?;
+        if out.status.success() {
+            Ok(())
+        } else {
+            Err(Error::from_stderr(&out.stderr))
+        }
+    }
+
+    fn rename<N: Into<PathBuf>, M: Into<PathBuf>>(
+        &self,
+        name: N,
+        new_name: M,
+        options: RenameOptions,
+    ) -> Result<()> {
+        let mut z = self.zfs();
+        z.arg("rename");
+        if *options.create_parents() {
+            z.arg("-p");
+        }
+        if *options.no_remount() {
+            z.arg("-u");
+        }
+        z.arg(name.into().as_os_str());
+        z.arg(new_name.into().as_os_str());
+        debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
+        let out = z.output()?;
+        if out.status.success() {
+            Ok(())
+        } else {
+            Err(Error::from_stderr(&out.stderr))
+        }
+    }
+
+    fn clone_snapshot<N: Into<PathBuf>, M: Into<PathBuf>>(
+        &self,
+        snapshot: N,
+        target: M,
+        properties: Option<HashMap<String, String>>,
+    ) -> Result<()> {
+        let mut z = self.zfs();
+        z.arg("clone");
+        for (key, value) in properties.unwrap_or_default() {
+            z.arg("-o");
+            z.arg(property_arg(&key, &value)?);
+        }
+        z.arg(snapshot.into().as_os_str());
+        z.arg(target.into().as_os_str());
+        debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
+        let out = z.output()?;
+        if out.status.success() {
+            Ok(())
+        } else {
+            Err(Error::from_stderr(&out.stderr))
+        }
+    }
+
+    fn promote<N: Into<PathBuf>>(&self, name: N) -> Result<()> {
+        let mut z = self.zfs();
+        z.arg("promote");
+        z.arg(name.into().as_os_str());
+        debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
+        let out = z.output()?;
+        if out.status.success() {
+            Ok(())
+        } else {
+            Err(Error::from_stderr(&out.stderr))
+        }
+    }
+
+    fn rollback<N: Into<PathBuf>>(&self, name: N, destroy_newer: bool) -> Result<PathBuf> {
+        let name = name.into();
+        let snapshot = if name.is_snapshot() {
+            name
+        } else {
+            let mut z = self.zfs();
+            z.args(&["list", "-t", "snapshot", "-o", "name", "-s", "createtxg", "-Hp", "-d", "1"]);
+            z.arg(name.as_os_str());
+            debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
+            let snapshots = ZfsOpen3::stdout_to_list_of_datasets(&mut z)?;
+            snapshots.into_iter().last().ok_or(Error::NoSnapshots(name))?
+        };
+
+        let mut z = self.zfs();
+        z.arg("rollback");
+        if destroy_newer {
+            z.arg("-r");
+        }
+        z.arg(snapshot.as_os_str());
+        debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
+        let out = z.output()?;
+        if out.status.success() {
+            Ok(snapshot)
+        } else {
+            Err(Error::from_stderr(&out.stderr))
+        }
+    }
+
+    fn set_property<N: Into<PathBuf>>(&self, name: N, key: &str, value: &str) -> Result<()> {
+        let mut z = self.zfs();
+        z.arg("set");
+        z.arg(property_arg(key, value)?);
+        z.arg(name.into().as_os_str());
+        debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
+        let out = z.output()?;
+        if out.status.success() {
+            Ok(())
+        } else {
+            Err(Error::from_stderr(&out.stderr))
+        }
+    }
+
+    fn permissions<N: Into<PathBuf>>(&self, dataset: N) -> Result<Vec<Delegation>> {
+        let mut z = self.zfs();
+        z.arg("allow");
+        z.arg(dataset.into().as_os_str());
+        debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
+        let out = z.output()?;
+        if out.status.success() {
+            Ok(Delegation::parse_allow(&String::from_utf8_lossy(&out.stdout)))
+        } else {
+            Err(Error::from_stderr(&out.stderr))
+        }
+    }
 }
 
 impl ZfsOpen3 {
-    #[allow(clippy::option_unwrap_used)]
     fn stdout_to_list_of_datasets(z: &mut Command) -> Result<Vec<PathBuf>, Error> {
         let out = z.output()?;
         if out.status.success() {
-            let stdout = String::from_utf8_lossy(&out.stdout);
-            ZfsParser::parse(Rule::datasets, &stdout)
-                .map(|mut pairs| {
-                    pairs
-                        .next()
-                        .unwrap()
-                        .into_inner()
-                        .map(|pair| {
-                            debug_assert_eq!(Rule::dataset_name, pair.as_rule());
-                            PathBuf::from(pair.as_str())
-                        })
-                        .collect()
-                })
-                .map_err(|_| Error::UnknownSoFar(String::from(stdout)))
+            Ok(parse_datasets(&String::from_utf8_lossy(&out.stdout)))
         } else {
             Err(Error::from_stderr(&out.stderr))
         }
@@ -204,88 +338,110 @@ fn parse_list_of_pathbufs(value: &str) -> Option<Vec<PathBuf>> {
     Some(clones)
 }
 
-fn parse_creation_into_timestamp(value: &str) -> i64 {
+fn parse_creation_into_timestamp(value: &str) -> Result<i64> {
     if let Ok(timestamp) = value.parse() {
-        timestamp
+        Ok(timestamp)
     } else {
-        let date = NaiveDateTime::parse_from_str(value, DATE_FORMAT).expect(FAILED_TO_PARSE);
-        date.timestamp()
+        let date = NaiveDateTime::parse_from_str(value, DATE_FORMAT)
+            .map_err(|_| parse_error("creation", value))?;
+        Ok(date.timestamp())
     }
 }
 
-pub(crate) fn parse_filesystem_lines(lines: &mut Lines, name: PathBuf) -> Properties {
+fn parse_error(key: &str, value: &str) -> Error {
+    Error::ParseError { property: key.into(), value: value.into() }
+}
+
+fn parse<T: FromStr>(key: &str, value: &str) -> Result<T> {
+    value.parse().map_err(|_| parse_error(key, value))
+}
+
+fn parse_num<T: Bounded>(key: &str, value: &str, overflow: Overflow) -> Result<T> {
+    parse_int(value, overflow).map_err(|e| {
+        if is_overflow(&e) {
+            Error::Overflow(value.into())
+        } else {
+            parse_error(key, value)
+        }
+    })
+}
+
+pub(crate) fn parse_filesystem_lines(
+    lines: &mut Lines,
+    name: PathBuf,
+    overflow: Overflow,
+) -> Result<Properties> {
     let mut properties = FilesystemProperties::builder(name);
     for (key, value) in lines.map(parse_prop_line) {
         match key.as_ref() {
             "aclinherit" => {
-                properties.acl_inherit(value.parse().expect(FAILED_TO_PARSE));
+                properties.acl_inherit(parse(&key, &value)?);
             },
             "aclmode" => {
-                properties.acl_mode(Some(value.parse().expect(FAILED_TO_PARSE)));
+                properties.acl_mode(Some(parse(&key, &value)?));
             },
             "atime" => {
                 properties.atime(parse_bool(&value));
             },
             "available" => {
-                properties.available(value.parse().expect(FAILED_TO_PARSE));
+                properties.available(parse_num(&key, &value, overflow)?);
             },
             "canmount" => {
-                properties.can_mount(value.parse().expect(FAILED_TO_PARSE));
+                properties.can_mount(parse(&key, &value)?);
             },
             "casesensitivity" => {
-                properties.case_sensitivity(value.parse().expect(FAILED_TO_PARSE));
+                properties.case_sensitivity(parse(&key, &value)?);
             },
             "checksum" => {
-                properties.checksum(value.parse().expect(FAILED_TO_PARSE));
+                properties.checksum(parse(&key, &value)?);
             },
             "compression" => {
-                properties.compression(value.parse().expect(FAILED_TO_PARSE));
+                properties.compression(parse(&key, &value)?);
             },
             "compressratio" => {
-                properties
-                    .compression_ratio(parse_float(&mut value.clone()).expect(FAILED_TO_PARSE));
+                properties.compression_ratio(parse(&key, &value)?);
             },
             "copies" => {
-                properties.copies(value.parse().expect(FAILED_TO_PARSE));
+                properties.copies(parse(&key, &value)?);
             },
             "createtxg" => {
-                properties.create_txg(Some(value.parse().expect(FAILED_TO_PARSE)));
+                properties.create_txg(Some(parse_num(&key, &value, overflow)?));
             },
             "creation" => {
-                properties.creation(value.parse().expect(FAILED_TO_PARSE));
+                properties.creation(parse_num(&key, &value, overflow)?);
             },
             "dedup" => {
-                properties.dedup(value.parse().expect(FAILED_TO_PARSE));
+                properties.dedup(parse(&key, &value)?);
             },
             "devices" => {
                 properties.devices(parse_bool(&value));
             },
             "dnodesize" => {
-                properties.dnode_size(value.parse().expect(FAILED_TO_PARSE));
+                properties.dnode_size(parse(&key, &value)?);
             },
             "exec" => {
                 properties.exec(parse_bool(&value));
             },
             "filesystem_count" => {
-                properties.filesystem_count(value.parse().expect(FAILED_TO_PARSE));
+                properties.filesystem_count(parse_num(&key, &value, overflow)?);
             },
             "filesystem_limit" => {
-                properties.filesystem_limit(value.parse().expect(FAILED_TO_PARSE));
+                properties.filesystem_limit(parse_num(&key, &value, overflow)?);
             },
             "guid" => {
-                properties.guid(Some(value.parse().expect(FAILED_TO_PARSE)));
+                properties.guid(Some(parse_num(&key, &value, overflow)?));
             },
             "jailed" => {
                 properties.jailed(Some(parse_bool(&value)));
             },
             "logbias" => {
-                properties.log_bias(value.parse().expect(FAILED_TO_PARSE));
+                properties.log_bias(parse(&key, &value)?);
             },
             "logicalreferenced" => {
-                properties.logical_referenced(value.parse().expect(FAILED_TO_PARSE));
+                properties.logical_referenced(parse_num(&key, &value, overflow)?);
             },
             "logicalused" => {
-                properties.logical_used(value.parse().expect(FAILED_TO_PARSE));
+                properties.logical_used(parse_num(&key, &value, overflow)?);
             },
             "mlslabel" => {
                 properties.mls_label(parse_mls_label(value));
@@ -300,89 +456,88 @@ pub(crate) fn parse_filesystem_lines(lines: &mut Lines, name: PathBuf) -> Proper
                 properties.nbmand(parse_bool(&value));
             },
             "normalization" => {
-                properties.normalization(value.parse().expect(FAILED_TO_PARSE));
+                properties.normalization(parse(&key, &value)?);
             },
             "origin" => {
                 properties.origin(Some(value));
             },
             "primarycache" => {
-                properties.primary_cache(value.parse().expect(FAILED_TO_PARSE));
+                properties.primary_cache(parse(&key, &value)?);
             },
             "quota" => {
-                properties.quota(value.parse().expect(FAILED_TO_PARSE));
+                properties.quota(parse_num(&key, &value, overflow)?);
             },
             "readonly" => {
                 properties.readonly(parse_bool(&value));
             },
             "recordsize" => {
-                properties.record_size(value.parse().expect(FAILED_TO_PARSE));
+                properties.record_size(parse_num(&key, &value, overflow)?);
             },
             "redundant_metadata" => {
-                properties.redundant_metadata(value.parse().expect(FAILED_TO_PARSE));
+                properties.redundant_metadata(parse(&key, &value)?);
             },
             "refcompressratio" => {
-                properties
-                    .ref_compression_ratio(parse_float(&mut value.clone()).expect(FAILED_TO_PARSE));
+                properties.ref_compression_ratio(parse(&key, &value)?);
             },
             "refquota" => {
-                properties.ref_quota(value.parse().expect(FAILED_TO_PARSE));
+                properties.ref_quota(parse_num(&key, &value, overflow)?);
             },
             "refreservation" => {
-                properties.ref_reservation(value.parse().expect(FAILED_TO_PARSE));
+                properties.ref_reservation(parse_num(&key, &value, overflow)?);
             },
             "referenced" => {
-                properties.referenced(value.parse().expect(FAILED_TO_PARSE));
+                properties.referenced(parse_num(&key, &value, overflow)?);
             },
             "reservation" => {
-                properties.reservation(value.parse().expect(FAILED_TO_PARSE));
+                properties.reservation(parse_num(&key, &value, overflow)?);
             },
             "secondarycache" => {
-                properties.secondary_cache(value.parse().expect(FAILED_TO_PARSE));
+                properties.secondary_cache(parse(&key, &value)?);
             },
             "setuid" => {
                 properties.setuid(parse_bool(&value));
             },
             "snapdir" => {
-                properties.snap_dir(value.parse().expect(FAILED_TO_PARSE));
+                properties.snap_dir(parse(&key, &value)?);
             },
             "snapshot_count" => {
-                properties.snapshot_count(value.parse().expect(FAILED_TO_PARSE));
+                properties.snapshot_count(parse_num(&key, &value, overflow)?);
             },
             "snapshot_limit" => {
-                properties.snapshot_limit(value.parse().expect(FAILED_TO_PARSE));
+                properties.snapshot_limit(parse_num(&key, &value, overflow)?);
             },
             "sync" => {
-                properties.sync(value.parse().expect(FAILED_TO_PARSE));
+                properties.sync(parse(&key, &value)?);
             },
             "used" => {
-                properties.used(value.parse().expect(FAILED_TO_PARSE));
+                properties.used(parse_num(&key, &value, overflow)?);
             },
             "usedbychildren" => {
-                properties.used_by_children(value.parse().expect(FAILED_TO_PARSE));
+                properties.used_by_children(parse_num(&key, &value, overflow)?);
             },
             "usedbydataset" => {
-                properties.used_by_dataset(value.parse().expect(FAILED_TO_PARSE));
+                properties.used_by_dataset(parse_num(&key, &value, overflow)?);
             },
             "usedbyrefreservation" => {
-                properties.used_by_ref_reservation(value.parse().expect(FAILED_TO_PARSE));
+                properties.used_by_ref_reservation(parse_num(&key, &value, overflow)?);
             },
             "usedbysnapshots" => {
-                properties.used_by_snapshots(value.parse().expect(FAILED_TO_PARSE));
+                properties.used_by_snapshots(parse_num(&key, &value, overflow)?);
             },
             "utf8only" => {
                 properties.utf8_only(Some(parse_bool(&value)));
             },
             "version" => {
-                properties.version(value.parse().expect(FAILED_TO_PARSE));
+                properties.version(parse_num(&key, &value, overflow)?);
             },
             "volmode" => {
-                properties.volume_mode(Some(value.parse().expect(FAILED_TO_PARSE)));
+                properties.volume_mode(Some(parse(&key, &value)?));
             },
             "vscan" => {
                 properties.vscan(parse_bool(&value));
             },
             "written" => {
-                properties.written(value.parse().expect(FAILED_TO_PARSE));
+                properties.written(parse_num(&key, &value, overflow)?);
             },
             "xattr" => {
                 properties.xattr(parse_bool(&value));
@@ -392,28 +547,31 @@ pub(crate) fn parse_filesystem_lines(lines: &mut Lines, name: PathBuf) -> Proper
             _ => properties.insert_unknown_property(key, value),
         };
     }
-    Properties::Filesystem(properties.build().expect("Failed to build properties"))
+    Ok(Properties::Filesystem(properties.build().expect("Failed to build properties")))
 }
 
-pub(crate) fn parse_snapshot_lines(lines: &mut Lines, name: PathBuf) -> Properties {
+pub(crate) fn parse_snapshot_lines(
+    lines: &mut Lines,
+    name: PathBuf,
+    overflow: Overflow,
+) -> Result<Properties> {
     let mut properties = SnapshotProperties::builder(name);
     for (key, value) in lines.map(parse_prop_line) {
         match key.as_ref() {
             "casesensitivity" => {
-                properties.case_sensitivity(value.parse().expect(FAILED_TO_PARSE));
+                properties.case_sensitivity(parse(&key, &value)?);
             },
             "clones" => {
                 properties.clones(parse_list_of_pathbufs(&value));
             },
             "compressratio" => {
-                properties
-                    .compression_ratio(parse_float(&mut value.clone()).expect(FAILED_TO_PARSE));
+                properties.compression_ratio(parse(&key, &value)?);
             },
             "createtxg" => {
-                properties.create_txg(Some(value.parse().expect(FAILED_TO_PARSE)));
+                properties.create_txg(Some(parse_num(&key, &value, overflow)?));
             },
             "creation" => {
-                properties.creation(parse_creation_into_timestamp(&value));
+                properties.creation(parse_creation_into_timestamp(&value)?);
             },
             "defer_destroy" => {
                 properties.defer_destroy(parse_bool(&value));
@@ -425,10 +583,10 @@ pub(crate) fn parse_snapshot_lines(lines: &mut Lines, name: PathBuf) -> Properti
                 properties.exec(parse_bool(&value));
             },
             "guid" => {
-                properties.guid(Some(value.parse().expect(FAILED_TO_PARSE)));
+                properties.guid(Some(parse_num(&key, &value, overflow)?));
             },
             "logicalreferenced" => {
-                properties.logically_referenced(value.parse().expect(FAILED_TO_PARSE));
+                properties.logically_referenced(parse_num(&key, &value, overflow)?);
             },
             "mlslabel" => {
                 properties.mls_label(parse_mls_label(value));
@@ -437,41 +595,40 @@ pub(crate) fn parse_snapshot_lines(lines: &mut Lines, name: PathBuf) -> Properti
                 properties.nbmand(parse_bool(&value));
             },
             "normalization" => {
-                properties.normalization(value.parse().expect(FAILED_TO_PARSE));
+                properties.normalization(parse(&key, &value)?);
             },
             "primarycache" => {
-                properties.primary_cache(value.parse().expect(FAILED_TO_PARSE));
+                properties.primary_cache(parse(&key, &value)?);
             },
             "refcompressratio" => {
-                properties
-                    .ref_compression_ratio(parse_float(&mut value.clone()).expect(FAILED_TO_PARSE));
+                properties.ref_compression_ratio(parse(&key, &value)?);
             },
             "referenced" => {
-                properties.referenced(value.parse().expect(FAILED_TO_PARSE));
+                properties.referenced(parse_num(&key, &value, overflow)?);
             },
             "secondarycache" => {
-                properties.secondary_cache(value.parse().expect(FAILED_TO_PARSE));
+                properties.secondary_cache(parse(&key, &value)?);
             },
             "setuid" => {
                 properties.setuid(parse_bool(&value));
             },
             "used" => {
-                properties.used(value.parse().expect(FAILED_TO_PARSE));
+                properties.used(parse_num(&key, &value, overflow)?);
             },
             "userrefs" => {
-                properties.user_refs(value.parse().expect(FAILED_TO_PARSE));
+                properties.user_refs(parse_num(&key, &value, overflow)?);
             },
             "utf8only" => {
                 properties.utf8_only(Some(parse_bool(&value)));
             },
             "version" => {
-                properties.version(value.parse().expect(FAILED_TO_PARSE));
+                properties.version(parse_num(&key, &value, overflow)?);
             },
             "volmode" => {
-                properties.volume_mode(Some(value.parse().expect(FAILED_TO_PARSE)));
+                properties.volume_mode(Some(parse(&key, &value)?));
             },
             "written" => {
-                properties.written(value.parse().expect(FAILED_TO_PARSE));
+                properties.written(parse_num(&key, &value, overflow)?);
             },
             "xattr" => {
                 properties.xattr(parse_bool(&value));
@@ -481,141 +638,147 @@ pub(crate) fn parse_snapshot_lines(lines: &mut Lines, name: PathBuf) -> Properti
             _ => properties.insert_unknown_property(key, value),
         };
     }
-    Properties::Snapshot(properties.build().expect("Failed to build properties"))
+    Ok(Properties::Snapshot(properties.build().expect("Failed to build properties")))
 }
 
-pub(crate) fn parse_volume_lines(lines: &mut Lines, name: PathBuf) -> Properties {
+pub(crate) fn parse_volume_lines(
+    lines: &mut Lines,
+    name: PathBuf,
+    overflow: Overflow,
+) -> Result<Properties> {
     let mut properties = VolumeProperties::builder(name);
     for (key, value) in lines.map(parse_prop_line) {
         match key.as_ref() {
             "available" => {
-                properties.available(value.parse().expect(FAILED_TO_PARSE));
+                properties.available(parse_num(&key, &value, overflow)?);
             },
             "checksum" => {
-                properties.checksum(value.parse().expect(FAILED_TO_PARSE));
+                properties.checksum(parse(&key, &value)?);
             },
             "compression" => {
-                properties.compression(value.parse().expect(FAILED_TO_PARSE));
+                properties.compression(parse(&key, &value)?);
             },
             "compressratio" => {
-                properties
-                    .compression_ratio(parse_float(&mut value.clone()).expect(FAILED_TO_PARSE));
+                properties.compression_ratio(parse(&key, &value)?);
             },
             "copies" => {
-                properties.copies(value.parse().expect(FAILED_TO_PARSE));
+                properties.copies(parse(&key, &value)?);
             },
             "createtxg" => {
-                properties.create_txg(Some(value.parse().expect(FAILED_TO_PARSE)));
+                properties.create_txg(Some(parse_num(&key, &value, overflow)?));
             },
             "creation" => {
-                properties.creation(value.parse().expect(FAILED_TO_PARSE));
+                properties.creation(parse_num(&key, &value, overflow)?);
             },
             "dedup" => {
-                properties.dedup(value.parse().expect(FAILED_TO_PARSE));
+                properties.dedup(parse(&key, &value)?);
             },
             "guid" => {
-                properties.guid(Some(value.parse().expect(FAILED_TO_PARSE)));
+                properties.guid(Some(parse_num(&key, &value, overflow)?));
             },
             "logbias" => {
-                properties.log_bias(value.parse().expect(FAILED_TO_PARSE));
+                properties.log_bias(parse(&key, &value)?);
             },
             "logicalreferenced" => {
-                properties.logical_referenced(value.parse().expect(FAILED_TO_PARSE));
+                properties.logical_referenced(parse_num(&key, &value, overflow)?);
             },
             "logicalused" => {
-                properties.logical_used(value.parse().expect(FAILED_TO_PARSE));
+                properties.logical_used(parse_num(&key, &value, overflow)?);
             },
             "mlslabel" => {
                 properties.mls_label(parse_mls_label(value));
             },
             "primarycache" => {
-                properties.primary_cache(value.parse().expect(FAILED_TO_PARSE));
+                properties.primary_cache(parse(&key, &value)?);
             },
             "readonly" => {
                 properties.readonly(parse_bool(&value));
             },
             "redundant_metadata" => {
-                properties.redundant_metadata(value.parse().expect(FAILED_TO_PARSE));
+                properties.redundant_metadata(parse(&key, &value)?);
             },
             "refcompressratio" => {
-                properties
-                    .ref_compression_ratio(parse_float(&mut value.clone()).expect(FAILED_TO_PARSE));
+                properties.ref_compression_ratio(parse(&key, &value)?);
             },
             "referenced" => {
-                properties.referenced(value.parse().expect(FAILED_TO_PARSE));
+                properties.referenced(parse_num(&key, &value, overflow)?);
             },
             "refreservation" => {
-                properties.ref_reservation(value.parse().expect(FAILED_TO_PARSE));
+                properties.ref_reservation(parse_num(&key, &value, overflow)?);
             },
             "reservation" => {
-                properties.reservation(value.parse().expect(FAILED_TO_PARSE));
+                properties.reservation(parse_num(&key, &value, overflow)?);
             },
             "secondarycache" => {
-                properties.secondary_cache(value.parse().expect(FAILED_TO_PARSE));
+                properties.secondary_cache(parse(&key, &value)?);
             },
             "snapshot_count" => {
-                properties.snapshot_count(value.parse().expect(FAILED_TO_PARSE));
+                properties.snapshot_count(parse_num(&key, &value, overflow)?);
             },
             "snapshot_limit" => {
-                properties.snapshot_limit(value.parse().expect(FAILED_TO_PARSE));
+                properties.snapshot_limit(parse_num(&key, &value, overflow)?);
             },
             "sync" => {
-                properties.sync(value.parse().expect(FAILED_TO_PARSE));
+                properties.sync(parse(&key, &value)?);
             },
             "used" => {
-                properties.used(value.parse().expect(FAILED_TO_PARSE));
+                properties.used(parse_num(&key, &value, overflow)?);
             },
             "usedbychildren" => {
-                properties.used_by_children(value.parse().expect(FAILED_TO_PARSE));
+                properties.used_by_children(parse_num(&key, &value, overflow)?);
             },
             "usedbydataset" => {
-                properties.used_by_dataset(value.parse().expect(FAILED_TO_PARSE));
+                properties.used_by_dataset(parse_num(&key, &value, overflow)?);
             },
             "usedbyrefreservation" => {
-                properties.used_by_ref_reservation(value.parse().expect(FAILED_TO_PARSE));
+                properties.used_by_ref_reservation(parse_num(&key, &value, overflow)?);
             },
             "usedbysnapshots" => {
-                properties.used_by_snapshots(value.parse().expect(FAILED_TO_PARSE));
+                properties.used_by_snapshots(parse_num(&key, &value, overflow)?);
             },
             "volblocksize" => {
-                properties.volume_block_size(value.parse().expect(FAILED_TO_PARSE));
+                properties.volume_block_size(parse_num(&key, &value, overflow)?);
             },
             "volmode" => {
-                properties.volume_mode(Some(value.parse().expect(FAILED_TO_PARSE)));
+                properties.volume_mode(Some(parse(&key, &value)?));
             },
             "volsize" => {
-                properties.volume_size(value.parse().expect(FAILED_TO_PARSE));
+                properties.volume_size(parse_num(&key, &value, overflow)?);
             },
             "written" => {
-                properties.written(value.parse().expect(FAILED_TO_PARSE));
+                properties.written(parse_num(&key, &value, overflow)?);
             },
             "type" => { /* no-op */ },
 
             _ => properties.insert_unknown_property(key, value),
         };
     }
-    Properties::Volume(properties.build().expect("Failed to build properties"))
+    Ok(Properties::Volume(properties.build().expect("Failed to build properties")))
 }
 
-pub(crate) fn parse_bookmark_lines(lines: &mut Lines, name: PathBuf) -> Properties {
+pub(crate) fn parse_bookmark_lines(
+    lines: &mut Lines,
+    name: PathBuf,
+    overflow: Overflow,
+) -> Result<Properties> {
     let mut properties = BookmarkProperties::builder(name);
     for (key, value) in lines.map(parse_prop_line) {
         match key.as_ref() {
             "createtxg" => {
-                properties.create_txg(Some(value.parse().expect(FAILED_TO_PARSE)));
+                properties.create_txg(Some(parse_num(&key, &value, overflow)?));
             },
             "creation" => {
-                properties.creation(value.parse().expect(FAILED_TO_PARSE));
+                properties.creation(parse_num(&key, &value, overflow)?);
             },
             "guid" => {
-                properties.guid(Some(value.parse().expect(FAILED_TO_PARSE)));
+                properties.guid(Some(parse_num(&key, &value, overflow)?));
             },
             "type" => { /* no-op */ },
 
             _ => properties.insert_unknown_property(key, value),
         }
     }
-    Properties::Bookmark(properties.build().expect("Failed to build properties"))
+    Ok(Properties::Bookmark(properties.build().expect("Failed to build properties")))
 }
 
 fn parse_unknown_lines(lines: &mut Lines) -> Properties {
@@ -640,10 +803,12 @@ fn parse_mls_label(val: String) -> Option<String> {
 #[cfg(test)]
 mod test {
     use super::*;
-    use crate::zfs::{properties::{AclInheritMode, AclMode, BookmarkProperties, CaseSensitivity,
-                                  Dedup, DnodeSize, LogBias, Normalization, RedundantMetadata,
-                                  SnapshotProperties, SyncMode, VolumeMode},
-                     CacheMode, CanMount, Checksum, Compression, Copies, SnapDir, VolumeProperties};
+    use crate::{utils::Ratio,
+                zfs::{properties::{AclInheritMode, AclMode, BookmarkProperties, CaseSensitivity,
+                                   Dedup, DnodeSize, LogBias, Normalization, RedundantMetadata,
+                                   SnapshotProperties, SyncMode, VolumeMode},
+                      CacheMode, CanMount, Checksum, Compression, Copies, SnapDir,
+                      ErrorKind, VolumeProperties}};
     use std::collections::HashMap;
 
     #[test]
@@ -661,7 +826,8 @@ mod test {
         let stdout = include_str!("fixtures/filesystem_properties_freebsd.sorted");
 
         let name = PathBuf::from("z/usr/home");
-        let result = parse_filesystem_lines(&mut stdout.lines(), name.clone());
+        let result =
+            parse_filesystem_lines(&mut stdout.lines(), name.clone(), Overflow::Error).unwrap();
 
         // Goal to have zero unknown before 1.0
         let unknown = [("sharenfs", "off"), ("sharesmb", "off")]
@@ -678,7 +844,7 @@ mod test {
             .case_sensitivity(CaseSensitivity::Sensitive)
             .checksum(Checksum::On)
             .compression(Compression::LZ4)
-            .compression_ratio(1.25)
+            .compression_ratio(Ratio::new(1.25))
             .copies(Copies::One)
             .create_txg(Some(46918))
             .creation(1_493_670_099)
@@ -703,7 +869,7 @@ mod test {
             .readonly(false)
             .record_size(0x0002_0000)
             .redundant_metadata(RedundantMetadata::All)
-            .ref_compression_ratio(1.23)
+            .ref_compression_ratio(Ratio::new(1.23))
             .referenced(97_392_148_480)
             .ref_quota(0)
             .ref_reservation(0)
@@ -735,7 +901,8 @@ mod test {
     fn volume_properties_freebsd() {
         let stdout = include_str!("fixtures/volume_properties_freebsd.sorted");
         let name = PathBuf::from("z/iohyve/rancher/disk0");
-        let result = parse_volume_lines(&mut stdout.lines(), name.clone());
+        let result =
+            parse_volume_lines(&mut stdout.lines(), name.clone(), Overflow::Error).unwrap();
 
         // Goal to have zero unknown before 1.0
         let unknown = HashMap::new();
@@ -743,7 +910,7 @@ mod test {
             .available(175_800_672_256)
             .checksum(Checksum::On)
             .compression(Compression::LZ4)
-            .compression_ratio(1.30)
+            .compression_ratio(Ratio::new(1.30))
             .copies(Copies::One)
             .create_txg(Some(2_432_774))
             .creation(1_531_943_675)
@@ -756,7 +923,7 @@ mod test {
             .primary_cache(CacheMode::All)
             .readonly(false)
             .redundant_metadata(RedundantMetadata::All)
-            .ref_compression_ratio(1.30)
+            .ref_compression_ratio(Ratio::new(1.30))
             .referenced(2_781_577_216)
             .ref_reservation(70_871_154_688)
             .reservation(0)
@@ -784,7 +951,8 @@ mod test {
     fn snapshot_properties_freebsd() {
         let stdout = include_str!("fixtures/snapshot_properties_freebsd.sorted");
         let name = PathBuf::from("z/usr@backup-2019-11-24");
-        let result = parse_snapshot_lines(&mut stdout.lines(), name.clone());
+        let result =
+            parse_snapshot_lines(&mut stdout.lines(), name.clone(), Overflow::Error).unwrap();
 
         // Goal to have zero unknown before 1.0
         let unknown = HashMap::new();
@@ -792,7 +960,7 @@ mod test {
         let expected = SnapshotProperties::builder(name)
             .case_sensitivity(CaseSensitivity::Sensitive)
             .clones(None)
-            .compression_ratio(1.0)
+            .compression_ratio(Ratio::new(1.0))
             .create_txg(Some(3_034_392))
             .creation(1_574_590_597)
             .defer_destroy(false)
@@ -804,7 +972,7 @@ mod test {
             .nbmand(false)
             .normalization(Normalization::None)
             .primary_cache(CacheMode::All)
-            .ref_compression_ratio(1.0)
+            .ref_compression_ratio(Ratio::new(1.0))
             .referenced(90210)
             .secondary_cache(CacheMode::All)
             .setuid(true)
@@ -826,7 +994,8 @@ mod test {
     fn bookmark_properties_freebsd() {
         let stdout = include_str!("fixtures/bookmark_properties_freebsd.sorted");
         let name = PathBuf::from("z/var/tmp#backup-2019-08-08");
-        let result = parse_bookmark_lines(&mut stdout.lines(), name.clone());
+        let result =
+            parse_bookmark_lines(&mut stdout.lines(), name.clone(), Overflow::Error).unwrap();
 
         let expected = BookmarkProperties::builder(name)
             .create_txg(Some(2_967_653))
@@ -837,4 +1006,31 @@ mod test {
 
         assert_eq!(Properties::Bookmark(expected), result);
     }
+
+    #[test]
+    fn available_overflow() {
+        let stdout = include_str!("fixtures/filesystem_properties_freebsd.sorted")
+            .replace("\tavailable\t161379753984\t", "\tavailable\t99999999999999999999\t");
+        let name = PathBuf::from("z/usr/home");
+
+        let err = parse_filesystem_lines(&mut stdout.lines(), name.clone(), Overflow::Error)
+            .unwrap_err();
+        assert_eq!(ErrorKind::Overflow, err.kind());
+
+        match parse_filesystem_lines(&mut stdout.lines(), name, Overflow::Saturate).unwrap() {
+            Properties::Filesystem(props) => assert_eq!(i64::MAX, *props.available()),
+            other => panic!("unexpected properties: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn invalid_ratio() {
+        let stdout = include_str!("fixtures/filesystem_properties_freebsd.sorted")
+            .replace("\tcompressratio\t1.25x\t", "\tcompressratio\t-\t");
+        let name = PathBuf::from("z/usr/home");
+
+        let err = parse_filesystem_lines(&mut stdout.lines(), name, Overflow::Error).unwrap_err();
+        let expected = Error::ParseError { property: "compressratio".into(), value: "-".into() };
+        assert_eq!(expected.to_string(), err.to_string());
+    }
 }
@@ -1,6 +1,8 @@
 use std::{default::Default, path::PathBuf};
 use strum_macros::{AsRefStr, Display, EnumString};
 
+use crate::utils::Ratio;
+
 use std::collections::HashMap;
 
 macro_rules! impl_zfs_prop {
@@ -456,7 +458,7 @@ pub struct FilesystemProperties {
     compression:             Compression,
     /// Read-only property that identifies the compression ratio achieved for a dataset, expressed
     /// as a multiplier.
-    compression_ratio:       f64,
+    compression_ratio:       Ratio,
     /// Sets the number of copies of user data per file system. Available values are 1, 2, or 3.
     /// These copies are in addition to any pool-level redundancy. Disk space used by multiple
     /// copies of user data charged to the corresponding file and dataset, and counts against
@@ -518,7 +520,7 @@ pub struct FilesystemProperties {
     /// Controls what types of metadata are stored redundantly
     redundant_metadata:      RedundantMetadata,
     /// Compression ratio achieved for the referenced space of this snapshot.
-    ref_compression_ratio:   f64,
+    ref_compression_ratio:   Ratio,
     /// Read-only property that identifies the amount of data accessible by a dataset, which might
     /// or might not be shared with other datasets in the pool.
     referenced:              u64,
@@ -635,7 +637,7 @@ pub struct VolumeProperties {
     compression:             Compression,
     /// Read-only property that identifies the compression ratio achieved for a dataset, expressed
     /// as a multiplier.
-    compression_ratio:       f64,
+    compression_ratio:       Ratio,
     /// Sets the number of copies of user data per file system. Available values are 1, 2, or 3.
     /// These copies are in addition to any pool-level redundancy. Disk space used by multiple
     /// copies of user data charged to the corresponding file and dataset, and counts against
@@ -671,7 +673,7 @@ pub struct VolumeProperties {
     /// Controls what types of metadata are stored redundantly
     redundant_metadata:      RedundantMetadata,
     /// Compression ratio achieved for the referenced space of this snapshot.
-    ref_compression_ratio:   f64,
+    ref_compression_ratio:   Ratio,
     /// Read-only property that identifies the amount of data accessible by a dataset, which might
     /// or might not be shared with other datasets in the pool.
     referenced:              u64,
@@ -757,7 +759,7 @@ pub struct SnapshotProperties {
     referenced:            u64,
     /// Read-only property that identifies the compression ratio achieved for a dataset, expressed
     /// as a multiplier.
-    compression_ratio:     f64,
+    compression_ratio:     Ratio,
     /// Controls whether device files in a file system can be opened.
     devices:               bool,
     /// Controls whether programs in a file system allowed to be executed. Also, when set to
@@ -786,7 +788,7 @@ pub struct SnapshotProperties {
     /// Number of holds on this snapshot.
     user_refs:             u64,
     /// Compression ratio achieved for the referenced space of this snapshot.
-    ref_compression_ratio: f64,
+    ref_compression_ratio: Ratio,
     /// The amount of referenced space written to this dataset since the previous snapshot.
     written:               u64,
     /// List of datasets which are clones of this snapshot.
@@ -0,0 +1,370 @@
+//! Renames of mounted dataset trees.
+//!
+//! Renaming a dataset moves every descendant that inherits its `mountpoint`. `zfs rename` does
+//! unmount and remount on its own, but if something is already in the way of a new mountpoint, it
+//! fails halfway and leaves the tree partly unmounted. [`RenamePlan`](struct.RenamePlan.html)
+//! works out new mountpoints up front, so collisions are reported before anything is touched, and
+//! [`execute`](struct.RenamePlan.html#method.execute) unmounts deepest datasets first, renames and
+//! mounts parents first. If a step fails, the steps before it are undone.
+//!
+//! A mountpoint is considered inherited when it's the parent's mountpoint plus the name of the
+//! dataset, which is where ZFS puts it unless it was set explicitly. `legacy` and `none`
+//! mountpoints are left to the administrator.
+//!
+//! ### Usage
+//! ```rust,no_run
+//! use libzetta::zfs::{rename::RenamePlan, DelegatingZfsEngine, Error};
+//!
+//! let engine = DelegatingZfsEngine::new().unwrap();
+//! let plan = RenamePlan::new(&engine, "tank/home", "tank/users/home").unwrap();
+//! for mv in plan.moves() {
+//!     println!("{:?} -> {:?}", mv.mountpoint(), mv.new_mountpoint());
+//! }
+//! match plan.execute(&engine) {
+//!     Err(Error::MountpointCollision { mountpoint, .. }) => {
+//!         println!("{} is in the way", mountpoint.display())
+//!     },
+//!     other => other.unwrap(),
+//! }
+//! ```
+use std::{collections::HashMap,
+          fs,
+          path::{Path, PathBuf}};
+
+use crate::zfs::{Error, Properties, Result, ZfsEngine};
+
+/// Flags of a single [`ZfsEngine::rename`](../trait.ZfsEngine.html#method.rename).
+#[derive(Builder, Debug, Clone, Copy, Default, Getters, PartialEq, Eq)]
+#[builder(setter(into))]
+#[get = "pub"]
+pub struct RenameOptions {
+    /// Create missing parents of the new name (`-p`). Not valid for snapshots.
+    #[builder(default)]
+    create_parents: bool,
+    /// Leave filesystems mounted where they are instead of remounting them (`-u`).
+    #[builder(default)]
+    no_remount:     bool,
+}
+
+impl RenameOptions {
+    /// A preferred way to create this.
+    pub fn builder() -> RenameOptionsBuilder { RenameOptionsBuilder::default() }
+}
+
+/// What happens to one filesystem of the renamed tree.
+#[derive(Debug, Clone, PartialEq, Eq, Getters)]
+#[get = "pub"]
+pub struct MountMove {
+    /// Current name.
+    dataset:        PathBuf,
+    /// Name after the rename.
+    new_dataset:    PathBuf,
+    /// Current mountpoint. `None` for `legacy` and `none`.
+    mountpoint:     Option<PathBuf>,
+    /// Mountpoint after the rename.
+    new_mountpoint: Option<PathBuf>,
+    /// Whether it was mounted when the plan was made.
+    mounted:        bool,
+}
+
+impl MountMove {
+    /// Mountpoint changes with the rename.
+    pub fn moves(&self) -> bool { self.mountpoint != self.new_mountpoint }
+
+    fn managed_and_mounted(&self) -> bool { self.mounted && self.mountpoint.is_some() }
+}
+
+/// Rename of a dataset with everything under it.
+#[derive(Debug, Clone, PartialEq, Eq, Getters)]
+#[get = "pub"]
+pub struct RenamePlan {
+    /// Dataset to rename.
+    from:  PathBuf,
+    /// New name.
+    to:    PathBuf,
+    /// Filesystems of the tree, parents first.
+    moves: Vec<MountMove>,
+}
+
+/// Parent of a dataset, `None` for the root dataset of a pool.
+fn parent_of(dataset: &Path) -> Option<&Path> {
+    dataset.parent().filter(|parent| !parent.as_os_str().is_empty())
+}
+
+fn mount_state<E: ZfsEngine>(engine: &E, dataset: &Path) -> Result<(Option<PathBuf>, bool)> {
+    match engine.read_properties(dataset)? {
+        Properties::Filesystem(props) => {
+            let mountpoint = props.mount_point().clone().filter(|mp| mp.is_absolute());
+            Ok((mountpoint, *props.mounted()))
+        },
+        _ => Ok((None, false)),
+    }
+}
+
+fn parent_mountpoint<E: ZfsEngine>(engine: &E, dataset: &Path) -> Result<Option<PathBuf>> {
+    match parent_of(dataset) {
+        Some(parent) => Ok(mount_state(engine, parent)?.0),
+        None => Ok(None),
+    }
+}
+
+/// Whether something is already at `path`: a file or a directory that isn't empty.
+fn path_in_use(path: &Path) -> bool {
+    match fs::read_dir(path) {
+        Ok(mut entries) => entries.next().is_some(),
+        Err(_) => path.exists(),
+    }
+}
+
+/// Run `op` on every item, even if some fail. Returns the first error.
+fn each<'a, I, F>(items: I, mut op: F) -> Result<()>
+where
+    I: Iterator<Item = &'a MountMove>,
+    F: FnMut(&'a MountMove) -> Result<()>,
+{
+    let mut result = Ok(());
+    for item in items {
+        if let Err(e) = op(item) {
+            if result.is_ok() {
+                result = Err(e);
+            }
+        }
+    }
+    result
+}
+
+/// Original error if rollback went fine, `RollbackFailed` otherwise.
+fn undo(error: Error, rollback: Result<()>) -> Error {
+    match rollback {
+        Ok(()) => error,
+        Err(rollback) => {
+            Error::RollbackFailed { error: error.to_string(), rollback: rollback.to_string() }
+        },
+    }
+}
+
+impl RenamePlan {
+    /// Look at the tree under `from` and work out where everything ends up.
+    ///
+    /// * `engine` - Engine to query.
+    /// * `from` - Dataset to rename.
+    /// * `to` - New name. Parent must exist.
+    pub fn new<E, N, M>(engine: &E, from: N, to: M) -> Result<RenamePlan>
+    where
+        E: ZfsEngine,
+        N: Into<PathBuf>,
+        M: Into<PathBuf>,
+    {
+        let from = from.into();
+        let to = to.into();
+        let mut datasets = Vec::new();
+        for dataset in engine.list_filesystems(from.clone())? {
+            let (mountpoint, mounted) = mount_state(engine, &dataset)?;
+            datasets.push((dataset, mountpoint, mounted));
+        }
+        let parent = parent_mountpoint(engine, &from)?;
+        let new_parent = parent_mountpoint(engine, &to)?;
+        Ok(RenamePlan::from_datasets(from, to, datasets, parent, new_parent))
+    }
+
+    /// Build a plan out of `(dataset, mountpoint, mounted)` of every filesystem in the tree and
+    /// mountpoints of old and new parents.
+    pub(crate) fn from_datasets(
+        from: PathBuf,
+        to: PathBuf,
+        mut datasets: Vec<(PathBuf, Option<PathBuf>, bool)>,
+        parent: Option<PathBuf>,
+        new_parent: Option<PathBuf>,
+    ) -> RenamePlan {
+        datasets.sort_by_key(|(dataset, ..)| dataset.components().count());
+        // Old and new mountpoint of every dataset seen so far.
+        let mut seen: HashMap<PathBuf, (Option<PathBuf>, Option<PathBuf>)> = HashMap::new();
+        let mut moves = Vec::with_capacity(datasets.len());
+        for (dataset, mountpoint, mounted) in datasets {
+            let relative = match dataset.strip_prefix(&from) {
+                Ok(relative) => relative.to_path_buf(),
+                Err(_) => continue,
+            };
+            let new_dataset =
+                if relative.as_os_str().is_empty() { to.clone() } else { to.join(&relative) };
+            let (parent_mp, new_parent_mp) = if dataset == from {
+                (parent.clone(), new_parent.clone())
+            } else {
+                parent_of(&dataset).and_then(|p| seen.get(p)).cloned().unwrap_or((None, None))
+            };
+            let inherited = match (&mountpoint, &parent_mp, dataset.file_name()) {
+                (Some(mp), Some(parent_mp), Some(name)) => *mp == parent_mp.join(name),
+                _ => false,
+            };
+            let new_mountpoint = match (inherited, new_parent_mp, new_dataset.file_name()) {
+                (true, Some(new_parent_mp), Some(name)) => Some(new_parent_mp.join(name)),
+                _ => mountpoint.clone(),
+            };
+            seen.insert(dataset.clone(), (mountpoint.clone(), new_mountpoint.clone()));
+            moves.push(MountMove { dataset, new_dataset, mountpoint, new_mountpoint, mounted });
+        }
+        RenamePlan { from, to, moves }
+    }
+
+    /// Mounted filesystems whose new mountpoint is already in use. Paths that are mountpoints of
+    /// the tree itself are not counted, they're vacated before anything is mounted.
+    pub fn collisions(&self) -> Vec<&MountMove> { self.collisions_with(path_in_use) }
+
+    pub(crate) fn collisions_with<F: Fn(&Path) -> bool>(&self, in_use: F) -> Vec<&MountMove> {
+        let vacated: Vec<&PathBuf> = self
+            .moves
+            .iter()
+            .filter(|mv| mv.managed_and_mounted())
+            .filter_map(|mv| mv.mountpoint.as_ref())
+            .collect();
+        self.moves
+            .iter()
+            .filter(|mv| mv.managed_and_mounted() && mv.moves())
+            .filter(|mv| match &mv.new_mountpoint {
+                Some(new_mp) => !vacated.contains(&new_mp) && in_use(new_mp),
+                None => false,
+            })
+            .collect()
+    }
+
+    /// Unmount, rename and mount again. Fails without doing anything if `to` exists or a new
+    /// mountpoint is in use. If a step fails, what was done so far is undone and the error of the
+    /// step is returned, or [`Error::RollbackFailed`](../enum.Error.html) if undoing failed too.
+    pub fn execute<E: ZfsEngine>(&self, engine: &E) -> Result<()> {
+        if engine.exists(self.to.clone())? {
+            return Err(Error::DatasetExists(self.to.clone()));
+        }
+        if let Some(mv) = self.collisions().first() {
+            return Err(Error::MountpointCollision {
+                dataset:    mv.new_dataset.clone(),
+                mountpoint: mv.new_mountpoint.clone().unwrap_or_default(),
+            });
+        }
+        let mounted: Vec<&MountMove> =
+            self.moves.iter().filter(|mv| mv.managed_and_mounted()).collect();
+
+        let mut unmounted: Vec<&MountMove> = Vec::with_capacity(mounted.len());
+        for mv in mounted.iter().rev() {
+            if let Err(e) = engine.unmount(mv.dataset.clone()) {
+                let rollback = each(unmounted.iter().rev().copied(), |mv| {
+                    engine.mount(mv.dataset.clone())
+                });
+                return Err(undo(e, rollback));
+            }
+            unmounted.push(mv);
+        }
+
+        let options = RenameOptions::default();
+        if let Err(e) = engine.rename(self.from.clone(), self.to.clone(), options) {
+            let rollback = each(mounted.iter().copied(), |mv| engine.mount(mv.dataset.clone()));
+            return Err(undo(e, rollback));
+        }
+
+        let mut remounted: Vec<&MountMove> = Vec::with_capacity(mounted.len());
+        for mv in &mounted {
+            // Some versions of `zfs rename` remount on their own.
+            let result = match mount_state(engine, &mv.new_dataset) {
+                Ok((_, true)) => Ok(()),
+                Ok((_, false)) => engine.mount(mv.new_dataset.clone()),
+                Err(e) => Err(e),
+            };
+            if let Err(e) = result {
+                let rollback = each(remounted.iter().rev().copied(), |mv| {
+                    engine.unmount(mv.new_dataset.clone())
+                })
+                .and_then(|_| engine.rename(self.to.clone(), self.from.clone(), options))
+                .and_then(|_| each(mounted.iter().copied(), |mv| engine.mount(mv.dataset.clone())));
+                return Err(undo(e, rollback));
+            }
+            remounted.push(mv);
+        }
+        Ok(())
+    }
+}
+
+/// Plan and execute a rename of `from` to `to`. Returns the plan that was executed.
+pub fn rename_tree<E, N, M>(engine: &E, from: N, to: M) -> Result<RenamePlan>
+where
+    E: ZfsEngine,
+    N: Into<PathBuf>,
+    M: Into<PathBuf>,
+{
+    let plan = RenamePlan::new(engine, from, to)?;
+    plan.execute(engine)?;
+    Ok(plan)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::zfs::ErrorKind;
+
+    fn path(value: &str) -> PathBuf { PathBuf::from(value) }
+
+    fn plan() -> RenamePlan {
+        let datasets = vec![
+            (path("tank/a/b"), Some(path("/tank/a/b")), true),
+            (path("tank/a"), Some(path("/tank/a")), true),
+            (path("tank/a/b/c"), Some(path("/tank/a/b/c")), false),
+            (path("tank/a/local"), Some(path("/srv/local")), true),
+            (path("tank/a/legacy"), None, true),
+        ];
+        RenamePlan::from_datasets(
+            path("tank/a"),
+            path("tank/x/y"),
+            datasets,
+            Some(path("/tank")),
+            Some(path("/mnt/x")),
+        )
+    }
+
+    #[test]
+    fn new_mountpoints() {
+        let plan = plan();
+        let moves: Vec<(&Path, Option<&Path>)> = plan
+            .moves()
+            .iter()
+            .map(|mv| (mv.new_dataset().as_path(), mv.new_mountpoint().as_deref()))
+            .collect();
+        assert_eq!(
+            vec![
+                (Path::new("tank/x/y"), Some(Path::new("/mnt/x/y"))),
+                (Path::new("tank/x/y/b"), Some(Path::new("/mnt/x/y/b"))),
+                (Path::new("tank/x/y/local"), Some(Path::new("/srv/local"))),
+                (Path::new("tank/x/y/legacy"), None),
+                (Path::new("tank/x/y/b/c"), Some(Path::new("/mnt/x/y/b/c"))),
+            ],
+            moves
+        );
+        assert!(!plan.moves()[2].moves());
+    }
+
+    #[test]
+    fn collisions() {
+        let plan = plan();
+        assert!(plan.collisions_with(|_| false).is_empty());
+        // Unmounted `c` is not going to be mounted, so its path doesn't matter.
+        let collisions = plan.collisions_with(|p| p.starts_with("/mnt/x/y/b"));
+        assert_eq!(1, collisions.len());
+        assert_eq!(Path::new("tank/a/b"), collisions[0].dataset());
+
+        // Paths the tree occupies now are vacated before anything is mounted.
+        let datasets = vec![
+            (path("tank/a"), Some(path("/tank/a")), true),
+            (path("tank/a/b"), Some(path("/tank/c")), true),
+        ];
+        let plan = RenamePlan::from_datasets(
+            path("tank/a"),
+            path("tank/c"),
+            datasets,
+            Some(path("/tank")),
+            Some(path("/tank")),
+        );
+        assert_eq!(&Some(path("/tank/c")), plan.moves()[0].new_mountpoint());
+        assert!(plan.collisions_with(|_| true).is_empty());
+
+        let err = undo(Error::Unknown, Err(Error::DatasetNotFound(path("tank/a"))));
+        assert_eq!(ErrorKind::RollbackFailed, err.kind());
+        assert_eq!(ErrorKind::Unknown, undo(Error::Unknown, Ok(())).kind());
+    }
+}
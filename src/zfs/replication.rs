@@ -0,0 +1,190 @@
+//! Replication of snapshots from one engine into another.
+//!
+//! A receive that runs out of space dies in the middle of the stream and leaves partial state
+//! behind. [`replicate`](fn.replicate.html) estimates the stream with
+//! [`send_space`](../trait.ZfsEngine.html#method.send_space) first and compares it with what the
+//! destination can take: `available` of the destination, further limited by its `quota` and
+//! `refquota`. If it doesn't fit, nothing is sent and
+//! [`Error::InsufficientSpace`](../enum.Error.html) is returned.
+//!
+//! The estimate is the size of the stream, not the space it will take after receive. With
+//! compressed or raw streams both are close, with plain streams into a compressed dataset the check
+//! errs on the safe side.
+//!
+//! ### Usage
+//! ```rust,no_run
+//! use libzetta::zfs::{replication::replicate, DelegatingZfsEngine, Error, SendFlags};
+//! use std::path::PathBuf;
+//!
+//! let engine = DelegatingZfsEngine::new().unwrap();
+//! let result = replicate(
+//!     &engine,
+//!     PathBuf::from("tank/data@today"),
+//!     None,
+//!     SendFlags::empty(),
+//!     &engine,
+//!     PathBuf::from("backup/data@today"),
+//!     false,
+//! );
+//! match result {
+//!     Err(Error::InsufficientSpace { needed, available }) => {
+//!         println!("backup needs {} more bytes", needed - available)
+//!     },
+//!     other => println!("{:?}", other),
+//! }
+//! ```
+use std::{convert::TryFrom,
+          io,
+          path::{Path, PathBuf},
+          thread};
+
+use crate::zfs::{chunked::pipe, Error, FilesystemProperties, Properties, Result, SendFlags,
+                 ZfsEngine};
+
+/// Dataset part of a snapshot name: `backup/data` for `backup/data@today`.
+fn dataset_of(snapshot: &Path) -> PathBuf {
+    let name = snapshot.to_string_lossy();
+    PathBuf::from(name.split('@').next().unwrap_or(&name))
+}
+
+fn filesystem_available(props: &FilesystemProperties, own: bool) -> u64 {
+    let mut available = u64::try_from(*props.available()).unwrap_or(0);
+    if *props.quota() > 0 {
+        available = available.min(props.quota().saturating_sub(*props.used()));
+    }
+    // `refquota` only limits the dataset itself, not a child that is about to be created.
+    if own && *props.ref_quota() > 0 {
+        available = available.min(props.ref_quota().saturating_sub(*props.referenced()));
+    }
+    available
+}
+
+/// How many bytes can be received into `dataset`. If dataset doesn't exist yet, its parent is
+/// checked, because that's where receive is going to create it.
+///
+/// * `engine` - Engine of the receiving side.
+/// * `dataset` - Dataset that will receive the stream.
+pub fn available_for_receive<E: ZfsEngine>(engine: &E, dataset: &Path) -> Result<u64> {
+    let own = engine.exists(dataset)?;
+    let target = if own {
+        dataset
+    } else {
+        dataset
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+            .ok_or_else(|| Error::DatasetNotFound(dataset.to_path_buf()))?
+    };
+    match engine.read_properties(target)? {
+        Properties::Filesystem(props) => Ok(filesystem_available(&props, own)),
+        Properties::Volume(props) => Ok(u64::try_from(*props.available()).unwrap_or(0)),
+        _ => Err(Error::invalid_input()),
+    }
+}
+
+/// Estimate the stream and make sure destination has room for it. Returns estimated size of the
+/// stream.
+///
+/// * `source` - Engine of the sending side.
+/// * `snapshot` - Snapshot to send.
+/// * `from` - Snapshot or bookmark to send incremental stream from.
+/// * `flags` - Send flags.
+/// * `target` - Engine of the receiving side.
+/// * `destination` - Snapshot to receive into, e.g. `backup/data@today`.
+pub fn check_receive_space<S: ZfsEngine, D: ZfsEngine>(
+    source: &S,
+    snapshot: &Path,
+    from: Option<PathBuf>,
+    flags: SendFlags,
+    target: &D,
+    destination: &Path,
+) -> Result<u64> {
+    let needed = source.send_space(snapshot, from, flags)?;
+    let available = available_for_receive(target, &dataset_of(destination))?;
+    if needed > available {
+        return Err(Error::InsufficientSpace { needed, available });
+    }
+    Ok(needed)
+}
+
+/// Send a snapshot from `source` and receive it on `target`, failing early if it doesn't fit.
+/// Returns estimated size of the stream.
+///
+/// * `force` - Roll back destination to its most recent snapshot before receiving.
+///
+/// See [`check_receive_space`](fn.check_receive_space.html) for the rest of arguments.
+pub fn replicate<S, D>(
+    source: &S,
+    snapshot: PathBuf,
+    from: Option<PathBuf>,
+    flags: SendFlags,
+    target: &D,
+    destination: PathBuf,
+    force: bool,
+) -> Result<u64>
+where
+    S: ZfsEngine + Sync,
+    D: ZfsEngine,
+{
+    let needed = check_receive_space(source, &snapshot, from.clone(), flags, target, &destination)?;
+    let (reader, writer) = pipe()?;
+
+    let (sent, received) = thread::scope(|scope| {
+        let sender = scope.spawn(move || {
+            // `writer` is dropped when send is done, so the receiver sees end of the stream.
+            let sent = match from {
+                Some(from) => source.send_incremental(snapshot, from, writer, flags),
+                None => source.send_full(snapshot, writer, flags),
+            };
+            // `Error` can carry an `NvList`, which can't leave the thread.
+            sent.map_err(|err| match err {
+                Error::Io(err) => err,
+                other => io::Error::new(io::ErrorKind::Other, other.to_string()),
+            })
+        });
+        let received = target.receive(destination, reader, force);
+        (sender.join().expect("Sender thread panicked"), received)
+    });
+    // If receive failed, send usually fails too with a broken pipe, which is not interesting.
+    received?;
+    sent?;
+    Ok(needed)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{utils::Overflow,
+                zfs::{open3::parse_filesystem_lines, ErrorKind}};
+
+    static PROPS: &str = include_str!("fixtures/filesystem_properties_freebsd.sorted");
+
+    fn props(stdout: &str) -> FilesystemProperties {
+        let name = PathBuf::from("z/usr/home");
+        match parse_filesystem_lines(&mut stdout.lines(), name, Overflow::Error).unwrap() {
+            Properties::Filesystem(props) => props,
+            other => panic!("unexpected properties: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn available_space() {
+        assert_eq!(161_379_753_984, filesystem_available(&props(PROPS), true));
+
+        // used is 102563762176, referenced is 97392148480.
+        let stdout = PROPS
+            .replace("\tquota\t0\tdefault", "\tquota\t102563763176\tlocal")
+            .replace("\trefquota\t0\tdefault", "\trefquota\t97392148980\tlocal");
+        let props = props(&stdout);
+        assert_eq!(500, filesystem_available(&props, true));
+        assert_eq!(1000, filesystem_available(&props, false));
+
+        let err = Error::InsufficientSpace { needed: 1001, available: 1000 };
+        assert_eq!(ErrorKind::InsufficientSpace, err.kind());
+    }
+
+    #[test]
+    fn snapshot_dataset() {
+        assert_eq!(PathBuf::from("backup/data"), dataset_of(Path::new("backup/data@today")));
+        assert_eq!(PathBuf::from("backup/data"), dataset_of(Path::new("backup/data")));
+    }
+}
@@ -0,0 +1,324 @@
+//! Send stream headers and receive dry runs.
+//!
+//! Every send stream starts with a `BEGIN` record: which snapshot it carries, which snapshot it is
+//! incremental from and what the receiving side has to support to take it.
+//! [`StreamHeader`](struct.StreamHeader.html) reads that record without touching the rest of the
+//! stream, so archives can be checked without a pool to receive them into.
+//!
+//! [`receive_dry_run`](fn.receive_dry_run.html) goes one step further and compares the header with
+//! the destination: whether the dataset would be created, overwritten or updated, and on top of
+//! which snapshot. Nothing is changed, neither on the destination nor in the stream beyond its
+//! header. The data itself is not verified, only a real receive does that.
+//!
+//! ### Usage
+//! ```rust,no_run
+//! use libzetta::zfs::{stream::{receive_dry_run, ReceiveAction}, DelegatingZfsEngine};
+//! use std::fs::File;
+//!
+//! let engine = DelegatingZfsEngine::new().unwrap();
+//! let mut archive = File::open("/backup/data@today.zfs").unwrap();
+//! let dry_run = receive_dry_run(&engine, "backup/data@today", &mut archive, false).unwrap();
+//! match dry_run.action() {
+//!     ReceiveAction::Create => println!("would create {}", dry_run.target().display()),
+//!     action => println!("{:?} with {:?}", action, dry_run.header().features()),
+//! }
+//! ```
+use std::{convert::{TryFrom, TryInto},
+          io::{self, Read},
+          path::{Path, PathBuf}};
+
+use bitflags::bitflags;
+
+use crate::zfs::{DatasetKind, Error, PathExt, Properties, Result, ValidationError, ZfsEngine};
+
+/// Magic number of the `BEGIN` record.
+const BACKUP_MAGIC: u64 = 0x2_F5BA_CBAC;
+/// Size of a stream record.
+const RECORD_LENGTH: usize = 312;
+/// Longest name in the `BEGIN` record, terminating NUL included.
+const NAME_LENGTH: usize = 256;
+/// Header type of a replication (`-R`) stream.
+const COMPOUND_STREAM: u64 = 2;
+/// `drr_flags` bit of streams that were sent from a clone.
+const FLAG_CLONE: u32 = 1;
+
+bitflags! {
+    /// Features the receiving side must support to take the stream.
+    #[derive(Default)]
+    pub struct StreamFeatures: u32 {
+        const DEDUP = 1 << 0;
+        const DEDUP_PROPS = 1 << 1;
+        const SA_SPILL = 1 << 2;
+        const EMBED_DATA = 1 << 16;
+        const LZ4 = 1 << 17;
+        const LARGE_BLOCKS = 1 << 19;
+        const RESUMING = 1 << 20;
+        const REDACTED = 1 << 21;
+        const COMPRESSED = 1 << 22;
+        const LARGE_DNODE = 1 << 23;
+        const RAW = 1 << 24;
+        const ZSTD = 1 << 25;
+        const HOLDS = 1 << 26;
+        const SWITCH_TO_LARGE_BLOCKS = 1 << 27;
+    }
+}
+
+/// `BEGIN` record of a send stream.
+#[derive(Debug, Clone, PartialEq, Eq, Getters)]
+#[get = "pub"]
+pub struct StreamHeader {
+    /// Snapshot the stream carries, named as on the sending side.
+    to_name:    PathBuf,
+    /// GUID of that snapshot.
+    to_guid:    u64,
+    /// GUID of the snapshot the stream is incremental from. `None` for full streams.
+    from_guid:  Option<u64>,
+    /// What the snapshot is of. `None` for replication streams and unknown types.
+    kind:       Option<DatasetKind>,
+    /// When the snapshot was taken, seconds since epoch.
+    creation:   u64,
+    /// Features needed to receive the stream.
+    features:   StreamFeatures,
+    /// Whether this is a replication (`-R`) stream with several snapshots in it.
+    compound:   bool,
+    /// Whether the snapshot is of a clone.
+    from_clone: bool,
+}
+
+/// Cursor over the record that reads words in byte order of the sender.
+struct Record<'a> {
+    bytes:      &'a [u8],
+    big_endian: bool,
+}
+
+impl Record<'_> {
+    fn u32_at(&self, offset: usize) -> u32 {
+        let bytes = self.bytes.get(offset..offset + 4).and_then(|b| b.try_into().ok());
+        let bytes = bytes.unwrap_or_default();
+        if self.big_endian {
+            u32::from_be_bytes(bytes)
+        } else {
+            u32::from_le_bytes(bytes)
+        }
+    }
+
+    fn u64_at(&self, offset: usize) -> u64 {
+        let bytes = self.bytes.get(offset..offset + 8).and_then(|b| b.try_into().ok());
+        let bytes = bytes.unwrap_or_default();
+        if self.big_endian {
+            u64::from_be_bytes(bytes)
+        } else {
+            u64::from_le_bytes(bytes)
+        }
+    }
+}
+
+impl StreamHeader {
+    /// Read the header off the beginning of a stream. Only the first record is consumed.
+    ///
+    /// * `stream` - Stream positioned at its beginning.
+    pub fn read<R: Read>(stream: &mut R) -> Result<StreamHeader> {
+        let mut record = [0_u8; RECORD_LENGTH];
+        stream.read_exact(&mut record).map_err(|err| match err.kind() {
+            io::ErrorKind::UnexpectedEof => {
+                Error::InvalidStream(String::from("stream is too short"))
+            },
+            _ => Error::Io(err),
+        })?;
+        StreamHeader::from_bytes(&record)
+    }
+
+    /// Parse the header out of the first bytes of a stream.
+    ///
+    /// * `bytes` - At least the first 312 bytes of the stream.
+    pub fn from_bytes(bytes: &[u8]) -> Result<StreamHeader> {
+        if bytes.len() < RECORD_LENGTH {
+            return Err(Error::InvalidStream(String::from("stream is too short")));
+        }
+        let mut record = Record { bytes, big_endian: false };
+        if record.u64_at(8) != BACKUP_MAGIC {
+            record.big_endian = true;
+            if record.u64_at(8) != BACKUP_MAGIC {
+                return Err(Error::InvalidStream(String::from("bad magic number")));
+            }
+        }
+        if record.u32_at(0) != 0 {
+            return Err(Error::InvalidStream(String::from("first record is not BEGIN")));
+        }
+        let version = record.u64_at(16);
+        let features = u32::try_from((version >> 2) & 0x3FFF_FFFF).unwrap_or_default();
+        let kind = match record.u32_at(32) {
+            2 => Some(DatasetKind::Filesystem),
+            3 => Some(DatasetKind::Volume),
+            _ => None,
+        };
+        let name = bytes.get(56..56 + NAME_LENGTH).unwrap_or_default();
+        let name = name.split(|b| *b == 0).next().unwrap_or_default();
+        let from_guid = record.u64_at(48);
+        Ok(StreamHeader {
+            to_name:    PathBuf::from(String::from_utf8_lossy(name).into_owned()),
+            to_guid:    record.u64_at(40),
+            from_guid:  if from_guid == 0 { None } else { Some(from_guid) },
+            kind,
+            creation:   record.u64_at(24),
+            features:   StreamFeatures::from_bits_truncate(features),
+            compound:   version & 0b11 == COMPOUND_STREAM,
+            from_clone: record.u32_at(36) & FLAG_CLONE != 0,
+        })
+    }
+
+    /// Whether the stream is incremental.
+    pub fn is_incremental(&self) -> bool { self.from_guid.is_some() }
+}
+
+/// What receive would do with the destination.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReceiveAction {
+    /// Full stream, dataset would be created.
+    Create,
+    /// Full stream, existing dataset would be replaced. Needs `force`.
+    Overwrite,
+    /// Incremental stream would be applied on top of an existing snapshot.
+    Update {
+        /// Snapshot the stream is incremental from.
+        from:      PathBuf,
+        /// Snapshots taken after `from`. They would be destroyed, which needs `force`.
+        discarded: Vec<PathBuf>,
+    },
+}
+
+/// Outcome of [`receive_dry_run`](fn.receive_dry_run.html).
+#[derive(Debug, Clone, PartialEq, Eq, Getters)]
+#[get = "pub"]
+pub struct ReceiveDryRun {
+    /// Header of the stream.
+    header: StreamHeader,
+    /// Snapshot that would be created.
+    target: PathBuf,
+    /// What would happen to the dataset.
+    action: ReceiveAction,
+}
+
+/// Dataset part of a snapshot name.
+fn dataset_of(snapshot: &Path) -> PathBuf {
+    let name = snapshot.to_string_lossy();
+    PathBuf::from(name.split('@').next().unwrap_or(&name))
+}
+
+/// Snapshots of exactly this dataset, not of its children, oldest first.
+fn own_snapshots<E: ZfsEngine>(engine: &E, dataset: &Path) -> Result<Vec<PathBuf>> {
+    let snapshots = engine.list_snapshots(dataset)?;
+    Ok(snapshots.into_iter().filter(|snapshot| dataset_of(snapshot) == dataset).collect())
+}
+
+/// Check what receiving the stream into `snapshot` would do, without receiving it. Fails with the
+/// error the receive would most likely fail with: missing parent or incremental source, existing
+/// snapshot, or a dataset that can't be replaced without `force`.
+///
+/// Only the header is read off the stream.
+///
+/// * `engine` - Engine of the receiving side.
+/// * `snapshot` - Snapshot to create, e.g. `backup/data@today`.
+/// * `stream` - Stream positioned at its beginning.
+/// * `force` - Whether the receive would roll back or replace the destination.
+pub fn receive_dry_run<E: ZfsEngine, N: Into<PathBuf>, R: Read>(
+    engine: &E,
+    snapshot: N,
+    stream: &mut R,
+    force: bool,
+) -> Result<ReceiveDryRun> {
+    let target = snapshot.into();
+    target.validate()?;
+    if target.get_snapshot().is_none() {
+        return Err(ValidationError::MissingSnapshotName(target).into());
+    }
+    let header = StreamHeader::read(stream)?;
+    if engine.exists(&target)? {
+        return Err(Error::DatasetExists(target));
+    }
+    let dataset = dataset_of(&target);
+    let exists = engine.exists(&dataset)?;
+    let action = match header.from_guid {
+        None if !exists => {
+            let parent = dataset.parent().filter(|parent| !parent.as_os_str().is_empty());
+            match parent {
+                Some(parent) if !engine.exists(parent)? => {
+                    return Err(Error::DatasetNotFound(parent.to_path_buf()));
+                },
+                _ => ReceiveAction::Create,
+            }
+        },
+        None if force => ReceiveAction::Overwrite,
+        None => return Err(Error::DatasetExists(dataset)),
+        Some(_) if !exists => return Err(Error::DatasetNotFound(dataset)),
+        Some(guid) => {
+            let snapshots = own_snapshots(engine, &dataset)?;
+            let mut position = None;
+            for (idx, snapshot) in snapshots.iter().enumerate() {
+                if let Properties::Snapshot(props) = engine.read_properties(snapshot)? {
+                    if *props.guid() == Some(guid) {
+                        position = Some(idx);
+                    }
+                }
+            }
+            let idx = position.ok_or(Error::MissingIncrementalSource { dataset, guid })?;
+            let mut snapshots = snapshots.into_iter().skip(idx);
+            let from = snapshots.next().unwrap_or_default();
+            let discarded: Vec<PathBuf> = snapshots.collect();
+            if let Some(newer) = discarded.first().filter(|_| !force) {
+                return Err(Error::DatasetExists(newer.clone()));
+            }
+            ReceiveAction::Update { from, discarded }
+        },
+    };
+    Ok(ReceiveDryRun { header, target, action })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn record(big_endian: bool, version: u64, from_guid: u64, name: &str) -> Vec<u8> {
+        let u32_bytes = |v: u32| if big_endian { v.to_be_bytes() } else { v.to_le_bytes() };
+        let u64_bytes = |v: u64| if big_endian { v.to_be_bytes() } else { v.to_le_bytes() };
+        let mut bytes = Vec::with_capacity(RECORD_LENGTH);
+        bytes.extend_from_slice(&u32_bytes(0));
+        bytes.extend_from_slice(&u32_bytes(0));
+        bytes.extend_from_slice(&u64_bytes(BACKUP_MAGIC));
+        bytes.extend_from_slice(&u64_bytes(version));
+        bytes.extend_from_slice(&u64_bytes(1_600_000_000));
+        bytes.extend_from_slice(&u32_bytes(3));
+        bytes.extend_from_slice(&u32_bytes(FLAG_CLONE));
+        bytes.extend_from_slice(&u64_bytes(42));
+        bytes.extend_from_slice(&u64_bytes(from_guid));
+        bytes.extend_from_slice(name.as_bytes());
+        bytes.resize(RECORD_LENGTH, 0);
+        bytes
+    }
+
+    #[test]
+    fn headers() {
+        let version = u64::from((StreamFeatures::LZ4 | StreamFeatures::COMPRESSED).bits());
+        let bytes = record(false, (version << 2) | 1, 0, "tank/vm@golden");
+        let header = StreamHeader::read(&mut bytes.as_slice()).unwrap();
+        assert_eq!(&PathBuf::from("tank/vm@golden"), header.to_name());
+        assert_eq!(42, *header.to_guid());
+        assert!(!header.is_incremental());
+        assert_eq!(&Some(DatasetKind::Volume), header.kind());
+        assert_eq!(1_600_000_000, *header.creation());
+        assert_eq!(StreamFeatures::LZ4 | StreamFeatures::COMPRESSED, *header.features());
+        assert!(!*header.compound());
+        assert!(*header.from_clone());
+
+        let bytes = record(true, COMPOUND_STREAM, 7, "tank/vm@today");
+        let header = StreamHeader::from_bytes(&bytes).unwrap();
+        assert_eq!(Some(7), *header.from_guid());
+        assert!(*header.compound());
+        assert_eq!(StreamFeatures::empty(), *header.features());
+
+        let err = StreamHeader::from_bytes(&bytes[..100]).unwrap_err();
+        assert_eq!("invalid send stream: stream is too short", err.to_string());
+        let err = StreamHeader::from_bytes(&[0; RECORD_LENGTH]).unwrap_err();
+        assert_eq!("invalid send stream: bad magic number", err.to_string());
+    }
+}
@@ -0,0 +1,172 @@
+//! Snapshots, clones and resizing of volumes.
+//!
+//! Block device consumers, such as VM managers, keep repeating the same sequence: snapshot a
+//! volume, clone it for a new guest, wait until the clone shows up as a device and grow it. Every
+//! step has an edge that is easy to miss:
+//!
+//! - Snapshots and clones of filesystems succeed too, so the volume is checked first.
+//! - Device node of a clone is created asynchronously (by udev on Linux, devfs on FreeBSD) and is
+//!   not there yet when `zfs clone` returns.
+//! - `volsize` accepts smaller values, and shrinking a volume cuts off whatever the guest
+//!   stored at its end. [`resize_zvol`](fn.resize_zvol.html) refuses to do it unless asked to.
+//!
+//! ### Usage
+//! ```rust,no_run
+//! use libzetta::zfs::{zvol::{clone_zvol, resize_zvol, snapshot_zvol, ResizeMode},
+//!                     DelegatingZfsEngine};
+//! use std::time::Duration;
+//!
+//! let engine = DelegatingZfsEngine::new().unwrap();
+//! let snapshot = snapshot_zvol(&engine, "tank/vm/base", "golden").unwrap();
+//! let device =
+//!     clone_zvol(&engine, &snapshot, "tank/vm/guest-1", None, Duration::from_secs(10)).unwrap();
+//! resize_zvol(&engine, "tank/vm/guest-1", 20 * 1024 * 1024 * 1024, ResizeMode::GrowOnly).unwrap();
+//! println!("guest-1 is at {}", device.display());
+//! ```
+use std::{collections::HashMap,
+          path::{Path, PathBuf},
+          thread,
+          time::{Duration, Instant}};
+
+use crate::zfs::{Error, Properties, Result, VolumeProperties, ZfsEngine};
+
+/// Directory with device nodes of volumes.
+pub static ZVOL_DEVICE_DIR: &str = "/dev/zvol";
+
+/// How often [`wait_for_device`](fn.wait_for_device.html) looks for the device node.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Whether [`resize_zvol`](fn.resize_zvol.html) may make a volume smaller.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResizeMode {
+    /// Fail with [`VolumeShrink`](../enum.Error.html#variant.VolumeShrink) if the new size is
+    /// smaller than the current one.
+    GrowOnly,
+    /// Shrink if asked to. Data past the new size is lost.
+    AllowShrink,
+}
+
+impl Default for ResizeMode {
+    fn default() -> ResizeMode { ResizeMode::GrowOnly }
+}
+
+/// Path of the device node of a volume, e.g. `/dev/zvol/tank/vm/disk0`.
+///
+/// * `zvol` - Name of the volume.
+pub fn device_path<N: AsRef<Path>>(zvol: N) -> PathBuf { Path::new(ZVOL_DEVICE_DIR).join(zvol) }
+
+/// Properties of a dataset that must be a volume.
+fn volume_properties<E: ZfsEngine>(engine: &E, name: &Path) -> Result<VolumeProperties> {
+    match engine.read_properties(name)? {
+        Properties::Volume(properties) => Ok(properties),
+        _ => Err(Error::NotAVolume(name.to_path_buf())),
+    }
+}
+
+/// Snapshot a volume. Returns full name of the snapshot.
+///
+/// * `engine` - Engine to run operations with.
+/// * `zvol` - Volume to snapshot.
+/// * `snapshot_name` - Name of the snapshot, without the volume part.
+pub fn snapshot_zvol<E: ZfsEngine, N: AsRef<Path>>(
+    engine: &E,
+    zvol: N,
+    snapshot_name: &str,
+) -> Result<PathBuf> {
+    let zvol = zvol.as_ref();
+    volume_properties(engine, zvol)?;
+    let snapshot = PathBuf::from(format!("{}@{}", zvol.display(), snapshot_name));
+    engine.snapshot(&[snapshot.clone()], None)?;
+    Ok(snapshot)
+}
+
+/// Clone a snapshot of a volume and wait for the device node of the clone. Returns path of the
+/// device node.
+///
+/// If the device doesn't appear in time, the clone is kept and
+/// [`DeviceNotReady`](../enum.Error.html#variant.DeviceNotReady) is returned. Clones with
+/// `volmode=none` never get a device node.
+///
+/// * `engine` - Engine to run operations with.
+/// * `snapshot` - Snapshot of a volume, e.g. `tank/vm/base@golden`.
+/// * `target` - Name of the clone.
+/// * `properties` - Properties to set on the clone.
+/// * `timeout` - How long to wait for the device node.
+pub fn clone_zvol<E: ZfsEngine, N: AsRef<Path>, M: AsRef<Path>>(
+    engine: &E,
+    snapshot: N,
+    target: M,
+    properties: Option<HashMap<String, String>>,
+    timeout: Duration,
+) -> Result<PathBuf> {
+    let snapshot = snapshot.as_ref();
+    let target = target.as_ref();
+    let name = snapshot.to_string_lossy();
+    let mut parts = name.splitn(2, '@');
+    match (parts.next(), parts.next()) {
+        (Some(origin), Some(_)) => {
+            volume_properties(engine, Path::new(origin))?;
+        },
+        _ => return Err(Error::NotAVolume(snapshot.to_path_buf())),
+    }
+    engine.clone_snapshot(snapshot, target, properties)?;
+    wait_for_device(target, timeout)
+}
+
+/// Wait until the device node of a volume exists. Returns path of the device node.
+///
+/// * `zvol` - Name of the volume.
+/// * `timeout` - How long to wait.
+pub fn wait_for_device<N: AsRef<Path>>(zvol: N, timeout: Duration) -> Result<PathBuf> {
+    let device = device_path(zvol);
+    let started = Instant::now();
+    loop {
+        if device.exists() {
+            return Ok(device);
+        }
+        if started.elapsed() >= timeout {
+            return Err(Error::DeviceNotReady(device));
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+}
+
+/// Change `volsize` of a volume. Does nothing if the volume already has this size.
+///
+/// * `engine` - Engine to run operations with.
+/// * `zvol` - Volume to resize.
+/// * `size` - New size in bytes. Must be a multiple of `volblocksize`.
+/// * `mode` - Whether the volume may get smaller.
+pub fn resize_zvol<E: ZfsEngine, N: AsRef<Path>>(
+    engine: &E,
+    zvol: N,
+    size: u64,
+    mode: ResizeMode,
+) -> Result<()> {
+    let zvol = zvol.as_ref();
+    let properties = volume_properties(engine, zvol)?;
+    let current = *properties.volume_size();
+    let block_size = *properties.volume_block_size();
+    if block_size != 0 && size % block_size != 0 {
+        return Err(Error::InvalidVolumeSize { size, block_size });
+    }
+    if size == current {
+        return Ok(());
+    }
+    if size < current && mode == ResizeMode::GrowOnly {
+        return Err(Error::VolumeShrink { current, requested: size });
+    }
+    engine.set_property(zvol, "volsize", &size.to_string())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn device_paths() {
+        assert_eq!(PathBuf::from("/dev/zvol/tank/vm/disk0"), device_path("tank/vm/disk0"));
+        let err = wait_for_device("tank/libzetta-missing", Duration::from_millis(0)).unwrap_err();
+        assert_eq!("device /dev/zvol/tank/libzetta-missing did not appear", err.to_string());
+    }
+}
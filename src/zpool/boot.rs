@@ -0,0 +1,216 @@
+//! Guided creation of mirrored boot pools.
+//!
+//! Installers need more than a plain `zpool create` to get a bootable system: the pool has to stay
+//! readable by the boot loader (`compatibility=grub2` or similar), `bootfs` has to point at the
+//! root dataset and every member disk usually carries its own EFI system partition (ESP) that has
+//! to be kept in sync with the one that is actually mounted.
+//!
+//! ### Usage
+//! ```rust,no_run
+//! use libzetta::zpool::{boot::{create_boot_pool, BootPoolRequest},
+//!                       ZpoolOpen3};
+//! use std::path::PathBuf;
+//!
+//! let engine = ZpoolOpen3::default();
+//! let request = BootPoolRequest::builder()
+//!     .name("bpool")
+//!     .disks(vec![PathBuf::from("/dev/sda3"), PathBuf::from("/dev/sdb3")])
+//!     .esps(vec![PathBuf::from("/dev/sda1"), PathBuf::from("/dev/sdb1")])
+//!     .build()
+//!     .unwrap();
+//! let report = create_boot_pool(&engine, request).unwrap();
+//! for esp in report.esps_to_sync() {
+//!     println!("copy {:?} to {:?}", report.primary_esp(), esp);
+//! }
+//! ```
+use std::path::PathBuf;
+
+use crate::zpool::{properties::check_bootfs, CreateMode, CreateVdevRequest, CreateZpoolRequest,
+                   DestroyMode, ZpoolEngine, ZpoolError, ZpoolPropertiesWrite, ZpoolResult};
+
+/// Compatibility feature set used by default. Keeps the pool readable by GRUB.
+pub static DEFAULT_BOOT_COMPATIBILITY: &str = "grub2";
+
+/// Request to create a mirrored boot pool.
+#[derive(Builder, Debug, Clone, Getters, PartialEq, Eq)]
+#[builder(setter(into))]
+#[get = "pub"]
+pub struct BootPoolRequest {
+    /// Name of the boot pool. Usually `bpool` or `bootpool`.
+    name:          String,
+    /// Partitions that will form the mirror. Single partition is allowed, but then there is
+    /// nothing to mirror.
+    disks:         Vec<PathBuf>,
+    /// EFI system partitions, one per disk. First one is treated as primary.
+    #[builder(default)]
+    esps:          Vec<PathBuf>,
+    /// Value for `compatibility` property.
+    #[builder(default = "String::from(DEFAULT_BOOT_COMPATIBILITY)")]
+    compatibility: String,
+    /// Dataset to set as `bootfs` once pool is created. It must exist at that point and `zpool
+    /// create` only makes the root dataset (`-O` just sets its properties), so only the root
+    /// dataset is accepted here. Anything deeper has to be set with `set_bootfs` after creating
+    /// it.
+    #[builder(default)]
+    bootfs:        Option<String>,
+    /// Altroot for the pool. Installers usually want `/mnt`.
+    #[builder(default)]
+    altroot:       Option<PathBuf>,
+    /// Mount point for the pool's root dataset.
+    #[builder(default)]
+    mount:         Option<PathBuf>,
+    /// Use `-f` or not.
+    #[builder(default)]
+    create_mode:   CreateMode,
+}
+
+impl BootPoolRequest {
+    /// A preferred way to create this.
+    pub fn builder() -> BootPoolRequestBuilder { BootPoolRequestBuilder::default() }
+
+    /// Verify that request makes sense: at least one disk, either no ESPs or one ESP per disk and
+    /// `bootfs` pointing at the root dataset of the pool.
+    pub fn is_valid(&self) -> bool {
+        if self.disks.is_empty() {
+            return false;
+        }
+        if !self.esps.is_empty() && self.esps.len() != self.disks.len() {
+            return false;
+        }
+        match self.bootfs {
+            Some(ref bootfs) => bootfs == &self.name && check_bootfs(&self.name, bootfs).is_ok(),
+            None => true,
+        }
+    }
+
+    /// Turn this into [`CreateZpoolRequest`](../topology/struct.CreateZpoolRequest.html) that will
+    /// be passed to the engine.
+    pub fn to_create_request(&self) -> CreateZpoolRequest {
+        let vdev = if self.disks.len() == 1 {
+            CreateVdevRequest::SingleDisk(self.disks[0].clone())
+        } else {
+            CreateVdevRequest::Mirror(self.disks.clone())
+        };
+        let props = ZpoolPropertiesWrite::builder()
+            .compatibility(Some(self.compatibility.clone()))
+            .build()
+            .expect("Failed to build boot pool properties");
+
+        CreateZpoolRequest::builder()
+            .name(self.name.clone())
+            .vdev(vdev)
+            .props(props)
+            .altroot(self.altroot.clone())
+            .mount(self.mount.clone())
+            .create_mode(self.create_mode.clone())
+            .build()
+            .expect("Failed to build boot pool request")
+    }
+}
+
+/// What was done and what is left for the installer.
+#[derive(Debug, Clone, Getters, PartialEq, Eq)]
+#[get = "pub"]
+pub struct BootPoolReport {
+    /// Name of the created pool.
+    name:         String,
+    /// `bootfs` that was set, if any.
+    bootfs:       Option<String>,
+    /// ESP that is supposed to be mounted and updated by the boot loader installer.
+    primary_esp:  Option<PathBuf>,
+    /// ESPs that need to be kept in sync with primary one after every boot loader update.
+    esps_to_sync: Vec<PathBuf>,
+}
+
+/// Create a mirrored boot pool and set `bootfs`.
+///
+/// * `engine` - Engine to use.
+/// * `request` - A request to create boot pool.
+pub fn create_boot_pool<E: ZpoolEngine>(
+    engine: &E,
+    request: BootPoolRequest,
+) -> ZpoolResult<BootPoolReport> {
+    if !request.is_valid() {
+        let reason = "boot pool needs disks, one ESP per disk if any and bootfs at the pool root";
+        return Err(ZpoolError::InvalidTopology(reason.into()));
+    }
+    engine.create(request.to_create_request())?;
+
+    if let Some(ref bootfs) = request.bootfs {
+        if let Err(e) = engine.set_property(&request.name, "bootfs", bootfs) {
+            // Pool that doesn't boot isn't what was asked for. Report why bootfs failed, not
+            // how cleanup went.
+            engine.destroy(&request.name, DestroyMode::Force).ok();
+            return Err(e);
+        }
+    }
+
+    let mut esps = request.esps.into_iter();
+    let primary_esp = esps.next();
+    Ok(BootPoolReport {
+        name:         request.name,
+        bootfs:       request.bootfs,
+        primary_esp,
+        esps_to_sync: esps.collect(),
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use std::ffi::OsString;
+
+    fn disks() -> Vec<PathBuf> { vec![PathBuf::from("sda3"), PathBuf::from("sdb3")] }
+
+    #[test]
+    fn validation() {
+        let request = BootPoolRequest::builder().name("bpool").disks(disks()).build().unwrap();
+        assert!(request.is_valid());
+        assert_eq!("grub2", request.compatibility());
+
+        let request = BootPoolRequest::builder().name("bpool").disks(Vec::new()).build().unwrap();
+        assert!(!request.is_valid());
+
+        let request = BootPoolRequest::builder()
+            .name("bpool")
+            .disks(disks())
+            .esps(vec![PathBuf::from("sda1")])
+            .build()
+            .unwrap();
+        assert!(!request.is_valid());
+
+        let request = BootPoolRequest::builder()
+            .name("bpool")
+            .disks(disks())
+            .bootfs(Some(String::from("rpool/ROOT")))
+            .build()
+            .unwrap();
+        assert!(!request.is_valid());
+
+        let request = BootPoolRequest::builder()
+            .name("bpool")
+            .disks(disks())
+            .bootfs(Some(String::from("bpool")))
+            .build()
+            .unwrap();
+        assert!(request.is_valid());
+
+        // Doesn't exist right after `zpool create`.
+        let request = BootPoolRequest::builder()
+            .name("bpool")
+            .disks(disks())
+            .bootfs(Some(String::from("bpool/BOOT/default")))
+            .build()
+            .unwrap();
+        assert!(!request.is_valid());
+    }
+
+    #[test]
+    fn create_request() {
+        let request = BootPoolRequest::builder().name("bpool").disks(disks()).build().unwrap();
+        let create = request.to_create_request();
+        assert_eq!(&vec![CreateVdevRequest::Mirror(disks())], create.vdevs());
+        let args = create.props().clone().unwrap().into_args();
+        assert!(args.contains(&OsString::from("compatibility=grub2")));
+    }
+}
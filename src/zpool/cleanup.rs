@@ -0,0 +1,120 @@
+//! Cleanup after a failed `zpool create`.
+//!
+//! `zpool create` can fail after it already wrote labels to some of the devices. Those devices
+//! then look like members of a pool that doesn't exist, and the next attempt to create the pool
+//! fails with [`VdevReuse`](../enum.ZpoolError.html#variant.VdevReuse) unless forced.
+//! [`cleanup_failed_create`](fn.cleanup_failed_create.html) clears labels of devices of the
+//! request, but only those that are free or belong to the pool that failed to be created. Name
+//! alone doesn't tell the latter apart from an exported pool with the same name, so such labels are
+//! only cleared if their pool GUID isn't one of the pools that `zpool import` finds. Devices of
+//! other pools and devices of imported pools are never touched.
+//!
+//! ### Usage
+//! ```rust,no_run
+//! use libzetta::zpool::{cleanup::cleanup_failed_create, CreateVdevRequest, CreateZpoolRequest,
+//!                       ZpoolEngine, ZpoolOpen3};
+//!
+//! let engine = ZpoolOpen3::default();
+//! let request = CreateZpoolRequest::builder()
+//!     .name("tank")
+//!     .vdev(CreateVdevRequest::Mirror(vec!["/dev/sda".into(), "/dev/sdb".into()]))
+//!     .build()
+//!     .unwrap();
+//! if let Err(e) = engine.create(request.clone()) {
+//!     let report = cleanup_failed_create(&engine, &request, &e).unwrap();
+//!     println!("cleared {:?}, left alone {:?}", report.cleared(), report.skipped());
+//! }
+//! ```
+use std::path::PathBuf;
+
+use crate::zpool::{label::read_label, CreateZpoolRequest, LabelClearMode, ZpoolEngine, ZpoolError,
+                   ZpoolResult};
+
+/// What happened to devices of a failed request.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Getters)]
+#[get = "pub"]
+pub struct CleanupReport {
+    /// Devices whose labels were cleared.
+    cleared: Vec<PathBuf>,
+    /// Devices that belong to other pools, with the pool they belong to.
+    skipped: Vec<(PathBuf, String)>,
+    /// Devices that failed to be cleared, with the error.
+    failed:  Vec<(PathBuf, String)>,
+}
+
+impl CleanupReport {
+    /// Every device is either cleared or belongs to someone else.
+    pub fn is_clean(&self) -> bool { self.failed.is_empty() }
+}
+
+/// Errors that `create` returns before it writes anything to devices.
+fn nothing_written(error: &ZpoolError) -> bool {
+    match error {
+        ZpoolError::CmdNotFound
+        | ZpoolError::InvalidTopology(_)
+        | ZpoolError::InvalidProperty(_)
+        | ZpoolError::PermissionDenied
+        | ZpoolError::Denied(_)
+        | ZpoolError::VdevReuse(..)
+        | ZpoolError::ActiveVdev(..) => true,
+        _ => false,
+    }
+}
+
+/// Clear labels that a failed `create` left on devices of the request. Nothing is done if the pool
+/// exists after all or if the error is one that happens before devices are touched.
+///
+/// * `engine` - Engine to use.
+/// * `request` - Request that failed.
+/// * `error` - Error that `create` returned.
+pub fn cleanup_failed_create<E: ZpoolEngine>(
+    engine: &E,
+    request: &CreateZpoolRequest,
+    error: &ZpoolError,
+) -> ZpoolResult<CleanupReport> {
+    let mut report = CleanupReport::default();
+    if nothing_written(error) || engine.exists(request.name())? {
+        return Ok(report);
+    }
+    // GUIDs of pools that `zpool import` finds, looked up on the first label with our name.
+    let mut importable: Option<Vec<u64>> = None;
+    for device in request.devices() {
+        let result = match engine.labelclear(device, LabelClearMode::Gentle) {
+            Err(ZpoolError::VdevReuse(vdev, owner)) if &owner == request.name() => {
+                if importable.is_none() {
+                    let pools = engine.available()?;
+                    importable = Some(pools.iter().filter_map(|pool| *pool.id()).collect());
+                }
+                let guids = importable.as_deref().unwrap_or_default();
+                match read_label(device).ok().and_then(|label| *label.pool_guid()) {
+                    Some(guid) if !guids.contains(&guid) => {
+                        engine.labelclear(device, LabelClearMode::Force)
+                    },
+                    _ => Err(ZpoolError::VdevReuse(vdev, owner)),
+                }
+            },
+            other => other,
+        };
+        match result {
+            Ok(()) => report.cleared.push(device.clone()),
+            Err(ZpoolError::VdevReuse(_, owner)) | Err(ZpoolError::ActiveVdev(_, owner)) => {
+                report.skipped.push((device.clone(), owner))
+            },
+            Err(e) => report.failed.push((device.clone(), e.to_string())),
+        }
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn error_context() {
+        assert!(nothing_written(&ZpoolError::InvalidTopology(String::new())));
+        assert!(nothing_written(&ZpoolError::VdevReuse("/dev/sda".into(), "backup".into())));
+        assert!(nothing_written(&ZpoolError::ActiveVdev("/dev/sda".into(), "tank".into())));
+        assert!(!nothing_written(&ZpoolError::DeviceTooSmall));
+    }
+}
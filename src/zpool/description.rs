@@ -1,21 +1,343 @@
 //! If anyone has a better name for this module - hit me up. This module is where consumer friendly
-//! representation of Zpool is defined. This is where pest's
-//! [Pairs](../../../pest/iterators/struct.Pair.html) turned into [Zpool](struct.Zpool.html).
-use std::{path::PathBuf, str::FromStr};
+//! representation of Zpool is defined. Output of `zpool status` is turned into
+//! [Zpool](struct.Zpool.html) by [`parse_zpools`](../../parsers/fn.parse_zpools.html).
+use std::path::PathBuf;
 
-use pest::iterators::{Pair, Pairs};
+use chrono::NaiveDateTime;
 
-use crate::{parsers::Rule,
-            zpool::{vdev::{ErrorStatistics, Vdev, VdevType},
-                    CreateZpoolRequest, Disk, Health}};
+use crate::zpool::{scan::{ScanKind, ScanStatus},
+                   vdev::{ErrorStatistics, Vdev},
+                   vdevprops::VdevProperties,
+                   CreateZpoolRequest, Disk, Health};
 
-/// The reason why zpool is in this state. Right now it's just a wrapper around `String`, but in the
-/// future there _might_ be a more machine friendly format.
+/// The reason why zpool or device is in this state: the note `zpool status` prints after error
+/// counters.
 #[derive(Clone, Debug, Eq, PartialEq)]
 pub enum Reason {
-    /// Not yet classified reason.
+    /// Device can't be opened (`cannot open`).
+    CannotOpen,
+    /// Vdev configuration refers to a device that isn't there (`missing device`).
+    MissingDevice,
+    /// Not enough healthy children to work (`insufficient replicas`).
+    InsufficientReplicas,
+    /// Label was written by newer version of ZFS (`newer version`).
+    NewerVersion,
+    /// Pool uses features running ZFS doesn't support (`unsupported feature(s)`).
+    UnsupportedFeatures,
+    /// Device needs bigger ashift than vdev has (`unsupported minimum blocksize`).
+    UnsupportedBlockSize,
+    /// Device was faulted by ZFS after too many I/O or checksum errors (`too many errors`).
+    TooManyErrors,
+    /// Pool is suspended after I/O failures (`experienced I/O failures`).
+    IoFailures,
+    /// Intent log can't be replayed (`bad intent log`).
+    BadIntentLog,
+    /// Device was faulted from outside, e.g. by `zpool offline -f` (`external device fault`).
+    ExternalFault,
+    /// Device went into another pool with `zpool split` (`split into new pool`).
+    SplitIntoNewPool,
+    /// Every child of the vdev is offline (`all children offline`).
+    AllChildrenOffline,
+    /// Label of the device is invalid (`invalid label`).
+    InvalidLabel,
+    /// Spare is used by another pool (`currently in use`).
+    CurrentlyInUse,
+    /// Label is there, but can't be trusted (`corrupted data`).
+    CorruptedData,
+    /// Any other note, e.g. `was /dev/sdb` for a device that is gone.
     Other(String),
 }
+
+impl Reason {
+    /// Parse a note `zpool status` prints after error counters. Unknown notes are `Other`.
+    pub fn from_note(note: &str) -> Reason {
+        match note {
+            "cannot open" => Reason::CannotOpen,
+            "missing device" => Reason::MissingDevice,
+            "insufficient replicas" => Reason::InsufficientReplicas,
+            "newer version" => Reason::NewerVersion,
+            "unsupported feature(s)" => Reason::UnsupportedFeatures,
+            "unsupported minimum blocksize" => Reason::UnsupportedBlockSize,
+            "too many errors" => Reason::TooManyErrors,
+            "experienced I/O failures" => Reason::IoFailures,
+            "bad intent log" => Reason::BadIntentLog,
+            "external device fault" => Reason::ExternalFault,
+            "split into new pool" => Reason::SplitIntoNewPool,
+            "all children offline" => Reason::AllChildrenOffline,
+            "invalid label" => Reason::InvalidLabel,
+            "currently in use" => Reason::CurrentlyInUse,
+            "corrupted data" => Reason::CorruptedData,
+            other => Reason::Other(other.to_string()),
+        }
+    }
+
+    /// Note as `zpool status` prints it.
+    pub fn as_str(&self) -> &str {
+        match *self {
+            Reason::CannotOpen => "cannot open",
+            Reason::MissingDevice => "missing device",
+            Reason::InsufficientReplicas => "insufficient replicas",
+            Reason::NewerVersion => "newer version",
+            Reason::UnsupportedFeatures => "unsupported feature(s)",
+            Reason::UnsupportedBlockSize => "unsupported minimum blocksize",
+            Reason::TooManyErrors => "too many errors",
+            Reason::IoFailures => "experienced I/O failures",
+            Reason::BadIntentLog => "bad intent log",
+            Reason::ExternalFault => "external device fault",
+            Reason::SplitIntoNewPool => "split into new pool",
+            Reason::AllChildrenOffline => "all children offline",
+            Reason::InvalidLabel => "invalid label",
+            Reason::CurrentlyInUse => "currently in use",
+            Reason::CorruptedData => "corrupted data",
+            Reason::Other(ref note) => note,
+        }
+    }
+}
+/// State of the most recent scrub as reported on `scan:` line of `zpool status`. A summary of
+/// [`ScanStatus`](scan/enum.ScanStatus.html) for callers that only care about scrubs.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ScrubState {
+    /// Pool was never scrubbed.
+    NotRequested,
+    /// Scrub is running right now.
+    InProgress,
+    /// Scrub was paused with `zpool scrub -p`.
+    Paused,
+    /// Last scrub finished.
+    Finished,
+    /// Last scrub was stopped before it finished.
+    Canceled,
+    /// Pool is being (or was) resilvered. Resilver replaces the scrub on the `scan:` line, so
+    /// state of previous scrub is unknown.
+    Resilver,
+}
+
+impl ScrubState {
+    /// Figure out scrub state from the text after `scan:`. Returns `None` if text isn't
+    /// recognized.
+    pub fn from_scan_line(line: &str) -> Option<ScrubState> {
+        ScanStatus::from_scan_line(line).as_ref().map(ScrubState::from)
+    }
+}
+
+impl From<&ScanStatus> for ScrubState {
+    fn from(scan: &ScanStatus) -> ScrubState {
+        match scan {
+            ScanStatus::NotRequested => ScrubState::NotRequested,
+            ScanStatus::InProgress(progress) | ScanStatus::Paused(progress)
+                if progress.kind() == &ScanKind::Resilver =>
+            {
+                ScrubState::Resilver
+            },
+            ScanStatus::Finished(summary) if summary.kind() == &ScanKind::Resilver => {
+                ScrubState::Resilver
+            },
+            ScanStatus::Canceled(ScanKind::Resilver) => ScrubState::Resilver,
+            ScanStatus::InProgress(_) => ScrubState::InProgress,
+            ScanStatus::Paused(_) => ScrubState::Paused,
+            ScanStatus::Finished(_) => ScrubState::Finished,
+            ScanStatus::Canceled(ScanKind::Scrub) => ScrubState::Canceled,
+        }
+    }
+}
+
+/// Entry of the permanent error list printed by `zpool status -v`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum DataError {
+    /// File in a mounted dataset, e.g. `/tank/fs/file`.
+    File(PathBuf),
+    /// File in a dataset or snapshot that isn't mounted, e.g. `tank/fs@snap:/file`.
+    DatasetFile(String, PathBuf),
+    /// Object that can't be resolved to a path, e.g. `tank/fs:<0x1f>`.
+    DatasetObject(String, u64),
+    /// Object of a dataset that is gone, e.g. `<0x36>:<0x1f>`. Dataset id comes first.
+    Object(u64, u64),
+    /// Pool metadata object, e.g. `<metadata>:<0x3f>`.
+    Metadata(u64),
+    /// Not yet classified entry.
+    Other(String),
+}
+
+fn hex_id(text: &str) -> Option<u64> {
+    let hex = text.strip_prefix("<0x")?.strip_suffix('>')?;
+    u64::from_str_radix(hex, 16).ok()
+}
+
+impl DataError {
+    /// Parse single line of the list.
+    pub fn from_entry(text: &str) -> DataError {
+        let text = text.trim();
+        if text.starts_with('/') {
+            return DataError::File(PathBuf::from(text));
+        }
+        // Dataset names can't have `:`, so the first one separates dataset from the rest.
+        let parsed = text.find(':').and_then(|idx| {
+            let (head, tail) = text.split_at(idx);
+            let tail = tail.get(1..)?;
+            if tail.starts_with('/') {
+                return Some(DataError::DatasetFile(head.to_string(), PathBuf::from(tail)));
+            }
+            let object = hex_id(tail)?;
+            match head {
+                "<metadata>" => Some(DataError::Metadata(object)),
+                _ if head.starts_with('<') => Some(DataError::Object(hex_id(head)?, object)),
+                _ => Some(DataError::DatasetObject(head.to_string(), object)),
+            }
+        });
+        parsed.unwrap_or_else(|| DataError::Other(text.to_string()))
+    }
+}
+
+/// Stage of top-level vdev removal.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum RemovalState {
+    /// Data is being evacuated from the device.
+    InProgress,
+    /// Removal finished.
+    Completed,
+    /// Removal was stopped with `zpool remove -s`.
+    Canceled,
+}
+
+/// Top-level vdev removal as reported on `remove:` line of `zpool status`.
+#[derive(Clone, Debug, Eq, PartialEq, Getters)]
+#[get = "pub"]
+pub struct RemovalStatus {
+    /// Stage of the removal.
+    state:    RemovalState,
+    /// Device being removed as `zpool status` names it, e.g. `/dev/sdb` or `vdev 1`.
+    device:   String,
+    /// Progress line, e.g. `1.23G copied out of 10.0G at 50.0M/s, 12.30% done, 0h2m to go`.
+    progress: Option<String>,
+}
+
+impl RemovalStatus {
+    /// Parse the text after `remove:`. Returns `None` if text isn't recognized.
+    pub fn from_remove_line(text: &str) -> Option<RemovalStatus> {
+        let mut lines = text.lines().map(str::trim).filter(|line| !line.is_empty());
+        let first = lines.next()?;
+        let progress = lines.next().map(String::from);
+
+        let (state, device) = if first.starts_with("Evacuation of ") {
+            let rest = &first["Evacuation of ".len()..];
+            (RemovalState::InProgress, &rest[..rest.find(" in progress")?])
+        } else if first.starts_with("Removal of ") {
+            let rest = &first["Removal of ".len()..];
+            if let Some(idx) = rest.find(" canceled") {
+                (RemovalState::Canceled, &rest[..idx])
+            } else {
+                (RemovalState::Completed, &rest[..rest.find(" copied")?])
+            }
+        } else {
+            return None;
+        };
+        Some(RemovalStatus { state, device: device.to_string(), progress })
+    }
+
+    /// Percent of data evacuated so far. Only known while removal is in progress.
+    pub fn percent_done(&self) -> Option<f64> {
+        let progress = self.progress.as_ref()?;
+        let end = progress.find("% done")?;
+        let start = progress[..end].rfind(' ').map_or(0, |idx| idx + 1);
+        progress[start..end].parse().ok()
+    }
+}
+
+/// Format of timestamps in `zpool history`.
+static HISTORY_TIME_FORMAT: &str = "%Y-%m-%d.%H:%M:%S";
+
+/// Single entry of `zpool history -l`.
+#[derive(Clone, Debug, Eq, PartialEq, Getters)]
+#[get = "pub"]
+pub struct HistoryRecord {
+    /// When it happened, in local time of the host that logged it.
+    time:     NaiveDateTime,
+    /// Command as it was typed (`zfs create tank/foo`) or, for internal events, event
+    /// description (`create tank/foo (123)`).
+    command:  String,
+    /// Transaction group of internal event. `None` for commands.
+    txg:      Option<u64>,
+    /// Whether this is an internal event (`zpool history -i`).
+    internal: bool,
+    /// User that ran the command, if logged.
+    user:     Option<String>,
+    /// Host (and zone, if any) where it happened, if logged.
+    host:     Option<String>,
+    /// Indented lines that follow the record, e.g. ioctl input and output.
+    details:  Vec<String>,
+}
+
+impl HistoryRecord {
+    /// Parse output of `zpool history -l` (optionally with `-i`). Lines that don't start with a
+    /// timestamp are attached to the previous record as details.
+    pub fn parse_history(stdout: &str) -> Vec<HistoryRecord> {
+        let mut records: Vec<HistoryRecord> = Vec::new();
+        for line in stdout.lines() {
+            if line.trim().is_empty() || line.starts_with("History for ") {
+                continue;
+            }
+            match HistoryRecord::from_line(line) {
+                Some(record) => records.push(record),
+                None => {
+                    if let Some(last) = records.last_mut() {
+                        last.details.push(line.trim().to_string());
+                    }
+                },
+            }
+        }
+        records
+    }
+
+    fn from_line(line: &str) -> Option<HistoryRecord> {
+        let mut parts = line.splitn(2, ' ');
+        let time = NaiveDateTime::parse_from_str(parts.next()?, HISTORY_TIME_FORMAT).ok()?;
+        let mut rest = parts.next().unwrap_or("").trim();
+
+        let mut txg = None;
+        let mut internal = false;
+        if rest.starts_with("[txg:") {
+            let end = rest.find(']')?;
+            txg = rest["[txg:".len()..end].parse().ok();
+            internal = true;
+            rest = rest[end + 1..].trim_start();
+        } else if rest.starts_with("ioctl ") {
+            internal = true;
+        }
+
+        let (mut user, mut host) = (None, None);
+        if rest.ends_with(']') {
+            if let Some(start) = rest.rfind(" [") {
+                let meta = &rest[start + 2..rest.len() - 1];
+                let (who, on) = if meta.starts_with("on ") {
+                    ("", Some(&meta[3..]))
+                } else {
+                    match meta.find(" on ") {
+                        Some(idx) => (&meta[..idx], Some(&meta[idx + 4..])),
+                        None => (meta, None),
+                    }
+                };
+                // `user 0 (root) ` -> `root`
+                user = who
+                    .find('(')
+                    .and_then(|open| who.rfind(')').map(|close| &who[open + 1..close]))
+                    .map(String::from);
+                host = on.map(|on| on.trim().to_string());
+                rest = rest[..start].trim_end();
+            }
+        }
+
+        Some(HistoryRecord {
+            time,
+            command: rest.to_string(),
+            txg,
+            internal,
+            user,
+            host,
+            details: Vec::new(),
+        })
+    }
+}
+
 /// Consumer friendly Zpool representation. It has generic health status information, structure of
 /// vdevs, devices used to create said vdevs as well as error statistics.
 #[derive(Getters, Builder, Debug, Eq, PartialEq, Clone)]
@@ -40,67 +362,85 @@ pub struct Zpool {
     /// Spare devices.
     #[builder(default)]
     spares:           Vec<Disk>,
+    /// Vdevs of the `special` allocation class: metadata and small blocks.
+    #[builder(default)]
+    special:          Vec<Vdev>,
+    /// Vdevs of the `dedup` allocation class: dedup tables.
+    #[builder(default)]
+    dedup:            Vec<Vdev>,
     /// Value of action field what ever it is.
     #[builder(default)]
     action:           Option<String>,
-    /// Errors?
+    /// Summary of data errors, `None` if there are none.
     #[builder(default)]
     errors:           Option<String>,
+    /// Files and objects with permanent errors. Only `zpool status -v` lists them.
+    #[builder(default)]
+    data_errors:      Vec<DataError>,
     /// Reason why this Zpool is not healthy.
     #[builder(default)]
     reason:           Option<Reason>,
     /// Error statistics
     #[builder(default)]
     error_statistics: ErrorStatistics,
+    /// Progress or outcome of the most recent scrub or resilver. `None` if status doesn't say.
+    #[builder(default)]
+    scan:             Option<ScanStatus>,
+    /// Top-level vdev removal, if one is running or was done since pool import.
+    #[builder(default)]
+    removal:          Option<RemovalStatus>,
+    /// Checkpoint as status describes it, e.g. `created Tue Apr 10 12:02:19 2018, consumes 1.20M`.
+    /// `None` if pool has no checkpoint.
+    #[builder(default)]
+    checkpoint:       Option<String>,
+    /// Pool was destroyed and can only be imported back with
+    /// [`ImportRequest::destroyed`](import/struct.ImportRequest.html#method.destroyed). Only set by
+    /// [`available_including_destroyed`](trait.ZpoolEngine.html#tymethod.available_including_destroyed).
+    #[builder(default)]
+    destroyed:        bool,
 }
 
 impl Zpool {
     /// Create a builder - the preferred way to create a structure.
     pub fn builder() -> ZpoolBuilder { ZpoolBuilder::default() }
 
-    #[allow(clippy::option_unwrap_used, clippy::wildcard_enum_match_arm)]
-    pub(crate) fn from_pest_pair(pair: Pair<'_, Rule>) -> Zpool {
-        debug_assert!(pair.as_rule() == Rule::zpool);
-        let pairs = pair.into_inner();
-        let mut zpool = ZpoolBuilder::default();
-        for pair in pairs {
-            match pair.as_rule() {
-                Rule::pool_name => {
-                    zpool.name(get_string_from_pair(pair));
-                },
-                Rule::pool_id => {
-                    zpool.id(Some(get_u64_from_pair(pair)));
-                },
-                Rule::state => {
-                    zpool.health(get_health_from_pair(pair));
-                },
-                Rule::action => {
-                    zpool.action(Some(get_string_from_pair(pair)));
-                },
-                Rule::errors => {
-                    zpool.errors(get_error_from_pair(pair));
-                },
-                Rule::vdevs => {
-                    zpool.vdevs(get_vdevs_from_pair(pair));
-                },
-                Rule::pool_line => {
-                    set_stats_and_reason_from_pool_line(pair, &mut zpool);
-                },
-                Rule::logs => {
-                    zpool.logs(get_logs_from_pair(pair));
-                },
-                Rule::caches => {
-                    zpool.caches(get_caches_from_pair(pair));
-                },
-                Rule::spares => {
-                    zpool.spares(get_spares_from_pair(pair));
-                },
-                Rule::config | Rule::status | Rule::see | Rule::pool_headers => {},
-                Rule::scan_line => {},
-                _ => unreachable!(),
-            }
+    /// State of the most recent scrub, summarized from [`scan`](#method.scan). `None` if status
+    /// doesn't say.
+    pub fn scrub(&self) -> Option<ScrubState> { self.scan.as_ref().map(ScrubState::from) }
+
+    /// Attach vdev properties to every vdev and device of the pool. Vdevs that are not in the list
+    /// are left without properties.
+    ///
+    /// * `props` - Output of [`VdevProperties::parse_stdout`](vdevprops/struct.VdevProperties.html)
+    /// for all vdevs of the pool.
+    pub fn with_vdev_properties(mut self, props: &[VdevProperties]) -> Zpool {
+        let vdevs = self.vdevs.iter_mut().chain(self.logs.iter_mut());
+        for vdev in vdevs.chain(self.special.iter_mut()).chain(self.dedup.iter_mut()) {
+            vdev.attach_properties(props);
         }
-        zpool.build().expect("Can't build zpool out of pair. Please report at: https://github.com/Inner-Heaven/libzetta-rs")
+        for disk in self.caches.iter_mut().chain(self.spares.iter_mut()) {
+            disk.attach_properties(props);
+        }
+        self
+    }
+
+    /// Devices with non-zero error counters: data, log, special and dedup devices (including the
+    /// ones under `spare-N` nodes), caches and spares.
+    pub fn disks_with_errors(&self) -> Vec<&Disk> {
+        let vdevs = self.vdevs.iter().chain(self.logs.iter());
+        let vdevs = vdevs.chain(self.special.iter()).chain(self.dedup.iter());
+        let vdev_disks = vdevs.flat_map(|vdev| vdev.disks());
+        vdev_disks
+            .flat_map(|disk| std::iter::once(disk).chain(disk.children()))
+            .chain(self.caches.iter())
+            .chain(self.spares.iter())
+            .filter(|disk| disk.error_statistics().has_errors())
+            .collect()
+    }
+
+    /// Hot spares that took over a device and are in service now.
+    pub fn spares_in_use(&self) -> Vec<&Disk> {
+        self.spares.iter().filter(|spare| spare.health() == &Health::InUse).collect()
     }
 }
 
@@ -118,194 +458,6 @@ impl PartialEq<Zpool> for CreateZpoolRequest {
     fn eq(&self, other: &Zpool) -> bool { other == self }
 }
 
-#[inline]
-#[allow(clippy::option_unwrap_used, clippy::result_unwrap_used, clippy::wildcard_enum_match_arm)]
-fn get_error_statistics_from_pair(pair: Pair<'_, Rule>) -> ErrorStatistics {
-    debug_assert_eq!(Rule::error_statistics, pair.as_rule());
-    let mut inner = pair.into_inner();
-    ErrorStatistics {
-        read:     inner.next().unwrap().as_span().as_str().parse().unwrap_or(std::u64::MAX),
-        write:    inner.next().unwrap().as_span().as_str().parse().unwrap_or(std::u64::MAX),
-        checksum: inner.next().unwrap().as_span().as_str().parse().unwrap_or(std::u64::MAX),
-    }
-}
-
-#[inline]
-#[allow(clippy::option_unwrap_used, clippy::wildcard_enum_match_arm)]
-fn set_stats_and_reason_from_pool_line(pool_line: Pair<'_, Rule>, zpool: &mut ZpoolBuilder) {
-    debug_assert_eq!(pool_line.as_rule(), Rule::pool_line);
-
-    for pair in pool_line.into_inner() {
-        match pair.as_rule() {
-            Rule::reason => {
-                zpool.reason(Some(Reason::Other(String::from(pair.as_span().as_str()))));
-            },
-            Rule::error_statistics => {
-                zpool.error_statistics(get_error_statistics_from_pair(pair));
-            },
-            _ => { /* no-op */ },
-        };
-    }
-}
-
-#[inline]
-fn get_vdev_type(raid_name: Pair<'_, Rule>) -> VdevType {
-    let raid_enum = raid_name.into_inner().next().expect("Failed to parse raid_enum");
-    debug_assert!(raid_enum.as_rule() == Rule::raid_enum);
-    VdevType::from_str(raid_enum.as_str()).expect("Failed to parse raid type")
-}
-
-#[inline]
-fn get_path_from_path(path: Option<Pair<'_, Rule>>) -> PathBuf {
-    let path = path.expect("Missing path from disk line");
-    debug_assert!(path.as_rule() == Rule::path);
-    PathBuf::from(path.as_span().as_str())
-}
-
-#[inline]
-fn get_health_from_health(health: Option<Pair<'_, Rule>>) -> Health {
-    let health = health.expect("Missing health from disk line");
-    debug_assert!(health.as_rule() == Rule::state_enum);
-    Health::try_from_str(Some(health.as_span().as_str())).expect("Failed to parse Health")
-}
-
-#[inline]
-fn get_disk_from_disk_line(disk_line: Pair<'_, Rule>) -> Disk {
-    debug_assert!(disk_line.as_rule() == Rule::disk_line);
-
-    let mut inner = disk_line.into_inner();
-
-    let path = get_path_from_path(inner.next());
-    let health = get_health_from_health(inner.next());
-
-    let (error_statics, reason) = get_stats_and_reason_from_pairs(inner);
-    Disk::builder()
-        .path(path)
-        .health(health)
-        .error_statistics(error_statics)
-        .reason(reason)
-        .build()
-        .expect("Failed to build disk")
-}
-
-#[inline]
-#[allow(clippy::option_unwrap_used, clippy::wildcard_enum_match_arm)]
-fn get_stats_and_reason_from_pairs(pairs: Pairs<'_, Rule>) -> (ErrorStatistics, Option<Reason>) {
-    let mut stats = None;
-    let mut reason = None;
-    for pair in pairs {
-        match pair.as_rule() {
-            Rule::error_statistics => stats = Some(get_error_statistics_from_pair(pair)),
-            Rule::reason => reason = Some(Reason::Other(String::from(pair.as_span().as_str()))),
-            _ => {
-                unreachable!();
-            },
-        }
-    }
-    (stats.unwrap_or_default(), reason)
-}
-
-#[inline]
-#[allow(clippy::option_unwrap_used, clippy::wildcard_enum_match_arm)]
-fn get_vdevs_from_pair(pair: Pair<'_, Rule>) -> Vec<Vdev> {
-    debug_assert!(pair.as_rule() == Rule::vdevs);
-
-    pair.into_inner()
-        .map(|vdev| match vdev.as_rule() {
-            Rule::naked_vdev => {
-                let disk_line = vdev.into_inner().next().unwrap();
-
-                let disk = get_disk_from_disk_line(disk_line);
-
-                Vdev::builder()
-                    .kind(VdevType::SingleDisk)
-                    .health(disk.health().clone())
-                    .reason(None)
-                    .disks(vec![disk])
-                    .build()
-                    .expect("Failed to build Vdev")
-            },
-            Rule::raided_vdev => {
-                let mut inner = vdev.into_inner();
-                let raid_line = inner.next().unwrap();
-                debug_assert!(raid_line.as_rule() == Rule::raid_line);
-                let mut raid_line = raid_line.into_inner();
-                let raid_name = raid_line.next().unwrap();
-
-                let health = get_health_from_health(raid_line.next());
-
-                let (error_statics, reason) = get_stats_and_reason_from_pairs(raid_line);
-
-                Vdev::builder()
-                    .kind(get_vdev_type(raid_name))
-                    .health(health)
-                    .disks(inner.map(get_disk_from_disk_line).collect())
-                    .error_statistics(error_statics)
-                    .reason(reason)
-                    .build()
-                    .expect("Failed to build vdev")
-            },
-            _ => {
-                unreachable!();
-            },
-        })
-        .collect()
-}
-
-#[inline]
-fn get_health_from_pair(pair: Pair<'_, Rule>) -> Health {
-    let health = get_string_from_pair(pair);
-    Health::try_from_str(Some(&health)).expect("Failed to unwrap health")
-}
-
-#[inline]
-fn get_u64_from_pair(pair: Pair<'_, Rule>) -> u64 {
-    get_value_from_pair(pair).as_str().parse().expect("Failed to unwrap u64")
-}
-
-#[inline]
-fn get_string_from_pair(pair: Pair<'_, Rule>) -> String {
-    String::from(get_value_from_pair(pair).as_str())
-}
-
-#[inline]
-fn get_value_from_pair(pair: Pair<'_, Rule>) -> Pair<'_, Rule> {
-    let mut pairs = pair.into_inner();
-    pairs.next().expect("Failed to unwrap value")
-}
-
-#[inline]
-#[allow(clippy::option_unwrap_used, clippy::wildcard_enum_match_arm)]
-fn get_error_from_pair(pair: Pair<'_, Rule>) -> Option<String> {
-    let mut pairs = pair.into_inner();
-    let error_pair = pairs.next().expect("Failed to unwrap error");
-    match error_pair.as_rule() {
-        Rule::no_errors => None,
-        _ => Some(String::from(error_pair.as_str())),
-    }
-}
-
-#[inline]
-fn get_logs_from_pair(pair: Pair<'_, Rule>) -> Vec<Vdev> {
-    debug_assert!(pair.as_rule() == Rule::logs);
-    if let Some(vdevs) = pair.into_inner().next() {
-        get_vdevs_from_pair(vdevs)
-    } else {
-        Vec::new()
-    }
-}
-
-#[inline]
-fn get_caches_from_pair(pair: Pair<'_, Rule>) -> Vec<Disk> {
-    debug_assert!(pair.as_rule() == Rule::caches);
-    pair.into_inner().map(get_disk_from_disk_line).collect()
-}
-#[inline]
-fn get_spares_from_pair(pair: Pair<'_, Rule>) -> Vec<Disk> {
-    debug_assert!(pair.as_rule() == Rule::spares);
-    pair.into_inner().map(get_disk_from_disk_line).collect()
-}
-
 // This module can have better tests. Issue #65
 #[cfg(test)]
 mod test {
@@ -313,7 +465,34 @@ mod test {
 
     use crate::zpool::{CreateVdevRequest, Disk, Health, Vdev, VdevType};
 
-    use super::{CreateZpoolRequest, Zpool};
+    use super::{CreateZpoolRequest, DataError, HistoryRecord, Reason, RemovalState,
+                RemovalStatus, ScrubState, Zpool};
+
+    #[test]
+    fn test_reason_notes() {
+        let notes = [
+            ("cannot open", Reason::CannotOpen),
+            ("missing device", Reason::MissingDevice),
+            ("insufficient replicas", Reason::InsufficientReplicas),
+            ("newer version", Reason::NewerVersion),
+            ("unsupported feature(s)", Reason::UnsupportedFeatures),
+            ("unsupported minimum blocksize", Reason::UnsupportedBlockSize),
+            ("too many errors", Reason::TooManyErrors),
+            ("experienced I/O failures", Reason::IoFailures),
+            ("bad intent log", Reason::BadIntentLog),
+            ("external device fault", Reason::ExternalFault),
+            ("split into new pool", Reason::SplitIntoNewPool),
+            ("all children offline", Reason::AllChildrenOffline),
+            ("invalid label", Reason::InvalidLabel),
+            ("currently in use", Reason::CurrentlyInUse),
+            ("corrupted data", Reason::CorruptedData),
+            ("was /dev/sdb", Reason::Other(String::from("was /dev/sdb"))),
+        ];
+        for (note, reason) in notes.iter() {
+            assert_eq!(reason, &Reason::from_note(note));
+            assert_eq!(*note, reason.as_str());
+        }
+    }
 
     #[test]
     fn test_eq_zpool() {
@@ -340,6 +519,81 @@ mod test {
         assert_eq!(request, zpool);
     }
 
+    #[test]
+    fn test_scrub_state_from_scan_line() {
+        let cases = [
+            ("none requested\n", Some(ScrubState::NotRequested)),
+            ("scrub in progress since Sun Jul 25 16:07:49 2021\n", Some(ScrubState::InProgress)),
+            ("scrub repaired 0B in 00:00:01 with 0 errors on Sun Jul 25 16:07:50 2021\n", Some(ScrubState::Finished)),
+            ("scrub canceled on Sun Jul 25 16:07:50 2021\n", Some(ScrubState::Canceled)),
+            ("scrub paused since Mon Jul 26 10:00:00 2021\n", Some(ScrubState::Paused)),
+            ("resilvered 512 in 0 days 00:00:01 with 0 errors on Tue Aug 13 23:03:12 2019\n", Some(ScrubState::Resilver)),
+            ("resilver in progress since Tue Aug 13 23:03:11 2019\n", Some(ScrubState::Resilver)),
+            ("resilver canceled on Tue Aug 13 23:03:12 2019\n", Some(ScrubState::Resilver)),
+            ("something new\n", None),
+        ];
+        for (line, expected) in cases.iter() {
+            assert_eq!(*expected, ScrubState::from_scan_line(line), "{}", line);
+        }
+    }
+
+    #[test]
+    fn test_removal_status_from_remove_line() {
+        let text = "Evacuation of /dev/sdb in progress since Tue Jul 27 10:00:00 2021\n        1.23G copied out of 10.0G at 50.0M/s, 12.30% done, 0h2m to go\n";
+        let removal = RemovalStatus::from_remove_line(text).unwrap();
+        assert_eq!(&RemovalState::InProgress, removal.state());
+        assert_eq!("/dev/sdb", removal.device());
+        assert_eq!(Some(12.30), removal.percent_done());
+
+        let text = "Removal of vdev 1 copied 39.5K in 0h0m, completed on Tue Jul 27 10:03:00 2021\n        1.50K memory used for removed device mappings\n";
+        let removal = RemovalStatus::from_remove_line(text).unwrap();
+        assert_eq!(&RemovalState::Completed, removal.state());
+        assert_eq!("vdev 1", removal.device());
+        assert_eq!(None, removal.percent_done());
+
+        let text = "Removal of /dev/sdb canceled on Tue Jul 27 10:01:00 2021\n";
+        let removal = RemovalStatus::from_remove_line(text).unwrap();
+        assert_eq!(&RemovalState::Canceled, removal.state());
+        assert_eq!(&None, removal.progress());
+
+        assert_eq!(None, RemovalStatus::from_remove_line("something new\n"));
+    }
+
+    #[test]
+    fn test_history() {
+        let stdout = "History for 'tank':
+2021-07-25.16:07:49 zpool create tank /dev/sdb [user 0 (root) on storage-01]
+2021-07-25.16:07:50 [txg:7] set tank (21) compression=lz4 [on storage-01]
+2021-07-25.16:07:51 ioctl create
+    input:
+        type: 2
+2021-07-25.16:07:52 zfs create tank/foo [user 1001 (backup) on storage-01:global]
+";
+        let history = HistoryRecord::parse_history(stdout);
+        assert_eq!(4, history.len());
+
+        let first = &history[0];
+        assert_eq!("zpool create tank /dev/sdb", first.command());
+        assert_eq!("2021-07-25 16:07:49", first.time().to_string());
+        assert_eq!(&Some(String::from("root")), first.user());
+        assert_eq!(&Some(String::from("storage-01")), first.host());
+        assert!(!first.internal());
+
+        let second = &history[1];
+        assert_eq!("set tank (21) compression=lz4", second.command());
+        assert_eq!(&Some(7), second.txg());
+        assert_eq!(&None, second.user());
+        assert!(second.internal());
+
+        let third = &history[2];
+        assert_eq!("ioctl create", third.command());
+        assert!(third.internal());
+        assert_eq!(&vec![String::from("input:"), String::from("type: 2")], third.details());
+
+        assert_eq!(&Some(String::from("storage-01:global")), history[3].host());
+        assert_eq!(&Some(String::from("backup")), history[3].user());
+    }
+
     #[test]
     fn test_ne_zpool() {
         let request = CreateZpoolRequest::builder()
@@ -351,4 +605,19 @@ mod test {
             Zpool::builder().name("wat").health(Health::Online).vdevs(vec![]).build().unwrap();
         assert_ne!(request, zpool);
     }
+
+    #[test]
+    fn test_data_error_entries() {
+        let cases = [
+            ("/tank/fs/file", DataError::File(PathBuf::from("/tank/fs/file"))),
+            ("tank/fs@snap:/a b", DataError::DatasetFile("tank/fs@snap".into(), "/a b".into())),
+            ("tank/fs:<0x1f>", DataError::DatasetObject("tank/fs".into(), 0x1f)),
+            ("<0x36>:<0x1f>", DataError::Object(0x36, 0x1f)),
+            ("<metadata>:<0x3f>", DataError::Metadata(0x3f)),
+            ("tank/fs:<junk>", DataError::Other("tank/fs:<junk>".into())),
+        ];
+        for (entry, expected) in cases.iter() {
+            assert_eq!(expected, &DataError::from_entry(entry));
+        }
+    }
 }
@@ -0,0 +1,165 @@
+//! Difference between a live pool and the request it was created from.
+//!
+//! Declarative tooling keeps a [`CreateZpoolRequest`](../topology/struct.CreateZpoolRequest.html)
+//! as the desired state of a pool. [`TopologyDrift`](struct.TopologyDrift.html) tells what changed
+//! since: devices that are gone, devices nobody asked for and devices that are not healthy.
+//! Device paths are compared as is, so they must be in the same form `zpool status` prints them.
+//!
+//! ### Usage
+//! ```rust,no_run
+//! use libzetta::zpool::{drift::TopologyDrift, CreateVdevRequest, CreateZpoolRequest, ZpoolEngine,
+//!                       ZpoolOpen3};
+//!
+//! let engine = ZpoolOpen3::default();
+//! let desired = CreateZpoolRequest::builder()
+//!     .name("tank")
+//!     .vdev(CreateVdevRequest::mirror(vec!["/vdevs/vdev0", "/vdevs/vdev1"]))
+//!     .build()
+//!     .unwrap();
+//! let drift = TopologyDrift::between(&engine.status("tank").unwrap(), &desired);
+//! if !drift.is_empty() {
+//!     println!("missing: {:?}, extra: {:?}", drift.missing(), drift.extra());
+//! }
+//! ```
+use std::path::PathBuf;
+
+use crate::zpool::{CreateZpoolRequest, Disk, Health, Vdev, Zpool};
+
+/// Structured difference between a pool and a request.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Getters)]
+#[get = "pub"]
+pub struct TopologyDrift {
+    /// Devices in the request that are not in the pool, in request order.
+    missing:  Vec<PathBuf>,
+    /// Devices in the pool that are not in the request. Distributed spares of dRAID vdevs are
+    /// never reported.
+    extra:    Vec<PathBuf>,
+    /// Devices of the pool in a state they are not supposed to be in: anything but `ONLINE` for
+    /// data, log and cache devices and anything but `AVAIL` or `INUSE` for hot spares.
+    degraded: Vec<Disk>,
+}
+
+impl TopologyDrift {
+    /// Compare `pool` against `request`. Devices are matched by path regardless of the vdev they
+    /// are in, so a device moved between vdevs isn't reported. Devices under `spare-N` nodes count
+    /// as members of the vdev.
+    pub fn between(pool: &Zpool, request: &CreateZpoolRequest) -> TopologyDrift {
+        let members: Vec<&Disk> =
+            vdev_disks(pool.vdevs()).chain(vdev_disks(pool.logs())).chain(pool.caches()).collect();
+        let spares: Vec<&Disk> =
+            pool.spares().iter().filter(|spare| !spare.is_distributed_spare()).collect();
+
+        let requested: Vec<&PathBuf> = request
+            .vdevs()
+            .iter()
+            .chain(request.logs())
+            .flat_map(|vdev| vdev.disks())
+            .chain(request.caches())
+            .chain(request.spares())
+            .collect();
+        let present = |path: &PathBuf| members.iter().chain(&spares).any(|disk| disk == &path);
+
+        let missing = requested.iter().filter(|path| !present(path)).map(|&path| path.clone());
+        let mut extra: Vec<PathBuf> = Vec::new();
+        for disk in members.iter().chain(&spares) {
+            if !requested.contains(&disk.path()) && !extra.contains(disk.path()) {
+                extra.push(disk.path().clone());
+            }
+        }
+
+        let unhealthy_member = members.iter().filter(|disk| disk.health() != &Health::Online);
+        let unhealthy_spare = spares.iter().filter(|spare| {
+            !matches!(spare.health(), Health::Available | Health::InUse)
+        });
+        TopologyDrift {
+            missing: missing.collect(),
+            extra,
+            degraded: unhealthy_member.chain(unhealthy_spare).map(|&disk| disk.clone()).collect(),
+        }
+    }
+
+    /// Whether pool matches the request and every device is healthy.
+    pub fn is_empty(&self) -> bool {
+        self.missing.is_empty() && self.extra.is_empty() && self.degraded.is_empty()
+    }
+}
+
+/// Devices of the vdevs with `spare-N` nodes replaced by their children.
+fn vdev_disks<'a>(vdevs: &'a [Vdev]) -> impl Iterator<Item = &'a Disk> {
+    vdevs.iter().flat_map(|vdev| vdev.disks()).flat_map(|disk| {
+        let node = if disk.is_spare_node() { None } else { Some(disk) };
+        node.into_iter().chain(disk.children())
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::zpool::{CreateVdevRequest, VdevType};
+
+    fn disk(path: &str, health: Health) -> Disk {
+        Disk::builder().path(path).health(health).build().unwrap()
+    }
+
+    fn mirror(disks: Vec<Disk>) -> Vdev {
+        Vdev::builder().kind(VdevType::Mirror).health(Health::Online).disks(disks).build().unwrap()
+    }
+
+    fn request() -> CreateZpoolRequest {
+        CreateZpoolRequest::builder()
+            .name("tank")
+            .vdev(CreateVdevRequest::mirror(vec!["sda", "sdb"]))
+            .zil(CreateVdevRequest::disk("nvd0"))
+            .cache("nvd1".into())
+            .spare("sdc".into())
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn no_drift() {
+        let pool = Zpool::builder()
+            .name("tank")
+            .health(Health::Online)
+            .vdevs(vec![mirror(vec![disk("sda", Health::Online), disk("sdb", Health::Online)])])
+            .logs(vec![Vdev::builder()
+                .kind(VdevType::SingleDisk)
+                .health(Health::Online)
+                .disks(vec![disk("nvd0", Health::Online)])
+                .build()
+                .unwrap()])
+            .caches(vec![disk("nvd1", Health::Online)])
+            .spares(vec![disk("sdc", Health::Available), disk("draid1-0-0", Health::Available)])
+            .build()
+            .unwrap();
+        let drift = TopologyDrift::between(&pool, &request());
+        assert!(drift.is_empty());
+    }
+
+    #[test]
+    fn drift() {
+        let node = Disk::builder()
+            .path("spare-1")
+            .health(Health::Degraded)
+            .children(vec![disk("sdb", Health::Faulted), disk("sdc", Health::Online)])
+            .build()
+            .unwrap();
+        let pool = Zpool::builder()
+            .name("tank")
+            .health(Health::Degraded)
+            .vdevs(vec![
+                mirror(vec![disk("sda", Health::Online), node]),
+                mirror(vec![disk("sdd", Health::Online), disk("sde", Health::Online)]),
+            ])
+            .spares(vec![disk("sdc", Health::InUse), disk("sdf", Health::Unavailable)])
+            .build()
+            .unwrap();
+        let drift = TopologyDrift::between(&pool, &request());
+        assert!(!drift.is_empty());
+        assert_eq!(&vec![PathBuf::from("nvd0"), PathBuf::from("nvd1")], drift.missing());
+        let extra: Vec<PathBuf> = vec!["sdd".into(), "sde".into(), "sdf".into()];
+        assert_eq!(&extra, drift.extra());
+        let degraded: Vec<&PathBuf> = drift.degraded().iter().map(Disk::path).collect();
+        assert_eq!(vec![&PathBuf::from("sdb"), &PathBuf::from("sdf")], degraded);
+    }
+}
@@ -0,0 +1,426 @@
+//! ZFS events as reported by `zpool events -v`.
+//!
+//! Every event has a class (`ereport.fs.zfs.checksum`, `sysevent.fs.zfs.scrub_start`, ...) and a
+//! payload of name-value pairs. Embedded nvlists are flattened: `detector = (embedded nvlist)`
+//! followed by `scheme = "zfs"` becomes `detector.scheme`.
+//!
+//! [`ZpoolEngine::events`](../trait.ZpoolEngine.html#tymethod.events) reads the whole event log,
+//! commonly used fields (event id, pool and vdev GUIDs, timestamp) have typed accessors on
+//! [`ZpoolEvent`](struct.ZpoolEvent.html).
+//! [`ZpoolOpen3::events_follow`](../struct.ZpoolOpen3.html#method.events_follow) keeps
+//! `zpool events -f` running and yields events as they are emitted.
+//!
+//! Error reports (ereports) about I/O and checksum errors can be routed to an
+//! [`EreportHook`](trait.EreportHook.html). This crate parses events and resolves the device,
+//! what to do about it (query SMART, open a ticket) is up to the hook.
+//!
+//! ### Usage
+//! ```rust
+//! use libzetta::zpool::events::{dispatch_ereports, parse_events, Ereport, EreportHook};
+//! use std::path::Path;
+//!
+//! struct Printer;
+//! impl EreportHook for Printer {
+//!     fn on_ereport(&self, ereport: &Ereport, device: &Path) {
+//!         println!("{:?} on {}", ereport.kind(), device.display());
+//!     }
+//! }
+//!
+//! let stdout = "TIME                           CLASS\n\
+//!               Jul 25 2021 16:07:49.123456789 ereport.fs.zfs.io\n\
+//!               \tclass = \"ereport.fs.zfs.io\"\n\
+//!               \tpool = \"tank\"\n\
+//!               \tvdev_path = \"/dev/sdb1\"\n\n";
+//! let events = parse_events(stdout);
+//! assert_eq!(1, dispatch_ereports(&events, &Printer));
+//! ```
+use std::{collections::{HashMap, VecDeque},
+          convert::TryFrom,
+          fs,
+          io::{BufRead, BufReader, Lines},
+          path::{Path, PathBuf},
+          process::{Child, ChildStdout}};
+
+use chrono::NaiveDateTime;
+
+use crate::zpool::{iostat::exit_error, ZpoolError, ZpoolResult};
+
+/// A single event.
+#[derive(Debug, Clone, PartialEq, Eq, Getters)]
+#[get = "pub"]
+pub struct ZpoolEvent {
+    /// Time as printed in the event header.
+    time:    String,
+    /// Event class.
+    class:   String,
+    /// Flattened payload. Strings are unquoted, numbers are left as printed (usually hex).
+    payload: HashMap<String, String>,
+}
+
+impl ZpoolEvent {
+    /// Get payload value.
+    pub fn get(&self, key: &str) -> Option<&str> { self.payload.get(key).map(String::as_str) }
+
+    /// Get payload value as a number. Understands both decimal and `0x` prefixed hex.
+    pub fn get_u64(&self, key: &str) -> Option<u64> { self.get(key).and_then(parse_u64) }
+
+    /// Is this an error report.
+    pub fn is_ereport(&self) -> bool { self.class.starts_with("ereport.") }
+
+    /// Event id. Grows monotonically until reboot or `zpool events -c`.
+    pub fn eid(&self) -> Option<u64> { self.get_u64("eid") }
+
+    /// Name of the pool the event is about.
+    pub fn pool(&self) -> Option<&str> { self.get("pool") }
+
+    /// GUID of the pool the event is about.
+    pub fn pool_guid(&self) -> Option<u64> { self.get_u64("pool_guid") }
+
+    /// GUID of the vdev the event is about.
+    pub fn vdev_guid(&self) -> Option<u64> { self.get_u64("vdev_guid") }
+
+    /// Path of the vdev the event is about.
+    pub fn vdev_path(&self) -> Option<&Path> { self.get("vdev_path").map(Path::new) }
+
+    /// When event happened (UTC), taken from `time` payload (seconds and nanoseconds).
+    pub fn timestamp(&self) -> Option<NaiveDateTime> {
+        let mut parts = self.get("time")?.split_whitespace();
+        let secs = parts.next().and_then(parse_u64)?;
+        let nanos = parts.next().and_then(parse_u64).unwrap_or(0);
+        NaiveDateTime::from_timestamp_opt(i64::try_from(secs).ok()?, u32::try_from(nanos).ok()?)
+    }
+}
+
+pub(crate) fn parse_u64(value: &str) -> Option<u64> {
+    if value.starts_with("0x") {
+        u64::from_str_radix(&value[2..], 16).ok()
+    } else {
+        value.parse().ok()
+    }
+}
+
+/// Parse output of `zpool events -v`. Lines that don't make sense are skipped.
+pub fn parse_events(stdout: &str) -> Vec<ZpoolEvent> {
+    let mut events = Vec::new();
+    let mut current: Option<ZpoolEvent> = None;
+    let mut prefix: Vec<String> = Vec::new();
+
+    for line in stdout.lines() {
+        if line.trim().is_empty() || line.starts_with("TIME ") {
+            continue;
+        }
+        if !line.starts_with(char::is_whitespace) {
+            events.extend(current.take());
+            prefix.clear();
+            let line = line.trim_end();
+            if let Some(idx) = line.rfind(char::is_whitespace) {
+                current = Some(ZpoolEvent {
+                    time:    line[..idx].trim().to_string(),
+                    class:   line[idx + 1..].to_string(),
+                    payload: HashMap::new(),
+                });
+            }
+            continue;
+        }
+        let event = match current.as_mut() {
+            Some(event) => event,
+            None => continue,
+        };
+        let line = line.trim();
+        if line.starts_with("(end ") {
+            prefix.pop();
+            continue;
+        }
+        let mut kv = line.splitn(2, " = ");
+        let (key, value) = match (kv.next(), kv.next()) {
+            (Some(key), Some(value)) => (key, value),
+            _ => continue,
+        };
+        if value == "(embedded nvlist)" {
+            prefix.push(key.to_string());
+            continue;
+        }
+        let mut name = prefix.join(".");
+        if !name.is_empty() {
+            name.push('.');
+        }
+        name.push_str(key);
+        event.payload.insert(name, value.trim_matches('"').to_string());
+    }
+    events.extend(current);
+    events
+}
+
+/// Events of `zpool events -Hvf` as they are emitted. Events that happened before the stream was
+/// started come first. Child process is killed when stream is dropped. Stream ends when `zpool`
+/// exits, if it exited with an error that error is the last item.
+pub struct EventStream {
+    child:   Child,
+    lines:   Lines<BufReader<ChildStdout>>,
+    block:   String,
+    pending: VecDeque<ZpoolEvent>,
+    error:   Option<ZpoolError>,
+    done:    bool,
+}
+
+impl EventStream {
+    /// Wrap a spawned `zpool events` with piped `stdout` and `stderr`.
+    pub(crate) fn new(mut child: Child) -> ZpoolResult<EventStream> {
+        let stdout = child
+            .stdout
+            .take()
+            .ok_or_else(|| ZpoolError::Other(String::from("stdout of zpool is not piped")))?;
+        Ok(EventStream {
+            child,
+            lines: BufReader::new(stdout).lines(),
+            block: String::new(),
+            pending: VecDeque::new(),
+            error: None,
+            done: false,
+        })
+    }
+
+    /// Stop `zpool events`. Same as dropping the stream, but reports errors.
+    pub fn stop(mut self) -> ZpoolResult<()> {
+        self.done = true;
+        self.child.kill()?;
+        self.child.wait()?;
+        Ok(())
+    }
+
+    fn flush_block(&mut self) {
+        self.pending.extend(parse_events(&self.block));
+        self.block.clear();
+    }
+}
+
+impl Iterator for EventStream {
+    type Item = ZpoolResult<ZpoolEvent>;
+
+    fn next(&mut self) -> Option<ZpoolResult<ZpoolEvent>> {
+        loop {
+            if let Some(event) = self.pending.pop_front() {
+                return Some(Ok(event));
+            }
+            if self.done {
+                return self.error.take().map(Err);
+            }
+            // Every event is followed by an empty line, that's when it is complete.
+            match self.lines.next() {
+                Some(Ok(line)) => {
+                    if line.trim().is_empty() {
+                        self.flush_block();
+                    } else {
+                        self.block.push_str(&line);
+                        self.block.push('\n');
+                    }
+                },
+                Some(Err(e)) => {
+                    self.done = true;
+                    return Some(Err(e.into()));
+                },
+                None => {
+                    self.done = true;
+                    self.flush_block();
+                    self.error = exit_error(&mut self.child);
+                },
+            }
+        }
+    }
+}
+
+impl Drop for EventStream {
+    fn drop(&mut self) {
+        // Child might have exited already, nothing to do about errors here.
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// What kind of error an ereport is about.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EreportKind {
+    /// I/O error (`ereport.fs.zfs.io`).
+    Io,
+    /// Checksum error (`ereport.fs.zfs.checksum`).
+    Checksum,
+    /// Anything else.
+    Other,
+}
+
+/// An error report about a vdev.
+#[derive(Debug, Clone, PartialEq, Eq, Getters)]
+#[get = "pub"]
+pub struct Ereport {
+    /// Kind of error.
+    kind:      EreportKind,
+    /// Pool the vdev belongs to.
+    pool:      Option<String>,
+    /// GUID of the vdev.
+    vdev_guid: Option<u64>,
+    /// Path to the vdev as ZFS knows it.
+    vdev_path: Option<PathBuf>,
+    /// Event this report was made from.
+    event:     ZpoolEvent,
+}
+
+impl Ereport {
+    /// Make an ereport out of event. `None` if event isn't an ereport.
+    pub fn from_event(event: &ZpoolEvent) -> Option<Ereport> {
+        if !event.is_ereport() {
+            return None;
+        }
+        let kind = match event.class.as_str() {
+            "ereport.fs.zfs.io" => EreportKind::Io,
+            "ereport.fs.zfs.checksum" => EreportKind::Checksum,
+            _ => EreportKind::Other,
+        };
+        Some(Ereport {
+            kind,
+            pool:      event.get("pool").map(String::from),
+            vdev_guid: event.get_u64("vdev_guid"),
+            vdev_path: event.get("vdev_path").map(PathBuf::from),
+            event:     event.clone(),
+        })
+    }
+}
+
+/// Hook that is called for every I/O or checksum ereport with known device.
+pub trait EreportHook {
+    /// Called with the ereport and resolved device path.
+    fn on_ereport(&self, ereport: &Ereport, device: &Path);
+}
+
+impl<F: Fn(&Ereport, &Path)> EreportHook for F {
+    fn on_ereport(&self, ereport: &Ereport, device: &Path) { self(ereport, device) }
+}
+
+/// Resolve symlinks like `/dev/disk/by-id/...` into the actual device node. If path can't be
+/// resolved (e.g. device is gone) it's returned as is.
+pub fn resolve_device<P: AsRef<Path>>(path: P) -> PathBuf {
+    fs::canonicalize(path.as_ref()).unwrap_or_else(|_| path.as_ref().to_path_buf())
+}
+
+/// Pass I/O and checksum ereports to the hook. Returns how many times hook was called.
+pub fn dispatch_ereports<H: EreportHook + ?Sized>(events: &[ZpoolEvent], hook: &H) -> usize {
+    let mut dispatched = 0;
+    for ereport in events.iter().filter_map(Ereport::from_event) {
+        if ereport.kind == EreportKind::Other {
+            continue;
+        }
+        if let Some(ref vdev_path) = ereport.vdev_path {
+            hook.on_ereport(&ereport, &resolve_device(vdev_path));
+            dispatched += 1;
+        }
+    }
+    dispatched
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::zpool::ZpoolErrorKind;
+    use std::{cell::RefCell,
+              process::{Command, Stdio}};
+
+    static EVENTS: &str = r#"TIME                           CLASS
+Jul 25 2021 16:07:49.123456789 ereport.fs.zfs.checksum
+        class = "ereport.fs.zfs.checksum"
+        ena = 0x2f1b8c2d5a00001
+        detector = (embedded nvlist)
+                version = 0x0
+                scheme = "zfs"
+                pool = 0x8d6b1b8f0c4f1a2b
+                vdev = 0x4e1b7f8d4a3c2b1a
+        (end detector)
+        pool = "tank"
+        pool_guid = 0x8d6b1b8f0c4f1a2b
+        vdev_guid = 0x4e1b7f8d4a3c2b1a
+        vdev_type = "disk"
+        vdev_path = "/nonexistent/sdb1"
+        time = 0x60fd8a35 0x75bcd15
+        eid = 0x1d
+
+Jul 25 2021 16:07:50.000000000 sysevent.fs.zfs.scrub_start
+        version = 0x0
+        class = "sysevent.fs.zfs.scrub_start"
+        pool = "tank"
+        eid = 0x1e
+
+Jul 25 2021 16:07:51.000000000 ereport.fs.zfs.io
+        class = "ereport.fs.zfs.io"
+        pool = "tank"
+        eid = 0x1f
+"#;
+
+    #[test]
+    fn parsing() {
+        let events = parse_events(EVENTS);
+        assert_eq!(3, events.len());
+
+        let first = &events[0];
+        assert_eq!("Jul 25 2021 16:07:49.123456789", first.time());
+        assert_eq!("ereport.fs.zfs.checksum", first.class());
+        assert_eq!(Some("zfs"), first.get("detector.scheme"));
+        assert_eq!(Some("tank"), first.get("pool"));
+        assert_eq!(Some(0x1d), first.get_u64("eid"));
+        assert_eq!(Some("0x60fd8a35 0x75bcd15"), first.get("time"));
+        assert!(first.is_ereport());
+        assert!(!events[1].is_ereport());
+        assert_eq!(Some(0x1d), first.eid());
+        assert_eq!(Some("tank"), first.pool());
+        assert_eq!(Some(0x8d6b_1b8f_0c4f_1a2b), first.pool_guid());
+        assert_eq!(Some(Path::new("/nonexistent/sdb1")), first.vdev_path());
+        let timestamp = NaiveDateTime::from_timestamp_opt(0x60fd_8a35, 0x75b_cd15);
+        assert_eq!(timestamp, first.timestamp());
+        assert_eq!(None, events[1].timestamp());
+
+        let ereport = Ereport::from_event(first).unwrap();
+        assert_eq!(EreportKind::Checksum, *ereport.kind());
+        assert_eq!(&Some(0x4e1b_7f8d_4a3c_2b1a), ereport.vdev_guid());
+    }
+
+    fn spawn(script: &str) -> Child {
+        Command::new("sh")
+            .arg("-c")
+            .arg(script)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap()
+    }
+
+    #[test]
+    fn following() {
+        let script = format!("printf '{}'", EVENTS.splitn(2, '\n').nth(1).unwrap());
+        let events: Vec<_> = EventStream::new(spawn(&script)).unwrap().collect();
+        assert_eq!(3, events.len());
+        assert_eq!(Some(0x1f), events[2].as_ref().unwrap().eid());
+
+        let script = format!("printf '{}'; echo 'internal error' >&2; exit 1", EVENTS);
+        let mut stream = EventStream::new(spawn(&script)).unwrap();
+        assert_eq!(3, stream.by_ref().take(3).filter(Result::is_ok).count());
+        assert_eq!(ZpoolErrorKind::Other, stream.next().unwrap().unwrap_err().kind());
+        assert!(stream.next().is_none());
+
+        let script = format!("printf '{}'; sleep 60", EVENTS);
+        let mut stream = EventStream::new(spawn(&script)).unwrap();
+        assert!(stream.next().unwrap().is_ok());
+        stream.stop().unwrap();
+    }
+
+    #[test]
+    fn dispatching() {
+        let events = parse_events(EVENTS);
+        let seen = RefCell::new(Vec::new());
+        let hook = |ereport: &Ereport, device: &Path| {
+            seen.borrow_mut().push((*ereport.kind(), device.to_path_buf()));
+        };
+        // Last ereport has no vdev_path, so it's skipped.
+        assert_eq!(1, dispatch_ereports(&events, &hook));
+        assert_eq!(
+            vec![(EreportKind::Checksum, PathBuf::from("/nonexistent/sdb1"))],
+            seen.into_inner()
+        );
+    }
+}
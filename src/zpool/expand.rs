@@ -0,0 +1,169 @@
+//! Online expansion of pools after backing devices grew.
+//!
+//! Once a LUN is grown on the SAN side, ZFS doesn't use the new space until every device of the
+//! vdev is brought online with `zpool online -e` (or `autoexpand=on` picks it up). Doing it by hand
+//! per device is easy to get wrong: one forgotten mirror leg keeps the whole vdev at the old size.
+//! [`expand_pool`](fn.expand_pool.html) expands every healthy device of the pool and reports pool
+//! `size` and `expandsize` before and after.
+//!
+//! ### Usage
+//! ```rust,no_run
+//! use libzetta::zpool::{expand::expand_pool, ZpoolOpen3};
+//!
+//! let engine = ZpoolOpen3::default();
+//! let report = expand_pool(&engine, "tank").unwrap();
+//! println!("tank grew by {} bytes", report.grown());
+//! if !report.skipped().is_empty() {
+//!     println!("not expanded: {:?}", report.skipped());
+//! }
+//! ```
+use std::path::PathBuf;
+
+use crate::zpool::{Disk, Health, OnlineMode, Zpool, ZpoolEngine, ZpoolResult};
+
+/// Outcome of pool expansion.
+#[derive(Debug, Clone, PartialEq, Eq, Getters)]
+#[get = "pub"]
+pub struct ExpandReport {
+    /// Value of `autoexpand` on the pool.
+    auto_expand:        bool,
+    /// Pool size before expansion.
+    size_before:        usize,
+    /// Pool size after expansion.
+    size_after:         usize,
+    /// Space that could be claimed before expansion.
+    expand_size_before: Option<usize>,
+    /// Space that could still be claimed after expansion. Anything but `None` means some vdev
+    /// didn't grow, usually because not all of its devices got bigger.
+    expand_size_after:  Option<usize>,
+    /// Devices that were brought online with expansion.
+    expanded:           Vec<PathBuf>,
+    /// Devices that were skipped because they are not online.
+    skipped:            Vec<PathBuf>,
+}
+
+impl ExpandReport {
+    /// How much pool size went up.
+    pub fn grown(&self) -> usize { self.size_after.saturating_sub(self.size_before) }
+}
+
+/// Split devices of data, dedup, special and log vdevs into those that can be expanded and those
+/// that can't. Only online devices are expanded, because `online -e` would also bring offline
+/// devices back. `spare-N` and `replacing-N` aren't devices, their children are looked at instead.
+pub fn expandable_devices(zpool: &Zpool) -> (Vec<PathBuf>, Vec<PathBuf>) {
+    let mut expandable = Vec::new();
+    let mut skipped = Vec::new();
+    let vdevs = zpool
+        .vdevs()
+        .iter()
+        .chain(zpool.dedup().iter())
+        .chain(zpool.special().iter())
+        .chain(zpool.logs().iter());
+    for disk in vdevs.flat_map(|vdev| vdev.disks()) {
+        sort_device(disk, &mut expandable, &mut skipped);
+    }
+    (expandable, skipped)
+}
+
+fn sort_device(disk: &Disk, expandable: &mut Vec<PathBuf>, skipped: &mut Vec<PathBuf>) {
+    if !disk.children().is_empty() {
+        for child in disk.children() {
+            sort_device(child, expandable, skipped);
+        }
+    } else if disk.health() == &Health::Online {
+        expandable.push(disk.path().clone());
+    } else {
+        skipped.push(disk.path().clone());
+    }
+}
+
+/// Expand every online device of the pool to use all available space.
+///
+/// * `engine` - Engine to use.
+/// * `name` - Name of the pool.
+pub fn expand_pool<E: ZpoolEngine, N: AsRef<str>>(
+    engine: &E,
+    name: N,
+) -> ZpoolResult<ExpandReport> {
+    let before = engine.read_properties(&name)?;
+    let zpool = engine.status(&name)?;
+    let (expanded, skipped) = expandable_devices(&zpool);
+    for device in &expanded {
+        engine.bring_online(&name, device, OnlineMode::Expand)?;
+    }
+    let after = engine.read_properties(&name)?;
+
+    Ok(ExpandReport {
+        auto_expand:        *before.auto_expand(),
+        size_before:        *before.size(),
+        size_after:         *after.size(),
+        expand_size_before: *before.expand_size(),
+        expand_size_after:  *after.expand_size(),
+        expanded,
+        skipped,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::zpool::{Vdev, VdevType};
+
+    fn disk(path: &str, health: Health) -> Disk {
+        Disk::builder().path(path).health(health).build().unwrap()
+    }
+
+    #[test]
+    fn only_online_devices_are_expanded() {
+        let mirror = Vdev::builder()
+            .kind(VdevType::Mirror)
+            .health(Health::Degraded)
+            .disks(vec![disk("sda", Health::Online), disk("sdb", Health::Offline)])
+            .build()
+            .unwrap();
+        let log = Vdev::builder()
+            .kind(VdevType::SingleDisk)
+            .health(Health::Online)
+            .disks(vec![disk("sdc", Health::Online)])
+            .build()
+            .unwrap();
+        let zpool = Zpool::builder()
+            .name("tank")
+            .health(Health::Degraded)
+            .vdevs(vec![mirror])
+            .logs(vec![log])
+            .caches(vec![disk("sdd", Health::Online)])
+            .build()
+            .unwrap();
+
+        let (expandable, skipped) = expandable_devices(&zpool);
+        assert_eq!(vec![PathBuf::from("sda"), PathBuf::from("sdc")], expandable);
+        assert_eq!(vec![PathBuf::from("sdb")], skipped);
+    }
+
+    #[test]
+    fn devices_under_spare_are_expanded() {
+        let spare = Disk::builder()
+            .path("spare-1")
+            .health(Health::Degraded)
+            .children(vec![disk("sdb", Health::Faulted), disk("sdc", Health::Online)])
+            .build()
+            .unwrap();
+        let mirror = Vdev::builder()
+            .kind(VdevType::Mirror)
+            .health(Health::Degraded)
+            .disks(vec![disk("sda", Health::Online), spare])
+            .build()
+            .unwrap();
+        let zpool = Zpool::builder()
+            .name("tank")
+            .health(Health::Degraded)
+            .vdevs(vec![mirror])
+            .build()
+            .unwrap();
+
+        let (expandable, skipped) = expandable_devices(&zpool);
+        assert_eq!(vec![PathBuf::from("sda"), PathBuf::from("sdc")], expandable);
+        assert_eq!(vec![PathBuf::from("sdb")], skipped);
+    }
+}
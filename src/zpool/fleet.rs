@@ -0,0 +1,207 @@
+//! Fan-out of the same operation over many hosts.
+//!
+//! A controller that looks after a handful of backup servers usually asks all of them the same
+//! question: is everything healthy, does every host have the pool. [`Fleet`](struct.Fleet.html)
+//! holds named engines (local and remote ones made with
+//! [`ZpoolOpen3::over_ssh`](../open3/struct.ZpoolOpen3.html#method.over_ssh)) and runs an
+//! operation on all of them, at most `concurrency` hosts at a time. One unreachable host doesn't
+//! fail the whole call: every host gets its own [`HostResult`](struct.HostResult.html), in the
+//! order hosts were added.
+//!
+//! ### Usage
+//! ```rust,no_run
+//! use libzetta::zpool::{fleet::Fleet, ZpoolOpen3};
+//!
+//! let fleet = Fleet::new()
+//!     .with_host("local", ZpoolOpen3::default())
+//!     .with_host("backup-01", ZpoolOpen3::over_ssh("root@backup-01"))
+//!     .with_host("backup-02", ZpoolOpen3::over_ssh("root@backup-02"))
+//!     .with_concurrency(2);
+//!
+//! for result in fleet.exists_everywhere("backup") {
+//!     match result.result() {
+//!         Ok(true) => {},
+//!         Ok(false) => println!("{} has no backup pool", result.host()),
+//!         Err(e) => println!("{} failed: {}", result.host(), e),
+//!     }
+//! }
+//! ```
+use std::{sync::{atomic::{AtomicUsize, Ordering},
+                 mpsc, Arc},
+          thread};
+
+use crate::zpool::{Zpool, ZpoolEngine, ZpoolError, ZpoolOpen3, ZpoolResult};
+
+/// How many hosts are queried at the same time unless told otherwise.
+pub const DEFAULT_CONCURRENCY: usize = 4;
+
+/// Outcome of an operation on a single host.
+#[derive(Debug, Getters)]
+#[get = "pub"]
+pub struct HostResult<T> {
+    /// Name of the host as it was added to the fleet.
+    host:   String,
+    /// What the operation returned.
+    result: ZpoolResult<T>,
+}
+
+impl<T> HostResult<T> {
+    /// Whether operation succeeded on this host.
+    pub fn is_ok(&self) -> bool { self.result.is_ok() }
+
+    /// Drop host name and keep the result.
+    pub fn into_result(self) -> ZpoolResult<T> { self.result }
+}
+
+/// Named engines that are queried together.
+pub struct Fleet<E = ZpoolOpen3> {
+    hosts:       Vec<(String, Arc<E>)>,
+    concurrency: usize,
+}
+
+impl<E: ZpoolEngine + Send + Sync + 'static> Default for Fleet<E> {
+    fn default() -> Fleet<E> { Fleet::new() }
+}
+
+impl<E: ZpoolEngine + Send + Sync + 'static> Fleet<E> {
+    /// Create an empty fleet with [`DEFAULT_CONCURRENCY`](constant.DEFAULT_CONCURRENCY.html).
+    pub fn new() -> Fleet<E> { Fleet { hosts: Vec::new(), concurrency: DEFAULT_CONCURRENCY } }
+
+    /// Add a host. Host that is already in the fleet gets the new engine.
+    ///
+    /// * `host` - Name used in results.
+    /// * `engine` - Engine that talks to the host.
+    pub fn add<H: Into<String>>(&mut self, host: H, engine: E) {
+        let host = host.into();
+        let engine = Arc::new(engine);
+        match self.hosts.iter_mut().find(|(name, _)| *name == host) {
+            Some(entry) => entry.1 = engine,
+            None => self.hosts.push((host, engine)),
+        }
+    }
+
+    /// Same as [`add`](#method.add), but consumes and returns the fleet.
+    pub fn with_host<H: Into<String>>(mut self, host: H, engine: E) -> Fleet<E> {
+        self.add(host, engine);
+        self
+    }
+
+    /// Limit how many hosts are queried at the same time. Zero is treated as one.
+    pub fn with_concurrency(mut self, concurrency: usize) -> Fleet<E> {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    /// Names of all hosts in the order they were added.
+    pub fn hosts(&self) -> Vec<&str> { self.hosts.iter().map(|(name, _)| name.as_str()).collect() }
+
+    /// Engine of the host.
+    pub fn engine(&self, host: &str) -> Option<&E> {
+        self.hosts.iter().find(|(name, _)| name == host).map(|(_, engine)| engine.as_ref())
+    }
+
+    /// Number of hosts.
+    pub fn len(&self) -> usize { self.hosts.len() }
+
+    /// Whether there are no hosts.
+    pub fn is_empty(&self) -> bool { self.hosts.is_empty() }
+
+    /// Run the operation on every host, at most `concurrency` at a time. Results are in the
+    /// order hosts were added. If the operation panics, hosts that were left without a result get
+    /// `ZpoolError::Other`.
+    pub fn run<T, F>(&self, op: F) -> Vec<HostResult<T>>
+    where
+        T: Send + 'static,
+        F: Fn(&E) -> ZpoolResult<T> + Send + Sync + 'static,
+    {
+        let op = Arc::new(op);
+        let hosts = Arc::new(self.hosts.clone());
+        let next = Arc::new(AtomicUsize::new(0));
+        let (tx, rx) = mpsc::channel();
+
+        for _ in 0..self.concurrency.min(self.hosts.len()) {
+            let (op, hosts, next, tx) =
+                (Arc::clone(&op), Arc::clone(&hosts), Arc::clone(&next), tx.clone());
+            thread::spawn(move || loop {
+                let idx = next.fetch_add(1, Ordering::SeqCst);
+                let engine = match hosts.get(idx) {
+                    Some((_, engine)) => engine,
+                    None => break,
+                };
+                if tx.send((idx, op(engine))).is_err() {
+                    break;
+                }
+            });
+        }
+        drop(tx);
+
+        let mut results: Vec<Option<ZpoolResult<T>>> = self.hosts.iter().map(|_| None).collect();
+        for (idx, result) in rx {
+            if let Some(slot) = results.get_mut(idx) {
+                *slot = Some(result);
+            }
+        }
+        self.hosts
+            .iter()
+            .zip(results)
+            .map(|((host, _), result)| HostResult {
+                host:   host.clone(),
+                result: result.unwrap_or_else(|| {
+                    Err(ZpoolError::Other(String::from("operation panicked")))
+                }),
+            })
+            .collect()
+    }
+
+    /// `zpool status` of every pool on every host.
+    pub fn status_all(&self) -> Vec<HostResult<Vec<Zpool>>> { self.run(|engine| engine.all()) }
+
+    /// Check whether pool exists on every host.
+    ///
+    /// * `name` - Name of the pool.
+    pub fn exists_everywhere<N: Into<String>>(&self, name: N) -> Vec<HostResult<bool>> {
+        let name = name.into();
+        self.run(move |engine| engine.exists(&name))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::zpool::ZpoolErrorKind;
+
+    #[test]
+    fn fan_out_keeps_order() {
+        let fleet = Fleet::new()
+            .with_host("alpha", ZpoolOpen3::with_cmd("true"))
+            .with_host("beta", ZpoolOpen3::with_cmd("false"))
+            .with_host("gamma", ZpoolOpen3::with_cmd("/nonexistent/zpool"))
+            .with_host("delta", ZpoolOpen3::with_cmd("true"))
+            .with_concurrency(2);
+        assert_eq!(vec!["alpha", "beta", "gamma", "delta"], fleet.hosts());
+
+        let results = fleet.exists_everywhere("tank");
+        let hosts: Vec<&str> = results.iter().map(|r| r.host().as_str()).collect();
+        assert_eq!(vec!["alpha", "beta", "gamma", "delta"], hosts);
+        assert!(*results[0].result().as_ref().unwrap());
+        assert!(!*results[1].result().as_ref().unwrap());
+        let err = results[2].result().as_ref().unwrap_err();
+        assert_eq!(ZpoolErrorKind::CmdNotFound, err.kind());
+        assert!(results[3].is_ok());
+
+        let results = fleet.status_all();
+        assert_eq!(4, results.len());
+        assert!(!results[2].is_ok());
+    }
+
+    #[test]
+    fn replacing_host() {
+        let mut fleet = Fleet::new().with_concurrency(0);
+        fleet.add("alpha", ZpoolOpen3::with_cmd("false"));
+        fleet.add("alpha", ZpoolOpen3::with_cmd("true"));
+        assert_eq!(1, fleet.len());
+        assert!(fleet.engine("alpha").is_some());
+        assert!(fleet.engine("beta").is_none());
+        assert!(fleet.exists_everywhere("tank").remove(0).into_result().unwrap());
+    }
+}
@@ -0,0 +1,234 @@
+//! Pool handles pinned to a pool GUID.
+//!
+//! Pool names are not identities: after export and import a daemon can find a different pool
+//! under the same name, for example a backup pool that was imported while the original one was
+//! away. A [`ZpoolHandle`](struct.ZpoolHandle.html) remembers GUID of the pool it was opened for
+//! and checks it before every operation that changes the pool. If the name now refers to another
+//! pool, the operation is not run and
+//! [`PoolChanged`](../enum.ZpoolError.html#variant.PoolChanged) is returned instead.
+//!
+//! The check and the operation are two commands, so this protects against pools that were
+//! swapped between operations, not against a swap that races with the operation itself.
+//!
+//! ### Usage
+//! ```rust,no_run
+//! use libzetta::zpool::{DestroyMode, ZpoolEngine, ZpoolError, ZpoolOpen3};
+//!
+//! let engine = ZpoolOpen3::default();
+//! let tank = engine.open("tank").unwrap();
+//! // ... time passes, pools are exported and imported ...
+//! match tank.destroy(DestroyMode::Gentle) {
+//!     Err(ZpoolError::PoolChanged(name, ..)) => println!("{} is not our pool anymore", name),
+//!     other => other.unwrap(),
+//! }
+//! ```
+use std::ffi::OsStr;
+
+use crate::zpool::{CreateMode, CreateVdevRequest, DestroyMode, ExportMode, InitializeAction,
+                   OfflineMode, OnlineMode, PropPair, SplitOptions, TrimOptions, Zpool,
+                   ZpoolEngine, ZpoolError, ZpoolProperties, ZpoolPropertiesWrite, ZpoolResult};
+
+/// Pool opened by [`ZpoolEngine::open`](../trait.ZpoolEngine.html#method.open).
+#[derive(Debug)]
+pub struct ZpoolHandle<'a, E: ZpoolEngine> {
+    engine: &'a E,
+    name:   String,
+    guid:   u64,
+}
+
+impl<'a, E: ZpoolEngine> ZpoolHandle<'a, E> {
+    /// Open the pool and remember its GUID.
+    ///
+    /// * `engine` - Engine to run operations with.
+    /// * `name` - Name of the zpool.
+    pub fn new<N: AsRef<str>>(engine: &'a E, name: N) -> ZpoolResult<ZpoolHandle<'a, E>> {
+        let guid = *engine.read_properties(&name)?.guid();
+        Ok(ZpoolHandle { engine, name: name.as_ref().to_string(), guid })
+    }
+
+    /// Name of the pool.
+    pub fn name(&self) -> &str { &self.name }
+
+    /// GUID of the pool the handle was opened for.
+    pub fn guid(&self) -> u64 { self.guid }
+
+    /// Engine the handle runs operations with.
+    pub fn engine(&self) -> &'a E { self.engine }
+
+    /// Check that the name still refers to the same pool.
+    pub fn verify(&self) -> ZpoolResult<()> {
+        let found = *self.engine.read_properties(&self.name)?.guid();
+        if found == self.guid {
+            Ok(())
+        } else {
+            Err(ZpoolError::PoolChanged(self.name.clone(), self.guid, found))
+        }
+    }
+
+    /// Status of the pool. Not checked, it doesn't change anything.
+    pub fn status(&self) -> ZpoolResult<Zpool> { self.engine.status(&self.name) }
+
+    /// Properties of the pool. Not checked, it doesn't change anything.
+    pub fn read_properties(&self) -> ZpoolResult<ZpoolProperties> {
+        self.engine.read_properties(&self.name)
+    }
+
+    /// See [`ZpoolEngine::destroy`](../trait.ZpoolEngine.html#tymethod.destroy).
+    pub fn destroy(&self, mode: DestroyMode) -> ZpoolResult<()> {
+        self.verify()?;
+        self.engine.destroy(&self.name, mode)
+    }
+
+    /// See [`ZpoolEngine::export`](../trait.ZpoolEngine.html#tymethod.export).
+    pub fn export(&self, mode: ExportMode) -> ZpoolResult<()> {
+        self.verify()?;
+        self.engine.export(&self.name, mode)
+    }
+
+    /// See [`ZpoolEngine::update_properties`](../trait.ZpoolEngine.html#method.update_properties).
+    pub fn update_properties(&self, props: ZpoolPropertiesWrite) -> ZpoolResult<ZpoolProperties> {
+        self.verify()?;
+        self.engine.update_properties(&self.name, props)
+    }
+
+    /// See [`ZpoolEngine::set_property`](../trait.ZpoolEngine.html#tymethod.set_property).
+    pub fn set_property<P: PropPair>(&self, key: &str, value: &P) -> ZpoolResult<()> {
+        self.verify()?;
+        self.engine.set_property(&self.name, key, value)
+    }
+
+    /// See [`ZpoolEngine::scrub`](../trait.ZpoolEngine.html#tymethod.scrub).
+    pub fn scrub(&self) -> ZpoolResult<()> {
+        self.verify()?;
+        self.engine.scrub(&self.name)
+    }
+
+    /// See [`ZpoolEngine::stop_scrub`](../trait.ZpoolEngine.html#tymethod.stop_scrub).
+    pub fn stop_scrub(&self) -> ZpoolResult<()> {
+        self.verify()?;
+        self.engine.stop_scrub(&self.name)
+    }
+
+    /// See [`ZpoolEngine::clear`](../trait.ZpoolEngine.html#tymethod.clear).
+    pub fn clear(&self, device: Option<&OsStr>) -> ZpoolResult<()> {
+        self.verify()?;
+        self.engine.clear(&self.name, device)
+    }
+
+    /// See [`ZpoolEngine::take_offline`](../trait.ZpoolEngine.html#tymethod.take_offline).
+    pub fn take_offline<D: AsRef<OsStr>>(&self, device: D, mode: OfflineMode) -> ZpoolResult<()> {
+        self.verify()?;
+        self.engine.take_offline(&self.name, device, mode)
+    }
+
+    /// See [`ZpoolEngine::bring_online`](../trait.ZpoolEngine.html#tymethod.bring_online).
+    pub fn bring_online<D: AsRef<OsStr>>(&self, device: D, mode: OnlineMode) -> ZpoolResult<()> {
+        self.verify()?;
+        self.engine.bring_online(&self.name, device, mode)
+    }
+
+    /// See [`ZpoolEngine::attach`](../trait.ZpoolEngine.html#tymethod.attach).
+    pub fn attach<D: AsRef<OsStr>>(&self, device: D, new_device: D) -> ZpoolResult<()> {
+        self.verify()?;
+        self.engine.attach(&self.name, device, new_device)
+    }
+
+    /// See [`ZpoolEngine::detach`](../trait.ZpoolEngine.html#tymethod.detach).
+    pub fn detach<D: AsRef<OsStr>>(&self, device: D) -> ZpoolResult<()> {
+        self.verify()?;
+        self.engine.detach(&self.name, device)
+    }
+
+    /// See [`ZpoolEngine::add_vdev`](../trait.ZpoolEngine.html#tymethod.add_vdev).
+    pub fn add_vdev(&self, new_vdev: CreateVdevRequest, add_mode: CreateMode) -> ZpoolResult<()> {
+        self.verify()?;
+        self.engine.add_vdev(&self.name, new_vdev, add_mode)
+    }
+
+    /// See [`ZpoolEngine::add_zil`](../trait.ZpoolEngine.html#tymethod.add_zil).
+    pub fn add_zil(&self, new_zil: CreateVdevRequest, add_mode: CreateMode) -> ZpoolResult<()> {
+        self.verify()?;
+        self.engine.add_zil(&self.name, new_zil, add_mode)
+    }
+
+    /// See [`ZpoolEngine::add_cache`](../trait.ZpoolEngine.html#tymethod.add_cache).
+    pub fn add_cache<D: AsRef<OsStr>>(
+        &self,
+        new_cache: D,
+        add_mode: CreateMode,
+    ) -> ZpoolResult<()> {
+        self.verify()?;
+        self.engine.add_cache(&self.name, new_cache, add_mode)
+    }
+
+    /// See [`ZpoolEngine::add_spare`](../trait.ZpoolEngine.html#tymethod.add_spare).
+    pub fn add_spare<D: AsRef<OsStr>>(
+        &self,
+        new_spare: D,
+        add_mode: CreateMode,
+    ) -> ZpoolResult<()> {
+        self.verify()?;
+        self.engine.add_spare(&self.name, new_spare, add_mode)
+    }
+
+    /// See [`ZpoolEngine::replace`](../trait.ZpoolEngine.html#tymethod.replace).
+    pub fn replace<D: AsRef<OsStr>, O: AsRef<OsStr>>(
+        &self,
+        old_disk: D,
+        new_disk: O,
+        mode: CreateMode,
+    ) -> ZpoolResult<()> {
+        self.verify()?;
+        self.engine.replace(&self.name, old_disk, new_disk, mode)
+    }
+
+    /// See [`ZpoolEngine::remove`](../trait.ZpoolEngine.html#tymethod.remove).
+    pub fn remove<D: AsRef<OsStr>>(&self, device: D) -> ZpoolResult<()> {
+        self.verify()?;
+        self.engine.remove(&self.name, device)
+    }
+
+    /// See
+    /// [`ZpoolEngine::discard_checkpoint`](../trait.ZpoolEngine.html#tymethod.discard_checkpoint).
+    pub fn discard_checkpoint(&self) -> ZpoolResult<()> {
+        self.verify()?;
+        self.engine.discard_checkpoint(&self.name)
+    }
+
+    /// See [`ZpoolEngine::upgrade`](../trait.ZpoolEngine.html#tymethod.upgrade).
+    pub fn upgrade(&self) -> ZpoolResult<()> {
+        self.verify()?;
+        self.engine.upgrade(&self.name)
+    }
+
+    /// See [`ZpoolEngine::trim`](../trait.ZpoolEngine.html#tymethod.trim).
+    pub fn trim(&self, options: TrimOptions) -> ZpoolResult<()> {
+        self.verify()?;
+        self.engine.trim(&self.name, options)
+    }
+
+    /// See [`ZpoolEngine::initialize`](../trait.ZpoolEngine.html#tymethod.initialize).
+    pub fn initialize<D: AsRef<OsStr>>(
+        &self,
+        devices: &[D],
+        action: InitializeAction,
+    ) -> ZpoolResult<()> {
+        self.verify()?;
+        self.engine.initialize(&self.name, devices, action)
+    }
+
+    /// See [`ZpoolEngine::split`](../trait.ZpoolEngine.html#tymethod.split).
+    pub fn split<M: AsRef<str>>(&self, new_name: M, options: SplitOptions) -> ZpoolResult<()> {
+        self.verify()?;
+        self.engine.split(&self.name, new_name, options)
+    }
+
+    /// See [`ZpoolEngine::reguid`](../trait.ZpoolEngine.html#tymethod.reguid). The handle follows
+    /// the pool to its new GUID.
+    pub fn reguid(&mut self) -> ZpoolResult<()> {
+        self.verify()?;
+        self.engine.reguid(&self.name)?;
+        self.guid = *self.engine.read_properties(&self.name)?.guid();
+        Ok(())
+    }
+}
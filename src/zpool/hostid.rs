@@ -0,0 +1,106 @@
+//! Host ownership of pools.
+//!
+//! Every pool remembers hostid and hostname of the system that imported it last. Import refuses
+//! to touch a pool that belongs to another host unless forced, and if pool has `multihost=on`
+//! (MMP), it refuses even with force while the other host is alive. This module surfaces those
+//! facts: local hostid, host recorded in device labels, and
+//! [`ZpoolError::ActiveOnOtherHost`](../enum.ZpoolError.html) that carries
+//! [`HostInfo`](struct.HostInfo.html) instead of plain stderr.
+//!
+//! ### Usage
+//! ```rust,no_run
+//! use libzetta::zpool::{hostid::{local_hostid, read_label_host},
+//!                       ZpoolEngine, ZpoolError, ZpoolOpen3};
+//!
+//! let engine = ZpoolOpen3::default();
+//! match engine.import("tank") {
+//!     Err(ZpoolError::ActiveOnOtherHost(host)) if host.can_force_import() => {
+//!         println!("tank was last used by {:?}, we are {:x}", host.hostname(), local_hostid());
+//!     },
+//!     Err(ZpoolError::ActiveOnOtherHost(host)) => {
+//!         println!("tank is alive on {:?}, export it there first", host.hostname());
+//!     },
+//!     other => {
+//!         other.unwrap();
+//!     },
+//! }
+//! let label = read_label_host("/dev/sdb1").unwrap();
+//! println!("{:?}", label.hostid());
+//! ```
+use std::{convert::TryFrom, fmt, fs, path::Path};
+
+use crate::zpool::{label::read_label, ZpoolResult};
+
+/// Where hostid is persisted on Linux.
+pub static HOSTID_PATH: &str = "/etc/hostid";
+
+/// Host that (last) imported a pool.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Getters)]
+#[get = "pub"]
+pub struct HostInfo {
+    /// Hostname, if known.
+    hostname:  Option<String>,
+    /// Hostid, if known.
+    hostid:    Option<u32>,
+    /// Pool is protected by multihost (MMP) and the other host is still writing to it.
+    multihost: bool,
+}
+
+impl HostInfo {
+    /// Create a new host description.
+    pub fn new(hostname: Option<String>, hostid: Option<u32>, multihost: bool) -> HostInfo {
+        HostInfo { hostname, hostid, multihost }
+    }
+
+    /// Whether forced import is an option at all. It is never an option for MMP-protected pools
+    /// that are active elsewhere. For other pools it's only safe if the other host is down.
+    pub fn can_force_import(&self) -> bool { !self.multihost }
+
+    /// Whether this is the host we are running on.
+    pub fn is_local(&self) -> bool { self.hostid == Some(local_hostid()) }
+}
+
+impl fmt::Display for HostInfo {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let hostname = self.hostname.as_ref().map_or("unknown host", String::as_str);
+        match self.hostid {
+            Some(hostid) => write!(f, "{} (hostid={:x})", hostname, hostid),
+            None => write!(f, "{}", hostname),
+        }
+    }
+}
+
+/// Hostid of this system: `/etc/hostid` if present, `gethostid(3)` otherwise.
+pub fn local_hostid() -> u32 {
+    if let Ok(bytes) = fs::read(HOSTID_PATH) {
+        if bytes.len() >= 4 {
+            return u32::from_ne_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        }
+    }
+    // `gethostid` returns a sign-extended 32-bit value in `long`.
+    let hostid = i64::from(unsafe { libc::gethostid() }) & 0xffff_ffff;
+    u32::try_from(hostid).unwrap_or_default()
+}
+
+/// Read hostid and hostname from ZFS label on the device with `zdb -l`.
+///
+/// * `device` - Device that is part of the pool.
+pub fn read_label_host<P: AsRef<Path>>(device: P) -> ZpoolResult<HostInfo> {
+    read_label(device).map(|label| label.host())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn host_info() {
+        let host = HostInfo::new(Some(String::from("storage-01")), Some(2_831_187_970), false);
+        assert!(host.can_force_import());
+        assert_eq!("storage-01 (hostid=a8c08002)", host.to_string());
+
+        let host = HostInfo::new(None, None, true);
+        assert!(!host.can_force_import());
+        assert_eq!("unknown host", host.to_string());
+    }
+}
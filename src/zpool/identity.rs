@@ -0,0 +1,266 @@
+//! Stable names of pool devices.
+//!
+//! `zpool status` prints devices the way they were added to the pool: `sdb`, `ada1p3`,
+//! `ata-WDC_WD40EFRX-68N32N0_WD-WCC7K0XXXXXX` or `gptid/5c5e1c2a-...`. Kernel names like `sdb`
+//! can point at another disk after a reboot, so automation that is told "replace the disk at
+//! `/dev/sdb`" has to find out which device of the pool that is right now.
+//! [`DeviceResolver`](struct.DeviceResolver.html) maps a name to the device node and its stable
+//! aliases: links in `/dev/disk/by-*` on Linux and GEOM labels (`gpt/`, `gptid/`, `diskid/`) on
+//! FreeBSD.
+//!
+//! ### Usage
+//! ```rust,no_run
+//! use libzetta::zpool::{identity::DeviceResolver, ZpoolEngine, ZpoolOpen3};
+//!
+//! let engine = ZpoolOpen3::default();
+//! let resolver = DeviceResolver::system().unwrap();
+//! let pool = engine.status("tank").unwrap();
+//! if let Some(disk) = resolver.find_in_pool(&pool, "/dev/sdb").unwrap() {
+//!     let identity = resolver.resolve(disk.path()).unwrap();
+//!     println!("{:?} is {:?}", disk.path(), identity.stable_path());
+//! }
+//! ```
+use std::{fs,
+          path::{Path, PathBuf}};
+
+use crate::zpool::{Disk, Zpool, ZpoolError, ZpoolResult};
+
+/// Directories with links to device nodes on Linux, most stable first.
+const LINUX_ALIAS_DIRS: &[&str] =
+    &["disk/by-id", "disk/by-vdev", "disk/by-partuuid", "disk/by-path"];
+
+/// Device node of a pool device and its stable names.
+#[derive(Debug, Clone, PartialEq, Eq, Getters)]
+#[get = "pub"]
+pub struct DeviceIdentity {
+    /// Name as it was given, usually as `zpool status` printed it.
+    name:    PathBuf,
+    /// Device node, e.g. `/dev/sdb1` or `/dev/ada0p3`.
+    node:    PathBuf,
+    /// Stable paths of the same device, most stable first. Empty if device has none.
+    aliases: Vec<PathBuf>,
+}
+
+impl DeviceIdentity {
+    /// The most stable path of the device: first alias or the node itself.
+    pub fn stable_path(&self) -> &Path { self.aliases.first().unwrap_or(&self.node) }
+}
+
+/// Resolves device names to [`DeviceIdentity`](struct.DeviceIdentity.html).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeviceResolver {
+    /// Where device nodes are, `/dev` normally.
+    dev:    PathBuf,
+    /// GEOM labels and their providers, relative to `dev`.
+    labels: Vec<(PathBuf, PathBuf)>,
+}
+
+impl DeviceResolver {
+    /// Resolver for devices in `dev`. GEOM labels are not known until
+    /// [`with_glabel_status`](#method.with_glabel_status) is called.
+    pub fn new<D: Into<PathBuf>>(dev: D) -> DeviceResolver {
+        DeviceResolver { dev: dev.into(), labels: Vec::new() }
+    }
+
+    /// Resolver for devices of running system. On FreeBSD it runs `glabel status -s` to learn
+    /// GEOM labels.
+    #[cfg(target_os = "linux")]
+    pub fn system() -> ZpoolResult<DeviceResolver> { Ok(DeviceResolver::new("/dev")) }
+
+    /// Resolver for devices of running system. On FreeBSD it runs `glabel status -s` to learn
+    /// GEOM labels.
+    #[cfg(not(target_os = "linux"))]
+    pub fn system() -> ZpoolResult<DeviceResolver> {
+        let out = std::process::Command::new("glabel").args(&["status", "-s"]).output()?;
+        if out.status.success() {
+            let stdout = String::from_utf8_lossy(&out.stdout);
+            Ok(DeviceResolver::new("/dev").with_glabel_status(&stdout))
+        } else {
+            Err(ZpoolError::UnrecognizedOutput(String::from_utf8_lossy(&out.stderr).into_owned()))
+        }
+    }
+
+    /// Learn GEOM labels from output of `glabel status -s`: label, status and provider per line.
+    pub fn with_glabel_status(mut self, stdout: &str) -> DeviceResolver {
+        self.labels = stdout
+            .lines()
+            .filter_map(|line| {
+                let columns: Vec<&str> = line.split_whitespace().collect();
+                match columns.as_slice() {
+                    [label, _, provider] => Some((PathBuf::from(label), PathBuf::from(provider))),
+                    _ => None,
+                }
+            })
+            .collect();
+        self
+    }
+
+    /// Find device node and stable names of a device. Returns `NoSuchDevice` if there is no
+    /// such device.
+    ///
+    /// * `name` - Absolute path or a name relative to `/dev` or one of Linux `/dev/disk/by-*`
+    /// directories, as `zpool status` prints it.
+    pub fn resolve<P: AsRef<Path>>(&self, name: P) -> ZpoolResult<DeviceIdentity> {
+        let name = name.as_ref();
+        let relative = name.strip_prefix(&self.dev).unwrap_or(name);
+        let node = match self.labels.iter().find(|(label, _)| label == relative) {
+            Some((_, provider)) => self.dev.join(provider),
+            None => fs::canonicalize(self.locate(name).ok_or(ZpoolError::NoSuchDevice)?)?,
+        };
+
+        let provider = node.strip_prefix(&self.dev).unwrap_or(&node);
+        let mut labels: Vec<PathBuf> = self
+            .labels
+            .iter()
+            .filter(|(_, p)| p == provider)
+            .map(|(label, _)| self.dev.join(label))
+            .collect();
+        // `gpt/` labels are set by the admin, `gptid/` and `diskid/` are generated.
+        labels.sort_by_key(|label| !label.starts_with(self.dev.join("gpt")));
+
+        let mut aliases = self.links_to(&node)?;
+        aliases.extend(labels);
+        Ok(DeviceIdentity { name: name.to_path_buf(), node, aliases })
+    }
+
+    /// Device of the pool that is the same device as `device`, no matter how either of them is
+    /// named. Devices that can't be resolved, e.g. missing ones, are skipped.
+    ///
+    /// * `pool` - Status of the pool.
+    /// * `device` - Any name of the device, e.g. `/dev/sdb`.
+    pub fn find_in_pool<'a, P: AsRef<Path>>(
+        &self,
+        pool: &'a Zpool,
+        device: P,
+    ) -> ZpoolResult<Option<&'a Disk>> {
+        let node = self.resolve(device)?.node;
+        let vdev_disks = pool.vdevs().iter().chain(pool.logs()).flat_map(|vdev| vdev.disks());
+        let mut disks = vdev_disks
+            .flat_map(|disk| std::iter::once(disk).chain(disk.children()))
+            .chain(pool.caches())
+            .chain(pool.spares())
+            .filter(|disk| !disk.is_spare_node());
+        Ok(disks.find(|disk| self.resolve(disk.path()).map_or(false, |id| id.node == node)))
+    }
+
+    /// Existing path for `name`.
+    fn locate(&self, name: &Path) -> Option<PathBuf> {
+        if name.is_absolute() {
+            return Some(name.to_path_buf()).filter(|path| path.exists());
+        }
+        std::iter::once(self.dev.join(name))
+            .chain(LINUX_ALIAS_DIRS.iter().map(|dir| self.dev.join(dir).join(name)))
+            .find(|path| path.exists())
+    }
+
+    /// Links in Linux alias directories pointing at `node`.
+    fn links_to(&self, node: &Path) -> ZpoolResult<Vec<PathBuf>> {
+        let mut aliases = Vec::new();
+        for dir in LINUX_ALIAS_DIRS.iter().map(|dir| self.dev.join(dir)) {
+            let entries = match fs::read_dir(&dir) {
+                Ok(entries) => entries,
+                Err(ref e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(ZpoolError::Io(e)),
+            };
+            let mut links: Vec<PathBuf> = entries
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .filter(|link| fs::canonicalize(link).map_or(false, |target| target == node))
+                .collect();
+            links.sort();
+            aliases.extend(links);
+        }
+        Ok(aliases)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::{fs::File, os::unix::fs::symlink};
+
+    use tempdir::TempDir;
+
+    use super::*;
+    use crate::zpool::{Health, Vdev, VdevType, ZpoolErrorKind};
+
+    fn disk(path: &str) -> Disk {
+        Disk::builder().path(path).health(Health::Online).build().unwrap()
+    }
+
+    /// `/dev` with `sdb` and `sdc`, and by-id links to both.
+    fn dev() -> (TempDir, PathBuf) {
+        let tmp = TempDir::new("device-identity").unwrap();
+        let dev = fs::canonicalize(tmp.path()).unwrap();
+        File::create(dev.join("sdb")).unwrap();
+        File::create(dev.join("sdc")).unwrap();
+        fs::create_dir_all(dev.join("disk/by-id")).unwrap();
+        fs::create_dir_all(dev.join("disk/by-path")).unwrap();
+        symlink("../../sdb", dev.join("disk/by-id/wwn-0x5000c500a1b2c3d4")).unwrap();
+        symlink("../../sdb", dev.join("disk/by-id/ata-ST4000_Z1Z0AAAA")).unwrap();
+        symlink("../../sdb", dev.join("disk/by-path/pci-0000:00:1f.2-ata-2")).unwrap();
+        symlink("../../sdc", dev.join("disk/by-id/ata-ST4000_Z1Z0BBBB")).unwrap();
+        (tmp, dev)
+    }
+
+    #[test]
+    fn resolve_linux() {
+        let (_tmp, dev) = dev();
+        let resolver = DeviceResolver::new(&dev);
+
+        let identity = resolver.resolve("ata-ST4000_Z1Z0AAAA").unwrap();
+        assert_eq!(&dev.join("sdb"), identity.node());
+        let expected = vec![
+            dev.join("disk/by-id/ata-ST4000_Z1Z0AAAA"),
+            dev.join("disk/by-id/wwn-0x5000c500a1b2c3d4"),
+            dev.join("disk/by-path/pci-0000:00:1f.2-ata-2"),
+        ];
+        assert_eq!(&expected, identity.aliases());
+        assert_eq!(dev.join("disk/by-id/ata-ST4000_Z1Z0AAAA"), identity.stable_path());
+
+        assert_eq!(identity.aliases(), resolver.resolve("sdb").unwrap().aliases());
+        assert_eq!(identity.node(), resolver.resolve(dev.join("sdb")).unwrap().node());
+
+        let err = resolver.resolve("sdz").unwrap_err();
+        assert_eq!(ZpoolErrorKind::NoSuchDevice, err.kind());
+    }
+
+    #[test]
+    fn resolve_glabel() {
+        let (_tmp, dev) = dev();
+        let stdout = "gptid/5c5e1c2a-d17d-11e4-9eed-10c37b9d936f     N/A  ada0p3\n\
+                      gpt/disk0                                      N/A  ada0p3\n\
+                      diskid/DISK-Z1Z0AAAA                           N/A  ada1\n";
+        let resolver = DeviceResolver::new(&dev).with_glabel_status(stdout);
+
+        let identity = resolver.resolve("gptid/5c5e1c2a-d17d-11e4-9eed-10c37b9d936f").unwrap();
+        assert_eq!(&dev.join("ada0p3"), identity.node());
+        let expected = vec![
+            dev.join("gpt/disk0"),
+            dev.join("gptid/5c5e1c2a-d17d-11e4-9eed-10c37b9d936f"),
+        ];
+        assert_eq!(&expected, identity.aliases());
+    }
+
+    #[test]
+    fn find_in_pool() {
+        let (_tmp, dev) = dev();
+        let resolver = DeviceResolver::new(&dev);
+        let vdev = Vdev::builder()
+            .kind(VdevType::Mirror)
+            .health(Health::Online)
+            .disks(vec![disk("ata-ST4000_Z1Z0AAAA"), disk("ata-ST4000_Z1Z0BBBB"), disk("sdz")])
+            .build()
+            .unwrap();
+        let pool = Zpool::builder()
+            .name("tank")
+            .health(Health::Online)
+            .vdevs(vec![vdev])
+            .build()
+            .unwrap();
+
+        let found = resolver.find_in_pool(&pool, dev.join("sdc")).unwrap().unwrap();
+        assert_eq!(&PathBuf::from("ata-ST4000_Z1Z0BBBB"), found.path());
+
+        File::create(dev.join("sdd")).unwrap();
+        assert_eq!(None, resolver.find_in_pool(&pool, "sdd").unwrap());
+    }
+}
@@ -0,0 +1,365 @@
+//! Importing pools and warnings of successful imports.
+//!
+//! [`ImportRequest`](struct.ImportRequest.html) covers what `zpool import` needs for pools that
+//! don't come up on their own: forcing a pool last used by another host, importing read-only or
+//! under an altroot, importing without lost log devices, rewinding a damaged pool to an earlier
+//! transaction group, bringing back a pool that was destroyed by mistake and importing a pool
+//! under another name, for good or only until export.
+//!
+//! `zpool import` can succeed and still report problems: devices that were unavailable, datasets
+//! that failed to mount or share. [`import`](../trait.ZpoolEngine.html#method.import) returns
+//! them as [`ImportWarning`](enum.ImportWarning.html) instead of dropping them, so a pool that came
+//! up degraded or half-mounted doesn't look like a clean import.
+//!
+//! ### Usage
+//! ```rust,no_run
+//! use libzetta::zpool::{import::{ImportName, ImportRequest, ImportWarning, Recovery},
+//!                       ZpoolEngine, ZpoolOpen3};
+//!
+//! let engine = ZpoolOpen3::default();
+//! for warning in engine.import("tank").unwrap() {
+//!     match warning {
+//!         ImportWarning::MountFailed { dataset, reason } => {
+//!             println!("{} is not mounted: {}", dataset, reason)
+//!         },
+//!         other => println!("{}", other),
+//!     }
+//! }
+//!
+//! let request = ImportRequest::builder()
+//!     .name("backup")
+//!     .dir("/dev/disk/by-id")
+//!     .readonly(true)
+//!     .altroot("/mnt/backup")
+//!     .recovery(Recovery::Rewind)
+//!     .build()
+//!     .unwrap();
+//! engine.import_with(request).unwrap();
+//!
+//! // Look at a copy of "tank" while the real one stays imported.
+//! let request = ImportRequest::builder()
+//!     .name("1234567890")
+//!     .import_name(ImportName::Temporary("tank-copy".into()))
+//!     .build()
+//!     .unwrap();
+//! engine.import_with(request).unwrap();
+//! ```
+use std::{collections::HashMap, ffi::OsString, fmt, path::PathBuf};
+
+use crate::{args::property_arg, zpool::ZpoolResult};
+
+/// How to recover a pool that can't be imported as it is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Recovery {
+    /// Import the pool as it is.
+    Off,
+    /// Discard the last few transactions if that makes the pool importable (`-F`).
+    Rewind,
+    /// Look for an importable transaction group much further back (`-F -X`). Can take a very long
+    /// time and loses everything written after that group.
+    ExtremeRewind,
+}
+
+impl Default for Recovery {
+    fn default() -> Recovery { Recovery::Off }
+}
+
+/// Name of the pool after import.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ImportName {
+    /// Keep the name the pool has.
+    Same,
+    /// Rename the pool. New name is written to its devices.
+    Rename(String),
+    /// Use this name while the pool is imported, but leave the name on devices as it is (`-t`).
+    /// Lets a copy of a pool be looked at next to the pool it was copied from.
+    Temporary(String),
+}
+
+impl Default for ImportName {
+    fn default() -> ImportName { ImportName::Same }
+}
+
+/// What to import and how.
+#[derive(Builder, Debug, Clone, Default, Getters, PartialEq, Eq)]
+#[builder(setter(into))]
+#[get = "pub"]
+pub struct ImportRequest {
+    /// Name or GUID of the pool.
+    name:        String,
+    /// Directories to look for devices in (`-d`). Empty means `/dev/`.
+    #[builder(default)]
+    dirs:        Vec<PathBuf>,
+    /// Import a pool that looks in use by another host (`-f`). Make sure it's not.
+    #[builder(default)]
+    force:       bool,
+    /// Import without writing anything to the pool (`-o readonly=on`).
+    #[builder(default)]
+    readonly:    bool,
+    /// Import under this altroot (`-R`). Pool is not added to the cache file.
+    #[builder(default, setter(into, strip_option))]
+    altroot:     Option<PathBuf>,
+    /// Import even if log devices are missing (`-m`). Whatever was in the log is lost.
+    #[builder(default)]
+    missing_log: bool,
+    /// Whether to rewind a pool that can't be imported as it is.
+    #[builder(default)]
+    recovery:    Recovery,
+    /// Pool properties to set for this import (`-o key=value`).
+    #[builder(default)]
+    properties:  HashMap<String, String>,
+    /// Import a destroyed pool (`-D`). Only works until its devices are reused.
+    #[builder(default)]
+    destroyed:   bool,
+    /// Name of the pool after import.
+    #[builder(default)]
+    import_name: ImportName,
+}
+
+impl ImportRequest {
+    /// A preferred way to create this.
+    pub fn builder() -> ImportRequestBuilder { ImportRequestBuilder::default() }
+
+    /// Import pool by name or GUID with default options.
+    ///
+    /// * `name` - Name or GUID of the pool.
+    pub fn new<N: Into<String>>(name: N) -> ImportRequest {
+        ImportRequest { name: name.into(), ..ImportRequest::default() }
+    }
+
+    /// Same request, but devices are also looked for in `dir`.
+    pub(crate) fn with_dir(mut self, dir: PathBuf) -> ImportRequest {
+        self.dirs.push(dir);
+        self
+    }
+
+    /// Arguments for `zpool import`, pool name included.
+    pub(crate) fn into_args(self) -> ZpoolResult<Vec<OsString>> {
+        let mut args: Vec<OsString> = Vec::new();
+        for dir in self.dirs {
+            args.push("-d".into());
+            args.push(dir.into_os_string());
+        }
+        if self.destroyed {
+            args.push("-D".into());
+        }
+        if self.force {
+            args.push("-f".into());
+        }
+        if self.missing_log {
+            args.push("-m".into());
+        }
+        match self.recovery {
+            Recovery::Off => {},
+            Recovery::Rewind => args.push("-F".into()),
+            Recovery::ExtremeRewind => {
+                args.push("-F".into());
+                args.push("-X".into());
+            },
+        }
+        if let Some(altroot) = self.altroot {
+            args.push("-R".into());
+            args.push(altroot.into_os_string());
+        }
+        if self.readonly {
+            args.push("-o".into());
+            args.push("readonly=on".into());
+        }
+        for (key, value) in &self.properties {
+            args.push("-o".into());
+            args.push(property_arg(key, value)?);
+        }
+        if let ImportName::Temporary(_) = self.import_name {
+            args.push("-t".into());
+        }
+        args.push(self.name.into());
+        match self.import_name {
+            ImportName::Same => {},
+            ImportName::Rename(new_name) | ImportName::Temporary(new_name) => {
+                args.push(new_name.into())
+            },
+        }
+        Ok(args)
+    }
+}
+
+impl ImportRequestBuilder {
+    /// Add directory to look for devices in.
+    ///
+    /// * `dir` - Directory, e.g. `/dev/disk/by-id`.
+    pub fn dir<D: Into<PathBuf>>(&mut self, dir: D) -> &mut ImportRequestBuilder {
+        self.dirs.get_or_insert_with(Vec::new).push(dir.into());
+        self
+    }
+
+    /// Add pool property to set for this import.
+    ///
+    /// * `key` - Name of the property.
+    /// * `value` - Value of the property.
+    pub fn property<K: Into<String>, V: Into<String>>(
+        &mut self,
+        key: K,
+        value: V,
+    ) -> &mut ImportRequestBuilder {
+        self.properties.get_or_insert_with(HashMap::new).insert(key.into(), value.into());
+        self
+    }
+}
+
+/// Something `zpool import` complained about while importing the pool.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ImportWarning {
+    /// Some devices are missing or unavailable, pool is degraded.
+    DevicesUnavailable(String),
+    /// Dataset failed to mount.
+    MountFailed {
+        /// Dataset or mountpoint as reported.
+        dataset: String,
+        /// Why it failed.
+        reason:  String,
+    },
+    /// Dataset failed to share.
+    ShareFailed {
+        /// Dataset as reported.
+        dataset: String,
+        /// Why it failed.
+        reason:  String,
+    },
+    /// Anything else.
+    Other(String),
+}
+
+impl fmt::Display for ImportWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ImportWarning::DevicesUnavailable(msg) | ImportWarning::Other(msg) => f.write_str(msg),
+            ImportWarning::MountFailed { dataset, reason } => {
+                write!(f, "cannot mount '{}': {}", dataset, reason)
+            },
+            ImportWarning::ShareFailed { dataset, reason } => {
+                write!(f, "cannot share '{}': {}", dataset, reason)
+            },
+        }
+    }
+}
+
+/// `cannot <verb> 'dataset': reason` split into dataset and reason.
+fn cannot<'a>(line: &'a str, verb: &str) -> Option<(&'a str, &'a str)> {
+    let rest = line.strip_prefix("cannot ")?.strip_prefix(verb)?.strip_prefix(" '")?;
+    let end = rest.find("': ")?;
+    Some((&rest[..end], &rest[end + 3..]))
+}
+
+impl ImportWarning {
+    /// Parse one line of output.
+    fn parse(line: &str) -> ImportWarning {
+        if let Some((dataset, reason)) = cannot(line, "mount") {
+            return ImportWarning::MountFailed {
+                dataset: dataset.to_string(),
+                reason:  reason.to_string(),
+            };
+        }
+        if let Some((dataset, reason)) = cannot(line, "share") {
+            return ImportWarning::ShareFailed {
+                dataset: dataset.to_string(),
+                reason:  reason.to_string(),
+            };
+        }
+        let lower = line.to_lowercase();
+        if lower.contains("unavailable") || lower.contains("missing") {
+            ImportWarning::DevicesUnavailable(line.to_string())
+        } else {
+            ImportWarning::Other(line.to_string())
+        }
+    }
+
+    /// Collect warnings from stdout and stderr of a successful `zpool import`. Every non-empty
+    /// line is a warning.
+    pub fn from_output(stdout: &[u8], stderr: &[u8]) -> Vec<ImportWarning> {
+        let stdout = String::from_utf8_lossy(stdout);
+        let stderr = String::from_utf8_lossy(stderr);
+        stdout
+            .lines()
+            .chain(stderr.lines())
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(ImportWarning::parse)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parsing() {
+        let stderr = b"cannot mount '/tank/data': directory is not empty
+cannot share 'tank/nfs': smb add share failed
+
+one or more devices is currently unavailable
+";
+        let warnings = ImportWarning::from_output(b"", stderr);
+        assert_eq!(3, warnings.len());
+        assert_eq!(
+            ImportWarning::MountFailed {
+                dataset: String::from("/tank/data"),
+                reason:  String::from("directory is not empty"),
+            },
+            warnings[0]
+        );
+        assert_eq!("cannot share 'tank/nfs': smb add share failed", warnings[1].to_string());
+        assert_eq!(
+            ImportWarning::DevicesUnavailable(String::from(
+                "one or more devices is currently unavailable"
+            )),
+            warnings[2]
+        );
+
+        let warnings = ImportWarning::from_output(b"something odd\n", b"");
+        assert_eq!(vec![ImportWarning::Other(String::from("something odd"))], warnings);
+        assert!(ImportWarning::from_output(b"\n", b"").is_empty());
+    }
+
+    #[test]
+    fn request_args() {
+        assert_eq!(vec!["tank"], ImportRequest::new("tank").into_args().unwrap());
+
+        let request = ImportRequest::builder()
+            .name("tank")
+            .dir("/vdevs")
+            .force(true)
+            .readonly(true)
+            .altroot("/mnt")
+            .missing_log(true)
+            .recovery(Recovery::ExtremeRewind)
+            .property("cachefile", "none")
+            .destroyed(true)
+            .build()
+            .unwrap();
+        let expected = vec![
+            "-d", "/vdevs", "-D", "-f", "-m", "-F", "-X", "-R", "/mnt", "-o", "readonly=on", "-o",
+            "cachefile=none", "tank",
+        ];
+        assert_eq!(expected, request.into_args().unwrap());
+
+        let request = ImportRequest::builder().name("tank").property("-f", "x").build().unwrap();
+        assert!(request.into_args().is_err());
+
+        let request = ImportRequest::builder()
+            .name("tank")
+            .import_name(ImportName::Rename("tank2".into()))
+            .build()
+            .unwrap();
+        assert_eq!(vec!["tank", "tank2"], request.into_args().unwrap());
+
+        let request = ImportRequest::builder()
+            .name("12345")
+            .readonly(true)
+            .import_name(ImportName::Temporary("tank-copy".into()))
+            .build()
+            .unwrap();
+        let expected = vec!["-o", "readonly=on", "-t", "12345", "tank-copy"];
+        assert_eq!(expected, request.into_args().unwrap());
+    }
+}
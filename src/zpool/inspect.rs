@@ -0,0 +1,94 @@
+//! Identifying exported pools without importing them.
+//!
+//! Importing a pool just to find out what it is writes to it: hostid in labels, txgs, possibly
+//! replayed intent log. [`inspect_exported`](fn.inspect_exported.html) only reads. It combines
+//! what `zpool import` prints when it scans directories (name, GUID, state and members of every
+//! pool it finds) with device labels read by `zdb -l` (features needed to read the pool and host
+//! that used it last). Pools that are imported are not listed by the scan, so they are not
+//! reported.
+//!
+//! ### Usage
+//! ```rust,no_run
+//! use libzetta::zpool::{inspect::inspect_exported, ZpoolOpen3};
+//!
+//! let engine = ZpoolOpen3::default();
+//! for pool in inspect_exported(&engine, &["/dev/disk/by-id"]).unwrap() {
+//!     println!("{} {:?} {:?}: {:?}", pool.name(), pool.guid(), pool.health(), pool.devices());
+//! }
+//! ```
+use std::path::{Path, PathBuf};
+
+use crate::zpool::{hostid::HostInfo,
+                   label::{read_label, Label},
+                   Health, Zpool, ZpoolEngine, ZpoolResult};
+
+/// Pool found on devices, but not imported.
+#[derive(Debug, Clone, PartialEq, Eq, Getters)]
+#[get = "pub"]
+pub struct ExportedPool {
+    /// Name of the pool.
+    name:     String,
+    /// GUID of the pool.
+    guid:     Option<u64>,
+    /// State that import would most likely end up with, as `zpool import` guesses it.
+    health:   Health,
+    /// Every device of the pool that was found: vdevs, logs, caches and spares.
+    devices:  Vec<PathBuf>,
+    /// Features that must be supported to read the pool. Empty if no label could be read.
+    features: Vec<String>,
+    /// Host that used the pool last. `None` if no label could be read.
+    host:     Option<HostInfo>,
+}
+
+/// Paths of every device of the pool. Relative paths are resolved against the directory that was
+/// scanned.
+fn member_devices(pool: &Zpool, dir: &Path) -> Vec<PathBuf> {
+    let vdevs = pool.vdevs().iter().chain(pool.logs().iter());
+    let disks = vdevs
+        .flat_map(|vdev| vdev.disks().iter())
+        .chain(pool.caches().iter())
+        .chain(pool.spares().iter());
+    disks.map(|disk| dir.join(disk.path())).collect()
+}
+
+/// First label that `zdb -l` can read off the devices.
+fn first_label(devices: &[PathBuf]) -> Option<Label> {
+    devices.iter().find_map(|device| read_label(device).ok())
+}
+
+/// Find pools that can be imported from devices in the directories and describe them without
+/// importing. Pool that is found in more than one directory is reported once.
+///
+/// * `engine` - Engine to scan with.
+/// * `device_dirs` - Directories to look for devices in, e.g. `/dev` or `/dev/disk/by-id`.
+pub fn inspect_exported<E: ZpoolEngine, P: AsRef<Path>>(
+    engine: &E,
+    device_dirs: &[P],
+) -> ZpoolResult<Vec<ExportedPool>> {
+    let mut found: Vec<ExportedPool> = Vec::new();
+    for dir in device_dirs {
+        let dir = dir.as_ref();
+        for pool in engine.available_in_dir(dir.to_path_buf())? {
+            let guid = *pool.id();
+            let known = found.iter().any(|other| match (guid, other.guid) {
+                (Some(guid), Some(other)) => guid == other,
+                _ => pool.name() == &other.name,
+            });
+            if known {
+                continue;
+            }
+            let devices = member_devices(&pool, dir);
+            let label = first_label(&devices);
+            let features = label.as_ref().map(|label| label.features_for_read().clone());
+            found.push(ExportedPool {
+                name:     pool.name().clone(),
+                guid,
+                health:   pool.health().clone(),
+                devices,
+                features: features.unwrap_or_default(),
+                host:     label.as_ref().map(Label::host),
+            });
+        }
+    }
+    Ok(found)
+}
@@ -0,0 +1,950 @@
+//! I/O statistics for pools and vdevs.
+//!
+//! [`IoStat`](struct.IoStat.html) is a typed line of `zpool iostat -Hp`, see
+//! [`ZpoolEngine::iostat`](../trait.ZpoolEngine.html#tymethod.iostat). For continuous sampling
+//! [`ZpoolOpen3::iostat_stream`](../struct.ZpoolOpen3.html#method.iostat_stream) keeps
+//! `zpool iostat` running and yields a sample every interval.
+//! [`PoolIoStat`](struct.PoolIoStat.html) breaks statistics down per vdev and per device, which
+//! helps to spot a single slow disk. [`IoStatExt`](struct.IoStatExt.html) adds latencies (`-l`)
+//! and queue depths (`-q`), [`LatencyHistogram`](struct.LatencyHistogram.html) is the output of
+//! `-w`.
+//!
+//! [`IoAggregator`](struct.IoAggregator.html) keeps rolling 1, 5 and 15 minute averages per vdev.
+//! It is fed with cumulative counters (as found in kstats or `zpool iostat` totals) and takes
+//! care of turning them into rates: computing deltas between samples, ignoring the first sample
+//! of a new device, resetting baseline when counters go backwards (pool re-import, device
+//! replacement) and forgetting devices that are gone.
+//!
+//! ### Usage
+//! ```rust
+//! use libzetta::zpool::iostat::{IoAggregator, IoCounters};
+//! use std::time::{Duration, Instant};
+//!
+//! let mut aggregator = IoAggregator::new();
+//! let start = Instant::now();
+//! aggregator.record(start, "sda", IoCounters::new(100, 10, 4096, 1024));
+//! aggregator.record(start + Duration::from_secs(10), "sda", IoCounters::new(200, 20, 8192, 2048));
+//!
+//! let averages = aggregator.averages("sda").unwrap();
+//! assert_eq!(10.0, averages.one().read_ops());
+//! ```
+use std::{collections::{HashMap, HashSet, VecDeque},
+          io::{BufRead, BufReader, Lines, Read},
+          path::Path,
+          process::{Child, ChildStdout},
+          time::{Duration, Instant}};
+
+use crate::zpool::{Disk, Vdev, VdevType, Zpool, ZpoolError, ZpoolResult};
+
+/// Length of the shortest window.
+pub const ONE_MINUTE: Duration = Duration::from_secs(60);
+/// Length of the middle window.
+pub const FIVE_MINUTES: Duration = Duration::from_secs(5 * 60);
+/// Length of the longest window. Nothing older than this is kept.
+pub const FIFTEEN_MINUTES: Duration = Duration::from_secs(15 * 60);
+
+/// Cumulative I/O counters of a single device or vdev.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, CopyGetters)]
+#[get_copy = "pub"]
+pub struct IoCounters {
+    /// Number of read operations.
+    read_ops:    u64,
+    /// Number of write operations.
+    write_ops:   u64,
+    /// Number of bytes read.
+    read_bytes:  u64,
+    /// Number of bytes written.
+    write_bytes: u64,
+}
+
+impl IoCounters {
+    /// Create new set of counters.
+    pub fn new(read_ops: u64, write_ops: u64, read_bytes: u64, write_bytes: u64) -> IoCounters {
+        IoCounters { read_ops, write_ops, read_bytes, write_bytes }
+    }
+
+    /// Difference between two samples. `None` if any counter went backwards.
+    fn delta(self, previous: IoCounters) -> Option<IoCounters> {
+        Some(IoCounters {
+            read_ops:    self.read_ops.checked_sub(previous.read_ops)?,
+            write_ops:   self.write_ops.checked_sub(previous.write_ops)?,
+            read_bytes:  self.read_bytes.checked_sub(previous.read_bytes)?,
+            write_bytes: self.write_bytes.checked_sub(previous.write_bytes)?,
+        })
+    }
+}
+
+/// Average rates per second.
+#[derive(Debug, Clone, Copy, Default, PartialEq, CopyGetters)]
+#[get_copy = "pub"]
+pub struct IoRates {
+    /// Read operations per second.
+    read_ops:    f64,
+    /// Write operations per second.
+    write_ops:   f64,
+    /// Bytes read per second.
+    read_bytes:  f64,
+    /// Bytes written per second.
+    write_bytes: f64,
+}
+
+/// Rolling averages of a single device.
+#[derive(Debug, Clone, Copy, PartialEq, CopyGetters)]
+#[get_copy = "pub"]
+pub struct IoAverages {
+    /// Average over the last minute.
+    one:     IoRates,
+    /// Average over the last 5 minutes.
+    five:    IoRates,
+    /// Average over the last 15 minutes.
+    fifteen: IoRates,
+}
+
+/// Statistics of a pool or vdev as reported by `zpool iostat -Hp`. Without an interval `zpool`
+/// reports averages since the pool was imported.
+#[derive(Debug, Clone, PartialEq, Eq, Getters)]
+#[get = "pub"]
+pub struct IoStat {
+    /// Name of the pool or vdev.
+    name:        String,
+    /// Allocated space. `None` if not applicable (e.g. for some vdevs).
+    alloc:       Option<u64>,
+    /// Free space. `None` if not applicable.
+    free:        Option<u64>,
+    /// Read operations per second.
+    read_ops:    u64,
+    /// Write operations per second.
+    write_ops:   u64,
+    /// Bytes read per second.
+    read_bytes:  u64,
+    /// Bytes written per second.
+    write_bytes: u64,
+}
+
+impl IoStat {
+    /// Parse a single line of `zpool iostat -Hp`.
+    pub fn parse_line(line: &str) -> ZpoolResult<IoStat> {
+        let mut cols = line.trim_end().split('\t');
+        let mut next = || cols.next().ok_or(ZpoolError::ParseError);
+        let name = next()?.trim().to_string();
+        let alloc = parse_optional(next()?)?;
+        let free = parse_optional(next()?)?;
+        Ok(IoStat {
+            name,
+            alloc,
+            free,
+            read_ops: next()?.parse()?,
+            write_ops: next()?.parse()?,
+            read_bytes: next()?.parse()?,
+            write_bytes: next()?.parse()?,
+        })
+    }
+
+    /// Parse output of `zpool iostat -Hp`. Empty lines are skipped.
+    pub fn parse_stdout(stdout: &str) -> ZpoolResult<Vec<IoStat>> {
+        stdout.lines().filter(|line| !line.trim().is_empty()).map(IoStat::parse_line).collect()
+    }
+
+    /// Same numbers as `IoRates`.
+    // There is no lossless conversion from u64 to f64, rates don't need that precision anyway.
+    #[allow(clippy::as_conversions)]
+    pub fn rates(&self) -> IoRates {
+        IoRates {
+            read_ops:    self.read_ops as f64,
+            write_ops:   self.write_ops as f64,
+            read_bytes:  self.read_bytes as f64,
+            write_bytes: self.write_bytes as f64,
+        }
+    }
+}
+
+/// Error of a finished `zpool` that streams its output. `None` if it exited successfully.
+pub(crate) fn exit_error(child: &mut Child) -> Option<ZpoolError> {
+    let status = match child.wait() {
+        Ok(status) => status,
+        Err(e) => return Some(e.into()),
+    };
+    if status.success() {
+        return None;
+    }
+    let mut stderr = Vec::new();
+    if let Some(mut err) = child.stderr.take() {
+        if let Err(e) = err.read_to_end(&mut stderr) {
+            return Some(e.into());
+        }
+    }
+    Some(ZpoolError::from_stderr(&stderr))
+}
+
+/// Samples of `zpool iostat -Hp -y <interval>` as they are printed. Child process is killed when
+/// stream is dropped. Stream ends when `zpool` exits, if it exited with an error (e.g. pool was
+/// exported) that error is the last item.
+pub struct IoStatStream {
+    child: Child,
+    lines: Lines<BufReader<ChildStdout>>,
+    done:  bool,
+}
+
+impl IoStatStream {
+    /// Wrap a spawned `zpool iostat` with piped `stdout` and `stderr`.
+    pub(crate) fn new(mut child: Child) -> ZpoolResult<IoStatStream> {
+        let stdout = child
+            .stdout
+            .take()
+            .ok_or_else(|| ZpoolError::Other(String::from("stdout of zpool is not piped")))?;
+        Ok(IoStatStream { child, lines: BufReader::new(stdout).lines(), done: false })
+    }
+
+    /// Stop `zpool iostat`. Same as dropping the stream, but reports errors.
+    pub fn stop(mut self) -> ZpoolResult<()> {
+        self.done = true;
+        self.child.kill()?;
+        self.child.wait()?;
+        Ok(())
+    }
+}
+
+impl Iterator for IoStatStream {
+    type Item = ZpoolResult<IoStat>;
+
+    fn next(&mut self) -> Option<ZpoolResult<IoStat>> {
+        while !self.done {
+            match self.lines.next() {
+                Some(Ok(line)) => {
+                    if !line.trim().is_empty() {
+                        return Some(IoStat::parse_line(&line));
+                    }
+                },
+                Some(Err(e)) => {
+                    self.done = true;
+                    return Some(Err(e.into()));
+                },
+                None => {
+                    self.done = true;
+                    return exit_error(&mut self.child).map(Err);
+                },
+            }
+        }
+        None
+    }
+}
+
+impl Drop for IoStatStream {
+    fn drop(&mut self) {
+        // Child might have exited already, nothing to do about errors here.
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// Statistics of a top-level vdev and its devices.
+#[derive(Debug, Clone, PartialEq, Eq, Getters)]
+#[get = "pub"]
+pub struct VdevIoStat {
+    /// Statistics of the vdev itself.
+    vdev:  IoStat,
+    /// Statistics of every device of the vdev. For single disk vdevs it's the same as `vdev`.
+    /// Devices under `spare-N` and `replacing-N` take the place of the node.
+    disks: Vec<IoStat>,
+}
+
+/// Statistics of a pool broken down per vdev, as reported by `zpool iostat -Hpv`.
+#[derive(Debug, Clone, PartialEq, Eq, Getters)]
+#[get = "pub"]
+pub struct PoolIoStat {
+    /// Statistics of the whole pool.
+    pool:    IoStat,
+    /// Data vdevs in the same order as in [`Zpool::vdevs`](../struct.Zpool.html).
+    vdevs:   Vec<VdevIoStat>,
+    /// Dedup vdevs in the same order as in `Zpool::dedup`.
+    dedup:   Vec<VdevIoStat>,
+    /// Special vdevs in the same order as in `Zpool::special`.
+    special: Vec<VdevIoStat>,
+    /// Log vdevs in the same order as in `Zpool::logs`.
+    logs:    Vec<VdevIoStat>,
+    /// Cache devices in the same order as in `Zpool::caches`.
+    caches:  Vec<IoStat>,
+}
+
+impl PoolIoStat {
+    /// Match output of `zpool iostat -Hpv` against the vdev tree of the pool. `zpool` doesn't
+    /// indent lines in scripted mode, so the tree has to come from
+    /// [`status`](../trait.ZpoolEngine.html#tymethod.status). Fails if they don't match, for
+    /// example if a device was added in between.
+    ///
+    /// * `zpool` - Status of the pool.
+    /// * `stdout` - Output of `zpool iostat -Hpv <pool>`.
+    pub fn from_stdout(zpool: &Zpool, stdout: &str) -> ZpoolResult<PoolIoStat> {
+        // Class headers (`logs`, `cache`, ...) have no numbers at all.
+        let mut stats = stdout
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .filter(|line| line.split('\t').nth(3).map_or(false, |ops| ops != "-"))
+            .map(IoStat::parse_line)
+            .collect::<ZpoolResult<Vec<IoStat>>>()?
+            .into_iter();
+
+        let pool = stats.next().ok_or(ZpoolError::ParseError)?;
+        if pool.name() != zpool.name() {
+            return Err(ZpoolError::ParseError);
+        }
+        // Same order as `zpool iostat -v` prints allocation classes.
+        let vdevs = match_vdevs(zpool.vdevs(), &mut stats)?;
+        let dedup = match_vdevs(zpool.dedup(), &mut stats)?;
+        let special = match_vdevs(zpool.special(), &mut stats)?;
+        let logs = match_vdevs(zpool.logs(), &mut stats)?;
+        let mut caches = Vec::with_capacity(zpool.caches().len());
+        for disk in zpool.caches() {
+            match_disk(disk, &mut stats, &mut caches)?;
+        }
+        // Anything left is a device the status doesn't know about.
+        if stats.next().is_some() {
+            return Err(ZpoolError::ParseError);
+        }
+        Ok(PoolIoStat { pool, vdevs, dedup, special, logs, caches })
+    }
+
+    /// Statistics of every device of data, dedup, special and log vdevs, and of cache devices.
+    pub fn disks(&self) -> Vec<&IoStat> {
+        self.vdevs
+            .iter()
+            .chain(self.dedup.iter())
+            .chain(self.special.iter())
+            .chain(self.logs.iter())
+            .flat_map(|vdev| vdev.disks.iter())
+            .chain(self.caches.iter())
+            .collect()
+    }
+
+    /// Statistics of a single device.
+    pub fn disk<P: AsRef<Path>>(&self, path: P) -> Option<&IoStat> {
+        self.disks().into_iter().find(|stat| Path::new(stat.name()) == path.as_ref())
+    }
+}
+
+fn next_named<I: Iterator<Item = IoStat>>(stats: &mut I, path: &Path) -> ZpoolResult<IoStat> {
+    let stat = stats.next().ok_or(ZpoolError::ParseError)?;
+    if Path::new(stat.name()) == path {
+        Ok(stat)
+    } else {
+        Err(ZpoolError::ParseError)
+    }
+}
+
+/// Match the line of a device and lines of devices under it (`spare-N`, `replacing-N`). Leaf
+/// devices are pushed to `leaves`, statistics of the device itself are returned.
+fn match_disk<I: Iterator<Item = IoStat>>(
+    disk: &Disk,
+    stats: &mut I,
+    leaves: &mut Vec<IoStat>,
+) -> ZpoolResult<IoStat> {
+    let stat = next_named(stats, disk.path())?;
+    if disk.children().is_empty() {
+        leaves.push(stat.clone());
+    }
+    for child in disk.children() {
+        match_disk(child, stats, leaves)?;
+    }
+    Ok(stat)
+}
+
+fn match_vdevs<I: Iterator<Item = IoStat>>(
+    vdevs: &[Vdev],
+    stats: &mut I,
+) -> ZpoolResult<Vec<VdevIoStat>> {
+    let mut ret = Vec::with_capacity(vdevs.len());
+    for vdev in vdevs {
+        let mut disks = Vec::new();
+        if vdev.kind() == &VdevType::SingleDisk {
+            let disk = vdev.disks().first().ok_or(ZpoolError::ParseError)?;
+            let stat = match_disk(disk, stats, &mut disks)?;
+            ret.push(VdevIoStat { vdev: stat, disks });
+        } else {
+            // Lines of top-level vdevs are named `<type>-<index>`, e.g. `mirror-0`.
+            let stat = stats.next().ok_or(ZpoolError::ParseError)?;
+            let kind = stat.name().rsplitn(2, '-').nth(1).map(str::parse::<VdevType>);
+            match kind {
+                Some(Ok(ref kind)) if kind == vdev.kind() => {},
+                _ => return Err(ZpoolError::ParseError),
+            }
+            for disk in vdev.disks() {
+                match_disk(disk, stats, &mut disks)?;
+            }
+            ret.push(VdevIoStat { vdev: stat, disks });
+        }
+    }
+    Ok(ret)
+}
+
+fn parse_optional(value: &str) -> ZpoolResult<Option<u64>> {
+    match value {
+        "-" => Ok(None),
+        v => Ok(Some(v.parse()?)),
+    }
+}
+
+/// A pair of read and write values. `None` where `zpool` printed `-`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, CopyGetters)]
+#[get_copy = "pub"]
+pub struct ReadWrite {
+    /// Value for reads.
+    read:  Option<u64>,
+    /// Value for writes.
+    write: Option<u64>,
+}
+
+/// Wait times split by where I/O was waiting. Used for both average latencies in nanoseconds
+/// (`-l`) and number of I/Os in a histogram bucket (`-w`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, CopyGetters)]
+#[get_copy = "pub"]
+pub struct Waits {
+    /// Total time I/O spent, queued and on disk.
+    total_wait:  ReadWrite,
+    /// Time I/O spent on disk.
+    disk_wait:   ReadWrite,
+    /// Time I/O spent in sync queues.
+    syncq_wait:  ReadWrite,
+    /// Time I/O spent in async queues.
+    asyncq_wait: ReadWrite,
+    /// Time scrub I/O spent in queue.
+    scrub_wait:  Option<u64>,
+    /// Time trim I/O spent in queue. `None` on platforms without trim.
+    trim_wait:   Option<u64>,
+}
+
+/// Number of I/Os waiting in a queue and being issued to disk.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, CopyGetters)]
+#[get_copy = "pub"]
+pub struct QueueDepth {
+    /// I/Os waiting in queue.
+    pending: Option<u64>,
+    /// I/Os issued to disk.
+    active:  Option<u64>,
+}
+
+/// Queue depths as reported by `zpool iostat -q`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, CopyGetters)]
+#[get_copy = "pub"]
+pub struct Queues {
+    /// Sync reads.
+    sync_read:   QueueDepth,
+    /// Sync writes.
+    sync_write:  QueueDepth,
+    /// Async reads.
+    async_read:  QueueDepth,
+    /// Async writes.
+    async_write: QueueDepth,
+    /// Scrub reads.
+    scrub_read:  QueueDepth,
+    /// Trim writes. `None` on platforms without trim.
+    trim_write:  Option<QueueDepth>,
+}
+
+/// Line of `zpool iostat -Hp` with latencies (`-l`) and/or queue depths (`-q`).
+#[derive(Debug, Clone, PartialEq, Eq, Getters)]
+#[get = "pub"]
+pub struct IoStatExt {
+    /// Basic statistics.
+    stat:    IoStat,
+    /// Average latencies in nanoseconds, if requested.
+    latency: Option<Waits>,
+    /// Queue depths, if requested.
+    queues:  Option<Queues>,
+}
+
+impl IoStatExt {
+    /// Parse a single line. Column layout depends on what was requested and whether platform
+    /// supports trim, so both flags must match the command line.
+    ///
+    /// * `line` - Line of `zpool iostat -Hp [-l] [-q]`.
+    /// * `latency` - `-l` was passed.
+    /// * `queues` - `-q` was passed.
+    pub fn parse_line(line: &str, latency: bool, queues: bool) -> ZpoolResult<IoStatExt> {
+        let cols: Vec<&str> = line.trim_end().split('\t').collect();
+        if cols.len() < 7 {
+            return Err(ZpoolError::ParseError);
+        }
+        let stat = IoStat::parse_line(&cols[..7].join("\t"))?;
+        let extra = &cols[7..];
+        // Without trim latency has 9 columns and queues have 10, with trim 10 and 12.
+        let trim = match (latency, queues, extra.len()) {
+            (true, false, 9) | (false, true, 10) | (true, true, 19) | (false, false, 0) => false,
+            (true, false, 10) | (false, true, 12) | (true, true, 22) => true,
+            _ => return Err(ZpoolError::ParseError),
+        };
+        let mut values = extra.iter().map(|v| parse_optional(v));
+        let latency = if latency { Some(parse_waits(&mut values, trim)?) } else { None };
+        let queues = if queues {
+            let mut next = || -> ZpoolResult<QueueDepth> {
+                let pending = next_value(&mut values)?;
+                Ok(QueueDepth { pending, active: next_value(&mut values)? })
+            };
+            Some(Queues {
+                sync_read:   next()?,
+                sync_write:  next()?,
+                async_read:  next()?,
+                async_write: next()?,
+                scrub_read:  next()?,
+                trim_write:  if trim { Some(next()?) } else { None },
+            })
+        } else {
+            None
+        };
+        Ok(IoStatExt { stat, latency, queues })
+    }
+
+    /// Parse output of `zpool iostat -Hp [-v] [-l] [-q]`. Class headers (`logs`, `cache`, ...)
+    /// are skipped.
+    pub fn parse_stdout(stdout: &str, latency: bool, queues: bool) -> ZpoolResult<Vec<IoStatExt>> {
+        stdout
+            .lines()
+            .filter(|line| line.split('\t').nth(3).map_or(false, |ops| ops != "-"))
+            .map(|line| IoStatExt::parse_line(line, latency, queues))
+            .collect()
+    }
+}
+
+/// Single row of a latency histogram.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, CopyGetters)]
+#[get_copy = "pub"]
+pub struct HistogramBucket {
+    /// Upper bound of the bucket in nanoseconds.
+    upper_bound: u64,
+    /// Number of I/Os that fell into this bucket.
+    counts:      Waits,
+}
+
+/// Latency histogram of a pool or vdev as reported by `zpool iostat -Hpw`.
+#[derive(Debug, Clone, PartialEq, Eq, Getters)]
+#[get = "pub"]
+pub struct LatencyHistogram {
+    /// Name of the pool or vdev.
+    name:    String,
+    /// Buckets from fastest to slowest.
+    buckets: Vec<HistogramBucket>,
+}
+
+impl LatencyHistogram {
+    /// Parse output of `zpool iostat -Hpw [-v]`.
+    pub fn parse_stdout(stdout: &str) -> ZpoolResult<Vec<LatencyHistogram>> {
+        let mut histograms: Vec<LatencyHistogram> = Vec::new();
+        for line in stdout.lines().map(str::trim_end).filter(|line| !line.is_empty()) {
+            let mut cols = line.split('\t');
+            let first = cols.next().ok_or(ZpoolError::ParseError)?;
+            let rest: Vec<&str> = cols.collect();
+            if rest.is_empty() {
+                histograms.push(LatencyHistogram { name: first.into(), buckets: Vec::new() });
+                continue;
+            }
+            let trim = match rest.len() {
+                9 => false,
+                10 => true,
+                _ => return Err(ZpoolError::ParseError),
+            };
+            let mut values = rest.iter().map(|v| parse_optional(v));
+            let counts = parse_waits(&mut values, trim)?;
+            let bucket = HistogramBucket { upper_bound: first.parse()?, counts };
+            histograms.last_mut().ok_or(ZpoolError::ParseError)?.buckets.push(bucket);
+        }
+        Ok(histograms)
+    }
+}
+
+fn next_value<I: Iterator<Item = ZpoolResult<Option<u64>>>>(
+    values: &mut I,
+) -> ZpoolResult<Option<u64>> {
+    values.next().ok_or(ZpoolError::ParseError)?
+}
+
+fn parse_waits<I: Iterator<Item = ZpoolResult<Option<u64>>>>(
+    values: &mut I,
+    trim: bool,
+) -> ZpoolResult<Waits> {
+    let mut pair = || -> ZpoolResult<ReadWrite> {
+        Ok(ReadWrite { read: next_value(values)?, write: next_value(values)? })
+    };
+    Ok(Waits {
+        total_wait:  pair()?,
+        disk_wait:   pair()?,
+        syncq_wait:  pair()?,
+        asyncq_wait: pair()?,
+        scrub_wait:  next_value(values)?,
+        trim_wait:   if trim { next_value(values)? } else { None },
+    })
+}
+
+#[derive(Debug, Clone)]
+struct Interval {
+    end:      Instant,
+    duration: Duration,
+    delta:    IoCounters,
+}
+
+#[derive(Debug, Clone)]
+struct DeviceHistory {
+    last_seen: Instant,
+    last:      IoCounters,
+    intervals: VecDeque<Interval>,
+}
+
+impl DeviceHistory {
+    #[allow(clippy::as_conversions, clippy::float_arithmetic)]
+    fn rates(&self, now: Instant, window: Duration) -> IoRates {
+        let mut total = IoCounters::default();
+        let mut duration = Duration::from_secs(0);
+        for interval in self.intervals.iter().rev() {
+            if now.duration_since(interval.end) >= window {
+                break;
+            }
+            total.read_ops += interval.delta.read_ops;
+            total.write_ops += interval.delta.write_ops;
+            total.read_bytes += interval.delta.read_bytes;
+            total.write_bytes += interval.delta.write_bytes;
+            duration += interval.duration;
+        }
+        let secs = duration.as_secs_f64();
+        if secs == 0.0 {
+            return IoRates::default();
+        }
+        IoRates {
+            read_ops:    total.read_ops as f64 / secs,
+            write_ops:   total.write_ops as f64 / secs,
+            read_bytes:  total.read_bytes as f64 / secs,
+            write_bytes: total.write_bytes as f64 / secs,
+        }
+    }
+}
+
+/// In-memory aggregator of I/O counters. Keeps up to 15 minutes of history per device.
+#[derive(Debug, Clone, Default)]
+pub struct IoAggregator {
+    devices: HashMap<String, DeviceHistory>,
+}
+
+impl IoAggregator {
+    /// Create an empty aggregator.
+    pub fn new() -> IoAggregator { IoAggregator::default() }
+
+    /// Record a sample for a single device. First sample of a device only sets a baseline.
+    ///
+    /// * `at` - When sample was taken. Samples must be recorded in order.
+    /// * `device` - Name of the device or vdev.
+    /// * `counters` - Cumulative counters of the device.
+    pub fn record<D: AsRef<str>>(&mut self, at: Instant, device: D, counters: IoCounters) {
+        let history = self.devices.entry(device.as_ref().to_owned()).or_insert_with(|| {
+            DeviceHistory { last_seen: at, last: counters, intervals: VecDeque::new() }
+        });
+
+        if at > history.last_seen {
+            match counters.delta(history.last) {
+                Some(delta) => history.intervals.push_back(Interval {
+                    end:      at,
+                    duration: at.duration_since(history.last_seen),
+                    delta,
+                }),
+                // Counters were reset, old history says nothing about the new baseline.
+                None => history.intervals.clear(),
+            }
+        }
+        history.last_seen = at;
+        history.last = counters;
+
+        while history
+            .intervals
+            .front()
+            .map_or(false, |interval| at.duration_since(interval.end) >= FIFTEEN_MINUTES)
+        {
+            history.intervals.pop_front();
+        }
+    }
+
+    /// Record a sample that covers every device of the pool. Devices that are missing from the
+    /// sample are considered gone and their history is dropped.
+    ///
+    /// * `at` - When sample was taken.
+    /// * `sample` - Device names with their cumulative counters.
+    pub fn record_all<I, D>(&mut self, at: Instant, sample: I)
+    where
+        I: IntoIterator<Item = (D, IoCounters)>,
+        D: AsRef<str>,
+    {
+        let mut seen = HashSet::new();
+        for (device, counters) in sample {
+            seen.insert(device.as_ref().to_owned());
+            self.record(at, device, counters);
+        }
+        self.devices.retain(|device, _| seen.contains(device));
+    }
+
+    /// Drop history of the device.
+    pub fn forget<D: AsRef<str>>(&mut self, device: D) { self.devices.remove(device.as_ref()); }
+
+    /// Names of the devices aggregator knows about.
+    pub fn devices(&self) -> Vec<&str> { self.devices.keys().map(String::as_str).collect() }
+
+    /// Rolling averages of the device, relative to its last sample. `None` if device is unknown.
+    pub fn averages<D: AsRef<str>>(&self, device: D) -> Option<IoAverages> {
+        self.devices.get(device.as_ref()).map(|history| {
+            let now = history.last_seen;
+            IoAverages {
+                one:     history.rates(now, ONE_MINUTE),
+                five:    history.rates(now, FIVE_MINUTES),
+                fifteen: history.rates(now, FIFTEEN_MINUTES),
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::zpool::ZpoolErrorKind;
+    use std::process::{Command, Stdio};
+
+    fn secs(n: u64) -> Duration { Duration::from_secs(n) }
+
+    #[test]
+    fn windows() {
+        let mut aggregator = IoAggregator::new();
+        let start = Instant::now();
+        aggregator.record(start, "sda", IoCounters::new(0, 0, 0, 0));
+        assert_eq!(0.0, aggregator.averages("sda").unwrap().one().read_ops());
+
+        // 10 minutes of 1 op/s, then 1 minute of 10 op/s.
+        aggregator.record(start + secs(600), "sda", IoCounters::new(600, 0, 0, 0));
+        aggregator.record(start + secs(660), "sda", IoCounters::new(1200, 60, 0, 0));
+
+        let averages = aggregator.averages("sda").unwrap();
+        assert_eq!(10.0, averages.one().read_ops());
+        assert_eq!(1.0, averages.one().write_ops());
+        assert_eq!(1200.0 / 660.0, averages.fifteen().read_ops());
+        assert!(aggregator.averages("sdb").is_none());
+    }
+
+    #[test]
+    fn counter_reset_and_expiry() {
+        let mut aggregator = IoAggregator::new();
+        let start = Instant::now();
+        aggregator.record(start, "sda", IoCounters::new(1000, 0, 0, 0));
+        aggregator.record(start + secs(10), "sda", IoCounters::new(1100, 0, 0, 0));
+        // Counters went backwards, so this sample is a new baseline.
+        aggregator.record(start + secs(20), "sda", IoCounters::new(5, 0, 0, 0));
+        assert_eq!(0.0, aggregator.averages("sda").unwrap().fifteen().read_ops());
+
+        aggregator.record(start + secs(30), "sda", IoCounters::new(105, 0, 0, 0));
+        assert_eq!(10.0, aggregator.averages("sda").unwrap().one().read_ops());
+
+        aggregator.record(start + secs(30 + 15 * 60), "sda", IoCounters::new(105, 0, 0, 0));
+        assert_eq!(0.0, aggregator.averages("sda").unwrap().fifteen().read_ops());
+    }
+
+    #[test]
+    fn parsing_iostat() {
+        let stdout = "tank\t1161216\t10736256000\t0\t3\t1467\t40517\n";
+        let stats = IoStat::parse_stdout(stdout).unwrap();
+        assert_eq!(1, stats.len());
+        let stat = &stats[0];
+        assert_eq!("tank", stat.name());
+        assert_eq!(&Some(1_161_216), stat.alloc());
+        assert_eq!(&Some(10_736_256_000), stat.free());
+        assert_eq!(&0, stat.read_ops());
+        assert_eq!(&3, stat.write_ops());
+        assert_eq!(&1467, stat.read_bytes());
+        assert_eq!(&40517, stat.write_bytes());
+        assert_eq!(3.0, stat.rates().write_ops());
+
+        let stat = IoStat::parse_line("sdb\t-\t-\t1\t2\t3\t4").unwrap();
+        assert_eq!(&None, stat.alloc());
+
+        assert!(IoStat::parse_line("tank\t1\t2\t3").is_err());
+        assert!(IoStat::parse_line("tank\t1\t2\t3\t4\t5K\t6").is_err());
+    }
+
+    #[test]
+    fn per_vdev_iostat() {
+        let status = r#"  pool: tank
+ state: ONLINE
+  scan: none requested
+config:
+
+        NAME        STATE     READ WRITE CKSUM
+        tank        ONLINE       0     0     0
+          sdc       ONLINE       0     0     0
+          mirror-0  ONLINE       0     0     0
+            sda     ONLINE       0     0     0
+            sdb     ONLINE       0     0     0
+        logs
+          sdd       ONLINE       0     0     0
+        cache
+          sde       ONLINE       0     0     0
+
+errors: No known data errors
+"#;
+        let zpool = crate::parsers::parse_zpools(status).unwrap().remove(0);
+        let stdout = "tank\t300\t700\t6\t9\t600\t900\n\
+                      sdc\t100\t400\t2\t3\t200\t300\n\
+                      mirror-0\t200\t300\t4\t6\t400\t600\n\
+                      sda\t-\t-\t2\t3\t200\t300\n\
+                      sdb\t-\t-\t2\t3\t200\t300\n\
+                      logs\t-\t-\t-\t-\t-\t-\n\
+                      sdd\t0\t100\t0\t0\t0\t0\n\
+                      cache\t-\t-\t-\t-\t-\t-\n\
+                      sde\t50\t50\t90\t1\t9000\t100\n";
+
+        let stats = PoolIoStat::from_stdout(&zpool, stdout).unwrap();
+        assert_eq!("tank", stats.pool().name());
+        assert_eq!(2, stats.vdevs().len());
+        assert_eq!("sdc", stats.vdevs()[0].vdev().name());
+        assert_eq!("mirror-0", stats.vdevs()[1].vdev().name());
+        assert_eq!(2, stats.vdevs()[1].disks().len());
+        assert_eq!(1, stats.logs().len());
+        assert_eq!(5, stats.disks().len());
+        assert_eq!(&90, stats.disk("sde").unwrap().read_ops());
+        assert!(stats.disk("sdz").is_none());
+
+        // Device was added after status was taken.
+        let renamed = stdout.replace("sdc\t", "sdx\t");
+        assert!(PoolIoStat::from_stdout(&zpool, &renamed).is_err());
+        let added = format!("{}sdf\t50\t50\t90\t1\t9000\t100\n", stdout);
+        assert!(PoolIoStat::from_stdout(&zpool, &added).is_err());
+        let retyped = stdout.replace("mirror-0\t", "raidz1-0\t");
+        assert!(PoolIoStat::from_stdout(&zpool, &retyped).is_err());
+    }
+
+    #[test]
+    fn per_vdev_iostat_engaged_spare() {
+        let status = r#"  pool: tank
+ state: DEGRADED
+  scan: resilvered 1.5M in 00:00:01 with 0 errors on Sun Jan  1 00:00:00 2023
+config:
+
+        NAME         STATE     READ WRITE CKSUM
+        tank         DEGRADED     0     0     0
+          mirror-0   DEGRADED     0     0     0
+            sda      ONLINE       0     0     0
+            spare-1  DEGRADED     0     0     0
+              sdb    FAULTED      0     0     0  too many errors
+              sdc    ONLINE       0     0     0
+          sdd        ONLINE       0     0     0
+        spares
+          sdc        INUSE     currently in use
+
+errors: No known data errors
+"#;
+        let zpool = crate::parsers::parse_zpools(status).unwrap().remove(0);
+        let stdout = "tank\t300\t700\t6\t9\t600\t900\n\
+                      mirror-0\t200\t300\t4\t6\t400\t600\n\
+                      sda\t-\t-\t2\t3\t200\t300\n\
+                      spare-1\t-\t-\t2\t3\t200\t300\n\
+                      sdb\t-\t-\t0\t1\t0\t100\n\
+                      sdc\t-\t-\t2\t2\t200\t200\n\
+                      sdd\t100\t400\t2\t3\t200\t300\n";
+
+        let stats = PoolIoStat::from_stdout(&zpool, stdout).unwrap();
+        assert_eq!(2, stats.vdevs().len());
+        let names: Vec<&str> =
+            stats.vdevs()[0].disks().iter().map(|stat| stat.name().as_str()).collect();
+        assert_eq!(vec!["sda", "sdb", "sdc"], names);
+        assert_eq!("sdd", stats.vdevs()[1].vdev().name());
+        assert_eq!(&2, stats.disk("sdc").unwrap().write_ops());
+        assert!(stats.disk("spare-1").is_none());
+    }
+
+    #[test]
+    fn latency_and_queues() {
+        let stdout = "tank\t1\t2\t3\t4\t5\t6\t100\t200\t50\t60\t-\t-\t10\t20\t-\t-\n\
+                      logs\t-\t-\t-\t-\t-\t-\t-\t-\t-\t-\t-\t-\t-\t-\t-\t-\n";
+        let stats = IoStatExt::parse_stdout(stdout, true, false).unwrap();
+        assert_eq!(1, stats.len());
+        let latency = stats[0].latency().unwrap();
+        assert_eq!(Some(100), latency.total_wait().read());
+        assert_eq!(Some(60), latency.disk_wait().write());
+        assert_eq!(None, latency.syncq_wait().read());
+        assert_eq!(Some(20), latency.asyncq_wait().write());
+        assert_eq!(None, latency.scrub_wait());
+        assert_eq!(None, latency.trim_wait());
+        assert!(stats[0].queues().is_none());
+
+        // With trim: 10 latency columns and 12 queue columns.
+        let line = "sda\t-\t-\t3\t4\t5\t6\t1\t2\t3\t4\t5\t6\t7\t8\t9\t10\t\
+                    0\t1\t0\t2\t0\t3\t4\t5\t0\t0\t0\t6";
+        let stat = IoStatExt::parse_line(line, true, true).unwrap();
+        assert_eq!(Some(10), stat.latency().unwrap().trim_wait());
+        let queues = stat.queues().unwrap();
+        assert_eq!(Some(1), queues.sync_read().active());
+        assert_eq!(Some(4), queues.async_write().pending());
+        assert_eq!(Some(6), queues.trim_write().unwrap().active());
+
+        // Flags don't match the columns.
+        assert!(IoStatExt::parse_line(line, true, false).is_err());
+        assert!(IoStatExt::parse_line("tank\t1\t2\t3\t4\t5\t6", false, false).is_ok());
+    }
+
+    #[test]
+    fn histograms() {
+        let stdout = "tank\n\
+                      1\t0\t0\t0\t0\t0\t0\t0\t0\t0\t0\n\
+                      3\t0\t0\t0\t0\t0\t0\t0\t0\t0\t0\n\
+                      4095\t7\t12\t5\t10\t0\t1\t0\t3\t0\t0\n\
+                      \n\
+                      sda\n\
+                      1\t0\t0\t0\t0\t0\t0\t0\t0\t0\t0\n";
+        let histograms = LatencyHistogram::parse_stdout(stdout).unwrap();
+        assert_eq!(2, histograms.len());
+        assert_eq!("tank", histograms[0].name());
+        assert_eq!(3, histograms[0].buckets().len());
+        let bucket = histograms[0].buckets()[2];
+        assert_eq!(4095, bucket.upper_bound());
+        assert_eq!(Some(12), bucket.counts().total_wait().write());
+        assert_eq!(Some(3), bucket.counts().asyncq_wait().write());
+        assert_eq!(Some(0), bucket.counts().trim_wait());
+        assert_eq!("sda", histograms[1].name());
+
+        assert!(LatencyHistogram::parse_stdout("1\t0\t0\t0\t0\t0\t0\t0\t0\t0\n").is_err());
+    }
+
+    fn spawn(script: &str) -> Child {
+        Command::new("sh")
+            .arg("-c")
+            .arg(script)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .unwrap()
+    }
+
+    #[test]
+    fn streaming_iostat() {
+        let sample = "tank\\t1\\t2\\t3\\t4\\t5\\t6\\n";
+        let child = spawn(&format!("printf '{}\\n{}'", sample, sample.replace('3', "0")));
+        let stats: Vec<_> = IoStatStream::new(child).unwrap().collect();
+        assert_eq!(2, stats.len());
+        assert_eq!(&3, stats[0].as_ref().unwrap().read_ops());
+        assert_eq!(&0, stats[1].as_ref().unwrap().read_ops());
+
+        let child = spawn("echo \"cannot open 'tank': no such pool\" >&2; exit 1");
+        let mut stream = IoStatStream::new(child).unwrap();
+        assert_eq!(ZpoolErrorKind::PoolNotFound, stream.next().unwrap().unwrap_err().kind());
+        assert!(stream.next().is_none());
+
+        let child = spawn(&format!("while true; do printf '{}'; sleep 1; done", sample));
+        let mut stream = IoStatStream::new(child).unwrap();
+        assert!(stream.next().unwrap().is_ok());
+        stream.stop().unwrap();
+    }
+
+    #[test]
+    fn devices_come_and_go() {
+        let mut aggregator = IoAggregator::new();
+        let start = Instant::now();
+        let sample = vec![("sda", IoCounters::default()), ("sdb", IoCounters::default())];
+        aggregator.record_all(start, sample);
+        assert_eq!(2, aggregator.devices().len());
+
+        aggregator.record_all(start + secs(10), vec![("sda", IoCounters::default())]);
+        assert_eq!(vec!["sda"], aggregator.devices());
+
+        aggregator.forget("sda");
+        assert!(aggregator.devices().is_empty());
+    }
+}
@@ -0,0 +1,167 @@
+//! ZFS labels of pool devices.
+//!
+//! Every device of a pool carries a label that says which pool it belongs to, what state that pool
+//! was left in, which host used it last and which features are needed to read it. Labels can be
+//! read with `zdb -l` without importing the pool. [`read_label`](fn.read_label.html) does that
+//! and parses the output into a [`Label`](struct.Label.html).
+//!
+//! ### Usage
+//! ```rust,no_run
+//! use libzetta::zpool::label::read_label;
+//!
+//! let label = read_label("/dev/sdb1").unwrap();
+//! println!("{:?} {:?} {:?}", label.name(), label.pool_guid(), label.state());
+//! println!("last used by {}", label.host());
+//! ```
+use std::{path::Path, process::Command};
+
+use crate::zpool::{hostid::HostInfo, ZpoolError, ZpoolResult};
+
+/// State of the pool as recorded in the label (`pool_state_t`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LabelState {
+    /// Pool is in use by some host.
+    Active,
+    /// Pool was exported.
+    Exported,
+    /// Pool was destroyed.
+    Destroyed,
+    /// Device is a hot spare.
+    Spare,
+    /// Device is a cache device.
+    L2Cache,
+    /// Pool is being created.
+    Uninitialized,
+    /// Pool isn't available.
+    Unavailable,
+    /// Pool might be in use by some host.
+    PotentiallyActive,
+    /// State this library doesn't know about.
+    Unknown(u64),
+}
+
+impl From<u64> for LabelState {
+    fn from(state: u64) -> LabelState {
+        match state {
+            0 => LabelState::Active,
+            1 => LabelState::Exported,
+            2 => LabelState::Destroyed,
+            3 => LabelState::Spare,
+            4 => LabelState::L2Cache,
+            5 => LabelState::Uninitialized,
+            6 => LabelState::Unavailable,
+            7 => LabelState::PotentiallyActive,
+            other => LabelState::Unknown(other),
+        }
+    }
+}
+
+/// Label of a pool device as printed by `zdb -l`. Fields that are missing in the label are `None`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Getters)]
+#[get = "pub"]
+pub struct Label {
+    /// Name of the pool.
+    name:              Option<String>,
+    /// GUID of the pool.
+    pool_guid:         Option<u64>,
+    /// State of the pool.
+    state:             Option<LabelState>,
+    /// Hostid of the host that used the pool last.
+    hostid:            Option<u32>,
+    /// Hostname of the host that used the pool last.
+    hostname:          Option<String>,
+    /// Features that must be supported to read the pool.
+    features_for_read: Vec<String>,
+}
+
+impl Label {
+    /// Parse output of `zdb -l`. Only top-level fields are looked at, the vdev tree is skipped.
+    /// If labels on the device differ, the first one wins.
+    pub fn from_stdout(stdout: &str) -> Label {
+        let mut label = Label::default();
+        let mut top = None;
+        let mut in_features = false;
+        for line in stdout.lines() {
+            let depth = line.len() - line.trim_start().len();
+            let line = line.trim();
+            if in_features && top.map_or(false, |top| depth > top) && !line.is_empty() {
+                let feature = line.to_string();
+                if !label.features_for_read.contains(&feature) {
+                    label.features_for_read.push(feature);
+                }
+                continue;
+            }
+            in_features = false;
+
+            let mut kv = line.splitn(2, ':');
+            let (key, value) = match (kv.next(), kv.next()) {
+                (Some(key), Some(value)) => (key, value.trim()),
+                _ => continue,
+            };
+            if *top.get_or_insert(depth) != depth {
+                continue;
+            }
+            match key {
+                "name" if label.name.is_none() => label.name = Some(unquote(value)),
+                "pool_guid" if label.pool_guid.is_none() => label.pool_guid = value.parse().ok(),
+                "state" if label.state.is_none() => {
+                    label.state = value.parse::<u64>().ok().map(LabelState::from)
+                },
+                "hostid" if label.hostid.is_none() => label.hostid = value.parse().ok(),
+                "hostname" if label.hostname.is_none() => label.hostname = Some(unquote(value)),
+                "features_for_read" => in_features = true,
+                _ => {},
+            }
+        }
+        label
+    }
+
+    /// Host that used the pool last.
+    pub fn host(&self) -> HostInfo { HostInfo::new(self.hostname.clone(), self.hostid, false) }
+}
+
+fn unquote(value: &str) -> String { value.trim_matches('\'').to_string() }
+
+/// Read ZFS label on the device with `zdb -l`.
+///
+/// * `device` - Device that is part of the pool.
+pub fn read_label<P: AsRef<Path>>(device: P) -> ZpoolResult<Label> {
+    let out = Command::new("zdb").arg("-l").arg(device.as_ref()).output()?;
+    if !out.status.success() {
+        return Err(ZpoolError::Other(String::from_utf8_lossy(&out.stderr).into()));
+    }
+    Ok(Label::from_stdout(&String::from_utf8_lossy(&out.stdout)))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn label_parsing() {
+        let stdout = "------------------------------------\nLABEL 0\n\
+                      ------------------------------------\n    version: 5000\n    \
+                      name: 'tank'\n    state: 1\n    txg: 4\n    pool_guid: 1234\n    \
+                      hostid: 2831187970\n    hostname: 'storage-01'\n    top_guid: 5678\n    \
+                      vdev_tree:\n        type: 'disk'\n        guid: 5678\n        \
+                      path: '/dev/sdb1'\n    features_for_read:\n        \
+                      com.delphix:hole_birth\n        com.delphix:embedded_data\n    \
+                      labels = 0 1 2 3\n\
+                      ------------------------------------\nLABEL 2\n\
+                      ------------------------------------\n    name: 'old'\n    \
+                      pool_guid: 9999\n    features_for_read:\n        \
+                      com.delphix:hole_birth\n";
+        let label = Label::from_stdout(stdout);
+        assert_eq!(&Some(String::from("tank")), label.name());
+        assert_eq!(&Some(1234), label.pool_guid());
+        assert_eq!(&Some(LabelState::Exported), label.state());
+        assert_eq!(&Some(2_831_187_970), label.hostid());
+        assert_eq!(&Some(String::from("storage-01")), label.hostname());
+        let expected = vec!["com.delphix:hole_birth", "com.delphix:embedded_data"];
+        assert_eq!(&expected, label.features_for_read());
+        assert_eq!("storage-01 (hostid=a8c08002)", label.host().to_string());
+
+        assert_eq!(Label::default(), Label::from_stdout("failed to unpack label 0\n"));
+        assert_eq!(LabelState::Unknown(42), LabelState::from(42));
+    }
+}
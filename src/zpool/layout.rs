@@ -0,0 +1,170 @@
+//! Preview of a pool before it's created.
+//!
+//! [`ZpoolEngine::create_dry_run`](../trait.ZpoolEngine.html#tymethod.create_dry_run) runs
+//! `zpool create -n` - devices are checked, but nothing is written to them.
+//! [`PoolLayout`](struct.PoolLayout.html) is what `zpool` says it would have built.
+//!
+//! ### Usage
+//! ```rust,no_run
+//! use libzetta::zpool::{CreateVdevRequest, CreateZpoolRequest, ZpoolEngine, ZpoolOpen3};
+//!
+//! let engine = ZpoolOpen3::default();
+//! let request = CreateZpoolRequest::builder()
+//!     .name("tank")
+//!     .vdev(CreateVdevRequest::mirror(vec!["/dev/ada0", "/dev/ada1"]))
+//!     .build()
+//!     .unwrap();
+//! let layout = engine.create_dry_run(request).unwrap();
+//! for vdev in layout.vdevs() {
+//!     println!("{:?}: {:?}", vdev.kind(), vdev.disks());
+//! }
+//! ```
+use std::{path::PathBuf, str::FromStr};
+
+use crate::zpool::{CreateVdevRequest, VdevType, ZpoolError, ZpoolResult};
+
+/// Layout `zpool create -n` would build.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Getters)]
+#[get = "pub"]
+pub struct PoolLayout {
+    /// Name of the pool.
+    name:   String,
+    /// Data vdevs.
+    vdevs:  Vec<CreateVdevRequest>,
+    /// ZFS Intent Log (ZIL) vdevs.
+    logs:   Vec<CreateVdevRequest>,
+    /// Cache devices.
+    caches: Vec<PathBuf>,
+    /// Hot spares.
+    spares: Vec<PathBuf>,
+}
+
+/// Top-level entry of a section and its children, if any.
+type Entry = (String, Vec<PathBuf>);
+
+impl PoolLayout {
+    /// Parse output of `zpool create -n`. Sections `zpool` supports but this library doesn't
+    /// (e.g. `special`) are reported as `UnrecognizedOutput`.
+    pub fn parse_dry_run(stdout: &str) -> ZpoolResult<PoolLayout> {
+        let mut lines = stdout.lines().filter(|line| !line.trim().is_empty());
+        let header = lines.next().ok_or(ZpoolError::ParseError)?;
+        if !header.starts_with("would create") {
+            return Err(ZpoolError::UnrecognizedOutput(header.into()));
+        }
+
+        // Tree is indented by two spaces per level after a tab: section, vdev, vdev child.
+        let mut sections: Vec<(String, Vec<Entry>)> = Vec::new();
+        for line in lines {
+            let line = line.strip_prefix('\t').unwrap_or(line);
+            let name = line.trim_start();
+            let entries = sections.last_mut().map(|section| &mut section.1);
+            match line.len() - name.len() {
+                0 => sections.push((name.into(), Vec::new())),
+                2 => entries.ok_or(ZpoolError::ParseError)?.push((name.into(), Vec::new())),
+                _ => entries
+                    .and_then(|entries| entries.last_mut())
+                    .ok_or(ZpoolError::ParseError)?
+                    .1
+                    .push(name.into()),
+            }
+        }
+
+        let mut sections = sections.into_iter();
+        let (name, vdevs) = sections.next().ok_or(ZpoolError::ParseError)?;
+        let mut layout = PoolLayout { name, vdevs: to_vdevs(vdevs)?, ..PoolLayout::default() };
+        for (section, entries) in sections {
+            let devices = || entries.iter().map(|(name, _)| PathBuf::from(name)).collect();
+            match section.as_str() {
+                "logs" => layout.logs = to_vdevs(entries)?,
+                "cache" => layout.caches = devices(),
+                "spares" => layout.spares = devices(),
+                other => return Err(ZpoolError::UnrecognizedOutput(other.into())),
+            }
+        }
+        Ok(layout)
+    }
+}
+
+fn to_vdevs(entries: Vec<Entry>) -> ZpoolResult<Vec<CreateVdevRequest>> {
+    entries
+        .into_iter()
+        .map(|(name, children)| {
+            if children.is_empty() {
+                Ok(CreateVdevRequest::SingleDisk(name.into()))
+            } else {
+                Ok(CreateVdevRequest::from_kind(VdevType::from_str(&name)?, children))
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::zpool::ZpoolErrorKind;
+
+    #[test]
+    fn parse_layout() {
+        let stdout = "would create 'tank' with the following layout:\n\n\
+                      \ttank\n\
+                      \t  mirror\n\
+                      \t    /vdevs/vdev0\n\
+                      \t    /vdevs/vdev1\n\
+                      \t  raidz2\n\
+                      \t    /vdevs/vdev2\n\
+                      \t    /vdevs/vdev3\n\
+                      \t    /vdevs/vdev4\n\
+                      \t    /vdevs/vdev5\n\
+                      \t    /vdevs/vdev6\n\
+                      \tlogs\n\
+                      \t  /vdevs/vdev7\n\
+                      \tcache\n\
+                      \t  /vdevs/vdev8\n\
+                      \tspares\n\
+                      \t  /vdevs/vdev9\n";
+        let layout = PoolLayout::parse_dry_run(stdout).unwrap();
+        let disks = |range: std::ops::Range<usize>| {
+            range.map(|n| PathBuf::from(format!("/vdevs/vdev{}", n))).collect::<Vec<_>>()
+        };
+        let expected = PoolLayout {
+            name:   String::from("tank"),
+            vdevs:  vec![
+                CreateVdevRequest::Mirror(disks(0..2)),
+                CreateVdevRequest::RaidZ2(disks(2..7)),
+            ],
+            logs:   vec![CreateVdevRequest::disk("/vdevs/vdev7")],
+            caches: disks(8..9),
+            spares: disks(9..10),
+        };
+        assert_eq!(expected, layout);
+    }
+
+    #[test]
+    fn parse_draid_layout() {
+        let stdout = "would create 'tank' with the following layout:\n\n\
+                      \ttank\n\
+                      \t  draid1:2d:4c:0s\n\
+                      \t    sda\n\
+                      \t    sdb\n\
+                      \t    sdc\n\
+                      \t    sdd\n";
+        let layout = PoolLayout::parse_dry_run(stdout).unwrap();
+        let children = vec!["sda".into(), "sdb".into(), "sdc".into(), "sdd".into()];
+        let draid = CreateVdevRequest::DRaid { parity: 1, data: 2, spares: 0, children };
+        assert_eq!(&vec![draid], layout.vdevs());
+    }
+
+    #[test]
+    fn parse_unsupported() {
+        let err = PoolLayout::parse_dry_run("cannot open 'sdz': no such device\n").unwrap_err();
+        assert_eq!(ZpoolErrorKind::UnrecognizedOutput, err.kind());
+
+        let stdout = "would create 'tank' with the following layout:\n\n\
+                      \ttank\n\
+                      \t  sda\n\
+                      \tspecial\n\
+                      \t  sdb\n";
+        let err = PoolLayout::parse_dry_run(stdout).unwrap_err();
+        assert_eq!(ZpoolErrorKind::UnrecognizedOutput, err.kind());
+    }
+}
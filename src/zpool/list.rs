@@ -0,0 +1,110 @@
+//! Capacity summary of pools.
+//!
+//! [`PoolSummary`](struct.PoolSummary.html) is a typed line of `zpool list -Hp`, see
+//! [`ZpoolEngine::list`](../trait.ZpoolEngine.html#tymethod.list). It's a lot cheaper than
+//! [`status`](../trait.ZpoolEngine.html#tymethod.status) when only capacity numbers and health
+//! are needed.
+//!
+//! ### Usage
+//! ```rust,no_run
+//! use libzetta::zpool::{ZpoolEngine, ZpoolOpen3};
+//!
+//! let engine = ZpoolOpen3::default();
+//! for pool in engine.list().unwrap() {
+//!     println!("{} is {} full", pool.name(), pool.capacity());
+//! }
+//! ```
+use crate::{utils::{Percent, Ratio},
+            zpool::{Health, ZpoolError, ZpoolResult}};
+
+/// Columns [`PoolSummary::parse_line`](struct.PoolSummary.html#method.parse_line) expects, in
+/// this order.
+pub(crate) const LIST_COLUMNS: &str = "name,size,alloc,free,frag,cap,dedup,health";
+
+/// Space usage and health of a single pool.
+#[derive(Debug, Clone, PartialEq, Getters)]
+#[get = "pub"]
+pub struct PoolSummary {
+    /// Name of the pool.
+    name:          String,
+    /// Total size of the pool in bytes.
+    size:          u64,
+    /// Allocated space in bytes.
+    alloc:         u64,
+    /// Free space in bytes.
+    free:          u64,
+    /// Fragmentation of free space. `None` if pool doesn't track it.
+    fragmentation: Option<Percent>,
+    /// Percentage of pool space used.
+    capacity:      Percent,
+    /// Deduplication ratio.
+    dedup_ratio:   Ratio,
+    /// Current health of the pool.
+    health:        Health,
+}
+
+impl PoolSummary {
+    /// Parse a single line of `zpool list -Hp -o name,size,alloc,free,frag,cap,dedup,health`.
+    pub fn parse_line(line: &str) -> ZpoolResult<PoolSummary> {
+        let mut cols = line.trim_end().split('\t');
+        let mut next = || cols.next().ok_or(ZpoolError::ParseError);
+        let name = next()?.to_string();
+        let size = next()?.parse()?;
+        let alloc = next()?.parse()?;
+        let free = next()?.parse()?;
+        let fragmentation = match next()? {
+            "-" => None,
+            frag => Some(frag.parse()?),
+        };
+        Ok(PoolSummary {
+            name,
+            size,
+            alloc,
+            free,
+            fragmentation,
+            capacity: next()?.parse()?,
+            dedup_ratio: next()?.parse()?,
+            health: Health::try_from_str(Some(next()?))?,
+        })
+    }
+
+    /// Parse output of `zpool list -Hp` with [`parse_line`](#method.parse_line) columns. Empty
+    /// lines are skipped.
+    pub fn parse_stdout(stdout: &str) -> ZpoolResult<Vec<PoolSummary>> {
+        stdout.lines().filter(|line| !line.trim().is_empty()).map(PoolSummary::parse_line).collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::zpool::ZpoolErrorKind;
+
+    #[test]
+    fn parse() {
+        let stdout = "tank\t10737418240\t1073741824\t9663676416\t3\t10\t1.00\tONLINE\n\
+                      boot\t1073741824\t0\t1073741824\t-\t0\t1.25\tDEGRADED\n\n";
+        let pools = PoolSummary::parse_stdout(stdout).unwrap();
+        assert_eq!(2, pools.len());
+        assert_eq!("tank", pools[0].name());
+        assert_eq!(&10_737_418_240, pools[0].size());
+        assert_eq!(&1_073_741_824, pools[0].alloc());
+        assert_eq!(&9_663_676_416, pools[0].free());
+        assert_eq!(&Some(Percent::new(3)), pools[0].fragmentation());
+        assert_eq!(&Percent::new(10), pools[0].capacity());
+        assert_eq!(&Ratio::new(1.0), pools[0].dedup_ratio());
+        assert_eq!(&Health::Online, pools[0].health());
+
+        assert_eq!(&None, pools[1].fragmentation());
+        assert_eq!(&Ratio::new(1.25), pools[1].dedup_ratio());
+        assert_eq!(&Health::Degraded, pools[1].health());
+    }
+
+    #[test]
+    fn parse_garbage() {
+        let err = PoolSummary::parse_line("tank\t10G\t0\t0\t-\t0\t1.00\tONLINE").unwrap_err();
+        assert_eq!(ZpoolErrorKind::ParseError, err.kind());
+        let err = PoolSummary::parse_line("tank\t1\t0\t1").unwrap_err();
+        assert_eq!(ZpoolErrorKind::ParseError, err.kind());
+    }
+}
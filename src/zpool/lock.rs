@@ -0,0 +1,222 @@
+//! Advisory locking around operations that change which pools are imported.
+//!
+//! Nothing stops two processes on the same host from importing, exporting or destroying the same
+//! pool at the same time. This module takes an exclusive `flock(2)` on a lock file named after
+//! the pool GUID before doing any of that. The lock is purely advisory - it only protects against
+//! other users of this module.
+//!
+//! ### Usage
+//! ```rust,no_run
+//! use libzetta::zpool::{lock::PoolLocker, DestroyMode, ZpoolOpen3};
+//! use std::time::Duration;
+//!
+//! let engine = ZpoolOpen3::default();
+//! let locker = PoolLocker::new("/var/run/libzetta").with_timeout(Duration::from_secs(5));
+//! locker.destroy(&engine, "tank", DestroyMode::Gentle).unwrap();
+//! ```
+use std::{fs::{self, File, OpenOptions},
+          io,
+          os::unix::io::AsRawFd,
+          path::{Path, PathBuf},
+          thread,
+          time::{Duration, Instant}};
+
+use crate::zpool::{DestroyMode, ExportMode, ImportWarning, ZpoolEngine, ZpoolError,
+                   ZpoolResult};
+
+/// Default directory for lock files.
+pub static DEFAULT_LOCK_DIR: &str = "/var/run/libzetta";
+
+/// How long to sleep between attempts when waiting for a lock.
+const RETRY_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Held lock on a pool. Lock is released when this is dropped.
+#[derive(Debug)]
+pub struct PoolLock {
+    guid: u64,
+    path: PathBuf,
+    file: File,
+}
+
+impl PoolLock {
+    /// Try to take a lock once. Returns [`ZpoolError::LockHeld`](../enum.ZpoolError.html) if
+    /// someone else holds it.
+    ///
+    /// * `dir` - Directory with lock files. Created if missing.
+    /// * `guid` - GUID of the pool.
+    pub fn try_lock<P: AsRef<Path>>(dir: P, guid: u64) -> ZpoolResult<PoolLock> {
+        let dir = dir.as_ref();
+        fs::create_dir_all(dir).map_err(ZpoolError::Io)?;
+        let path = dir.join(format!("{}.lock", guid));
+        // Truncating on open would clobber the file under whoever holds the lock, so it's only
+        // emptied once the lock is ours.
+        let file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(false)
+            .open(&path)
+            .map_err(ZpoolError::Io)?;
+        let ret = unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) };
+        if ret == 0 {
+            file.set_len(0).map_err(ZpoolError::Io)?;
+            return Ok(PoolLock { guid, path, file });
+        }
+        let err = io::Error::last_os_error();
+        if err.kind() == io::ErrorKind::WouldBlock {
+            Err(ZpoolError::LockHeld(guid))
+        } else {
+            Err(ZpoolError::Io(err))
+        }
+    }
+
+    /// Keep trying to take a lock until `timeout` runs out.
+    ///
+    /// * `dir` - Directory with lock files. Created if missing.
+    /// * `guid` - GUID of the pool.
+    /// * `timeout` - How long to wait.
+    pub fn lock_timeout<P: AsRef<Path>>(
+        dir: P,
+        guid: u64,
+        timeout: Duration,
+    ) -> ZpoolResult<PoolLock> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            match PoolLock::try_lock(dir.as_ref(), guid) {
+                Err(ZpoolError::LockHeld(_)) if Instant::now() < deadline => {
+                    thread::sleep(RETRY_INTERVAL);
+                },
+                result => return result,
+            }
+        }
+    }
+
+    /// GUID of locked pool.
+    pub fn guid(&self) -> u64 { self.guid }
+
+    /// Path to the lock file.
+    pub fn path(&self) -> &Path { &self.path }
+}
+
+impl Drop for PoolLock {
+    fn drop(&mut self) {
+        // Closing the file releases the lock anyway, this just makes it explicit.
+        unsafe { libc::flock(self.file.as_raw_fd(), libc::LOCK_UN) };
+    }
+}
+
+/// Performs import, export and destroy while holding a [`PoolLock`](struct.PoolLock.html).
+#[derive(Debug, Clone)]
+pub struct PoolLocker {
+    dir:     PathBuf,
+    timeout: Option<Duration>,
+}
+
+impl Default for PoolLocker {
+    fn default() -> PoolLocker { PoolLocker::new(DEFAULT_LOCK_DIR) }
+}
+
+impl PoolLocker {
+    /// Create locker that keeps lock files in `dir` and fails right away if lock is held.
+    pub fn new<P: Into<PathBuf>>(dir: P) -> PoolLocker {
+        PoolLocker { dir: dir.into(), timeout: None }
+    }
+
+    /// Wait up to `timeout` for lock instead of failing right away.
+    pub fn with_timeout(mut self, timeout: Duration) -> PoolLocker {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Take a lock on pool with given GUID.
+    pub fn lock(&self, guid: u64) -> ZpoolResult<PoolLock> {
+        match self.timeout {
+            Some(timeout) => PoolLock::lock_timeout(&self.dir, guid, timeout),
+            None => PoolLock::try_lock(&self.dir, guid),
+        }
+    }
+
+    /// Import pool from `/dev/`. Pool is looked up by name among available pools and imported by
+    /// its GUID, so it's the same pool lock was taken for.
+    pub fn import<E: ZpoolEngine, N: AsRef<str>>(
+        &self,
+        engine: &E,
+        name: N,
+    ) -> ZpoolResult<Vec<ImportWarning>> {
+        let guid = engine
+            .available()?
+            .into_iter()
+            .find(|zpool| zpool.name() == name.as_ref())
+            .and_then(|zpool| *zpool.id())
+            .ok_or(ZpoolError::PoolNotFound)?;
+        let _lock = self.lock(guid)?;
+        engine.import(guid.to_string())
+    }
+
+    /// Export pool while holding a lock.
+    pub fn export<E: ZpoolEngine, N: AsRef<str>>(
+        &self,
+        engine: &E,
+        name: N,
+        mode: ExportMode,
+    ) -> ZpoolResult<()> {
+        let guid = *engine.read_properties(&name)?.guid();
+        let _lock = self.lock(guid)?;
+        engine.export(name, mode)
+    }
+
+    /// Destroy pool while holding a lock.
+    pub fn destroy<E: ZpoolEngine, N: AsRef<str>>(
+        &self,
+        engine: &E,
+        name: N,
+        mode: DestroyMode,
+    ) -> ZpoolResult<()> {
+        let guid = *engine.read_properties(&name)?.guid();
+        let _lock = self.lock(guid)?;
+        engine.destroy(name, mode)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::zpool::ZpoolErrorKind;
+    use tempdir::TempDir;
+
+    #[test]
+    fn lock_is_exclusive() {
+        let dir = TempDir::new("zpool-lock").unwrap();
+        let lock = PoolLock::try_lock(dir.path(), 42).unwrap();
+        assert_eq!(42, lock.guid());
+        assert!(lock.path().exists());
+
+        let err = PoolLock::try_lock(dir.path(), 42).unwrap_err();
+        assert_eq!(ZpoolErrorKind::LockHeld, err.kind());
+
+        // Other pools are not affected.
+        let _other = PoolLock::try_lock(dir.path(), 43).unwrap();
+
+        drop(lock);
+        let _again = PoolLock::try_lock(dir.path(), 42).unwrap();
+    }
+
+    #[test]
+    fn stale_content_is_dropped() {
+        let dir = TempDir::new("zpool-lock").unwrap();
+        let path = dir.path().join("42.lock");
+        fs::write(&path, "left over").unwrap();
+        let lock = PoolLock::try_lock(dir.path(), 42).unwrap();
+        assert_eq!(0, fs::metadata(lock.path()).unwrap().len());
+    }
+
+    #[test]
+    fn lock_timeout() {
+        let dir = TempDir::new("zpool-lock").unwrap();
+        let _lock = PoolLock::try_lock(dir.path(), 7).unwrap();
+
+        let started = Instant::now();
+        let err = PoolLock::lock_timeout(dir.path(), 7, Duration::from_millis(120)).unwrap_err();
+        assert_eq!(ZpoolErrorKind::LockHeld, err.kind());
+        assert!(started.elapsed() >= Duration::from_millis(120));
+    }
+}
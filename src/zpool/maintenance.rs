@@ -0,0 +1,328 @@
+//! State of TRIM and initialization of devices.
+//!
+//! `zpool status -t` and `-i` append state of TRIM and initialization to every device:
+//! `(100% trimmed, completed at Tue Oct 15 10:00:00 2024)`, `(untrimmed)`,
+//! `(42% initialized, started at ...)`. Those are parsed into
+//! [`MaintenanceState`](enum.MaintenanceState.html) and stored on [`Disk`](../struct.Disk.html)
+//! by [`status_with_maintenance`](../trait.ZpoolEngine.html#tymethod.status_with_maintenance).
+//! [`Vdev::trim`](../struct.Vdev.html#method.trim) and
+//! [`Vdev::initialize`](../struct.Vdev.html#method.initialize) sum up devices of a vdev.
+//! [`last_trim`](../struct.Zpool.html#method.last_trim) and
+//! [`last_initialize`](../struct.Zpool.html#method.last_initialize) tell when the whole pool was
+//! last done, so a scheduler can decide if next pass is due without keeping its own records.
+//!
+//! `zpool status` only prints progress in percent, so that's all there is. Byte counts are not
+//! reported.
+//!
+//! ### Usage
+//! ```rust,no_run
+//! use chrono::{Duration, Local};
+//! use libzetta::zpool::{ZpoolEngine, ZpoolOpen3};
+//!
+//! let engine = ZpoolOpen3::default();
+//! let zpool = engine.status_with_maintenance("tank").unwrap();
+//! let week_ago = Local::now().naive_local() - Duration::days(7);
+//! if zpool.last_trim().map_or(true, |at| at < week_ago) {
+//!     println!("time to trim tank");
+//! }
+//! ```
+use chrono::NaiveDateTime;
+
+use crate::zpool::{Disk, Vdev, Zpool};
+
+/// Format of timestamps in `zpool status` device suffixes (`ctime(3)`), with whitespace squeezed.
+static TIME_FORMAT: &str = "%a %b %d %H:%M:%S %Y";
+
+/// State of TRIM or initialization of a device.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MaintenanceState {
+    /// Never done on this device (`untrimmed`, `uninitialized`).
+    Never,
+    /// Device doesn't support it (`trim unsupported`).
+    Unsupported,
+    /// Running.
+    Active {
+        /// Progress in percent.
+        percent: u8,
+        /// When it was started.
+        started: Option<NaiveDateTime>,
+    },
+    /// Suspended by the administrator.
+    Suspended {
+        /// Progress in percent.
+        percent: u8,
+        /// When it was suspended.
+        at:      Option<NaiveDateTime>,
+    },
+    /// Finished.
+    Completed {
+        /// When it was finished.
+        at: Option<NaiveDateTime>,
+    },
+}
+
+impl MaintenanceState {
+    /// When it was finished. `None` unless completed.
+    pub fn completed_at(&self) -> Option<NaiveDateTime> {
+        match self {
+            MaintenanceState::Completed { at } => *at,
+            _ => None,
+        }
+    }
+
+    /// Whether it's running right now.
+    pub fn is_active(&self) -> bool {
+        match self {
+            MaintenanceState::Active { .. } => true,
+            _ => false,
+        }
+    }
+
+    /// Progress in percent. `None` if device doesn't support it.
+    pub fn percent(&self) -> Option<u8> {
+        match self {
+            MaintenanceState::Never => Some(0),
+            MaintenanceState::Unsupported => None,
+            MaintenanceState::Active { percent, .. }
+            | MaintenanceState::Suspended { percent, .. } => Some(*percent),
+            MaintenanceState::Completed { .. } => Some(100),
+        }
+    }
+
+    /// When the last run was started, suspended or finished, whichever is the latest event.
+    pub fn last_run(&self) -> Option<NaiveDateTime> {
+        match self {
+            MaintenanceState::Never | MaintenanceState::Unsupported => None,
+            MaintenanceState::Active { started: at, .. }
+            | MaintenanceState::Suspended { at, .. }
+            | MaintenanceState::Completed { at } => *at,
+        }
+    }
+
+    /// Parse the inside of one parenthesized group. `verb` is `trimmed` or `initialized`.
+    fn parse(group: &str, verb: &str) -> Option<MaintenanceState> {
+        let mut parts = group.splitn(2, ", ");
+        let head = parts.next()?.trim();
+        let percent = match head.strip_suffix(verb) {
+            Some(percent) => percent.trim().trim_end_matches('%').parse().ok()?,
+            None => return None,
+        };
+        let tail = parts.next().unwrap_or("").trim();
+        let time = |prefix: &str| {
+            let value = tail.strip_prefix(prefix)?;
+            let value = value.split_whitespace().collect::<Vec<_>>().join(" ");
+            NaiveDateTime::parse_from_str(&value, TIME_FORMAT).ok()
+        };
+        if tail.starts_with("completed at ") {
+            Some(MaintenanceState::Completed { at: time("completed at ") })
+        } else if tail.starts_with("suspended at ") {
+            Some(MaintenanceState::Suspended { percent, at: time("suspended at ") })
+        } else {
+            Some(MaintenanceState::Active { percent, started: time("started at ") })
+        }
+    }
+}
+
+/// What `zpool status -i -t` appended to a device line.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub(crate) struct Suffixes {
+    pub(crate) trim:       Option<MaintenanceState>,
+    pub(crate) initialize: Option<MaintenanceState>,
+    /// The rest of the reason, if anything is left.
+    pub(crate) rest:       Option<String>,
+}
+
+/// Take TRIM and initialization state out of the reason part of a device line.
+pub(crate) fn parse_suffixes(reason: &str) -> Suffixes {
+    let mut suffixes = Suffixes::default();
+    let mut rest = String::new();
+    let mut remaining = reason;
+    while let Some(start) = remaining.find('(') {
+        let end = match remaining[start..].find(')') {
+            Some(end) => start + end,
+            None => break,
+        };
+        let group = &remaining[start + 1..end];
+        let parsed = match group {
+            "untrimmed" => Some((true, MaintenanceState::Never)),
+            "trim unsupported" => Some((true, MaintenanceState::Unsupported)),
+            "uninitialized" => Some((false, MaintenanceState::Never)),
+            _ => MaintenanceState::parse(group, "trimmed")
+                .map(|state| (true, state))
+                .or_else(|| MaintenanceState::parse(group, "initialized").map(|s| (false, s))),
+        };
+        match parsed {
+            Some((true, state)) => suffixes.trim = Some(state),
+            Some((false, state)) => suffixes.initialize = Some(state),
+            None => rest.push_str(&remaining[start..=end]),
+        }
+        rest.push_str(&remaining[..start]);
+        remaining = &remaining[end + 1..];
+    }
+    rest.push_str(remaining);
+    let rest = rest.split_whitespace().collect::<Vec<_>>().join(" ");
+    if !rest.is_empty() {
+        suffixes.rest = Some(rest);
+    }
+    suffixes
+}
+
+/// The oldest completion time among devices that report state. `None` if some device isn't
+/// done (never done, running or suspended) or nothing reports state at all.
+fn oldest_completion<'a, I, F>(disks: I, state: F) -> Option<NaiveDateTime>
+where
+    I: Iterator<Item = &'a Disk>,
+    F: Fn(&Disk) -> &Option<MaintenanceState>,
+{
+    let mut oldest: Option<NaiveDateTime> = None;
+    for disk in disks {
+        match state(disk) {
+            None | Some(MaintenanceState::Unsupported) => continue,
+            Some(state) => {
+                let at = state.completed_at()?;
+                oldest = Some(oldest.map_or(at, |oldest| oldest.min(at)));
+            },
+        }
+    }
+    oldest
+}
+
+/// State of a group of devices. Running wins over suspended, suspended over never done, and the
+/// group is complete when every device is. Progress is the lowest one, time is the earliest one.
+/// Devices that don't report state or don't support it are ignored, unless none of them do.
+fn combine<'a, I>(states: I) -> Option<MaintenanceState>
+where
+    I: Iterator<Item = &'a MaintenanceState>,
+{
+    let (unsupported, states): (Vec<_>, Vec<_>) =
+        states.partition(|state| **state == MaintenanceState::Unsupported);
+    if states.is_empty() {
+        return unsupported.first().map(|_| MaintenanceState::Unsupported);
+    }
+    let percent = states.iter().filter_map(|state| state.percent()).min().unwrap_or(0);
+    let is_suspended = |s: &MaintenanceState| matches!(s, MaintenanceState::Suspended { .. });
+    let earliest = |include: &dyn Fn(&MaintenanceState) -> bool| {
+        states.iter().filter(|s| include(s)).filter_map(|s| s.last_run()).min()
+    };
+    if states.iter().any(|s| s.is_active()) {
+        Some(MaintenanceState::Active { percent, started: earliest(&MaintenanceState::is_active) })
+    } else if states.iter().any(|s| is_suspended(s)) {
+        Some(MaintenanceState::Suspended { percent, at: earliest(&is_suspended) })
+    } else if states.iter().any(|s| **s == MaintenanceState::Never) {
+        Some(MaintenanceState::Never)
+    } else {
+        let at = states.iter().map(|s| s.completed_at()).collect::<Option<Vec<_>>>();
+        Some(MaintenanceState::Completed { at: at.and_then(|at| at.into_iter().min()) })
+    }
+}
+
+impl Vdev {
+    /// TRIM state of the vdev as a whole. Requires
+    /// [`status_with_maintenance`](trait.ZpoolEngine.html#tymethod.status_with_maintenance).
+    pub fn trim(&self) -> Option<MaintenanceState> {
+        combine(self.disks().iter().filter_map(|disk| disk.trim().as_ref()))
+    }
+
+    /// Initialization state of the vdev as a whole. Requires
+    /// [`status_with_maintenance`](trait.ZpoolEngine.html#tymethod.status_with_maintenance).
+    pub fn initialize(&self) -> Option<MaintenanceState> {
+        combine(self.disks().iter().filter_map(|disk| disk.initialize().as_ref()))
+    }
+}
+
+impl Zpool {
+    fn data_disks(&self) -> impl Iterator<Item = &Disk> {
+        self.vdevs().iter().chain(self.logs().iter()).flat_map(|vdev| vdev.disks().iter())
+    }
+
+    /// When every device of data and log vdevs and every cache device was last trimmed. Devices
+    /// that don't support TRIM are ignored. Requires
+    /// [`status_with_maintenance`](trait.ZpoolEngine.html#tymethod.status_with_maintenance).
+    pub fn last_trim(&self) -> Option<NaiveDateTime> {
+        oldest_completion(self.data_disks().chain(self.caches().iter()), Disk::trim)
+    }
+
+    /// When every device of data and log vdevs was last initialized. Requires
+    /// [`status_with_maintenance`](trait.ZpoolEngine.html#tymethod.status_with_maintenance).
+    pub fn last_initialize(&self) -> Option<NaiveDateTime> {
+        oldest_completion(self.data_disks(), Disk::initialize)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::parsers::parse_zpools;
+
+    static STATUS: &str = "  pool: tank
+ state: ONLINE
+config:
+
+\tNAME        STATE     READ WRITE CKSUM
+\ttank        ONLINE       0     0     0
+\t  sdc       ONLINE       0     0     0  (100% initialized, completed at Tue Oct 15 10:00:00 2024)  (trim unsupported)
+\t  mirror-1  ONLINE       0     0     0
+\t    sda     ONLINE       0     0     0  (100% initialized, completed at Sat Oct  5 09:07:03 2024)  (100% trimmed, completed at Sat Oct  5 09:07:03 2024)
+\t    sdb     ONLINE       0     0     0  (42% initialized, started at Tue Oct 15 10:00:00 2024)  (100% trimmed, completed at Mon Oct 14 01:00:00 2024)
+
+errors: No known data errors
+";
+
+    fn time(value: &str) -> Option<NaiveDateTime> {
+        NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S").ok()
+    }
+
+    #[test]
+    fn suffixes() {
+        let suffixes = parse_suffixes("was /dev/sdb1  (untrimmed)");
+        assert_eq!(Some(MaintenanceState::Never), suffixes.trim);
+        assert_eq!(None, suffixes.initialize);
+        assert_eq!(Some(String::from("was /dev/sdb1")), suffixes.rest);
+
+        let suffixes = parse_suffixes("(7% trimmed, suspended at Tue Oct 15 10:00:00 2024)");
+        let expected = MaintenanceState::Suspended { percent: 7, at: time("2024-10-15 10:00:00") };
+        assert_eq!(Some(expected), suffixes.trim);
+        assert_eq!(None, suffixes.rest);
+
+        let suffixes = parse_suffixes("(too many errors)");
+        let rest = Some(String::from("(too many errors)"));
+        let expected = Suffixes { rest, ..Suffixes::default() };
+        assert_eq!(expected, suffixes);
+    }
+
+    #[test]
+    fn pool_last_done() {
+        let zpool = parse_zpools(STATUS).unwrap().remove(0);
+        let sdc = &zpool.vdevs()[0].disks()[0];
+        assert_eq!(&Some(MaintenanceState::Unsupported), sdc.trim());
+        assert_eq!(&None, sdc.reason());
+        let sdb = &zpool.vdevs()[1].disks()[1];
+        assert!(sdb.initialize().as_ref().unwrap().is_active());
+
+        assert_eq!(time("2024-10-05 09:07:03"), zpool.last_trim());
+        assert_eq!(None, zpool.last_initialize());
+    }
+
+    #[test]
+    fn vdev_state() {
+        let zpool = parse_zpools(STATUS).unwrap().remove(0);
+        assert_eq!(Some(MaintenanceState::Unsupported), zpool.vdevs()[0].trim());
+        let mirror = &zpool.vdevs()[1];
+        let trim = mirror.trim().unwrap();
+        assert_eq!(MaintenanceState::Completed { at: time("2024-10-05 09:07:03") }, trim);
+        assert_eq!(Some(100), trim.percent());
+        let initialize = mirror.initialize().unwrap();
+        assert_eq!(
+            MaintenanceState::Active { percent: 42, started: time("2024-10-15 10:00:00") },
+            initialize
+        );
+        assert_eq!(time("2024-10-15 10:00:00"), initialize.last_run());
+        assert_eq!(None, MaintenanceState::Unsupported.percent());
+
+        let suspended = MaintenanceState::Suspended { percent: 7, at: None };
+        let states = [MaintenanceState::Never, suspended];
+        let expected = MaintenanceState::Suspended { percent: 0, at: None };
+        assert_eq!(Some(expected), combine(states.iter()));
+        assert_eq!(None, combine([].iter()));
+    }
+}
@@ -17,21 +17,68 @@ use std::{default::Default,
           ffi::OsStr,
           io,
           num::{ParseFloatError, ParseIntError},
-          path::PathBuf};
+          path::PathBuf,
+          thread,
+          time::Duration};
 
 use regex::Regex;
 
-pub use self::{description::{Reason, Zpool},
+use crate::args::ArgError;
+
+pub use self::{boot::{BootPoolReport, BootPoolRequest, BootPoolRequestBuilder},
+               description::{DataError, HistoryRecord, Reason, RemovalState, RemovalStatus,
+                             ScrubState, Zpool},
+               drift::TopologyDrift,
+               events::ZpoolEvent,
+               handle::ZpoolHandle,
+               hostid::HostInfo,
+               import::{ImportName, ImportRequest, ImportRequestBuilder, ImportWarning, Recovery},
+               iostat::{IoStat, IoStatExt, LatencyHistogram, PoolIoStat, VdevIoStat},
+               label::{Label, LabelState},
+               layout::PoolLayout,
+               list::PoolSummary,
+               maintenance::MaintenanceState,
                open3::ZpoolOpen3,
-               properties::{CacheType, FailMode, Health, PropPair, ZpoolProperties,
-                            ZpoolPropertiesWrite, ZpoolPropertiesWriteBuilder},
+               properties::{CacheType, FailMode, FeatureState, Health, PropPair, ZpoolProperties,
+                            ZpoolPropertiesRef, ZpoolPropertiesWrite,
+                            ZpoolPropertiesWriteBuilder},
+               scan::{ScanKind, ScanProgress, ScanStatus, ScanSummary},
+               split::{SplitOptions, SplitOptionsBuilder},
                topology::{CreateZpoolRequest, CreateZpoolRequestBuilder},
-               vdev::{CreateVdevRequest, Disk, Vdev, VdevType}};
-
+               trim::{TrimOptions, TrimOptionsBuilder},
+               upgrade::UpgradeStatus,
+               vdev::{CreateVdevRequest, Disk, Vdev, VdevType},
+               vdevprops::{VdevProperties, VdevPropertiesWrite, VdevPropertiesWriteBuilder}};
+
+pub mod boot;
+pub mod cleanup;
+pub mod drift;
+pub mod events;
+pub mod expand;
+pub mod fleet;
+pub mod handle;
+pub mod hostid;
+pub mod identity;
+pub mod import;
+pub mod inspect;
+pub mod iostat;
+pub mod label;
+pub mod layout;
+pub mod list;
+pub mod lock;
+pub mod maintenance;
 pub mod open3;
+#[cfg(feature = "disk-management")] pub mod partition;
 pub mod properties;
+pub mod scan;
+pub mod spare;
+pub mod split;
 pub mod topology;
+pub mod trim;
+pub mod txg;
+pub mod upgrade;
 pub mod vdev;
+pub mod vdevprops;
 
 pub mod description;
 lazy_static! {
@@ -45,9 +92,20 @@ lazy_static! {
     static ref RE_NO_SUCH_POOL: Regex = Regex::new(r"cannot open '\S+': no such pool\n?").expect("failed to compile RE_NO_SUCH_POOL");
     static ref RE_NO_VALID_REPLICAS: Regex = Regex::new(r"cannot offline \S+: no valid replicas\n?").expect("failed to compile RE_NO_VALID_REPLICAS");
     static ref RE_CANNOT_ATTACH: Regex = Regex::new(r"cannot attach \S+ to \S+ can only attach to mirrors and top-level disks").expect("failed to compile RE_CANNOT_ATTACH");
-    static ref RE_NO_SUCH_DEVICE: Regex = Regex::new(r"cannot attach \S+ to \S+: no such device in pool").expect("failed to compile RE_NO_SUCH_DEVICE");
+    static ref RE_NO_SUCH_DEVICE: Regex = Regex::new(r"cannot (?:attach \S+ to \S+|replace \S+ with \S+|clear errors for \S+): no such device in pool").expect("failed to compile RE_NO_SUCH_DEVICE");
+    static ref RE_CANNOT_REMOVE: Regex = Regex::new(r"cannot remove \S+: ([^\n]+)").expect("failed to compile RE_CANNOT_REMOVE");
+    static ref RE_CANNOT_REPLACE: Regex = Regex::new(r"cannot replace \S+ with \S+: ([^\n]+)").expect("failed to compile RE_CANNOT_REPLACE");
     static ref RE_ONLY_DEVICE: Regex = Regex::new(r"cannot detach \S+ only applicable to mirror and replacing vdevs").expect("failed to compile RE_ONLY_DEVICE");
     static ref RE_MISMATCH_REPLICATION: Regex = Regex::new(r"invalid vdev specification\nuse '-f' to override the following errors:\nmismatched replication level:.+").expect("failed to compile RE_MISMATCHED_REPLICATION");
+    static ref RE_PREVIOUSLY_IN_USE: Regex = Regex::new(r"pool was previously in use from another system\.\nLast accessed by (\S+) \(hostid=(?:0x)?([0-9a-fA-F]+)\)").expect("failed to compile RE_PREVIOUSLY_IN_USE");
+    static ref RE_MMP_ACTIVE: Regex = Regex::new(r"pool is imported on (?:host '([^']+)' \(hostid=(?:0x)?([0-9a-fA-F]+)\)|another system)").expect("failed to compile RE_MMP_ACTIVE");
+    static ref RE_LABELCLEAR_ACTIVE: Regex = Regex::new(r#"labelclear operation failed\.\n\s*Vdev (\S+) is a member \(ACTIVE\), of pool "([^"]+)""#).expect("failed to compile RE_LABELCLEAR_ACTIVE");
+    static ref RE_LABELCLEAR_MEMBER: Regex = Regex::new(r#"(?:labelclear operation failed\.\n\s*Vdev (\S+) is a member \(\S+\), of pool|use '-f' to override the following error:\n(\S+) is a member of (?:exported|potentially active) pool) "([^"]+)""#).expect("failed to compile RE_LABELCLEAR_MEMBER");
+    static ref RE_CHECKPOINT_EXISTS: Regex = Regex::new(r"cannot checkpoint '\S+': checkpoint exists").expect("failed to compile RE_CHECKPOINT_EXISTS");
+    static ref RE_NO_CHECKPOINT: Regex = Regex::new(r"cannot (?:discard checkpoint in|import) '\S+': checkpoint does not exist").expect("failed to compile RE_NO_CHECKPOINT");
+    static ref RE_TRIM_NOT_SUPPORTED: Regex = Regex::new(r"cannot trim '([^']+)': (?:trim operations are not supported by this device|no devices in pool support trim operations)").expect("failed to compile RE_TRIM_NOT_SUPPORTED");
+    static ref RE_NOT_INITIALIZING: Regex = Regex::new(r"cannot initialize '[^']+': there is no active initialization").expect("failed to compile RE_NOT_INITIALIZING");
+    static ref RE_CANNOT_SPLIT: Regex = Regex::new(r"Unable to split \S+: ([^\n]+)").expect("failed to compile RE_CANNOT_SPLIT");
     static ref RE_INVALID_CACHE_DEVICE: Regex = Regex::new(r"cannot add to \S+: cache device must be a disk or disk slice\n?").expect("failed to compile RE_INVALID_CACHE_DEVICE");
 }
 
@@ -63,17 +121,39 @@ quick_error! {
         }
         /// Trying to manipulate non-existent pool.
         PoolNotFound {}
-        /// Given topology failed validation.
-        InvalidTopology {}
+        /// Name of the pool now refers to a different pool: name, GUID that was expected and GUID
+        /// that was found.
+        PoolChanged(name: String, expected: u64, found: u64) {
+            display("pool {} has changed: expected guid {}, found {}", name, expected, found)
+        }
+        /// Given topology failed validation. Contains the reason.
+        InvalidTopology(reason: String) {
+            display("invalid topology: {}", reason)
+        }
         /// Trying to create new Zpool, but one or more vdevs are lready used in another pool.
         VdevReuse(vdev: String, pool: String) {
             display("{} is part of {}", vdev, pool)
         }
+        /// Output has lines that parser doesn't recognize. Only returned in
+        /// [`ParseMode::Strict`](../utils/enum.ParseMode.html).
+        UnrecognizedOutput(line: String) {
+            display("unrecognized output: {}", line)
+        }
         /// Failed to parse value. Ideally you never see it, if you see it - it's a bug.
         ParseError {
             from(ParseIntError)
             from(ParseFloatError)
         }
+        /// Property name or value can't be passed to `zpool`. See [`args`](../args/index.html).
+        InvalidProperty(err: ArgError) {
+            cause(err)
+            display("{}", err)
+            from()
+        }
+        /// Numeric value doesn't fit into its type. See [`Overflow`](../utils/enum.Overflow.html).
+        Overflow(value: String) {
+            display("value {} is out of range", value)
+        }
         /// Device used in CreateZpoolRequest is smaller than 64M (or 128M on some platforms).
         DeviceTooSmall {}
         /// Permission denied to create zpool. This might happened because:
@@ -90,6 +170,21 @@ quick_error! {
         CannotAttach {}
         /// Operation on a device that was not found in the pool.
         NoSuchDevice {}
+        /// ZFS refused to remove a device. For example, pool has raidz vdevs or sector sizes of
+        /// top-level vdevs don't match.
+        CannotRemove(reason: String) {
+            display("cannot remove device: {}", reason)
+        }
+        /// ZFS refused to replace a device. For example, a replacement is already in progress or
+        /// the new device has different sector alignment.
+        CannotReplace(reason: String) {
+            display("cannot replace device: {}", reason)
+        }
+        /// ZFS refused to split a pool. For example, it has vdevs other than mirrors or a resilver
+        /// is in progress.
+        CannotSplit(reason: String) {
+            display("cannot split pool: {}", reason)
+        }
         /// Trying to detach a device from vdev without any valid replicas left.
         OnlyDevice {}
         /// Trying to add vdev with wrong replication level to existing zpool with different replication level.
@@ -97,6 +192,39 @@ quick_error! {
         MismatchedReplicationLevel {}
         /// Cache device must a disk or disk slice/partition.
         InvalidCacheDevice {}
+        /// Pool belongs to another host. Import with force might be possible, see
+        /// [`HostInfo::can_force_import`](hostid/struct.HostInfo.html#method.can_force_import).
+        ActiveOnOtherHost(host: HostInfo) {
+            display("pool is in use by {}: {}", host, if host.can_force_import() {
+                "make sure it's not imported there and import with force"
+            } else {
+                "pool is protected by multihost, export it on that host first"
+            })
+        }
+        /// Device is part of an imported pool. Export or destroy the pool, or remove device from it
+        /// first.
+        ActiveVdev(vdev: String, pool: String) {
+            display("{} is part of active pool {}", vdev, pool)
+        }
+        /// Pool already has a checkpoint. There can be only one.
+        CheckpointExists {}
+        /// Pool has no checkpoint to discard or rewind to.
+        NoCheckpoint {}
+        /// Device doesn't support TRIM, or secure TRIM if it was requested. For a whole pool, none
+        /// of its devices do.
+        TrimNotSupported(device: String) {
+            display("{} doesn't support trim", device)
+        }
+        /// Trying to cancel or suspend initialization that is not running.
+        NotInitializing {}
+        /// Operation was stopped by [`Middleware`](../middleware/trait.Middleware.html).
+        Denied(reason: String) {
+            display("operation denied: {}", reason)
+        }
+        /// Advisory lock for pool with this GUID is held by someone else.
+        LockHeld(guid: u64) {
+            display("lock for pool {} is held by another process", guid)
+        }
         /// Don't know (yet) how to categorize this error. If you see this error - open an issue.
         Other(err: String) {}
     }
@@ -109,9 +237,13 @@ impl ZpoolError {
             ZpoolError::CmdNotFound => ZpoolErrorKind::CmdNotFound,
             ZpoolError::Io(_) => ZpoolErrorKind::Io,
             ZpoolError::PoolNotFound => ZpoolErrorKind::PoolNotFound,
-            ZpoolError::InvalidTopology => ZpoolErrorKind::InvalidTopology,
+            ZpoolError::PoolChanged(..) => ZpoolErrorKind::PoolChanged,
+            ZpoolError::InvalidTopology(_) => ZpoolErrorKind::InvalidTopology,
             ZpoolError::VdevReuse(..) => ZpoolErrorKind::VdevReuse,
+            ZpoolError::UnrecognizedOutput(_) => ZpoolErrorKind::UnrecognizedOutput,
             ZpoolError::ParseError => ZpoolErrorKind::ParseError,
+            ZpoolError::InvalidProperty(_) => ZpoolErrorKind::InvalidProperty,
+            ZpoolError::Overflow(_) => ZpoolErrorKind::Overflow,
             ZpoolError::DeviceTooSmall => ZpoolErrorKind::DeviceTooSmall,
             ZpoolError::PermissionDenied => ZpoolErrorKind::PermissionDenied,
             ZpoolError::NoActiveScrubs => ZpoolErrorKind::NoActiveScrubs,
@@ -119,9 +251,20 @@ impl ZpoolError {
             ZpoolError::UnknownRaidType(_) => ZpoolErrorKind::UnknownRaidType,
             ZpoolError::CannotAttach => ZpoolErrorKind::CannotAttach,
             ZpoolError::NoSuchDevice => ZpoolErrorKind::NoSuchDevice,
+            ZpoolError::CannotReplace(_) => ZpoolErrorKind::CannotReplace,
+            ZpoolError::CannotRemove(_) => ZpoolErrorKind::CannotRemove,
+            ZpoolError::CannotSplit(_) => ZpoolErrorKind::CannotSplit,
             ZpoolError::OnlyDevice => ZpoolErrorKind::OnlyDevice,
             ZpoolError::MismatchedReplicationLevel => ZpoolErrorKind::MismatchedReplicationLevel,
             ZpoolError::InvalidCacheDevice => ZpoolErrorKind::InvalidCacheDevice,
+            ZpoolError::ActiveOnOtherHost(_) => ZpoolErrorKind::ActiveOnOtherHost,
+            ZpoolError::ActiveVdev(..) => ZpoolErrorKind::ActiveVdev,
+            ZpoolError::CheckpointExists => ZpoolErrorKind::CheckpointExists,
+            ZpoolError::NoCheckpoint => ZpoolErrorKind::NoCheckpoint,
+            ZpoolError::TrimNotSupported(_) => ZpoolErrorKind::TrimNotSupported,
+            ZpoolError::NotInitializing => ZpoolErrorKind::NotInitializing,
+            ZpoolError::Denied(_) => ZpoolErrorKind::Denied,
+            ZpoolError::LockHeld(_) => ZpoolErrorKind::LockHeld,
             ZpoolError::Other(_) => ZpoolErrorKind::Other,
         }
     }
@@ -138,6 +281,8 @@ pub enum ZpoolErrorKind {
     Io,
     /// Trying to manipulate non-existent pool.
     PoolNotFound,
+    /// Name of the pool now refers to a different pool.
+    PoolChanged,
     /// At least one vdev points to incorrect location.
     /// If vdev type is File then it means file not found.
     DeviceNotFound,
@@ -146,9 +291,15 @@ pub enum ZpoolErrorKind {
     VdevReuse,
     /// Given topology failed validation.
     InvalidTopology,
+    /// Output has lines that parser doesn't recognize.
+    UnrecognizedOutput,
     /// Failed to parse value. Ideally you never see it, if you see it - it's a
     /// bug.
     ParseError,
+    /// Property name or value can't be passed to `zpool`.
+    InvalidProperty,
+    /// Numeric value doesn't fit into its type.
+    Overflow,
     /// Device used in CreateZpoolRequest is smaller than 64M (or 128M on some platforms)
     DeviceTooSmall,
     /// Permission denied to create zpool. This might happened because:
@@ -166,6 +317,12 @@ pub enum ZpoolErrorKind {
     CannotAttach,
     /// Operation on device that was not found in the pool.
     NoSuchDevice,
+    /// ZFS refused to replace a device.
+    CannotReplace,
+    /// ZFS refused to remove a device.
+    CannotRemove,
+    /// ZFS refused to split a pool.
+    CannotSplit,
     /// Trying to detach a device from vdev without any valid replicas left.
     OnlyDevice,
     /// Trying to add vdev with wrong replication level to existing zpool with
@@ -173,6 +330,22 @@ pub enum ZpoolErrorKind {
     MismatchedReplicationLevel,
     /// Cache device must be a disk or disk slice/partition.
     InvalidCacheDevice,
+    /// Pool belongs to another host.
+    ActiveOnOtherHost,
+    /// Device is part of an imported pool.
+    ActiveVdev,
+    /// Pool already has a checkpoint.
+    CheckpointExists,
+    /// Pool has no checkpoint.
+    NoCheckpoint,
+    /// Device doesn't support TRIM.
+    TrimNotSupported,
+    /// Initialization is not running.
+    NotInitializing,
+    /// Operation was stopped by middleware.
+    Denied,
+    /// Advisory lock for pool is held by someone else.
+    LockHeld,
     /// Don't know (yet) how to categorize this error. If you see this error -
     /// open an issue.
     Other,
@@ -227,12 +400,47 @@ impl ZpoolError {
             ZpoolError::CannotAttach
         } else if RE_NO_SUCH_DEVICE.is_match(&stderr) {
             ZpoolError::NoSuchDevice
+        } else if let Some(caps) = RE_CANNOT_REMOVE.captures(&stderr) {
+            ZpoolError::CannotRemove(caps.get(1).map_or("", |m| m.as_str()).to_string())
+        } else if let Some(caps) = RE_CANNOT_REPLACE.captures(&stderr) {
+            match caps.get(1).map_or("", |m| m.as_str()) {
+                "device is too small" => ZpoolError::DeviceTooSmall,
+                reason => ZpoolError::CannotReplace(reason.to_string()),
+            }
         } else if RE_ONLY_DEVICE.is_match(&stderr) {
             ZpoolError::OnlyDevice
         } else if RE_MISMATCH_REPLICATION.is_match(&stderr) {
             ZpoolError::MismatchedReplicationLevel
+        } else if let Some(caps) = RE_LABELCLEAR_ACTIVE.captures(&stderr) {
+            ZpoolError::ActiveVdev(
+                caps.get(1).unwrap().as_str().into(),
+                caps.get(2).unwrap().as_str().into(),
+            )
+        } else if let Some(caps) = RE_LABELCLEAR_MEMBER.captures(&stderr) {
+            ZpoolError::VdevReuse(
+                caps.get(1).or_else(|| caps.get(2)).unwrap().as_str().into(),
+                caps.get(3).unwrap().as_str().into(),
+            )
+        } else if RE_CHECKPOINT_EXISTS.is_match(&stderr) {
+            ZpoolError::CheckpointExists
+        } else if RE_NO_CHECKPOINT.is_match(&stderr) {
+            ZpoolError::NoCheckpoint
+        } else if let Some(caps) = RE_TRIM_NOT_SUPPORTED.captures(&stderr) {
+            ZpoolError::TrimNotSupported(caps.get(1).unwrap().as_str().into())
+        } else if RE_NOT_INITIALIZING.is_match(&stderr) {
+            ZpoolError::NotInitializing
+        } else if let Some(caps) = RE_CANNOT_SPLIT.captures(&stderr) {
+            ZpoolError::CannotSplit(caps.get(1).map_or("", |m| m.as_str()).to_string())
         } else if RE_INVALID_CACHE_DEVICE.is_match(&stderr) {
             ZpoolError::InvalidCacheDevice
+        } else if let Some(caps) = RE_PREVIOUSLY_IN_USE.captures(&stderr) {
+            let hostname = caps.get(1).map(|m| m.as_str().to_string());
+            let hostid = caps.get(2).and_then(|m| u32::from_str_radix(m.as_str(), 16).ok());
+            ZpoolError::ActiveOnOtherHost(HostInfo::new(hostname, hostid, false))
+        } else if let Some(caps) = RE_MMP_ACTIVE.captures(&stderr) {
+            let hostname = caps.get(1).map(|m| m.as_str().to_string());
+            let hostid = caps.get(2).and_then(|m| u32::from_str_radix(m.as_str(), 16).ok());
+            ZpoolError::ActiveOnOtherHost(HostInfo::new(hostname, hostid, true))
         } else {
             ZpoolError::Other(stderr.into())
         }
@@ -292,10 +500,36 @@ pub enum ExportMode {
     Gentle,
 }
 
+/// Strategy to use when clearing labels.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum LabelClearMode {
+    /// Clear labels of devices that are part of exported or potentially active pools. Devices of
+    /// imported pools are never cleared.
+    Force,
+    /// Do not use force mode.
+    Gentle,
+}
+
+/// What to do with initialization of devices.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum InitializeAction {
+    /// Start writing to unallocated space, or resume if suspended.
+    Start,
+    /// Stop and forget progress.
+    Cancel,
+    /// Stop and keep progress, `Start` resumes.
+    Suspend,
+}
+
 impl Default for CreateMode {
     fn default() -> CreateMode { CreateMode::Gentle }
 }
 
+/// What names a pool for `zpool import`: GUID if the scan printed it, name otherwise.
+fn import_id(pool: &Zpool) -> String {
+    pool.id().map_or_else(|| pool.name().clone(), |id| id.to_string())
+}
+
 /// Interface to manage zpools. This documentation implies that you know how to use [`zpool(8)`](https://www.freebsd.org/cgi/man.cgi?zpool(8)).
 pub trait ZpoolEngine {
     /// Check if pool with given name exists. NOTE: this won't return
@@ -311,6 +545,12 @@ pub trait ZpoolEngine {
     ///   [`CreateZpoolRequest`](vdev/enum.CreateVdevRequest.html) for more information.
     fn create(&self, request: CreateZpoolRequest) -> ZpoolResult<()>;
 
+    /// Check the request and devices without creating anything (`zpool create -n`). Returns the
+    /// layout zpool would have built.
+    ///
+    /// * request - A request to create a zpool.
+    fn create_dry_run(&self, request: CreateZpoolRequest) -> ZpoolResult<PoolLayout>;
+
     /// Destroy zpool. NOTE: returns `Ok(())` if pool doesn't exist.
     ///
     /// * `name` - Name of the zpool.
@@ -363,6 +603,36 @@ pub trait ZpoolEngine {
             self.set_property(&name, "failmode", props.fail_mode())?;
         }
 
+        if let Some(ref bootfs) = props.boot_fs() {
+            if current.boot_fs() != props.boot_fs() {
+                properties::check_bootfs(name.as_ref(), bootfs)?;
+                self.set_property(&name, "bootfs", bootfs)?;
+            }
+        }
+
+        if let Some(multihost) = props.multihost() {
+            if current.multihost() != multihost {
+                self.set_property(&name, "multihost", multihost)?;
+            }
+        }
+
+        if let Some(auto_trim) = props.auto_trim() {
+            if current.auto_trim() != auto_trim {
+                self.set_property(&name, "autotrim", auto_trim)?;
+            }
+        }
+
+        if let Some(list_snapshots) = props.list_snapshots() {
+            if current.list_snapshots() != list_snapshots {
+                self.set_property(&name, "listsnapshots", list_snapshots)?;
+            }
+        }
+
+        // Not part of `read_properties`: reading it would break pools on OpenZFS before 2.1.
+        if let Some(compatibility) = props.compatibility() {
+            self.set_property(&name, "compatibility", compatibility)?;
+        }
+
         self.read_properties(name)
     }
 
@@ -379,6 +649,78 @@ pub trait ZpoolEngine {
         value: &P,
     ) -> ZpoolResult<()>;
 
+    /// Dataset the boot loader will boot from, if set.
+    ///
+    /// * `name` - Name of the zpool.
+    fn bootfs<N: AsRef<str>>(&self, name: N) -> ZpoolResult<Option<String>> {
+        Ok(self.read_properties(name)?.boot_fs().clone())
+    }
+
+    /// Set or clear `bootfs`. Dataset has to be inside the pool, otherwise `InvalidProperty` is
+    /// returned and nothing is changed.
+    ///
+    /// * `name` - Name of the zpool.
+    /// * `bootfs` - Dataset to boot from or `None` to clear the property.
+    fn set_bootfs<N: AsRef<str>>(&self, name: N, bootfs: Option<&str>) -> ZpoolResult<()> {
+        let value = match bootfs {
+            Some(bootfs) => {
+                properties::check_bootfs(name.as_ref(), bootfs)?;
+                bootfs.to_string()
+            },
+            None => String::new(),
+        };
+        self.set_property(name, "bootfs", &value)
+    }
+
+    /// Read properties of a vdev (OpenZFS 2.2+).
+    ///
+    /// * `name` - Name of the zpool.
+    /// * `vdev` - Device name or top-level vdev name like `mirror-0`.
+    fn read_vdev_properties<N: AsRef<str>, V: AsRef<OsStr>>(
+        &self,
+        name: N,
+        vdev: V,
+    ) -> ZpoolResult<VdevProperties>;
+
+    /// Set a property of a vdev (OpenZFS 2.2+).
+    ///
+    /// * `name` - Name of the zpool.
+    /// * `vdev` - Device name or top-level vdev name like `mirror-0`.
+    /// * `key` - Key for the property.
+    /// * `value` - Any [supported](properties/trait.PropPair.html) value.
+    fn set_vdev_property<N: AsRef<str>, V: AsRef<OsStr>, P: PropPair>(
+        &self,
+        name: N,
+        vdev: V,
+        key: &str,
+        value: &P,
+    ) -> ZpoolResult<()>;
+
+    /// Set several properties of a vdev (OpenZFS 2.2+). Properties are set one by one, so an error
+    /// can leave some of them changed.
+    ///
+    /// * `name` - Name of the zpool.
+    /// * `vdev` - Device name or top-level vdev name like `mirror-0`.
+    /// * `props` - Properties to set. Unset fields are left alone.
+    fn update_vdev_properties<N: AsRef<str>, V: AsRef<OsStr>>(
+        &self,
+        name: N,
+        vdev: V,
+        props: VdevPropertiesWrite,
+    ) -> ZpoolResult<()>;
+
+    /// Same as [`status`](#tymethod.status), but with properties attached to every vdev and
+    /// device (OpenZFS 2.2+).
+    ///
+    /// * `name` - Name of the zpool.
+    fn status_with_vdev_properties<N: AsRef<str>>(&self, name: N) -> ZpoolResult<Zpool>;
+
+    /// Same as [`status`](#tymethod.status), but with state of TRIM and initialization of every
+    /// device (`zpool status -i -t`). See [`maintenance`](maintenance/index.html).
+    ///
+    /// * `name` - Name of the zpool.
+    fn status_with_maintenance<N: AsRef<str>>(&self, name: N) -> ZpoolResult<Zpool>;
+
     /// Exports the given pools from the system.
     ///
     /// * `name` - Name of the zpool.
@@ -394,21 +736,99 @@ pub trait ZpoolEngine {
     ///   from files.
     fn available_in_dir(&self, dir: PathBuf) -> ZpoolResult<Vec<Zpool>>;
 
-    /// Import pool from `/dev/`.
-    fn import<N: AsRef<str>>(&self, name: N) -> ZpoolResult<()>;
+    /// Same as [`available`](#tymethod.available), but recently destroyed pools are listed too.
+    /// They have [`destroyed`](struct.Zpool.html#method.destroyed) set and are imported with
+    /// [`ImportRequest::destroyed`](import/struct.ImportRequest.html#method.destroyed).
+    ///
+    /// * `dir` - Directory to look for pools in instead of `/dev/`.
+    fn available_including_destroyed(&self, dir: Option<PathBuf>) -> ZpoolResult<Vec<Zpool>>;
+
+    /// Import pool from `/dev/`. Returns warnings that import printed, see
+    /// [`import`](import/index.html).
+    fn import<N: AsRef<str>>(&self, name: N) -> ZpoolResult<Vec<ImportWarning>> {
+        self.import_with(ImportRequest::new(name.as_ref()))
+    }
+
+    /// Import pool from `dir`. Returns warnings that import printed.
+    ///
+    /// * `dir` - Directory to look for pools. Useful when you are looking for pool that created
+    ///   from files.
+    fn import_from_dir<N: AsRef<str>>(
+        &self,
+        name: N,
+        dir: PathBuf,
+    ) -> ZpoolResult<Vec<ImportWarning>> {
+        self.import_with(ImportRequest::new(name.as_ref()).with_dir(dir))
+    }
+
+    /// Import pool as described by the request: forced, read-only, under altroot, without log
+    /// devices or rewound. Returns warnings that import printed.
+    ///
+    /// * `request` - What to import and how, see
+    ///   [`ImportRequest`](import/struct.ImportRequest.html).
+    fn import_with(&self, request: ImportRequest) -> ZpoolResult<Vec<ImportWarning>>;
+
+    /// Import every pool that [`available`](#tymethod.available) finds. Pools are imported one by
+    /// one by GUID, so pools with the same name don't get in the way and a pool that fails to
+    /// import doesn't stop the rest. Returns name of every pool with result of its import.
+    fn import_all(&self) -> ZpoolResult<Vec<(String, ZpoolResult<Vec<ImportWarning>>)>> {
+        let pools = self.available()?;
+        Ok(pools.iter().map(|pool| (pool.name().clone(), self.import(import_id(pool)))).collect())
+    }
 
-    /// Import pool from `dir`.
+    /// Same as [`import_all`](#method.import_all), but pools are looked for in `dir`.
     ///
     /// * `dir` - Directory to look for pools. Useful when you are looking for pool that created
     ///   from files.
-    fn import_from_dir<N: AsRef<str>>(&self, name: N, dir: PathBuf) -> ZpoolResult<()>;
+    fn import_all_from_dir(
+        &self,
+        dir: PathBuf,
+    ) -> ZpoolResult<Vec<(String, ZpoolResult<Vec<ImportWarning>>)>> {
+        let pools = self.available_in_dir(dir.clone())?;
+        let import = |pool: &Zpool| self.import_from_dir(import_id(pool), dir.clone());
+        Ok(pools.iter().map(|pool| (pool.name().clone(), import(pool))).collect())
+    }
 
-    /// Get the detailed status of the given pools.
+    /// Import pool and rewind it to its checkpoint, discarding every change made after
+    /// [`checkpoint`](#tymethod.checkpoint). Pool must be exported. The checkpoint itself is
+    /// discarded in the process.
+    ///
+    /// * `name` - Name of the zpool.
+    /// * `dir` - Directory to look for pools in, `/dev/` if `None`.
+    fn import_rewind_to_checkpoint<N: AsRef<str>>(
+        &self,
+        name: N,
+        dir: Option<PathBuf>,
+    ) -> ZpoolResult<Vec<ImportWarning>>;
+
+    /// Take a checkpoint of the pool before risky maintenance: the pool can be brought back to
+    /// this state with [`import_rewind_to_checkpoint`](#tymethod.import_rewind_to_checkpoint).
+    /// While checkpoint exists, space freed after it is not reclaimed, and some operations (e.g.
+    /// removing vdevs) are refused.
+    ///
+    /// * `name` - Name of the zpool.
+    fn checkpoint<N: AsRef<str>>(&self, name: N) -> ZpoolResult<()>;
+
+    /// Discard the checkpoint once maintenance went well.
+    ///
+    /// * `name` - Name of the zpool.
+    fn discard_checkpoint<N: AsRef<str>>(&self, name: N) -> ZpoolResult<()>;
+
+    /// Get the detailed status of the given pools, including files with permanent errors
+    /// (`zpool status -v`).
     fn status<N: AsRef<str>>(&self, name: N) -> ZpoolResult<Zpool>;
 
     /// Get a status of each active (imported) pool in the system
     fn all(&self) -> ZpoolResult<Vec<Zpool>>;
 
+    /// Get size, usage and health of each active pool (`zpool list`). Much cheaper than
+    /// [`all`](#tymethod.all).
+    fn list(&self) -> ZpoolResult<Vec<PoolSummary>>;
+
+    /// Get a status of active pools that are not healthy or have errors (`zpool status -x`).
+    /// Returns an empty list if everything is fine. Doesn't list files with permanent errors.
+    fn unhealthy(&self) -> ZpoolResult<Vec<Zpool>>;
+
     /// Begins a scrub or resumes a paused scrub. The scrub examines all data
     /// in the specified pools to verify that it checksums correctly. For
     /// replicated (mirror or raidz) devices, ZFS automatically repairs any
@@ -426,11 +846,87 @@ pub trait ZpoolEngine {
     /// * `name` - Name of the zpool.
     fn pause_scrub<N: AsRef<str>>(&self, name: N) -> ZpoolResult<()>;
 
+    /// Resume paused scrub. Unlike [`scrub`](#tymethod.scrub) it won't start a new scrub if
+    /// there is nothing to resume.
+    ///
+    /// * `name` - Name of the zpool.
+    fn resume_scrub<N: AsRef<str>>(&self, name: N) -> ZpoolResult<()>;
+
     ///  Stop scrubbing.
     ///
     /// * `name` - Name of the zpool.
     fn stop_scrub<N: AsRef<str>>(&self, name: N) -> ZpoolResult<()>;
 
+    /// Poll [`status`](#tymethod.status) every `interval` and pass
+    /// [`ScanStatus`](scan/enum.ScanStatus.html) to `callback` until scrub or resilver is no
+    /// longer running (finished, canceled or paused). Returns the last state, `None` if status
+    /// has no `scan:` line.
+    ///
+    /// * `name` - Name of the zpool.
+    /// * `interval` - Time between polls.
+    /// * `callback` - Called after every poll, including the last one.
+    fn watch_scan<N: AsRef<str>, F: FnMut(&ScanStatus)>(
+        &self,
+        name: N,
+        interval: Duration,
+        mut callback: F,
+    ) -> ZpoolResult<Option<ScanStatus>> {
+        loop {
+            let scan = self.status(name.as_ref())?.scan().clone();
+            if let Some(ref status) = scan {
+                callback(status);
+            }
+            if !scan.as_ref().map_or(false, ScanStatus::is_running) {
+                return Ok(scan);
+            }
+            thread::sleep(interval);
+        }
+    }
+
+    /// Read history of commands (and, if asked, internal events) that modified the pool.
+    ///
+    /// * `name` - Name of the zpool.
+    /// * `internal` - Include internal events (`zpool history -i`).
+    fn history<N: AsRef<str>>(&self, name: N, internal: bool) -> ZpoolResult<Vec<HistoryRecord>>;
+
+    /// Read all events the kernel keeps (`zpool events -v`), oldest first.
+    fn events(&self) -> ZpoolResult<Vec<ZpoolEvent>>;
+
+    /// Read I/O statistics of the pool. Numbers are averages since the pool was imported.
+    ///
+    /// * `name` - Name of the zpool.
+    fn iostat<N: AsRef<str>>(&self, name: N) -> ZpoolResult<IoStat>;
+
+    /// Read I/O statistics of the pool broken down per vdev and per device.
+    ///
+    /// * `name` - Name of the zpool.
+    fn iostat_vdevs<N: AsRef<str>>(&self, name: N) -> ZpoolResult<PoolIoStat>;
+
+    /// Read average latencies and/or queue depths of the pool and every vdev, in the order
+    /// `zpool iostat -v` prints them.
+    ///
+    /// * `name` - Name of the zpool.
+    /// * `latency` - Include average latencies (`-l`).
+    /// * `queues` - Include queue depths (`-q`).
+    fn iostat_ext<N: AsRef<str>>(
+        &self,
+        name: N,
+        latency: bool,
+        queues: bool,
+    ) -> ZpoolResult<Vec<IoStatExt>>;
+
+    /// Read latency histograms of the pool and every vdev.
+    ///
+    /// * `name` - Name of the zpool.
+    fn iostat_histograms<N: AsRef<str>>(&self, name: N) -> ZpoolResult<Vec<LatencyHistogram>>;
+
+    /// Clear device errors in a pool. If the pool is faulted because of a transient issue (e.g. loose
+    /// cable) this brings it back.
+    ///
+    /// * `name` - Name of the zpool.
+    /// * `device` - Device to clear errors of. All devices of the pool if `None`.
+    fn clear<N: AsRef<str>>(&self, name: N, device: Option<&OsStr>) -> ZpoolResult<()>;
+
     /// Takes the specified physical device offline. While the device is
     /// offline, no attempt is made to read or write to the device.
     ///
@@ -494,7 +990,8 @@ pub trait ZpoolEngine {
     /// Add a ZIL to existing Zpool.
     ///
     /// * `name` - Name of the zpool
-    /// * `new_zil` - A VDEV to use as ZIL
+    /// * `new_zil` - A VDEV to use as ZIL: single disk or mirror. RAID-Z fails with
+    ///   `InvalidTopology`.
     /// * `add_mode` - Disable some safety checks
     fn add_zil<N: AsRef<str>>(
         &self,
@@ -536,6 +1033,23 @@ pub trait ZpoolEngine {
         name: N,
         old_disk: D,
         new_disk: O,
+    ) -> ZpoolResult<()> {
+        self.replace(name, old_disk, new_disk, CreateMode::Gentle)
+    }
+
+    /// Replace a device with another and start resilvering onto it. Returns as soon as resilver
+    /// has started, use [`status`](#tymethod.status) to follow its progress.
+    ///
+    /// * `name` - Name of the zpool
+    /// * `old_disk` - A disk to be replaced.
+    /// * `new_disk` - A new disk.
+    /// * `mode` - `CreateMode::Force` uses `new_disk` even if it appears to be in use.
+    fn replace<N: AsRef<str>, D: AsRef<OsStr>, O: AsRef<OsStr>>(
+        &self,
+        name: N,
+        old_disk: D,
+        new_disk: O,
+        mode: CreateMode,
     ) -> ZpoolResult<()>;
 
     /// Remove Spare, Cache or log device
@@ -543,6 +1057,109 @@ pub trait ZpoolEngine {
     /// * `name` - Name of the zpool
     /// * `device` - Name of the device or path to sparse file.
     fn remove<N: AsRef<str>, D: AsRef<OsStr>>(&self, name: N, device: D) -> ZpoolResult<()>;
+
+    /// Remove top-level vdev. Data is evacuated to the remaining vdevs in the background, use
+    /// [`status`](#tymethod.status) and [`Zpool::removal`](struct.Zpool.html#method.removal) to
+    /// follow the progress. Pools with raidz vdevs don't support this.
+    ///
+    /// * `name` - Name of the zpool
+    /// * `vdev` - Name of the vdev (e.g. `mirror-1`) or a path to the device.
+    fn remove_vdev<N: AsRef<str>, D: AsRef<OsStr>>(&self, name: N, vdev: D) -> ZpoolResult<()> {
+        self.remove(name, vdev)
+    }
+
+    /// Stop removal of top-level vdev that is in progress.
+    ///
+    /// * `name` - Name of the zpool
+    fn cancel_removal<N: AsRef<str>>(&self, name: N) -> ZpoolResult<()>;
+
+    /// Enable all features supported by running ZFS on the pool. Can't be undone. See
+    /// [`upgrade`](upgrade/index.html).
+    ///
+    /// * `name` - Name of the zpool
+    fn upgrade<N: AsRef<str>>(&self, name: N) -> ZpoolResult<()>;
+
+    /// Enable all supported features on every pool.
+    fn upgrade_all(&self) -> ZpoolResult<()>;
+
+    /// Check whether the pool is behind running ZFS: legacy version or disabled features.
+    ///
+    /// * `name` - Name of the zpool
+    fn upgrade_status<N: AsRef<str>>(&self, name: N) -> ZpoolResult<UpgradeStatus>;
+
+    /// Wipe ZFS labels from a device that is not part of an imported pool, for example left over
+    /// from a destroyed pool. Returns
+    /// [`ZpoolError::ActiveVdev`](enum.ZpoolError.html#variant.ActiveVdev) if device belongs to an
+    /// imported pool and [`ZpoolError::VdevReuse`](enum.ZpoolError.html#variant.VdevReuse) if it
+    /// belongs to an exported one and `mode` is not `Force`.
+    ///
+    /// * `device` - Device to clear.
+    /// * `mode` - Strategy to use when device belongs to a pool.
+    fn labelclear<D: AsRef<OsStr>>(&self, device: D, mode: LabelClearMode) -> ZpoolResult<()>;
+
+    /// Start TRIM of free space on devices of the pool. Returns once it's started, see
+    /// [`trim`](trim/index.html).
+    ///
+    /// * `name` - Name of the zpool
+    /// * `options` - Devices, rate and secure TRIM.
+    fn trim<N: AsRef<str>>(&self, name: N, options: TrimOptions) -> ZpoolResult<()>;
+
+    /// Start, cancel or suspend initialization: writing a pattern to unallocated space, so thin
+    /// provisioned or cloud disks don't pay for the first write later. Progress is reported by
+    /// [`status_with_maintenance`](trait.ZpoolEngine.html#tymethod.status_with_maintenance).
+    ///
+    /// * `name` - Name of the zpool
+    /// * `devices` - Devices to act on. Empty means every device of the pool.
+    /// * `action` - What to do.
+    fn initialize<N: AsRef<str>, D: AsRef<OsStr>>(
+        &self,
+        name: N,
+        devices: &[D],
+        action: InitializeAction,
+    ) -> ZpoolResult<()>;
+
+    /// Split one device off every mirror of the pool into a new pool. See
+    /// [`split`](split/index.html).
+    ///
+    /// * `name` - Name of the zpool to split.
+    /// * `new_name` - Name of the new zpool.
+    /// * `options` - Devices to take and whether to import the new pool.
+    fn split<N: AsRef<str>, M: AsRef<str>>(
+        &self,
+        name: N,
+        new_name: M,
+        options: SplitOptions,
+    ) -> ZpoolResult<()>;
+
+    /// Give the pool a new random GUID. Pools cloned below ZFS (e.g. SAN snapshots of every
+    /// device) share GUID with the original, and only one of them can be imported at a time until
+    /// one is reguided. Pool must be imported.
+    ///
+    /// * `name` - Name of the zpool.
+    fn reguid<N: AsRef<str>>(&self, name: N) -> ZpoolResult<()>;
+
+    /// Close and open again every device of the pool, e.g. after multipath or udev changed the
+    /// paths under it. No export and import is needed. Scrub in progress is restarted.
+    ///
+    /// * `name` - Name of the zpool.
+    fn reopen<N: AsRef<str>>(&self, name: N) -> ZpoolResult<()>;
+
+    /// Force a transaction group sync, so everything written so far is on disk when this returns.
+    /// Useful right before taking crash-consistent snapshots of devices outside ZFS.
+    ///
+    /// * `name` - Name of the zpool, every imported pool if `None`.
+    fn sync(&self, name: Option<&str>) -> ZpoolResult<()>;
+
+    /// Open the pool: get a [`ZpoolHandle`](handle/struct.ZpoolHandle.html) that refuses to change
+    /// the pool once its name refers to a different pool.
+    ///
+    /// * `name` - Name of the zpool.
+    fn open<N: AsRef<str>>(&self, name: N) -> ZpoolResult<ZpoolHandle<'_, Self>>
+    where
+        Self: Sized,
+    {
+        ZpoolHandle::new(self, name)
+    }
 }
 
 #[cfg(test)]
@@ -667,6 +1284,41 @@ mod test {
         assert_eq!(ZpoolErrorKind::NoSuchDevice, err.kind());
     }
 
+    #[test]
+    fn test_cannot_replace() {
+        let text = b"cannot replace /vdevs/vdev3 with /vdevs/vdev6: no such device in pool\n";
+        let err = ZpoolError::from_stderr(text);
+        assert_eq!(ZpoolErrorKind::NoSuchDevice, err.kind());
+
+        let text = b"cannot replace /vdevs/vdev0 with /vdevs/vdev2: device is too small\n";
+        let err = ZpoolError::from_stderr(text);
+        assert_eq!(ZpoolErrorKind::DeviceTooSmall, err.kind());
+
+        let text = b"cannot replace /vdevs/vdev0 with /vdevs/vdev2: already in replacing/spare config; wait for completion or use 'zpool detach'\n";
+        let err = ZpoolError::from_stderr(text);
+        assert_eq!(ZpoolErrorKind::CannotReplace, err.kind());
+        if let ZpoolError::CannotReplace(reason) = err {
+            assert_eq!(
+                "already in replacing/spare config; wait for completion or use 'zpool detach'",
+                reason
+            );
+        }
+    }
+
+    #[test]
+    fn test_cannot_remove() {
+        let text = b"cannot remove /vdevs/vdev0: invalid config; all top-level vdevs must have the same sector size and not be raidz.\n";
+        let err = ZpoolError::from_stderr(text);
+        assert_eq!(ZpoolErrorKind::CannotRemove, err.kind());
+    }
+
+    #[test]
+    fn test_clear_no_such_device() {
+        let text = b"cannot clear errors for /vdevs/vdev9: no such device in pool\n";
+        let err = ZpoolError::from_stderr(text);
+        assert_eq!(ZpoolErrorKind::NoSuchDevice, err.kind());
+    }
+
     #[test]
     fn test_only_device() {
         let text = b"cannot detach /vdevs/vdev0: only applicable to mirror and replacing vdevs";
@@ -681,10 +1333,97 @@ mod test {
         assert_eq!(ZpoolErrorKind::MismatchedReplicationLevel, err.kind());
     }
 
+    #[test]
+    fn test_active_on_other_host() {
+        let text = b"cannot import 'tank': pool was previously in use from another system.\nLast accessed by storage-01 (hostid=a8c08002) at Mon Jul 26 10:00:00 2021\nThe pool can be imported, use 'zpool import -f' to import the pool.\n";
+        let err = ZpoolError::from_stderr(text);
+        assert_eq!(ZpoolErrorKind::ActiveOnOtherHost, err.kind());
+        if let ZpoolError::ActiveOnOtherHost(host) = err {
+            assert_eq!(&Some(String::from("storage-01")), host.hostname());
+            assert_eq!(&Some(0xa8c0_8002), host.hostid());
+            assert!(host.can_force_import());
+        }
+
+        let text = b"cannot import 'tank': pool is imported on host 'storage-02' (hostid=0x1a2b3c4d).\nExport the pool on the other system, then run 'zpool import'.\n";
+        let err = ZpoolError::from_stderr(text);
+        if let ZpoolError::ActiveOnOtherHost(host) = err {
+            assert_eq!(&Some(String::from("storage-02")), host.hostname());
+            assert_eq!(&Some(0x1a2b_3c4d), host.hostid());
+            assert!(!host.can_force_import());
+        } else {
+            panic!("wrong error");
+        }
+    }
+
     #[test]
     fn test_invalid_cache_device() {
         let text = b"cannot add to 'asd': cache device must be a disk or disk slice\n?";
         let err = ZpoolError::from_stderr(text);
         assert_eq!(ZpoolErrorKind::InvalidCacheDevice, err.kind());
     }
+
+    #[test]
+    fn test_checkpoint() {
+        let text = b"cannot checkpoint 'tank': checkpoint exists\n";
+        let err = ZpoolError::from_stderr(text);
+        assert_eq!(ZpoolErrorKind::CheckpointExists, err.kind());
+
+        let text = b"cannot discard checkpoint in 'tank': checkpoint does not exist\n";
+        let err = ZpoolError::from_stderr(text);
+        assert_eq!(ZpoolErrorKind::NoCheckpoint, err.kind());
+    }
+
+    #[test]
+    fn test_labelclear_member() {
+        let text = b"labelclear operation failed.\n\tVdev /dev/sdb is a member (ACTIVE), of pool \"tank\".\n\tTo remove label information from this device, export or destroy\n\tthe pool, or remove /dev/sdb from the configuration of this pool\n\tand retry the labelclear operation.\n";
+        let err = ZpoolError::from_stderr(text);
+        assert_eq!(ZpoolErrorKind::ActiveVdev, err.kind());
+        if let ZpoolError::ActiveVdev(vdev, pool) = err {
+            assert_eq!("/dev/sdb", vdev);
+            assert_eq!("tank", pool);
+        }
+
+        let text = b"use '-f' to override the following error:\n/dev/sdb is a member of exported pool \"tank\"\n";
+        let err = ZpoolError::from_stderr(text);
+        assert_eq!(ZpoolErrorKind::VdevReuse, err.kind());
+
+        let text = b"labelclear operation failed.\n\tVdev /dev/sdc is a member (SPARE), of pool \"tank\".\n";
+        let err = ZpoolError::from_stderr(text);
+        if let ZpoolError::VdevReuse(vdev, pool) = err {
+            assert_eq!("/dev/sdc", vdev);
+            assert_eq!("tank", pool);
+        } else {
+            panic!("unexpected error");
+        }
+    }
+
+    #[test]
+    fn test_trim_not_supported() {
+        let text = b"cannot trim 'sda': trim operations are not supported by this device\n";
+        let err = ZpoolError::from_stderr(text);
+        assert_eq!(ZpoolErrorKind::TrimNotSupported, err.kind());
+        if let ZpoolError::TrimNotSupported(device) = err {
+            assert_eq!("sda", device);
+        }
+
+        let text = b"cannot trim 'tank': no devices in pool support trim operations\n";
+        let err = ZpoolError::from_stderr(text);
+        assert_eq!(ZpoolErrorKind::TrimNotSupported, err.kind());
+    }
+
+    #[test]
+    fn test_cannot_split() {
+        let text = b"Unable to split tank: Source pool must be composed only of mirrors\n";
+        let err = ZpoolError::from_stderr(text);
+        assert_eq!(ZpoolErrorKind::CannotSplit, err.kind());
+        let expected = "cannot split pool: Source pool must be composed only of mirrors";
+        assert_eq!(expected, err.to_string());
+    }
+
+    #[test]
+    fn test_not_initializing() {
+        let text = b"cannot initialize 'sda': there is no active initialization\n";
+        let err = ZpoolError::from_stderr(text);
+        assert_eq!(ZpoolErrorKind::NotInitializing, err.kind());
+    }
 }
@@ -21,35 +21,58 @@
 //!
 //! It's called [open3](https://docs.ruby-lang.org/en/2.0.0/Open3.html) because it opens `stdin`, `stdout`, `stderr`.
 
-use std::{env,
+use std::{collections::HashMap,
+          env,
           ffi::{OsStr, OsString},
+          fmt, io,
+          io::Read,
+          os::unix::ffi::{OsStrExt, OsStringExt},
           path::PathBuf,
-          process::{Command, Output, Stdio}};
+          process::{Child, Command, ExitStatus, Output, Stdio},
+          time::Duration};
 
-use crate::{parsers::{Rule, StdoutParser},
-            zpool::description::Zpool,
+use crate::{args::{check_pair, pair_arg},
+            parsers::parse_zpools_with,
+            utils::{Overflow, ParseMode},
+            zpool::{description::Zpool,
+                    events::{parse_events, EventStream},
+                    iostat::IoStatStream,
+                    list::LIST_COLUMNS,
+                    properties::parse_features},
             GlobalLogger};
-use pest::Parser;
 use slog::Logger;
 
 use super::{CreateMode, CreateVdevRequest, CreateZpoolRequest, DestroyMode, ExportMode,
-            OfflineMode, OnlineMode, PropPair, ZpoolEngine, ZpoolError, ZpoolProperties,
-            ZpoolResult};
+            FeatureState, HistoryRecord, ImportRequest, ImportWarning, InitializeAction, IoStat,
+            IoStatExt, LabelClearMode, LatencyHistogram, OfflineMode, OnlineMode, PoolIoStat,
+            PoolLayout, PoolSummary, PropPair, ScrubState, SplitOptions, TrimOptions, UpgradeStatus,
+            VdevProperties, VdevPropertiesWrite, ZpoolEngine, ZpoolError, ZpoolEvent,
+            ZpoolProperties, ZpoolPropertiesRef, ZpoolResult};
 
 lazy_static! {
     static ref ZPOOL_PROP_ARG: OsString = {
-        let mut arg = OsString::with_capacity(171);
+        let mut arg = OsString::with_capacity(218);
         arg.push("alloc,cap,comment,dedupratio,expandsize,fragmentation,free,");
         arg.push("freeing,guid,health,size,leaked,altroot,readonly,autoexpand,");
-        arg.push("autoreplace,bootfs,cachefile,dedupditto,delegation,failmode");
+        arg.push("autoreplace,bootfs,cachefile,dedupditto,delegation,failmode,ashift,");
+        arg.push("multihost,autotrim,listsnapshots");
         arg
     };
 }
+
+/// What `zpool status -x` prints instead of pools when there is nothing to report.
+const ALL_HEALTHY: &str = "all pools are healthy";
+const NO_POOLS: &str = "no pools available";
+
 /// Open3 implementation of [`ZpoolEngine`](../trait.ZpoolEngine.html). You can use
 /// `ZpoolOpen3::default` to create it.
 pub struct ZpoolOpen3 {
-    cmd_name: OsString,
-    logger:   Logger,
+    cmd_name:   OsString,
+    cmd_args:   Vec<OsString>,
+    quote_args: bool,
+    logger:     Logger,
+    overflow:   Overflow,
+    parse_mode: ParseMode,
 }
 
 impl Default for ZpoolOpen3 {
@@ -63,7 +86,14 @@ impl Default for ZpoolOpen3 {
 
         let logger =
             GlobalLogger::get().new(o!("zetta_module" => "zpool", "zpool_impl" => "open3"));
-        ZpoolOpen3 { cmd_name, logger }
+        ZpoolOpen3 {
+            cmd_name,
+            cmd_args:   Vec::new(),
+            quote_args: false,
+            logger,
+            overflow:   Overflow::default(),
+            parse_mode: ParseMode::default(),
+        }
     }
 }
 impl ZpoolOpen3 {
@@ -75,52 +105,160 @@ impl ZpoolOpen3 {
         z
     }
 
-    fn zpool(&self) -> Command { Command::new(&self.cmd_name) }
+    /// Run `zpool` on a remote host over `ssh`. Authentication must be non-interactive (keys or
+    /// agent), because `ssh` runs with `BatchMode=yes`. Arguments are passed through the remote
+    /// shell, so each of them is quoted for a POSIX shell.
+    ///
+    /// * `host` - Destination as understood by `ssh` (`host`, `user@host` or an alias from
+    /// `ssh_config`).
+    pub fn over_ssh<H: Into<OsString>>(host: H) -> ZpoolOpen3 {
+        let mut z = ZpoolOpen3::default();
+        z.cmd_args = vec!["-o".into(), "BatchMode=yes".into(), host.into(), "zpool".into()];
+        z.cmd_name = "ssh".into();
+        z.quote_args = true;
+        z
+    }
+
+    /// Set what to do with property values that don't fit into their type. By default it's an
+    /// error.
+    pub fn with_overflow(mut self, overflow: Overflow) -> ZpoolOpen3 {
+        self.overflow = overflow;
+        self
+    }
+
+    /// Set what to do with output of `zpool status`, `zpool import` and `zpool list` that isn't
+    /// recognized. By default it's skipped.
+    pub fn with_parse_mode(mut self, parse_mode: ParseMode) -> ZpoolOpen3 {
+        self.parse_mode = parse_mode;
+        self
+    }
+
+    /// Same as [`read_properties`](trait.ZpoolEngine.html#tymethod.read_properties), but reads
+    /// output of `zpool` into `buf` and returns a view into it. Reuse the same buffer when polling
+    /// pool often to avoid allocating on every call.
+    ///
+    /// * `name` - Name of the zpool.
+    /// * `buf` - Buffer for `zpool` output. Cleared before use.
+    pub fn read_properties_into<'b, N: AsRef<str>>(
+        &self,
+        name: N,
+        buf: &'b mut String,
+    ) -> ZpoolResult<ZpoolPropertiesRef<'b>> {
+        let mut z = self.zpool();
+        z.args(&["list", "-p", "-H", "-o"]);
+        z.arg(&*ZPOOL_PROP_ARG);
+        z.arg(name.as_ref());
+        z.stdout(Stdio::piped());
+        z.stderr(Stdio::piped());
+        debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
+        let mut child = z.spawn()?;
+        buf.clear();
+        if let Some(mut stdout) = child.stdout.take() {
+            stdout.read_to_string(buf)?;
+        }
+        let mut stderr = Vec::new();
+        if let Some(mut err) = child.stderr.take() {
+            err.read_to_end(&mut stderr)?;
+        }
+        if child.wait()?.success() {
+            if self.parse_mode == ParseMode::Strict {
+                ZpoolPropertiesRef::check_strict(buf)?;
+            }
+            ZpoolPropertiesRef::parse_with(buf, self.overflow)
+        } else {
+            Err(ZpoolError::from_stderr(&stderr))
+        }
+    }
+
+    /// Keep `zpool iostat` running and yield a sample of the pool every `interval`. Samples
+    /// cover only the last interval, unlike [`iostat`](trait.ZpoolEngine.html#tymethod.iostat).
+    /// `zpool` is killed when stream is dropped.
+    ///
+    /// * `name` - Name of the zpool.
+    /// * `interval` - Time between samples. Rounded down to whole seconds, at least 1 second.
+    pub fn iostat_stream<N: AsRef<str>>(
+        &self,
+        name: N,
+        interval: Duration,
+    ) -> ZpoolResult<IoStatStream> {
+        let mut z = self.zpool();
+        z.args(&["iostat", "-H", "-p", "-y"]);
+        z.arg(name.as_ref());
+        z.arg(interval.as_secs().max(1).to_string());
+        z.stdout(Stdio::piped());
+        z.stderr(Stdio::piped());
+        debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
+        IoStatStream::new(z.spawn()?)
+    }
+
+    /// Keep `zpool events -f` running and yield events as they are emitted, starting with the
+    /// ones that are already in the log. `zpool` is killed when stream is dropped.
+    pub fn events_follow(&self) -> ZpoolResult<EventStream> {
+        let mut z = self.zpool();
+        z.args(&["events", "-H", "-v", "-f"]);
+        z.stdout(Stdio::piped());
+        z.stderr(Stdio::piped());
+        debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
+        EventStream::new(z.spawn()?)
+    }
+
+    fn zpool(&self) -> ZpoolCommand {
+        let mut cmd = Command::new(&self.cmd_name);
+        cmd.args(&self.cmd_args);
+        ZpoolCommand { cmd, quote_args: self.quote_args }
+    }
 
     #[allow(dead_code)]
     /// Force disable logging by using `/dev/null` as drain.
-    fn zpool_mute(&self) -> Command {
+    fn zpool_mute(&self) -> ZpoolCommand {
         let mut z = self.zpool();
         z.stdout(Stdio::null());
         z.stderr(Stdio::null());
         z
     }
 
-    fn zpools_from_import(&self, out: Output) -> ZpoolResult<Vec<Zpool>> {
+    /// Feature flags of the pool out of `zpool get all`.
+    fn read_features(&self, name: &str) -> ZpoolResult<HashMap<String, FeatureState>> {
+        let mut z = self.zpool();
+        z.args(&["get", "-Hp", "all", name]);
+        debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
+        let out = z.output()?;
         if out.status.success() {
-            let stdout: String = String::from_utf8_lossy(&out.stdout).into();
-            StdoutParser::parse(Rule::zpools, stdout.as_ref())
-                .map_err(|_| ZpoolError::ParseError)
-                .map(|pairs| pairs.map(Zpool::from_pest_pair).collect())
+            parse_features(name, &String::from_utf8_lossy(&out.stdout))
         } else {
-            if out.stderr.is_empty() && out.stdout.is_empty() {
-                return Ok(Vec::new());
-            }
             Err(ZpoolError::from_stderr(&out.stderr))
         }
     }
-}
-
-impl ZpoolEngine for ZpoolOpen3 {
-    fn exists<N: AsRef<str>>(&self, name: N) -> ZpoolResult<bool> {
-        let mut z = self.zpool_mute();
-        z.arg("list").arg(name.as_ref());
-        debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
-        let status = z.status()?;
-        Ok(status.success())
-    }
 
-    fn create(&self, request: CreateZpoolRequest) -> ZpoolResult<()> {
-        if !request.is_suitable_for_create() {
-            return Err(ZpoolError::InvalidTopology);
-        }
+    /// `zpool create` for given request. With `dry_run` zpool only prints the layout.
+    fn create_command(
+        &self,
+        request: CreateZpoolRequest,
+        dry_run: bool,
+    ) -> ZpoolResult<ZpoolCommand> {
+        request.validate_for_create()?;
         let mut z = self.zpool();
         z.arg("create");
+        if dry_run {
+            z.arg("-n");
+        }
         if request.create_mode() == &CreateMode::Force {
             z.arg("-f");
         }
+        if *request.no_features() {
+            z.arg("-d");
+        }
+        for arg in request.feature_args()? {
+            z.arg("-o");
+            z.arg(arg);
+        }
+        if let Some(arg) = request.ashift_arg()? {
+            z.arg("-o");
+            z.arg(arg);
+        }
         if let Some(props) = request.props().clone() {
             for arg in props.into_args() {
+                check_pair(&arg)?;
                 z.arg("-o");
                 z.arg(arg);
             }
@@ -135,6 +273,100 @@ impl ZpoolEngine for ZpoolOpen3 {
         }
         z.arg(request.name());
         z.args(request.into_args());
+        Ok(z)
+    }
+
+    fn zpools_from_import(&self, out: Output) -> ZpoolResult<Vec<Zpool>> {
+        if out.status.success() {
+            parse_zpools_with(&String::from_utf8_lossy(&out.stdout), self.parse_mode)
+        } else {
+            if out.stderr.is_empty() && out.stdout.is_empty() {
+                return Ok(Vec::new());
+            }
+            Err(ZpoolError::from_stderr(&out.stderr))
+        }
+    }
+}
+
+/// `zpool` invocation. Quotes arguments on the way in if they go through a remote shell.
+struct ZpoolCommand {
+    cmd:        Command,
+    quote_args: bool,
+}
+
+impl ZpoolCommand {
+    fn arg<S: AsRef<OsStr>>(&mut self, arg: S) -> &mut ZpoolCommand {
+        if self.quote_args {
+            self.cmd.arg(shell_quote(arg.as_ref()));
+        } else {
+            self.cmd.arg(arg);
+        }
+        self
+    }
+
+    fn args<I, S>(&mut self, args: I) -> &mut ZpoolCommand
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<OsStr>,
+    {
+        for arg in args {
+            self.arg(arg);
+        }
+        self
+    }
+
+    fn stdout<T: Into<Stdio>>(&mut self, cfg: T) -> &mut ZpoolCommand {
+        self.cmd.stdout(cfg);
+        self
+    }
+
+    fn stderr<T: Into<Stdio>>(&mut self, cfg: T) -> &mut ZpoolCommand {
+        self.cmd.stderr(cfg);
+        self
+    }
+
+    fn output(&mut self) -> io::Result<Output> { self.cmd.output() }
+
+    fn spawn(&mut self) -> io::Result<Child> { self.cmd.spawn() }
+
+    fn status(&mut self) -> io::Result<ExitStatus> { self.cmd.status() }
+}
+
+impl fmt::Debug for ZpoolCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result { self.cmd.fmt(f) }
+}
+
+/// Quote `arg` for a POSIX shell, unless the shell would pass it as is anyway.
+fn shell_quote(arg: &OsStr) -> OsString {
+    let bytes = arg.as_bytes();
+    let plain = |b: &u8| b.is_ascii_alphanumeric() || b"@%+=:,./_-".contains(b);
+    if !bytes.is_empty() && bytes.iter().all(plain) {
+        return arg.to_os_string();
+    }
+    let mut quoted = Vec::with_capacity(bytes.len() + 2);
+    quoted.push(b'\'');
+    for &b in bytes {
+        if b == b'\'' {
+            quoted.extend_from_slice(b"'\\''");
+        } else {
+            quoted.push(b);
+        }
+    }
+    quoted.push(b'\'');
+    OsString::from_vec(quoted)
+}
+
+impl ZpoolEngine for ZpoolOpen3 {
+    fn exists<N: AsRef<str>>(&self, name: N) -> ZpoolResult<bool> {
+        let mut z = self.zpool_mute();
+        z.arg("list").arg(name.as_ref());
+        debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
+        let status = z.status()?;
+        Ok(status.success())
+    }
+
+    fn create(&self, request: CreateZpoolRequest) -> ZpoolResult<()> {
+        let mut z = self.create_command(request, false)?;
         debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
         let out = z.output()?;
         if out.status.success() {
@@ -144,6 +376,17 @@ impl ZpoolEngine for ZpoolOpen3 {
         }
     }
 
+    fn create_dry_run(&self, request: CreateZpoolRequest) -> ZpoolResult<PoolLayout> {
+        let mut z = self.create_command(request, true)?;
+        debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
+        let out = z.output()?;
+        if out.status.success() {
+            PoolLayout::parse_dry_run(&String::from_utf8_lossy(&out.stdout))
+        } else {
+            Err(ZpoolError::from_stderr(&out.stderr))
+        }
+    }
+
     fn destroy<N: AsRef<str>>(&self, name: N, mode: DestroyMode) -> ZpoolResult<()> {
         let mut z = self.zpool_mute();
         z.arg("destroy");
@@ -163,7 +406,11 @@ impl ZpoolEngine for ZpoolOpen3 {
         debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
         let out = z.output()?;
         if out.status.success() {
-            ZpoolProperties::try_from_stdout(&out.stdout)
+            if self.parse_mode == ParseMode::Strict {
+                ZpoolPropertiesRef::check_strict(&String::from_utf8_lossy(&out.stdout))?;
+            }
+            let props = ZpoolProperties::try_from_stdout(&out.stdout, self.overflow)?;
+            Ok(props.with_features(self.read_features(name.as_ref())?))
         } else {
             Err(ZpoolError::from_stderr(&out.stderr))
         }
@@ -177,7 +424,7 @@ impl ZpoolEngine for ZpoolOpen3 {
     ) -> ZpoolResult<()> {
         let mut z = self.zpool();
         z.arg("set");
-        z.arg(OsString::from(PropPair::to_pair(value, key)));
+        z.arg(pair_arg(key, value)?);
         z.arg(name.as_ref());
         debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
         let out = z.output()?;
@@ -188,6 +435,94 @@ impl ZpoolEngine for ZpoolOpen3 {
         }
     }
 
+    fn read_vdev_properties<N: AsRef<str>, V: AsRef<OsStr>>(
+        &self,
+        name: N,
+        vdev: V,
+    ) -> ZpoolResult<VdevProperties> {
+        let mut z = self.zpool();
+        z.args(&["get", "-Hp", "all", name.as_ref()]);
+        z.arg(vdev.as_ref());
+        debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
+        let out = z.output()?;
+        if out.status.success() {
+            VdevProperties::parse_stdout(&String::from_utf8_lossy(&out.stdout))?
+                .into_iter()
+                .next()
+                .ok_or(ZpoolError::ParseError)
+        } else {
+            Err(ZpoolError::from_stderr(&out.stderr))
+        }
+    }
+
+    fn set_vdev_property<N: AsRef<str>, V: AsRef<OsStr>, P: PropPair>(
+        &self,
+        name: N,
+        vdev: V,
+        key: &str,
+        value: &P,
+    ) -> ZpoolResult<()> {
+        let mut z = self.zpool();
+        z.arg("set");
+        z.arg(pair_arg(key, value)?);
+        z.arg(name.as_ref());
+        z.arg(vdev.as_ref());
+        debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
+        let out = z.output()?;
+        if out.status.success() {
+            Ok(())
+        } else {
+            Err(ZpoolError::from_stderr(&out.stderr))
+        }
+    }
+
+    fn update_vdev_properties<N: AsRef<str>, V: AsRef<OsStr>>(
+        &self,
+        name: N,
+        vdev: V,
+        props: VdevPropertiesWrite,
+    ) -> ZpoolResult<()> {
+        for arg in props.into_args() {
+            check_pair(&arg)?;
+            let mut z = self.zpool();
+            z.arg("set");
+            z.arg(arg);
+            z.arg(name.as_ref());
+            z.arg(vdev.as_ref());
+            debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
+            let out = z.output()?;
+            if !out.status.success() {
+                return Err(ZpoolError::from_stderr(&out.stderr));
+            }
+        }
+        Ok(())
+    }
+
+    fn status_with_vdev_properties<N: AsRef<str>>(&self, name: N) -> ZpoolResult<Zpool> {
+        let zpool = self.status(&name)?;
+        let mut z = self.zpool();
+        z.args(&["get", "-Hp", "all", name.as_ref(), "all-vdevs"]);
+        debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
+        let out = z.output()?;
+        if out.status.success() {
+            let props = VdevProperties::parse_stdout(&String::from_utf8_lossy(&out.stdout))?;
+            Ok(zpool.with_vdev_properties(&props))
+        } else {
+            Err(ZpoolError::from_stderr(&out.stderr))
+        }
+    }
+
+    fn status_with_maintenance<N: AsRef<str>>(&self, name: N) -> ZpoolResult<Zpool> {
+        let mut z = self.zpool();
+        z.args(&["status", "-i", "-t", name.as_ref()]);
+        debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
+        let out = z.output()?;
+        self.zpools_from_import(out)?
+            .into_iter()
+            .find(|zpool| zpool.name().as_str() == name.as_ref())
+            .ok_or(ZpoolError::PoolNotFound)
+    }
+
     fn export<N: AsRef<str>>(&self, name: N, mode: ExportMode) -> ZpoolResult<()> {
         let mut z = self.zpool();
         z.arg("export");
@@ -222,12 +557,62 @@ impl ZpoolEngine for ZpoolOpen3 {
         self.zpools_from_import(out)
     }
 
-    fn import<N: AsRef<str>>(&self, name: N) -> Result<(), ZpoolError> {
+    fn available_including_destroyed(&self, dir: Option<PathBuf>) -> ZpoolResult<Vec<Zpool>> {
+        let mut zpools = match dir {
+            Some(ref dir) => self.available_in_dir(dir.clone())?,
+            None => self.available()?,
+        };
+        let mut z = self.zpool();
+        z.args(&["import", "-D"]);
+        if let Some(dir) = dir {
+            z.arg("-d");
+            z.arg(dir);
+        }
+        debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
+        let out = z.output()?;
+        zpools.extend(self.zpools_from_import(out)?);
+        Ok(zpools)
+    }
+
+    fn import_with(&self, request: ImportRequest) -> ZpoolResult<Vec<ImportWarning>> {
         let mut z = self.zpool();
         z.arg("import");
+        z.args(request.into_args()?);
+        debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
+        let out = z.output()?;
+        if out.status.success() {
+            Ok(ImportWarning::from_output(&out.stdout, &out.stderr))
+        } else {
+            Err(ZpoolError::from_stderr(&out.stderr))
+        }
+    }
+
+    fn import_rewind_to_checkpoint<N: AsRef<str>>(
+        &self,
+        name: N,
+        dir: Option<PathBuf>,
+    ) -> ZpoolResult<Vec<ImportWarning>> {
+        let mut z = self.zpool();
+        z.args(&["import", "--rewind-to-checkpoint"]);
+        if let Some(dir) = dir {
+            z.arg("-d");
+            z.arg(dir);
+        }
         z.arg(name.as_ref());
         debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
         let out = z.output()?;
+        if out.status.success() {
+            Ok(ImportWarning::from_output(&out.stdout, &out.stderr))
+        } else {
+            Err(ZpoolError::from_stderr(&out.stderr))
+        }
+    }
+
+    fn checkpoint<N: AsRef<str>>(&self, name: N) -> ZpoolResult<()> {
+        let mut z = self.zpool();
+        z.args(&["checkpoint", name.as_ref()]);
+        debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
+        let out = z.output()?;
         if out.status.success() {
             Ok(())
         } else {
@@ -235,12 +620,9 @@ impl ZpoolEngine for ZpoolOpen3 {
         }
     }
 
-    fn import_from_dir<N: AsRef<str>>(&self, name: N, dir: PathBuf) -> ZpoolResult<()> {
+    fn discard_checkpoint<N: AsRef<str>>(&self, name: N) -> ZpoolResult<()> {
         let mut z = self.zpool();
-        z.arg("import");
-        z.arg("-d");
-        z.arg(dir);
-        z.arg(name.as_ref());
+        z.args(&["checkpoint", "-d", name.as_ref()]);
         debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
         let out = z.output()?;
         if out.status.success() {
@@ -252,12 +634,11 @@ impl ZpoolEngine for ZpoolOpen3 {
 
     fn status<N: AsRef<str>>(&self, name: N) -> ZpoolResult<Zpool> {
         let mut z = self.zpool();
-        z.arg("status");
+        z.args(&["status", "-v"]);
         z.arg(name.as_ref());
         debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
         let out = z.output()?;
-        let zpools =
-            self.zpools_from_import(out).expect("Failed to unwrap zpool from status check");
+        let zpools = self.zpools_from_import(out)?;
         if zpools.is_empty() {
             return Err(ZpoolError::PoolNotFound);
         }
@@ -270,12 +651,37 @@ impl ZpoolEngine for ZpoolOpen3 {
 
     fn all(&self) -> ZpoolResult<Vec<Zpool>> {
         let mut z = self.zpool();
-        z.arg("status");
+        z.args(&["status", "-v"]);
         debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
         let out = z.output()?;
         self.zpools_from_import(out)
     }
 
+    fn list(&self) -> ZpoolResult<Vec<PoolSummary>> {
+        let mut z = self.zpool();
+        z.args(&["list", "-H", "-p", "-o", LIST_COLUMNS]);
+        debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
+        let out = z.output()?;
+        if out.status.success() {
+            PoolSummary::parse_stdout(&String::from_utf8_lossy(&out.stdout))
+        } else {
+            Err(ZpoolError::from_stderr(&out.stderr))
+        }
+    }
+
+    fn unhealthy(&self) -> ZpoolResult<Vec<Zpool>> {
+        let mut z = self.zpool();
+        z.args(&["status", "-x"]);
+        debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
+        let out = z.output()?;
+        let stdout = String::from_utf8_lossy(&out.stdout);
+        let trimmed = stdout.trim();
+        if out.status.success() && (trimmed == ALL_HEALTHY || trimmed == NO_POOLS) {
+            return Ok(Vec::new());
+        }
+        self.zpools_from_import(out)
+    }
+
     fn scrub<N: AsRef<str>>(&self, name: N) -> ZpoolResult<()> {
         let mut z = self.zpool();
         z.arg("scrub");
@@ -303,6 +709,13 @@ impl ZpoolEngine for ZpoolOpen3 {
         }
     }
 
+    fn resume_scrub<N: AsRef<str>>(&self, name: N) -> ZpoolResult<()> {
+        if self.status(&name)?.scrub() != Some(ScrubState::Paused) {
+            return Err(ZpoolError::NoActiveScrubs);
+        }
+        self.scrub(name)
+    }
+
     fn stop_scrub<N: AsRef<str>>(&self, name: N) -> ZpoolResult<()> {
         let mut z = self.zpool();
         z.arg("scrub");
@@ -317,6 +730,118 @@ impl ZpoolEngine for ZpoolOpen3 {
         }
     }
 
+    fn history<N: AsRef<str>>(&self, name: N, internal: bool) -> ZpoolResult<Vec<HistoryRecord>> {
+        let mut z = self.zpool();
+        z.arg("history");
+        z.arg("-l");
+        if internal {
+            z.arg("-i");
+        }
+        z.arg(name.as_ref());
+        debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
+        let out = z.output()?;
+        if out.status.success() {
+            Ok(HistoryRecord::parse_history(&String::from_utf8_lossy(&out.stdout)))
+        } else {
+            Err(ZpoolError::from_stderr(&out.stderr))
+        }
+    }
+
+    fn events(&self) -> ZpoolResult<Vec<ZpoolEvent>> {
+        let mut z = self.zpool();
+        z.args(&["events", "-v"]);
+        debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
+        let out = z.output()?;
+        if out.status.success() {
+            Ok(parse_events(&String::from_utf8_lossy(&out.stdout)))
+        } else {
+            Err(ZpoolError::from_stderr(&out.stderr))
+        }
+    }
+
+    fn iostat<N: AsRef<str>>(&self, name: N) -> ZpoolResult<IoStat> {
+        let mut z = self.zpool();
+        z.args(&["iostat", "-H", "-p"]);
+        z.arg(name.as_ref());
+        debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
+        let out = z.output()?;
+        if out.status.success() {
+            IoStat::parse_stdout(&String::from_utf8_lossy(&out.stdout))?
+                .into_iter()
+                .next()
+                .ok_or(ZpoolError::ParseError)
+        } else {
+            Err(ZpoolError::from_stderr(&out.stderr))
+        }
+    }
+
+    fn iostat_vdevs<N: AsRef<str>>(&self, name: N) -> ZpoolResult<PoolIoStat> {
+        let zpool = self.status(&name)?;
+        let mut z = self.zpool();
+        z.args(&["iostat", "-H", "-p", "-v"]);
+        z.arg(name.as_ref());
+        debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
+        let out = z.output()?;
+        if out.status.success() {
+            PoolIoStat::from_stdout(&zpool, &String::from_utf8_lossy(&out.stdout))
+        } else {
+            Err(ZpoolError::from_stderr(&out.stderr))
+        }
+    }
+
+    fn iostat_ext<N: AsRef<str>>(
+        &self,
+        name: N,
+        latency: bool,
+        queues: bool,
+    ) -> ZpoolResult<Vec<IoStatExt>> {
+        let mut z = self.zpool();
+        z.args(&["iostat", "-H", "-p", "-v"]);
+        if latency {
+            z.arg("-l");
+        }
+        if queues {
+            z.arg("-q");
+        }
+        z.arg(name.as_ref());
+        debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
+        let out = z.output()?;
+        if out.status.success() {
+            IoStatExt::parse_stdout(&String::from_utf8_lossy(&out.stdout), latency, queues)
+        } else {
+            Err(ZpoolError::from_stderr(&out.stderr))
+        }
+    }
+
+    fn iostat_histograms<N: AsRef<str>>(&self, name: N) -> ZpoolResult<Vec<LatencyHistogram>> {
+        let mut z = self.zpool();
+        z.args(&["iostat", "-H", "-p", "-v", "-w"]);
+        z.arg(name.as_ref());
+        debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
+        let out = z.output()?;
+        if out.status.success() {
+            LatencyHistogram::parse_stdout(&String::from_utf8_lossy(&out.stdout))
+        } else {
+            Err(ZpoolError::from_stderr(&out.stderr))
+        }
+    }
+
+    fn clear<N: AsRef<str>>(&self, name: N, device: Option<&OsStr>) -> ZpoolResult<()> {
+        let mut z = self.zpool();
+        z.arg("clear");
+        z.arg(name.as_ref());
+        if let Some(device) = device {
+            z.arg(device);
+        }
+        debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
+        let out = z.output()?;
+        if out.status.success() {
+            Ok(())
+        } else {
+            Err(ZpoolError::from_stderr(&out.stderr))
+        }
+    }
+
     fn take_offline<N: AsRef<str>, D: AsRef<OsStr>>(
         &self,
         name: N,
@@ -401,6 +926,7 @@ impl ZpoolEngine for ZpoolOpen3 {
         new_vdev: CreateVdevRequest,
         add_mode: CreateMode,
     ) -> Result<(), ZpoolError> {
+        new_vdev.validate()?;
         let mut z = self.zpool();
         z.arg("add");
         if add_mode == CreateMode::Force {
@@ -423,6 +949,7 @@ impl ZpoolEngine for ZpoolOpen3 {
         new_zil: CreateVdevRequest,
         add_mode: CreateMode,
     ) -> Result<(), ZpoolError> {
+        new_zil.validate_log()?;
         let mut z = self.zpool();
         z.arg("add");
         if add_mode == CreateMode::Force {
@@ -486,14 +1013,18 @@ impl ZpoolEngine for ZpoolOpen3 {
         }
     }
 
-    fn replace_disk<N: AsRef<str>, D: AsRef<OsStr>, O: AsRef<OsStr>>(
+    fn replace<N: AsRef<str>, D: AsRef<OsStr>, O: AsRef<OsStr>>(
         &self,
         name: N,
         old_disk: D,
         new_disk: O,
+        mode: CreateMode,
     ) -> Result<(), ZpoolError> {
         let mut z = self.zpool();
         z.arg("replace");
+        if mode == CreateMode::Force {
+            z.arg("-f");
+        }
         z.arg(name.as_ref());
         z.arg(old_disk.as_ref());
         z.arg(new_disk.as_ref());
@@ -519,4 +1050,202 @@ impl ZpoolEngine for ZpoolOpen3 {
             Err(ZpoolError::from_stderr(&out.stderr))
         }
     }
+
+    fn cancel_removal<N: AsRef<str>>(&self, name: N) -> ZpoolResult<()> {
+        let mut z = self.zpool();
+        z.arg("remove");
+        z.arg("-s");
+        z.arg(name.as_ref());
+        debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
+        let out = z.output()?;
+        if out.status.success() {
+            Ok(())
+        } else {
+            Err(ZpoolError::from_stderr(&out.stderr))
+        }
+    }
+
+    fn upgrade<N: AsRef<str>>(&self, name: N) -> ZpoolResult<()> {
+        let mut z = self.zpool();
+        z.args(&["upgrade", name.as_ref()]);
+        debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
+        let out = z.output()?;
+        if out.status.success() {
+            Ok(())
+        } else {
+            Err(ZpoolError::from_stderr(&out.stderr))
+        }
+    }
+
+    fn upgrade_all(&self) -> ZpoolResult<()> {
+        let mut z = self.zpool();
+        z.args(&["upgrade", "-a"]);
+        debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
+        let out = z.output()?;
+        if out.status.success() {
+            Ok(())
+        } else {
+            Err(ZpoolError::from_stderr(&out.stderr))
+        }
+    }
+
+    fn labelclear<D: AsRef<OsStr>>(&self, device: D, mode: LabelClearMode) -> ZpoolResult<()> {
+        let mut z = self.zpool();
+        z.arg("labelclear");
+        if let LabelClearMode::Force = mode {
+            z.arg("-f");
+        }
+        z.arg(device.as_ref());
+        debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
+        let out = z.output()?;
+        if out.status.success() {
+            Ok(())
+        } else {
+            Err(ZpoolError::from_stderr(&out.stderr))
+        }
+    }
+
+    fn trim<N: AsRef<str>>(&self, name: N, options: TrimOptions) -> ZpoolResult<()> {
+        let (options, devices) = options.into_args();
+        let mut z = self.zpool();
+        z.arg("trim");
+        z.args(options);
+        z.arg(name.as_ref());
+        z.args(devices);
+        debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
+        let out = z.output()?;
+        if out.status.success() {
+            Ok(())
+        } else {
+            Err(ZpoolError::from_stderr(&out.stderr))
+        }
+    }
+
+    fn initialize<N: AsRef<str>, D: AsRef<OsStr>>(
+        &self,
+        name: N,
+        devices: &[D],
+        action: InitializeAction,
+    ) -> ZpoolResult<()> {
+        let mut z = self.zpool();
+        z.arg("initialize");
+        match action {
+            InitializeAction::Start => {},
+            InitializeAction::Cancel => {
+                z.arg("-c");
+            },
+            InitializeAction::Suspend => {
+                z.arg("-s");
+            },
+        }
+        z.arg(name.as_ref());
+        z.args(devices.iter().map(AsRef::as_ref));
+        debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
+        let out = z.output()?;
+        if out.status.success() {
+            Ok(())
+        } else {
+            Err(ZpoolError::from_stderr(&out.stderr))
+        }
+    }
+
+    fn split<N: AsRef<str>, M: AsRef<str>>(
+        &self,
+        name: N,
+        new_name: M,
+        options: SplitOptions,
+    ) -> ZpoolResult<()> {
+        let (options, devices) = options.into_args();
+        let mut z = self.zpool();
+        z.arg("split");
+        z.args(options);
+        z.args(&[name.as_ref(), new_name.as_ref()]);
+        z.args(devices);
+        debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
+        let out = z.output()?;
+        if out.status.success() {
+            Ok(())
+        } else {
+            Err(ZpoolError::from_stderr(&out.stderr))
+        }
+    }
+
+    fn reguid<N: AsRef<str>>(&self, name: N) -> ZpoolResult<()> {
+        let mut z = self.zpool();
+        z.args(&["reguid", name.as_ref()]);
+        debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
+        let out = z.output()?;
+        if out.status.success() {
+            Ok(())
+        } else {
+            Err(ZpoolError::from_stderr(&out.stderr))
+        }
+    }
+
+    fn reopen<N: AsRef<str>>(&self, name: N) -> ZpoolResult<()> {
+        let mut z = self.zpool();
+        z.args(&["reopen", name.as_ref()]);
+        debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
+        let out = z.output()?;
+        if out.status.success() {
+            Ok(())
+        } else {
+            Err(ZpoolError::from_stderr(&out.stderr))
+        }
+    }
+
+    fn sync(&self, name: Option<&str>) -> ZpoolResult<()> {
+        let mut z = self.zpool();
+        z.arg("sync");
+        z.args(name);
+        debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
+        let out = z.output()?;
+        if out.status.success() {
+            Ok(())
+        } else {
+            Err(ZpoolError::from_stderr(&out.stderr))
+        }
+    }
+
+    fn upgrade_status<N: AsRef<str>>(&self, name: N) -> ZpoolResult<UpgradeStatus> {
+        let mut z = self.zpool();
+        z.args(&["get", "-Hp", "all", name.as_ref()]);
+        debug!(self.logger, "executing"; "cmd" => format_args!("{:?}", z));
+        let out = z.output()?;
+        if out.status.success() {
+            UpgradeStatus::parse_stdout(name.as_ref(), &String::from_utf8_lossy(&out.stdout))
+        } else {
+            Err(ZpoolError::from_stderr(&out.stderr))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn ssh_quotes_args() {
+        let zpool = ZpoolOpen3::over_ssh("root@backup-01");
+        let mut z = zpool.zpool();
+        z.args(&["status", "-P"]).arg("my pool; rm -rf /").arg("it's").arg("");
+        let args: Vec<&OsStr> = z.cmd.get_args().collect();
+        let expected = [
+            "-o",
+            "BatchMode=yes",
+            "root@backup-01",
+            "zpool",
+            "status",
+            "-P",
+            "'my pool; rm -rf /'",
+            "'it'\\''s'",
+            "''",
+        ];
+        assert_eq!(expected.iter().map(OsStr::new).collect::<Vec<_>>(), args);
+
+        let zpool = ZpoolOpen3::with_cmd("zpool");
+        let mut z = zpool.zpool();
+        z.arg("my pool");
+        assert_eq!(vec![OsStr::new("my pool")], z.cmd.get_args().collect::<Vec<_>>());
+    }
 }
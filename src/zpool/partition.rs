@@ -0,0 +1,159 @@
+//! Partition layout helpers for whole-disk vdevs.
+//!
+//! When `zpool(8)` is given a whole disk on ZFS on Linux it quietly writes a GPT label with two
+//! partitions: a data partition that starts at 1MiB and a small reserved partition at the end of
+//! the disk. Provisioning code that mixes ZFS with other partitions needs to know where those
+//! partitions end up, so this module computes the exact layout `zpool` would produce without
+//! touching the disk.
+//!
+//! Only available with `disk-management` feature, because acting on this information means
+//! rewriting partition tables.
+//!
+//! ### Usage
+//! ```rust
+//! use libzetta::zpool::partition::WholeDiskLayout;
+//!
+//! // 1TB drive with 512 byte sectors
+//! let layout = WholeDiskLayout::for_disk(1_000_204_886_016, 512).unwrap();
+//! assert_eq!(2048, layout.data().start());
+//! assert_eq!(9, layout.reserved().number());
+//! ```
+
+/// First usable sector of the data partition. Gives 1MiB alignment on 512 byte sector disks.
+pub const START_SECTOR: u64 = 2048;
+/// Data partition size is aligned down to this many sectors.
+pub const END_ALIGNMENT: u64 = 2048;
+/// Size of reserved partition in sectors.
+pub const RESERVED_SECTORS: u64 = 16384;
+/// Size of GPT partition entry array in bytes.
+const GPT_ENTRIES_SIZE: u64 = 16384;
+
+/// What partition is used for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PartitionKind {
+    /// Partition that holds actual ZFS data. GPT type `6a898cc3-1dd2-11b2-99a6-080020736631`.
+    Data,
+    /// Reserved partition at the end of the disk. GPT type `6a945a3b-1dd2-11b2-99a6-080020736631`.
+    Reserved,
+}
+
+impl PartitionKind {
+    /// GPT partition type GUID that `zpool` uses for this kind.
+    pub fn type_guid(self) -> &'static str {
+        match self {
+            PartitionKind::Data => "6a898cc3-1dd2-11b2-99a6-080020736631",
+            PartitionKind::Reserved => "6a945a3b-1dd2-11b2-99a6-080020736631",
+        }
+    }
+}
+
+/// A single partition in the layout. All values are in sectors.
+#[derive(Debug, Clone, PartialEq, Eq, CopyGetters)]
+#[get_copy = "pub"]
+pub struct Partition {
+    /// Partition number as it appears in device name (e.g. `sda1`, `sda9`).
+    number: u8,
+    /// What this partition is for.
+    kind:   PartitionKind,
+    /// First sector of the partition.
+    start:  u64,
+    /// Size of the partition in sectors.
+    size:   u64,
+}
+
+impl Partition {
+    /// Last sector of the partition (inclusive).
+    pub fn end(&self) -> u64 { self.start + self.size - 1 }
+}
+
+/// Layout `zpool` creates when whole disk is given to it.
+#[derive(Debug, Clone, PartialEq, Eq, Getters)]
+#[get = "pub"]
+pub struct WholeDiskLayout {
+    /// Sector size used for calculations.
+    sector_size: u64,
+    /// Data partition.
+    data:        Partition,
+    /// Reserved partition.
+    reserved:    Partition,
+}
+
+impl WholeDiskLayout {
+    /// Compute layout for disk of given size. Returns `None` if disk is too small to hold both
+    /// partitions or `sector_size` isn't a power of two.
+    ///
+    /// * `disk_size` - Size of the disk in bytes.
+    /// * `sector_size` - Logical sector size of the disk in bytes.
+    pub fn for_disk(disk_size: u64, sector_size: u64) -> Option<WholeDiskLayout> {
+        if sector_size == 0 || !sector_size.is_power_of_two() {
+            return None;
+        }
+        let sectors = disk_size / sector_size;
+        // Backup GPT: header and partition entries.
+        let backup_gpt = 1 + GPT_ENTRIES_SIZE / sector_size;
+        let last_usable = sectors.checked_sub(backup_gpt + 1)?;
+
+        let data_size = (last_usable + 1).checked_sub(RESERVED_SECTORS + START_SECTOR)?;
+        let data_size = data_size - data_size % END_ALIGNMENT;
+        if data_size == 0 {
+            return None;
+        }
+
+        let data =
+            Partition { number: 1, kind: PartitionKind::Data, start: START_SECTOR, size: data_size };
+        let reserved = Partition {
+            number: 9,
+            kind:   PartitionKind::Reserved,
+            start:  START_SECTOR + data_size,
+            size:   RESERVED_SECTORS,
+        };
+        Some(WholeDiskLayout { sector_size, data, reserved })
+    }
+
+    /// Partitions in the order they appear on disk.
+    pub fn partitions(&self) -> [&Partition; 2] { [&self.data, &self.reserved] }
+
+    /// Usable size of data partition in bytes.
+    pub fn data_bytes(&self) -> u64 { self.data.size * self.sector_size }
+
+    /// First sector after the reserved partition. Everything from here up to backup GPT is
+    /// unused.
+    pub fn first_free_sector(&self) -> u64 { self.reserved.end() + 1 }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn layout_512() {
+        // 1GiB disk
+        let layout = WholeDiskLayout::for_disk(1 << 30, 512).unwrap();
+        let sectors = (1u64 << 30) / 512;
+
+        assert_eq!(START_SECTOR, layout.data().start());
+        assert_eq!(0, layout.data().size() % END_ALIGNMENT);
+        assert_eq!(layout.data().end() + 1, layout.reserved().start());
+        assert_eq!(RESERVED_SECTORS, layout.reserved().size());
+        // Must not overlap backup GPT.
+        assert!(layout.reserved().end() < sectors - 33);
+        assert_eq!(layout.data().size() * 512, layout.data_bytes());
+    }
+
+    #[test]
+    fn layout_4k() {
+        let layout = WholeDiskLayout::for_disk(1 << 32, 4096).unwrap();
+        let sectors = (1u64 << 32) / 4096;
+        assert!(layout.reserved().end() < sectors - 5);
+        assert_eq!(1, layout.data().number());
+        assert_eq!(9, layout.reserved().number());
+    }
+
+    #[test]
+    fn too_small() {
+        assert!(WholeDiskLayout::for_disk(8 << 20, 512).is_none());
+        assert!(WholeDiskLayout::for_disk(0, 512).is_none());
+        assert!(WholeDiskLayout::for_disk(1 << 30, 0).is_none());
+        assert!(WholeDiskLayout::for_disk(1 << 30, 520).is_none());
+    }
+}
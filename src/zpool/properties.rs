@@ -1,9 +1,16 @@
 //! Consumer friendly representation of Zpool's properties.
 
-use std::{ffi::OsString, path::PathBuf};
+use std::{collections::HashMap,
+          ffi::OsString,
+          path::{Path, PathBuf}};
 
 use super::{ZpoolError, ZpoolResult};
-use crate::utils::parse_float;
+use crate::{args::ArgError,
+            names::DatasetName,
+            utils::{is_overflow, parse_int, Bounded, Overflow, Percent, Ratio}};
+
+/// Number of columns in a line of `zpool list` that `ZpoolPropertiesRef::parse` reads.
+const COLUMNS: usize = 25;
 
 /// Implement this for your custom properties.
 pub trait PropPair {
@@ -44,10 +51,14 @@ pub enum Health {
     Offline,
     /// Spare is ready to take over failed device.
     Available,
+    /// Spare took over failed device and is in service.
+    InUse,
     /// Can't open device.
     Unavailable,
     /// Physically removed while the system was running.
     Removed,
+    /// Went into another pool with `zpool split`.
+    Split,
 }
 
 impl Health {
@@ -61,11 +72,86 @@ impl Health {
             "FAULTED" => Ok(Health::Faulted),
             "OFFLINE" => Ok(Health::Offline),
             "AVAIL" => Ok(Health::Available),
+            "INUSE" => Ok(Health::InUse),
             "UNAVAIL" => Ok(Health::Unavailable),
             "REMOVED" => Ok(Health::Removed),
+            "SPLIT" => Ok(Health::Split),
+            _ => Err(ZpoolError::ParseError),
+        }
+    }
+
+    /// Health as `zpool status` prints it.
+    pub fn as_str(&self) -> &'static str {
+        match *self {
+            Health::Online => "ONLINE",
+            Health::Degraded => "DEGRADED",
+            Health::Faulted => "FAULTED",
+            Health::Offline => "OFFLINE",
+            Health::Available => "AVAIL",
+            Health::InUse => "INUSE",
+            Health::Unavailable => "UNAVAIL",
+            Health::Removed => "REMOVED",
+            Health::Split => "SPLIT",
+        }
+    }
+}
+
+/// State of a feature flag, the value of `feature@<name>` property.
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum FeatureState {
+    /// Known to the running ZFS, but not enabled on the pool. Doesn't get in the way of importing
+    /// the pool on systems that don't know the feature.
+    Disabled,
+    /// Enabled, but nothing on disk uses it yet.
+    Enabled,
+    /// In use. Systems that don't support the feature can't import the pool, or only read-only.
+    Active,
+}
+
+impl FeatureState {
+    /// parse str to FeatureState.
+    #[doc(hidden)]
+    pub fn try_from_str(val: Option<&str>) -> ZpoolResult<FeatureState> {
+        let val_str = val.ok_or(ZpoolError::ParseError)?;
+        match val_str {
+            "disabled" => Ok(FeatureState::Disabled),
+            "enabled" => Ok(FeatureState::Enabled),
+            "active" => Ok(FeatureState::Active),
             _ => Err(ZpoolError::ParseError),
         }
     }
+
+    #[doc(hidden)]
+    pub fn as_str(&self) -> &str {
+        match *self {
+            FeatureState::Disabled => "disabled",
+            FeatureState::Enabled => "enabled",
+            FeatureState::Active => "active",
+        }
+    }
+}
+
+/// Feature flags of `pool` in output of `zpool get -Hp all <pool>`. Names are without
+/// `feature@`. Pools on legacy version numbers don't have any.
+pub(crate) fn parse_features(
+    pool: &str,
+    stdout: &str,
+) -> ZpoolResult<HashMap<String, FeatureState>> {
+    let mut features = HashMap::new();
+    for line in stdout.lines().filter(|l| !l.trim().is_empty()) {
+        let mut columns = line.split('\t');
+        let (name, key, value) = match (columns.next(), columns.next(), columns.next()) {
+            (Some(name), Some(key), Some(value)) => (name, key, value),
+            _ => return Err(ZpoolError::ParseError),
+        };
+        if name != pool {
+            continue;
+        }
+        if let Some(feature) = key.strip_prefix("feature@") {
+            features.insert(feature.to_string(), FeatureState::try_from_str(Some(value))?);
+        }
+    }
+    Ok(features)
 }
 
 /// Controls the system behavior in the event of catastrophic pool failure.
@@ -121,11 +207,14 @@ pub enum CacheType {
 impl CacheType {
     /// parse str to CacheType.
     pub fn try_from_str(val: Option<&str>) -> ZpoolResult<CacheType> {
-        let val_str = val.ok_or(ZpoolError::ParseError)?;
-        match val_str {
-            "-" | "" => Ok(CacheType::Default),
-            "none" => Ok(CacheType::None),
-            n => Ok(CacheType::Custom(String::from(n))),
+        val.map(CacheType::from_raw).ok_or(ZpoolError::ParseError)
+    }
+
+    fn from_raw(val: &str) -> CacheType {
+        match val {
+            "-" | "" => CacheType::Default,
+            "none" => CacheType::None,
+            n => CacheType::Custom(String::from(n)),
         }
     }
 
@@ -185,18 +274,33 @@ pub struct ZpoolPropertiesWrite {
     /// this property.
     #[builder(default)]
     #[builder(setter(into))]
-    comment:    String,
+    comment:        String,
     /// Controls whether a non-privileged user is granted access based on the
     /// dataset permissions defined on the dataset. See zfs(8) for more
     /// information on ZFS delegated administration.
     #[builder(default = "false")]
-    delegation: bool,
+    delegation:     bool,
     /// Controls the system behavior in the event of catastrophic pool
     /// failure. This condition is typically a result of a loss of
     /// connectivity to the underlying storage device(s) or a failure of all
     /// devices within the pool.
     #[builder(default = "FailMode::Wait")]
-    fail_mode:  FailMode,
+    fail_mode:      FailMode,
+    /// Multi-modifier protection (MMP): pool keeps writing heartbeats so another host can't
+    /// import it while it's in use. Needs a non-zero hostid, see
+    /// [`hostid`](../hostid/index.html). Only sent when set.
+    #[builder(default)]
+    multihost:      Option<bool>,
+    /// Automatically TRIM space that was freed, mostly for SSD pools. Only sent when set.
+    #[builder(default)]
+    auto_trim:      Option<bool>,
+    /// Restrict pool features to a named set (e.g. `grub2`) so the pool stays usable by other
+    /// software like boot loaders. Only sent when set. Available since OpenZFS 2.1.
+    #[builder(default)]
+    compatibility:  Option<String>,
+    /// Show snapshots in `zfs list` without `-t snapshot`. Only sent when set.
+    #[builder(default)]
+    list_snapshots: Option<bool>,
 }
 
 impl ZpoolPropertiesWrite {
@@ -205,16 +309,28 @@ impl ZpoolPropertiesWrite {
 
     #[doc(hidden)]
     pub fn into_args(self) -> Vec<OsString> {
-        let mut ret = Vec::with_capacity(7);
+        let mut ret = Vec::with_capacity(9);
         ret.push(PropPair::to_pair(&self.auto_expand, "autoexpand"));
         ret.push(PropPair::to_pair(&self.auto_replace, "autoreplace"));
         ret.push(PropPair::to_pair(&self.cache_file, "cachefile"));
         ret.push(PropPair::to_pair(&self.comment, "comment"));
         ret.push(PropPair::to_pair(&self.delegation, "delegation"));
         ret.push(PropPair::to_pair(&self.fail_mode, "failmode"));
+        if let Some(multihost) = self.multihost {
+            ret.push(PropPair::to_pair(&multihost, "multihost"));
+        }
+        if let Some(auto_trim) = self.auto_trim {
+            ret.push(PropPair::to_pair(&auto_trim, "autotrim"));
+        }
         if let Some(ref btfs) = self.boot_fs {
             ret.push(PropPair::to_pair(btfs, "bootfs"));
         }
+        if let Some(ref compatibility) = self.compatibility {
+            ret.push(PropPair::to_pair(compatibility, "compatibility"));
+        }
+        if let Some(list_snapshots) = self.list_snapshots {
+            ret.push(PropPair::to_pair(&list_snapshots, "listsnapshots"));
+        }
         ret.iter().map(OsString::from).collect()
     }
 }
@@ -230,6 +346,9 @@ impl ZpoolPropertiesWriteBuilder {
         b.cache_file(props.cache_file.clone());
         b.delegation(props.delegation);
         b.fail_mode(props.fail_mode.clone());
+        b.multihost(Some(props.multihost));
+        b.auto_trim(Some(props.auto_trim));
+        b.list_snapshots(Some(props.list_snapshots));
         if let Some(ref comment) = props.comment {
             b.comment(comment.clone());
         }
@@ -244,76 +363,101 @@ impl ZpoolPropertiesWriteBuilder {
 pub struct ZpoolProperties {
     /// Amount of storage space within the pool that has been physically
     /// allocated.
-    alloc:         usize,
+    alloc:          usize,
     /// Percentage of pool space used. Percentage.
-    capacity:      u8,
+    capacity:       Percent,
     /// A text string consisting of printable ASCII characters that will be
     /// stored such that it is
     /// available even if the pool becomes faulted. An administrator can
     /// provide additional information about a pool using this property.
-    comment:       Option<String>,
+    comment:        Option<String>,
     /// The deduplication ratio specified for a pool, expressed as a
     /// multiplier.  For example,
     /// a dedupratio value of 1.76 indicates that 1.76 units of data were
     /// stored but only 1 unit
     /// of disk space was actually consumed. See `zfs(8)` for a description of
     /// the deduplication feature.
-    dedup_ratio:   f64,
+    dedup_ratio:    Ratio,
     /// Amount of uninitialized space within the pool or device that
     /// can be used to increase the total capacity of the pool.
     /// Uninitialized space consists of any space on an EFI labeled
     /// vdev, which has not been brought online (i.e. zpool online
     /// -e).  This space occurs when a LUN is dynamically expanded.
-    expand_size:   Option<usize>,
+    expand_size:    Option<usize>,
     /// The amount of fragmentation in the pool. In percents.
-    fragmentation: i8,
+    fragmentation:  Percent,
     /// Number of blocks within the pool that are not allocated.
-    free:          i64,
+    free:           i64,
     ///  After a file system or snapshot is destroyed, the space it
     ///  was using is returned to the pool asynchronously.  freeing is
     /// the amount of space remaining to be reclaimed.  Over time
     /// freeing will decrease while free increases.
-    freeing:       i64,
+    freeing:        i64,
     /// A unique identifier for the pool.
-    guid:          u64,
+    guid:           u64,
     /// The current health of the pool.
-    health:        Health,
+    health:         Health,
     /// Total size of the storage pool.
-    size:          usize,
+    size:           usize,
     /// Leaked space?
-    leaked:        usize,
+    leaked:         usize,
     // writable
     /// Alternate root directory, can only be set during creation or import.
-    alt_root:      Option<PathBuf>,
+    alt_root:       Option<PathBuf>,
     /// Pool is read only
-    read_only:     bool,
+    read_only:      bool,
     /// Controls automatic pool expansion when the underlying LUN is grown.
-    auto_expand:   bool,
+    auto_expand:    bool,
     /// Controls automatic device replacement. If set to "on", any new device,
     /// found in the
     /// same physical location as a device that previously belonged to the
     /// pool, is automatically
     /// formatted and replaced. The default behavior is "off".
-    auto_replace:  bool,
+    auto_replace:   bool,
     ///  Identifies the default bootable dataset for the root pool.
-    boot_fs:       Option<String>,
+    boot_fs:        Option<String>,
     /// Controls the location of where the pool configuration is cached.
-    cache_file:    CacheType,
+    cache_file:     CacheType,
     /// Threshold for the number of block ditto copies. If the reference
     /// count for a deduplicated block increases above this number, a new
     /// ditto copy of this block is automatically stored. Default setting is
     /// 0 which causes no ditto copies to be created for deduplicated blocks.
     /// The minimum legal nonzero setting is 100.
-    dedup_ditto:   usize,
+    dedup_ditto:    usize,
     /// Controls whether a non-privileged user is granted access based on the
     /// dataset permissions defined on the dataset. See `zfs(8)` for more
     /// information on ZFS delegated administration.
-    delegation:    bool,
+    delegation:     bool,
     /// Controls the system behavior in the event of catastrophic pool
     /// failure. This condition is typically a result of a loss of
     /// connectivity to the underlying storage device(s) or a failure of all
     /// devices within the pool.
-    fail_mode:     FailMode,
+    fail_mode:      FailMode,
+    /// Sector size exponent new vdevs get: `12` means 4KiB sectors. `0` means ZFS picks it for
+    /// each vdev from what the devices report, which isn't always right. Effective value of
+    /// every vdev is in [`VdevProperties::ashift`](../vdevprops/struct.VdevProperties.html).
+    ashift:         u8,
+    /// Pool is protected by multi-modifier protection (MMP) from being imported on two hosts at
+    /// once.
+    multihost:      bool,
+    /// Freed space is automatically TRIMmed.
+    auto_trim:      bool,
+    /// Snapshots are shown in `zfs list` without `-t snapshot`.
+    list_snapshots: bool,
+    /// State of every feature flag the running ZFS knows, by name without `feature@`. Empty for
+    /// pools on legacy version numbers.
+    features:       HashMap<String, FeatureState>,
+}
+
+/// Check that `bootfs` is a well-formed name of a dataset in `pool`. ZFS itself only finds out
+/// when the pool is imported by a boot loader.
+pub fn check_bootfs(pool: &str, bootfs: &str) -> Result<(), ArgError> {
+    let dataset = DatasetName::new(bootfs)
+        .map_err(|_| ArgError::InvalidValue("bootfs".into(), "not a dataset name"))?;
+    if dataset.pool().as_str() != pool {
+        return Err(ArgError::InvalidValue("bootfs".into(), "dataset is in another pool"));
+    }
+    Ok(())
 }
 
 fn parse_bool(val: Option<&str>) -> ZpoolResult<bool> {
@@ -324,86 +468,183 @@ fn parse_bool(val: Option<&str>) -> ZpoolResult<bool> {
         _ => Err(ZpoolError::ParseError),
     }
 }
-fn parse_usize(val: Option<&str>) -> ZpoolResult<usize> {
+fn parse_num<T: Bounded>(val: Option<&str>, overflow: Overflow) -> ZpoolResult<T> {
     let val_str = val.ok_or(ZpoolError::ParseError)?;
-    Ok(val_str.parse()?)
-}
-fn parse_i64(val: Option<&str>) -> ZpoolResult<i64> {
-    let val_str = val.ok_or(ZpoolError::ParseError)?;
-    Ok(val_str.parse()?)
+    parse_int(val_str, overflow).map_err(|e| {
+        if is_overflow(&e) {
+            ZpoolError::Overflow(val_str.into())
+        } else {
+            ZpoolError::ParseError
+        }
+    })
 }
 fn parse_u64(val: Option<&str>) -> ZpoolResult<u64> {
     let val_str = val.ok_or(ZpoolError::ParseError)?;
     Ok(val_str.parse()?)
 }
 impl ZpoolProperties {
-    pub(crate) fn try_from_stdout(out: &[u8]) -> ZpoolResult<ZpoolProperties> {
-        let mut stdout: String = String::from_utf8_lossy(out).into();
-        // remove new line at the end.
-        stdout.pop();
-        let mut cols = stdout.split('\t');
+    pub(crate) fn try_from_stdout(
+        out: &[u8],
+        overflow: Overflow,
+    ) -> ZpoolResult<ZpoolProperties> {
+        ZpoolPropertiesRef::parse_with(&String::from_utf8_lossy(out), overflow)
+            .map(|props| props.into_owned())
+    }
 
-        let alloc = parse_usize(cols.next())?;
+    /// Same properties with these feature flags. Handy when properties are polled with
+    /// `read_properties_into`, which doesn't read feature flags.
+    pub fn with_features(mut self, features: HashMap<String, FeatureState>) -> ZpoolProperties {
+        self.features = features;
+        self
+    }
 
-        let cap_str = cols.next().ok_or(ZpoolError::ParseError)?;
-        let cap: u8 = cap_str.parse()?;
+    /// State of a feature flag. `None` if the running ZFS doesn't know it.
+    ///
+    /// * `name` - Name of the feature, with or without `feature@`.
+    pub fn feature(&self, name: &str) -> Option<FeatureState> {
+        let name = name.strip_prefix("feature@").unwrap_or(name);
+        self.features.get(name).copied()
+    }
 
-        let comment_str = cols.next().ok_or(ZpoolError::ParseError)?;
-        let comment = match comment_str {
-            "-" | "" => None,
-            c => Some(String::from(c)),
-        };
+    /// Names of features in this state, sorted. Active features are the ones another host must
+    /// support to import the pool.
+    pub fn features_in(&self, state: FeatureState) -> Vec<&str> {
+        let mut names: Vec<&str> = self
+            .features
+            .iter()
+            .filter(|(_, s)| **s == state)
+            .map(|(name, _)| name.as_str())
+            .collect();
+        names.sort_unstable();
+        names
+    }
+}
 
-        let mut dedup_ratio_string = cols.next().ok_or(ZpoolError::ParseError).map(String::from)?;
-        let dedup_ratio: f64 = parse_float(&mut dedup_ratio_string)?;
+/// Borrowed view of [`ZpoolProperties`](struct.ZpoolProperties.html). Parsing it doesn't allocate,
+/// so it's a better fit for polling pools every second. See `ZpoolOpen3::read_properties_into`.
+#[derive(Debug, Clone, PartialEq, Getters)]
+#[get = "pub"]
+pub struct ZpoolPropertiesRef<'a> {
+    /// Amount of storage space within the pool that has been physically allocated.
+    alloc:          usize,
+    /// Percentage of pool space used.
+    capacity:       Percent,
+    /// Comment.
+    comment:        Option<&'a str>,
+    /// The deduplication ratio.
+    dedup_ratio:    Ratio,
+    /// Amount of uninitialized space that can be used to increase the total capacity of the pool.
+    expand_size:    Option<usize>,
+    /// The amount of fragmentation in the pool. In percents.
+    fragmentation:  Percent,
+    /// Number of blocks within the pool that are not allocated.
+    free:           i64,
+    /// Space remaining to be reclaimed after destroy.
+    freeing:        i64,
+    /// A unique identifier for the pool.
+    guid:           u64,
+    /// The current health of the pool.
+    health:         Health,
+    /// Total size of the storage pool.
+    size:           usize,
+    /// Leaked space.
+    leaked:         usize,
+    /// Alternate root directory.
+    alt_root:       Option<&'a Path>,
+    /// Pool is read only
+    read_only:      bool,
+    /// Controls automatic pool expansion when the underlying LUN is grown.
+    auto_expand:    bool,
+    /// Controls automatic device replacement.
+    auto_replace:   bool,
+    /// Identifies the default bootable dataset for the root pool.
+    boot_fs:        Option<&'a str>,
+    /// Raw value of `cachefile`. See [`CacheType`](enum.CacheType.html).
+    cache_file:     &'a str,
+    /// Threshold for the number of block ditto copies.
+    dedup_ditto:    usize,
+    /// Controls whether a non-privileged user is granted access based on the dataset permissions.
+    delegation:     bool,
+    /// Controls the system behavior in the event of catastrophic pool failure.
+    fail_mode:      FailMode,
+    /// Sector size exponent new vdevs get, `0` if ZFS picks it.
+    ashift:         u8,
+    /// Pool is protected by multi-modifier protection (MMP).
+    multihost:      bool,
+    /// Freed space is automatically TRIMmed.
+    auto_trim:      bool,
+    /// Snapshots are shown in `zfs list` without `-t snapshot`.
+    list_snapshots: bool,
+}
 
-        let expand_size_str = cols.next().ok_or(ZpoolError::ParseError)?;
-        let expand_size: Option<usize> = match expand_size_str {
-            "-" => None,
-            c => Some(c.parse()?),
-        };
+impl<'a> ZpoolPropertiesRef<'a> {
+    /// Parse a line of `zpool list -p -H -o` with the same columns `read_properties` asks for.
+    /// Values that don't fit into their type are an error.
+    pub fn parse(line: &'a str) -> ZpoolResult<ZpoolPropertiesRef<'a>> {
+        ZpoolPropertiesRef::parse_with(line, Overflow::Error)
+    }
 
-        // remove '%'
-        let mut frag_string = cols.next().ok_or(ZpoolError::ParseError).map(String::from)?;
-        let last_char = {
-            let chars = frag_string.chars();
-            chars.last()
-        };
-        if last_char == Some('%') {
-            frag_string.pop();
+    /// Check that output of `zpool list` is exactly one line with the columns `parse` reads. Used
+    /// in [`ParseMode::Strict`](../../utils/enum.ParseMode.html).
+    pub(crate) fn check_strict(stdout: &str) -> ZpoolResult<()> {
+        let mut lines = stdout.lines().filter(|line| !line.is_empty());
+        let first = lines.next().ok_or(ZpoolError::ParseError)?;
+        if first.split('\t').count() != COLUMNS {
+            return Err(ZpoolError::UnrecognizedOutput(first.to_string()));
+        }
+        match lines.next() {
+            Some(extra) => Err(ZpoolError::UnrecognizedOutput(extra.to_string())),
+            None => Ok(()),
         }
-        let fragmentation: i8 = frag_string.parse()?;
+    }
 
-        let free = parse_i64(cols.next())?;
-        let freeing = parse_i64(cols.next())?;
+    /// Same as `parse`, but with explicit handling of values that don't fit into their type.
+    /// `guid` is never saturated.
+    pub fn parse_with(line: &'a str, overflow: Overflow) -> ZpoolResult<ZpoolPropertiesRef<'a>> {
+        let line = line.strip_suffix('\n').unwrap_or(line);
+        let mut cols = line.split('\t');
+
+        let alloc = parse_num(cols.next(), overflow)?;
+        let capacity = cols.next().ok_or(ZpoolError::ParseError)?.parse()?;
+        let comment = match cols.next().ok_or(ZpoolError::ParseError)? {
+            "-" | "" => None,
+            c => Some(c),
+        };
+        let dedup_ratio = cols.next().ok_or(ZpoolError::ParseError)?.parse()?;
+        let expand_size = match cols.next().ok_or(ZpoolError::ParseError)? {
+            "-" => None,
+            c => Some(parse_num(Some(c), overflow)?),
+        };
+        let fragmentation = cols.next().ok_or(ZpoolError::ParseError)?.parse()?;
+        let free = parse_num(cols.next(), overflow)?;
+        let freeing = parse_num(cols.next(), overflow)?;
         let guid = parse_u64(cols.next())?;
         let health = Health::try_from_str(cols.next())?;
-        let size = parse_usize(cols.next())?;
-        let leaked = parse_usize(cols.next())?;
-
-        let alt_root_str = cols.next().ok_or(ZpoolError::ParseError)?;
-        let alt_root = match alt_root_str {
+        let size = parse_num(cols.next(), overflow)?;
+        let leaked = parse_num(cols.next(), overflow)?;
+        let alt_root = match cols.next().ok_or(ZpoolError::ParseError)? {
             "-" => None,
-            r => Some(PathBuf::from(r)),
+            r => Some(Path::new(r)),
         };
-
         let read_only = parse_bool(cols.next())?;
         let auto_expand = parse_bool(cols.next())?;
         let auto_replace = parse_bool(cols.next())?;
-
-        let boot_fs_str = cols.next().ok_or(ZpoolError::ParseError)?;
-        let boot_fs = match boot_fs_str {
+        let boot_fs = match cols.next().ok_or(ZpoolError::ParseError)? {
             "-" => None,
-            r => Some(String::from(r)),
+            r => Some(r),
         };
-        let cache_file = CacheType::try_from_str(cols.next())?;
-        let dedup_ditto = parse_usize(cols.next())?;
+        let cache_file = cols.next().ok_or(ZpoolError::ParseError)?;
+        let dedup_ditto = parse_num(cols.next(), overflow)?;
         let delegation = parse_bool(cols.next())?;
         let fail_mode = FailMode::try_from_str(cols.next())?;
+        let ashift = cols.next().ok_or(ZpoolError::ParseError)?.parse()?;
+        let multihost = parse_bool(cols.next())?;
+        let auto_trim = parse_bool(cols.next())?;
+        let list_snapshots = parse_bool(cols.next())?;
 
-        Ok(ZpoolProperties {
+        Ok(ZpoolPropertiesRef {
             alloc,
-            capacity: cap,
+            capacity,
             comment,
             dedup_ratio,
             expand_size,
@@ -423,31 +664,83 @@ impl ZpoolProperties {
             dedup_ditto,
             delegation,
             fail_mode,
+            ashift,
+            multihost,
+            auto_trim,
+            list_snapshots,
         })
     }
+
+    /// Copy into owned `ZpoolProperties`. Feature flags are not part of `zpool list`, so they are
+    /// left empty.
+    pub fn into_owned(self) -> ZpoolProperties {
+        ZpoolProperties {
+            alloc:          self.alloc,
+            capacity:       self.capacity,
+            comment:        self.comment.map(String::from),
+            dedup_ratio:    self.dedup_ratio,
+            expand_size:    self.expand_size,
+            fragmentation:  self.fragmentation,
+            free:           self.free,
+            freeing:        self.freeing,
+            guid:           self.guid,
+            health:         self.health,
+            size:           self.size,
+            leaked:         self.leaked,
+            alt_root:       self.alt_root.map(PathBuf::from),
+            read_only:      self.read_only,
+            auto_expand:    self.auto_expand,
+            auto_replace:   self.auto_replace,
+            boot_fs:        self.boot_fs.map(String::from),
+            cache_file:     CacheType::from_raw(self.cache_file),
+            dedup_ditto:    self.dedup_ditto,
+            delegation:     self.delegation,
+            fail_mode:      self.fail_mode,
+            ashift:         self.ashift,
+            multihost:      self.multihost,
+            auto_trim:      self.auto_trim,
+            list_snapshots: self.list_snapshots,
+            features:       HashMap::new(),
+        }
+    }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::zpool::ZpoolErrorKind;
 
     #[test]
     fn test_defaults() {
         let built = ZpoolPropertiesWriteBuilder::default().build().unwrap();
         let handmade = ZpoolPropertiesWrite {
-            read_only:    false,
-            auto_expand:  false,
-            auto_replace: false,
-            boot_fs:      None,
-            cache_file:   CacheType::Default,
-            comment:      String::new(),
-            delegation:   false,
-            fail_mode:    FailMode::Wait,
+            read_only:      false,
+            auto_expand:    false,
+            auto_replace:   false,
+            boot_fs:        None,
+            cache_file:     CacheType::Default,
+            comment:        String::new(),
+            delegation:     false,
+            fail_mode:      FailMode::Wait,
+            multihost:      None,
+            auto_trim:      None,
+            compatibility:  None,
+            list_snapshots: None,
         };
 
         assert_eq!(handmade, built);
     }
 
+    #[test]
+    fn test_check_bootfs() {
+        assert!(check_bootfs("rpool", "rpool").is_ok());
+        assert!(check_bootfs("rpool", "rpool/ROOT/default").is_ok());
+        assert!(check_bootfs("rpool", "bpool/BOOT").is_err());
+        assert!(check_bootfs("rpool", "rpoolx/ROOT").is_err());
+        assert!(check_bootfs("rpool", "rpool/ROOT@snap").is_err());
+        assert!(check_bootfs("rpool", "").is_err());
+    }
+
     #[test]
     fn test_create_props() {
         let built = ZpoolPropertiesWriteBuilder::default()
@@ -466,6 +759,9 @@ mod test {
         let offline = Some("OFFLINE");
         let unavailable = Some("UNAVAIL");
         let removed = Some("REMOVED");
+        let available = Some("AVAIL");
+        let in_use = Some("INUSE");
+        let split = Some("SPLIT");
         let bad = Some("wat");
 
         assert_eq!(Health::Online, Health::try_from_str(online).unwrap());
@@ -474,6 +770,9 @@ mod test {
         assert_eq!(Health::Offline, Health::try_from_str(offline).unwrap());
         assert_eq!(Health::Unavailable, Health::try_from_str(unavailable).unwrap());
         assert_eq!(Health::Removed, Health::try_from_str(removed).unwrap());
+        assert_eq!(Health::Available, Health::try_from_str(available).unwrap());
+        assert_eq!(Health::InUse, Health::try_from_str(in_use).unwrap());
+        assert_eq!(Health::Split, Health::try_from_str(split).unwrap());
 
         let err = Health::try_from_str(bad);
         assert!(err.is_err());
@@ -515,47 +814,106 @@ mod test {
         assert!(err.is_err());
     }
 
+    #[test]
+    fn parsing_props_borrowed() {
+        let line = "69120\t0\ttouch it\t1.50x\t-\t22%\t67039744\t0\t4957928072935098740\tOFFLINE\t67108864\t0\t/mnt/\toff\toff\toff\tz/ROOT/default\tnone\t0\ton\twait\t12\toff\toff\toff\n";
+        let props = ZpoolPropertiesRef::parse(line).unwrap();
+        assert_eq!(&Some("touch it"), props.comment());
+        assert_eq!(&Some(Path::new("/mnt")), props.alt_root());
+        assert_eq!(&Some("z/ROOT/default"), props.boot_fs());
+        assert_eq!(&Percent::new(22), props.fragmentation());
+        assert_eq!(&Ratio::new(1.5), props.dedup_ratio());
+        assert_eq!(&12, props.ashift());
+
+        let owned = props.into_owned();
+        let parsed = ZpoolProperties::try_from_stdout(line.as_bytes(), Overflow::Error).unwrap();
+        assert_eq!(parsed, owned);
+        assert_eq!(&CacheType::None, owned.cache_file());
+    }
+
+    #[test]
+    fn strict_columns() {
+        let line = "69120\t0\t-\t1.00x\t-\t1%\t67039744\t0\t15867762423891129245\tONLINE\t67108864\t0\t-\toff\toff\toff\t-\t-\t0\ton\twait\t12\toff\toff\toff\n";
+        assert!(ZpoolPropertiesRef::check_strict(line).is_ok());
+
+        let extra_column = line.replace("\toff\n", "\toff\toff\n");
+        let err = ZpoolPropertiesRef::check_strict(&extra_column).unwrap_err();
+        assert_eq!(ZpoolErrorKind::UnrecognizedOutput, err.kind());
+
+        let extra_line = format!("{}{}", line, line);
+        assert!(ZpoolPropertiesRef::check_strict(&extra_line).is_err());
+        assert!(ZpoolPropertiesRef::parse(&extra_column).is_ok());
+    }
+
     #[test]
     fn parsing_props_u64_guid() {
-        let line = b"69120\t0\t-\t1.00x\t-\t1%\t67039744\t0\t15867762423891129245\tONLINE\t67108864\t0\t-\toff\toff\toff\t-\t-\t0\ton\twait\n";
-        let props = ZpoolProperties::try_from_stdout(line);
+        let line = b"69120\t0\t-\t1.00x\t-\t1%\t67039744\t0\t15867762423891129245\tONLINE\t67108864\t0\t-\toff\toff\toff\t-\t-\t0\ton\twait\t12\toff\toff\toff\n";
+        let props = ZpoolProperties::try_from_stdout(line, Overflow::Error);
         assert!(props.is_ok());
     }
 
+    #[test]
+    fn parsing_props_overflow() {
+        let line = "69120\t100%\t-\t0.00x\t18446744073709551616\t100%\t18446744073709551615\t0\t18446744073709551615\tONLINE\t18446744073709551615\t0\t-\toff\toff\toff\t-\t-\t0\ton\twait\t12\toff\toff\toff\n";
+        let err = ZpoolPropertiesRef::parse(line).unwrap_err();
+        assert_eq!(ZpoolErrorKind::Overflow, err.kind());
+
+        let props = ZpoolPropertiesRef::parse_with(line, Overflow::Saturate).unwrap();
+        assert_eq!(&Percent::new(100), props.capacity());
+        assert_eq!(&Ratio::new(0.0), props.dedup_ratio());
+        assert_eq!(&Some(usize::max_value()), props.expand_size());
+        assert_eq!(&i64::max_value(), props.free());
+        assert_eq!(&u64::max_value(), props.guid());
+        assert_eq!(&usize::max_value(), props.size());
+
+        // guid is an identifier, clamping it would point to a different pool.
+        let bad_guid = line.replace("551615\tONLINE", "551616\tONLINE");
+        let err = ZpoolPropertiesRef::parse_with(&bad_guid, Overflow::Saturate).unwrap_err();
+        assert_eq!(ZpoolErrorKind::ParseError, err.kind());
+
+        // Percentages are never saturated either.
+        let bad_capacity = line.replace("\t100%\t-\t", "\t256%\t-\t");
+        assert!(ZpoolPropertiesRef::parse_with(&bad_capacity, Overflow::Saturate).is_err());
+    }
+
     #[test]
     fn parsing_on_zol() {
-        let line = b"99840\t0\t-\t1.00\t-\t1\t67009024\t0\t5667188105885376774\tONLINE\t67108864\t0\t-\toff\toff\toff\t-\t-\t0\ton\twait\n";
-        let props = ZpoolProperties::try_from_stdout(line);
+        let line = b"99840\t0\t-\t1.00\t-\t1\t67009024\t0\t5667188105885376774\tONLINE\t67108864\t0\t-\toff\toff\toff\t-\t-\t0\ton\twait\t12\toff\toff\toff\n";
+        let props = ZpoolProperties::try_from_stdout(line, Overflow::Error);
         assert!(props.is_ok());
     }
 
     #[test]
     fn parsing_props() {
-        let line = b"69120\t0\t-\t1.50x\t-\t22%\t67039744\t0\t4957928072935098740\tONLINE\t67108864\t0\t-\toff\toff\toff\t-\t-\t0\ton\twait\n";
-        let props = ZpoolProperties::try_from_stdout(line);
+        let line = b"69120\t0\t-\t1.50x\t-\t22%\t67039744\t0\t4957928072935098740\tONLINE\t67108864\t0\t-\toff\toff\toff\t-\t-\t0\ton\twait\t12\toff\toff\toff\n";
+        let props = ZpoolProperties::try_from_stdout(line, Overflow::Error);
         assert!(props.is_ok());
 
-        let line = b"69120\t0\ttouch it\t1.50x\t-\t22%\t67039744\t0\t4957928072935098740\tONLINE\t67108864\t0\t-\toff\toff\toff\t-\t-\t0\ton\tpanic\n";
-        let props = ZpoolProperties::try_from_stdout(line).unwrap();
+        let line = b"69120\t0\ttouch it\t1.50x\t-\t22%\t67039744\t0\t4957928072935098740\tONLINE\t67108864\t0\t-\toff\toff\toff\t-\t-\t0\ton\tpanic\t0\ton\ton\ton\n";
+        let props = ZpoolProperties::try_from_stdout(line, Overflow::Error).unwrap();
         assert_eq!(Some(String::from("touch it")), props.comment);
         assert_eq!(FailMode::Panic, props.fail_mode);
+        assert_eq!(0, props.ashift);
+        assert!(props.multihost);
+        assert!(props.auto_trim);
+        assert!(props.list_snapshots);
 
-        let line = b"69120\t0\ttouch it\t1.50x\t-\t22%\t67039744\t0\t4957928072935098740\tOFFLINE\t67108864\t0\t/mnt/\toff\toff\toff\t-\t-\t0\ton\twait\n";
-        let props = ZpoolProperties::try_from_stdout(line).unwrap();
+        let line = b"69120\t0\ttouch it\t1.50x\t-\t22%\t67039744\t0\t4957928072935098740\tOFFLINE\t67108864\t0\t/mnt/\toff\toff\toff\t-\t-\t0\ton\twait\t12\toff\toff\toff\n";
+        let props = ZpoolProperties::try_from_stdout(line, Overflow::Error).unwrap();
         assert_eq!(Health::Offline, props.health);
         assert_eq!(Some(PathBuf::from("/mnt")), props.alt_root);
 
         let line = b"waf\tasd";
-        let props = ZpoolProperties::try_from_stdout(line);
+        let props = ZpoolProperties::try_from_stdout(line, Overflow::Error);
         assert!(props.is_err());
 
-        let line = b"69120\t0\ttouch it\t1.50x\t1\t22%\t67039744\t0\t4957928072935098740\tOFFLINE\t67108864\t0\t/mnt/\toff\toff\toff\tz/ROOT/default\t-\t0\ton\twait\n";
-        let props = ZpoolProperties::try_from_stdout(line).unwrap();
+        let line = b"69120\t0\ttouch it\t1.50x\t1\t22%\t67039744\t0\t4957928072935098740\tOFFLINE\t67108864\t0\t/mnt/\toff\toff\toff\tz/ROOT/default\t-\t0\ton\twait\t12\toff\toff\toff\n";
+        let props = ZpoolProperties::try_from_stdout(line, Overflow::Error).unwrap();
         assert_eq!(Some(String::from("z/ROOT/default")), props.boot_fs);
         assert_eq!(Some(1), props.expand_size);
 
         let line = b"69120\t0\t-\t1.50x\t-\t22%\t67039744\t0\t4957928072935098740\tONLINE\t67108864\t0\t-\toff\toff\toff\t-\t-\t0\tomn\twait\n";
-        let props = ZpoolProperties::try_from_stdout(line);
+        let props = ZpoolProperties::try_from_stdout(line, Overflow::Error);
         assert!(props.is_err());
     }
 
@@ -633,10 +991,52 @@ mod test {
         .collect();
         let result = props.into_args();
         assert_eq!(expected, result);
+
+        let props = ZpoolPropertiesWriteBuilder::default().multihost(Some(true)).build().unwrap();
+        let result = props.into_args();
+        assert_eq!(Some(&OsString::from("multihost=on")), result.last());
+
+        let props = ZpoolPropertiesWriteBuilder::default().auto_trim(Some(true)).build().unwrap();
+        let result = props.into_args();
+        assert_eq!(Some(&OsString::from("autotrim=on")), result.last());
+
+        let props =
+            ZpoolPropertiesWriteBuilder::default().list_snapshots(Some(true)).build().unwrap();
+        let result = props.into_args();
+        assert_eq!(Some(&OsString::from("listsnapshots=on")), result.last());
     }
 
     #[test]
     fn write_builder() {
         let _right: ZpoolPropertiesWriteBuilder = ZpoolPropertiesWrite::builder();
     }
+
+    #[test]
+    fn features() {
+        let stdout = "tank\tsize\t67108864\t-
+tank\tfeature@async_destroy\tenabled\tlocal
+tank\tfeature@empty_bpobj\tactive\tlocal
+tank\tfeature@draid\tdisabled\tlocal
+other\tfeature@draid\tactive\tlocal
+";
+        let features = parse_features("tank", stdout).unwrap();
+        assert_eq!(3, features.len());
+        assert_eq!(Some(&FeatureState::Disabled), features.get("draid"));
+
+        let line = [
+            "0", "0", "-", "1.00", "-", "0", "0", "0", "5", "ONLINE", "0", "0", "-", "off", "off",
+            "off", "-", "-", "0", "on", "wait", "0", "off", "off", "off",
+        ]
+        .join("\t");
+        let props = ZpoolPropertiesRef::parse(&line).unwrap().into_owned();
+        assert!(props.features().is_empty());
+        let props = props.with_features(features);
+        assert_eq!(Some(FeatureState::Active), props.feature("feature@empty_bpobj"));
+        assert_eq!(Some(FeatureState::Enabled), props.feature("async_destroy"));
+        assert_eq!(None, props.feature("unknown"));
+        assert_eq!(vec!["empty_bpobj"], props.features_in(FeatureState::Active));
+
+        assert!(parse_features("tank", "tank\tfeature@draid\tmaybe\tlocal\n").is_err());
+        assert!(parse_features("tank", "tank\n").is_err());
+    }
 }
@@ -0,0 +1,307 @@
+//! Progress and outcome of scrub and resilver, parsed from `scan:` line of `zpool status`.
+//!
+//! `zpool status` prints sizes, rates and times rounded for humans, so numbers here are
+//! approximate. They are good enough for progress bars and ETAs, not for accounting.
+//!
+//! ### Usage
+//! ```rust,no_run
+//! use libzetta::zpool::{ScanStatus, ZpoolEngine, ZpoolOpen3};
+//!
+//! let engine = ZpoolOpen3::default();
+//! let zpool = engine.status("tank").unwrap();
+//! if let Some(ScanStatus::InProgress(progress)) = zpool.scan() {
+//!     println!("{:?}% done, {:?} left", progress.percent_done(), progress.eta());
+//! }
+//! ```
+use std::time::Duration;
+
+/// Scrub or resilver.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ScanKind {
+    /// Verification of every block started with `zpool scrub`.
+    Scrub,
+    /// Rebuild of a replaced or reattached device.
+    Resilver,
+}
+
+/// Progress of a running or paused scan.
+#[derive(Clone, Debug, Eq, PartialEq, Getters)]
+pub struct ScanProgress {
+    /// What is being scanned.
+    #[get = "pub"]
+    kind:     ScanKind,
+    /// Bytes of metadata traversed.
+    #[get = "pub"]
+    scanned:  Option<u64>,
+    /// Bytes issued to devices. Older ZFS doesn't split scan into two phases and doesn't report
+    /// it.
+    #[get = "pub"]
+    issued:   Option<u64>,
+    /// Bytes to scan in total.
+    #[get = "pub"]
+    total:    Option<u64>,
+    /// Bytes per second. Issue rate if reported, otherwise scan rate. `None` while paused.
+    #[get = "pub"]
+    rate:     Option<u64>,
+    /// Bytes repaired (scrub) or resilvered so far.
+    #[get = "pub"]
+    repaired: Option<u64>,
+    /// Estimated time left. `None` if zpool has no estimate yet.
+    #[get = "pub"]
+    eta:      Option<Duration>,
+    /// Hundredths of a percent done, kept as integer so the struct can be `Eq`.
+    done:     Option<u32>,
+}
+
+impl ScanProgress {
+    /// Percent done, e.g. `96.39`.
+    pub fn percent_done(&self) -> Option<f64> { self.done.map(|done| f64::from(done) / 100.0) }
+
+    /// Parse lines that follow `... in progress since ...`.
+    fn from_lines<'a, I: Iterator<Item = &'a str>>(kind: ScanKind, lines: I) -> ScanProgress {
+        let mut progress = ScanProgress {
+            kind,
+            scanned: None,
+            issued: None,
+            total: None,
+            rate: None,
+            repaired: None,
+            eta: None,
+            done: None,
+        };
+        let mut scan_rate = None;
+        for field in lines.flat_map(|line| line.split(',')).map(str::trim) {
+            if let Some(eta) = field.strip_suffix(" to go") {
+                progress.eta = parse_duration(eta);
+            } else if let Some(done) = field.strip_suffix("% done") {
+                progress.done = done.parse::<f64>().ok().map(|done| (done * 100.0).round() as u32);
+            } else if let Some(total) = field.strip_suffix(" total") {
+                progress.total = parse_size(total);
+            } else if let Some(repaired) = field.strip_suffix(" repaired") {
+                progress.repaired = parse_size(repaired);
+            } else if let Some(resilvered) = field.strip_suffix(" resilvered") {
+                progress.repaired = parse_size(resilvered);
+            } else if let Some(idx) = field.find(" issued") {
+                progress.issued = parse_size(&field[..idx]);
+                progress.rate = rate_of(field);
+            } else if let Some(idx) = field.find(" scanned") {
+                // Older ZFS: `1.23G scanned out of 10.0G at 100M/s`.
+                progress.scanned = parse_size(&field[..idx]);
+                scan_rate = rate_of(field);
+                if let Some(rest) = field.split(" out of ").nth(1) {
+                    progress.total = parse_size(rest.split(" at ").next().unwrap_or(rest));
+                }
+            }
+        }
+        progress.rate = progress.rate.or(scan_rate);
+        progress
+    }
+}
+
+/// Outcome of a finished scan.
+#[derive(Clone, Debug, Eq, PartialEq, Getters)]
+#[get = "pub"]
+pub struct ScanSummary {
+    /// What was scanned.
+    kind:     ScanKind,
+    /// Bytes repaired (scrub) or resilvered.
+    repaired: Option<u64>,
+    /// How long it took.
+    duration: Option<Duration>,
+    /// Number of errors found.
+    errors:   Option<u64>,
+}
+
+impl ScanSummary {
+    /// Parse `0B in 00:00:01 with 0 errors on Sun Jul 25 16:07:50 2021`.
+    fn from_text(kind: ScanKind, text: &str) -> ScanSummary {
+        let mut parts = text.splitn(2, " in ");
+        let repaired = parts.next().and_then(parse_size);
+        let rest = parts.next().unwrap_or_default();
+        let mut parts = rest.splitn(2, " with ");
+        let duration = parts.next().and_then(parse_duration);
+        let errors = parts.next().and_then(|rest| rest.split(' ').next()?.parse().ok());
+        ScanSummary { kind, repaired, duration, errors }
+    }
+}
+
+/// Everything `scan:` line can say.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ScanStatus {
+    /// Pool was never scrubbed or resilvered.
+    NotRequested,
+    /// Scrub or resilver is running right now.
+    InProgress(ScanProgress),
+    /// Scrub was paused with `zpool scrub -p`.
+    Paused(ScanProgress),
+    /// Last scan finished.
+    Finished(ScanSummary),
+    /// Last scan was stopped before it finished.
+    Canceled(ScanKind),
+}
+
+impl ScanStatus {
+    /// Parse the text after `scan:`. Returns `None` if text isn't recognized.
+    pub fn from_scan_line(text: &str) -> Option<ScanStatus> {
+        let mut lines = text.lines().map(str::trim).filter(|line| !line.is_empty());
+        let first = lines.next()?;
+        let status = if first.starts_with("none requested") {
+            ScanStatus::NotRequested
+        } else if first.starts_with("scrub in progress") {
+            ScanStatus::InProgress(ScanProgress::from_lines(ScanKind::Scrub, lines))
+        } else if first.starts_with("resilver in progress") {
+            ScanStatus::InProgress(ScanProgress::from_lines(ScanKind::Resilver, lines))
+        } else if first.starts_with("scrub paused") {
+            ScanStatus::Paused(ScanProgress::from_lines(ScanKind::Scrub, lines))
+        } else if let Some(rest) = first.strip_prefix("scrub repaired ") {
+            ScanStatus::Finished(ScanSummary::from_text(ScanKind::Scrub, rest))
+        } else if let Some(rest) = first.strip_prefix("resilvered ") {
+            ScanStatus::Finished(ScanSummary::from_text(ScanKind::Resilver, rest))
+        } else if first.starts_with("scrub canceled") {
+            ScanStatus::Canceled(ScanKind::Scrub)
+        } else if first.starts_with("resilver canceled") {
+            ScanStatus::Canceled(ScanKind::Resilver)
+        } else {
+            return None;
+        };
+        Some(status)
+    }
+
+    /// Whether scrub or resilver is still running. Paused scrub is not.
+    pub fn is_running(&self) -> bool {
+        match self {
+            ScanStatus::InProgress(_) => true,
+            ScanStatus::NotRequested
+            | ScanStatus::Paused(_)
+            | ScanStatus::Finished(_)
+            | ScanStatus::Canceled(_) => false,
+        }
+    }
+}
+
+/// Rate out of `... at 100M/s`.
+fn rate_of(field: &str) -> Option<u64> {
+    let idx = field.find(" at ")?;
+    parse_size(field[idx + " at ".len()..].strip_suffix("/s")?)
+}
+
+/// Parse size like `1.23G`, `80K` or `0B`. Also used for abbreviated error counts.
+pub(crate) fn parse_size(text: &str) -> Option<u64> {
+    let text = text.trim();
+    let text = text.strip_suffix('B').unwrap_or(text);
+    let unit = text.chars().last()?;
+    let (number, power) = match "KMGTPE".find(unit) {
+        Some(idx) => (&text[..text.len() - 1], idx + 1),
+        None => (text, 0),
+    };
+    let number: f64 = number.parse().ok()?;
+    Some((number * 1024_f64.powi(power as i32)).round() as u64)
+}
+
+/// Parse duration like `00:03:04`, `1 days 02:03:04` or `0h3m`.
+fn parse_duration(text: &str) -> Option<Duration> {
+    let text = text.trim();
+    let mut parts = text.splitn(2, " days ");
+    let (days, clock) = match (parts.next(), parts.next()) {
+        (Some(days), Some(clock)) => (days.parse::<u64>().ok()?, clock),
+        _ => (0, text),
+    };
+    let secs = if clock.contains(':') {
+        clock.split(':').try_fold(0_u64, |acc, part| Some(acc * 60 + part.parse::<u64>().ok()?))?
+    } else {
+        // Older ZFS prints `0h3m`.
+        let mut parts = clock.strip_suffix('m')?.splitn(2, 'h');
+        let hours: u64 = parts.next()?.parse().ok()?;
+        let minutes: u64 = parts.next()?.parse().ok()?;
+        hours * 3600 + minutes * 60
+    };
+    Some(Duration::from_secs(days * 86400 + secs))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn secs(n: u64) -> Option<Duration> { Some(Duration::from_secs(n)) }
+
+    #[test]
+    fn in_progress() {
+        let text = "scrub in progress since Sun Jul 25 16:07:49 2021\n\t1.50G scanned at 100M/s, \
+                    1.00G issued at 50.0M/s, 10.0G total\n\t0B repaired, 10.00% done, 00:03:04 \
+                    to go\n";
+        let progress = match ScanStatus::from_scan_line(text) {
+            Some(ScanStatus::InProgress(progress)) => progress,
+            other => panic!("{:?}", other),
+        };
+        assert_eq!(&ScanKind::Scrub, progress.kind());
+        assert_eq!(&Some(1_610_612_736), progress.scanned());
+        assert_eq!(&Some(1_073_741_824), progress.issued());
+        assert_eq!(&Some(10_737_418_240), progress.total());
+        assert_eq!(&Some(52_428_800), progress.rate());
+        assert_eq!(&Some(0), progress.repaired());
+        assert_eq!(Some(10.0), progress.percent_done());
+        assert_eq!(&secs(184), progress.eta());
+
+        let text = "resilver in progress since Tue Aug 13 23:03:11 2019\n\t42.5K scanned at \
+                    42.5K/s, 80K issued at 80K/s, 83K total\n\t512 resilvered, 96.39% done, no \
+                    estimated completion time\n";
+        let progress = match ScanStatus::from_scan_line(text) {
+            Some(ScanStatus::InProgress(progress)) => progress,
+            other => panic!("{:?}", other),
+        };
+        assert_eq!(&ScanKind::Resilver, progress.kind());
+        assert_eq!(&Some(512), progress.repaired());
+        assert_eq!(Some(96.39), progress.percent_done());
+        assert_eq!(&None, progress.eta());
+
+        let text = "scrub in progress since Tue Nov 28 02:04:11 2017\n\t1.23G scanned out of \
+                    10.0G at 100M/s, 0h3m to go\n\t0 repaired, 12.30% done\n";
+        let progress = match ScanStatus::from_scan_line(text) {
+            Some(ScanStatus::InProgress(progress)) => progress,
+            other => panic!("{:?}", other),
+        };
+        assert_eq!(&Some(10_737_418_240), progress.total());
+        assert_eq!(&Some(104_857_600), progress.rate());
+        assert_eq!(&None, progress.issued());
+        assert_eq!(&secs(180), progress.eta());
+    }
+
+    #[test]
+    fn other_states() {
+        let text = "scrub repaired 4K in 1 days 00:00:01 with 2 errors on Sun Jul 25 16:07:50 2021";
+        let expected = ScanSummary {
+            kind:     ScanKind::Scrub,
+            repaired: Some(4096),
+            duration: secs(86401),
+            errors:   Some(2),
+        };
+        assert_eq!(Some(ScanStatus::Finished(expected)), ScanStatus::from_scan_line(text));
+
+        let text = "resilvered 512 in 0h0m with 0 errors on Tue Aug 13 23:03:12 2019\n";
+        match ScanStatus::from_scan_line(text) {
+            Some(ScanStatus::Finished(summary)) => {
+                assert_eq!(&ScanKind::Resilver, summary.kind());
+                assert_eq!(&secs(0), summary.duration());
+            },
+            other => panic!("{:?}", other),
+        }
+
+        let text = "scrub paused since Mon Jul 26 10:00:00 2021\n\tscrub started on Mon Jul 26 \
+                    09:00:00 2021\n\t1.23G scanned, 500M issued, 10.0G total\n\t0B repaired, \
+                    4.88% done\n";
+        let status = ScanStatus::from_scan_line(text).unwrap();
+        assert!(!status.is_running());
+        match status {
+            ScanStatus::Paused(progress) => {
+                assert_eq!(&Some(524_288_000), progress.issued());
+                assert_eq!(&None, progress.rate());
+            },
+            other => panic!("{:?}", other),
+        }
+
+        assert_eq!(Some(ScanStatus::NotRequested), ScanStatus::from_scan_line("none requested"));
+        let canceled = ScanStatus::from_scan_line("resilver canceled on Sun Jul 25 16:07:50 2021");
+        assert_eq!(Some(ScanStatus::Canceled(ScanKind::Resilver)), canceled);
+        assert_eq!(None, ScanStatus::from_scan_line("something new"));
+    }
+}
@@ -0,0 +1,229 @@
+//! Verification that hot spares actually took over faulted devices.
+//!
+//! ZFS is supposed to pull a hot spare into service on its own once a device faults (with the help
+//! of zed or `autoreplace=on`), but that doesn't always happen: zed might not be running, the
+//! event might have been missed or the spare might have been busy. Call
+//! [`ensure_spare_engaged`](fn.ensure_spare_engaged.html) after a device fault event - it
+//! re-reads the pool status and if no spare is attached to faulted device yet, replaces it with
+//! the first available spare.
+//!
+//! ### Usage
+//! ```rust,no_run
+//! use libzetta::zpool::{spare::{ensure_spare_engaged, SpareCheck},
+//!                       ZpoolOpen3};
+//!
+//! let engine = ZpoolOpen3::default();
+//! match ensure_spare_engaged(&engine, "tank", "sdb").unwrap() {
+//!     SpareCheck::Needed(spare) => println!("replaced sdb with {:?}", spare),
+//!     SpareCheck::NoSpareAvailable => println!("out of spares"),
+//!     _ => {},
+//! }
+//! ```
+use std::path::{Path, PathBuf};
+
+use crate::zpool::{Disk, Health, Zpool, ZpoolEngine, ZpoolError, ZpoolResult};
+
+/// State of a faulted device with regard to hot spares.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SpareCheck {
+    /// Device is not faulted, nothing to do.
+    NotFaulted,
+    /// Spare is already attached to the same vdev as faulted device.
+    Engaged(PathBuf),
+    /// No spare is attached. Contains a spare that should be used.
+    Needed(PathBuf),
+    /// No spare is attached and there are no available spares left.
+    NoSpareAvailable,
+}
+
+impl SpareCheck {
+    /// Figure out if spare took over `device` in given pool. Device paths must be in the same form
+    /// `zpool status` prints them.
+    ///
+    /// * `zpool` - Status of the pool.
+    /// * `device` - Device that was reported as faulted.
+    pub fn from_status<D: AsRef<Path>>(zpool: &Zpool, device: D) -> ZpoolResult<SpareCheck> {
+        let device = device.as_ref();
+        let (siblings, disk) = find_device(zpool, device).ok_or(ZpoolError::NoSuchDevice)?;
+
+        if !is_faulted(disk) {
+            return Ok(SpareCheck::NotFaulted);
+        }
+
+        let spares = zpool.spares();
+        if let Some(engaged) =
+            siblings.iter().find(|disk| spares.iter().any(|spare| spare == *disk))
+        {
+            return Ok(SpareCheck::Engaged(engaged.path().clone()));
+        }
+
+        Ok(spares
+            .iter()
+            .find(|spare| spare.health() == &Health::Available)
+            .map(|spare| SpareCheck::Needed(spare.path().clone()))
+            .unwrap_or(SpareCheck::NoSpareAvailable))
+    }
+}
+
+/// Find device and its siblings. Once a spare took over, the device is under a `spare-N` node
+/// together with the spare, so children of those nodes are searched too.
+fn find_device<'a>(zpool: &'a Zpool, device: &Path) -> Option<(&'a [Disk], &'a Disk)> {
+    zpool.vdevs().iter().map(|vdev| vdev.disks()).find_map(|disks| {
+        disks.iter().find_map(|disk| {
+            if disk.is_spare_node() {
+                let children = disk.children();
+                children.iter().find(|child| *child == device).map(|child| (&children[..], child))
+            } else if disk == device {
+                Some((&disks[..], disk))
+            } else {
+                None
+            }
+        })
+    })
+}
+
+#[allow(clippy::wildcard_enum_match_arm)]
+fn is_faulted(disk: &Disk) -> bool {
+    match disk.health() {
+        Health::Faulted | Health::Unavailable | Health::Removed => true,
+        _ => false,
+    }
+}
+
+/// Make sure a hot spare took over faulted device. If it didn't, replace the device with first
+/// available spare. Returned value describes state before replacement.
+///
+/// * `engine` - Engine to use.
+/// * `name` - Name of the pool.
+/// * `device` - Device that was reported as faulted.
+pub fn ensure_spare_engaged<E: ZpoolEngine, N: AsRef<str>, D: AsRef<Path>>(
+    engine: &E,
+    name: N,
+    device: D,
+) -> ZpoolResult<SpareCheck> {
+    let zpool = engine.status(&name)?;
+    let check = SpareCheck::from_status(&zpool, &device)?;
+    if let SpareCheck::Needed(ref spare) = check {
+        engine.replace_disk(&name, device.as_ref(), spare)?;
+    }
+    Ok(check)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{parsers::parse_zpools,
+                zpool::{Vdev, VdevType, ZpoolErrorKind}};
+
+    fn disk(path: &str, health: Health) -> Disk {
+        Disk::builder().path(path).health(health).build().unwrap()
+    }
+
+    fn zpool(mirror: Vec<Disk>, spares: Vec<Disk>) -> Zpool {
+        let vdev = Vdev::builder()
+            .kind(VdevType::Mirror)
+            .health(Health::Degraded)
+            .disks(mirror)
+            .build()
+            .unwrap();
+        Zpool::builder()
+            .name("tank")
+            .health(Health::Degraded)
+            .vdevs(vec![vdev])
+            .spares(spares)
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn spare_needed() {
+        let pool = zpool(
+            vec![disk("sda", Health::Online), disk("sdb", Health::Faulted)],
+            vec![disk("sdc", Health::Unavailable), disk("sdd", Health::Available)],
+        );
+        assert_eq!(SpareCheck::NotFaulted, SpareCheck::from_status(&pool, "sda").unwrap());
+        assert_eq!(
+            SpareCheck::Needed(PathBuf::from("sdd")),
+            SpareCheck::from_status(&pool, "sdb").unwrap()
+        );
+        let err = SpareCheck::from_status(&pool, "sdz").unwrap_err();
+        assert_eq!(ZpoolErrorKind::NoSuchDevice, err.kind());
+    }
+
+    #[test]
+    fn spare_engaged_or_missing() {
+        let pool = zpool(
+            vec![
+                disk("sda", Health::Online),
+                disk("sdb", Health::Faulted),
+                disk("sdc", Health::Online),
+            ],
+            vec![disk("sdc", Health::Online)],
+        );
+        assert_eq!(
+            SpareCheck::Engaged(PathBuf::from("sdc")),
+            SpareCheck::from_status(&pool, "sdb").unwrap()
+        );
+
+        let pool = zpool(vec![disk("sda", Health::Online), disk("sdb", Health::Removed)], vec![]);
+        assert_eq!(SpareCheck::NoSpareAvailable, SpareCheck::from_status(&pool, "sdb").unwrap());
+    }
+
+    #[test]
+    fn spare_engaged_under_spare_node() {
+        let node = Disk::builder()
+            .path("spare-0")
+            .health(Health::Degraded)
+            .children(vec![disk("sdb", Health::Faulted), disk("sdc", Health::Online)])
+            .build()
+            .unwrap();
+        let pool = zpool(vec![disk("sda", Health::Online), node], vec![disk("sdc", Health::InUse)]);
+        assert_eq!(
+            SpareCheck::Engaged(PathBuf::from("sdc")),
+            SpareCheck::from_status(&pool, "sdb").unwrap()
+        );
+        assert_eq!(SpareCheck::NotFaulted, SpareCheck::from_status(&pool, "sda").unwrap());
+    }
+
+    #[test]
+    fn spare_engaged_from_status() {
+        let stdout = r#"  pool: tank
+ state: DEGRADED
+status: One or more devices are faulted in response to persistent errors.
+        Sufficient replicas exist for the pool to continue functioning in a
+        degraded state.
+action: Replace the faulted device, or use 'zpool clear' to mark the device
+        repaired.
+  scan: resilvered 1.02M in 00:00:01 with 0 errors on Sun Oct 11 12:01:14 2026
+config:
+
+        NAME                STATE     READ WRITE CKSUM
+        tank                DEGRADED     0     0     0
+          mirror-0          DEGRADED     0     0     0
+            spare-0         DEGRADED     0     0     0
+              /vdevs/vdev0  FAULTED      0    14     0  too many errors
+              /vdevs/vdev3  ONLINE       0     0     0
+            /vdevs/vdev1    ONLINE       0     0     0
+          mirror-1          DEGRADED     0     0     0
+            /vdevs/vdev2    ONLINE       0     0     0
+            /vdevs/vdev6    FAULTED      0    11     0  too many errors
+        spares
+          /vdevs/vdev3      INUSE     currently in use
+          /vdevs/vdev4      AVAIL
+
+errors: No known data errors
+"#;
+        let pool = parse_zpools(stdout).unwrap().remove(0);
+        assert_eq!(
+            SpareCheck::Engaged(PathBuf::from("/vdevs/vdev3")),
+            SpareCheck::from_status(&pool, "/vdevs/vdev0").unwrap()
+        );
+        assert_eq!(SpareCheck::NotFaulted, SpareCheck::from_status(&pool, "/vdevs/vdev1").unwrap());
+        assert_eq!(
+            SpareCheck::Needed(PathBuf::from("/vdevs/vdev4")),
+            SpareCheck::from_status(&pool, "/vdevs/vdev6").unwrap()
+        );
+        let err = SpareCheck::from_status(&pool, "spare-0").unwrap_err();
+        assert_eq!(ZpoolErrorKind::NoSuchDevice, err.kind());
+    }
+}
@@ -0,0 +1,75 @@
+//! Splitting mirrored pools.
+//!
+//! `zpool split` detaches one device from every mirror of a pool and makes a new pool out of them:
+//! a full copy that can be moved to another host or kept as a backup. The pool must consist only
+//! of mirrors. By default the last device of each mirror is taken and the new pool is left
+//! exported; [`SplitOptions`](struct.SplitOptions.html) picks devices and imports the new pool
+//! under an altroot instead.
+//!
+//! ### Usage
+//! ```rust,no_run
+//! use libzetta::zpool::{split::SplitOptions, ZpoolEngine, ZpoolOpen3};
+//!
+//! let engine = ZpoolOpen3::default();
+//! let options = SplitOptions::builder().altroot("/mnt/copy").device("sdb").build().unwrap();
+//! engine.split("tank", "tank-copy", options).unwrap();
+//! ```
+use std::{ffi::OsString, path::PathBuf};
+
+/// How to split a pool.
+#[derive(Builder, Debug, Clone, Default, Getters, PartialEq, Eq)]
+#[builder(setter(into))]
+#[get = "pub"]
+pub struct SplitOptions {
+    /// Import the new pool with this altroot (`-R`). `None` leaves it exported.
+    #[builder(default, setter(into, strip_option))]
+    altroot: Option<PathBuf>,
+    /// Devices to take into the new pool, at most one per mirror. Mirrors without a device in this
+    /// list give their last device.
+    #[builder(default)]
+    devices: Vec<PathBuf>,
+}
+
+impl SplitOptions {
+    /// A preferred way to create this.
+    pub fn builder() -> SplitOptionsBuilder { SplitOptionsBuilder::default() }
+
+    /// Options and devices for `zpool split`. Pool names go between them.
+    pub(crate) fn into_args(self) -> (Vec<OsString>, Vec<OsString>) {
+        let mut options: Vec<OsString> = Vec::with_capacity(2);
+        if let Some(altroot) = self.altroot {
+            options.push("-R".into());
+            options.push(altroot.into_os_string());
+        }
+        let devices = self.devices.into_iter().map(PathBuf::into_os_string).collect();
+        (options, devices)
+    }
+}
+
+impl SplitOptionsBuilder {
+    /// Add device to take into the new pool.
+    ///
+    /// * `device` - Device as it's named in the pool or path to it.
+    pub fn device<D: Into<PathBuf>>(&mut self, device: D) -> &mut SplitOptionsBuilder {
+        self.devices.get_or_insert_with(Vec::new).push(device.into());
+        self
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn args() {
+        let (options, devices) = SplitOptions::default().into_args();
+        assert!(options.is_empty());
+        assert!(devices.is_empty());
+
+        let options =
+            SplitOptions::builder().altroot("/mnt").device("sdb").device("sdd").build().unwrap();
+        let (options, devices) = options.into_args();
+        assert_eq!(vec!["-R", "/mnt"], options);
+        assert_eq!(vec!["sdb", "sdd"], devices);
+    }
+}
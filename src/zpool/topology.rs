@@ -17,7 +17,7 @@
 //!     .unwrap();
 //! ```
 //! Overkill example: 2 drives in mirror and a single drive, zil on double
-//! mirror and 2 l2rc.
+//! mirror, 2 l2rc and a hot spare.
 //!
 //! ```rust, norun
 //! use libzetta::zpool::{CreateZpoolRequest, CreateVdevRequest};
@@ -33,15 +33,19 @@
 //!     .vdev(CreateVdevRequest::SingleDisk(PathBuf::from("hd6")))
 //!     .caches(cache_drives)
 //!     .zil(CreateVdevRequest::Mirror(zil_drives))
+//!     .spare(PathBuf::from("hd7"))
 //!     .altroot(PathBuf::from("/mnt"))
 //!     .mount(PathBuf::from("/mnt"))
 //!     .build()
 //!     .unwrap();
 //! ```
 
-use std::{ffi::OsString, path::PathBuf};
+use std::{collections::BTreeMap, ffi::OsString, path::PathBuf};
 
-use crate::zpool::{properties::ZpoolPropertiesWrite, vdev::CreateVdevRequest, CreateMode};
+use crate::{args::{property_arg, ArgError},
+            zpool::{properties::{FeatureState, ZpoolPropertiesWrite},
+                    vdev::CreateVdevRequest,
+                    CreateMode, ZpoolError, ZpoolResult}};
 #[derive(Default, Builder, Debug, Clone, Getters, PartialEq, Eq)]
 #[builder(setter(into))]
 #[get = "pub"]
@@ -85,35 +89,83 @@ pub struct CreateZpoolRequest {
     /// fails, the hot spare automatically replaces the failed device.
     #[builder(default)]
     spares:      Vec<PathBuf>,
+    /// Create pool with every feature disabled (`-d`). Features can be turned back on one by one
+    /// with `features`. Use this when pool must stay importable on older systems.
+    #[builder(default)]
+    no_features: bool,
+    /// State of individual features, keyed by name without `feature@` prefix. Only `Enabled`
+    /// and `Disabled` can be requested.
+    #[builder(default)]
+    features:    BTreeMap<String, FeatureState>,
+    /// Sector size exponent for every vdev of the pool (`-o ashift=`), `12` for 4KiB sectors.
+    /// Without it ZFS trusts the sector size devices report, and many 4KiB drives claim 512
+    /// bytes. Can't be changed for a vdev once it's created. Allowed values are 9 to 16, `0`
+    /// means auto-detect.
+    #[builder(default)]
+    ashift:      Option<u8>,
 }
 
 impl CreateZpoolRequest {
     /// A preferred way to create this.
     pub fn builder() -> CreateZpoolRequestBuilder { CreateZpoolRequestBuilder::default() }
 
+    /// Every device of the request: data and log vdevs, caches and spares.
+    pub fn devices(&self) -> Vec<&PathBuf> {
+        let vdevs = self.vdevs.iter().chain(self.logs.iter());
+        vdevs
+            .flat_map(CreateVdevRequest::disks)
+            .chain(self.caches.iter())
+            .chain(self.spares.iter())
+            .collect()
+    }
+
     /// Verify that given topology can be used to update existing pool.
-    pub fn is_suitable_for_update(&self) -> bool {
-        let valid_vdevs = self.vdevs.iter().all(CreateVdevRequest::is_valid);
-        if !valid_vdevs {
-            return false;
-        }
+    pub fn is_suitable_for_update(&self) -> bool { self.validate_for_update().is_ok() }
 
-        let valid_logs = self.logs.iter().all(CreateVdevRequest::is_valid);
-        if !valid_logs {
-            return false;
+    /// Same as [`is_suitable_for_update`](#method.is_suitable_for_update), but `InvalidTopology`
+    /// says what's wrong.
+    pub fn validate_for_update(&self) -> ZpoolResult<()> {
+        for vdev in &self.vdevs {
+            vdev.validate()?;
         }
-        true
+        for log in &self.logs {
+            log.validate_log()?;
+        }
+        Ok(())
     }
 
     /// Verify that given topology can be used to create new zpool.
     ///
     /// That means it as at least one valid vdev and all optional devices are
     /// valid if present.
-    pub fn is_suitable_for_create(&self) -> bool {
+    pub fn is_suitable_for_create(&self) -> bool { self.validate_for_create().is_ok() }
+
+    /// Same as [`is_suitable_for_create`](#method.is_suitable_for_create), but `InvalidTopology`
+    /// says what's wrong.
+    pub fn validate_for_create(&self) -> ZpoolResult<()> {
         if self.vdevs.is_empty() {
-            return false;
+            return Err(ZpoolError::InvalidTopology(String::from("no data vdevs")));
+        }
+        self.validate_for_update()
+    }
+
+    /// Make feature states usable as `-o` values.
+    pub(crate) fn feature_args(&self) -> Result<Vec<OsString>, ArgError> {
+        self.features
+            .iter()
+            .map(|(name, state)| property_arg(&format!("feature@{}", name), state.as_str()))
+            .collect()
+    }
+
+    /// Make ashift usable as `-o` value.
+    pub(crate) fn ashift_arg(&self) -> Result<Option<OsString>, ArgError> {
+        match self.ashift {
+            None => Ok(None),
+            Some(ashift) if ashift == 0 || (9..=16).contains(&ashift) => {
+                property_arg("ashift", &ashift.to_string()).map(Some)
+            },
+            Some(_) => Err(ArgError::InvalidValue("ashift".into(), "must be 0 or 9 to 16")),
         }
-        self.is_suitable_for_update()
     }
 
     /// Make CreateZpoolRequest usable as arg for [`Command`](https://doc.rust-lang.org/std/process/struct.Command.html).
@@ -188,6 +240,34 @@ impl CreateZpoolRequestBuilder {
         self
     }
 
+    /// Pass `-f` to `zpool create`: use devices even if they appear to be in use (e.g. have an
+    /// old label) or vdevs have mismatched replication levels. Off by default.
+    ///
+    /// * `force` - Same as `create_mode(CreateMode::Force)` when `true`.
+    pub fn force(&mut self, force: bool) -> &mut CreateZpoolRequestBuilder {
+        self.create_mode(if force { CreateMode::Force } else { CreateMode::Gentle })
+    }
+
+    /// Enable single feature, usually on top of `no_features`.
+    ///
+    /// * `name` - name of the feature, with or without `feature@` prefix.
+    pub fn enable_feature<N: AsRef<str>>(&mut self, name: N) -> &mut CreateZpoolRequestBuilder {
+        self.feature(name.as_ref(), FeatureState::Enabled)
+    }
+
+    /// Disable single feature.
+    ///
+    /// * `name` - name of the feature, with or without `feature@` prefix.
+    pub fn disable_feature<N: AsRef<str>>(&mut self, name: N) -> &mut CreateZpoolRequestBuilder {
+        self.feature(name.as_ref(), FeatureState::Disabled)
+    }
+
+    fn feature(&mut self, name: &str, state: FeatureState) -> &mut CreateZpoolRequestBuilder {
+        let name = name.trim_start_matches("feature@").to_string();
+        self.features.get_or_insert_with(BTreeMap::new).insert(name, state);
+        self
+    }
+
     /// Add spare disk that will be used to replace failed device in zpool.
     ///
     /// * `disk` - path to file or name of block device in `/dev/`.
@@ -243,6 +323,16 @@ mod test {
 
         assert!(!topo.is_suitable_for_create());
 
+        // Zpool with RAID-Z as ZIL
+        let topo = CreateZpoolRequestBuilder::default()
+            .name("tank")
+            .vdevs(vec![CreateVdevRequest::Mirror(get_disks(2, &file_path))])
+            .zil(CreateVdevRequest::RaidZ(get_disks(3, &file_path)))
+            .build()
+            .unwrap();
+
+        assert!(!topo.is_suitable_for_create());
+
         // Zpool with valid cache and valid vdev
         let topo = CreateZpoolRequestBuilder::default()
             .name("tank")
@@ -267,6 +357,19 @@ mod test {
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_force() {
+        let request = CreateZpoolRequest::builder().name("tank").build().unwrap();
+        assert_eq!(&CreateMode::Gentle, request.create_mode());
+
+        let request = CreateZpoolRequest::builder().name("tank").force(true).build().unwrap();
+        assert_eq!(&CreateMode::Force, request.create_mode());
+
+        let request =
+            CreateZpoolRequest::builder().name("tank").force(true).force(false).build().unwrap();
+        assert_eq!(&CreateMode::Gentle, request.create_mode());
+    }
+
     #[test]
     fn test_args() {
         let tmp_dir = TempDir::new("zpool-tests").unwrap();
@@ -333,4 +436,51 @@ mod test {
         let expected = args_from_slice(&["raidz3", path, path, path, path, path, path, path, path]);
         assert_eq!(expected, result);
     }
+    #[test]
+    fn test_devices() {
+        let topo = CreateZpoolRequestBuilder::default()
+            .name("tank")
+            .vdev(CreateVdevRequest::Mirror(vec!["sda".into(), "sdb".into()]))
+            .zil(CreateVdevRequest::disk("sdc"))
+            .cache("sdd".into())
+            .spare("sde".into())
+            .build()
+            .unwrap();
+        let devices: Vec<&str> = topo.devices().iter().map(|d| d.to_str().unwrap()).collect();
+        assert_eq!(vec!["sda", "sdb", "sdc", "sdd", "sde"], devices);
+    }
+
+    #[test]
+    fn test_feature_args() {
+        let topo = CreateZpoolRequestBuilder::default()
+            .name("tank")
+            .no_features(true)
+            .enable_feature("lz4_compress")
+            .disable_feature("feature@async_destroy")
+            .build()
+            .unwrap();
+        assert!(*topo.no_features());
+        let expected =
+            args_from_slice(&["feature@async_destroy=disabled", "feature@lz4_compress=enabled"]);
+        assert_eq!(expected, topo.feature_args().unwrap());
+
+        let topo = CreateZpoolRequestBuilder::default()
+            .name("tank")
+            .disable_feature("bad name")
+            .build()
+            .unwrap();
+        assert!(topo.feature_args().is_err());
+    }
+
+    #[test]
+    fn test_ashift_arg() {
+        let topo = CreateZpoolRequestBuilder::default().name("tank").build().unwrap();
+        assert_eq!(None, topo.ashift_arg().unwrap());
+
+        let topo = CreateZpoolRequestBuilder::default().name("tank").ashift(12).build().unwrap();
+        assert_eq!(Some(OsString::from("ashift=12")), topo.ashift_arg().unwrap());
+
+        let topo = CreateZpoolRequestBuilder::default().name("tank").ashift(17).build().unwrap();
+        assert!(topo.ashift_arg().is_err());
+    }
 }
@@ -0,0 +1,94 @@
+//! Manual TRIM of pool devices.
+//!
+//! SSDs slow down and wear faster when they don't know which blocks are free. `autotrim` only
+//! trims space freed from now on, a full pass with
+//! [`trim`](../trait.ZpoolEngine.html#tymethod.trim) covers everything that is free right now. It
+//! runs in the background, progress is reported by
+//! [`status_with_maintenance`](../trait.ZpoolEngine.html#tymethod.status_with_maintenance).
+//!
+//! [`TrimOptions`](struct.TrimOptions.html) picks devices, limits the rate, so a pass doesn't
+//! starve regular I/O, and asks for secure TRIM, which is needed when freed data must not be
+//! recoverable from the device. Devices that don't support (secure) TRIM fail with
+//! [`ZpoolError::TrimNotSupported`](../enum.ZpoolError.html#variant.TrimNotSupported).
+//!
+//! ### Usage
+//! ```rust,no_run
+//! use libzetta::zpool::{trim::TrimOptions, ZpoolEngine, ZpoolOpen3};
+//!
+//! let engine = ZpoolOpen3::default();
+//! let options = TrimOptions::builder()
+//!     .rate(100 * 1024 * 1024)
+//!     .device("/dev/nvme0n1")
+//!     .build()
+//!     .unwrap();
+//! engine.trim("tank", options).unwrap();
+//! ```
+use std::{ffi::OsString, path::PathBuf};
+
+/// What to trim and how.
+#[derive(Builder, Debug, Clone, Default, Getters, PartialEq, Eq)]
+#[builder(setter(into))]
+#[get = "pub"]
+pub struct TrimOptions {
+    /// Use secure TRIM (`-d`). Slower and supported by fewer devices.
+    #[builder(default)]
+    secure:  bool,
+    /// Upper limit per device in bytes per second (`-r`). `None` means as fast as possible.
+    #[builder(default)]
+    rate:    Option<u64>,
+    /// Devices to trim. Empty means every device of the pool.
+    #[builder(default)]
+    devices: Vec<PathBuf>,
+}
+
+impl TrimOptions {
+    /// A preferred way to create this.
+    pub fn builder() -> TrimOptionsBuilder { TrimOptionsBuilder::default() }
+
+    /// Options and devices for `zpool trim`. Pool name goes between them.
+    pub(crate) fn into_args(self) -> (Vec<OsString>, Vec<OsString>) {
+        let mut options: Vec<OsString> = Vec::with_capacity(3);
+        if self.secure {
+            options.push("-d".into());
+        }
+        if let Some(rate) = self.rate {
+            options.push("-r".into());
+            options.push(rate.to_string().into());
+        }
+        let devices = self.devices.into_iter().map(PathBuf::into_os_string).collect();
+        (options, devices)
+    }
+}
+
+impl TrimOptionsBuilder {
+    /// Add device to trim.
+    ///
+    /// * `device` - Device as it's named in the pool or path to it.
+    pub fn device<D: Into<PathBuf>>(&mut self, device: D) -> &mut TrimOptionsBuilder {
+        self.devices.get_or_insert_with(Vec::new).push(device.into());
+        self
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn args() {
+        let (options, devices) = TrimOptions::default().into_args();
+        assert!(options.is_empty());
+        assert!(devices.is_empty());
+
+        let options = TrimOptions::builder()
+            .secure(true)
+            .rate(1_048_576)
+            .device("sda")
+            .device("/dev/sdb")
+            .build()
+            .unwrap();
+        let (options, devices) = options.into_args();
+        assert_eq!(vec!["-d", "-r", "1048576"], options);
+        assert_eq!(vec!["sda", "/dev/sdb"], devices);
+    }
+}
@@ -0,0 +1,250 @@
+//! Transaction group history.
+//!
+//! Every write lands in a transaction group (txg) that goes through open, quiescing and syncing
+//! states before it's committed to disk. ZFS keeps history of recent txgs in kstat:
+//! `/proc/spl/kstat/zfs/<pool>/txgs` on Linux and `kstat.zfs.<pool>.txgs` sysctl on FreeBSD. Slow
+//! sync times show up there long before a pool stops being `ONLINE`.
+//! [`read_txgs`](fn.read_txgs.html) takes a [`TxgSample`](struct.TxgSample.html) of it; comparing
+//! samples taken over time tells how fast txgs are committed and how long they take to sync.
+//!
+//! History is only kept if `zfs_txg_history` module parameter is not zero.
+//!
+//! ### Usage
+//! ```rust,no_run
+//! use libzetta::zpool::txg::read_txgs;
+//! use std::time::Duration;
+//!
+//! let sample = read_txgs("tank").unwrap();
+//! println!("current txg: {:?}", sample.current_txg());
+//! if sample.max_sync_time().map_or(false, |t| t > Duration::from_secs(5)) {
+//!     println!("tank is slow to sync");
+//! }
+//! ```
+use std::{collections::HashMap,
+          convert::TryFrom,
+          time::{Duration, SystemTime}};
+
+use crate::zpool::{ZpoolError, ZpoolResult};
+
+/// State of a transaction group.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TxgState {
+    /// Accepting new writes.
+    Open,
+    /// Waiting for writes in flight to finish.
+    Quiescing,
+    /// Waiting to be synced.
+    WaitingForSync,
+    /// Being written to disk.
+    Syncing,
+    /// Written to disk.
+    Committed,
+}
+
+impl TxgState {
+    fn from_letter(state: &str) -> ZpoolResult<TxgState> {
+        match state {
+            "O" => Ok(TxgState::Open),
+            "Q" => Ok(TxgState::Quiescing),
+            "W" => Ok(TxgState::WaitingForSync),
+            "S" => Ok(TxgState::Syncing),
+            "C" => Ok(TxgState::Committed),
+            _ => Err(ZpoolError::ParseError),
+        }
+    }
+}
+
+/// One transaction group.
+#[derive(Debug, Clone, PartialEq, Eq, Getters, CopyGetters)]
+pub struct TxgInfo {
+    /// Transaction group number.
+    #[get_copy = "pub"]
+    txg:      u64,
+    /// When it was opened, in nanoseconds since boot.
+    #[get_copy = "pub"]
+    birth:    u64,
+    /// Current state.
+    #[get_copy = "pub"]
+    state:    TxgState,
+    /// Dirty data, in bytes. Not reported by old versions.
+    #[get_copy = "pub"]
+    ndirty:   Option<u64>,
+    /// Bytes read.
+    #[get_copy = "pub"]
+    nread:    u64,
+    /// Bytes written.
+    #[get_copy = "pub"]
+    nwritten: u64,
+    /// Number of read operations.
+    #[get_copy = "pub"]
+    reads:    u64,
+    /// Number of write operations.
+    #[get_copy = "pub"]
+    writes:   u64,
+    /// Time spent open.
+    #[get = "pub"]
+    otime:    Duration,
+    /// Time spent quiescing.
+    #[get = "pub"]
+    qtime:    Duration,
+    /// Time spent waiting for sync.
+    #[get = "pub"]
+    wtime:    Duration,
+    /// Time spent syncing.
+    #[get = "pub"]
+    stime:    Duration,
+}
+
+/// Transaction group history of a pool at some point in time.
+#[derive(Debug, Clone, PartialEq, Eq, Getters)]
+#[get = "pub"]
+pub struct TxgSample {
+    /// Name of the pool.
+    pool:     String,
+    /// When the sample was taken.
+    taken_at: SystemTime,
+    /// Transaction groups, oldest first.
+    txgs:     Vec<TxgInfo>,
+}
+
+impl TxgSample {
+    /// Parse kstat `txgs` table.
+    ///
+    /// * `pool` - Name of the pool.
+    /// * `taken_at` - When the table was read.
+    /// * `table` - Content of the kstat.
+    pub fn parse(pool: &str, taken_at: SystemTime, table: &str) -> ZpoolResult<TxgSample> {
+        let mut lines = table.lines().skip_while(|line| !line.starts_with("txg "));
+        let header: Vec<&str> =
+            lines.next().ok_or(ZpoolError::ParseError)?.split_whitespace().collect();
+        let mut txgs = Vec::new();
+        for line in lines.filter(|line| !line.trim().is_empty()) {
+            let row: HashMap<&str, &str> =
+                header.iter().copied().zip(line.split_whitespace()).collect();
+            let num = |key: &str| -> ZpoolResult<u64> {
+                Ok(row.get(key).ok_or(ZpoolError::ParseError)?.parse()?)
+            };
+            let nanos = |key: &str| num(key).map(Duration::from_nanos);
+            txgs.push(TxgInfo {
+                txg:      num("txg")?,
+                birth:    num("birth")?,
+                state:    TxgState::from_letter(row.get("state").ok_or(ZpoolError::ParseError)?)?,
+                ndirty:   row.get("ndirty").map(|v| v.parse()).transpose()?,
+                nread:    num("nread")?,
+                nwritten: num("nwritten")?,
+                reads:    num("reads")?,
+                writes:   num("writes")?,
+                otime:    nanos("otime")?,
+                qtime:    nanos("qtime")?,
+                wtime:    nanos("wtime")?,
+                stime:    nanos("stime")?,
+            });
+        }
+        Ok(TxgSample { pool: pool.to_string(), taken_at, txgs })
+    }
+
+    /// Newest transaction group.
+    pub fn current_txg(&self) -> Option<u64> { self.txgs.iter().map(|txg| txg.txg).max() }
+
+    /// Newest transaction group that is committed.
+    pub fn last_committed(&self) -> Option<&TxgInfo> { self.committed().last() }
+
+    fn committed(&self) -> impl Iterator<Item = &TxgInfo> {
+        self.txgs.iter().filter(|txg| txg.state == TxgState::Committed)
+    }
+
+    /// Longest sync time among committed transaction groups.
+    pub fn max_sync_time(&self) -> Option<Duration> { self.committed().map(|txg| txg.stime).max() }
+
+    /// Average sync time of committed transaction groups.
+    pub fn average_sync_time(&self) -> Option<Duration> {
+        let times: Vec<Duration> = self.committed().map(|txg| txg.stime).collect();
+        let count = u32::try_from(times.len()).ok().filter(|count| *count > 0)?;
+        Some(times.into_iter().sum::<Duration>() / count)
+    }
+
+    /// Transaction groups committed after `earlier` sample was taken.
+    pub fn committed_since<'a>(&'a self, earlier: &TxgSample) -> Vec<&'a TxgInfo> {
+        let last = earlier.last_committed().map_or(0, |txg| txg.txg);
+        self.committed().filter(|txg| txg.txg > last).collect()
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn read_kstat(pool: &str) -> ZpoolResult<String> {
+    match std::fs::read_to_string(format!("/proc/spl/kstat/zfs/{}/txgs", pool)) {
+        Ok(table) => Ok(table),
+        Err(ref e) if e.kind() == std::io::ErrorKind::NotFound => Err(ZpoolError::PoolNotFound),
+        Err(e) => Err(ZpoolError::Io(e)),
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn read_kstat(pool: &str) -> ZpoolResult<String> {
+    let out = std::process::Command::new("sysctl")
+        .arg("-n")
+        .arg(format!("kstat.zfs.{}.txgs", pool))
+        .output()?;
+    if out.status.success() {
+        Ok(String::from_utf8_lossy(&out.stdout).into_owned())
+    } else {
+        Err(ZpoolError::PoolNotFound)
+    }
+}
+
+/// Take a sample of transaction group history of the pool.
+///
+/// * `pool` - Name of the pool.
+pub fn read_txgs<N: AsRef<str>>(pool: N) -> ZpoolResult<TxgSample> {
+    let table = read_kstat(pool.as_ref())?;
+    TxgSample::parse(pool.as_ref(), SystemTime::now(), &table)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::zpool::ZpoolErrorKind;
+
+    static TXGS: &str = "20 0 0x01 3 336 5467347316 192818089633
+txg      birth            state ndirty       nread        nwritten     reads    writes   otime        qtime        wtime        stime
+3105     192583046405     C     4096         0            69632        0        12       5000104604   3604         10854        46219
+3106     197583150613     C     0            1024         1073152      2        48       5000095017   3010         9817         2046219
+3107     202583245630     O     0            0            0            0        0        0            0            0            0
+";
+
+    #[test]
+    fn parsing() {
+        let sample = TxgSample::parse("tank", SystemTime::UNIX_EPOCH, TXGS).unwrap();
+        assert_eq!(3, sample.txgs().len());
+        assert_eq!(Some(3107), sample.current_txg());
+        let last = sample.last_committed().unwrap();
+        assert_eq!(3106, last.txg());
+        assert_eq!(Some(0), last.ndirty());
+        assert_eq!(1_073_152, last.nwritten());
+        assert_eq!(&Duration::from_nanos(5_000_095_017), last.otime());
+        assert_eq!(TxgState::Open, sample.txgs()[2].state());
+        assert_eq!(Some(Duration::from_nanos(2_046_219)), sample.max_sync_time());
+        assert_eq!(Some(Duration::from_nanos(1_046_219)), sample.average_sync_time());
+
+        let lines: Vec<&str> = TXGS.lines().collect();
+        let earlier = lines[..3].join("\n");
+        let earlier = TxgSample::parse("tank", SystemTime::UNIX_EPOCH, &earlier).unwrap();
+        let since: Vec<u64> = sample.committed_since(&earlier).iter().map(|t| t.txg()).collect();
+        assert_eq!(vec![3106], since);
+    }
+
+    #[test]
+    fn old_format() {
+        let table = "txg birth state nread nwritten reads writes otime qtime wtime stime
+7 1000 C 0 0 0 0 1 2 3 4
+";
+        let sample = TxgSample::parse("tank", SystemTime::UNIX_EPOCH, table).unwrap();
+        assert_eq!(None, sample.txgs()[0].ndirty());
+        assert_eq!(&Duration::from_nanos(4), sample.txgs()[0].stime());
+        let err = TxgSample::parse("tank", SystemTime::UNIX_EPOCH, "").unwrap_err();
+        assert_eq!(ZpoolErrorKind::ParseError, err.kind());
+        let truncated = format!("{}8 2000 C 0 0", table);
+        let err = TxgSample::parse("tank", SystemTime::UNIX_EPOCH, &truncated).unwrap_err();
+        assert_eq!(ZpoolErrorKind::ParseError, err.kind());
+    }
+}
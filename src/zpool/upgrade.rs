@@ -0,0 +1,102 @@
+//! Pool upgrades.
+//!
+//! After OpenZFS is upgraded, existing pools keep their old on-disk format: new features are left
+//! `disabled` and pools created before feature flags stay on a legacy version number.
+//! [`upgrade_status`](../trait.ZpoolEngine.html#tymethod.upgrade_status) tells what is missing,
+//! [`upgrade`](../trait.ZpoolEngine.html#tymethod.upgrade) and
+//! [`upgrade_all`](../trait.ZpoolEngine.html#tymethod.upgrade_all) enable everything the running
+//! version supports. Upgrade can't be undone and older systems might not be able to import the
+//! pool afterwards.
+//!
+//! ### Usage
+//! ```rust,no_run
+//! use libzetta::zpool::{ZpoolEngine, ZpoolOpen3};
+//!
+//! let engine = ZpoolOpen3::default();
+//! let status = engine.upgrade_status("tank").unwrap();
+//! if status.needs_upgrade() {
+//!     println!("enabling {}", status.disabled().join(", "));
+//!     engine.upgrade("tank").unwrap();
+//! }
+//! ```
+use crate::zpool::{ZpoolError, ZpoolResult};
+
+/// Which parts of the on-disk format of a pool are behind the running ZFS.
+#[derive(Debug, Clone, PartialEq, Eq, Getters)]
+#[get = "pub"]
+pub struct UpgradeStatus {
+    /// Name of the pool.
+    pool:     String,
+    /// Legacy version number. `None` for pools with feature flags.
+    version:  Option<u64>,
+    /// Features that are supported, but not enabled on this pool. Names are without `feature@`.
+    disabled: Vec<String>,
+}
+
+impl UpgradeStatus {
+    /// Pool still uses version numbers instead of feature flags.
+    pub fn is_legacy(&self) -> bool { self.version.is_some() }
+
+    /// [`upgrade`](../trait.ZpoolEngine.html#tymethod.upgrade) would change something.
+    pub fn needs_upgrade(&self) -> bool { self.is_legacy() || !self.disabled.is_empty() }
+
+    /// Parse output of `zpool get -Hp all <pool>`.
+    pub fn parse_stdout(pool: &str, stdout: &str) -> ZpoolResult<UpgradeStatus> {
+        let mut version = None;
+        let mut disabled = Vec::new();
+        let mut found = false;
+        for line in stdout.lines().filter(|l| !l.trim().is_empty()) {
+            let mut columns = line.split('\t');
+            let (name, key, value) = match (columns.next(), columns.next(), columns.next()) {
+                (Some(name), Some(key), Some(value)) => (name, key, value),
+                _ => return Err(ZpoolError::ParseError),
+            };
+            if name != pool {
+                continue;
+            }
+            found = true;
+            if key == "version" && value != "-" {
+                version = Some(value.parse()?);
+            } else if let Some(feature) = key.strip_prefix("feature@") {
+                if value == "disabled" {
+                    disabled.push(feature.to_string());
+                }
+            }
+        }
+        if !found {
+            return Err(ZpoolError::PoolNotFound);
+        }
+        Ok(UpgradeStatus { pool: pool.to_string(), version, disabled })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::zpool::ZpoolErrorKind;
+
+    #[test]
+    fn parsing() {
+        let stdout = "tank\tsize\t67108864\t-
+tank\tversion\t-\tdefault
+tank\tfeature@async_destroy\tenabled\tlocal
+tank\tfeature@empty_bpobj\tactive\tlocal
+tank\tfeature@draid\tdisabled\tlocal
+tank\tfeature@zilsaxattr\tdisabled\tlocal
+";
+        let status = UpgradeStatus::parse_stdout("tank", stdout).unwrap();
+        assert!(!status.is_legacy());
+        assert!(status.needs_upgrade());
+        assert_eq!(&vec![String::from("draid"), String::from("zilsaxattr")], status.disabled());
+
+        let status = UpgradeStatus::parse_stdout("old", "old\tversion\t28\tlocal\n").unwrap();
+        assert_eq!(&Some(28), status.version());
+        assert!(status.needs_upgrade());
+
+        let status = UpgradeStatus::parse_stdout("tank", "tank\tversion\t-\tdefault\n").unwrap();
+        assert!(!status.needs_upgrade());
+
+        let err = UpgradeStatus::parse_stdout("tank", "").unwrap_err();
+        assert_eq!(ZpoolErrorKind::PoolNotFound, err.kind());
+    }
+}
@@ -23,13 +23,24 @@
 //! let path = PathBuf::from("/tmp/sparseFile0");
 //! let vdev = CreateVdevRequest::SingleDisk(path);
 //! ```
+//! ##### Create a dRAID with double parity, 4 data disks and a distributed spare
+//!
+//! ```rust
+//! use libzetta::zpool::CreateVdevRequest;
+//! use std::path::PathBuf;
+//!
+//! let children = (0..11).map(|n| PathBuf::from(format!("da{}", n))).collect();
+//! let vdev = CreateVdevRequest::DRaid { parity: 2, data: 4, spares: 1, children };
+//! assert!(vdev.is_valid());
+//! ```
 
 use std::{default::Default,
           ffi::OsString,
           path::{Path, PathBuf},
           str::FromStr};
 
-use crate::zpool::{Health, Reason, ZpoolError};
+use crate::zpool::{maintenance::MaintenanceState, vdevprops::VdevProperties, Health, Reason,
+                   ZpoolError, ZpoolResult};
 
 /// Error statistics.
 ///
@@ -49,6 +60,11 @@ impl Default for ErrorStatistics {
     fn default() -> ErrorStatistics { ErrorStatistics { read: 0, write: 0, checksum: 0 } }
 }
 
+impl ErrorStatistics {
+    /// Whether any of the counters is above zero.
+    pub fn has_errors(&self) -> bool { self.read > 0 || self.write > 0 || self.checksum > 0 }
+}
+
 /// Basic building block of vdev.
 ///
 /// It can be backed by a entire block device, a partition or a file. This particular structure
@@ -70,10 +86,51 @@ pub struct Disk {
     /// reset.
     #[builder(default)]
     error_statistics: ErrorStatistics,
+    /// Vdev properties of the device. Only filled by
+    /// [`status_with_vdev_properties`](trait.ZpoolEngine.html#tymethod.status_with_vdev_properties)
+    #[builder(default)]
+    properties:       Option<VdevProperties>,
+    /// State of TRIM. Only filled by
+    /// [`status_with_maintenance`](trait.ZpoolEngine.html#tymethod.status_with_maintenance)
+    #[builder(default)]
+    trim:             Option<MaintenanceState>,
+    /// State of initialization. Only filled by
+    /// [`status_with_maintenance`](trait.ZpoolEngine.html#tymethod.status_with_maintenance)
+    #[builder(default)]
+    initialize:       Option<MaintenanceState>,
+    /// Devices under a `spare-N` node: the original device and the hot spare that stands in for
+    /// it. Empty for everything else.
+    #[builder(default)]
+    children:         Vec<Disk>,
 }
 
 impl Disk {
     pub fn builder() -> DiskBuilder { DiskBuilder::default() }
+
+    /// Whether this is a distributed spare of a dRAID vdev, e.g. `draid2-0-1`. These are listed
+    /// under `spares` like regular hot spares, but have no device of their own.
+    pub fn is_distributed_spare(&self) -> bool {
+        let name = self.path.to_string_lossy();
+        let mut parts = match name.strip_prefix("draid") {
+            Some(rest) => rest.split('-'),
+            None => return false,
+        };
+        parts.by_ref().take(3).filter(|part| part.parse::<u64>().is_ok()).count() == 3
+            && parts.next().is_none()
+    }
+
+    /// Whether this is a `spare-N` node that `zpool status` shows in place of a device once a hot
+    /// spare took over. Actual devices are in [`children`](#method.children).
+    pub fn is_spare_node(&self) -> bool {
+        let name = self.path.to_string_lossy();
+        name.strip_prefix("spare-").and_then(|n| n.parse::<u64>().ok()).is_some()
+    }
+
+    /// Take properties of this device from the list, matching by name.
+    pub(crate) fn attach_properties(&mut self, props: &[VdevProperties]) {
+        let name = self.path.to_string_lossy();
+        self.properties = props.iter().find(|p| p.name().as_str() == name).cloned();
+    }
 }
 
 /// Equal if path is the same.
@@ -112,6 +169,29 @@ pub enum VdevType {
     RaidZ2,
     /// The same as RAID-Z, but with 3 parity drives.
     RaidZ3,
+    /// Distributed RAID (OpenZFS 2.1+), named `draid<parity>:<data>d:<children>c:<spares>s` by
+    /// `zpool status`.
+    DRaid {
+        /// Number of parity devices in a redundancy group.
+        parity:   u8,
+        /// Number of data devices in a redundancy group.
+        data:     usize,
+        /// Number of devices in the vdev.
+        children: usize,
+        /// Number of distributed spares.
+        spares:   usize,
+    },
+}
+
+impl VdevType {
+    /// Parse `draid2:4d:11c:1s`.
+    fn parse_draid(source: &str) -> Option<VdevType> {
+        let mut parts = source.strip_prefix("draid")?.split(':');
+        let parity = parts.next()?.parse().ok()?;
+        let mut field = |suffix: char| parts.next()?.strip_suffix(suffix)?.parse().ok();
+        let (data, children, spares) = (field('d')?, field('c')?, field('s')?);
+        Some(VdevType::DRaid { parity, data, children, spares })
+    }
 }
 
 impl FromStr for VdevType {
@@ -123,7 +203,7 @@ impl FromStr for VdevType {
             "raidz1" => Ok(VdevType::RaidZ),
             "raidz2" => Ok(VdevType::RaidZ2),
             "raidz3" => Ok(VdevType::RaidZ3),
-            n => Err(ZpoolError::UnknownRaidType(String::from(n))),
+            n => VdevType::parse_draid(n).ok_or_else(|| ZpoolError::UnknownRaidType(n.into())),
         }
     }
 }
@@ -150,15 +230,35 @@ pub enum CreateVdevRequest {
     RaidZ2(Vec<PathBuf>),
     /// The same as RAID-Z, but with 3 parity drives.
     RaidZ3(Vec<PathBuf>),
+    /// [dRAID](https://openzfs.github.io/openzfs-docs/Basic%20Concepts/dRAID%20Howto.html)
+    /// (OpenZFS 2.1+) spreads parity, data and spare space of fixed width redundancy groups over
+    /// all children, so a failed disk is rebuilt by every remaining disk at once.
+    DRaid {
+        /// Number of parity devices in a redundancy group, 1 to 3.
+        parity:   u8,
+        /// Number of data devices in a redundancy group.
+        data:     usize,
+        /// Number of distributed spares.
+        spares:   usize,
+        /// Devices of the vdev.
+        children: Vec<PathBuf>,
+    },
 }
 
 impl CreateVdevRequest {
-    #[inline]
-    fn is_valid_raid(disks: &[PathBuf], min_disks: usize) -> bool {
-        if disks.len() < min_disks {
-            return false;
+    /// Smallest number of devices this vdev is accepted with. For RAID-Z it's one more than
+    /// parity would allow, see [`is_valid`](#method.is_valid).
+    pub fn min_disks(&self) -> usize {
+        match *self {
+            CreateVdevRequest::SingleDisk(_) => 1,
+            CreateVdevRequest::Mirror(_) => 2,
+            CreateVdevRequest::RaidZ(_) => 3,
+            CreateVdevRequest::RaidZ2(_) => 5,
+            CreateVdevRequest::RaidZ3(_) => 8,
+            CreateVdevRequest::DRaid { parity, data, spares, .. } => {
+                usize::from(parity) + data + spares
+            },
         }
-        true
     }
 
     /// Check if given CreateVdevRequest is valid.
@@ -171,13 +271,56 @@ impl CreateVdevRequest {
     /// This gives false negative results in RAIDZ2 and RAIDZ3. This is
     /// intentional.
     /// possible makes no sense.
-    pub fn is_valid(&self) -> bool {
+    pub fn is_valid(&self) -> bool { self.validate().is_ok() }
+
+    /// Same as [`is_valid`](#method.is_valid), but `InvalidTopology` says what's wrong.
+    pub fn validate(&self) -> ZpoolResult<()> {
+        let name = self.kind_name();
+        if let CreateVdevRequest::DRaid { parity, data, .. } = *self {
+            if !(1..=3).contains(&parity) {
+                let reason = format!("{} parity must be 1 to 3, got {}", name, parity);
+                return Err(ZpoolError::InvalidTopology(reason));
+            }
+            if data == 0 {
+                let reason = format!("{} needs at least one data device per group", name);
+                return Err(ZpoolError::InvalidTopology(reason));
+            }
+        }
+        let (min, got) = (self.min_disks(), self.disks().len());
+        if got < min {
+            let reason = format!("{} needs at least {} devices, got {}", name, min, got);
+            return Err(ZpoolError::InvalidTopology(reason));
+        }
+        Ok(())
+    }
+
+    /// Check if given CreateVdevRequest can be used as ZFS Intent Log: a valid single disk or
+    /// mirror. RAID-Z is not supported for log devices.
+    pub fn is_valid_log(&self) -> bool { self.validate_log().is_ok() }
+
+    /// Same as [`is_valid_log`](#method.is_valid_log), but `InvalidTopology` says what's wrong.
+    pub fn validate_log(&self) -> ZpoolResult<()> {
+        match *self {
+            CreateVdevRequest::SingleDisk(_) | CreateVdevRequest::Mirror(_) => self.validate(),
+            CreateVdevRequest::RaidZ(_)
+            | CreateVdevRequest::RaidZ2(_)
+            | CreateVdevRequest::RaidZ3(_)
+            | CreateVdevRequest::DRaid { .. } => {
+                let reason = format!("{} can't be used as log", self.kind_name());
+                Err(ZpoolError::InvalidTopology(reason))
+            },
+        }
+    }
+
+    /// Name of the vdev type as `zpool create` takes it.
+    fn kind_name(&self) -> &'static str {
         match *self {
-            CreateVdevRequest::SingleDisk(ref _disk) => true,
-            CreateVdevRequest::Mirror(ref disks) => CreateVdevRequest::is_valid_raid(disks, 2),
-            CreateVdevRequest::RaidZ(ref disks) => CreateVdevRequest::is_valid_raid(disks, 3),
-            CreateVdevRequest::RaidZ2(ref disks) => CreateVdevRequest::is_valid_raid(disks, 5),
-            CreateVdevRequest::RaidZ3(ref disks) => CreateVdevRequest::is_valid_raid(disks, 8),
+            CreateVdevRequest::SingleDisk(_) => "disk",
+            CreateVdevRequest::Mirror(_) => "mirror",
+            CreateVdevRequest::RaidZ(_) => "raidz",
+            CreateVdevRequest::RaidZ2(_) => "raidz2",
+            CreateVdevRequest::RaidZ3(_) => "raidz3",
+            CreateVdevRequest::DRaid { .. } => "draid",
         }
     }
 
@@ -199,6 +342,10 @@ impl CreateVdevRequest {
             CreateVdevRequest::RaidZ(disks) => CreateVdevRequest::conv_to_args("raidz", disks),
             CreateVdevRequest::RaidZ2(disks) => CreateVdevRequest::conv_to_args("raidz2", disks),
             CreateVdevRequest::RaidZ3(disks) => CreateVdevRequest::conv_to_args("raidz3", disks),
+            CreateVdevRequest::DRaid { parity, data, spares, children } => {
+                let kind = format!("draid{}:{}d:{}c:{}s", parity, data, children.len(), spares);
+                CreateVdevRequest::conv_to_args(kind, children)
+            },
         }
     }
 
@@ -207,6 +354,54 @@ impl CreateVdevRequest {
         CreateVdevRequest::SingleDisk(value.into())
     }
 
+    /// Short-cut to CreateVdevRequest::Mirror(disks)
+    pub fn mirror<I: IntoIterator<Item = O>, O: Into<PathBuf>>(disks: I) -> CreateVdevRequest {
+        CreateVdevRequest::Mirror(disks.into_iter().map(Into::into).collect())
+    }
+
+    /// Short-cut to CreateVdevRequest::RaidZ(disks)
+    pub fn raidz<I: IntoIterator<Item = O>, O: Into<PathBuf>>(disks: I) -> CreateVdevRequest {
+        CreateVdevRequest::RaidZ(disks.into_iter().map(Into::into).collect())
+    }
+
+    /// Short-cut to CreateVdevRequest::RaidZ2(disks)
+    pub fn raidz2<I: IntoIterator<Item = O>, O: Into<PathBuf>>(disks: I) -> CreateVdevRequest {
+        CreateVdevRequest::RaidZ2(disks.into_iter().map(Into::into).collect())
+    }
+
+    /// Short-cut to CreateVdevRequest::RaidZ3(disks)
+    pub fn raidz3<I: IntoIterator<Item = O>, O: Into<PathBuf>>(disks: I) -> CreateVdevRequest {
+        CreateVdevRequest::RaidZ3(disks.into_iter().map(Into::into).collect())
+    }
+
+    /// RAID-Z with given number of parity devices, 1 to 3.
+    pub fn raidz_with_parity<I: IntoIterator<Item = O>, O: Into<PathBuf>>(
+        parity: u8,
+        disks: I,
+    ) -> ZpoolResult<CreateVdevRequest> {
+        match parity {
+            1 => Ok(CreateVdevRequest::raidz(disks)),
+            2 => Ok(CreateVdevRequest::raidz2(disks)),
+            3 => Ok(CreateVdevRequest::raidz3(disks)),
+            n => {
+                let reason = format!("raidz parity must be 1 to 3, got {}", n);
+                Err(ZpoolError::InvalidTopology(reason))
+            },
+        }
+    }
+
+    /// Devices of this vdev.
+    pub fn disks(&self) -> &[PathBuf] {
+        match self {
+            CreateVdevRequest::SingleDisk(disk) => std::slice::from_ref(disk),
+            CreateVdevRequest::Mirror(disks)
+            | CreateVdevRequest::RaidZ(disks)
+            | CreateVdevRequest::RaidZ2(disks)
+            | CreateVdevRequest::RaidZ3(disks)
+            | CreateVdevRequest::DRaid { children: disks, .. } => disks,
+        }
+    }
+
     /// Get kind
     pub fn kind(&self) -> VdevType {
         match self {
@@ -215,6 +410,29 @@ impl CreateVdevRequest {
             CreateVdevRequest::RaidZ(_) => VdevType::RaidZ,
             CreateVdevRequest::RaidZ2(_) => VdevType::RaidZ2,
             CreateVdevRequest::RaidZ3(_) => VdevType::RaidZ3,
+            CreateVdevRequest::DRaid { parity, data, spares, children } => VdevType::DRaid {
+                parity:   *parity,
+                data:     *data,
+                children: children.len(),
+                spares:   *spares,
+            },
+        }
+    }
+
+    /// Opposite of [`kind`](#method.kind): request for a vdev of given type made of `disks`.
+    /// Single disk vdev takes the first disk.
+    pub(crate) fn from_kind(kind: VdevType, disks: Vec<PathBuf>) -> CreateVdevRequest {
+        match kind {
+            VdevType::SingleDisk => {
+                CreateVdevRequest::SingleDisk(disks.into_iter().next().unwrap_or_default())
+            },
+            VdevType::Mirror => CreateVdevRequest::Mirror(disks),
+            VdevType::RaidZ => CreateVdevRequest::RaidZ(disks),
+            VdevType::RaidZ2 => CreateVdevRequest::RaidZ2(disks),
+            VdevType::RaidZ3 => CreateVdevRequest::RaidZ3(disks),
+            VdevType::DRaid { parity, data, spares, .. } => {
+                CreateVdevRequest::DRaid { parity, data, spares, children: disks }
+            },
         }
     }
 }
@@ -245,11 +463,30 @@ pub struct Vdev {
     /// reset.
     #[builder(default)]
     error_statistics: ErrorStatistics,
+    /// Vdev properties. Only filled by
+    /// [`status_with_vdev_properties`](trait.ZpoolEngine.html#tymethod.status_with_vdev_properties)
+    #[builder(default)]
+    properties:       Option<VdevProperties>,
 }
 
 impl Vdev {
     /// Create a builder - a referred way of creating Vdev structure.
     pub fn builder() -> VdevBuilder { VdevBuilder::default() }
+
+    /// Take properties of this vdev and its devices from the list. Single disk vdevs share
+    /// properties with their device, other vdevs are matched by the list of their children.
+    pub(crate) fn attach_properties(&mut self, props: &[VdevProperties]) {
+        for disk in &mut self.disks {
+            disk.attach_properties(props);
+        }
+        self.properties = if self.kind == VdevType::SingleDisk {
+            self.disks.first().and_then(|disk| disk.properties.clone())
+        } else {
+            let children: Vec<String> =
+                self.disks.iter().map(|disk| disk.path.to_string_lossy().into_owned()).collect();
+            props.iter().find(|p| p.children() == &children).cloned()
+        };
+    }
 }
 /// Vdevs are equal of their type and backing disks are equal.
 impl PartialEq for Vdev {
@@ -269,6 +506,7 @@ impl PartialEq<CreateVdevRequest> for Vdev {
                 CreateVdevRequest::RaidZ(ref disks) => self.disks() == disks,
                 CreateVdevRequest::RaidZ2(ref disks) => self.disks() == disks,
                 CreateVdevRequest::RaidZ3(ref disks) => self.disks() == disks,
+                CreateVdevRequest::DRaid { ref children, .. } => self.disks() == children,
             }
         }
     }
@@ -431,6 +669,57 @@ mod test {
         assert_eq!(OsString::from("raidz3"), args[0]);
     }
 
+    #[test]
+    fn test_parity_helpers() {
+        let disks = ["da0", "da1", "da2", "da3", "da4", "da5", "da6", "da7"];
+        let raidz3 = CreateVdevRequest::raidz3(disks.iter());
+        assert_eq!(VdevType::RaidZ3, raidz3.kind());
+        assert_eq!(8, raidz3.disks().len());
+        assert!(raidz3.is_valid());
+        assert_eq!(CreateVdevRequest::mirror(&disks[..2]).kind(), VdevType::Mirror);
+
+        let raidz2 = CreateVdevRequest::raidz_with_parity(2, &disks[..3]).unwrap();
+        assert_eq!(VdevType::RaidZ2, raidz2.kind());
+        match raidz2.validate().unwrap_err() {
+            ZpoolError::InvalidTopology(reason) => {
+                assert_eq!("raidz2 needs at least 5 devices, got 3", reason)
+            },
+            other => panic!("{:?}", other),
+        }
+        let err = CreateVdevRequest::raidz_with_parity(4, &disks[..]).unwrap_err();
+        assert_eq!("invalid topology: raidz parity must be 1 to 3, got 4", err.to_string());
+        let err = CreateVdevRequest::raidz(&disks[..3]).validate_log().unwrap_err();
+        assert_eq!("invalid topology: raidz can't be used as log", err.to_string());
+    }
+
+    #[test]
+    fn test_draid() {
+        let children: Vec<PathBuf> = (0..7).map(|n| PathBuf::from(format!("da{}", n))).collect();
+        let vdev = CreateVdevRequest::DRaid { parity: 2, data: 4, spares: 1, children };
+        assert!(vdev.is_valid());
+        assert!(!vdev.is_valid_log());
+        let kind = VdevType::DRaid { parity: 2, data: 4, children: 7, spares: 1 };
+        assert_eq!(kind, vdev.kind());
+        assert_eq!(kind, VdevType::from_str("draid2:4d:7c:1s").unwrap());
+        assert!(VdevType::from_str("draid2:4d").is_err());
+
+        let args = vdev.into_args();
+        assert_eq!(8, args.len());
+        assert_eq!(OsString::from("draid2:4d:7c:1s"), args[0]);
+
+        let children = vec![PathBuf::from("da0"); 7];
+        let too_few = CreateVdevRequest::DRaid { parity: 2, data: 4, spares: 2, children };
+        assert!(!too_few.is_valid());
+        let children = vec![PathBuf::from("da0"); 7];
+        let no_parity = CreateVdevRequest::DRaid { parity: 0, data: 4, spares: 0, children };
+        assert!(!no_parity.is_valid());
+
+        let spare = |path: &str| Disk::builder().path(path).health(Health::Available).build();
+        assert!(spare("draid2-0-1").unwrap().is_distributed_spare());
+        assert!(!spare("draid2-0").unwrap().is_distributed_spare());
+        assert!(!spare("/dev/da8").unwrap().is_distributed_spare());
+    }
+
     #[test]
     fn short_versions_disk() {
         let name = "wat";
@@ -0,0 +1,352 @@
+//! Properties of individual vdevs.
+//!
+//! OpenZFS 2.2 added properties to vdevs, both top-level and leaf ones: space and error counters,
+//! `allocating` (whether new blocks go to this vdev) and tunables that decide when a slow or
+//! failing device is faulted (`checksum_n`/`checksum_t`, `io_n`/`io_t`). They are read with
+//! [`read_vdev_properties`](../trait.ZpoolEngine.html#tymethod.read_vdev_properties) and set with
+//! [`set_vdev_property`](../trait.ZpoolEngine.html#tymethod.set_vdev_property), or several at once
+//! with [`update_vdev_properties`](../trait.ZpoolEngine.html#tymethod.update_vdev_properties).
+//! [`status_with_vdev_properties`](../trait.ZpoolEngine.html#tymethod.status_with_vdev_properties)
+//! attaches them to every vdev and device of the pool.
+//!
+//! ### Usage
+//! ```rust,no_run
+//! use libzetta::zpool::{VdevPropertiesWrite, ZpoolEngine, ZpoolOpen3};
+//!
+//! let engine = ZpoolOpen3::default();
+//! let props = engine.read_vdev_properties("tank", "sda").unwrap();
+//! println!("{} checksum errors", props.checksum_errors());
+//!
+//! // Fault sda after 10 I/O errors in 60 seconds.
+//! engine.set_vdev_property("tank", "sda", "io_n", &String::from("10")).unwrap();
+//! engine.set_vdev_property("tank", "sda", "io_t", &String::from("60")).unwrap();
+//!
+//! // Stop writing to mirror-1 before removing it, and don't retry on sdc.
+//! let noalloc = VdevPropertiesWrite::builder().allocating(false).build().unwrap();
+//! engine.update_vdev_properties("tank", "mirror-1", noalloc).unwrap();
+//! let props = VdevPropertiesWrite::builder().failfast(true).build().unwrap();
+//! engine.update_vdev_properties("tank", "sdc", props).unwrap();
+//!
+//! let zpool = engine.status_with_vdev_properties("tank").unwrap();
+//! for vdev in zpool.vdevs() {
+//!     println!("{:?}", vdev.properties().as_ref().map(|p| p.allocating()));
+//! }
+//! ```
+use std::{collections::HashMap, ffi::OsString, path::PathBuf};
+
+use crate::zpool::{Health, PropPair, ZpoolError, ZpoolResult};
+
+/// Properties of a vdev as reported by `zpool get -Hp all <pool> <vdev>`.
+#[derive(Debug, Clone, PartialEq, Eq, Getters)]
+#[get = "pub"]
+pub struct VdevProperties {
+    /// Name of the vdev: device name for leaves, `mirror-0`, `raidz1-1`, etc. for top-level ones.
+    name:            String,
+    /// Unique identifier of the vdev.
+    guid:            u64,
+    /// Current state.
+    state:           Health,
+    /// Size of the vdev.
+    size:            Option<u64>,
+    /// Allocated space.
+    allocated:       Option<u64>,
+    /// Free space.
+    free:            Option<u64>,
+    /// Space that can be claimed by expanding the vdev.
+    expand_size:     Option<u64>,
+    /// Fragmentation of free space, in percent.
+    fragmentation:   Option<u64>,
+    /// Used space, in percent.
+    capacity:        Option<u64>,
+    /// Block size shift.
+    ashift:          Option<u64>,
+    /// Path of the device. Only leaf vdevs have it.
+    path:            Option<PathBuf>,
+    /// Name of the parent vdev.
+    parent:          Option<String>,
+    /// Names of child vdevs.
+    children:        Vec<String>,
+    /// Free-form comment.
+    comment:         Option<String>,
+    /// Number of read errors.
+    read_errors:     u64,
+    /// Number of write errors.
+    write_errors:    u64,
+    /// Number of checksum errors.
+    checksum_errors: u64,
+    /// Vdev is being removed.
+    removing:        bool,
+    /// New blocks are allocated on this vdev. Only top-level vdevs have it.
+    allocating:      Option<bool>,
+    /// Device is failed fast on I/O errors instead of retrying.
+    failfast:        bool,
+    /// Number of checksum errors within `checksum_t` seconds that faults the device.
+    checksum_n:      Option<u64>,
+    /// Window for `checksum_n`, in seconds.
+    checksum_t:      Option<u64>,
+    /// Number of I/O errors within `io_t` seconds that faults the device.
+    io_n:            Option<u64>,
+    /// Window for `io_n`, in seconds.
+    io_t:            Option<u64>,
+    /// Properties this library doesn't know about.
+    unknown:         HashMap<String, String>,
+}
+
+fn value(props: &HashMap<&str, &str>, key: &str) -> Option<String> {
+    props.get(key).filter(|v| **v != "-" && !v.is_empty()).map(|v| (*v).to_string())
+}
+
+fn num(props: &HashMap<&str, &str>, key: &str) -> ZpoolResult<Option<u64>> {
+    match value(props, key) {
+        Some(v) => Ok(Some(v.parse()?)),
+        None => Ok(None),
+    }
+}
+
+fn flag(props: &HashMap<&str, &str>, key: &str) -> ZpoolResult<Option<bool>> {
+    match props.get(key) {
+        Some(&"on") => Ok(Some(true)),
+        Some(&"off") => Ok(Some(false)),
+        Some(&"-") | None => Ok(None),
+        Some(_) => Err(ZpoolError::ParseError),
+    }
+}
+
+static KNOWN: &[&str] = &[
+    "name",
+    "guid",
+    "state",
+    "size",
+    "allocated",
+    "free",
+    "expandsize",
+    "fragmentation",
+    "capacity",
+    "ashift",
+    "path",
+    "parent",
+    "children",
+    "comment",
+    "read_errors",
+    "write_errors",
+    "checksum_errors",
+    "removing",
+    "allocating",
+    "failfast",
+    "checksum_n",
+    "checksum_t",
+    "io_n",
+    "io_t",
+];
+
+impl VdevProperties {
+    fn from_map(name: &str, props: &HashMap<&str, &str>) -> ZpoolResult<VdevProperties> {
+        let unknown = props
+            .iter()
+            .filter(|(key, _)| !KNOWN.contains(key))
+            .map(|(key, value)| ((*key).to_string(), (*value).to_string()))
+            .collect();
+        let children = value(props, "children")
+            .map(|c| c.split(',').map(String::from).collect())
+            .unwrap_or_default();
+        Ok(VdevProperties {
+            name:            name.to_string(),
+            guid:            num(props, "guid")?.ok_or(ZpoolError::ParseError)?,
+            state:           Health::try_from_str(props.get("state").copied())?,
+            size:            num(props, "size")?,
+            allocated:       num(props, "allocated")?,
+            free:            num(props, "free")?,
+            expand_size:     num(props, "expandsize")?,
+            fragmentation:   num(props, "fragmentation")?,
+            capacity:        num(props, "capacity")?,
+            ashift:          num(props, "ashift")?,
+            path:            value(props, "path").map(PathBuf::from),
+            parent:          value(props, "parent"),
+            children,
+            comment:         value(props, "comment"),
+            read_errors:     num(props, "read_errors")?.unwrap_or(0),
+            write_errors:    num(props, "write_errors")?.unwrap_or(0),
+            checksum_errors: num(props, "checksum_errors")?.unwrap_or(0),
+            removing:        flag(props, "removing")?.unwrap_or(false),
+            allocating:      flag(props, "allocating")?,
+            failfast:        flag(props, "failfast")?.unwrap_or(false),
+            checksum_n:      num(props, "checksum_n")?,
+            checksum_t:      num(props, "checksum_t")?,
+            io_n:            num(props, "io_n")?,
+            io_t:            num(props, "io_t")?,
+            unknown,
+        })
+    }
+
+    /// Parse output of `zpool get -Hp all <pool> <vdev>...`. Vdevs are returned in the order they
+    /// first appear.
+    pub fn parse_stdout(stdout: &str) -> ZpoolResult<Vec<VdevProperties>> {
+        let mut vdevs: Vec<(&str, HashMap<&str, &str>)> = Vec::new();
+        for line in stdout.lines().filter(|l| !l.trim().is_empty()) {
+            let mut columns = line.split('\t');
+            let (vdev, key, value) = match (columns.next(), columns.next(), columns.next()) {
+                (Some(vdev), Some(key), Some(value)) => (vdev, key, value),
+                _ => return Err(ZpoolError::ParseError),
+            };
+            match vdevs.iter_mut().find(|(name, _)| *name == vdev) {
+                Some((_, props)) => {
+                    props.insert(key, value);
+                },
+                None => vdevs.push((vdev, [(key, value)].iter().copied().collect())),
+            }
+        }
+        vdevs.iter().map(|(vdev, props)| VdevProperties::from_map(vdev, props)).collect()
+    }
+}
+
+/// Vdev properties that can be changed. Only properties that are set are passed to `zpool set`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Getters, Builder)]
+#[builder(default, setter(into, strip_option))]
+#[get = "pub"]
+pub struct VdevPropertiesWrite {
+    /// Allocate new blocks on this top-level vdev. `false` (noalloc) drains it before removal.
+    allocating: Option<bool>,
+    /// Fail fast on I/O errors instead of retrying.
+    failfast:   Option<bool>,
+    /// Number of checksum errors within `checksum_t` seconds that faults the device.
+    checksum_n: Option<u64>,
+    /// Window for `checksum_n`, in seconds.
+    checksum_t: Option<u64>,
+    /// Number of I/O errors within `io_t` seconds that faults the device.
+    io_n:       Option<u64>,
+    /// Window for `io_n`, in seconds.
+    io_t:       Option<u64>,
+    /// Free-form comment.
+    comment:    Option<String>,
+}
+
+impl VdevPropertiesWrite {
+    /// A preferred way to create this structure.
+    pub fn builder() -> VdevPropertiesWriteBuilder { VdevPropertiesWriteBuilder::default() }
+
+    /// Make properties usable as `zpool set` args, one `key=value` per property.
+    pub(crate) fn into_args(self) -> Vec<OsString> {
+        let flags = [("allocating", self.allocating), ("failfast", self.failfast)];
+        let numbers = [
+            ("checksum_n", self.checksum_n),
+            ("checksum_t", self.checksum_t),
+            ("io_n", self.io_n),
+            ("io_t", self.io_t),
+        ];
+        let flags = flags.iter().filter_map(|(key, v)| v.map(|v| v.to_pair(key)));
+        let numbers = numbers.iter().filter_map(|(key, v)| v.map(|v| format!("{}={}", key, v)));
+        let comment = self.comment.map(|c| c.to_pair("comment"));
+        flags.chain(numbers).chain(comment).map(OsString::from).collect()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::zpool::{Disk, Vdev, VdevType, Zpool};
+
+    static STDOUT: &str = "mirror-0\tcapacity\t1\t-
+mirror-0\tstate\tONLINE\t-
+mirror-0\tguid\t7184621392617042921\t-
+mirror-0\tsize\t5905580032\t-
+mirror-0\tallocated\t110592\t-
+mirror-0\tfree\t5905469440\t-
+mirror-0\tfragmentation\t0\t-
+mirror-0\tparent\troot-0\t-
+mirror-0\tchildren\tsda,sdb\t-
+mirror-0\tremoving\toff\t-
+mirror-0\tallocating\ton\tdefault
+mirror-0\tfailfast\ton\tdefault
+mirror-0\tio_n\t-\tdefault
+mirror-0\tbytes_read\t4096\t-
+sda\tstate\tONLINE\t-
+sda\tguid\t1234\t-
+sda\tpath\t/dev/sda1\t-
+sda\tparent\tmirror-0\t-
+sda\tchecksum_errors\t3\t-
+sda\tremoving\t-\t-
+sda\tallocating\t-\t-
+sda\tio_n\t10\tlocal
+sda\tio_t\t60\tlocal
+";
+
+    #[test]
+    fn parsing() {
+        let props = VdevProperties::parse_stdout(STDOUT).unwrap();
+        assert_eq!(2, props.len());
+
+        let mirror = &props[0];
+        assert_eq!("mirror-0", mirror.name());
+        assert_eq!(&7_184_621_392_617_042_921, mirror.guid());
+        assert_eq!(&Health::Online, mirror.state());
+        assert_eq!(&Some(110_592), mirror.allocated());
+        assert_eq!(&vec![String::from("sda"), String::from("sdb")], mirror.children());
+        assert_eq!(&Some(true), mirror.allocating());
+        assert_eq!(&None, mirror.io_n());
+        assert_eq!(Some(&String::from("4096")), mirror.unknown().get("bytes_read"));
+
+        let sda = &props[1];
+        assert_eq!(&Some(PathBuf::from("/dev/sda1")), sda.path());
+        assert_eq!(&Some(String::from("mirror-0")), sda.parent());
+        assert_eq!(&3, sda.checksum_errors());
+        assert_eq!(&None, sda.allocating());
+        assert_eq!(&Some(10), sda.io_n());
+        assert_eq!(&Some(60), sda.io_t());
+        assert!(sda.children().is_empty());
+
+        let err = VdevProperties::parse_stdout("sda\tstate\tONLINE\t-\n").unwrap_err();
+        assert_eq!(ZpoolError::ParseError.kind(), err.kind());
+    }
+
+    #[test]
+    fn attaching_to_tree() {
+        let disk = |path: &str| Disk::builder().path(path).health(Health::Online).build().unwrap();
+        let mirror = Vdev::builder()
+            .kind(VdevType::Mirror)
+            .health(Health::Online)
+            .disks(vec![disk("sda"), disk("sdb")])
+            .build()
+            .unwrap();
+        let single = Vdev::builder()
+            .kind(VdevType::SingleDisk)
+            .health(Health::Online)
+            .disks(vec![disk("sdc")])
+            .build()
+            .unwrap();
+        let zpool = Zpool::builder()
+            .name("tank")
+            .health(Health::Online)
+            .vdevs(vec![mirror, single])
+            .build()
+            .unwrap();
+
+        let stdout = format!("{}sdc\tstate\tONLINE\t-\nsdc\tguid\t42\t-\n", STDOUT);
+        let props = VdevProperties::parse_stdout(&stdout).unwrap();
+        let zpool = zpool.with_vdev_properties(&props);
+
+        let mirror = &zpool.vdevs()[0];
+        assert_eq!("mirror-0", mirror.properties().as_ref().unwrap().name());
+        assert_eq!(&3, mirror.disks()[0].properties().as_ref().unwrap().checksum_errors());
+        assert!(mirror.disks()[1].properties().is_none());
+        let single = &zpool.vdevs()[1];
+        assert_eq!(&42, single.properties().as_ref().unwrap().guid());
+        assert_eq!(single.properties(), single.disks()[0].properties());
+    }
+
+    #[test]
+    fn write_args() {
+        let props = VdevPropertiesWrite::builder()
+            .allocating(false)
+            .io_n(10_u64)
+            .io_t(60_u64)
+            .comment("slot 4")
+            .build()
+            .unwrap();
+        let args: Vec<OsString> = ["allocating=off", "io_n=10", "io_t=60", "comment=slot 4"]
+            .iter()
+            .map(OsString::from)
+            .collect();
+        assert_eq!(args, props.into_args());
+        assert!(VdevPropertiesWrite::default().into_args().is_empty());
+    }
+}
@@ -1,20 +1,34 @@
 #![allow(clippy::mutex_atomic)]
 #[macro_use] extern crate lazy_static;
 
-use std::{fs::{self, DirBuilder},
+use std::{collections::HashMap,
+          convert::TryInto,
+          fs::{self, DirBuilder},
+          io::{Seek, SeekFrom},
+          os::unix::io::AsRawFd,
           panic,
           path::{Path, PathBuf},
-          sync::Mutex};
+          sync::Mutex,
+          time::Duration};
 
 use cavity::{fill, Bytes, WriteMode};
 use rand::Rng;
 
-use libzetta::{slog::*,
-               zfs::{BookmarkRequest, Copies, CreateDatasetRequest, DatasetKind, Error,
-                     Properties, SendFlags, SnapDir, ZfsEngine, ZfsLzc},
+use libzetta::{names::{DatasetName, PoolName},
+               slog::*,
+               zfs::{BookmarkRequest, CanMount, Copies, CreateDatasetRequest, DatasetKind, Error,
+                     ErrorKind, Properties, SendFlags, SnapDir, ZfsEngine, ZfsLzc},
                zpool::{CreateVdevRequest, CreateZpoolRequest, ZpoolEngine, ZpoolOpen3}};
 
-use libzetta::{zfs::{properties::VolumeMode, DelegatingZfsEngine, DestroyTiming},
+use libzetta::{zfs::{delegation::{Delegate, Delegation, PermissionSet},
+                     destroy::DestroyOptions,
+                     holds::{cleanup_file, HoldGuard},
+                     properties::VolumeMode,
+                     rename::{rename_tree, RenameOptions, RenamePlan},
+                     replication::replicate,
+                     stream::{receive_dry_run, ReceiveAction},
+                     zvol::{clone_zvol, resize_zvol, snapshot_zvol, ResizeMode},
+                     DelegatingZfsEngine, DestroyTiming, ZfsOpen3},
                zpool::CreateMode};
 
 static ONE_MB_IN_BYTES: u64 = 1024 * 1024;
@@ -172,6 +186,72 @@ fn easy_invalid_zfs() {
     assert_eq!(Error::invalid_input(), res);
 }
 
+#[test]
+fn create_with_properties() {
+    let zpool = SHARED_ZPOOL.clone();
+    let dataset_path = PathBuf::from(format!("{}/{}", zpool, get_dataset_name()));
+    let mount_point = PathBuf::from(format!("/tmp/{}", get_dataset_name()));
+
+    let zfs = DelegatingZfsEngine::new().expect("Failed to initialize ZfsLzc");
+    let request = CreateDatasetRequest::builder()
+        .name(dataset_path.clone())
+        .kind(DatasetKind::Filesystem)
+        .atime(false)
+        .exec(false)
+        .can_mount(CanMount::NoAuto)
+        .mount_point(mount_point.clone())
+        .reservation(ONE_MB_IN_BYTES)
+        .build()
+        .unwrap();
+    zfs.create(request).expect("Failed to create dataset");
+
+    if let Properties::Filesystem(properties) = zfs.read_properties(&dataset_path).unwrap() {
+        assert_eq!(&false, properties.atime());
+        assert_eq!(&false, properties.exec());
+        assert_eq!(&true, properties.setuid());
+        assert_eq!(&CanMount::NoAuto, properties.can_mount());
+        assert_eq!(&Some(mount_point), properties.mount_point());
+        assert_eq!(&ONE_MB_IN_BYTES, properties.reservation());
+    } else {
+        panic!("Read not fs properties");
+    }
+
+    let volume_path = PathBuf::from(format!("{}/{}", zpool, get_dataset_name()));
+    let request = CreateDatasetRequest::builder()
+        .name(volume_path.clone())
+        .kind(DatasetKind::Volume)
+        .volume_size(ONE_MB_IN_BYTES)
+        .atime(false)
+        .build()
+        .unwrap();
+    zfs.create(request).expect("Failed to create volume");
+    assert!(zfs.exists(volume_path).unwrap());
+}
+
+#[test]
+fn create_typed_errors() {
+    let zpool = SHARED_ZPOOL.clone();
+    let dataset_path = PathBuf::from(format!("{}/{}", zpool, get_dataset_name()));
+
+    let zfs = ZfsLzc::new().expect("Failed to initialize ZfsLzc");
+    let request = CreateDatasetRequest::builder()
+        .name(dataset_path.clone())
+        .kind(DatasetKind::Filesystem)
+        .build()
+        .unwrap();
+    zfs.create(request.clone()).expect("Failed to create dataset");
+    let err = zfs.create(request).unwrap_err();
+    assert_eq!(Error::DatasetExists(dataset_path.clone()), err);
+
+    let orphan = dataset_path.join("missing").join("child");
+    let request =
+        CreateDatasetRequest::builder().name(orphan).kind(DatasetKind::Filesystem).build().unwrap();
+    match zfs.create(request).unwrap_err() {
+        Error::DatasetNotFound(parent) => assert_eq!(dataset_path.join("missing"), parent),
+        err => panic!("Unexpected error: {}", err),
+    }
+}
+
 #[test]
 fn create_and_destroy() {
     let zpool = SHARED_ZPOOL.clone();
@@ -195,6 +275,364 @@ fn create_and_destroy() {
     assert!(!res);
 }
 
+#[test]
+fn destroy_lzc() {
+    let zpool = SHARED_ZPOOL.clone();
+    let parent = PathBuf::from(format!("{}/{}", zpool, get_dataset_name()));
+    let child = parent.join("child");
+    let snapshot = PathBuf::from(format!("{}@snap", child.display()));
+
+    // lzc doesn't mount what it creates, so nothing has to be unmounted.
+    let zfs = ZfsLzc::new().expect("Failed to initialize ZfsLzc");
+    for name in &[&parent, &child] {
+        let request =
+            CreateDatasetRequest::builder().name(*name).kind(DatasetKind::Filesystem).build();
+        zfs.create(request.unwrap()).expect("Failed to create dataset");
+    }
+    zfs.snapshot(&[snapshot.clone()], None).expect("Failed to create snapshot");
+
+    assert_eq!(Error::DatasetBusy(parent.clone()), zfs.destroy(parent.clone()).unwrap_err());
+    assert_eq!(Error::DatasetBusy(child.clone()), zfs.destroy(child.clone()).unwrap_err());
+
+    zfs.destroy(snapshot.clone()).unwrap();
+    zfs.destroy(child.clone()).unwrap();
+    zfs.destroy(parent.clone()).unwrap();
+    assert!(!zfs.exists(parent.clone()).unwrap());
+
+    let err = zfs.destroy(parent).unwrap_err();
+    assert_eq!(ErrorKind::DatasetNotFound, err.kind());
+    let err = zfs.destroy(snapshot).unwrap_err();
+    assert_eq!(ErrorKind::DatasetNotFound, err.kind());
+}
+
+#[test]
+fn destroy_recursive_with_clones() {
+    let zpool = SHARED_ZPOOL.clone();
+    let root = PathBuf::from(format!("{}/{}", zpool, get_dataset_name()));
+    let child = root.join("child");
+    let snapshot = PathBuf::from(format!("{}@snap", child.display()));
+    let clone = PathBuf::from(format!("{}/{}", zpool, get_dataset_name()));
+
+    let zfs = DelegatingZfsEngine::new().expect("Failed to initialize ZfsLzc");
+    for name in &[&root, &child] {
+        let request =
+            CreateDatasetRequest::builder().name(*name).kind(DatasetKind::Filesystem).build();
+        zfs.create(request.unwrap()).expect("Failed to create dataset");
+    }
+    zfs.snapshot(&[snapshot.clone()], None).expect("Failed to create snapshot");
+    zfs.clone_snapshot(snapshot.clone(), clone.clone(), None).expect("Failed to clone");
+
+    let err = zfs.destroy_recursive(root.clone(), DestroyOptions::default()).unwrap_err();
+    assert_eq!(Error::DatasetBusy(snapshot.clone()), err);
+
+    let options = DestroyOptions::builder().dependents(true).dry_run(true).build().unwrap();
+    let planned = zfs.destroy_recursive(root.clone(), options).unwrap();
+    assert_eq!(vec![clone.clone(), snapshot, child, root.clone()], planned);
+    assert!(zfs.exists(clone.clone()).unwrap());
+
+    let options = DestroyOptions::builder().dependents(true).build().unwrap();
+    assert_eq!(planned, zfs.destroy_recursive(root.clone(), options).unwrap());
+    assert!(!zfs.exists(root).unwrap());
+    assert!(!zfs.exists(clone).unwrap());
+}
+
+#[test]
+fn snapshot_atomic_with_errors() {
+    let zpool = SHARED_ZPOOL.clone();
+    let zfs = DelegatingZfsEngine::new().expect("Failed to initialize ZfsLzc");
+    let first = PathBuf::from(format!("{}/{}", zpool, get_dataset_name()));
+    let second = PathBuf::from(format!("{}/{}", zpool, get_dataset_name()));
+    for name in &[&first, &second] {
+        let request =
+            CreateDatasetRequest::builder().name(*name).kind(DatasetKind::Filesystem).build();
+        zfs.create(request.unwrap()).expect("Failed to create dataset");
+    }
+    let taken = PathBuf::from(format!("{}@taken", first.display()));
+    zfs.snapshot(&[taken.clone()], None).expect("Failed to create snapshot");
+
+    let missing = PathBuf::from(format!("{}/missing@new", zpool));
+    let fresh = PathBuf::from(format!("{}@new", second.display()));
+    let err = zfs.snapshot(&[taken.clone(), fresh.clone(), missing.clone()], None).unwrap_err();
+    if let Error::BatchFailed(errors) = err {
+        assert_eq!(Some(&Error::DatasetExists(taken.clone())), errors.get(&taken));
+        assert_eq!(Some(&Error::DatasetNotFound(missing.clone())), errors.get(&missing));
+        assert!(!errors.contains_key(&fresh));
+    } else {
+        panic!("Unexpected error: {}", err);
+    }
+    assert!(!zfs.exists(fresh).unwrap());
+
+    let other = PathBuf::from("other/dataset@new");
+    let err = zfs.snapshot(&[missing, other], None).unwrap_err();
+    assert_eq!(ErrorKind::ValidationErrors, err.kind());
+}
+
+#[test]
+fn destroy_snapshots_with_errors() {
+    let zpool = SHARED_ZPOOL.clone();
+    let zfs = DelegatingZfsEngine::new().expect("Failed to initialize ZfsLzc");
+    let root = PathBuf::from(format!("{}/{}", zpool, get_dataset_name()));
+    let request =
+        CreateDatasetRequest::builder().name(root.clone()).kind(DatasetKind::Filesystem).build();
+    zfs.create(request.unwrap()).expect("Failed to create dataset");
+    let cloned = PathBuf::from(format!("{}@cloned", root.display()));
+    let plain = PathBuf::from(format!("{}@plain", root.display()));
+    zfs.snapshot(&[cloned.clone(), plain.clone()], None).expect("Failed to create snapshots");
+    let clone = PathBuf::from(format!("{}/{}", zpool, get_dataset_name()));
+    zfs.clone_snapshot(cloned.clone(), clone.clone(), None).expect("Failed to clone");
+
+    let snapshots = [cloned.clone(), plain.clone()];
+    let err = zfs.destroy_snapshots(&snapshots, DestroyTiming::RightNow).unwrap_err();
+    if let Error::BatchFailed(errors) = err {
+        assert_eq!(1, errors.len());
+        assert_eq!(Some(&Error::DatasetBusy(cloned.clone())), errors.get(&cloned));
+    } else {
+        panic!("Unexpected error: {}", err);
+    }
+    assert!(zfs.exists(plain).unwrap());
+
+    zfs.destroy(clone).unwrap();
+    zfs.destroy_snapshots(&snapshots, DestroyTiming::RightNow).unwrap();
+    assert!(!zfs.exists(cloned).unwrap());
+}
+
+#[test]
+fn destroy_deferred() {
+    let zpool = SHARED_ZPOOL.clone();
+    let zfs = DelegatingZfsEngine::new().expect("Failed to initialize ZfsLzc");
+    let root = PathBuf::from(format!("{}/{}", zpool, get_dataset_name()));
+    let request =
+        CreateDatasetRequest::builder().name(root.clone()).kind(DatasetKind::Filesystem).build();
+    zfs.create(request.unwrap()).expect("Failed to create dataset");
+    let snapshot = PathBuf::from(format!("{}@golden", root.display()));
+    zfs.snapshot(&[snapshot.clone()], None).expect("Failed to create snapshot");
+    let clone = PathBuf::from(format!("{}/{}", zpool, get_dataset_name()));
+    zfs.clone_snapshot(snapshot.clone(), clone.clone(), None).expect("Failed to clone");
+
+    let options = DestroyOptions::builder().defer(true).build().unwrap();
+    assert_eq!(vec![snapshot.clone()], zfs.destroy_recursive(snapshot.clone(), options).unwrap());
+    if let Properties::Snapshot(properties) = zfs.read_properties(&snapshot).unwrap() {
+        assert_eq!(&true, properties.defer_destroy());
+    } else {
+        panic!("Read not snapshot properties");
+    }
+
+    zfs.destroy(clone).unwrap();
+    assert!(!zfs.exists(snapshot).unwrap());
+}
+
+#[test]
+fn clone_lzc_with_properties() {
+    let zpool = SHARED_ZPOOL.clone();
+    let zfs = ZfsLzc::new().expect("Failed to initialize ZfsLzc");
+    let golden = PathBuf::from(format!("{}/{}", zpool, get_dataset_name()));
+    let request =
+        CreateDatasetRequest::builder().name(golden.clone()).kind(DatasetKind::Filesystem).build();
+    zfs.create(request.unwrap()).expect("Failed to create dataset");
+    let snapshot = PathBuf::from(format!("{}@golden", golden.display()));
+    zfs.snapshot(&[snapshot.clone()], None).expect("Failed to create snapshot");
+
+    let clone = PathBuf::from(format!("{}/{}", zpool, get_dataset_name()));
+    let mut properties = HashMap::new();
+    properties.insert(String::from("compression"), String::from("lz4"));
+    properties.insert(String::from("atime"), String::from("off"));
+    properties.insert(String::from("ci:job"), String::from("42"));
+    zfs.clone_snapshot(snapshot.clone(), clone.clone(), Some(properties)).unwrap();
+
+    let reader = DelegatingZfsEngine::new().expect("Failed to initialize ZfsLzc");
+    if let Properties::Filesystem(props) = reader.read_properties(&clone).unwrap() {
+        assert_eq!(&Some(snapshot.to_string_lossy().to_string()), props.origin());
+        assert_eq!("lz4", props.compression().as_ref());
+        assert_eq!(&false, props.atime());
+        assert_eq!(Some(&String::from("42")), props.unknown_properties().get("ci:job"));
+    } else {
+        panic!("Read not fs properties");
+    }
+
+    let err = zfs.clone_snapshot(snapshot.clone(), clone.clone(), None).unwrap_err();
+    assert_eq!(Error::DatasetExists(clone.clone()), err);
+    let missing = PathBuf::from(format!("{}@missing", golden.display()));
+    let err = zfs.clone_snapshot(missing.clone(), clone.join("nope"), None).unwrap_err();
+    assert_eq!(Error::DatasetNotFound(missing), err);
+
+    let mut properties = HashMap::new();
+    properties.insert(String::from("sync"), String::from("always"));
+    let target = clone.join("sync");
+    let err = zfs.clone_snapshot(snapshot, target, Some(properties)).unwrap_err();
+    assert_eq!(ErrorKind::InvalidProperty, err.kind());
+}
+
+#[test]
+fn promote_clone() {
+    let zpool = SHARED_ZPOOL.clone();
+    let zfs = DelegatingZfsEngine::new().expect("Failed to initialize ZfsLzc");
+    let origin = PathBuf::from(format!("{}/{}", zpool, get_dataset_name()));
+    let request =
+        CreateDatasetRequest::builder().name(origin.clone()).kind(DatasetKind::Filesystem).build();
+    zfs.create(request.unwrap()).expect("Failed to create dataset");
+    let snapshot = PathBuf::from(format!("{}@base", origin.display()));
+    zfs.snapshot(&[snapshot.clone()], None).expect("Failed to create snapshot");
+    let clone = PathBuf::from(format!("{}/{}", zpool, get_dataset_name()));
+    zfs.clone_snapshot(snapshot, clone.clone(), None).expect("Failed to clone");
+
+    assert_eq!(Error::NotAClone(origin.clone()), zfs.promote(origin.clone()).unwrap_err());
+    zfs.promote(clone.clone()).unwrap();
+
+    let promoted = PathBuf::from(format!("{}@base", clone.display()));
+    assert!(zfs.exists(promoted.clone()).unwrap());
+    if let Properties::Filesystem(properties) = zfs.read_properties(&origin).unwrap() {
+        assert_eq!(&Some(promoted.to_string_lossy().to_string()), properties.origin());
+    } else {
+        panic!("Read not fs properties");
+    }
+
+    zfs.destroy(origin).unwrap();
+    assert!(zfs.exists(clone).unwrap());
+}
+
+#[test]
+fn rollback_to_snapshots() {
+    let zpool = SHARED_ZPOOL.clone();
+    let zfs = DelegatingZfsEngine::new().expect("Failed to initialize ZfsLzc");
+    let root = PathBuf::from(format!("{}/{}", zpool, get_dataset_name()));
+    let request =
+        CreateDatasetRequest::builder().name(root.clone()).kind(DatasetKind::Filesystem).build();
+    zfs.create(request.unwrap()).expect("Failed to create dataset");
+    let first = PathBuf::from(format!("{}@first", root.display()));
+    let second = PathBuf::from(format!("{}@second", root.display()));
+    zfs.snapshot(&[first.clone()], None).expect("Failed to create snapshot");
+    zfs.snapshot(&[second.clone()], None).expect("Failed to create snapshot");
+
+    assert_eq!(second, zfs.rollback(root.clone(), false).unwrap());
+    let err = zfs.rollback(first.clone(), false).unwrap_err();
+    assert_eq!(Error::NotLatestSnapshot(first.clone()), err);
+
+    assert_eq!(first, zfs.rollback(first.clone(), true).unwrap());
+    assert!(!zfs.exists(second).unwrap());
+    assert_eq!(first, zfs.rollback(root, false).unwrap());
+}
+
+#[test]
+fn rollback_without_snapshots() {
+    let zpool = SHARED_ZPOOL.clone();
+    let zfs = DelegatingZfsEngine::new().expect("Failed to initialize ZfsLzc");
+    let root = PathBuf::from(format!("{}/{}", zpool, get_dataset_name()));
+    let request =
+        CreateDatasetRequest::builder().name(root.clone()).kind(DatasetKind::Filesystem).build();
+    zfs.create(request.unwrap()).expect("Failed to create dataset");
+
+    let err = ZfsOpen3::new().rollback(root.clone(), false).unwrap_err();
+    assert_eq!(Error::NoSnapshots(root), err);
+}
+
+#[test]
+fn hold_and_release() {
+    let zpool = SHARED_ZPOOL.clone();
+    let zfs = DelegatingZfsEngine::new().expect("Failed to initialize ZfsLzc");
+    let root = PathBuf::from(format!("{}/{}", zpool, get_dataset_name()));
+    let request =
+        CreateDatasetRequest::builder().name(root.clone()).kind(DatasetKind::Filesystem).build();
+    zfs.create(request.unwrap()).expect("Failed to create dataset");
+    let snapshot = PathBuf::from(format!("{}@held", root.display()));
+    zfs.snapshot(&[snapshot.clone()], None).expect("Failed to create snapshot");
+
+    zfs.hold(snapshot.clone(), "keep", None).unwrap();
+    let err = zfs.hold(snapshot.clone(), "keep", None).unwrap_err();
+    assert_eq!(ErrorKind::HoldExists, err.kind());
+    assert_eq!(ErrorKind::DatasetBusy, zfs.destroy(snapshot.clone()).unwrap_err().kind());
+    let err = zfs.release(snapshot.clone(), "nope").unwrap_err();
+    assert_eq!(ErrorKind::HoldNotFound, err.kind());
+    zfs.release(snapshot.clone(), "keep").unwrap();
+
+    let cleanup = cleanup_file().expect("Failed to open /dev/zfs");
+    zfs.hold(snapshot.clone(), "send", Some(cleanup.as_raw_fd())).unwrap();
+    assert_eq!(ErrorKind::DatasetBusy, zfs.destroy(snapshot.clone()).unwrap_err().kind());
+    drop(cleanup);
+    zfs.destroy(snapshot.clone()).unwrap();
+
+    let missing = PathBuf::from(format!("{}@missing", root.display()));
+    let err = zfs.hold(missing, "keep", None).unwrap_err();
+    assert_eq!(ErrorKind::DatasetNotFound, err.kind());
+}
+
+#[test]
+fn list_holds() {
+    let zpool = SHARED_ZPOOL.clone();
+    let zfs = DelegatingZfsEngine::new().expect("Failed to initialize ZfsLzc");
+    let root = PathBuf::from(format!("{}/{}", zpool, get_dataset_name()));
+    let request =
+        CreateDatasetRequest::builder().name(root.clone()).kind(DatasetKind::Filesystem).build();
+    zfs.create(request.unwrap()).expect("Failed to create dataset");
+    let snapshot = PathBuf::from(format!("{}@held", root.display()));
+    zfs.snapshot(&[snapshot.clone()], None).expect("Failed to create snapshot");
+    assert!(zfs.holds(snapshot.clone()).unwrap().is_empty());
+
+    zfs.hold(snapshot.clone(), "replication", None).unwrap();
+    zfs.hold(snapshot.clone(), "backup", None).unwrap();
+    let holds = zfs.holds(snapshot.clone()).unwrap();
+    let mut tags: Vec<&str> = holds.iter().map(|hold| hold.tag().as_str()).collect();
+    tags.sort();
+    assert_eq!(vec!["backup", "replication"], tags);
+    assert!(holds.iter().all(|hold| *hold.timestamp() > 0));
+
+    zfs.release(snapshot.clone(), "backup").unwrap();
+    zfs.release(snapshot.clone(), "replication").unwrap();
+    assert!(zfs.holds(snapshot.clone()).unwrap().is_empty());
+
+    let missing = PathBuf::from(format!("{}@missing", root.display()));
+    assert_eq!(Error::DatasetNotFound(missing.clone()), zfs.holds(missing).unwrap_err());
+}
+
+#[test]
+fn hold_guard() {
+    let zpool = SHARED_ZPOOL.clone();
+    let zfs = DelegatingZfsEngine::new().expect("Failed to initialize ZfsLzc");
+    let root = PathBuf::from(format!("{}/{}", zpool, get_dataset_name()));
+    let request =
+        CreateDatasetRequest::builder().name(root.clone()).kind(DatasetKind::Filesystem).build();
+    zfs.create(request.unwrap()).expect("Failed to create dataset");
+    let snapshot = PathBuf::from(format!("{}@guarded", root.display()));
+    zfs.snapshot(&[snapshot.clone()], None).expect("Failed to create snapshot");
+
+    {
+        let guard = HoldGuard::new(&zfs, snapshot.clone(), "send", None).unwrap();
+        assert_eq!("send", zfs.holds(guard.snapshot()).unwrap()[0].tag());
+        assert_eq!(ErrorKind::DatasetBusy, zfs.destroy(snapshot.clone()).unwrap_err().kind());
+    }
+    assert!(zfs.holds(snapshot.clone()).unwrap().is_empty());
+
+    let guard = HoldGuard::new(&zfs, snapshot.clone(), "send", None).unwrap();
+    zfs.release(snapshot.clone(), "send").unwrap();
+    assert_eq!(ErrorKind::HoldNotFound, guard.release().unwrap_err().kind());
+    zfs.destroy(snapshot).unwrap();
+}
+
+#[test]
+fn allow_and_unallow() {
+    let zpool = SHARED_ZPOOL.clone();
+    let dataset_path = PathBuf::from(format!("{}/{}", zpool, get_dataset_name()));
+
+    let zfs = DelegatingZfsEngine::new().expect("Failed to initialize ZfsLzc");
+    let request = CreateDatasetRequest::builder()
+        .name(dataset_path.clone())
+        .kind(DatasetKind::Filesystem)
+        .build()
+        .unwrap();
+    zfs.create(request).expect("Failed to create the dataset");
+
+    let root = Delegate::User("root".into());
+    let delegation = Delegation::new(root, PermissionSet::backup_operator());
+    zfs.allow(dataset_path.clone(), &delegation).unwrap();
+    let granted = zfs.permissions(dataset_path.clone()).unwrap();
+    assert!(granted.iter().any(|d| d.covers(&delegation)));
+
+    zfs.unallow(dataset_path.clone(), &delegation).unwrap();
+    let granted = zfs.permissions(dataset_path.clone()).unwrap();
+    assert!(granted.is_empty());
+
+    zfs.destroy(dataset_path).unwrap();
+}
+
 #[test]
 fn create_and_list() {
     let zpool = SHARED_ZPOOL.clone();
@@ -289,6 +727,40 @@ fn easy_snapshot_and_bookmark() {
     assert!(bookmarks.is_empty())
 }
 
+#[test]
+fn bookmark_of_bookmark() {
+    let zpool = SHARED_ZPOOL.clone();
+    let zfs = DelegatingZfsEngine::new().expect("Failed to initialize ZfsLzc");
+    let root_name = get_dataset_name();
+    let root = PathBuf::from(format!("{}/{}", zpool, &root_name));
+    let request = CreateDatasetRequest::builder()
+        .name(root.clone())
+        .kind(DatasetKind::Filesystem)
+        .build()
+        .unwrap();
+    zfs.create(request).expect("Failed to create a root dataset");
+    let snapshot = PathBuf::from(format!("{}/{}@snap-1", zpool, &root_name));
+    let source = PathBuf::from(format!("{}/{}#source", zpool, &root_name));
+    let copy = PathBuf::from(format!("{}/{}#target-a", zpool, &root_name));
+
+    zfs.snapshot(&[snapshot.clone()], None).expect("Failed to create snapshots");
+    zfs.bookmark(&[BookmarkRequest::new(snapshot.clone(), source.clone())])
+        .expect("Failed to create bookmark");
+    // Source is gone, but bookmark still can be copied.
+    zfs.destroy_snapshots(&[snapshot], DestroyTiming::RightNow).unwrap();
+
+    let request = BookmarkRequest::from_bookmark(source.clone(), copy.clone());
+    assert!(request.is_copy());
+    zfs.bookmark(&[request]).expect("Failed to copy bookmark");
+
+    let bookmarks = zfs.list_bookmarks(root).expect("failed to list bookmarks");
+    assert_eq!(vec![source.clone(), copy.clone()], bookmarks);
+
+    let not_a_bookmark = PathBuf::from(format!("{}/{}", zpool, &root_name));
+    let request = BookmarkRequest::from_bookmark(source, not_a_bookmark);
+    assert!(matches!(zfs.bookmark(&[request]), Err(Error::ValidationErrors(_))));
+}
+
 #[test]
 fn read_properties_of_filesystem() {
     let zpool = SHARED_ZPOOL.clone();
@@ -392,6 +864,38 @@ fn read_properties_of_snapshot() {
     }
 }
 #[test]
+fn snapshot_with_user_properties() {
+    let zpool = SHARED_ZPOOL.clone();
+    let zfs = DelegatingZfsEngine::new().expect("Failed to initialize ZfsLzc");
+    let root_name = get_dataset_name();
+    let root = PathBuf::from(format!("{}/{}", zpool, &root_name));
+    let request =
+        CreateDatasetRequest::builder().name(root).kind(DatasetKind::Filesystem).build().unwrap();
+    zfs.create(request).expect("Failed to create a root dataset");
+
+    let snapshot_name = format!("{}/{}@tagged", zpool, &root_name);
+    let mut user_properties = HashMap::new();
+    user_properties.insert(String::from("backup:job-id"), String::from("42"));
+
+    zfs.snapshot(&[PathBuf::from(&snapshot_name)], Some(user_properties))
+        .expect("Failed to create snapshots");
+
+    if let Properties::Snapshot(properties) = zfs.read_properties(&snapshot_name).unwrap() {
+        assert_eq!(
+            Some(&String::from("42")),
+            properties.unknown_properties().get("backup:job-id")
+        );
+    } else {
+        panic!("Read wrong properties");
+    }
+
+    let mut invalid = HashMap::new();
+    invalid.insert(String::from("jobid"), String::from("42"));
+    let snapshot_name = format!("{}/{}@bad", zpool, &root_name);
+    let result = zfs.snapshot(&[PathBuf::from(&snapshot_name)], Some(invalid));
+    assert!(matches!(result, Err(Error::ValidationErrors(_))));
+}
+#[test]
 fn read_properties_of_volume() {
     let zpool = SHARED_ZPOOL.clone();
     let zfs = DelegatingZfsEngine::new().expect("Failed to initialize ZfsLzc");
@@ -460,4 +964,234 @@ fn send_snapshot_incremental() {
     let tmpfile = tempfile::tempfile().unwrap();
 
     zfs.send_incremental(snapshot, src_snapshot, tmpfile, SendFlags::empty()).unwrap();
-}
\ No newline at end of file
+}
+#[test]
+fn replicate_with_space_check() {
+    let zpool = SHARED_ZPOOL.clone();
+    let zfs = DelegatingZfsEngine::new().expect("Failed to initialize ZfsLzc");
+    let root_name = get_dataset_name();
+    let root = PathBuf::from(format!("{}/{}", zpool, &root_name));
+    let request = CreateDatasetRequest::builder()
+        .name(root)
+        .kind(DatasetKind::Filesystem)
+        .build()
+        .unwrap();
+    zfs.create(request).expect("Failed to create a root dataset");
+
+    let snapshot = PathBuf::from(format!("{}/{}@tosend", zpool, &root_name));
+    zfs.snapshot(&[snapshot.clone()], None).expect("Failed to create snapshots");
+
+    let destination = PathBuf::from(format!("{}/{}-copy@tosend", zpool, &root_name));
+    let flags = SendFlags::empty();
+    let needed =
+        replicate(&zfs, snapshot.clone(), None, flags, &zfs, destination.clone(), false).unwrap();
+    assert!(needed > 0);
+    assert!(zfs.exists(destination).unwrap());
+
+    let result = replicate(
+        &zfs,
+        snapshot,
+        None,
+        SendFlags::empty(),
+        &zfs,
+        PathBuf::from("nope/nope@tosend"),
+        false,
+    );
+    assert_eq!(Error::DatasetNotFound(PathBuf::from("nope")), result.unwrap_err());
+}
+
+#[test]
+fn rename_mounted_tree() {
+    let zpool = SHARED_ZPOOL.clone();
+    let zfs = DelegatingZfsEngine::new().expect("Failed to initialize ZfsLzc");
+    let root_name = get_dataset_name();
+    let root = PathBuf::from(format!("{}/{}", zpool, &root_name));
+    for name in &[root.clone(), root.join("child")] {
+        let request = CreateDatasetRequest::builder()
+            .name(name.clone())
+            .kind(DatasetKind::Filesystem)
+            .build()
+            .unwrap();
+        zfs.create(request).expect("Failed to create a dataset");
+        zfs.mount(name.clone()).expect("Failed to mount");
+    }
+
+    let renamed = PathBuf::from(format!("{}/{}-renamed", zpool, &root_name));
+    let plan = rename_tree(&zfs, root.clone(), renamed.clone()).unwrap();
+    assert_eq!(2, plan.moves().len());
+    assert!(!zfs.exists(root.clone()).unwrap());
+    let child = renamed.join("child");
+    match zfs.read_properties(&child).unwrap() {
+        Properties::Filesystem(props) => {
+            assert!(*props.mounted());
+            assert_eq!(plan.moves()[1].new_mountpoint(), props.mount_point());
+        },
+        _ => panic!("Not a filesystem"),
+    }
+
+    // Something is in the way of the new mountpoint.
+    let blocked = PathBuf::from(format!("{}/{}-blocked", zpool, &root_name));
+    let plan = RenamePlan::new(&zfs, renamed.clone(), blocked).unwrap();
+    let in_the_way = plan.moves()[0].new_mountpoint().clone().unwrap();
+    DirBuilder::new().recursive(true).create(&in_the_way).unwrap();
+    fs::write(in_the_way.join("file"), b"hello").unwrap();
+    assert_eq!(1, plan.collisions().len());
+    let err = plan.execute(&zfs).unwrap_err();
+    assert_eq!(ErrorKind::MountpointCollision, err.kind());
+    assert!(zfs.exists(child).unwrap());
+    fs::remove_dir_all(in_the_way).unwrap();
+}
+
+#[test]
+fn rename_with_options() {
+    let zpool = SHARED_ZPOOL.clone();
+    let zfs = DelegatingZfsEngine::new().expect("Failed to initialize ZfsLzc");
+    let lzc = ZfsLzc::new().expect("Failed to initialize ZfsLzc");
+    let root = PathBuf::from(format!("{}/{}", zpool, get_dataset_name()));
+    let request =
+        CreateDatasetRequest::builder().name(root.clone()).kind(DatasetKind::Filesystem).build();
+    zfs.create(request.unwrap()).expect("Failed to create dataset");
+    let snapshot = PathBuf::from(format!("{}@first", root.display()));
+    zfs.snapshot(&[snapshot.clone()], None).expect("Failed to create snapshot");
+
+    let nested = PathBuf::from(format!("{}/{}/a/b", zpool, get_dataset_name()));
+    let result = zfs.rename(root.clone(), nested.clone(), RenameOptions::default());
+    assert_eq!(ErrorKind::DatasetNotFound, result.unwrap_err().kind());
+
+    let parents = RenameOptions::builder().create_parents(true).build().unwrap();
+    zfs.rename(root.clone(), nested.clone(), parents).unwrap();
+    assert!(!zfs.exists(root.clone()).unwrap());
+    assert!(zfs.exists(nested.clone()).unwrap());
+
+    let deeper = PathBuf::from(format!("{}/{}/c/d", zpool, get_dataset_name()));
+    let no_remount =
+        RenameOptions::builder().create_parents(true).no_remount(true).build().unwrap();
+    lzc.rename(nested.clone(), deeper.clone(), no_remount).unwrap();
+    assert!(zfs.exists(deeper.clone()).unwrap());
+    assert!(zfs.exists(deeper.parent().unwrap()).unwrap());
+
+    let first = PathBuf::from(format!("{}@first", deeper.display()));
+    let second = PathBuf::from(format!("{}@second", deeper.display()));
+    lzc.rename(first.clone(), second.clone(), RenameOptions::default()).unwrap();
+    assert!(zfs.exists(second.clone()).unwrap());
+    assert_eq!(Error::invalid_input(), lzc.rename(second, first, parents).unwrap_err());
+
+    let result = lzc.rename(root.clone(), nested.clone(), RenameOptions::default());
+    assert_eq!(Error::DatasetNotFound(root), result.unwrap_err());
+    zfs.create(
+        CreateDatasetRequest::builder()
+            .name(nested.clone())
+            .kind(DatasetKind::Filesystem)
+            .build()
+            .unwrap(),
+    )
+    .expect("Failed to create dataset");
+    let result = lzc.rename(nested.clone(), deeper.clone(), RenameOptions::default());
+    assert_eq!(Error::DatasetExists(deeper), result.unwrap_err());
+}
+#[test]
+fn zvol_snapshot_clone_resize() {
+    let zpool = SHARED_ZPOOL.clone();
+    let zfs = DelegatingZfsEngine::new().expect("Failed to initialize ZfsLzc");
+    let root_name = get_dataset_name();
+    let root = PathBuf::from(format!("{}/{}", zpool, &root_name));
+    let request = CreateDatasetRequest::builder()
+        .name(root.clone())
+        .kind(DatasetKind::Volume)
+        .volume_size(ONE_MB_IN_BYTES)
+        .build()
+        .unwrap();
+    zfs.create(request).expect("Failed to create a root dataset");
+
+    let snapshot = snapshot_zvol(&zfs, &root, "golden").unwrap();
+    assert_eq!(PathBuf::from(format!("{}@golden", root.display())), snapshot);
+    let clone = PathBuf::from(format!("{}/{}-clone", zpool, &root_name));
+    let device = clone_zvol(&zfs, &snapshot, &clone, None, Duration::from_secs(10)).unwrap();
+    assert!(device.exists());
+
+    let err = resize_zvol(&zfs, &clone, ONE_MB_IN_BYTES / 2, ResizeMode::GrowOnly).unwrap_err();
+    assert_eq!(ErrorKind::VolumeShrink, err.kind());
+    let err = resize_zvol(&zfs, &clone, ONE_MB_IN_BYTES + 1, ResizeMode::GrowOnly).unwrap_err();
+    assert_eq!(ErrorKind::InvalidVolumeSize, err.kind());
+    resize_zvol(&zfs, &clone, 2 * ONE_MB_IN_BYTES, ResizeMode::GrowOnly).unwrap();
+    match zfs.read_properties(&clone).unwrap() {
+        Properties::Volume(props) => assert_eq!(&(2 * ONE_MB_IN_BYTES), props.volume_size()),
+        _ => panic!("Not a volume"),
+    }
+    resize_zvol(&zfs, &clone, ONE_MB_IN_BYTES, ResizeMode::AllowShrink).unwrap();
+
+    // Filesystems are refused.
+    let fs_name = PathBuf::from(format!("{}/{}-fs", zpool, &root_name));
+    let request = CreateDatasetRequest::builder()
+        .name(fs_name.clone())
+        .kind(DatasetKind::Filesystem)
+        .build()
+        .unwrap();
+    zfs.create(request).expect("Failed to create a dataset");
+    let err = snapshot_zvol(&zfs, &fs_name, "golden").unwrap_err();
+    assert_eq!(ErrorKind::NotAVolume, err.kind());
+}
+#[test]
+fn receive_dry_run_of_full_and_incremental() {
+    let zpool = SHARED_ZPOOL.clone();
+    let zfs = DelegatingZfsEngine::new().expect("Failed to initialize ZfsLzc");
+    let root_name = get_dataset_name();
+    let root = PathBuf::from(format!("{}/{}", zpool, &root_name));
+    let request = CreateDatasetRequest::builder()
+        .name(root.clone())
+        .kind(DatasetKind::Filesystem)
+        .build()
+        .unwrap();
+    zfs.create(request).expect("Failed to create a root dataset");
+    let first = PathBuf::from(format!("{}@first", root.display()));
+    let second = PathBuf::from(format!("{}@second", root.display()));
+    zfs.snapshot(&[first.clone()], None).expect("Failed to create snapshots");
+    zfs.snapshot(&[second.clone()], None).expect("Failed to create snapshots");
+
+    let mut full = tempfile::tempfile().unwrap();
+    zfs.send_full(first.clone(), full.try_clone().unwrap(), SendFlags::empty()).unwrap();
+    full.seek(SeekFrom::Start(0)).unwrap();
+    let copy = PathBuf::from(format!("{}/{}-copy@first", zpool, &root_name));
+    let dry_run = receive_dry_run(&zfs, copy.clone(), &mut full, false).unwrap();
+    assert_eq!(&ReceiveAction::Create, dry_run.action());
+    assert_eq!(&first, dry_run.header().to_name());
+    assert!(!dry_run.header().is_incremental());
+    assert!(!zfs.exists(copy).unwrap());
+
+    full.seek(SeekFrom::Start(0)).unwrap();
+    let err = receive_dry_run(&zfs, second.clone(), &mut full, false).unwrap_err();
+    assert_eq!(ErrorKind::DatasetExists, err.kind());
+
+    let mut incremental = tempfile::tempfile().unwrap();
+    let fd = incremental.try_clone().unwrap();
+    zfs.send_incremental(second.clone(), first.clone(), fd, SendFlags::empty()).unwrap();
+    incremental.seek(SeekFrom::Start(0)).unwrap();
+    let third = PathBuf::from(format!("{}@third", root.display()));
+    let err = receive_dry_run(&zfs, third.clone(), &mut incremental, false).unwrap_err();
+    assert_eq!(ErrorKind::DatasetExists, err.kind());
+    incremental.seek(SeekFrom::Start(0)).unwrap();
+    let dry_run = receive_dry_run(&zfs, third, &mut incremental, true).unwrap();
+    let expected = ReceiveAction::Update { from: first, discarded: vec![second] };
+    assert_eq!(&expected, dry_run.action());
+}
+#[test]
+fn validated_names() {
+    let pool = PoolName::new(SHARED_ZPOOL.clone()).unwrap();
+    let zfs = DelegatingZfsEngine::new().expect("Failed to initialize ZfsLzc");
+    let root = pool.join(&get_dataset_name()).unwrap();
+    let request = CreateDatasetRequest::builder()
+        .name(root.clone())
+        .kind(DatasetKind::Filesystem)
+        .build()
+        .unwrap();
+    zfs.create(request).expect("Failed to create a root dataset");
+    assert!(zfs.exists(&root).unwrap());
+
+    let snapshot = root.snapshot("first").unwrap();
+    zfs.snapshot(&[snapshot.clone().into()], None).expect("Failed to create snapshots");
+    assert!(zfs.list_snapshots(&root).unwrap().contains(&PathBuf::from(&snapshot)));
+    let listed = zfs.list_filesystems(&root).unwrap();
+    let names: Vec<DatasetName> = listed.into_iter().map(|p| p.try_into().unwrap()).collect();
+    assert_eq!(vec![root.clone()], names);
+    assert!(ZpoolOpen3::default().exists(&pool).unwrap());
+}
@@ -6,15 +6,21 @@ use slog_term;
 use std::{fs::{self, DirBuilder},
           panic,
           path::{Path, PathBuf},
-          sync::Mutex};
+          sync::Mutex,
+          time::Duration};
 
 use cavity::{fill, Bytes, WriteMode};
 use rand::Rng;
 
 use libzetta::{slog::*,
-               zpool::{CreateMode, CreateVdevRequest, CreateZpoolRequestBuilder, DestroyMode,
-                       ExportMode, FailMode, Health, OfflineMode, OnlineMode, Zpool, ZpoolEngine,
-                       ZpoolError, ZpoolErrorKind, ZpoolOpen3, ZpoolPropertiesWriteBuilder}};
+               utils::ParseMode,
+               zpool::{hostid::local_hostid, inspect::inspect_exported, txg::read_txgs,
+                       CreateMode, CreateVdevRequest, CreateZpoolRequestBuilder, DestroyMode,
+                       ExportMode, FailMode, FeatureState, Health, ImportName, ImportRequest,
+                       InitializeAction, LabelClearMode, MaintenanceState, OfflineMode,
+                       OnlineMode, Recovery, ScanKind, ScanStatus, ScrubState, SplitOptions,
+                       TrimOptions, VdevType, Zpool, ZpoolEngine, ZpoolError, ZpoolErrorKind,
+                       ZpoolOpen3, ZpoolPropertiesWriteBuilder}};
 
 static ZPOOL_NAME_PREFIX: &'static str = "tests-zpool-";
 lazy_static! {
@@ -106,6 +112,8 @@ fn create_check_update_delete() {
         assert!(result);
 
         let props = zpool.read_properties(&name).unwrap();
+        assert_eq!(Some(FeatureState::Enabled), props.feature("feature@async_destroy"));
+        assert!(!props.features_in(FeatureState::Active).is_empty());
         let updated_props = ZpoolPropertiesWriteBuilder::from_props(&props)
             .auto_expand(true)
             .auto_replace(true)
@@ -140,6 +148,12 @@ fn create_check_update_delete() {
         assert_eq!(&None, props.comment());
         assert_eq!(&true, props.delegation());
 
+        let mut buf = String::new();
+        for _ in 0..3 {
+            let borrowed = zpool.read_properties_into(&name, &mut buf).unwrap();
+            assert_eq!(props, borrowed.into_owned().with_features(props.features().clone()));
+        }
+
         zpool.destroy(&name, DestroyMode::Force).unwrap();
 
         let result = zpool.exists(&name).unwrap();
@@ -147,6 +161,160 @@ fn create_check_update_delete() {
     })
 }
 
+#[test]
+fn create_with_selected_features() {
+    run_test(|name| {
+        let zpool = ZpoolOpen3::default();
+
+        let topo = CreateZpoolRequestBuilder::default()
+            .name(name.clone())
+            .vdev(CreateVdevRequest::SingleDisk("/vdevs/vdev0".into()))
+            .disable_feature("async_destroy")
+            .build()
+            .unwrap();
+        zpool.create(topo).unwrap();
+        let props = zpool.read_properties(&name).unwrap();
+        assert_eq!(Some(FeatureState::Disabled), props.feature("async_destroy"));
+        assert_eq!(Some(FeatureState::Enabled), props.feature("empty_bpobj"));
+        zpool.destroy(&name, DestroyMode::Force).unwrap();
+
+        let topo = CreateZpoolRequestBuilder::default()
+            .name(name.clone())
+            .vdev(CreateVdevRequest::SingleDisk("/vdevs/vdev0".into()))
+            .no_features(true)
+            .enable_feature("async_destroy")
+            .build()
+            .unwrap();
+        zpool.create(topo).unwrap();
+        let props = zpool.read_properties(&name).unwrap();
+        assert_eq!(Some(FeatureState::Enabled), props.feature("async_destroy"));
+        assert_eq!(vec!["async_destroy"], props.features_in(FeatureState::Enabled));
+        assert!(props.features_in(FeatureState::Active).is_empty());
+        zpool.destroy(&name, DestroyMode::Force).unwrap();
+    })
+}
+
+#[test]
+fn create_with_autotrim() {
+    run_test(|name| {
+        let zpool = ZpoolOpen3::default();
+        let props = ZpoolPropertiesWriteBuilder::default().auto_trim(Some(true)).build().unwrap();
+        let topo = CreateZpoolRequestBuilder::default()
+            .name(name.clone())
+            .vdev(CreateVdevRequest::SingleDisk("/vdevs/vdev0".into()))
+            .props(props)
+            .build()
+            .unwrap();
+        zpool.create(topo).unwrap();
+        let props = zpool.read_properties(&name).unwrap();
+        assert!(*props.auto_trim());
+
+        let updated =
+            ZpoolPropertiesWriteBuilder::from_props(&props).auto_trim(Some(false)).build().unwrap();
+        let props = zpool.update_properties(&name, updated).unwrap();
+        assert!(!*props.auto_trim());
+    })
+}
+
+#[test]
+fn set_bootfs() {
+    run_test(|name| {
+        let zpool = ZpoolOpen3::default();
+        let topo = CreateZpoolRequestBuilder::default()
+            .name(name.clone())
+            .vdev(CreateVdevRequest::SingleDisk("/vdevs/vdev0".into()))
+            .build()
+            .unwrap();
+        zpool.create(topo).unwrap();
+        assert_eq!(None, zpool.bootfs(&name).unwrap());
+
+        zpool.set_bootfs(&name, Some(&name)).unwrap();
+        assert_eq!(Some(name.clone()), zpool.bootfs(&name).unwrap());
+
+        let err = zpool.set_bootfs(&name, Some("elsewhere/ROOT")).unwrap_err();
+        assert_eq!(ZpoolErrorKind::InvalidProperty, err.kind());
+        assert_eq!(Some(name.clone()), zpool.bootfs(&name).unwrap());
+
+        zpool.set_bootfs(&name, None).unwrap();
+        assert_eq!(None, zpool.bootfs(&name).unwrap());
+    })
+}
+
+#[test]
+fn update_list_snapshots_and_compatibility() {
+    run_test(|name| {
+        let zpool = ZpoolOpen3::default();
+        let topo = CreateZpoolRequestBuilder::default()
+            .name(name.clone())
+            .vdev(CreateVdevRequest::SingleDisk("/vdevs/vdev0".into()))
+            .build()
+            .unwrap();
+        zpool.create(topo).unwrap();
+        let props = zpool.read_properties(&name).unwrap();
+        assert!(!*props.list_snapshots());
+
+        let updated = ZpoolPropertiesWriteBuilder::from_props(&props)
+            .list_snapshots(Some(true))
+            .compatibility(Some(String::from("legacy")))
+            .build()
+            .unwrap();
+        let props = zpool.update_properties(&name, updated).unwrap();
+        assert!(*props.list_snapshots());
+    })
+}
+
+#[test]
+fn update_multihost() {
+    if local_hostid() == 0 {
+        // MMP refuses to work without a hostid.
+        return;
+    }
+    run_test(|name| {
+        let zpool = ZpoolOpen3::default();
+        let topo = CreateZpoolRequestBuilder::default()
+            .name(name.clone())
+            .vdev(CreateVdevRequest::SingleDisk("/vdevs/vdev0".into()))
+            .build()
+            .unwrap();
+        zpool.create(topo).unwrap();
+        let props = zpool.read_properties(&name).unwrap();
+        assert!(!*props.multihost());
+
+        let updated =
+            ZpoolPropertiesWriteBuilder::from_props(&props).multihost(Some(true)).build().unwrap();
+        let props = zpool.update_properties(&name, updated).unwrap();
+        assert!(*props.multihost());
+    })
+}
+
+#[test]
+fn create_with_ashift() {
+    run_test(|name| {
+        let zpool = ZpoolOpen3::default();
+
+        let topo = CreateZpoolRequestBuilder::default()
+            .name(name.clone())
+            .vdev(CreateVdevRequest::SingleDisk("/vdevs/vdev0".into()))
+            .ashift(12)
+            .build()
+            .unwrap();
+        zpool.create(topo).unwrap();
+        let props = zpool.read_properties(&name).unwrap();
+        assert_eq!(&12, props.ashift());
+        zpool.destroy(&name, DestroyMode::Force).unwrap();
+
+        let topo = CreateZpoolRequestBuilder::default()
+            .name(name.clone())
+            .vdev(CreateVdevRequest::SingleDisk("/vdevs/vdev0".into()))
+            .ashift(20)
+            .build()
+            .unwrap();
+        let err = zpool.create(topo).unwrap_err();
+        assert_eq!(ZpoolErrorKind::InvalidProperty, err.kind());
+        assert!(!zpool.exists(&name).unwrap());
+    })
+}
+
 #[test]
 fn cmd_not_found() {
     run_test(|name| {
@@ -199,6 +367,32 @@ fn reuse_vdev() {
         zpool.destroy(&name_1, DestroyMode::Force).unwrap();
     });
 }
+#[test]
+fn create_force_over_exported() {
+    run_test(|name| {
+        let zpool = ZpoolOpen3::default();
+        let topo = CreateZpoolRequestBuilder::default()
+            .name(name.clone())
+            .vdev(CreateVdevRequest::SingleDisk("/vdevs/vdev0".into()))
+            .build()
+            .unwrap();
+        zpool.create(topo.clone()).unwrap();
+        zpool.export(&name, ExportMode::Gentle).unwrap();
+
+        // Device still has a label of the exported pool.
+        assert!(zpool.create(topo).is_err());
+
+        let forced = CreateZpoolRequestBuilder::default()
+            .name(name.clone())
+            .vdev(CreateVdevRequest::SingleDisk("/vdevs/vdev0".into()))
+            .force(true)
+            .build()
+            .unwrap();
+        zpool.create(forced).unwrap();
+        assert!(zpool.exists(&name).unwrap());
+    });
+}
+
 #[test]
 fn create_invalid_topo() {
     let zpool = ZpoolOpen3::default();
@@ -224,6 +418,9 @@ fn pool_not_found() {
     let err = zpool.read_properties(&name).unwrap_err();
     assert_eq!(ZpoolErrorKind::PoolNotFound, err.kind());
 
+    let err = zpool.read_properties_into(&name, &mut String::new()).unwrap_err();
+    assert_eq!(ZpoolErrorKind::PoolNotFound, err.kind());
+
     let props = ZpoolPropertiesWriteBuilder::default().build().unwrap();
     let err = zpool.update_properties(&name, props).unwrap_err();
     assert_eq!(ZpoolErrorKind::PoolNotFound, err.kind());
@@ -232,6 +429,28 @@ fn pool_not_found() {
     assert_eq!(ZpoolErrorKind::PoolNotFound, err.kind());
 }
 
+#[test]
+fn invalid_property() {
+    let zpool = ZpoolOpen3::with_cmd("zpool-not-found");
+
+    let comment = String::from("rack 4\nshelf 2");
+    let err = zpool.set_property("tank", "comment", &comment).unwrap_err();
+    assert_eq!(ZpoolErrorKind::InvalidProperty, err.kind());
+
+    let err = zpool.set_property("tank", "-f comment", &comment).unwrap_err();
+    assert_eq!(ZpoolErrorKind::InvalidProperty, err.kind());
+
+    let props = ZpoolPropertiesWriteBuilder::default().comment(comment).build().unwrap();
+    let topo = CreateZpoolRequestBuilder::default()
+        .name("tank")
+        .vdev(CreateVdevRequest::SingleDisk("/vdevs/vdev0".into()))
+        .props(props)
+        .build()
+        .unwrap();
+    let err = zpool.create(topo).unwrap_err();
+    assert_eq!(ZpoolErrorKind::InvalidProperty, err.kind());
+}
+
 #[test]
 fn read_args() {
     run_test(|name| {
@@ -347,151 +566,840 @@ fn test_export_import() {
         let vdev_dir = Path::new("/vdevs/import");
         setup_vdev(vdev_dir.join("vdev0"), &Bytes::MegaBytes(64 + 10));
         let zpool = ZpoolOpen3::default();
-
+
+        let topo = CreateZpoolRequestBuilder::default()
+            .name(name.clone())
+            .vdev(CreateVdevRequest::SingleDisk("/vdevs/import/vdev0".into()))
+            .build()
+            .unwrap();
+        zpool.create(topo).expect("Failed to create pool for export");
+
+        let result = zpool.export(&name, ExportMode::Gentle);
+        assert!(result.is_ok());
+        let list = zpool.available_in_dir(PathBuf::from(&vdev_dir)).unwrap();
+        assert_eq!(list.len(), 1);
+
+        let result = zpool.import_from_dir(&name, PathBuf::from(vdev_dir));
+        assert!(result.unwrap().is_empty());
+
+        zpool.destroy(&name, DestroyMode::Force).unwrap();
+
+        let result = zpool.available().unwrap();
+        assert!(result.is_empty());
+    });
+}
+
+#[test]
+fn test_inspect_exported() {
+    run_test(|name| {
+        let vdev_dir = Path::new("/vdevs/inspect");
+        let vdev_path = setup_vdev(vdev_dir.join("vdev0"), &Bytes::MegaBytes(64 + 10));
+        let zpool = ZpoolOpen3::default();
+        let topo = CreateZpoolRequestBuilder::default()
+            .name(name.clone())
+            .vdev(CreateVdevRequest::SingleDisk(vdev_path.clone()))
+            .build()
+            .unwrap();
+        zpool.create(topo).unwrap();
+        zpool.export(&name, ExportMode::Gentle).unwrap();
+
+        let found = inspect_exported(&zpool, &[vdev_dir, vdev_dir]).unwrap();
+        assert_eq!(1, found.len());
+        let pool = &found[0];
+        assert_eq!(&name, pool.name());
+        assert!(pool.guid().is_some());
+        assert_eq!(&Health::Online, pool.health());
+        assert_eq!(&vec![vdev_path], pool.devices());
+        assert!(pool.host().is_some());
+        assert!(!zpool.exists(&name).unwrap());
+
+        zpool.import_from_dir(&name, PathBuf::from(vdev_dir)).unwrap();
+    });
+}
+
+#[test]
+fn test_import_all() {
+    run_test(|name| {
+        let vdev_dir = Path::new("/vdevs/importall");
+        let vdev0_path = setup_vdev(vdev_dir.join("vdev0"), &Bytes::MegaBytes(64 + 10));
+        let vdev1_path = setup_vdev(vdev_dir.join("vdev1"), &Bytes::MegaBytes(64 + 10));
+        let zpool = ZpoolOpen3::default();
+        let second = get_zpool_name();
+        for (pool, vdev) in &[(&name, vdev0_path), (&second, vdev1_path)] {
+            let topo = CreateZpoolRequestBuilder::default()
+                .name(pool.to_string())
+                .vdev(CreateVdevRequest::SingleDisk(vdev.clone()))
+                .build()
+                .unwrap();
+            zpool.create(topo).unwrap();
+            zpool.export(pool, ExportMode::Gentle).unwrap();
+        }
+
+        let mut imported = zpool.import_all_from_dir(PathBuf::from(vdev_dir)).unwrap();
+        imported.sort_by(|a, b| a.0.cmp(&b.0));
+        let mut expected = vec![name.clone(), second.clone()];
+        expected.sort();
+        assert_eq!(expected, imported.iter().map(|(n, _)| n.clone()).collect::<Vec<_>>());
+        assert!(imported.iter().all(|(_, result)| result.is_ok()));
+        assert!(zpool.exists(&name).unwrap());
+        assert!(zpool.exists(&second).unwrap());
+
+        zpool.destroy(&second, DestroyMode::Force).unwrap();
+        assert!(zpool.import_all_from_dir(PathBuf::from(vdev_dir)).unwrap().is_empty());
+    });
+}
+
+#[test]
+fn test_import_with() {
+    run_test(|name| {
+        let vdev_dir = Path::new("/vdevs/importwith");
+        let vdev_path = setup_vdev(vdev_dir.join("vdev0"), &Bytes::MegaBytes(64 + 10));
+        let zpool = ZpoolOpen3::default();
+        let topo = CreateZpoolRequestBuilder::default()
+            .name(name.clone())
+            .vdev(CreateVdevRequest::SingleDisk(vdev_path))
+            .build()
+            .unwrap();
+        zpool.create(topo).unwrap();
+        zpool.export(&name, ExportMode::Gentle).unwrap();
+
+        let request = ImportRequest::builder()
+            .name(name.clone())
+            .dir(vdev_dir)
+            .readonly(true)
+            .altroot("/mnt")
+            .recovery(Recovery::Rewind)
+            .property("cachefile", "none")
+            .build()
+            .unwrap();
+        zpool.import_with(request).unwrap();
+        let props = zpool.read_properties(&name).unwrap();
+        assert!(*props.read_only());
+        assert_eq!(&Some(PathBuf::from("/mnt")), props.alt_root());
+        zpool.export(&name, ExportMode::Gentle).unwrap();
+
+        let request = ImportRequest::builder().name(name.clone()).property("-f", "x").build();
+        let err = zpool.import_with(request.unwrap()).unwrap_err();
+        assert_eq!(ZpoolErrorKind::InvalidProperty, err.kind());
+        zpool.import_from_dir(&name, PathBuf::from(vdev_dir)).unwrap();
+    });
+}
+
+#[test]
+fn test_import_destroyed() {
+    run_test(|name| {
+        let vdev_dir = Path::new("/vdevs/importdestroyed");
+        let vdev_path = setup_vdev(vdev_dir.join("vdev0"), &Bytes::MegaBytes(64 + 10));
+        let zpool = ZpoolOpen3::default();
+        let topo = CreateZpoolRequestBuilder::default()
+            .name(name.clone())
+            .vdev(CreateVdevRequest::SingleDisk(vdev_path))
+            .build()
+            .unwrap();
+        zpool.create(topo).unwrap();
+        zpool.destroy(&name, DestroyMode::Gentle).unwrap();
+
+        let available = zpool.available_in_dir(PathBuf::from(vdev_dir)).unwrap();
+        assert!(available.iter().all(|pool| pool.name() != &name));
+        let available =
+            zpool.available_including_destroyed(Some(PathBuf::from(vdev_dir))).unwrap();
+        let found = available.iter().find(|pool| pool.name() == &name).unwrap();
+        assert!(*found.destroyed());
+
+        let request =
+            ImportRequest::builder().name(name.clone()).dir(vdev_dir).destroyed(true).build();
+        zpool.import_with(request.unwrap()).unwrap();
+        assert!(zpool.exists(&name).unwrap());
+    });
+}
+
+#[test]
+fn test_import_under_other_name() {
+    run_test(|name| {
+        let vdev_dir = Path::new("/vdevs/importrename");
+        let vdev_path = setup_vdev(vdev_dir.join("vdev0"), &Bytes::MegaBytes(64 + 10));
+        let zpool = ZpoolOpen3::default();
+        let topo = CreateZpoolRequestBuilder::default()
+            .name(name.clone())
+            .vdev(CreateVdevRequest::SingleDisk(vdev_path))
+            .build()
+            .unwrap();
+        zpool.create(topo).unwrap();
+        zpool.export(&name, ExportMode::Gentle).unwrap();
+
+        let temporary = format!("{}-tmp", name);
+        let request = ImportRequest::builder()
+            .name(name.clone())
+            .dir(vdev_dir)
+            .import_name(ImportName::Temporary(temporary.clone()))
+            .build()
+            .unwrap();
+        zpool.import_with(request).unwrap();
+        assert!(zpool.exists(&temporary).unwrap());
+        assert!(!zpool.exists(&name).unwrap());
+        zpool.export(&temporary, ExportMode::Gentle).unwrap();
+
+        let renamed = format!("{}-new", name);
+        let request = ImportRequest::builder()
+            .name(name.clone())
+            .dir(vdev_dir)
+            .import_name(ImportName::Rename(renamed.clone()))
+            .build()
+            .unwrap();
+        zpool.import_with(request).unwrap();
+        assert!(zpool.exists(&renamed).unwrap());
+        zpool.export(&renamed, ExportMode::Gentle).unwrap();
+
+        let request = ImportRequest::builder()
+            .name(renamed.clone())
+            .dir(vdev_dir)
+            .import_name(ImportName::Rename(name.clone()))
+            .build()
+            .unwrap();
+        zpool.import_with(request).unwrap();
+    });
+}
+
+#[test]
+fn test_export_import_force() {
+    run_test(|name| {
+        let vdev_dir = Path::new("/vdevs/import");
+        setup_vdev(vdev_dir.join("vdev0"), &Bytes::MegaBytes(64 + 10));
+        let zpool = ZpoolOpen3::default();
+        //let zpool = ZpoolOpen3::with_logger(get_logger());
+
+        let topo = CreateZpoolRequestBuilder::default()
+            .name(name.clone())
+            .vdev(CreateVdevRequest::SingleDisk("/vdevs/import/vdev0".into()))
+            .build()
+            .unwrap();
+        zpool.create(topo).expect("Failed to create pool for export");
+
+        let result = zpool.export(&name, ExportMode::Force);
+        assert!(result.is_ok());
+        let list = zpool.available_in_dir(PathBuf::from(&vdev_dir)).unwrap();
+        assert_eq!(list.len(), 1);
+
+        let result = zpool.import_from_dir(&name, PathBuf::from(vdev_dir));
+        assert!(result.is_ok());
+
+        zpool.destroy(&name, DestroyMode::Force).unwrap();
+
+        let result = zpool.available().unwrap();
+        assert!(result.is_empty());
+    });
+}
+
+#[test]
+fn test_status() {
+    run_test(|name| {
+        let vdev_path = setup_vdev("/vdevs/vdev0", &Bytes::MegaBytes(64 + 10));
+        let zpool = ZpoolOpen3::default();
+
+        let topo = CreateZpoolRequestBuilder::default()
+            .name(name.clone())
+            .vdev(CreateVdevRequest::SingleDisk(vdev_path))
+            .build()
+            .unwrap();
+        zpool.create(topo.clone()).unwrap();
+
+        let result = zpool.status(&name).unwrap();
+        assert_eq!(&name, result.name());
+        assert_eq!(&result, &topo);
+    });
+}
+#[test]
+fn test_all() {
+    run_test(|name| {
+        let zpool = ZpoolOpen3::default();
+        let vdev_path = setup_vdev("/vdevs/vdev0", &Bytes::MegaBytes(64 + 10));
+        let topo = CreateZpoolRequestBuilder::default()
+            .name(name.clone())
+            .vdev(CreateVdevRequest::SingleDisk(vdev_path))
+            .build()
+            .unwrap();
+        zpool.create(topo.clone()).unwrap();
+
+        let result: Vec<Zpool> = zpool
+            .all()
+            .unwrap()
+            .iter()
+            .cloned()
+            .filter(|z| z.name().starts_with(ZPOOL_NAME_PREFIX))
+            .collect();
+        assert_eq!(1, result.len());
+        let result = result.into_iter().next().unwrap();
+        assert_eq!(&name, result.name());
+        assert_eq!(&result, &topo);
+    });
+}
+
+#[test]
+fn test_all_empty() {
+    run_test(|_name| {
+        let zpool = ZpoolOpen3::default();
+
+        let result: Vec<Zpool> = zpool
+            .all()
+            .unwrap()
+            .iter()
+            .cloned()
+            .filter(|z| z.name().starts_with(ZPOOL_NAME_PREFIX))
+            .collect();
+        assert!(result.is_empty());
+    });
+}
+
+#[test]
+fn test_zpool_scrub_not_found() {
+    let zpool = ZpoolOpen3::default();
+    let name = "non-existent";
+
+    let result = zpool.scrub(name);
+    assert_eq!(ZpoolErrorKind::PoolNotFound, result.unwrap_err().kind());
+
+    let result = zpool.stop_scrub(name);
+    assert_eq!(ZpoolErrorKind::PoolNotFound, result.unwrap_err().kind());
+}
+
+#[test]
+fn test_zpool_scrub() {
+    run_test(|name| {
+        let zpool = ZpoolOpen3::default();
+        let vdev_path = setup_vdev("/vdevs/vdev0", &Bytes::MegaBytes(64 + 10));
+        let topo = CreateZpoolRequestBuilder::default()
+            .name(name.clone())
+            .vdev(CreateVdevRequest::SingleDisk(vdev_path))
+            .build()
+            .unwrap();
+        zpool.create(topo).unwrap();
+
+        let status = zpool.status(&name).unwrap();
+        assert_eq!(Some(ScrubState::NotRequested), status.scrub());
+
+        let result = zpool.stop_scrub(&name);
+        assert_eq!(ZpoolErrorKind::NoActiveScrubs, result.unwrap_err().kind());
+
+        let result = zpool.pause_scrub(&name);
+        assert_eq!(ZpoolErrorKind::NoActiveScrubs, result.unwrap_err().kind());
+
+        let result = zpool.scrub(&name);
+        assert!(result.is_ok());
+
+        // Pool is tiny, so scrub might be already done.
+        let status = zpool.status(&name).unwrap();
+        let scrub = status.scrub().unwrap();
+        assert!(scrub == ScrubState::InProgress || scrub == ScrubState::Finished);
+
+        if zpool.pause_scrub(&name).is_ok() {
+            let status = zpool.status(&name).unwrap();
+            assert_eq!(Some(ScrubState::Paused), status.scrub());
+            zpool.resume_scrub(&name).unwrap();
+        } else {
+            let result = zpool.resume_scrub(&name);
+            assert_eq!(ZpoolErrorKind::NoActiveScrubs, result.unwrap_err().kind());
+        }
+
+        let mut polls = 0;
+        let last = zpool.watch_scan(&name, Duration::from_millis(100), |_| polls += 1).unwrap();
+        match last {
+            Some(ScanStatus::Finished(summary)) => {
+                assert_eq!(&ScanKind::Scrub, summary.kind());
+                assert_eq!(&Some(0), summary.errors());
+            },
+            other => panic!("unexpected scan state: {:?}", other),
+        }
+        assert!(polls > 0);
+    });
+}
+
+#[test]
+fn test_zpool_history() {
+    run_test(|name| {
+        let zpool = ZpoolOpen3::default();
+        let vdev_path = setup_vdev("/vdevs/vdev0", &Bytes::MegaBytes(64 + 10));
+        let topo = CreateZpoolRequestBuilder::default()
+            .name(name.clone())
+            .vdev(CreateVdevRequest::SingleDisk(vdev_path.clone()))
+            .build()
+            .unwrap();
+        zpool.create(topo).unwrap();
+
+        let history = zpool.history(&name, false).unwrap();
+        assert!(history[0].command().starts_with("zpool create"));
+        assert!(history.iter().all(|record| !record.internal()));
+
+        let history = zpool.history(&name, true).unwrap();
+        assert!(history.iter().any(|record| *record.internal()));
+
+        let result = zpool.history("nope", false);
+        assert_eq!(ZpoolErrorKind::PoolNotFound, result.unwrap_err().kind());
+    });
+}
+
+#[test]
+fn test_zpool_events() {
+    run_test(|name| {
+        let zpool = ZpoolOpen3::default();
+        let vdev_path = setup_vdev("/vdevs/vdev0", &Bytes::MegaBytes(64 + 10));
+        let topo = CreateZpoolRequestBuilder::default()
+            .name(name.clone())
+            .vdev(CreateVdevRequest::SingleDisk(vdev_path.clone()))
+            .build()
+            .unwrap();
+        zpool.create(topo).unwrap();
+
+        let events = zpool.events().unwrap();
+        let ours: Vec<_> = events.iter().filter(|e| e.pool() == Some(name.as_str())).collect();
+        assert!(!ours.is_empty());
+        assert!(ours.iter().all(|e| e.eid().is_some() && e.timestamp().is_some()));
+    });
+}
+
+#[test]
+fn test_zpool_status_with_maintenance() {
+    run_test(|name| {
+        let zpool = ZpoolOpen3::default();
+        let vdev_path = setup_vdev("/vdevs/vdev0", &Bytes::MegaBytes(64 + 10));
+        let topo = CreateZpoolRequestBuilder::default()
+            .name(name.clone())
+            .vdev(CreateVdevRequest::SingleDisk(vdev_path.clone()))
+            .build()
+            .unwrap();
+        zpool.create(topo).unwrap();
+
+        let result = zpool.status_with_maintenance(&name).unwrap();
+        let disk = &result.vdevs()[0].disks()[0];
+        assert_eq!(&Some(MaintenanceState::Never), disk.initialize());
+        assert!(disk.trim().is_some());
+        assert_eq!(None, result.last_initialize());
+        assert_eq!(None, result.last_trim());
+    });
+}
+
+#[test]
+fn test_zpool_upgrade() {
+    run_test(|name| {
+        let zpool = ZpoolOpen3::default();
+        let vdev_path = setup_vdev("/vdevs/vdev0", &Bytes::MegaBytes(64 + 10));
+        let topo = CreateZpoolRequestBuilder::default()
+            .name(name.clone())
+            .vdev(CreateVdevRequest::SingleDisk(vdev_path.clone()))
+            .build()
+            .unwrap();
+        zpool.create(topo).unwrap();
+
+        let status = zpool.upgrade_status(&name).unwrap();
+        assert_eq!(&name, status.pool());
+        zpool.upgrade(&name).unwrap();
+        let status = zpool.upgrade_status(&name).unwrap();
+        assert!(!status.needs_upgrade());
+        zpool.upgrade_all().unwrap();
+
+        let err = zpool.upgrade_status("fake").unwrap_err();
+        assert_eq!(ZpoolErrorKind::PoolNotFound, err.kind());
+    });
+}
+
+#[test]
+fn test_zpool_trim() {
+    run_test(|name| {
+        let zpool = ZpoolOpen3::default();
+        let vdev_path = setup_vdev("/vdevs/vdev0", &Bytes::MegaBytes(64 + 10));
+        let topo = CreateZpoolRequestBuilder::default()
+            .name(name.clone())
+            .vdev(CreateVdevRequest::SingleDisk(vdev_path.clone()))
+            .build()
+            .unwrap();
+        zpool.create(topo).unwrap();
+
+        let options = TrimOptions::builder().rate(1024 * 1024).build().unwrap();
+        zpool.trim(&name, options).unwrap();
+        let z = zpool.status_with_maintenance(&name).unwrap();
+        assert!(z.vdevs()[0].disks()[0].trim().is_some());
+
+        let options = TrimOptions::builder().device("/vdevs/fake").build().unwrap();
+        let err = zpool.trim(&name, options).unwrap_err();
+        assert_ne!(ZpoolErrorKind::TrimNotSupported, err.kind());
+        let err = zpool.trim("fake", TrimOptions::default()).unwrap_err();
+        assert_eq!(ZpoolErrorKind::PoolNotFound, err.kind());
+    });
+}
+
+#[test]
+fn test_zpool_initialize() {
+    run_test(|name| {
+        let zpool = ZpoolOpen3::default();
+        let vdev_path = setup_vdev("/vdevs/vdev0", &Bytes::MegaBytes(64 + 10));
+        let topo = CreateZpoolRequestBuilder::default()
+            .name(name.clone())
+            .vdev(CreateVdevRequest::SingleDisk(vdev_path.clone()))
+            .build()
+            .unwrap();
+        zpool.create(topo).unwrap();
+
+        let none: &[&str] = &[];
+        zpool.initialize(&name, none, InitializeAction::Start).unwrap();
+        let z = zpool.status_with_maintenance(&name).unwrap();
+        assert!(z.vdevs()[0].initialize().is_some());
+
+        // Small vdev might be done already.
+        match zpool.initialize(&name, &[&vdev_path], InitializeAction::Cancel) {
+            Ok(()) => {},
+            Err(e) => assert_eq!(ZpoolErrorKind::NotInitializing, e.kind()),
+        }
+        let err = zpool.initialize("fake", none, InitializeAction::Suspend).unwrap_err();
+        assert_eq!(ZpoolErrorKind::PoolNotFound, err.kind());
+    });
+}
+
+#[test]
+fn test_zpool_split() {
+    run_test(|name| {
+        let zpool = ZpoolOpen3::default();
+        let vdev0_path = setup_vdev("/vdevs/vdev3", &Bytes::MegaBytes(64 + 10));
+        let vdev1_path = setup_vdev("/vdevs/vdev4", &Bytes::MegaBytes(64 + 10));
+        let topo = CreateZpoolRequestBuilder::default()
+            .name(name.clone())
+            .create_mode(CreateMode::Force)
+            .vdev(CreateVdevRequest::Mirror(vec![vdev0_path.clone(), vdev1_path.clone()]))
+            .build()
+            .unwrap();
+        zpool.create(topo).unwrap();
+
+        let new_name = get_zpool_name();
+        let options =
+            SplitOptions::builder().altroot("/mnt").device(vdev1_path.clone()).build().unwrap();
+        zpool.split(&name, &new_name, options).unwrap();
+
+        assert!(zpool.exists(&new_name).unwrap());
+        let props = zpool.read_properties(&new_name).unwrap();
+        assert_eq!(&Some(PathBuf::from("/mnt")), props.alt_root());
+        let z = zpool.status(&new_name).unwrap();
+        assert_eq!(&vdev1_path, z.vdevs()[0].disks()[0].path());
+        let z = zpool.status(&name).unwrap();
+        assert_eq!(&vdev0_path, z.vdevs()[0].disks()[0].path());
+        zpool.destroy(&new_name, DestroyMode::Force).unwrap();
+
+        let err = zpool.split(&name, &new_name, SplitOptions::default()).unwrap_err();
+        assert_eq!(ZpoolErrorKind::CannotSplit, err.kind());
+    });
+}
+
+#[test]
+fn test_zpool_reguid() {
+    run_test(|name| {
+        let zpool = ZpoolOpen3::default();
+        let vdev_path = setup_vdev("/vdevs/vdev0", &Bytes::MegaBytes(64 + 10));
+        let topo = CreateZpoolRequestBuilder::default()
+            .name(name.clone())
+            .vdev(CreateVdevRequest::SingleDisk(vdev_path))
+            .build()
+            .unwrap();
+        zpool.create(topo).unwrap();
+
+        let before = *zpool.read_properties(&name).unwrap().guid();
+        zpool.reguid(&name).unwrap();
+        let after = *zpool.read_properties(&name).unwrap().guid();
+        assert_ne!(before, after);
+
+        let err = zpool.reguid("fake").unwrap_err();
+        assert_eq!(ZpoolErrorKind::PoolNotFound, err.kind());
+    });
+}
+
+#[test]
+fn test_zpool_reopen() {
+    run_test(|name| {
+        let zpool = ZpoolOpen3::default();
+        let vdev_path = setup_vdev("/vdevs/vdev0", &Bytes::MegaBytes(64 + 10));
+        let topo = CreateZpoolRequestBuilder::default()
+            .name(name.clone())
+            .vdev(CreateVdevRequest::SingleDisk(vdev_path))
+            .build()
+            .unwrap();
+        zpool.create(topo).unwrap();
+
+        zpool.reopen(&name).unwrap();
+        let z = zpool.status(&name).unwrap();
+        assert_eq!(&Health::Online, z.health());
+
+        let err = zpool.reopen("fake").unwrap_err();
+        assert_eq!(ZpoolErrorKind::PoolNotFound, err.kind());
+    });
+}
+
+#[test]
+fn test_strict_parsing() {
+    run_test(|name| {
+        let zpool = ZpoolOpen3::default().with_parse_mode(ParseMode::Strict);
+        let vdev_path = setup_vdev("/vdevs/vdev0", &Bytes::MegaBytes(64 + 10));
+        let topo = CreateZpoolRequestBuilder::default()
+            .name(name.clone())
+            .vdev(CreateVdevRequest::SingleDisk(vdev_path))
+            .build()
+            .unwrap();
+        zpool.create(topo).unwrap();
+
+        // Every line that this OpenZFS prints must be recognized.
+        zpool.status(&name).unwrap();
+        zpool.all().unwrap();
+        zpool.read_properties(&name).unwrap();
+        let mut buf = String::new();
+        zpool.read_properties_into(&name, &mut buf).unwrap();
+        zpool.available().unwrap();
+    });
+}
+
+#[test]
+fn test_zpool_sync() {
+    run_test(|name| {
+        let zpool = ZpoolOpen3::default();
+        let vdev_path = setup_vdev("/vdevs/vdev0", &Bytes::MegaBytes(64 + 10));
+        let topo = CreateZpoolRequestBuilder::default()
+            .name(name.clone())
+            .vdev(CreateVdevRequest::SingleDisk(vdev_path))
+            .build()
+            .unwrap();
+        zpool.create(topo).unwrap();
+
+        zpool.sync(Some(&name)).unwrap();
+        zpool.sync(None).unwrap();
+
+        let err = zpool.sync(Some("fake")).unwrap_err();
+        assert_eq!(ZpoolErrorKind::PoolNotFound, err.kind());
+    });
+}
+
+#[test]
+fn test_zpool_handle() {
+    run_test(|name| {
+        let zpool = ZpoolOpen3::default();
+        let vdev_dir = Path::new("/vdevs/handle");
+        let vdev0_path = setup_vdev(vdev_dir.join("vdev0"), &Bytes::MegaBytes(64 + 10));
+        let vdev1_path = setup_vdev("/vdevs/vdev1", &Bytes::MegaBytes(64 + 10));
+        let topo = CreateZpoolRequestBuilder::default()
+            .name(name.clone())
+            .vdev(CreateVdevRequest::SingleDisk(vdev0_path))
+            .build()
+            .unwrap();
+        zpool.create(topo).unwrap();
+
+        let mut handle = zpool.open(&name).unwrap();
+        let guid = handle.guid();
+        handle.reguid().unwrap();
+        assert_ne!(guid, handle.guid());
+        handle.scrub().unwrap();
+        handle.export(ExportMode::Gentle).unwrap();
+
+        // Same name, different pool.
+        let topo = CreateZpoolRequestBuilder::default()
+            .name(name.clone())
+            .vdev(CreateVdevRequest::SingleDisk(vdev1_path))
+            .build()
+            .unwrap();
+        zpool.create(topo).unwrap();
+        let err = handle.destroy(DestroyMode::Force).unwrap_err();
+        assert_eq!(ZpoolErrorKind::PoolChanged, err.kind());
+        assert!(zpool.exists(&name).unwrap());
+
+        zpool.destroy(&name, DestroyMode::Force).unwrap();
+        zpool.import_from_dir(&name, PathBuf::from(vdev_dir)).unwrap();
+        handle.destroy(DestroyMode::Force).unwrap();
+    });
+}
+
+#[test]
+fn test_zpool_labelclear() {
+    run_test(|name| {
+        let vdev_dir = Path::new("/vdevs/labelclear");
+        let vdev_path = setup_vdev(vdev_dir.join("vdev0"), &Bytes::MegaBytes(64 + 10));
+        let zpool = ZpoolOpen3::default();
+        let topo = CreateZpoolRequestBuilder::default()
+            .name(name.clone())
+            .vdev(CreateVdevRequest::SingleDisk(vdev_path.clone()))
+            .build()
+            .unwrap();
+        zpool.create(topo).unwrap();
+
+        let err = zpool.labelclear(&vdev_path, LabelClearMode::Force).unwrap_err();
+        assert_eq!(ZpoolErrorKind::ActiveVdev, err.kind());
+
+        zpool.export(&name, ExportMode::Gentle).unwrap();
+        let err = zpool.labelclear(&vdev_path, LabelClearMode::Gentle).unwrap_err();
+        assert_eq!(ZpoolErrorKind::VdevReuse, err.kind());
+
+        zpool.labelclear(&vdev_path, LabelClearMode::Force).unwrap();
+        let list = zpool.available_in_dir(PathBuf::from(vdev_dir)).unwrap();
+        assert!(list.is_empty());
+    });
+}
+
+#[test]
+fn test_zpool_txgs() {
+    run_test(|name| {
+        let zpool = ZpoolOpen3::default();
+        let vdev_path = setup_vdev("/vdevs/vdev0", &Bytes::MegaBytes(64 + 10));
         let topo = CreateZpoolRequestBuilder::default()
             .name(name.clone())
-            .vdev(CreateVdevRequest::SingleDisk("/vdevs/import/vdev0".into()))
+            .vdev(CreateVdevRequest::SingleDisk(vdev_path.clone()))
             .build()
             .unwrap();
-        zpool.create(topo).expect("Failed to create pool for export");
-
-        let result = zpool.export(&name, ExportMode::Gentle);
-        assert!(result.is_ok());
-        let list = zpool.available_in_dir(PathBuf::from(&vdev_dir)).unwrap();
-        assert_eq!(list.len(), 1);
-
-        let result = zpool.import_from_dir(&name, PathBuf::from(vdev_dir));
-        assert!(result.is_ok());
+        zpool.create(topo).unwrap();
 
-        zpool.destroy(&name, DestroyMode::Force).unwrap();
+        let sample = read_txgs(&name).unwrap();
+        assert_eq!(&name, sample.pool());
+        assert!(sample.current_txg().is_some());
 
-        let result = zpool.available().unwrap();
-        assert!(result.is_empty());
+        let err = read_txgs("fake").unwrap_err();
+        assert_eq!(ZpoolErrorKind::PoolNotFound, err.kind());
     });
 }
 
 #[test]
-fn test_export_import_force() {
+fn test_zpool_checkpoint_rewind() {
     run_test(|name| {
-        let vdev_dir = Path::new("/vdevs/import");
-        setup_vdev(vdev_dir.join("vdev0"), &Bytes::MegaBytes(64 + 10));
+        let vdev_dir = Path::new("/vdevs/checkpoint");
+        let vdev_path = setup_vdev(vdev_dir.join("vdev0"), &Bytes::MegaBytes(64 + 10));
         let zpool = ZpoolOpen3::default();
-        //let zpool = ZpoolOpen3::with_logger(get_logger());
-
         let topo = CreateZpoolRequestBuilder::default()
             .name(name.clone())
-            .vdev(CreateVdevRequest::SingleDisk("/vdevs/import/vdev0".into()))
+            .vdev(CreateVdevRequest::SingleDisk(vdev_path.clone()))
             .build()
             .unwrap();
-        zpool.create(topo).expect("Failed to create pool for export");
-
-        let result = zpool.export(&name, ExportMode::Force);
-        assert!(result.is_ok());
-        let list = zpool.available_in_dir(PathBuf::from(&vdev_dir)).unwrap();
-        assert_eq!(list.len(), 1);
+        zpool.create(topo).unwrap();
 
-        let result = zpool.import_from_dir(&name, PathBuf::from(vdev_dir));
-        assert!(result.is_ok());
+        let err = zpool.discard_checkpoint(&name).unwrap_err();
+        assert_eq!(ZpoolErrorKind::NoCheckpoint, err.kind());
+        zpool.checkpoint(&name).unwrap();
+        let err = zpool.checkpoint(&name).unwrap_err();
+        assert_eq!(ZpoolErrorKind::CheckpointExists, err.kind());
 
-        zpool.destroy(&name, DestroyMode::Force).unwrap();
+        zpool.export(&name, ExportMode::Gentle).unwrap();
+        zpool.import_rewind_to_checkpoint(&name, Some(PathBuf::from(vdev_dir))).unwrap();
+        assert!(zpool.exists(&name).unwrap());
 
-        let result = zpool.available().unwrap();
-        assert!(result.is_empty());
+        let err = zpool.discard_checkpoint(&name).unwrap_err();
+        assert_eq!(ZpoolErrorKind::NoCheckpoint, err.kind());
     });
 }
 
 #[test]
-fn test_status() {
+fn test_zpool_events_follow() {
     run_test(|name| {
-        let vdev_path = setup_vdev("/vdevs/vdev0", &Bytes::MegaBytes(64 + 10));
         let zpool = ZpoolOpen3::default();
-
+        let vdev_path = setup_vdev("/vdevs/vdev0", &Bytes::MegaBytes(64 + 10));
         let topo = CreateZpoolRequestBuilder::default()
             .name(name.clone())
-            .vdev(CreateVdevRequest::SingleDisk(vdev_path))
+            .vdev(CreateVdevRequest::SingleDisk(vdev_path.clone()))
             .build()
             .unwrap();
-        zpool.create(topo.clone()).unwrap();
+        zpool.create(topo).unwrap();
 
-        let result = zpool.status(&name).unwrap();
-        assert_eq!(&name, result.name());
-        assert_eq!(&result, &topo);
+        let mut stream = zpool.events_follow().unwrap();
+        let event = stream.find(|e| e.as_ref().unwrap().pool() == Some(name.as_str()));
+        assert!(event.is_some());
+        stream.stop().unwrap();
     });
 }
+
 #[test]
-fn test_all() {
+fn test_zpool_iostat() {
     run_test(|name| {
         let zpool = ZpoolOpen3::default();
         let vdev_path = setup_vdev("/vdevs/vdev0", &Bytes::MegaBytes(64 + 10));
         let topo = CreateZpoolRequestBuilder::default()
             .name(name.clone())
-            .vdev(CreateVdevRequest::SingleDisk(vdev_path))
+            .vdev(CreateVdevRequest::SingleDisk(vdev_path.clone()))
             .build()
             .unwrap();
-        zpool.create(topo.clone()).unwrap();
+        zpool.create(topo).unwrap();
 
-        let result: Vec<Zpool> = zpool
-            .all()
-            .unwrap()
-            .iter()
-            .cloned()
-            .filter(|z| z.name().starts_with(ZPOOL_NAME_PREFIX))
-            .collect();
-        assert_eq!(1, result.len());
-        let result = result.into_iter().next().unwrap();
-        assert_eq!(&name, result.name());
-        assert_eq!(&result, &topo);
+        let stat = zpool.iostat(&name).unwrap();
+        assert_eq!(&name, stat.name());
+        assert!(stat.alloc().unwrap() > 0);
+        assert!(stat.free().unwrap() > 0);
+
+        let result = zpool.iostat("nope");
+        assert_eq!(ZpoolErrorKind::PoolNotFound, result.unwrap_err().kind());
     });
 }
 
 #[test]
-fn test_all_empty() {
-    run_test(|_name| {
+fn test_zpool_iostat_vdevs() {
+    run_test(|name| {
         let zpool = ZpoolOpen3::default();
+        let vdev0 = setup_vdev("/vdevs/vdev0", &Bytes::MegaBytes(64 + 10));
+        let vdev1 = setup_vdev("/vdevs/vdev1", &Bytes::MegaBytes(64 + 10));
+        let vdev2 = setup_vdev("/vdevs/vdev2", &Bytes::MegaBytes(64 + 10));
+        let topo = CreateZpoolRequestBuilder::default()
+            .name(name.clone())
+            .create_mode(CreateMode::Force)
+            .vdev(CreateVdevRequest::SingleDisk(vdev0.clone()))
+            .vdev(CreateVdevRequest::Mirror(vec![vdev1.clone(), vdev2.clone()]))
+            .build()
+            .unwrap();
+        zpool.create(topo).unwrap();
 
-        let result: Vec<Zpool> = zpool
-            .all()
-            .unwrap()
-            .iter()
-            .cloned()
-            .filter(|z| z.name().starts_with(ZPOOL_NAME_PREFIX))
-            .collect();
-        assert!(result.is_empty());
+        let stats = zpool.iostat_vdevs(&name).unwrap();
+        assert_eq!(&name, stats.pool().name());
+        assert_eq!(2, stats.vdevs().len());
+        assert_eq!(3, stats.disks().len());
+        assert!(stats.disk(&vdev2).is_some());
+
+        let stats = zpool.iostat_ext(&name, true, true).unwrap();
+        // Pool, single disk, mirror and its two disks.
+        assert_eq!(5, stats.len());
+        assert!(stats.iter().all(|s| s.latency().is_some() && s.queues().is_some()));
+
+        let histograms = zpool.iostat_histograms(&name).unwrap();
+        assert_eq!(&name, histograms[0].name());
+        assert!(!histograms[0].buckets().is_empty());
     });
 }
 
 #[test]
-fn test_zpool_scrub_not_found() {
-    let zpool = ZpoolOpen3::default();
-    let name = "non-existent";
+fn test_zpool_iostat_stream() {
+    run_test(|name| {
+        let zpool = ZpoolOpen3::default();
+        let vdev_path = setup_vdev("/vdevs/vdev0", &Bytes::MegaBytes(64 + 10));
+        let topo = CreateZpoolRequestBuilder::default()
+            .name(name.clone())
+            .vdev(CreateVdevRequest::SingleDisk(vdev_path.clone()))
+            .build()
+            .unwrap();
+        zpool.create(topo).unwrap();
 
-    let result = zpool.scrub(name);
-    assert_eq!(ZpoolErrorKind::PoolNotFound, result.unwrap_err().kind());
+        let stream = zpool.iostat_stream(&name, Duration::from_secs(1)).unwrap();
+        let samples: Vec<_> = stream.take(2).collect::<std::result::Result<_, _>>().unwrap();
+        assert_eq!(2, samples.len());
+        assert!(samples.iter().all(|sample| sample.name() == &name));
 
-    let result = zpool.stop_scrub(name);
-    assert_eq!(ZpoolErrorKind::PoolNotFound, result.unwrap_err().kind());
+        let mut stream = zpool.iostat_stream("nope", Duration::from_secs(1)).unwrap();
+        assert_eq!(ZpoolErrorKind::PoolNotFound, stream.next().unwrap().unwrap_err().kind());
+    });
 }
 
 #[test]
-fn test_zpool_scrub() {
+fn test_zpool_clear() {
     run_test(|name| {
         let zpool = ZpoolOpen3::default();
         let vdev_path = setup_vdev("/vdevs/vdev0", &Bytes::MegaBytes(64 + 10));
         let topo = CreateZpoolRequestBuilder::default()
             .name(name.clone())
-            .vdev(CreateVdevRequest::SingleDisk(vdev_path))
+            .vdev(CreateVdevRequest::SingleDisk(vdev_path.clone()))
             .build()
             .unwrap();
         zpool.create(topo).unwrap();
 
-        let result = zpool.stop_scrub(&name);
-        assert_eq!(ZpoolErrorKind::NoActiveScrubs, result.unwrap_err().kind());
+        assert!(zpool.clear(&name, None).is_ok());
+        assert!(zpool.clear(&name, Some(vdev_path.as_ref())).is_ok());
 
-        let result = zpool.pause_scrub(&name);
-        assert_eq!(ZpoolErrorKind::NoActiveScrubs, result.unwrap_err().kind());
+        let result = zpool.clear(&name, Some("/vdevs/nope".as_ref()));
+        assert_eq!(ZpoolErrorKind::NoSuchDevice, result.unwrap_err().kind());
 
-        let result = zpool.scrub(&name);
-        assert!(result.is_ok());
+        let result = zpool.clear("nope", None);
+        assert_eq!(ZpoolErrorKind::PoolNotFound, result.unwrap_err().kind());
     });
 }
 
@@ -541,6 +1449,73 @@ fn test_zpool_take_device_from_mirror_offline() {
     });
 }
 
+#[test]
+fn test_zpool_create_dry_run() {
+    run_test(|name| {
+        let zpool = ZpoolOpen3::default();
+        let vdev0_path = setup_vdev("/vdevs/vdev0", &Bytes::MegaBytes(64 + 10));
+        let vdev1_path = setup_vdev("/vdevs/vdev1", &Bytes::MegaBytes(64 + 10));
+        let vdev2_path = setup_vdev("/vdevs/vdev2", &Bytes::MegaBytes(64 + 10));
+        let mirror = CreateVdevRequest::Mirror(vec![vdev0_path.clone(), vdev1_path.clone()]);
+        let topo = CreateZpoolRequestBuilder::default()
+            .name(name.clone())
+            .vdev(mirror.clone())
+            .spare(vdev2_path.clone())
+            .build()
+            .unwrap();
+
+        let layout = zpool.create_dry_run(topo).unwrap();
+        assert_eq!(&name, layout.name());
+        assert_eq!(&vec![mirror], layout.vdevs());
+        assert_eq!(&vec![vdev2_path], layout.spares());
+        assert!(!zpool.exists(&name).unwrap());
+    });
+}
+
+#[test]
+fn test_zpool_list() {
+    run_test(|name| {
+        let zpool = ZpoolOpen3::default();
+        let topo = CreateZpoolRequestBuilder::default()
+            .name(name.clone())
+            .vdev(CreateVdevRequest::SingleDisk("/vdevs/vdev0".into()))
+            .build()
+            .unwrap();
+        zpool.create(topo).unwrap();
+
+        let pools = zpool.list().unwrap();
+        let summary = pools.iter().find(|pool| pool.name() == &name).unwrap();
+        let props = zpool.read_properties(&name).unwrap();
+        assert_eq!(*props.size() as u64, *summary.size());
+        assert_eq!(summary.size(), &(summary.alloc() + summary.free()));
+        assert_eq!(props.capacity(), summary.capacity());
+        assert_eq!(&Health::Online, summary.health());
+    });
+}
+
+#[test]
+fn test_zpool_unhealthy() {
+    run_test(|name| {
+        let zpool = ZpoolOpen3::default();
+        let vdev0_path = setup_vdev("/vdevs/vdev3", &Bytes::MegaBytes(64 + 10));
+        let vdev1_path = setup_vdev("/vdevs/vdev4", &Bytes::MegaBytes(64 + 10));
+        let topo = CreateZpoolRequestBuilder::default()
+            .name(name.clone())
+            .create_mode(CreateMode::Force)
+            .vdev(CreateVdevRequest::Mirror(vec![vdev0_path.clone(), vdev1_path.clone()]))
+            .build()
+            .unwrap();
+        zpool.create(topo).unwrap();
+        let unhealthy = zpool.unhealthy().unwrap();
+        assert!(unhealthy.iter().all(|z| z.name() != &name));
+
+        zpool.take_offline(&name, &vdev0_path, OfflineMode::UntilReboot).unwrap();
+        let unhealthy = zpool.unhealthy().unwrap();
+        let z = unhealthy.iter().find(|z| z.name() == &name).unwrap();
+        assert_eq!(&Health::Degraded, z.health());
+    });
+}
+
 #[test]
 fn test_zpool_take_device_from_mirror_offline_expand() {
     run_test(|name| {
@@ -739,6 +1714,39 @@ fn test_zpool_add_mirror_to_raidz() {
     });
 }
 
+#[test]
+fn test_zpool_remove_vdev() {
+    use libzetta::zpool::RemovalState;
+
+    run_test(|name| {
+        let zpool = ZpoolOpen3::default();
+        let vdev0_path = setup_vdev("/vdevs/vdev1", &Bytes::MegaBytes(64 + 10));
+        let vdev1_path = setup_vdev("/vdevs/vdev2", &Bytes::MegaBytes(64 + 10));
+        let topo = CreateZpoolRequestBuilder::default()
+            .name(name.clone())
+            .create_mode(CreateMode::Force)
+            .vdev(CreateVdevRequest::SingleDisk(vdev0_path.clone()))
+            .vdev(CreateVdevRequest::SingleDisk(vdev1_path.clone()))
+            .build()
+            .unwrap();
+        zpool.create(topo).unwrap();
+
+        let result = zpool.remove_vdev(&name, &vdev1_path);
+        assert!(result.is_ok());
+
+        // evacuation of an empty vdev takes a moment.
+        std::thread::sleep(std::time::Duration::from_secs(5));
+
+        let result = zpool.status(&name).unwrap();
+        let removal = result.removal().as_ref().unwrap();
+        assert_eq!(&RemovalState::Completed, removal.state());
+        assert_eq!(1, result.vdevs().len());
+
+        let result = zpool.cancel_removal(&name);
+        assert!(result.is_err());
+    });
+}
+
 #[test]
 fn test_zpool_remove_zil() {
     run_test(|name| {
@@ -770,6 +1778,61 @@ fn test_zpool_remove_zil() {
     });
 }
 
+#[test]
+fn test_zpool_add_mirrored_zil() {
+    run_test(|name| {
+        let zpool = ZpoolOpen3::default();
+        let vdev0_path = setup_vdev("/vdevs/vdev0", &Bytes::MegaBytes(64 + 10));
+        let vdev1_path = setup_vdev("/vdevs/vdev1", &Bytes::MegaBytes(64 + 10));
+        let vdev2_path = setup_vdev("/vdevs/vdev2", &Bytes::MegaBytes(64 + 10));
+        let topo = CreateZpoolRequestBuilder::default()
+            .name(name.clone())
+            .vdev(CreateVdevRequest::SingleDisk(vdev0_path.clone()))
+            .build()
+            .unwrap();
+        zpool.create(topo.clone()).unwrap();
+
+        let raidz = CreateVdevRequest::RaidZ(vec![vdev1_path.clone(); 3]);
+        let result = zpool.add_zil(&name, raidz, CreateMode::Gentle);
+        assert_eq!(ZpoolErrorKind::InvalidTopology, result.unwrap_err().kind());
+
+        let mirror = CreateVdevRequest::Mirror(vec![vdev1_path.clone(), vdev2_path.clone()]);
+        zpool.add_zil(&name, mirror.clone(), CreateMode::Gentle).unwrap();
+
+        let expected = CreateZpoolRequestBuilder::default()
+            .name(name.clone())
+            .vdev(CreateVdevRequest::SingleDisk(vdev0_path.clone()))
+            .zil(mirror)
+            .build()
+            .unwrap();
+        let z = zpool.status(&name).unwrap();
+        assert_eq!(expected, z);
+        assert_eq!(&VdevType::Mirror, z.logs()[0].kind());
+    });
+}
+
+#[test]
+fn test_create_draid() {
+    run_test(|name| {
+        let zpool = ZpoolOpen3::default();
+        let children: Vec<PathBuf> = (4..8)
+            .map(|n| setup_vdev(format!("/vdevs/vdev{}", n), &Bytes::MegaBytes(64 + 10)))
+            .collect();
+        let draid = CreateVdevRequest::DRaid { parity: 1, data: 2, spares: 1, children };
+        let topo = CreateZpoolRequestBuilder::default()
+            .name(name.clone())
+            .vdev(draid.clone())
+            .build()
+            .unwrap();
+        zpool.create(topo).unwrap();
+
+        let z = zpool.status(&name).unwrap();
+        assert_eq!(&draid, &z.vdevs()[0]);
+        assert_eq!(1, z.spares().len());
+        assert!(z.spares()[0].is_distributed_spare());
+    });
+}
+
 #[test]
 fn test_zpool_add_cache() {
     run_test(|name| {
@@ -887,3 +1950,64 @@ fn test_zpool_replace_disk() {
         assert_eq!(topo_expected, z);
     });
 }
+
+#[test]
+fn test_zpool_replace_force() {
+    run_test(|name| {
+        let zpool = ZpoolOpen3::default();
+        let vdev0_path = setup_vdev("/vdevs/vdev0", &Bytes::MegaBytes(64 + 10));
+        let vdev1_path = setup_vdev("/vdevs/vdev1", &Bytes::MegaBytes(64 + 10));
+        let vdev2_path = setup_vdev("/vdevs/vdev2", &Bytes::MegaBytes(64 + 10));
+        let topo = CreateZpoolRequestBuilder::default()
+            .name(name.clone())
+            .create_mode(CreateMode::Force)
+            .vdev(CreateVdevRequest::Mirror(vec![vdev0_path.clone(), vdev1_path.clone()]))
+            .build()
+            .unwrap();
+        zpool.create(topo).unwrap();
+
+        let result = zpool.replace(&name, "/vdevs/nope", &vdev2_path, CreateMode::Gentle);
+        assert_eq!(ZpoolErrorKind::NoSuchDevice, result.unwrap_err().kind());
+
+        let result = zpool.replace(&name, &vdev0_path, &vdev2_path, CreateMode::Force);
+        assert!(result.is_ok());
+
+        // wait for resilver to finish, so there is no replacing vdev.
+        std::thread::sleep(std::time::Duration::from_secs(13));
+
+        let z = zpool.status(&name).unwrap();
+        let disks = z.vdevs()[0].disks();
+        assert!(disks.iter().any(|disk| disk == &vdev2_path));
+    });
+}
+
+#[test]
+fn test_zpool_expand() {
+    use libzetta::zpool::expand::expand_pool;
+
+    run_test(|name| {
+        let zpool = ZpoolOpen3::default();
+        let _ = fs::remove_file("/vdevs/expand0");
+        let _ = fs::remove_file("/vdevs/expand1");
+        let vdev0_path = setup_vdev("/vdevs/expand0", &Bytes::MegaBytes(64 + 10));
+        let vdev1_path = setup_vdev("/vdevs/expand1", &Bytes::MegaBytes(64 + 10));
+        let topo = CreateZpoolRequestBuilder::default()
+            .name(name.clone())
+            .create_mode(CreateMode::Force)
+            .vdev(CreateVdevRequest::Mirror(vec![vdev0_path.clone(), vdev1_path.clone()]))
+            .build()
+            .unwrap();
+        zpool.create(topo).unwrap();
+
+        for path in &[&vdev0_path, &vdev1_path] {
+            let f = fs::OpenOptions::new().write(true).open(path).unwrap();
+            f.set_len(256 * 1024 * 1024).unwrap();
+        }
+
+        let report = expand_pool(&zpool, &name).unwrap();
+        assert_eq!(&vec![vdev0_path.clone(), vdev1_path.clone()], report.expanded());
+        assert!(report.skipped().is_empty());
+        assert!(report.grown() > 0);
+        assert_eq!(&None, report.expand_size_after());
+    });
+}